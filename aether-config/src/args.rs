@@ -0,0 +1,178 @@
+//! Command line support for the service binaries
+//!
+//! Every binary takes `--config <file>` and `--set <section>.<key>=<value>`
+//! and has the `validate` and `print-effective-config` subcommands. Binaries
+//! parsing with clap declare the two options themselves and fill in a
+//! [`ConfigArgs`]; the others pull them out of their arguments with
+//! [`ConfigArgs::take`].
+
+use crate::loader::{ConfigLoader, CONFIG_FILE_ENV};
+use crate::sections::AetherConfig;
+use crate::ConfigError;
+use std::path::PathBuf;
+
+/// Where a binary's config comes from
+#[derive(Debug, Clone, Default)]
+pub struct ConfigArgs {
+    /// Config file; default: `AETHER_CONFIG`, if set
+    pub file: Option<PathBuf>,
+
+    /// `<section>.<key>=<value>` overrides, in order
+    pub overrides: Vec<String>,
+}
+
+impl ConfigArgs {
+    /// If `args[index]` is `--config` or `--set` (with its value, as the next
+    /// argument or after `=`), move it into `self`; returns whether it was
+    pub fn take(&mut self, args: &mut Vec<String>, index: usize) -> Result<bool, ConfigError> {
+        let Some(arg) = args.get(index) else {
+            return Ok(false);
+        };
+        let (name, inline) = match arg.split_once('=') {
+            Some((name, value)) => (name.to_string(), Some(value.to_string())),
+            None => (arg.clone(), None),
+        };
+        if name != "--config" && name != "--set" {
+            return Ok(false);
+        }
+
+        args.remove(index);
+        let value = match inline {
+            Some(value) => value,
+            None if index < args.len() => args.remove(index),
+            None => return Err(ConfigError::MissingValue(name)),
+        };
+
+        if name == "--config" {
+            self.file = Some(value.into());
+        } else {
+            self.overrides.push(value);
+        }
+        Ok(true)
+    }
+
+    /// Add an override of `key` with `value`, e.g. from a binary's own flag
+    pub fn set(&mut self, key: &str, value: impl Into<toml::Value>) {
+        self.overrides.push(format!("{}={}", key, value.into()));
+    }
+
+    /// The loader for these arguments and the process environment
+    pub fn loader(&self) -> ConfigLoader {
+        let file = self
+            .file
+            .clone()
+            .or_else(|| std::env::var_os(CONFIG_FILE_ENV).map(PathBuf::from));
+
+        let mut loader = ConfigLoader::new()
+            .env(std::env::vars())
+            .overrides(self.overrides.iter().cloned());
+        if let Some(file) = file {
+            loader = loader.file(file);
+        }
+        loader
+    }
+
+    /// Load and validate the config
+    pub fn load(&self) -> Result<AetherConfig, ConfigError> {
+        let config = self.loader().load()?;
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+/// The config subcommands of every binary
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigCommand {
+    /// Check the config and report every problem
+    Validate,
+
+    /// Print the config after all layers, as TOML
+    PrintEffectiveConfig,
+}
+
+impl ConfigCommand {
+    /// The command named `name` on the command line
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "validate" => Some(ConfigCommand::Validate),
+            "print-effective-config" => Some(ConfigCommand::PrintEffectiveConfig),
+            _ => None,
+        }
+    }
+
+    /// Run the command, printing its result to stdout
+    pub fn run(self, args: &ConfigArgs) -> Result<(), ConfigError> {
+        match self {
+            ConfigCommand::Validate => {
+                args.load()?;
+                println!("✓ Configuration is valid");
+            }
+            ConfigCommand::PrintEffectiveConfig => {
+                print!("{}", args.loader().load()?.to_toml()?);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_take() {
+        let mut argv = args(&[
+            "os-executor",
+            "--config",
+            "aether.yaml",
+            "--set=os_executor.allow_shell=true",
+            "--output",
+            "json",
+            "exec",
+            "ls",
+        ]);
+        let mut config_args = ConfigArgs::default();
+
+        while config_args.take(&mut argv, 1).unwrap() {}
+        assert_eq!(
+            argv,
+            args(&["os-executor", "--output", "json", "exec", "ls"])
+        );
+        assert_eq!(config_args.file, Some(PathBuf::from("aether.yaml")));
+        assert_eq!(config_args.overrides, ["os_executor.allow_shell=true"]);
+
+        let mut argv = args(&["wakeword-detector", "--set"]);
+        assert!(matches!(
+            config_args.take(&mut argv, 1),
+            Err(ConfigError::MissingValue(_))
+        ));
+    }
+
+    #[test]
+    fn test_set() {
+        let mut config_args = ConfigArgs::default();
+        // Quoted, so it stays a string
+        config_args.set("stt.model_path", "2024");
+        config_args.set("browser_executor.headless", false);
+
+        let config = ConfigLoader::new()
+            .overrides(config_args.overrides)
+            .load()
+            .unwrap();
+        assert_eq!(config.stt.model_path, PathBuf::from("2024"));
+        assert!(!config.browser_executor.headless);
+    }
+
+    #[test]
+    fn test_parse_command() {
+        assert_eq!(
+            ConfigCommand::parse("print-effective-config"),
+            Some(ConfigCommand::PrintEffectiveConfig)
+        );
+        assert_eq!(ConfigCommand::parse("exec"), None);
+    }
+}
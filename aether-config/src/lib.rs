@@ -0,0 +1,48 @@
+//! Aether Config - Layered configuration for AetherOS services
+//!
+//! One configuration for every service binary:
+//! - Typed sections for the wake-word detector, speech-to-text, the OS
+//!   executor, the browser executor, text-to-speech and the agent
+//! - Layers: defaults → TOML or YAML file → environment → command line
+//!   overrides
+//! - Validation reporting every problem at once
+//! - `--config`, `--set`, `validate` and `print-effective-config` for the
+//!   binaries
+
+pub mod args;
+pub mod loader;
+pub mod sections;
+
+pub use args::{ConfigArgs, ConfigCommand};
+pub use loader::{ConfigLoader, CONFIG_FILE_ENV, ENV_PREFIX, LEGACY_ENV};
+pub use sections::{
+    AetherConfig, AgentSection, BrowserExecutorSection, OsExecutorSection, SkillsSection,
+    SttSection, TtsSection, WakewordSection,
+};
+
+use std::path::PathBuf;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ConfigError {
+    #[error("Failed to read config file {path}: {source}")]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("Invalid config file {path}: {reason}")]
+    Parse { path: PathBuf, reason: String },
+
+    #[error("Invalid override {0:?}, expected <section>.<key>=<value>")]
+    Override(String),
+
+    #[error("Missing value for {0}")]
+    MissingValue(String),
+
+    #[error("Invalid configuration: {0}")]
+    Invalid(String),
+
+    #[error("Invalid configuration:\n  {}", .0.join("\n  "))]
+    Validation(Vec<String>),
+}
@@ -0,0 +1,253 @@
+//! Layered loading
+//!
+//! Later layers override earlier ones, key by key:
+//!
+//! 1. Defaults
+//! 2. A config file, as TOML for `.toml` files and YAML otherwise
+//! 3. Environment variables `AETHER_<SECTION>__<KEY>`, e.g.
+//!    `AETHER_STT__LANGUAGE=de`; the variables the services read before
+//!    (`WHISPER_MODEL_PATH`, `PORCUPINE_ACCESS_KEY`, ...) still work, below
+//!    the `AETHER_` ones
+//! 4. Command line overrides `<section>.<key>=<value>`, e.g.
+//!    `--set wakeword.sensitivity=0.7`
+//!
+//! Values from the environment and the command line are taken as they are
+//! for keys holding text, so an access key of `12345` stays a string, and
+//! read as TOML values (`0.7`, `true`, `["/tmp"]`) for the rest.
+
+use crate::sections::AetherConfig;
+use crate::ConfigError;
+use std::path::{Path, PathBuf};
+use toml::{Table, Value};
+
+/// Environment variable naming the config file
+pub const CONFIG_FILE_ENV: &str = "AETHER_CONFIG";
+
+/// Prefix of environment variables setting a key
+pub const ENV_PREFIX: &str = "AETHER_";
+
+/// Environment variables the services read before, and the keys they set
+pub const LEGACY_ENV: &[(&str, &str)] = &[
+    ("PORCUPINE_ACCESS_KEY", "wakeword.access_key"),
+    ("WAKEWORD_MODEL_PATH", "wakeword.model_path"),
+    ("WAKEWORD_SENSITIVITY", "wakeword.sensitivity"),
+    ("WHISPER_MODEL_PATH", "stt.model_path"),
+    ("WHISPER_LANGUAGE", "stt.language"),
+    ("WHISPER_THREADS", "stt.threads"),
+    ("WHISPER_USE_GPU", "stt.use_gpu"),
+    ("WHISPER_REFINER_MODEL_PATH", "stt.refiner_model_path"),
+    ("STT_GRAMMAR_PATH", "stt.grammar_path"),
+    ("STT_ARCHIVE_DIR", "stt.archive_dir"),
+    ("STT_ARCHIVE_RETENTION_DAYS", "stt.archive_retention_days"),
+];
+
+/// Builds an [`AetherConfig`] from its layers
+#[derive(Debug, Clone, Default)]
+pub struct ConfigLoader {
+    file: Option<PathBuf>,
+    env: Vec<(String, String)>,
+    overrides: Vec<String>,
+}
+
+impl ConfigLoader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read `path` over the defaults
+    pub fn file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.file = Some(path.into());
+        self
+    }
+
+    /// Apply these environment variables, e.g. `std::env::vars()`
+    pub fn env(mut self, vars: impl IntoIterator<Item = (String, String)>) -> Self {
+        self.env = vars.into_iter().collect();
+        self
+    }
+
+    /// Apply `<section>.<key>=<value>` overrides last
+    pub fn overrides(mut self, overrides: impl IntoIterator<Item = String>) -> Self {
+        self.overrides.extend(overrides);
+        self
+    }
+
+    pub fn load(&self) -> Result<AetherConfig, ConfigError> {
+        let mut table = match &self.file {
+            Some(path) => read_file(path)?,
+            None => Table::new(),
+        };
+
+        for (var, key) in LEGACY_ENV {
+            if let Some((_, value)) = self.env.iter().find(|(name, _)| name == var) {
+                set(&mut table, key, parse_value(key, value));
+            }
+        }
+        for (name, value) in &self.env {
+            let Some(key) = env_key(name) else {
+                continue;
+            };
+            set(&mut table, &key, parse_value(&key, value));
+        }
+
+        for arg in &self.overrides {
+            let (key, value) = arg
+                .split_once('=')
+                .filter(|(key, _)| key.contains('.'))
+                .ok_or_else(|| ConfigError::Override(arg.clone()))?;
+            let (key, value) = (key.trim(), value.trim());
+            set(&mut table, key, parse_value(key, value));
+        }
+
+        Value::Table(table)
+            .try_into()
+            .map_err(|e: toml::de::Error| ConfigError::Invalid(e.to_string()))
+    }
+}
+
+/// A config file as a table, whatever its format
+fn read_file(path: &Path) -> Result<Table, ConfigError> {
+    let contents = std::fs::read_to_string(path).map_err(|source| ConfigError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    let parse_error = |reason: String| ConfigError::Parse {
+        path: path.to_path_buf(),
+        reason,
+    };
+
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("toml") => toml::from_str(&contents).map_err(|e| parse_error(e.to_string())),
+        // An empty YAML file is null rather than a mapping
+        _ if contents.trim().is_empty() => Ok(Table::new()),
+        _ => serde_yaml::from_str(&contents).map_err(|e| parse_error(e.to_string())),
+    }
+}
+
+/// `stt.language` for `AETHER_STT__LANGUAGE`
+fn env_key(name: &str) -> Option<String> {
+    let (section, key) = name.strip_prefix(ENV_PREFIX)?.split_once("__")?;
+    Some(format!(
+        "{}.{}",
+        section.to_lowercase(),
+        key.to_lowercase().replace("__", ".")
+    ))
+}
+
+/// `raw` for the key `key`: the string itself if the key holds text, else
+/// a TOML value, or the string when `raw` is not one
+fn parse_value(key: &str, raw: &str) -> Value {
+    let text = Value::String(raw.to_string());
+    let mut probe = Table::new();
+    set(&mut probe, key, text.clone());
+    if Value::Table(probe).try_into::<AetherConfig>().is_ok() {
+        return text;
+    }
+
+    toml::from_str::<Table>(&format!("value = {}", raw))
+        .ok()
+        .and_then(|mut table| table.remove("value"))
+        .unwrap_or(text)
+}
+
+/// Set the dotted `key` in `table`, creating tables along the way
+fn set(table: &mut Table, key: &str, value: Value) {
+    let mut parts: Vec<&str> = key.split('.').collect();
+    let last = parts.pop().unwrap_or_default();
+
+    let mut current = table;
+    for part in parts {
+        let entry = current
+            .entry(part.to_string())
+            .or_insert_with(|| Value::Table(Table::new()));
+        if !entry.is_table() {
+            *entry = Value::Table(Table::new());
+        }
+        current = entry.as_table_mut().expect("a table");
+    }
+    current.insert(last.to_string(), value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_layers() {
+        let config = ConfigLoader::new()
+            .env(env(&[
+                ("WHISPER_LANGUAGE", "fr"),
+                ("AETHER_STT__LANGUAGE", "de"),
+                ("AETHER_STT__THREADS", "2"),
+                ("AETHER_OS_EXECUTOR__ALLOWED_ROOTS", r#"["/tmp"]"#),
+                ("WAKEWORD_MODEL_PATH", "models/hey.ppn"),
+                ("HOME", "/root"),
+            ]))
+            .overrides(["stt.threads=4".to_string()])
+            .load()
+            .unwrap();
+
+        // AETHER_ variables win over the old names, overrides over both
+        assert_eq!(config.stt.language, "de");
+        assert_eq!(config.stt.threads, Some(4));
+        assert_eq!(
+            config.os_executor.allowed_roots,
+            Some(vec!["/tmp".to_string()])
+        );
+        assert_eq!(config.wakeword.model_path, "models/hey.ppn");
+        assert_eq!(config.wakeword.sensitivity, 0.5);
+    }
+
+    #[test]
+    fn test_bad_values() {
+        let bad_override = ConfigLoader::new()
+            .overrides(["headless".to_string()])
+            .load();
+        assert!(matches!(bad_override, Err(ConfigError::Override(_))));
+
+        let unknown_key = ConfigLoader::new()
+            .overrides(["stt.langauge=de".to_string()])
+            .load();
+        assert!(matches!(unknown_key, Err(ConfigError::Invalid(_))));
+
+        let wrong_type = ConfigLoader::new()
+            .env(env(&[("AETHER_WAKEWORD__SENSITIVITY", "high")]))
+            .load();
+        assert!(matches!(wrong_type, Err(ConfigError::Invalid(_))));
+    }
+
+    #[test]
+    fn test_parse_value() {
+        assert_eq!(
+            parse_value("wakeword.sensitivity", "0.7"),
+            Value::Float(0.7)
+        );
+        assert_eq!(parse_value("stt.use_gpu", "false"), Value::Boolean(false));
+        assert_eq!(parse_value("stt.threads", "4"), Value::Integer(4));
+        assert_eq!(
+            parse_value("stt.model_path", "models/ggml-small.bin"),
+            Value::String("models/ggml-small.bin".to_string())
+        );
+
+        // Text that reads as TOML stays text where the key holds text
+        assert_eq!(
+            parse_value("wakeword.access_key", "12345"),
+            Value::String("12345".to_string())
+        );
+        assert_eq!(
+            parse_value("stt.language", "true"),
+            Value::String("true".to_string())
+        );
+        assert_eq!(
+            env_key("AETHER_OS_EXECUTOR__ALLOW_SHELL").unwrap(),
+            "os_executor.allow_shell"
+        );
+        assert_eq!(env_key("AETHER_CONFIG"), None);
+    }
+}
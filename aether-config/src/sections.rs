@@ -0,0 +1,435 @@
+//! Typed configuration sections
+//!
+//! One section per service. Every field has a default, so a config file only
+//! names what it changes; unknown keys are rejected, so a misspelt key fails
+//! loudly instead of being ignored.
+
+use crate::ConfigError;
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+/// Shown instead of secrets in printed configs
+const REDACTED: &str = "<redacted>";
+
+/// The configuration of all AetherOS services
+///
+/// ```toml
+/// [wakeword]
+/// model_path = "models/aether.ppn"
+/// sensitivity = 0.6
+///
+/// [stt]
+/// language = "de"
+///
+/// [browser_executor]
+/// headless = false
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AetherConfig {
+    pub wakeword: WakewordSection,
+    pub stt: SttSection,
+    pub os_executor: OsExecutorSection,
+    pub browser_executor: BrowserExecutorSection,
+    pub tts: TtsSection,
+    pub agent: AgentSection,
+    pub skills: SkillsSection,
+}
+
+/// `[wakeword]`: the wake-word detector
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WakewordSection {
+    /// Porcupine access key; empty runs with a test key
+    pub access_key: String,
+
+    pub model_path: String,
+
+    /// Detection sensitivity (0.0 - 1.0)
+    pub sensitivity: f32,
+
+    /// Skip detection on audio without speech
+    pub enable_vad_prefilter: bool,
+
+    /// Listen address of `/healthz` and `/readyz`
+    pub health_addr: String,
+}
+
+impl Default for WakewordSection {
+    fn default() -> Self {
+        Self {
+            access_key: String::new(),
+            model_path: "models/aether.ppn".to_string(),
+            sensitivity: 0.5,
+            enable_vad_prefilter: true,
+            health_addr: "127.0.0.1:8011".to_string(),
+        }
+    }
+}
+
+/// `[stt]`: speech-to-text
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SttSection {
+    /// Whisper model file
+    pub model_path: PathBuf,
+
+    pub language: String,
+
+    /// Transcription threads (default: one per CPU)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub threads: Option<usize>,
+
+    pub use_gpu: bool,
+
+    /// Larger model re-transcribing low-confidence finals
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refiner_model_path: Option<PathBuf>,
+
+    /// Command grammar biasing and correcting transcripts
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub grammar_path: Option<PathBuf>,
+
+    /// Directory sessions are archived to (default: not archived)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub archive_dir: Option<PathBuf>,
+
+    /// Delete archived sessions older than this many days
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub archive_retention_days: Option<u64>,
+
+    /// HTTP listen address of the service
+    pub http_addr: String,
+
+    /// gRPC listen address of the service (default: no gRPC)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub grpc_addr: Option<String>,
+}
+
+impl Default for SttSection {
+    fn default() -> Self {
+        Self {
+            model_path: PathBuf::from("models/ggml-base.en.bin"),
+            language: "en".to_string(),
+            threads: None,
+            use_gpu: true,
+            refiner_model_path: None,
+            grammar_path: None,
+            archive_dir: None,
+            archive_retention_days: None,
+            http_addr: "0.0.0.0:8002".to_string(),
+            grpc_addr: None,
+        }
+    }
+}
+
+/// `[os_executor]`: the OS command executor
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OsExecutorSection {
+    pub enable_sandbox: bool,
+
+    /// Longest a command may run
+    pub max_timeout_secs: u64,
+
+    /// Output kept per stream
+    pub max_output_bytes: usize,
+
+    /// Allow shell interpreters (`sh -c`)
+    pub allow_shell: bool,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub working_dir: Option<String>,
+
+    /// Directories path arguments must stay within (default: the working
+    /// directory; `["/"]` for anywhere)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allowed_roots: Option<Vec<String>>,
+}
+
+impl Default for OsExecutorSection {
+    fn default() -> Self {
+        Self {
+            enable_sandbox: true,
+            max_timeout_secs: 5,
+            max_output_bytes: 1024 * 1024,
+            allow_shell: false,
+            working_dir: None,
+            allowed_roots: None,
+        }
+    }
+}
+
+/// `[browser_executor]`: the browser executor
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BrowserExecutorSection {
+    pub headless: bool,
+
+    pub max_memory_mb: u64,
+
+    /// Longest an action may run
+    pub max_execution_time_secs: u64,
+
+    /// Element wait timeout
+    pub default_timeout_secs: u64,
+
+    pub navigation_timeout_secs: u64,
+
+    pub viewport_width: u32,
+    pub viewport_height: u32,
+
+    /// Default: the executor's own
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_agent: Option<String>,
+
+    pub stealth: bool,
+    pub enable_sandbox: bool,
+
+    /// Attach to a running Chrome instead of launching one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remote_debugging_url: Option<String>,
+
+    /// Encrypted credential vault for servers asking for HTTP
+    /// authentication; its passphrase is read from `AETHER_VAULT_PASSPHRASE`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub credential_vault: Option<String>,
+}
+
+impl Default for BrowserExecutorSection {
+    fn default() -> Self {
+        Self {
+            headless: true,
+            max_memory_mb: 2048,
+            max_execution_time_secs: 30,
+            default_timeout_secs: 10,
+            navigation_timeout_secs: 30,
+            viewport_width: 1920,
+            viewport_height: 1080,
+            user_agent: None,
+            stealth: false,
+            enable_sandbox: true,
+            remote_debugging_url: None,
+            credential_vault: None,
+        }
+    }
+}
+
+/// `[tts]`: text-to-speech
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TtsSection {
+    /// `piper`, `mock` (tones instead of speech) or `none`
+    pub backend: String,
+
+    /// The piper executable
+    pub piper_binary: PathBuf,
+
+    /// Directory of piper voices, `<voice>.onnx` with `<voice>.onnx.json`
+    pub voices_dir: PathBuf,
+
+    pub voice: String,
+
+    /// Speaker of a multi-speaker voice
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub speaker: Option<u32>,
+
+    /// Speaking rate; 1.0 is the voice's own
+    pub rate: f32,
+
+    /// Command playing raw 16-bit mono PCM from stdin; `{sample_rate}` in
+    /// its arguments is replaced
+    pub player: Vec<String>,
+}
+
+impl Default for TtsSection {
+    fn default() -> Self {
+        Self {
+            backend: "piper".to_string(),
+            piper_binary: PathBuf::from("piper"),
+            voices_dir: PathBuf::from("models/piper"),
+            voice: "en_US-lessac-medium".to_string(),
+            speaker: None,
+            rate: 1.0,
+            player: "aplay -q -t raw -f S16_LE -r {sample_rate} -c 1"
+                .split(' ')
+                .map(String::from)
+                .collect(),
+        }
+    }
+}
+
+/// `[agent]`: the voice agent, which runs the executors
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AgentSection {
+    /// Listen address of `/healthz` and `/readyz`
+    pub health_addr: String,
+}
+
+impl Default for AgentSection {
+    fn default() -> Self {
+        Self {
+            health_addr: "127.0.0.1:8010".to_string(),
+        }
+    }
+}
+
+/// `[skills]`: the agent's skills
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SkillsSection {
+    /// Skills registered at startup, by name, offered transcripts in this
+    /// order (e.g. `["smart_home"]`)
+    pub enabled: Vec<String>,
+}
+
+impl AetherConfig {
+    /// Check values the types allow but the services do not, reporting
+    /// every problem at once
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let mut problems = Vec::new();
+        let mut check = |ok: bool, problem: &str| {
+            if !ok {
+                problems.push(problem.to_string());
+            }
+        };
+
+        let wakeword = &self.wakeword;
+        check(
+            (0.0..=1.0).contains(&wakeword.sensitivity),
+            "wakeword.sensitivity must be between 0.0 and 1.0",
+        );
+        check(
+            !wakeword.model_path.is_empty(),
+            "wakeword.model_path must not be empty",
+        );
+        check(
+            wakeword.health_addr.parse::<SocketAddr>().is_ok(),
+            "wakeword.health_addr must be an address like 127.0.0.1:8011",
+        );
+
+        let stt = &self.stt;
+        check(
+            !stt.model_path.as_os_str().is_empty(),
+            "stt.model_path must not be empty",
+        );
+        check(!stt.language.is_empty(), "stt.language must not be empty");
+        check(stt.threads != Some(0), "stt.threads must be at least 1");
+        check(
+            stt.http_addr.parse::<SocketAddr>().is_ok(),
+            "stt.http_addr must be an address like 0.0.0.0:8002",
+        );
+        check(
+            stt.grpc_addr
+                .as_ref()
+                .map_or(true, |addr| addr.parse::<SocketAddr>().is_ok()),
+            "stt.grpc_addr must be an address like 0.0.0.0:50052",
+        );
+        check(
+            stt.archive_retention_days.is_none() || stt.archive_dir.is_some(),
+            "stt.archive_retention_days needs stt.archive_dir",
+        );
+
+        let os = &self.os_executor;
+        check(
+            os.max_timeout_secs > 0,
+            "os_executor.max_timeout_secs must be at least 1",
+        );
+        check(
+            os.max_output_bytes > 0,
+            "os_executor.max_output_bytes must be at least 1",
+        );
+
+        let browser = &self.browser_executor;
+        check(
+            browser.max_memory_mb > 0,
+            "browser_executor.max_memory_mb must be at least 1",
+        );
+        check(
+            browser.max_execution_time_secs > 0,
+            "browser_executor.max_execution_time_secs must be at least 1",
+        );
+        check(
+            browser.viewport_width > 0 && browser.viewport_height > 0,
+            "browser_executor.viewport_width and viewport_height must be at least 1",
+        );
+
+        let tts = &self.tts;
+        check(
+            ["piper", "mock", "none"].contains(&tts.backend.as_str()),
+            "tts.backend must be piper, mock or none",
+        );
+        check(!tts.voice.is_empty(), "tts.voice must not be empty");
+        check(
+            (0.25..=4.0).contains(&tts.rate),
+            "tts.rate must be between 0.25 and 4.0",
+        );
+        check(!tts.player.is_empty(), "tts.player must not be empty");
+
+        check(
+            self.agent.health_addr.parse::<SocketAddr>().is_ok(),
+            "agent.health_addr must be an address like 127.0.0.1:8010",
+        );
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Validation(problems))
+        }
+    }
+
+    /// The config as TOML, with secrets redacted, for
+    /// `print-effective-config`
+    pub fn to_toml(&self) -> Result<String, ConfigError> {
+        let mut printable = self.clone();
+        if !printable.wakeword.access_key.is_empty() {
+            printable.wakeword.access_key = REDACTED.to_string();
+        }
+        toml::to_string_pretty(&printable).map_err(|e| ConfigError::Invalid(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_defaults_are_valid() {
+        let config = AetherConfig::default();
+        assert!(config.validate().is_ok());
+
+        let printed = config.to_toml().unwrap();
+        let parsed: AetherConfig = toml::from_str(&printed).unwrap();
+        assert_eq!(parsed, config);
+    }
+
+    #[test]
+    fn test_validate_reports_every_problem() {
+        let mut config = AetherConfig::default();
+        config.wakeword.sensitivity = 1.5;
+        config.stt.http_addr = "localhost".to_string();
+        config.os_executor.max_timeout_secs = 0;
+        config.agent.health_addr = "8010".to_string();
+
+        match config.validate() {
+            Err(ConfigError::Validation(problems)) => {
+                assert_eq!(problems.len(), 4);
+                assert!(problems[0].starts_with("wakeword.sensitivity"));
+                assert!(problems[3].starts_with("agent.health_addr"));
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_secrets_redacted() {
+        let mut config = AetherConfig::default();
+        config.wakeword.access_key = "secret-key".to_string();
+
+        let printed = config.to_toml().unwrap();
+        assert!(!printed.contains("secret-key"));
+        assert!(printed.contains(REDACTED));
+    }
+}
@@ -0,0 +1,73 @@
+//! Integration tests for aether config
+
+use aether_config::{AetherConfig, ConfigError, ConfigLoader};
+use std::io::Write;
+use tempfile::NamedTempFile;
+
+fn config_file(suffix: &str, contents: &str) -> NamedTempFile {
+    let mut file = tempfile::Builder::new().suffix(suffix).tempfile().unwrap();
+    file.write_all(contents.as_bytes()).unwrap();
+    file
+}
+
+#[test]
+fn test_toml_file() {
+    let file = config_file(
+        ".toml",
+        r#"
+[wakeword]
+sensitivity = 0.7
+
+[browser_executor]
+headless = false
+viewport_width = 1280
+"#,
+    );
+
+    let config = ConfigLoader::new()
+        .file(file.path())
+        .env([(
+            "AETHER_BROWSER_EXECUTOR__VIEWPORT_WIDTH".to_string(),
+            "1024".to_string(),
+        )])
+        .overrides(["wakeword.sensitivity=0.9".to_string()])
+        .load()
+        .unwrap();
+
+    assert_eq!(config.wakeword.sensitivity, 0.9);
+    assert!(!config.browser_executor.headless);
+    assert_eq!(config.browser_executor.viewport_width, 1024);
+    assert_eq!(config.browser_executor.viewport_height, 1080);
+    assert_eq!(config.stt, AetherConfig::default().stt);
+}
+
+#[test]
+fn test_yaml_file() {
+    let file = config_file(
+        ".yaml",
+        "stt:\n  language: de\n  archive_dir: /var/lib/aether/sessions\nos_executor:\n  allowed_roots: [/home/aether]\n",
+    );
+
+    let config = ConfigLoader::new().file(file.path()).load().unwrap();
+    assert_eq!(config.stt.language, "de");
+    assert!(config.stt.archive_dir.is_some());
+    assert_eq!(
+        config.os_executor.allowed_roots,
+        Some(vec!["/home/aether".to_string()])
+    );
+    assert!(config.validate().is_ok());
+}
+
+#[test]
+fn test_file_errors() {
+    let missing = ConfigLoader::new().file("/nonexistent/aether.toml").load();
+    assert!(matches!(missing, Err(ConfigError::Io { .. })));
+
+    let file = config_file(".toml", "[stt\nlanguage = ");
+    let malformed = ConfigLoader::new().file(file.path()).load();
+    assert!(matches!(malformed, Err(ConfigError::Parse { .. })));
+
+    let file = config_file(".toml", "[calendar]\ncountry = \"US\"\n");
+    let unknown_section = ConfigLoader::new().file(file.path()).load();
+    assert!(matches!(unknown_section, Err(ConfigError::Invalid(_))));
+}
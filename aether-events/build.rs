@@ -0,0 +1,5 @@
+fn main() {
+    #[cfg(feature = "grpc")]
+    tonic_build::compile_protos("proto/events.proto")
+        .expect("Failed to compile proto/events.proto");
+}
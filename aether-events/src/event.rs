@@ -0,0 +1,338 @@
+//! Event types
+//!
+//! Every event travels in an [`Envelope`] carrying the schema version, a
+//! unique id, the publishing service and the time. Fields are only ever
+//! added to an event, with defaults, within a schema version; a reader
+//! rejects envelopes of a newer version and reads event types it does not
+//! know as [`Event::Unknown`], keeping their JSON to pass them on unchanged.
+
+use crate::EventError;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Schema version this build writes and reads
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// An event with its metadata
+///
+/// ```json
+/// { "version": 1, "id": "6f1c...", "source": "agent-core", "timestamp_ms": 1767225600000,
+///   "correlation_id": "1b9d...",
+///   "traceparent": "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+///   "event": { "type": "transcript_final", "text": "open github.com", "confidence": 0.85,
+///              "start_ms": 0, "end_ms": 1500 } }
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Envelope {
+    pub version: u32,
+
+    /// Unique per event
+    pub id: String,
+
+    /// The service that published the event, e.g. `stt-processor`
+    pub source: String,
+
+    /// Unix time (ms) the event was published
+    pub timestamp_ms: u64,
+
+    /// Shared by the events of one voice command, from wake word to result
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
+
+    /// W3C trace context of the voice command's trace, started at the wake
+    /// word; a service handling the event continues the trace from it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub traceparent: Option<String>,
+
+    pub event: Event,
+}
+
+/// What happened
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    WakeWordDetected(WakeWordDetected),
+    TranscriptPartial(TranscriptPartial),
+    TranscriptFinal(TranscriptFinal),
+    CommandPlanned(CommandPlanned),
+    CommandExecuted(CommandExecuted),
+    Error(ErrorEvent),
+
+    /// An event type this build does not know, as its JSON, `type` and all
+    #[serde(untagged)]
+    Unknown(serde_json::Value),
+}
+
+/// [`Event`] as read, with the JSON of unknown types dropped
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Tagged {
+    WakeWordDetected(WakeWordDetected),
+    TranscriptPartial(TranscriptPartial),
+    TranscriptFinal(TranscriptFinal),
+    CommandPlanned(CommandPlanned),
+    CommandExecuted(CommandExecuted),
+    Error(ErrorEvent),
+    #[serde(other)]
+    Unknown,
+}
+
+impl<'de> Deserialize<'de> for Event {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let json = serde_json::Value::deserialize(deserializer)?;
+        let tagged = Tagged::deserialize(&json).map_err(serde::de::Error::custom)?;
+        Ok(match tagged {
+            Tagged::WakeWordDetected(event) => Event::WakeWordDetected(event),
+            Tagged::TranscriptPartial(event) => Event::TranscriptPartial(event),
+            Tagged::TranscriptFinal(event) => Event::TranscriptFinal(event),
+            Tagged::CommandPlanned(event) => Event::CommandPlanned(event),
+            Tagged::CommandExecuted(event) => Event::CommandExecuted(event),
+            Tagged::Error(event) => Event::Error(event),
+            Tagged::Unknown => Event::Unknown(json),
+        })
+    }
+}
+
+/// The wake word was heard
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WakeWordDetected {
+    /// Confidence score (0.0 - 1.0)
+    pub confidence: f32,
+
+    /// Index of the detected keyword
+    #[serde(default)]
+    pub keyword_index: i32,
+
+    /// When it was heard (microseconds since the epoch)
+    pub timestamp_us: i64,
+}
+
+/// Transcription in progress; may still change
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TranscriptPartial {
+    pub text: String,
+    pub confidence: f32,
+
+    /// Offset of the end of the transcribed audio on the STT session clock
+    pub timestamp_ms: u64,
+}
+
+/// A finished transcription
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TranscriptFinal {
+    pub text: String,
+    pub confidence: f32,
+
+    /// Offsets of the transcribed audio on the STT session clock
+    pub start_ms: u64,
+    pub end_ms: u64,
+}
+
+/// The executor a command is for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Executor {
+    Os,
+    Browser,
+
+    /// Timers kept by agent-core
+    Timer,
+
+    /// Skills registered with agent-core
+    Skill,
+
+    /// An executor this build does not know
+    #[serde(other)]
+    Other,
+}
+
+/// A transcript was understood as a command
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CommandPlanned {
+    pub transcript: String,
+    pub executor: Executor,
+
+    /// The command, in its executor's JSON form (e.g. a list of
+    /// `BrowserAction`s)
+    pub command: serde_json::Value,
+}
+
+/// An executor finished a command
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CommandExecuted {
+    pub executor: Executor,
+    pub success: bool,
+    pub duration_ms: u64,
+
+    /// A short description of the result, for speaking
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
+
+    /// The executor's result (e.g. a `CommandResult`)
+    #[serde(default)]
+    pub output: serde_json::Value,
+}
+
+/// Something failed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ErrorEvent {
+    pub message: String,
+
+    /// Machine-readable kind, e.g. a browser `ErrorCode`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+}
+
+/// A new unique id, for an envelope or a correlation id
+pub fn new_id() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
+
+impl Event {
+    /// The event's type, as in its JSON `type`
+    pub fn name(&self) -> &str {
+        match self {
+            Event::WakeWordDetected(_) => "wake_word_detected",
+            Event::TranscriptPartial(_) => "transcript_partial",
+            Event::TranscriptFinal(_) => "transcript_final",
+            Event::CommandPlanned(_) => "command_planned",
+            Event::CommandExecuted(_) => "command_executed",
+            Event::Error(_) => "error",
+            Event::Unknown(json) => json["type"].as_str().unwrap_or("unknown"),
+        }
+    }
+}
+
+impl Envelope {
+    /// `event`, published now by `source`
+    pub fn new(source: impl Into<String>, event: Event) -> Self {
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+
+        Self {
+            version: SCHEMA_VERSION,
+            id: new_id(),
+            source: source.into(),
+            timestamp_ms,
+            correlation_id: None,
+            traceparent: None,
+            event,
+        }
+    }
+
+    pub fn with_correlation_id(mut self, correlation_id: impl Into<String>) -> Self {
+        self.correlation_id = Some(correlation_id.into());
+        self
+    }
+
+    pub fn with_traceparent(mut self, traceparent: impl Into<String>) -> Self {
+        self.traceparent = Some(traceparent.into());
+        self
+    }
+
+    pub fn to_json(&self) -> Result<Vec<u8>, EventError> {
+        Ok(serde_json::to_vec(self)?)
+    }
+
+    /// Decode an envelope, rejecting versions newer than [`SCHEMA_VERSION`]
+    pub fn from_json(json: &[u8]) -> Result<Self, EventError> {
+        #[derive(Deserialize)]
+        struct Versioned {
+            version: u32,
+        }
+
+        let Versioned { version } = serde_json::from_slice(json)?;
+        if version > SCHEMA_VERSION {
+            return Err(EventError::UnsupportedVersion(version));
+        }
+        Ok(serde_json::from_slice(json)?)
+    }
+}
+
+macro_rules! impl_from {
+    ($($variant:ident($ty:ty)),*) => {
+        $(impl From<$ty> for Event {
+            fn from(event: $ty) -> Self {
+                Event::$variant(event)
+            }
+        })*
+    };
+}
+
+impl_from!(
+    WakeWordDetected(WakeWordDetected),
+    TranscriptPartial(TranscriptPartial),
+    TranscriptFinal(TranscriptFinal),
+    CommandPlanned(CommandPlanned),
+    CommandExecuted(CommandExecuted),
+    Error(ErrorEvent)
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let envelope = Envelope::new(
+            "stt-processor",
+            TranscriptFinal {
+                text: "open github.com".to_string(),
+                confidence: 0.85,
+                start_ms: 0,
+                end_ms: 1500,
+            }
+            .into(),
+        )
+        .with_correlation_id("turn-1")
+        .with_traceparent("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01");
+
+        let json = envelope.to_json().unwrap();
+        let value: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        assert_eq!(value["version"], 1);
+        assert_eq!(value["event"]["type"], "transcript_final");
+        assert_eq!(value["event"]["text"], "open github.com");
+        assert_eq!(
+            value["traceparent"],
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"
+        );
+
+        assert_eq!(Envelope::from_json(&json).unwrap(), envelope);
+        assert_eq!(envelope.event.name(), "transcript_final");
+    }
+
+    #[test]
+    fn test_versions() {
+        let newer = br#"{"version": 2, "id": "1", "source": "x", "timestamp_ms": 0,
+                         "event": {"type": "wake_word_detected"}}"#;
+        assert!(matches!(
+            Envelope::from_json(newer),
+            Err(EventError::UnsupportedVersion(2))
+        ));
+
+        let unknown = br#"{"version": 1, "id": "1", "source": "x", "timestamp_ms": 0,
+                           "event": {"type": "speech_synthesized", "text": "hi"}}"#;
+        let envelope = Envelope::from_json(unknown).unwrap();
+        assert_eq!(envelope.event.name(), "speech_synthesized");
+        assert!(envelope.traceparent.is_none());
+
+        // Passed on as it came
+        let json: serde_json::Value = serde_json::from_slice(&envelope.to_json().unwrap()).unwrap();
+        assert_eq!(
+            json["event"],
+            serde_json::json!({"type": "speech_synthesized", "text": "hi"})
+        );
+        assert_eq!(
+            Envelope::from_json(&envelope.to_json().unwrap()).unwrap(),
+            envelope
+        );
+
+        let executed: CommandExecuted =
+            serde_json::from_str(r#"{"executor": "calendar", "success": true, "duration_ms": 12}"#)
+                .unwrap();
+        assert_eq!(executed.executor, Executor::Other);
+        assert_eq!(executed.output, serde_json::Value::Null);
+    }
+}
@@ -0,0 +1,151 @@
+//! gRPC transport
+//!
+//! One process runs a [`GrpcServer`] around its [`InProcess`] bus; other
+//! processes publish and subscribe through a [`GrpcTransport`] connected to
+//! it. Envelopes go over the wire as their JSON (see `proto/events.proto`).
+
+use crate::event::Envelope;
+use crate::transport::{InProcess, Subscription, Transport, DEFAULT_CAPACITY};
+use crate::EventError;
+use async_trait::async_trait;
+use futures::Stream;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use tokio::sync::mpsc;
+use tonic::transport::Channel;
+use tonic::{Request, Response, Status};
+use tracing::warn;
+
+pub mod proto {
+    tonic::include_proto!("aether.events.v1");
+}
+
+use proto::event_bus_client::EventBusClient;
+use proto::event_bus_server::{EventBus, EventBusServer};
+use proto::{EventMessage, PublishReply, SubscribeRequest};
+
+/// Serves a bus to other processes
+#[derive(Debug, Clone)]
+pub struct GrpcServer {
+    bus: InProcess,
+}
+
+impl GrpcServer {
+    pub fn new(bus: InProcess) -> Self {
+        Self { bus }
+    }
+
+    /// The tonic service, to add to a server of one's own
+    pub fn into_service(self) -> EventBusServer<Self> {
+        EventBusServer::new(self)
+    }
+
+    /// Serve on `addr` until the server fails
+    pub async fn serve(self, addr: SocketAddr) -> Result<(), EventError> {
+        tonic::transport::Server::builder()
+            .add_service(self.into_service())
+            .serve(addr)
+            .await
+            .map_err(|e| EventError::Transport(e.to_string()))
+    }
+}
+
+#[tonic::async_trait]
+impl EventBus for GrpcServer {
+    async fn publish(
+        &self,
+        request: Request<EventMessage>,
+    ) -> Result<Response<PublishReply>, Status> {
+        let envelope = Envelope::from_json(&request.into_inner().envelope)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+        Transport::publish(&self.bus, envelope)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+        Ok(Response::new(PublishReply {}))
+    }
+
+    type SubscribeStream = Pin<Box<dyn Stream<Item = Result<EventMessage, Status>> + Send>>;
+
+    async fn subscribe(
+        &self,
+        _request: Request<SubscribeRequest>,
+    ) -> Result<Response<Self::SubscribeStream>, Status> {
+        let subscription = Transport::subscribe(&self.bus)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        let stream = futures::stream::unfold(subscription, |mut subscription| async move {
+            let envelope = subscription.recv().await?;
+            let message = envelope
+                .to_json()
+                .map(|envelope| EventMessage { envelope })
+                .map_err(|e| Status::internal(e.to_string()));
+            Some((message, subscription))
+        });
+        Ok(Response::new(Box::pin(stream)))
+    }
+}
+
+/// A bus served by a [`GrpcServer`] in another process
+#[derive(Debug, Clone)]
+pub struct GrpcTransport {
+    client: EventBusClient<Channel>,
+}
+
+impl GrpcTransport {
+    /// Connect to the server at `endpoint`, e.g. `http://localhost:50051`
+    pub async fn connect(endpoint: impl Into<String>) -> Result<Self, EventError> {
+        let client = EventBusClient::connect(endpoint.into())
+            .await
+            .map_err(|e| EventError::Transport(e.to_string()))?;
+        Ok(Self { client })
+    }
+}
+
+#[async_trait]
+impl Transport for GrpcTransport {
+    async fn publish(&self, envelope: Envelope) -> Result<(), EventError> {
+        let message = EventMessage {
+            envelope: envelope.to_json()?,
+        };
+        self.client
+            .clone()
+            .publish(message)
+            .await
+            .map_err(|e| EventError::Transport(e.to_string()))?;
+        Ok(())
+    }
+
+    async fn subscribe(&self) -> Result<Subscription, EventError> {
+        let mut stream = self
+            .client
+            .clone()
+            .subscribe(SubscribeRequest {})
+            .await
+            .map_err(|e| EventError::Transport(e.to_string()))?
+            .into_inner();
+        let (tx, rx) = mpsc::channel(DEFAULT_CAPACITY);
+
+        tokio::spawn(async move {
+            loop {
+                match stream.message().await {
+                    Ok(Some(message)) => match Envelope::from_json(&message.envelope) {
+                        Ok(envelope) => {
+                            if tx.send(envelope).await.is_err() {
+                                break;
+                            }
+                        }
+                        Err(e) => warn!("Dropping event: {}", e),
+                    },
+                    Ok(None) => break,
+                    Err(status) => {
+                        warn!("Event stream ended: {}", status);
+                        break;
+                    }
+                }
+            }
+        });
+
+        Ok(Subscription::new(rx))
+    }
+}
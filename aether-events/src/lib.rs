@@ -0,0 +1,39 @@
+//! Aether Events - Shared event types and transports for AetherOS services
+//!
+//! One definition of the data the services exchange:
+//! - Versioned serde event types: wake word detected, partial and final
+//!   transcripts, commands planned and executed, errors
+//! - Envelopes with id, source, time and a correlation id per voice command
+//! - Transports: in-process broadcast, gRPC (`grpc` feature) and NATS
+//!   (`nats` feature)
+
+pub mod event;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+#[cfg(feature = "nats")]
+pub mod nats;
+pub mod transport;
+
+pub use event::{
+    new_id, CommandExecuted, CommandPlanned, Envelope, ErrorEvent, Event, Executor,
+    TranscriptFinal, TranscriptPartial, WakeWordDetected, SCHEMA_VERSION,
+};
+#[cfg(feature = "grpc")]
+pub use grpc::{GrpcServer, GrpcTransport};
+#[cfg(feature = "nats")]
+pub use nats::NatsTransport;
+pub use transport::{InProcess, Publisher, Subscription, Transport};
+
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum EventError {
+    #[error("Invalid event: {0}")]
+    Invalid(#[from] serde_json::Error),
+
+    #[error("Unsupported event schema version: {0}")]
+    UnsupportedVersion(u32),
+
+    #[error("Transport error: {0}")]
+    Transport(String),
+}
@@ -0,0 +1,78 @@
+//! NATS transport
+//!
+//! Envelopes are published as JSON on `<prefix>.<event type>`
+//! (`aether.events.transcript_final`, say), so other NATS clients can
+//! subscribe to one type of event; a [`NatsTransport`] subscribes to all of
+//! them.
+
+use crate::event::Envelope;
+use crate::transport::{Subscription, Transport, DEFAULT_CAPACITY};
+use crate::EventError;
+use async_trait::async_trait;
+use futures::StreamExt;
+use tokio::sync::mpsc;
+use tracing::warn;
+
+/// Subject prefix events are published under
+pub const SUBJECT_PREFIX: &str = "aether.events";
+
+/// A bus on a NATS server
+#[derive(Debug, Clone)]
+pub struct NatsTransport {
+    client: async_nats::Client,
+    prefix: String,
+}
+
+impl NatsTransport {
+    /// Connect to the server at `url`, e.g. `nats://localhost:4222`
+    pub async fn connect(url: &str) -> Result<Self, EventError> {
+        let client = async_nats::connect(url)
+            .await
+            .map_err(|e| EventError::Transport(e.to_string()))?;
+        Ok(Self {
+            client,
+            prefix: SUBJECT_PREFIX.to_string(),
+        })
+    }
+
+    /// Publish and subscribe under `prefix` instead of [`SUBJECT_PREFIX`]
+    pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+}
+
+#[async_trait]
+impl Transport for NatsTransport {
+    async fn publish(&self, envelope: Envelope) -> Result<(), EventError> {
+        let subject = format!("{}.{}", self.prefix, envelope.event.name());
+        self.client
+            .publish(subject, envelope.to_json()?.into())
+            .await
+            .map_err(|e| EventError::Transport(e.to_string()))
+    }
+
+    async fn subscribe(&self) -> Result<Subscription, EventError> {
+        let mut subscriber = self
+            .client
+            .subscribe(format!("{}.>", self.prefix))
+            .await
+            .map_err(|e| EventError::Transport(e.to_string()))?;
+        let (tx, rx) = mpsc::channel(DEFAULT_CAPACITY);
+
+        tokio::spawn(async move {
+            while let Some(message) = subscriber.next().await {
+                match Envelope::from_json(&message.payload) {
+                    Ok(envelope) => {
+                        if tx.send(envelope).await.is_err() {
+                            break;
+                        }
+                    }
+                    Err(e) => warn!("Dropping event from {}: {}", message.subject, e),
+                }
+            }
+        });
+
+        Ok(Subscription::new(rx))
+    }
+}
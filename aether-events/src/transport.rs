@@ -0,0 +1,184 @@
+//! Transports carrying envelopes between services
+//!
+//! A [`Transport`] publishes envelopes and hands out [`Subscription`]s to
+//! the ones published after subscribing. [`InProcess`] connects components
+//! of one process; with the `grpc` feature, `GrpcServer` serves an
+//! `InProcess` bus to other processes, which reach it through
+//! `GrpcTransport`; with the `nats` feature, `NatsTransport` uses a NATS
+//! server instead.
+
+use crate::event::{Envelope, Event};
+use crate::EventError;
+use async_trait::async_trait;
+use std::sync::Arc;
+use tokio::sync::{broadcast, mpsc};
+use tracing::warn;
+
+/// Events a subscriber or the in-process bus buffers
+pub const DEFAULT_CAPACITY: usize = 256;
+
+/// Carries envelopes between publishers and subscribers
+#[async_trait]
+pub trait Transport: Send + Sync {
+    async fn publish(&self, envelope: Envelope) -> Result<(), EventError>;
+
+    /// Receive the envelopes published from now on
+    async fn subscribe(&self) -> Result<Subscription, EventError>;
+}
+
+/// Envelopes published since subscribing
+#[derive(Debug)]
+pub struct Subscription {
+    receiver: mpsc::Receiver<Envelope>,
+}
+
+impl Subscription {
+    pub fn new(receiver: mpsc::Receiver<Envelope>) -> Self {
+        Self { receiver }
+    }
+
+    /// The next envelope; `None` once the transport has closed
+    pub async fn recv(&mut self) -> Option<Envelope> {
+        self.receiver.recv().await
+    }
+
+    /// The next envelope if one is waiting
+    pub fn try_recv(&mut self) -> Option<Envelope> {
+        self.receiver.try_recv().ok()
+    }
+}
+
+/// A bus within one process
+///
+/// A subscriber that falls more than its capacity behind loses the oldest
+/// envelopes.
+#[derive(Debug, Clone)]
+pub struct InProcess {
+    sender: broadcast::Sender<Envelope>,
+}
+
+impl InProcess {
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity);
+        Self { sender }
+    }
+}
+
+impl Default for InProcess {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY)
+    }
+}
+
+#[async_trait]
+impl Transport for InProcess {
+    async fn publish(&self, envelope: Envelope) -> Result<(), EventError> {
+        // Nobody subscribed is fine
+        let _ = self.sender.send(envelope);
+        Ok(())
+    }
+
+    async fn subscribe(&self) -> Result<Subscription, EventError> {
+        let mut receiver = self.sender.subscribe();
+        let (tx, rx) = mpsc::channel(DEFAULT_CAPACITY);
+
+        tokio::spawn(async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(envelope) => {
+                        if tx.send(envelope).await.is_err() {
+                            break;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(missed)) => {
+                        warn!("Subscriber fell behind, {} events lost", missed);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+
+        Ok(Subscription::new(rx))
+    }
+}
+
+/// Publishes events as one service, wrapping them in envelopes
+#[derive(Clone)]
+pub struct Publisher {
+    source: String,
+    transport: Arc<dyn Transport>,
+}
+
+impl Publisher {
+    pub fn new(source: impl Into<String>, transport: Arc<dyn Transport>) -> Self {
+        Self {
+            source: source.into(),
+            transport,
+        }
+    }
+
+    pub fn transport(&self) -> &Arc<dyn Transport> {
+        &self.transport
+    }
+
+    /// Publish `event`, as part of the voice command `correlation_id` if set
+    pub async fn publish(
+        &self,
+        event: impl Into<Event>,
+        correlation_id: Option<&str>,
+    ) -> Result<(), EventError> {
+        self.publish_traced(event, correlation_id, None).await
+    }
+
+    /// [`publish`](Self::publish), carrying the command's trace context
+    pub async fn publish_traced(
+        &self,
+        event: impl Into<Event>,
+        correlation_id: Option<&str>,
+        traceparent: Option<&str>,
+    ) -> Result<(), EventError> {
+        let mut envelope = Envelope::new(self.source.clone(), event.into());
+        envelope.correlation_id = correlation_id.map(str::to_string);
+        envelope.traceparent = traceparent.map(str::to_string);
+        self.transport.publish(envelope).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::ErrorEvent;
+
+    #[tokio::test]
+    async fn test_in_process() {
+        let bus = InProcess::default();
+        let unknown = Event::Unknown(serde_json::json!({"type": "speech_synthesized"}));
+        bus.publish(Envelope::new("test", unknown)).await.unwrap();
+
+        let mut first = bus.subscribe().await.unwrap();
+        let mut second = bus.subscribe().await.unwrap();
+        let publisher = Publisher::new("agent-core", Arc::new(bus.clone()));
+        publisher
+            .publish_traced(
+                ErrorEvent {
+                    message: "Microphone unavailable".to_string(),
+                    code: None,
+                },
+                Some("turn-1"),
+                Some("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"),
+            )
+            .await
+            .unwrap();
+
+        for subscription in [&mut first, &mut second] {
+            let envelope = subscription.recv().await.unwrap();
+            assert_eq!(envelope.source, "agent-core");
+            assert_eq!(envelope.correlation_id.as_deref(), Some("turn-1"));
+            assert!(envelope.traceparent.is_some());
+            assert_eq!(envelope.event.name(), "error");
+        }
+
+        // Published before subscribing
+        assert!(first.try_recv().is_none());
+    }
+}
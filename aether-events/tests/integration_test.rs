@@ -0,0 +1,88 @@
+//! Integration tests for aether events
+
+use aether_events::{
+    CommandExecuted, CommandPlanned, Envelope, Event, Executor, InProcess, Publisher, Transport,
+    WakeWordDetected,
+};
+use std::sync::Arc;
+
+#[tokio::test]
+async fn test_turn_over_in_process_bus() {
+    let bus = InProcess::default();
+    let mut subscription = bus.subscribe().await.unwrap();
+
+    let detector = Publisher::new("wakeword-detector", Arc::new(bus.clone()));
+    let agent = Publisher::new("agent-core", Arc::new(bus));
+
+    detector
+        .publish(
+            WakeWordDetected {
+                confidence: 0.9,
+                keyword_index: 0,
+                timestamp_us: 1_767_225_600_000_000,
+            },
+            Some("turn-1"),
+        )
+        .await
+        .unwrap();
+    agent
+        .publish(
+            CommandPlanned {
+                transcript: "run ls".to_string(),
+                executor: Executor::Os,
+                command: serde_json::json!({"command": "ls", "args": []}),
+            },
+            Some("turn-1"),
+        )
+        .await
+        .unwrap();
+    agent
+        .publish(
+            CommandExecuted {
+                executor: Executor::Os,
+                success: true,
+                duration_ms: 5,
+                summary: Some("3 files".to_string()),
+                output: serde_json::Value::Null,
+            },
+            Some("turn-1"),
+        )
+        .await
+        .unwrap();
+
+    let mut names = Vec::new();
+    for _ in 0..3 {
+        let envelope = subscription.recv().await.unwrap();
+        assert_eq!(envelope.correlation_id.as_deref(), Some("turn-1"));
+        names.push(envelope.event.name().to_string());
+    }
+    assert_eq!(
+        names,
+        ["wake_word_detected", "command_planned", "command_executed"]
+    );
+}
+
+#[test]
+fn test_envelope_json() {
+    let json = br#"{
+        "version": 1,
+        "id": "6f1c",
+        "source": "agent-core",
+        "timestamp_ms": 1767225600000,
+        "event": {"type": "command_executed", "executor": "browser", "success": false,
+                  "duration_ms": 120, "extra": "ignored"}
+    }"#;
+
+    let envelope = Envelope::from_json(json).unwrap();
+    assert!(envelope.correlation_id.is_none());
+    // Written before envelopes carried a trace context
+    assert!(envelope.traceparent.is_none());
+    match envelope.event {
+        Event::CommandExecuted(executed) => {
+            assert_eq!(executed.executor, Executor::Browser);
+            assert!(!executed.success);
+            assert!(executed.summary.is_none());
+        }
+        other => panic!("unexpected event: {:?}", other),
+    }
+}
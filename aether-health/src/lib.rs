@@ -0,0 +1,33 @@
+//! Aether Health - Liveness and readiness for AetherOS services
+//!
+//! Lets orchestrators (systemd, Kubernetes, docker compose) watch every
+//! service binary the same way:
+//! - `/healthz`: the process is up
+//! - `/readyz`: everything the service needs is ready (model loaded, browser
+//!   launched, whitelist parsed, audio device open), with the state of each
+//! - Checks components mark ready or failed, and probes run per request
+//! - A small built-in HTTP server for services without one, and
+//!   [`Health::respond`] for those with their own
+
+pub mod registry;
+pub mod server;
+
+pub use registry::{Check, CheckReport, Health, Report, Status, LIVENESS_PATH, READINESS_PATH};
+pub use server::{serve, shutdown_signal};
+
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum HealthError {
+    #[error("Failed to listen on {addr}: {source}")]
+    Bind {
+        addr: String,
+        source: std::io::Error,
+    },
+
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Library version
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -0,0 +1,361 @@
+//! Health checks
+//!
+//! A service creates one [`Health`] and hands clones to its components.
+//! Each registers what readiness depends on: a [`Check`] it marks ready or
+//! failed as its state changes, or a probe asked on every readiness request
+//! for state only known by asking (is the browser still running?).
+//!
+//! Anyone who can reach the endpoints reads the reasons, so they are short
+//! fixed phrases; the errors behind them go to the log.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+/// Liveness: the process is up and answering
+pub const LIVENESS_PATH: &str = "/healthz";
+
+/// Readiness: every check passes
+pub const READINESS_PATH: &str = "/readyz";
+
+/// Longest a probe may take before it counts as failed
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+type ProbeFn =
+    Arc<dyn Fn() -> Pin<Box<dyn Future<Output = Result<(), String>> + Send>> + Send + Sync>;
+
+/// What a registered name reports
+#[derive(Clone)]
+enum Entry {
+    Pending,
+    Ready,
+    Failed(String),
+    Probe(ProbeFn),
+}
+
+struct Inner {
+    service: String,
+    version: String,
+    started: Instant,
+    entries: RwLock<BTreeMap<String, Entry>>,
+}
+
+/// The health of one service, shared by its components
+#[derive(Clone)]
+pub struct Health {
+    inner: Arc<Inner>,
+}
+
+impl Health {
+    pub fn new(service: impl Into<String>, version: impl Into<String>) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                service: service.into(),
+                version: version.into(),
+                started: Instant::now(),
+                entries: RwLock::new(BTreeMap::new()),
+            }),
+        }
+    }
+
+    pub fn service(&self) -> &str {
+        &self.inner.service
+    }
+
+    /// Register a check readiness waits for; it is pending until marked
+    /// ready
+    pub fn check(&self, name: impl Into<String>) -> Check {
+        let name = name.into();
+        self.set(&name, Entry::Pending);
+        Check {
+            name,
+            health: self.clone(),
+        }
+    }
+
+    /// Register a check asked on every readiness request, replacing any
+    /// check of the same name; `Err` says what is wrong
+    pub fn probe<F, Fut>(&self, name: impl Into<String>, probe: F)
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), String>> + Send + 'static,
+    {
+        let probe: ProbeFn = Arc::new(move || Box::pin(probe()));
+        self.set(&name.into(), Entry::Probe(probe));
+    }
+
+    /// Stop reporting a check
+    pub fn remove(&self, name: &str) {
+        self.entries_mut().remove(name);
+    }
+
+    /// The liveness report: the process is up
+    pub fn liveness(&self) -> Report {
+        self.report(Status::Ok, BTreeMap::new())
+    }
+
+    /// The readiness report, running the probes
+    pub async fn readiness(&self) -> Report {
+        let entries: Vec<(String, Entry)> = self
+            .inner
+            .entries
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .iter()
+            .map(|(name, entry)| (name.clone(), entry.clone()))
+            .collect();
+
+        let mut checks = BTreeMap::new();
+        for (name, entry) in entries {
+            let check = match entry {
+                Entry::Pending => CheckReport::failed("pending"),
+                Entry::Ready => CheckReport::ready(),
+                Entry::Failed(reason) => CheckReport::failed(reason),
+                Entry::Probe(probe) => match tokio::time::timeout(PROBE_TIMEOUT, probe()).await {
+                    Ok(Ok(())) => CheckReport::ready(),
+                    Ok(Err(reason)) => CheckReport::failed(reason),
+                    Err(_) => CheckReport::failed("timed out"),
+                },
+            };
+            checks.insert(name, check);
+        }
+
+        let status = if checks.values().all(|check| check.ok) {
+            Status::Ok
+        } else {
+            Status::Unavailable
+        };
+        self.report(status, checks)
+    }
+
+    /// The report for an endpoint path, `None` for other paths
+    pub async fn respond(&self, path: &str) -> Option<Report> {
+        match path {
+            LIVENESS_PATH => Some(self.liveness()),
+            READINESS_PATH => Some(self.readiness().await),
+            _ => None,
+        }
+    }
+
+    fn report(&self, status: Status, checks: BTreeMap<String, CheckReport>) -> Report {
+        Report {
+            status,
+            service: self.inner.service.clone(),
+            version: self.inner.version.clone(),
+            uptime_secs: self.inner.started.elapsed().as_secs(),
+            checks,
+        }
+    }
+
+    fn set(&self, name: &str, entry: Entry) {
+        self.entries_mut().insert(name.to_string(), entry);
+    }
+
+    fn entries_mut(&self) -> std::sync::RwLockWriteGuard<'_, BTreeMap<String, Entry>> {
+        self.inner
+            .entries
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl fmt::Debug for Health {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Health")
+            .field("service", &self.inner.service)
+            .field("version", &self.inner.version)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A readiness check, marked by the component it belongs to
+#[derive(Debug, Clone)]
+pub struct Check {
+    name: String,
+    health: Health,
+}
+
+impl Check {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn ready(&self) {
+        self.health.set(&self.name, Entry::Ready);
+    }
+
+    /// Not ready, because of `reason`
+    pub fn fail(&self, reason: impl Into<String>) {
+        self.health.set(&self.name, Entry::Failed(reason.into()));
+    }
+
+    /// Ready on `Ok`, failed on `Err`, with the error logged rather than
+    /// reported
+    pub fn update<T, E: fmt::Display>(&self, result: &Result<T, E>) {
+        match result {
+            Ok(_) => self.ready(),
+            Err(e) => {
+                warn!("{} not ready: {}", self.name, e);
+                self.fail("failed");
+            }
+        }
+    }
+}
+
+/// Overall state in a [`Report`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Ok,
+    Unavailable,
+}
+
+/// One check in a readiness [`Report`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CheckReport {
+    pub ok: bool,
+
+    /// Why the check is not ready
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+impl CheckReport {
+    fn ready() -> Self {
+        Self {
+            ok: true,
+            message: None,
+        }
+    }
+
+    fn failed(reason: impl Into<String>) -> Self {
+        Self {
+            ok: false,
+            message: Some(reason.into()),
+        }
+    }
+}
+
+/// The JSON body of `/healthz` and `/readyz`
+///
+/// ```json
+/// {"status": "unavailable", "service": "browser-executor", "version": "1.0.0",
+///  "uptime_secs": 3, "checks": {"browser": {"ok": false, "message": "pending"}}}
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Report {
+    pub status: Status,
+    pub service: String,
+    pub version: String,
+    pub uptime_secs: u64,
+
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub checks: BTreeMap<String, CheckReport>,
+}
+
+impl Report {
+    pub fn is_ok(&self) -> bool {
+        self.status == Status::Ok
+    }
+
+    /// 200 when ok, 503 otherwise
+    pub fn status_code(&self) -> u16 {
+        if self.is_ok() {
+            200
+        } else {
+            503
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_checks() {
+        let health = Health::new("test-service", "1.2.3");
+        let model = health.check("model");
+        let whitelist = health.check("whitelist");
+
+        let report = health.readiness().await;
+        assert_eq!(report.status, Status::Unavailable);
+        assert_eq!(report.status_code(), 503);
+        assert_eq!(report.checks["model"].message.as_deref(), Some("pending"));
+
+        model.ready();
+        whitelist.update(&Err::<(), _>("bad pattern"));
+        let report = health.readiness().await;
+        assert!(report.checks["model"].ok);
+        assert_eq!(report.checks["whitelist"], CheckReport::failed("failed"));
+
+        whitelist.update(&Ok::<_, String>(()));
+        assert!(health.readiness().await.is_ok());
+
+        // Liveness doesn't depend on the checks
+        whitelist.fail("gone");
+        let live = health.liveness();
+        assert!(live.is_ok());
+        assert!(live.checks.is_empty());
+        assert_eq!(live.service, "test-service");
+    }
+
+    #[tokio::test]
+    async fn test_probes() {
+        let health = Health::new("test-service", "1.2.3");
+        let browser = health.check("browser");
+        browser.ready();
+
+        let running = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let probed = running.clone();
+        health.probe("browser", move || {
+            let running = probed.load(std::sync::atomic::Ordering::SeqCst);
+            async move {
+                if running {
+                    Ok(())
+                } else {
+                    Err("browser is not running".to_string())
+                }
+            }
+        });
+        assert!(!health.readiness().await.is_ok());
+
+        running.store(true, std::sync::atomic::Ordering::SeqCst);
+        assert!(health.readiness().await.is_ok());
+
+        health.probe("slow", || async {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            Ok(())
+        });
+        let report = health.readiness().await;
+        assert_eq!(report.checks["slow"].message.as_deref(), Some("timed out"));
+
+        health.remove("slow");
+        assert!(health.readiness().await.is_ok());
+    }
+
+    #[test]
+    fn test_report_json() {
+        let health = Health::new("stt-service", "0.1.0");
+        let json = serde_json::to_value(health.liveness()).unwrap();
+        assert_eq!(json["status"], "ok");
+        assert!(json.get("checks").is_none());
+
+        let report = Report {
+            status: Status::Unavailable,
+            service: "stt-service".to_string(),
+            version: "0.1.0".to_string(),
+            uptime_secs: 1,
+            checks: [("model".to_string(), CheckReport::failed("pending"))].into(),
+        };
+        let json = serde_json::to_string(&report).unwrap();
+        assert!(json.contains(r#""status":"unavailable""#));
+        assert!(json.contains(r#""model":{"ok":false,"message":"pending"}"#));
+        assert_eq!(serde_json::from_str::<Report>(&json).unwrap(), report);
+    }
+}
@@ -0,0 +1,195 @@
+//! The endpoints over HTTP
+//!
+//! A minimal HTTP/1.1 server for services without a web framework: `GET`
+//! (or `HEAD`) `/healthz` and `/readyz`, one request per connection.
+//! Services already serving HTTP route the two paths to
+//! [`Health::respond`] instead.
+
+use crate::registry::Health;
+use crate::HealthError;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{debug, info, warn};
+
+/// Largest request head read
+const MAX_REQUEST_BYTES: usize = 8192;
+
+/// Longest a client may take to send its request
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Serve `health` on `addr` in the background; returns the address
+/// listened on (useful with port 0)
+pub async fn serve(health: Health, addr: &str) -> Result<SocketAddr, HealthError> {
+    let listener = TcpListener::bind(addr)
+        .await
+        .map_err(|source| HealthError::Bind {
+            addr: addr.to_string(),
+            source,
+        })?;
+    let local_addr = listener.local_addr()?;
+    info!(
+        "{} health endpoints on http://{}",
+        health.service(),
+        local_addr
+    );
+
+    tokio::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    let health = health.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) = handle(stream, &health).await {
+                            debug!("Health request failed: {}", e);
+                        }
+                    });
+                }
+                Err(e) => {
+                    // Out of file descriptors and the like; don't spin
+                    warn!("Health endpoint accept failed: {}", e);
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                }
+            }
+        }
+    });
+
+    Ok(local_addr)
+}
+
+/// Resolves on Ctrl+C, or on SIGTERM from systemd, Kubernetes or docker
+pub async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = terminate.recv() => {}
+                }
+                return;
+            }
+            Err(e) => warn!("Failed to listen for SIGTERM: {}", e),
+        }
+    }
+
+    let _ = tokio::signal::ctrl_c().await;
+}
+
+async fn handle(mut stream: TcpStream, health: &Health) -> std::io::Result<()> {
+    let head = tokio::time::timeout(READ_TIMEOUT, read_head(&mut stream))
+        .await
+        .map_err(|_| std::io::Error::new(std::io::ErrorKind::TimedOut, "request timed out"))??;
+
+    // Only the request line matters
+    let mut request_line = head.lines().next().unwrap_or_default().split_whitespace();
+    let method = request_line.next().unwrap_or_default();
+    let target = request_line.next().unwrap_or_default();
+    let path = target.split('?').next().unwrap_or_default();
+
+    let (status, body) = match method {
+        "GET" | "HEAD" => match health.respond(path).await {
+            Some(report) => (report.status_code(), serde_json::to_string(&report)?),
+            None => (404, error_body("not found")),
+        },
+        _ => (405, error_body("method not allowed")),
+    };
+
+    let mut response = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        reason_phrase(status),
+        body.len()
+    );
+    if method != "HEAD" {
+        response.push_str(&body);
+    }
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+/// Read up to the blank line ending the request head
+async fn read_head(stream: &mut TcpStream) -> std::io::Result<String> {
+    let mut head = Vec::new();
+    let mut buffer = [0u8; 1024];
+    while !head.windows(4).any(|window| window == b"\r\n\r\n") {
+        if head.len() > MAX_REQUEST_BYTES {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "request head too large",
+            ));
+        }
+        let read = stream.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        head.extend_from_slice(&buffer[..read]);
+    }
+    Ok(String::from_utf8_lossy(&head).into_owned())
+}
+
+fn error_body(error: &str) -> String {
+    serde_json::json!({ "error": error }).to_string()
+}
+
+fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        503 => "Service Unavailable",
+        _ => "",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn request(addr: SocketAddr, request: &str) -> String {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[tokio::test]
+    async fn test_serve() {
+        let health = Health::new("test-service", "1.2.3");
+        let whitelist = health.check("whitelist");
+        let addr = serve(health.clone(), "127.0.0.1:0").await.unwrap();
+
+        let response = request(addr, "GET /healthz HTTP/1.1\r\nHost: x\r\n\r\n").await;
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.ends_with(r#""uptime_secs":0}"#));
+
+        let response = request(addr, "GET /readyz?verbose HTTP/1.1\r\n\r\n").await;
+        assert!(response.starts_with("HTTP/1.1 503 Service Unavailable\r\n"));
+        assert!(response.contains(r#""whitelist":{"ok":false,"message":"pending"}"#));
+
+        whitelist.ready();
+        let response = request(addr, "HEAD /readyz HTTP/1.1\r\n\r\n").await;
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.ends_with("\r\n\r\n"));
+
+        let response = request(addr, "GET /metrics HTTP/1.1\r\n\r\n").await;
+        assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+        let response = request(addr, "POST /readyz HTTP/1.1\r\n\r\n").await;
+        assert!(response.starts_with("HTTP/1.1 405 Method Not Allowed\r\n"));
+    }
+
+    #[tokio::test]
+    async fn test_bind_error() {
+        let health = Health::new("test-service", "1.2.3");
+        let addr = serve(health.clone(), "127.0.0.1:0").await.unwrap();
+
+        match serve(health, &addr.to_string()).await {
+            Err(HealthError::Bind { addr: failed, .. }) => assert_eq!(failed, addr.to_string()),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
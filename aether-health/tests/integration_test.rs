@@ -0,0 +1,64 @@
+//! Integration tests for aether health
+
+use aether_health::{serve, Health, Report, Status};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+/// Status code and report from one request
+async fn get(addr: std::net::SocketAddr, path: &str) -> (u16, Report) {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let request = format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path);
+    stream.write_all(request.as_bytes()).await.unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    let status = head.split(' ').nth(1).unwrap().parse().unwrap();
+    (status, serde_json::from_str(body).unwrap())
+}
+
+#[tokio::test]
+async fn test_service_startup() {
+    let health = Health::new("browser-executor", "1.0.0");
+    let browser = health.check("browser");
+    let whitelist = health.check("whitelist");
+    let addr = serve(health.clone(), "127.0.0.1:0").await.unwrap();
+
+    // Alive while still starting up
+    let (status, report) = get(addr, "/healthz").await;
+    assert_eq!(status, 200);
+    assert_eq!(report.service, "browser-executor");
+
+    let (status, report) = get(addr, "/readyz").await;
+    assert_eq!(status, 503);
+    assert_eq!(report.status, Status::Unavailable);
+    assert_eq!(report.checks.len(), 2);
+
+    whitelist.ready();
+    browser.fail("failed to launch Chrome");
+    let (status, report) = get(addr, "/readyz").await;
+    assert_eq!(status, 503);
+    assert!(report.checks["whitelist"].ok);
+    assert_eq!(
+        report.checks["browser"].message.as_deref(),
+        Some("failed to launch Chrome")
+    );
+
+    // Launched: asked on every request from now on
+    health.probe("browser", || async { Ok(()) });
+    let (status, report) = get(addr, "/readyz").await;
+    assert_eq!(status, 200);
+    assert!(report.is_ok());
+}
+
+#[tokio::test]
+async fn test_respond() {
+    let health = Health::new("stt-service", "0.1.0");
+    let model = health.check("model");
+
+    assert_eq!(health.respond("/healthz").await.unwrap().status_code(), 200);
+    assert_eq!(health.respond("/readyz").await.unwrap().status_code(), 503);
+    model.ready();
+    assert_eq!(health.respond("/readyz").await.unwrap().status_code(), 200);
+    assert!(health.respond("/health").await.is_none());
+}
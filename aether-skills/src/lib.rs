@@ -0,0 +1,93 @@
+//! Aether Skills - The skill API for AetherOS agents
+//!
+//! A [`Skill`] adds a capability to the voice agent (smart home, calendar,
+//! ...) without changing its executors or grammar. This crate holds only
+//! the trait and its types, so skills build without the agent and the agent
+//! can ship the skills it registers from its config.
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum SkillError {
+    #[error("A skill named {0} is already registered")]
+    Duplicate(String),
+
+    #[error("No skill named {0}")]
+    Unknown(String),
+
+    #[error("Invalid request for {skill}: {reason}")]
+    InvalidRequest { skill: String, reason: String },
+
+    #[error("{skill} failed: {reason}")]
+    Failed { skill: String, reason: String },
+}
+
+/// What a skill is for
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SkillDescription {
+    /// Unique name, e.g. "smart_home"
+    pub name: String,
+
+    /// What it does, e.g. "Turns lights on and off"
+    pub summary: String,
+
+    /// Things to say to use it, e.g. "turn on the kitchen lights"
+    #[serde(default)]
+    pub examples: Vec<String>,
+}
+
+/// What a skill did with a request
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SkillOutput {
+    pub success: bool,
+
+    /// What to say about it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
+
+    /// Anything else, for the `command_executed` event
+    #[serde(default)]
+    pub data: serde_json::Value,
+}
+
+impl SkillOutput {
+    /// A success, said as `summary`
+    pub fn done(summary: impl Into<String>) -> Self {
+        Self {
+            success: true,
+            summary: Some(summary.into()),
+            data: serde_json::Value::Null,
+        }
+    }
+
+    /// A failure, said as `summary`
+    pub fn failed(summary: impl Into<String>) -> Self {
+        Self {
+            success: false,
+            ..Self::done(summary)
+        }
+    }
+}
+
+/// A capability added to the agent
+#[async_trait]
+pub trait Skill: Send + Sync {
+    /// Its name and what it does; the name must stay the same
+    fn describe(&self) -> SkillDescription;
+
+    /// The request `transcript` makes of this skill, `None` if it is not for
+    /// it
+    ///
+    /// The transcript is lowercase, without the wake phrase or surrounding
+    /// punctuation, e.g. "turn on the kitchen lights". The request is kept
+    /// in the session, so "do that again" executes it again.
+    fn match_intent(&self, transcript: &str) -> Option<serde_json::Value>;
+
+    /// Carry out a request from `match_intent`
+    async fn execute(&self, request: serde_json::Value) -> Result<SkillOutput, SkillError>;
+}
+
+/// Library version
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -0,0 +1,211 @@
+//! W3C trace context
+//!
+//! A voice command's trace starts when the wake word is detected: the
+//! detector's span (or, without an exporter, [`TraceParent::generate`])
+//! gives the trace id, and every step after it (transcription, routing,
+//! executor calls, the spoken reply) runs in spans joined to it. Across
+//! processes the context travels as a `traceparent` string, on event
+//! envelopes or the `TRACEPARENT` variable of an executor's command line.
+
+use std::fmt;
+use std::str::FromStr;
+use tracing::{info_span, Span};
+
+/// A W3C trace context `traceparent` header,
+/// e.g. `00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceParent {
+    /// 32 hex digits
+    pub trace_id: String,
+
+    /// The caller's span (16 hex digits)
+    pub span_id: String,
+
+    pub sampled: bool,
+}
+
+impl TraceParent {
+    /// The start of a new, sampled trace
+    pub fn generate() -> Self {
+        Self {
+            trace_id: format!("{:032x}", random_trace_id()),
+            span_id: format!("{:016x}", random_span_id()),
+            sampled: true,
+        }
+    }
+
+    /// A new span in the same trace
+    pub fn child(&self) -> Self {
+        Self {
+            span_id: format!("{:016x}", random_span_id()),
+            ..self.clone()
+        }
+    }
+
+    /// The context of `span`, when it is exported (the `otel` feature, with
+    /// an exporter installed)
+    pub fn from_span(span: &Span) -> Option<Self> {
+        #[cfg(feature = "otel")]
+        {
+            use opentelemetry::trace::TraceContextExt;
+            use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+            let context = span.context();
+            let span_context = context.span().span_context().clone();
+            if !span_context.is_valid() {
+                return None;
+            }
+            Some(Self {
+                trace_id: span_context.trace_id().to_string(),
+                span_id: span_context.span_id().to_string(),
+                sampled: span_context.is_sampled(),
+            })
+        }
+        #[cfg(not(feature = "otel"))]
+        {
+            let _ = span;
+            None
+        }
+    }
+
+    /// The context of the span being run in, when it is exported
+    pub fn current() -> Option<Self> {
+        Self::from_span(&Span::current())
+    }
+}
+
+impl FromStr for TraceParent {
+    type Err = String;
+
+    fn from_str(header: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid traceparent: {:?}", header);
+        let hex = |part: &str, len: usize| {
+            part.len() == len && part.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+        };
+        let zero = |part: &str| part.bytes().all(|b| b == b'0');
+
+        let parts: Vec<&str> = header.trim().split('-').collect();
+        let [version, trace_id, span_id, flags] = parts[..] else {
+            return Err(invalid());
+        };
+        if !hex(version, 2) || version == "ff" || !hex(flags, 2) {
+            return Err(invalid());
+        }
+        if !hex(trace_id, 32) || zero(trace_id) || !hex(span_id, 16) || zero(span_id) {
+            return Err(invalid());
+        }
+        let flags = u8::from_str_radix(flags, 16).map_err(|_| invalid())?;
+
+        Ok(Self {
+            trace_id: trace_id.to_string(),
+            span_id: span_id.to_string(),
+            sampled: flags & 1 == 1,
+        })
+    }
+}
+
+impl fmt::Display for TraceParent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let flags = if self.sampled { "01" } else { "00" };
+        write!(f, "00-{}-{}-{}", self.trace_id, self.span_id, flags)
+    }
+}
+
+/// A span continuing the caller's trace; run work inside it (with
+/// `tracing::Instrument`) to make it part of that trace
+pub fn remote_span(parent: &TraceParent) -> Span {
+    let span = info_span!("remote_parent", trace_id = %parent.trace_id);
+    set_parent(&span, parent);
+    span
+}
+
+/// Make `parent` the parent of `span`, which must not have been entered yet
+///
+/// Without the `otel` feature spans are not exported and this does nothing;
+/// record `parent.trace_id` on the span to correlate logs instead.
+pub fn set_parent(span: &Span, parent: &TraceParent) {
+    #[cfg(feature = "otel")]
+    {
+        use opentelemetry::propagation::TextMapPropagator;
+        use opentelemetry_sdk::propagation::TraceContextPropagator;
+        use std::collections::HashMap;
+        use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+        let carrier = HashMap::from([("traceparent".to_string(), parent.to_string())]);
+        span.set_parent(TraceContextPropagator::new().extract(&carrier));
+    }
+    #[cfg(not(feature = "otel"))]
+    {
+        let _ = (span, parent);
+    }
+}
+
+/// All-zero ids are invalid
+fn random_trace_id() -> u128 {
+    loop {
+        let id = rand::random::<u128>();
+        if id != 0 {
+            return id;
+        }
+    }
+}
+
+fn random_span_id() -> u64 {
+    loop {
+        let id = rand::random::<u64>();
+        if id != 0 {
+            return id;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trace_parent() {
+        let header = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+        let parent: TraceParent = header.parse().unwrap();
+        assert_eq!(parent.trace_id, "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(parent.span_id, "00f067aa0ba902b7");
+        assert!(parent.sampled);
+        assert_eq!(parent.to_string(), header);
+
+        for invalid in [
+            "",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+            "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+        ] {
+            assert!(invalid.parse::<TraceParent>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_generate() {
+        let parent = TraceParent::generate();
+        assert!(parent.sampled);
+        // Valid, so it survives a round trip through a header
+        assert_eq!(parent.to_string().parse::<TraceParent>().unwrap(), parent);
+        assert_ne!(TraceParent::generate().trace_id, parent.trace_id);
+
+        let child = parent.child();
+        assert_eq!(child.trace_id, parent.trace_id);
+        assert_ne!(child.span_id, parent.span_id);
+        assert_eq!(child.span_id.len(), 16);
+    }
+
+    #[test]
+    fn test_unexported_span() {
+        // No exporter installed
+        let span = info_span!("wake_word");
+        assert_eq!(TraceParent::from_span(&span), None);
+        assert_eq!(span.in_scope(TraceParent::current), None);
+
+        let parent = TraceParent::generate();
+        let span = remote_span(&parent);
+        assert_eq!(TraceParent::from_span(&span), None);
+    }
+}
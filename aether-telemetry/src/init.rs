@@ -0,0 +1,151 @@
+//! Installing the subscriber
+//!
+//! Every binary logs the same way: to stderr (stdout is often JSON output),
+//! filtered by `RUST_LOG` plus the binary's own directive. Built with the
+//! `otel` feature and given an OTLP endpoint, the `info` spans of every
+//! crate are exported too, whatever the log filter, so a trace shows the
+//! whole voice command and not only the binary's own crate.
+
+use crate::TelemetryError;
+use tracing::warn;
+use tracing_subscriber::filter::EnvFilter;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, Layer};
+
+/// The environment variable naming the OTLP/gRPC endpoint to export spans
+/// to, e.g. `http://localhost:4317`
+pub const OTLP_ENDPOINT_ENV: &str = "OTEL_EXPORTER_OTLP_ENDPOINT";
+
+/// Log to stderr, and export spans as `service` when
+/// `OTEL_EXPORTER_OTLP_ENDPOINT` is set
+///
+/// `directives` are added to `RUST_LOG`, e.g. `"stt_processor=info"`.
+/// Call [`shutdown`] before exiting to flush the last spans.
+pub fn init(service: &str, directives: &str) -> Result<(), TelemetryError> {
+    let endpoint = std::env::var(OTLP_ENDPOINT_ENV)
+        .ok()
+        .filter(|endpoint| !endpoint.is_empty());
+    init_with_endpoint(service, directives, endpoint.as_deref())
+}
+
+/// [`init`] with the endpoint given, `None` to only log
+///
+/// An exporter that cannot be built is logged and left out; logging still
+/// works.
+pub fn init_with_endpoint(
+    service: &str,
+    directives: &str,
+    endpoint: Option<&str>,
+) -> Result<(), TelemetryError> {
+    let logs = tracing_subscriber::fmt::layer()
+        .with_writer(std::io::stderr)
+        .with_filter(log_filter(directives)?);
+
+    #[cfg(feature = "otel")]
+    {
+        let mut unexported = None;
+        let spans = match endpoint.map(|endpoint| otlp_tracer(service, endpoint)) {
+            Some(Ok(tracer)) => Some(
+                tracing_opentelemetry::layer()
+                    .with_tracer(tracer)
+                    .with_filter(tracing_subscriber::filter::LevelFilter::INFO),
+            ),
+            Some(Err(e)) => {
+                unexported = Some(e);
+                None
+            }
+            None => None,
+        };
+
+        tracing_subscriber::registry()
+            .with(logs)
+            .with(spans)
+            .try_init()
+            .map_err(|e| TelemetryError::Init(e.to_string()))?;
+        if let Some(e) = unexported {
+            warn!("Spans are not exported: {}", e);
+        }
+    }
+
+    #[cfg(not(feature = "otel"))]
+    {
+        let _ = service;
+        tracing_subscriber::registry()
+            .with(logs)
+            .try_init()
+            .map_err(|e| TelemetryError::Init(e.to_string()))?;
+        if endpoint.is_some() {
+            warn!("Built without the otel feature; spans are not exported");
+        }
+    }
+
+    Ok(())
+}
+
+/// Export the spans not yet sent
+pub fn shutdown() {
+    #[cfg(feature = "otel")]
+    opentelemetry::global::shutdown_tracer_provider();
+}
+
+/// `RUST_LOG` plus the comma separated `directives`
+fn log_filter(directives: &str) -> Result<EnvFilter, TelemetryError> {
+    let mut filter = EnvFilter::from_default_env();
+    for directive in directives.split(',').map(str::trim) {
+        if directive.is_empty() {
+            continue;
+        }
+        let parsed = directive.parse().map_err(|e| TelemetryError::Filter {
+            directive: directive.to_string(),
+            reason: format!("{}", e),
+        })?;
+        filter = filter.add_directive(parsed);
+    }
+    Ok(filter)
+}
+
+#[cfg(feature = "otel")]
+fn otlp_tracer(
+    service: &str,
+    endpoint: &str,
+) -> Result<opentelemetry_sdk::trace::Tracer, opentelemetry::trace::TraceError> {
+    use opentelemetry::KeyValue;
+    use opentelemetry_otlp::WithExportConfig;
+    use opentelemetry_sdk::propagation::TraceContextPropagator;
+    use opentelemetry_sdk::{trace, Resource};
+
+    let tracer = opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(
+            opentelemetry_otlp::new_exporter()
+                .tonic()
+                .with_endpoint(endpoint),
+        )
+        .with_trace_config(
+            trace::config().with_resource(Resource::new(vec![KeyValue::new(
+                "service.name",
+                service.to_string(),
+            )])),
+        )
+        .install_batch(opentelemetry_sdk::runtime::Tokio)?;
+    opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new());
+    Ok(tracer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_filter() {
+        assert!(log_filter("stt_processor=info").is_ok());
+        assert!(log_filter("agent_core=info, stt_processor=debug,").is_ok());
+        assert!(log_filter("").is_ok());
+
+        match log_filter("agent_core=loud") {
+            Err(TelemetryError::Filter { directive, .. }) => {
+                assert_eq!(directive, "agent_core=loud")
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,28 @@
+//! Aether Telemetry - Tracing for AetherOS services
+//!
+//! One trace per voice command, from wake word to spoken reply:
+//! - W3C `traceparent` contexts, started when the wake word is detected and
+//!   carried on events and executor calls
+//! - Spans joined to a caller's trace with [`remote_span`] or [`set_parent`]
+//! - One subscriber setup for every binary: logs on stderr, and spans
+//!   exported over OTLP with the `otel` feature
+
+pub mod context;
+pub mod init;
+
+pub use context::{remote_span, set_parent, TraceParent};
+pub use init::{init, init_with_endpoint, shutdown, OTLP_ENDPOINT_ENV};
+
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum TelemetryError {
+    #[error("Invalid log filter {directive:?}: {reason}")]
+    Filter { directive: String, reason: String },
+
+    #[error("Failed to install the tracing subscriber: {0}")]
+    Init(String),
+}
+
+/// Library version
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
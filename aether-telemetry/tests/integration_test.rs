@@ -0,0 +1,36 @@
+//! Integration tests for aether telemetry
+
+use aether_telemetry::{init_with_endpoint, remote_span, TelemetryError, TraceParent};
+use tracing::{info, info_span, Instrument};
+
+#[tokio::test]
+async fn test_init_once() {
+    init_with_endpoint("test-service", "aether_telemetry=debug", None).unwrap();
+    info!("Logging");
+
+    // One subscriber per process
+    match init_with_endpoint("test-service", "", None) {
+        Err(TelemetryError::Init(_)) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+
+    match init_with_endpoint("test-service", "agent_core=loud", None) {
+        Err(TelemetryError::Filter { .. }) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_propagation() {
+    // As an executor would: parse the caller's header, run inside its trace
+    let wake = TraceParent::generate();
+    let header = wake.child().to_string();
+    let parent: TraceParent = header.parse().unwrap();
+    assert_eq!(parent.trace_id, wake.trace_id);
+
+    let trace_id = async { info_span!("os_command").in_scope(TraceParent::current) }
+        .instrument(remote_span(&parent))
+        .await;
+    // Only exported spans have a context of their own
+    assert_eq!(trace_id, None);
+}
@@ -0,0 +1,859 @@
+//! The voice agent loop
+//!
+//! [`AgentCore::run`] feeds microphone audio to the wake-word detector. When
+//! it fires, the audio it captured (which includes the start of the command)
+//! and the audio that follows go to `StreamingSTT` until the speaker pauses
+//! for `end_of_speech`, or `listen_timeout` passes. The transcript is routed
+//! to a command by the [`IntentRouter`] and dispatched to the os-executor or
+//! browser-executor, or kept as a timer; then the detector listens again.
+//! Each step is published as an `aether-events` event, with one correlation
+//! id per turn, and with a voice ([`AgentCore::with_tts`]) the agent says
+//! what came of it.
+//!
+//! A turn runs in a `voice_turn` span continuing the trace the detector
+//! started at the wake word, with `listen`, `route`, `dispatch` and `speak`
+//! spans for its steps; the executors' and speech-to-text spans nest under
+//! them. Its events carry the turn's `traceparent`, so services handling
+//! them continue the same trace.
+//!
+//! With a [`SessionStore`] ([`AgentCore::with_sessions`]) each turn is
+//! remembered for the next: "do that again" runs the last command again, and
+//! an OS command that requires confirmation is asked about instead of run,
+//! then run once the user says "yes, go ahead".
+
+use crate::dispatch::{Executors, Outcome};
+use crate::router::{Command, IntentRouter};
+use crate::session::{FollowUp, PendingConfirmation, SessionStore};
+use crate::skill::SkillError;
+use crate::timer::{PendingTimer, Timers};
+use aether_events::{
+    new_id, CommandExecuted, CommandPlanned, ErrorEvent, Event, EventError, Executor, InProcess,
+    Publisher, Subscription, TranscriptFinal, Transport, WakeWordDetected,
+};
+use aether_telemetry::{set_parent, TraceParent};
+use os_executor::ApprovalRequest;
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use stt_processor::{AudioPreprocessor, StreamingError, StreamingEvent, StreamingSTT};
+use thiserror::Error;
+use tokio::sync::{mpsc, Mutex};
+use tracing::field::{self, Empty};
+use tracing::{info, info_span, warn, Instrument, Span};
+use tts::{AudioSink, TtsEngine};
+use wakeword_detector::{AudioSample, DetectorError, WakeWordDetector, WakeWordEvent};
+
+/// Source name on the agent's events
+pub const EVENT_SOURCE: &str = "agent-core";
+
+#[derive(Error, Debug)]
+pub enum AgentError {
+    #[error("Wake-word detector error: {0}")]
+    Detector(#[from] DetectorError),
+
+    #[error("Speech-to-text error: {0}")]
+    Stt(#[from] StreamingError),
+
+    #[error("Intent routing failed: {0}")]
+    Routing(String),
+
+    #[error("No {0} executor configured")]
+    NoExecutor(&'static str),
+
+    #[error("OS executor error: {0}")]
+    Os(#[from] os_executor::ExecutorError),
+
+    #[error("Browser executor error: {0}")]
+    Browser(#[from] browser_executor::executor::ExecutorError),
+
+    #[error("Skill error: {0}")]
+    Skill(#[from] SkillError),
+
+    #[error("Invalid timer: {0}")]
+    Timer(String),
+
+    #[error("Event bus error: {0}")]
+    Events(#[from] EventError),
+}
+
+impl AgentError {
+    /// Machine-readable kind, for [`ErrorEvent::code`]
+    pub fn code(&self) -> Option<String> {
+        match self {
+            AgentError::Browser(e) => Some(e.failure().code.to_string()),
+            AgentError::NoExecutor(_) => Some("no_executor".to_string()),
+            _ => None,
+        }
+    }
+}
+
+/// Agent configuration
+#[derive(Debug, Clone)]
+pub struct AgentConfig {
+    /// Longest a command may take to say after the wake word
+    pub listen_timeout: Duration,
+
+    /// Silence after speech that ends the command
+    pub end_of_speech: Duration,
+
+    /// Whose session spoken commands belong to
+    pub user: String,
+}
+
+impl Default for AgentConfig {
+    fn default() -> Self {
+        Self {
+            listen_timeout: Duration::from_secs(8),
+            end_of_speech: Duration::from_millis(800),
+            user: "default".to_string(),
+        }
+    }
+}
+
+/// A transcript and what came of it
+#[derive(Debug, Clone, Serialize)]
+pub struct Turn {
+    pub transcript: String,
+
+    /// `None` when the router did not understand the transcript
+    pub command: Option<Command>,
+
+    pub outcome: Option<Outcome>,
+
+    /// Why routing or dispatching failed
+    pub error: Option<String>,
+
+    /// The question asked instead of running `command`, which needs the
+    /// user's yes first
+    pub confirmation: Option<String>,
+
+    /// The trace the turn ran in, shared by its events' `traceparent`
+    pub trace_id: String,
+}
+
+/// What the events of one turn carry
+#[derive(Debug, Clone)]
+struct TurnIds {
+    correlation_id: String,
+
+    /// The turn's span, as the parent of whatever handles its events
+    trace: TraceParent,
+}
+
+impl TurnIds {
+    /// A new turn, and the `voice_turn` span to run it in: continuing the
+    /// trace started at `wake`, or under the current span without one
+    fn start(wake: Option<&TraceParent>) -> (Self, Span) {
+        let correlation_id = new_id();
+        let parent = match wake {
+            Some(_) => None,
+            None => Span::current().id(),
+        };
+        let span = info_span!(
+            parent: parent,
+            "voice_turn",
+            correlation_id = %correlation_id,
+            trace_id = Empty,
+            transcript = Empty,
+        );
+        if let Some(wake) = wake {
+            set_parent(&span, wake);
+        }
+
+        // Without an exporter the turn still shares the wake word's trace id
+        let trace = TraceParent::from_span(&span)
+            .unwrap_or_else(|| wake.map_or_else(TraceParent::generate, TraceParent::child));
+        span.record("trace_id", trace.trace_id.as_str());
+        (
+            Self {
+                correlation_id,
+                trace,
+            },
+            span,
+        )
+    }
+}
+
+/// Wake word → speech-to-text → intent router → executors
+pub struct AgentCore {
+    detector: WakeWordDetector,
+    stt: StreamingSTT,
+    router: Arc<dyn IntentRouter>,
+    executors: Executors,
+    config: AgentConfig,
+    events: Publisher,
+    voice: Option<Arc<Voice>>,
+    sessions: Option<Arc<SessionStore>>,
+    timers: Timers,
+}
+
+/// Speaks replies, one at a time
+struct Voice {
+    engine: TtsEngine,
+    sink: Mutex<Box<dyn AudioSink>>,
+}
+
+impl Voice {
+    async fn say(&self, text: &str) {
+        async {
+            let mut sink = self.sink.lock().await;
+            if let Err(e) = self.engine.speak_plain(text, sink.as_mut()).await {
+                warn!("Failed to say {:?}: {}", text, e);
+            }
+        }
+        .instrument(info_span!("speak"))
+        .await
+    }
+}
+
+impl AgentCore {
+    /// An agent publishing its events on an in-process bus
+    pub fn new(
+        detector: WakeWordDetector,
+        stt: StreamingSTT,
+        router: impl IntentRouter + 'static,
+        executors: Executors,
+        config: AgentConfig,
+    ) -> Self {
+        Self {
+            detector,
+            stt,
+            router: Arc::new(router),
+            executors,
+            config,
+            events: Publisher::new(EVENT_SOURCE, Arc::new(InProcess::default())),
+            voice: None,
+            sessions: None,
+            timers: Timers::default(),
+        }
+    }
+
+    /// Say the result of each command with `engine`, into `sink`
+    pub fn with_tts(mut self, engine: TtsEngine, sink: impl AudioSink + 'static) -> Self {
+        self.voice = Some(Arc::new(Voice {
+            engine,
+            sink: Mutex::new(Box::new(sink)),
+        }));
+        self
+    }
+
+    /// Remember each user's turns in `sessions`, to resolve follow-ups and
+    /// ask before commands that require confirmation
+    ///
+    /// `sessions` should also be the OS executor's approval provider, so
+    /// the commands the user says yes to are approved.
+    pub fn with_sessions(mut self, sessions: Arc<SessionStore>) -> Self {
+        self.sessions = Some(sessions);
+        self
+    }
+
+    /// Publish events on `transport` instead, e.g. one shared with other
+    /// services
+    pub fn with_transport(mut self, transport: Arc<dyn Transport>) -> Self {
+        self.events = Publisher::new(EVENT_SOURCE, transport);
+        self
+    }
+
+    /// The timers set that have not fired yet
+    pub fn timers(&self) -> Vec<PendingTimer> {
+        self.timers.list()
+    }
+
+    /// Cancel the timer `id`, so it never fires; false if it is not pending
+    pub fn cancel_timer(&self, id: u64) -> bool {
+        self.timers.cancel(id)
+    }
+
+    /// Receive the events published from now on
+    pub async fn subscribe(&self) -> Result<Subscription, AgentError> {
+        Ok(self.events.transport().subscribe().await?)
+    }
+
+    /// Listen for commands in `audio` (16kHz mono PCM) until it ends
+    pub async fn run(&self, mut audio: mpsc::Receiver<Vec<AudioSample>>) -> Result<(), AgentError> {
+        self.detector.start().await?;
+        info!("Agent listening for the wake word");
+
+        let result = async {
+            while let Some(samples) = audio.recv().await {
+                self.detector.process_audio(&samples).await?;
+                let Some(wake) = self.detector.try_recv_event().await else {
+                    continue;
+                };
+
+                let (ids, span) = TurnIds::start(Some(&wake.trace_parent));
+                async {
+                    self.emit(WakeWordDetected::from(&wake), &ids).await;
+
+                    let listening = info_span!("listen", chars = Empty);
+                    let transcript = self
+                        .listen(wake, &mut audio, &ids)
+                        .instrument(listening.clone())
+                        .await?;
+                    listening.record("chars", transcript.text.chars().count() as u64);
+
+                    let text = transcript.text.clone();
+                    self.emit(transcript, &ids).await;
+                    if !text.is_empty() {
+                        self.handle(&self.config.user, &text, &ids).await;
+                    }
+                    Ok::<(), AgentError>(())
+                }
+                .instrument(span)
+                .await?;
+                self.detector.reset().await;
+            }
+            Ok::<(), AgentError>(())
+        }
+        .await;
+
+        self.detector.stop().await?;
+        result
+    }
+
+    /// Route `transcript` and dispatch its command, as a turn of its own
+    /// (in a trace of its own, or the caller's)
+    pub async fn handle_transcript(&self, transcript: &str) -> Turn {
+        self.handle_transcript_for(&self.config.user, transcript)
+            .await
+    }
+
+    /// [`handle_transcript`](Self::handle_transcript) in `user`'s session
+    pub async fn handle_transcript_for(&self, user: &str, transcript: &str) -> Turn {
+        let (ids, span) = TurnIds::start(None);
+        self.handle(user, transcript, &ids).instrument(span).await
+    }
+
+    async fn handle(&self, user: &str, transcript: &str, ids: &TurnIds) -> Turn {
+        Span::current().record("transcript", transcript);
+        let mut turn = Turn {
+            transcript: transcript.to_string(),
+            command: None,
+            outcome: None,
+            error: None,
+            confirmation: None,
+            trace_id: ids.trace.trace_id.clone(),
+        };
+
+        let mut reply = "Sorry, I didn't understand that.".to_string();
+        let routing = info_span!("route", executor = Empty, follow_up = Empty);
+        let follow_up = self
+            .sessions
+            .as_ref()
+            .and_then(|_| FollowUp::parse(transcript));
+        let routed = match follow_up {
+            Some(follow_up) => {
+                routing.record("follow_up", field::debug(follow_up));
+                match self.resume(user, follow_up) {
+                    Ok(command) => Ok(Some(command)),
+                    Err(answer) => {
+                        reply = answer.to_string();
+                        Ok(None)
+                    }
+                }
+            }
+            None => match self.executors.skills.route(transcript) {
+                Some(command) => Ok(Some(command)),
+                None => {
+                    self.router
+                        .route(transcript)
+                        .instrument(routing.clone())
+                        .await
+                }
+            },
+        };
+        let confirmed = follow_up == Some(FollowUp::Confirm);
+        let result = match routed {
+            Ok(Some(command)) => {
+                routing.record("executor", field::debug(executor(&command)));
+                self.emit(planned(transcript, &command), ids).await;
+
+                let prompt = if confirmed {
+                    None
+                } else {
+                    self.confirmation(&command)
+                };
+                let result = match prompt {
+                    // Asked instead of run; "yes" runs it
+                    Some(prompt) => {
+                        info!("Asking first: {}", prompt);
+                        reply = prompt.clone();
+                        turn.confirmation = Some(prompt);
+                        Ok(None)
+                    }
+                    None => {
+                        let dispatched = self.dispatch(&command, confirmed, ids).await;
+                        dispatched.map(|(outcome, summary)| {
+                            reply = summary;
+                            Some(outcome)
+                        })
+                    }
+                };
+                turn.command = Some(command);
+                result
+            }
+            Ok(None) => {
+                if follow_up.is_none() {
+                    info!("Not understood: {:?}", transcript);
+                }
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        };
+
+        match result {
+            Ok(outcome) => turn.outcome = outcome,
+            Err(e) => {
+                reply = "Sorry, that didn't work.".to_string();
+                warn!("Command {:?} failed: {}", transcript, e);
+                let error = ErrorEvent {
+                    message: e.to_string(),
+                    code: e.code(),
+                };
+                self.emit(error, ids).await;
+                turn.error = Some(e.to_string());
+            }
+        }
+
+        self.remember(user, &turn).await;
+        if let Some(voice) = &self.voice {
+            voice.say(&reply).await;
+        }
+        turn
+    }
+
+    /// Run `command` on its executor and publish the result; `confirmed`
+    /// when the user said yes to it. Returns what to say about it.
+    async fn dispatch(
+        &self,
+        command: &Command,
+        confirmed: bool,
+        ids: &TurnIds,
+    ) -> Result<(Outcome, String), AgentError> {
+        let sessions = self.sessions.as_ref().filter(|_| confirmed);
+        if let Some(sessions) = sessions {
+            sessions.approve(command);
+        }
+
+        let started = Instant::now();
+        let dispatching = info_span!(
+            "dispatch",
+            executor = ?executor(command),
+            success = Empty,
+            otel.status_code = Empty,
+        );
+        let result = self
+            .executors
+            .dispatch(command)
+            .instrument(dispatching.clone())
+            .await;
+        if let Some(sessions) = sessions {
+            sessions.revoke(command);
+        }
+        let success = matches!(&result, Ok(outcome) if outcome.success());
+        dispatching.record("success", success);
+        if result.is_err() {
+            dispatching.record("otel.status_code", "ERROR");
+        }
+
+        let outcome = result?;
+        let executed = executed(&outcome, started.elapsed());
+        let reply = match (&executed.summary, executed.success) {
+            (Some(summary), _) => summary.clone(),
+            (None, true) => "Done.".to_string(),
+            (None, false) => "That didn't work.".to_string(),
+        };
+        self.emit(executed, ids).await;
+        if let Outcome::Timer { seconds, label } = &outcome {
+            self.start_timer(*seconds, label.clone(), ids);
+        }
+        Ok((outcome, reply))
+    }
+
+    /// The command `follow_up` refers to in `user`'s session, or what to say
+    /// when there is none
+    fn resume(&self, user: &str, follow_up: FollowUp) -> Result<Command, &'static str> {
+        let session = self
+            .sessions
+            .as_ref()
+            .and_then(|sessions| sessions.get(user));
+        let pending = session.as_ref().and_then(|session| session.pending.clone());
+        match follow_up {
+            FollowUp::Repeat => session
+                .and_then(|session| session.last_command)
+                .ok_or("There's nothing to repeat."),
+            FollowUp::Confirm => pending
+                .map(|pending| pending.command)
+                .ok_or("There's nothing to confirm."),
+            FollowUp::Cancel => Err(match pending {
+                Some(_) => "Okay, I won't.",
+                None => "Okay.",
+            }),
+        }
+    }
+
+    /// The question to ask before running `command`, when the OS executor
+    /// requires confirmation for it and the answer can be heard
+    fn confirmation(&self, command: &Command) -> Option<String> {
+        self.sessions.as_ref()?;
+        let Command::Os { command, args } = command else {
+            return None;
+        };
+        // A command the executor refuses outright fails when dispatched
+        let plan = self.executors.os.as_ref()?.plan(command, args).ok()?;
+        let request = ApprovalRequest {
+            command: command.clone(),
+            args: args.clone(),
+            description: None,
+        };
+        plan.requires_confirmation.then(|| request.prompt())
+    }
+
+    /// Keep `turn` in `user`'s session: what was said, the command run and
+    /// the question asked, if any. Any other question is dropped, the user
+    /// having moved on.
+    async fn remember(&self, user: &str, turn: &Turn) {
+        let Some(sessions) = &self.sessions else {
+            return;
+        };
+        let ran = turn.command.clone().filter(|_| turn.outcome.is_some());
+        let pending = turn
+            .confirmation
+            .clone()
+            .zip(turn.command.clone())
+            .map(|(prompt, command)| PendingConfirmation { command, prompt });
+
+        let saved = sessions
+            .update(user, |session| {
+                session.last_transcript = Some(turn.transcript.clone());
+                if ran.is_some() {
+                    session.last_command = ran;
+                }
+                session.pending = pending;
+            })
+            .await;
+        if let Err(e) = saved {
+            warn!("Failed to save the session of {:?}: {}", user, e);
+        }
+    }
+
+    /// Transcribe the command following `wake`
+    async fn listen(
+        &self,
+        wake: WakeWordEvent,
+        audio: &mut mpsc::Receiver<Vec<AudioSample>>,
+        ids: &TurnIds,
+    ) -> Result<TranscriptFinal, AgentError> {
+        self.stt.start().await?;
+        let deadline = tokio::time::Instant::now() + self.config.listen_timeout;
+        let end_of_speech = self.config.end_of_speech.as_millis() as u64;
+
+        let mut heard = Heard::default();
+        let mut samples = wake.audio_context;
+        loop {
+            let events = self
+                .stt
+                .process_chunk_events(&AudioPreprocessor::i16_to_f32(&samples))
+                .await?;
+            for event in events {
+                self.hear(event, &mut heard, ids).await;
+            }
+            if !heard.texts.is_empty() && self.stt.stats().await.silence_ms >= end_of_speech {
+                break;
+            }
+
+            samples = match tokio::time::timeout_at(deadline, audio.recv()).await {
+                Ok(Some(samples)) => samples,
+                Ok(None) | Err(_) => break,
+            };
+        }
+
+        if let Some(event) = self.stt.stop().await? {
+            self.hear(event, &mut heard, ids).await;
+        }
+        Ok(heard.finish())
+    }
+
+    /// Add a transcription event to `heard`, publishing partial results
+    async fn hear(&self, event: StreamingEvent, heard: &mut Heard, ids: &TurnIds) {
+        match &event {
+            StreamingEvent::Partial {
+                text,
+                confidence,
+                timestamp_ms,
+                ..
+            } => {
+                heard.add(text, *confidence, *timestamp_ms, *timestamp_ms);
+                if let Some(partial) = event.to_event() {
+                    self.emit(partial, ids).await;
+                }
+            }
+            StreamingEvent::Final {
+                text,
+                confidence,
+                start_ms,
+                end_ms,
+                ..
+            } => heard.add(text, *confidence, *start_ms, *end_ms),
+            StreamingEvent::Warning { message, .. } | StreamingEvent::Error { message } => {
+                warn!("Speech-to-text: {}", message)
+            }
+            StreamingEvent::Corrected { .. } | StreamingEvent::EndOfSpeech => {}
+        }
+    }
+
+    /// Publish a `command_executed` event for the timer after `seconds`,
+    /// and say it is done, still in the turn's trace
+    fn start_timer(&self, seconds: u64, label: Option<String>, ids: &TurnIds) {
+        let events = self.events.clone();
+        let voice = self.voice.clone();
+        let ids = ids.clone();
+        let span = info_span!("timer", seconds);
+        let name = label.clone();
+        let timer = async move {
+            info!("Timer {:?} done after {}s", label, seconds);
+            let done = CommandExecuted {
+                executor: Executor::Timer,
+                success: true,
+                duration_ms: seconds.saturating_mul(1000),
+                summary: Some(match &label {
+                    Some(label) => format!("Timer for {} is done.", label),
+                    None => "Timer done.".to_string(),
+                }),
+                output: serde_json::json!({ "seconds": seconds, "label": label }),
+            };
+            let summary = done.summary.clone().unwrap_or_default();
+            publish(&events, done, &ids).await;
+            if let Some(voice) = voice {
+                voice.say(&summary).await;
+            }
+        };
+        self.timers.start(seconds, name, span, timer);
+    }
+
+    async fn emit(&self, event: impl Into<Event>, ids: &TurnIds) {
+        publish(&self.events, event, ids).await;
+    }
+}
+
+/// Publish `event` as part of the turn `ids`
+async fn publish(events: &Publisher, event: impl Into<Event>, ids: &TurnIds) {
+    let traceparent = ids.trace.to_string();
+    if let Err(e) = events
+        .publish_traced(event, Some(&ids.correlation_id), Some(&traceparent))
+        .await
+    {
+        warn!("Failed to publish event: {}", e);
+    }
+}
+
+/// What was said after the wake word, so far
+#[derive(Default)]
+struct Heard {
+    texts: Vec<String>,
+    confidences: Vec<f32>,
+    start_ms: Option<u64>,
+    end_ms: u64,
+}
+
+impl Heard {
+    fn add(&mut self, text: &str, confidence: f32, start_ms: u64, end_ms: u64) {
+        let text = text.trim();
+        if text.is_empty() {
+            return;
+        }
+        self.texts.push(text.to_string());
+        self.confidences.push(confidence);
+        self.start_ms.get_or_insert(start_ms);
+        self.end_ms = self.end_ms.max(end_ms);
+    }
+
+    /// The whole transcript, with the mean confidence of its parts
+    fn finish(self) -> TranscriptFinal {
+        let confidence = if self.confidences.is_empty() {
+            0.0
+        } else {
+            self.confidences.iter().sum::<f32>() / self.confidences.len() as f32
+        };
+        TranscriptFinal {
+            text: self.texts.join(" "),
+            confidence,
+            start_ms: self.start_ms.unwrap_or(0),
+            end_ms: self.end_ms,
+        }
+    }
+}
+
+fn executor(command: &Command) -> Executor {
+    match command {
+        Command::Os { .. } => Executor::Os,
+        Command::Browser { .. } => Executor::Browser,
+        Command::Timer { .. } => Executor::Timer,
+        Command::Skill { .. } => Executor::Skill,
+    }
+}
+
+fn planned(transcript: &str, command: &Command) -> CommandPlanned {
+    CommandPlanned {
+        transcript: transcript.to_string(),
+        executor: executor(command),
+        command: serde_json::to_value(command).unwrap_or_default(),
+    }
+}
+
+fn executed(outcome: &Outcome, duration: Duration) -> CommandExecuted {
+    let (executor, summary) = match outcome {
+        Outcome::Os(result) => (Executor::Os, result.summary.clone()),
+        Outcome::Browser(_) => (Executor::Browser, None),
+        Outcome::Skill { output, .. } => (Executor::Skill, output.summary.clone()),
+        Outcome::Timer { seconds, .. } => (
+            Executor::Timer,
+            Some(format!("Timer set for {}.", spoken_duration(*seconds))),
+        ),
+    };
+    CommandExecuted {
+        executor,
+        success: outcome.success(),
+        duration_ms: duration.as_millis() as u64,
+        summary,
+        output: serde_json::to_value(outcome).unwrap_or_default(),
+    }
+}
+
+/// `seconds` as said, e.g. "1 hour 30 minutes"
+fn spoken_duration(seconds: u64) -> String {
+    let parts = [
+        (seconds / 3600, "hour"),
+        (seconds / 60 % 60, "minute"),
+        (seconds % 60, "second"),
+    ];
+    let spoken: Vec<String> = parts
+        .iter()
+        .filter(|(count, _)| *count > 0)
+        .map(|(count, unit)| match count {
+            1 => format!("1 {}", unit),
+            _ => format!("{} {}s", count, unit),
+        })
+        .collect();
+    if spoken.is_empty() {
+        "0 seconds".to_string()
+    } else {
+        spoken.join(" ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::router::KeywordRouter;
+    use stt_processor::{AudioFormat, StreamingConfig, WhisperConfig, WhisperProcessor};
+    use wakeword_detector::DetectorConfig;
+
+    fn agent(executors: Executors) -> AgentCore {
+        let detector = WakeWordDetector::new(DetectorConfig {
+            access_key: "test_key".to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+        let whisper = Arc::new(WhisperProcessor::new(WhisperConfig::default()).unwrap());
+        let stt = StreamingSTT::new(
+            whisper,
+            AudioFormat::whisper_format(),
+            StreamingConfig::default(),
+        )
+        .unwrap();
+        AgentCore::new(
+            detector,
+            stt,
+            KeywordRouter::default(),
+            executors,
+            AgentConfig::default(),
+        )
+    }
+
+    #[test]
+    fn test_spoken_duration() {
+        assert_eq!(spoken_duration(1), "1 second");
+        assert_eq!(spoken_duration(300), "5 minutes");
+        assert_eq!(spoken_duration(5430), "1 hour 30 minutes 30 seconds");
+    }
+
+    #[test]
+    fn test_turn_ids() {
+        // A turn continues the trace started at the wake word
+        let wake = TraceParent::generate();
+        let (ids, _span) = TurnIds::start(Some(&wake));
+        assert_eq!(ids.trace.trace_id, wake.trace_id);
+        assert_ne!(ids.trace.span_id, wake.span_id);
+
+        let (typed, _span) = TurnIds::start(None);
+        assert_ne!(typed.trace.trace_id, wake.trace_id);
+        assert_ne!(typed.correlation_id, ids.correlation_id);
+    }
+
+    #[tokio::test]
+    async fn test_handle_transcript() {
+        let agent = agent(Executors::default());
+        let mut events = agent.subscribe().await.unwrap();
+
+        let turn = agent.handle_transcript("Hey Aether, open github.com").await;
+        assert!(matches!(turn.command, Some(Command::Browser { .. })));
+        assert!(turn.outcome.is_none());
+        assert_eq!(
+            turn.error.as_deref(),
+            Some("No browser executor configured")
+        );
+
+        let planned = events.recv().await.unwrap();
+        assert_eq!(planned.event.name(), "command_planned");
+        let error = events.recv().await.unwrap();
+        assert_eq!(
+            error.event,
+            Event::Error(ErrorEvent {
+                message: "No browser executor configured".to_string(),
+                code: Some("no_executor".to_string()),
+            })
+        );
+        assert_eq!(planned.correlation_id, error.correlation_id);
+        assert_eq!(planned.traceparent, error.traceparent);
+        let trace: TraceParent = planned.traceparent.unwrap().parse().unwrap();
+        assert_eq!(trace.trace_id, turn.trace_id);
+
+        let turn = agent.handle_transcript("sing me a song").await;
+        assert!(turn.command.is_none() && turn.error.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_follow_ups() {
+        let sessions = Arc::new(SessionStore::in_memory(Duration::from_secs(60)));
+        let agent = agent(Executors::default()).with_sessions(sessions.clone());
+
+        // Nothing to refer back to yet
+        let turn = agent.handle_transcript("do that again").await;
+        assert!(turn.command.is_none() && turn.error.is_none());
+
+        agent.handle_transcript("set a timer for an hour").await;
+        let turn = agent.handle_transcript("Aether, do that again").await;
+        assert!(matches!(
+            turn.outcome,
+            Some(Outcome::Timer { seconds: 3600, .. })
+        ));
+
+        // Both timers are pending until cancelled
+        let timers = agent.timers();
+        assert_eq!(timers.len(), 2);
+        assert!(timers.iter().all(|timer| timer.seconds == 3600));
+        assert!(agent.cancel_timer(timers[0].id));
+        assert_eq!(agent.timers().len(), 1);
+
+        // Sessions are per user
+        let turn = agent.handle_transcript_for("ben", "do that again").await;
+        assert!(turn.command.is_none());
+        let session = sessions.get("default").unwrap();
+        assert_eq!(
+            session.last_transcript.as_deref(),
+            Some("Aether, do that again")
+        );
+        assert!(session.pending.is_none());
+    }
+}
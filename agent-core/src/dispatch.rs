@@ -0,0 +1,114 @@
+//! Dispatching commands to the executors
+
+use crate::agent::AgentError;
+use crate::router::Command;
+use crate::skill::{SkillOutput, SkillRegistry};
+use crate::timer::MAX_TIMER;
+use browser_executor::{ActionOutput, BrowserExecutor};
+use os_executor::{CapabilityToken, CommandExecutor, CommandResult};
+use serde::Serialize;
+use std::sync::Arc;
+use tracing::info;
+
+/// The executors commands are sent to; a command for one that is not set
+/// fails with [`AgentError::NoExecutor`]
+#[derive(Clone, Default)]
+pub struct Executors {
+    pub os: Option<Arc<CommandExecutor>>,
+
+    /// Token presented for OS commands, required by an executor with a
+    /// capability authority
+    pub os_capability: Option<CapabilityToken>,
+
+    pub browser: Option<Arc<BrowserExecutor>>,
+
+    /// Skills, also offered each transcript before the router
+    pub skills: SkillRegistry,
+}
+
+/// What an executor returned for a command
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "executor", content = "result", rename_all = "snake_case")]
+pub enum Outcome {
+    Os(CommandResult),
+
+    /// The output of each action run; the last one failed if `success` is
+    /// false
+    Browser(Vec<ActionOutput>),
+
+    /// A timer was started; it fires later
+    Timer {
+        seconds: u64,
+        label: Option<String>,
+    },
+
+    Skill {
+        skill: String,
+        output: SkillOutput,
+    },
+}
+
+impl Outcome {
+    pub fn success(&self) -> bool {
+        match self {
+            Outcome::Os(result) => result.success,
+            Outcome::Browser(outputs) => outputs.iter().all(|output| output.success),
+            Outcome::Timer { .. } => true,
+            Outcome::Skill { output, .. } => output.success,
+        }
+    }
+}
+
+impl Executors {
+    /// Run `command` on its executor; a timer is only described, the agent
+    /// keeps it
+    pub async fn dispatch(&self, command: &Command) -> Result<Outcome, AgentError> {
+        match command {
+            Command::Os { command, args } => {
+                let executor = self.os.as_ref().ok_or(AgentError::NoExecutor("os"))?;
+                info!("Running {} {:?}", command, args);
+                let result = match &self.os_capability {
+                    Some(token) => {
+                        executor
+                            .execute_with_capability(command, args, token)
+                            .await?
+                    }
+                    None => executor.execute(command, args).await?,
+                };
+                Ok(Outcome::Os(result))
+            }
+            Command::Browser { actions } => {
+                let executor = self
+                    .browser
+                    .as_ref()
+                    .ok_or(AgentError::NoExecutor("browser"))?;
+                let mut outputs = Vec::new();
+                for action in actions {
+                    info!("Running {:?}", action);
+                    let output = executor.execute(action.clone()).await?;
+                    let success = output.success;
+                    outputs.push(output);
+                    if !success {
+                        break;
+                    }
+                }
+                Ok(Outcome::Browser(outputs))
+            }
+            Command::Timer { seconds, .. } if *seconds > MAX_TIMER.as_secs() => Err(
+                AgentError::Timer(format!("{}s is longer than a day", seconds)),
+            ),
+            Command::Timer { seconds, label } => Ok(Outcome::Timer {
+                seconds: *seconds,
+                label: label.clone(),
+            }),
+            Command::Skill { skill, request } => {
+                info!("Running skill {} with {}", skill, request);
+                let output = self.skills.execute(skill, request.clone()).await?;
+                Ok(Outcome::Skill {
+                    skill: skill.clone(),
+                    output,
+                })
+            }
+        }
+    }
+}
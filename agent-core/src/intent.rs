@@ -0,0 +1,467 @@
+//! Intent parsing
+//!
+//! An [`IntentParser`] turns a final transcript into a typed [`Intent`] by
+//! matching it against a grammar of patterns, in order. A pattern is a
+//! sequence of words, optional words in brackets and slots in braces:
+//!
+//! ```text
+//! set [a] timer for {duration} called {label}
+//! ```
+//!
+//! A slot takes one or more words, as few as let the rest of the pattern
+//! match; the rule's builder turns the slots into an intent, or rejects the
+//! match (a `{duration}` that is not a duration) so later rules are tried.
+
+use crate::timer::MAX_TIMER;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// What a transcript asks for
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "intent", rename_all = "snake_case")]
+pub enum Intent {
+    /// Open a web page
+    OpenUrl {
+        url: String,
+    },
+
+    /// Search the web
+    Search {
+        query: String,
+    },
+
+    /// Run an OS command
+    RunCommand {
+        command: String,
+        args: Vec<String>,
+    },
+
+    /// Capture the browser page
+    Screenshot {
+        full_page: bool,
+    },
+
+    /// Say something after `seconds`
+    SetTimer {
+        seconds: u64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        label: Option<String>,
+    },
+
+    GoBack,
+    GoForward,
+    Reload,
+}
+
+/// The words a pattern's slots matched
+#[derive(Debug, Clone, Default)]
+pub struct Slots {
+    values: Vec<(String, String)>,
+}
+
+impl Slots {
+    /// The words slot `name` matched
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.values
+            .iter()
+            .find(|(slot, _)| slot == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Slot `name` as a spoken duration
+    pub fn duration(&self, name: &str) -> Option<Duration> {
+        parse_duration(self.get(name)?)
+    }
+}
+
+/// Builds an intent from a match, or rejects it
+pub type IntentBuilder = Arc<dyn Fn(&Slots) -> Option<Intent> + Send + Sync>;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Word(String),
+    Optional(String),
+    Slot(String),
+}
+
+/// A pattern and what its matches mean
+#[derive(Clone)]
+struct Rule {
+    pattern: String,
+    parts: Vec<Part>,
+    build: IntentBuilder,
+}
+
+impl fmt::Debug for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Rule").field(&self.pattern).finish()
+    }
+}
+
+/// Matches transcripts against a grammar of patterns
+#[derive(Debug, Clone)]
+pub struct IntentParser {
+    /// Spoken before the command and dropped, e.g. "hey aether"
+    pub wake_phrases: Vec<String>,
+
+    rules: Vec<Rule>,
+}
+
+impl Default for IntentParser {
+    /// The built-in grammar
+    fn default() -> Self {
+        let full_page = |_: &Slots| Some(Intent::Screenshot { full_page: true });
+        let screenshot = |_: &Slots| Some(Intent::Screenshot { full_page: false });
+        let timer = |slots: &Slots| {
+            Some(Intent::SetTimer {
+                seconds: slots.duration("duration")?.as_secs().max(1),
+                label: slots.get("label").map(str::to_string),
+            })
+        };
+        let search = |slots: &Slots| {
+            Some(Intent::Search {
+                query: slots.get("query")?.to_string(),
+            })
+        };
+        let open = |slots: &Slots| Some(open_target(slots.get("target")?));
+        let date = |_: &Slots| {
+            Some(Intent::RunCommand {
+                command: "date".to_string(),
+                args: Vec::new(),
+            })
+        };
+        let run = |slots: &Slots| {
+            let mut words = slots.get("command")?.split_whitespace().map(str::to_string);
+            Some(Intent::RunCommand {
+                command: words.next()?,
+                args: words.collect(),
+            })
+        };
+
+        Self::new()
+            .with_rule("search [for] {query}", search)
+            .with_rule("look up {query}", search)
+            .with_rule("take a full page screenshot", full_page)
+            .with_rule("screenshot the [whole] page", full_page)
+            .with_rule("take a screenshot of the [whole] page", full_page)
+            .with_rule("take a screenshot", screenshot)
+            .with_rule("[take] screenshot", screenshot)
+            .with_rule("capture the screen", screenshot)
+            .with_rule("set [a] timer for {duration} called {label}", timer)
+            .with_rule("set [a] timer for {duration}", timer)
+            .with_rule("set [a] {duration} timer", timer)
+            .with_rule("start [a] timer for {duration}", timer)
+            .with_rule("start [a] {duration} timer", timer)
+            .with_rule("timer for {duration}", timer)
+            .with_rule("remind me in {duration} to {label}", timer)
+            .with_rule("remind me to {label} in {duration}", timer)
+            .with_rule("open {target}", open)
+            .with_rule("go to {target}", open)
+            .with_rule("visit {target}", open)
+            .with_rule("browse to {target}", open)
+            .with_rule("run {command}", run)
+            .with_rule("execute {command}", run)
+            .with_rule("what's [the] date [today]", date)
+            .with_rule("what is [the] date [today]", date)
+            .with_rule("what's today's date", date)
+            .with_rule("what day is it [today]", date)
+            .with_rule("[go] back", |_| Some(Intent::GoBack))
+            .with_rule("[go] forward", |_| Some(Intent::GoForward))
+            .with_rule("reload [the] [page]", |_| Some(Intent::Reload))
+            .with_rule("refresh [the] [page]", |_| Some(Intent::Reload))
+    }
+}
+
+impl IntentParser {
+    /// A parser with no rules, dropping "hey aether" and "aether"
+    pub fn new() -> Self {
+        Self {
+            wake_phrases: vec!["hey aether".to_string(), "aether".to_string()],
+            rules: Vec::new(),
+        }
+    }
+
+    /// Add a rule, tried after the existing ones
+    pub fn with_rule(
+        mut self,
+        pattern: &str,
+        build: impl Fn(&Slots) -> Option<Intent> + Send + Sync + 'static,
+    ) -> Self {
+        let parts = pattern
+            .split_whitespace()
+            .map(|part| {
+                if let Some(slot) = part.strip_prefix('{').and_then(|p| p.strip_suffix('}')) {
+                    Part::Slot(slot.to_string())
+                } else if let Some(word) = part.strip_prefix('[').and_then(|p| p.strip_suffix(']'))
+                {
+                    Part::Optional(word.to_lowercase())
+                } else {
+                    Part::Word(part.to_lowercase())
+                }
+            })
+            .collect();
+
+        self.rules.push(Rule {
+            pattern: pattern.to_string(),
+            parts,
+            build: Arc::new(build),
+        });
+        self
+    }
+
+    /// The intent of `transcript`, from the first rule that matches
+    pub fn parse(&self, transcript: &str) -> Option<Intent> {
+        let normalized = normalize(transcript);
+        let text = self.strip_wake_phrase(&normalized);
+        let words: Vec<&str> = text.split_whitespace().collect();
+
+        self.rules.iter().find_map(|rule| {
+            let mut slots = Slots::default();
+            if match_parts(&rule.parts, &words, &mut slots) {
+                (rule.build)(&slots)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// `text` without a leading wake phrase (and the comma after it)
+    pub(crate) fn strip_wake_phrase<'a>(&self, text: &'a str) -> &'a str {
+        for phrase in &self.wake_phrases {
+            if let Some(rest) = text.strip_prefix(phrase.as_str()) {
+                if rest.is_empty() || rest.starts_with([' ', ',']) {
+                    return rest.trim_start_matches([' ', ',']);
+                }
+            }
+        }
+        text
+    }
+}
+
+/// Whether `words` match `parts`, filling in `slots`
+fn match_parts(parts: &[Part], words: &[&str], slots: &mut Slots) -> bool {
+    let Some((part, rest)) = parts.split_first() else {
+        return words.is_empty();
+    };
+
+    match part {
+        Part::Word(word) => {
+            words.first() == Some(&word.as_str()) && match_parts(rest, &words[1..], slots)
+        }
+        Part::Optional(word) => {
+            (words.first() == Some(&word.as_str()) && match_parts(rest, &words[1..], slots))
+                || match_parts(rest, words, slots)
+        }
+        Part::Slot(name) => {
+            for taken in 1..=words.len() {
+                slots.values.push((name.clone(), words[..taken].join(" ")));
+                if match_parts(rest, &words[taken..], slots) {
+                    return true;
+                }
+                slots.values.pop();
+            }
+            false
+        }
+    }
+}
+
+/// Lowercase, single-spaced, without surrounding punctuation
+pub(crate) fn normalize(transcript: &str) -> String {
+    transcript
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .trim_matches(|c: char| matches!(c, '.' | '!' | '?' | ',' | '"'))
+        .to_lowercase()
+}
+
+/// A spoken site: as said when it has a scheme, over https when it looks
+/// like a domain, otherwise a search for it
+fn open_target(target: &str) -> Intent {
+    let target = target.replace(" dot ", ".");
+    if target.contains("://") {
+        Intent::OpenUrl { url: target }
+    } else if target.contains('.') && !target.contains(' ') {
+        Intent::OpenUrl {
+            url: format!("https://{}", target),
+        }
+    } else {
+        Intent::Search { query: target }
+    }
+}
+
+/// A spoken duration: "5 minutes", "an hour and a half", "one minute and
+/// thirty seconds", "1.5 hours"; `None` over [`MAX_TIMER`]
+pub fn parse_duration(text: &str) -> Option<Duration> {
+    let mut total = 0.0;
+    let mut amount: Option<f64> = None;
+    // `amount` is only "a" or "an", which "a half" replaces
+    let mut article = false;
+    let mut last_unit = None;
+
+    for word in text.split_whitespace() {
+        if let Some(unit) = unit_seconds(word) {
+            total += amount.take()? * unit;
+            article = false;
+            last_unit = Some(unit);
+            continue;
+        }
+
+        match word {
+            "and" => {}
+            "a" | "an" => {
+                if amount.is_none() {
+                    amount = Some(1.0);
+                    article = true;
+                }
+            }
+            "half" => {
+                amount = Some(if article {
+                    0.5
+                } else {
+                    amount.unwrap_or(0.0) + 0.5
+                });
+                article = false;
+            }
+            _ => {
+                let number = word.parse::<f64>().ok().or_else(|| number_word(word))?;
+                amount = Some(amount.unwrap_or(0.0) + number);
+                article = false;
+            }
+        }
+    }
+
+    // "and a half" after a unit is half of that unit
+    if let Some(amount) = amount {
+        total += amount * last_unit?;
+    }
+    if total <= 0.0 {
+        return None;
+    }
+    Duration::try_from_secs_f64(total)
+        .ok()
+        .filter(|duration| *duration <= MAX_TIMER)
+}
+
+fn unit_seconds(word: &str) -> Option<f64> {
+    match word {
+        "second" | "seconds" | "sec" | "secs" => Some(1.0),
+        "minute" | "minutes" | "min" | "mins" => Some(60.0),
+        "hour" | "hours" | "hr" | "hrs" => Some(3600.0),
+        _ => None,
+    }
+}
+
+fn number_word(word: &str) -> Option<f64> {
+    const ONES: &str = "zero one two three four five six seven eight nine ten eleven twelve \
+                        thirteen fourteen fifteen sixteen seventeen eighteen nineteen";
+    const TENS: &str = "twenty thirty forty fifty sixty seventy eighty ninety";
+
+    if let Some(n) = ONES.split_whitespace().position(|w| w == word) {
+        return Some(n as f64);
+    }
+    TENS.split_whitespace()
+        .position(|w| w == word)
+        .map(|n| (n as f64 + 2.0) * 10.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_intents() {
+        let parser = IntentParser::default();
+        assert_eq!(
+            parser.parse("Hey Aether, open GitHub.com."),
+            Some(Intent::OpenUrl {
+                url: "https://github.com".to_string()
+            })
+        );
+        assert_eq!(
+            parser.parse("open the weather"),
+            Some(Intent::Search {
+                query: "the weather".to_string()
+            })
+        );
+        assert_eq!(
+            parser.parse("take a screenshot of the whole page"),
+            Some(Intent::Screenshot { full_page: true })
+        );
+        assert_eq!(
+            parser.parse("aether screenshot"),
+            Some(Intent::Screenshot { full_page: false })
+        );
+        assert_eq!(parser.parse("refresh the page"), Some(Intent::Reload));
+        let date = Some(Intent::RunCommand {
+            command: "date".to_string(),
+            args: Vec::new(),
+        });
+        assert_eq!(parser.parse("Hey Aether, what's the date?"), date);
+        assert_eq!(parser.parse("what day is it today"), date);
+        assert_eq!(parser.parse("what's the time"), None);
+    }
+
+    #[test]
+    fn test_timer_slots() {
+        let parser = IntentParser::default();
+        assert_eq!(
+            parser.parse("set a timer for 5 minutes called pasta"),
+            Some(Intent::SetTimer {
+                seconds: 300,
+                label: Some("pasta".to_string())
+            })
+        );
+        assert_eq!(
+            parser.parse("set a ten minute timer"),
+            Some(Intent::SetTimer {
+                seconds: 600,
+                label: None
+            })
+        );
+        assert_eq!(
+            parser.parse("Remind me in an hour and a half to call mom."),
+            Some(Intent::SetTimer {
+                seconds: 5400,
+                label: Some("call mom".to_string())
+            })
+        );
+        // Not a duration, so no timer
+        assert_eq!(parser.parse("set a timer for later"), None);
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        let secs = |text| parse_duration(text).map(|d| d.as_secs());
+        assert_eq!(secs("90 seconds"), Some(90));
+        assert_eq!(secs("twenty five minutes"), Some(1500));
+        assert_eq!(secs("half an hour"), Some(1800));
+        assert_eq!(secs("one and a half hours"), Some(5400));
+        assert_eq!(secs("a minute and thirty seconds"), Some(90));
+        assert_eq!(secs("1.5 hours"), Some(5400));
+        assert_eq!(secs("5"), None);
+        assert_eq!(secs("minutes"), None);
+        assert_eq!(secs("soon"), None);
+        assert_eq!(secs("24 hours"), Some(86400));
+        assert_eq!(secs("25 hours"), None);
+        assert_eq!(secs("1e300 hours"), None);
+    }
+
+    #[test]
+    fn test_custom_rule() {
+        let parser = IntentParser::new().with_rule("show me {site}", |slots| {
+            Some(Intent::OpenUrl {
+                url: format!("https://{}", slots.get("site")?),
+            })
+        });
+        assert_eq!(
+            parser.parse("show me example.org"),
+            Some(Intent::OpenUrl {
+                url: "https://example.org".to_string()
+            })
+        );
+        assert_eq!(parser.parse("open example.org"), None);
+    }
+}
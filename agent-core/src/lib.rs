@@ -0,0 +1,37 @@
+//! Agent Core - Voice agent orchestration for AetherOS
+//!
+//! Wires the services into one voice agent:
+//! - Wake-word detection on microphone audio
+//! - Speech-to-text of the command that follows, from the audio the detector
+//!   captured on
+//! - Intent parsing with a pattern grammar and typed slots ("set a timer
+//!   for {duration}")
+//! - Pluggable intent routing from transcripts to commands
+//! - Dispatch to the OS executor and the browser executor, and timers
+//! - `aether-events` events for each wake word, transcript, command and
+//!   error, shared by one correlation id per turn
+//! - Per-user sessions resolving follow-ups ("do that again", "yes, go
+//!   ahead") and confirming risky commands before they run
+//! - Skills registered at startup, adding capabilities (smart home,
+//!   calendar) without changing the executors
+
+pub mod agent;
+pub mod dispatch;
+pub mod intent;
+pub mod router;
+pub mod session;
+pub mod skill;
+pub mod timer;
+
+pub use agent::{AgentConfig, AgentCore, AgentError, Turn, EVENT_SOURCE};
+pub use dispatch::{Executors, Outcome};
+pub use intent::{parse_duration, Intent, IntentParser, Slots};
+pub use router::{Command, IntentRouter, KeywordRouter, SEARCH_URL};
+pub use session::{
+    FollowUp, PendingConfirmation, Session, SessionError, SessionStore, DEFAULT_FOLLOW_UP_TTL,
+};
+pub use skill::{Skill, SkillDescription, SkillError, SkillOutput, SkillRegistry};
+pub use timer::{PendingTimer, MAX_TIMER};
+
+/// Library version
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -0,0 +1,249 @@
+//! Agent Core binary
+//!
+//! Runs the voice agent on raw 16-bit 16kHz mono PCM from stdin
+//! (e.g. `arecord -f S16_LE -r 16000 -c 1 | agent-core`) and prints its
+//! event envelopes as JSON lines; replies are spoken through the `[tts]`
+//! player. The services are configured from the shared AetherOS config
+//! (`--config`, `--set`); `agent-core validate` and
+//! `agent-core print-effective-config` check and show it. Sessions are kept
+//! in `AGENT_SESSION_DIR` when it is set, otherwise in memory. The skills
+//! named in `[skills]` are registered in order. The executors run in this
+//! process, so `/healthz` and `/readyz` on `agent.health_addr` report them.
+//! Built with the `otel` feature, each voice command's trace goes to
+//! `OTEL_EXPORTER_OTLP_ENDPOINT`.
+
+use aether_config::{AetherConfig, ConfigArgs, ConfigCommand};
+use aether_health::Health;
+use agent_core::{
+    AgentConfig, AgentCore, Executors, KeywordRouter, SessionStore, Skill, DEFAULT_FOLLOW_UP_TTL,
+};
+use browser_executor::BrowserExecutor;
+use smart_home_skill::SmartHomeSkill;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use stt_processor::{AudioFormat, StreamingConfig, StreamingSTT, WhisperConfig, WhisperProcessor};
+use tokio::io::AsyncReadExt;
+use tokio::sync::mpsc;
+use tracing::{error, info, warn};
+use tts::{PlayerSink, TtsEngine};
+use wakeword_detector::{DetectorConfig, WakeWordDetector, SAMPLE_RATE};
+
+#[tokio::main]
+async fn main() {
+    // Logs go to stderr, events to stdout; each voice command's trace is
+    // exported when OTEL_EXPORTER_OTLP_ENDPOINT is set
+    if let Err(e) = aether_telemetry::init("agent-core", "agent_core=info") {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+
+    let result = run().await;
+    aether_telemetry::shutdown();
+    if let Err(e) = result {
+        error!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+async fn run() -> anyhow::Result<()> {
+    let mut args: Vec<String> = std::env::args().collect();
+    let mut config_args = ConfigArgs::default();
+    while config_args.take(&mut args, 1)? {}
+
+    if let Some(name) = args.get(1) {
+        let command = ConfigCommand::parse(name)
+            .ok_or_else(|| anyhow::anyhow!("Unknown command: {}", name))?;
+        command.run(&config_args)?;
+        return Ok(());
+    }
+
+    info!("Starting AetherOS Agent Core");
+    let config = config_args.load()?;
+
+    // Answering while the models load and the browser launches
+    let health = Health::new("agent-core", env!("CARGO_PKG_VERSION"));
+    aether_health::serve(health.clone(), &config.agent.health_addr).await?;
+
+    let detector = WakeWordDetector::new(detector_config(&config))?;
+
+    let whisper = Arc::new(WhisperProcessor::new(WhisperConfig::from(&config.stt))?);
+    let stt = StreamingSTT::new(
+        whisper,
+        AudioFormat::new(SAMPLE_RATE as u32, 1, 16),
+        StreamingConfig::default(),
+    )?;
+
+    let sessions = Arc::new(match std::env::var("AGENT_SESSION_DIR") {
+        Ok(dir) => SessionStore::open(dir, DEFAULT_FOLLOW_UP_TTL)?,
+        Err(_) => SessionStore::in_memory(DEFAULT_FOLLOW_UP_TTL),
+    });
+
+    let mut executors = load_executors(&config, &sessions, &health).await;
+    register_skills(&config, &mut executors)?;
+
+    let agent = AgentCore::new(
+        detector,
+        stt,
+        KeywordRouter::default(),
+        executors,
+        AgentConfig::default(),
+    )
+    .with_sessions(sessions);
+    let agent = match TtsEngine::from_config(&config.tts) {
+        Some(engine) => agent.with_tts(engine, PlayerSink::new(config.tts.player.clone())),
+        None => agent,
+    };
+
+    let mut events = agent.subscribe().await?;
+    tokio::spawn(async move {
+        while let Some(envelope) = events.recv().await {
+            match serde_json::to_string(&envelope) {
+                Ok(line) => println!("{}", line),
+                Err(e) => warn!("Unprintable event: {}", e),
+            }
+        }
+    });
+
+    let (audio_tx, audio_rx) = mpsc::channel(32);
+    tokio::spawn(async move {
+        let mut stdin = tokio::io::stdin();
+        // 32ms of audio per read
+        let mut buf = vec![0u8; 1024];
+
+        loop {
+            match stdin.read(&mut buf).await {
+                Ok(0) => break,
+                Ok(n) => {
+                    let pcm: Vec<i16> = buf[..n - n % 2]
+                        .chunks_exact(2)
+                        .map(|b| i16::from_le_bytes([b[0], b[1]]))
+                        .collect();
+
+                    if !pcm.is_empty() && audio_tx.send(pcm).await.is_err() {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    error!("Failed to read stdin: {}", e);
+                    break;
+                }
+            }
+        }
+    });
+
+    agent.run(audio_rx).await?;
+    info!("Agent Core stopped");
+    Ok(())
+}
+
+/// Wake-word settings from the `[wakeword]` section
+fn detector_config(config: &AetherConfig) -> DetectorConfig {
+    let mut detector_config = DetectorConfig::from(&config.wakeword);
+    if detector_config.access_key.is_empty() {
+        warn!("wakeword.access_key not set, using test key");
+        detector_config.access_key = "test_key".to_string();
+    }
+    detector_config
+}
+
+/// The executors named in `AGENT_EXECUTORS` (default `os,browser`); a browser
+/// that fails to launch is left out. The OS executor runs the commands that
+/// require confirmation once the user said yes in their session, and with
+/// `AGENT_CAPABILITY_KEY` set only commands under the agent's capability token.
+/// Each executor is a readiness check in `health`.
+async fn load_executors(
+    config: &AetherConfig,
+    sessions: &Arc<SessionStore>,
+    health: &Health,
+) -> Executors {
+    let wanted = std::env::var("AGENT_EXECUTORS").unwrap_or_else(|_| "os,browser".to_string());
+    let mut executors = Executors::default();
+
+    for name in wanted.split(',').map(str::trim) {
+        match name {
+            "os" => {
+                let whitelist = os_executor::CommandWhitelist::default();
+                health.check("whitelist").update(&whitelist.validate());
+                let executor = os_executor::CommandExecutor::new(
+                    os_executor::ExecutorConfig::from(&config.os_executor),
+                    whitelist,
+                )
+                .with_approval_provider(sessions.clone());
+                let executor = match std::env::var("AGENT_CAPABILITY_KEY") {
+                    Ok(key) if !key.is_empty() => {
+                        let authority = os_executor::CapabilityAuthority::new(key);
+                        let capability = os_executor::Capability::new("agent-core", &["*"]);
+                        executors.os_capability = Some(authority.issue(&capability));
+                        executor.with_capability_authority(authority)
+                    }
+                    _ => executor,
+                };
+                executors.os = Some(Arc::new(executor));
+            }
+            "browser" => {
+                let launching = health.check("browser");
+                let section = &config.browser_executor;
+                let mut config = browser_executor::ExecutorConfig::from(section);
+                if let Some(vault) = &section.credential_vault {
+                    // Without it, servers asking for a password are refused
+                    match browser_executor::CredentialVault::open_from_env(vault) {
+                        Ok(vault) => config.credentials = Some(Arc::new(vault)),
+                        Err(e) => warn!("Credential vault unavailable: {}", e),
+                    }
+                }
+                match BrowserExecutor::new(config).await {
+                    Ok(browser) => {
+                        let browser = Arc::new(browser);
+                        probe_browser(health, browser.clone());
+                        executors.browser = Some(browser);
+                    }
+                    Err(e) => {
+                        warn!("Browser executor unavailable: {}", e);
+                        launching.fail("failed to launch");
+                    }
+                }
+            }
+            "" => {}
+            other => warn!("Unknown executor {:?} in AGENT_EXECUTORS", other),
+        }
+    }
+
+    executors
+}
+
+/// Register the skills named in `skills.enabled`, offered transcripts in
+/// that order; an unknown name stops the agent, as an unknown key would
+fn register_skills(config: &AetherConfig, executors: &mut Executors) -> anyhow::Result<()> {
+    for name in &config.skills.enabled {
+        let skill: Arc<dyn Skill> = match name.as_str() {
+            smart_home_skill::SKILL_NAME => Arc::new(SmartHomeSkill::default()),
+            other => anyhow::bail!("Unknown skill {:?} in skills.enabled", other),
+        };
+        executors.skills.register(skill)?;
+    }
+    Ok(())
+}
+
+/// Report the browser on every readiness request, relaunching it in the
+/// background once it crashed rather than waiting for the next action
+fn probe_browser(health: &Health, browser: Arc<BrowserExecutor>) {
+    let relaunching = Arc::new(AtomicBool::new(false));
+    health.probe("browser", move || {
+        let browser = browser.clone();
+        let relaunching = relaunching.clone();
+        async move {
+            if browser.is_healthy().await {
+                return Ok(());
+            }
+            if !relaunching.swap(true, Ordering::SeqCst) {
+                tokio::spawn(async move {
+                    if let Err(e) = browser.recover().await {
+                        warn!("Failed to relaunch the browser: {}", e);
+                    }
+                    relaunching.store(false, Ordering::SeqCst);
+                });
+            }
+            Err("crashed, relaunching".to_string())
+        }
+    });
+}
@@ -0,0 +1,185 @@
+//! Intent routing
+//!
+//! An [`IntentRouter`] turns a transcript into a [`Command`] for one of the
+//! executors, or `None` when it does not understand it. [`KeywordRouter`]
+//! parses it into an [`Intent`] with an [`IntentParser`] ("open github.com",
+//! "search for rust tutorials", "run ls -la", "set a timer for 5 minutes");
+//! an LLM-backed router can implement the trait instead.
+
+use crate::agent::AgentError;
+use crate::intent::{Intent, IntentParser};
+use async_trait::async_trait;
+use browser_executor::{BrowserAction, WaitCondition};
+use serde::{Deserialize, Serialize};
+
+/// Search engine `search for ...` navigates to; the query is appended
+pub const SEARCH_URL: &str = "https://duckduckgo.com/?q=";
+
+/// A command for an executor
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "executor", rename_all = "snake_case")]
+pub enum Command {
+    /// A whitelisted OS command
+    Os { command: String, args: Vec<String> },
+
+    /// Browser actions, run in order until one fails
+    Browser { actions: Vec<BrowserAction> },
+
+    /// A timer kept by the agent itself
+    Timer {
+        seconds: u64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        label: Option<String>,
+    },
+
+    /// A request for a registered skill, as its `match_intent` made it
+    Skill {
+        skill: String,
+        request: serde_json::Value,
+    },
+}
+
+impl From<Intent> for Command {
+    fn from(intent: Intent) -> Self {
+        let browser = |action| Command::Browser {
+            actions: vec![action],
+        };
+        let navigate = |url| {
+            browser(BrowserAction::Navigate {
+                url,
+                wait_until: WaitCondition::Load,
+            })
+        };
+
+        match intent {
+            Intent::OpenUrl { url } => navigate(url),
+            Intent::Search { query } => navigate(format!("{}{}", SEARCH_URL, encode_query(&query))),
+            Intent::RunCommand { command, args } => Command::Os { command, args },
+            Intent::Screenshot { full_page } => browser(BrowserAction::Screenshot {
+                full_page,
+                stitch: false,
+            }),
+            Intent::SetTimer { seconds, label } => Command::Timer { seconds, label },
+            Intent::GoBack => browser(BrowserAction::GoBack {
+                wait_until: WaitCondition::Load,
+            }),
+            Intent::GoForward => browser(BrowserAction::GoForward {
+                wait_until: WaitCondition::Load,
+            }),
+            Intent::Reload => browser(BrowserAction::Reload),
+        }
+    }
+}
+
+/// Maps transcripts to commands
+#[async_trait]
+pub trait IntentRouter: Send + Sync {
+    /// The command `transcript` asks for; `None` if it is not understood
+    async fn route(&self, transcript: &str) -> Result<Option<Command>, AgentError>;
+}
+
+/// Routes the intents of an [`IntentParser`]'s grammar
+#[derive(Debug, Clone, Default)]
+pub struct KeywordRouter {
+    pub parser: IntentParser,
+}
+
+#[async_trait]
+impl IntentRouter for KeywordRouter {
+    async fn route(&self, transcript: &str) -> Result<Option<Command>, AgentError> {
+        Ok(self.parse(transcript))
+    }
+}
+
+impl KeywordRouter {
+    /// The command `transcript` asks for
+    pub fn parse(&self, transcript: &str) -> Option<Command> {
+        self.parser.parse(transcript).map(Command::from)
+    }
+}
+
+/// `query` for a URL query string, spaces as `+`
+fn encode_query(query: &str) -> String {
+    query
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            b' ' => "+".to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(command: Option<Command>) -> String {
+        match command {
+            Some(Command::Browser { actions }) => match &actions[..] {
+                [BrowserAction::Navigate { url, .. }] => url.clone(),
+                _ => panic!("not a navigation: {:?}", actions),
+            },
+            other => panic!("not a browser command: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_browser_commands() {
+        let router = KeywordRouter::default();
+        assert_eq!(
+            url(router.parse("Hey Aether, open GitHub.com.")),
+            "https://github.com"
+        );
+        assert_eq!(
+            url(router.parse("go to example dot org")),
+            "https://example.org"
+        );
+        assert_eq!(
+            url(router.parse("search for rust & tokio")),
+            "https://duckduckgo.com/?q=rust+%26+tokio"
+        );
+        assert_eq!(
+            url(router.parse("open the weather")),
+            "https://duckduckgo.com/?q=the+weather"
+        );
+        assert!(matches!(
+            router.parse("Aether go back").unwrap(),
+            Command::Browser { actions } if matches!(actions[..], [BrowserAction::GoBack { .. }])
+        ));
+    }
+
+    #[test]
+    fn test_os_commands() {
+        let router = KeywordRouter::default();
+        match router.parse("run ls -la") {
+            Some(Command::Os { command, args }) => {
+                assert_eq!(command, "ls");
+                assert_eq!(args, vec!["-la"]);
+            }
+            other => panic!("not an OS command: {:?}", other),
+        }
+        assert!(router.parse("run").is_none());
+        assert!(router.parse("what's the time").is_none());
+        assert!(router.parse("aetherial music").is_none());
+    }
+
+    #[test]
+    fn test_intent_commands() {
+        let router = KeywordRouter::default();
+        assert!(matches!(
+            router.parse("take a full page screenshot").unwrap(),
+            Command::Browser { actions }
+                if matches!(actions[..], [BrowserAction::Screenshot { full_page: true, .. }])
+        ));
+        match router.parse("set a timer for two minutes") {
+            Some(Command::Timer { seconds, label }) => {
+                assert_eq!(seconds, 120);
+                assert!(label.is_none());
+            }
+            other => panic!("not a timer: {:?}", other),
+        }
+    }
+}
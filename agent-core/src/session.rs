@@ -0,0 +1,527 @@
+//! Conversation state
+//!
+//! A [`SessionStore`] keeps, per user, what the last turn said and ran and
+//! the question it is waiting on, so the next turn can refer back to it: "do
+//! that again" runs the last command again, and "yes, go ahead" runs the
+//! command the agent asked about. Follow-ups only refer to a turn within the
+//! store's TTL; after that the session is stale and they are not understood.
+//!
+//! Sessions are kept in memory, or as one JSON file per user in a directory
+//! so they survive a restart. Files are replaced whole, so a crash leaves
+//! the old session or the new one; one that cannot be read is set aside as
+//! `<user>.json.invalid`, and stale sessions are dropped. A question waiting
+//! for a yes is never saved: after a restart, "yes" runs nothing a file
+//! could have changed. The store is also the OS executor's
+//! [`ApprovalProvider`]: it approves a command once, after the user said yes
+//! to it, and denies everything else.
+
+use crate::intent::{normalize, IntentParser};
+use crate::router::Command;
+use async_trait::async_trait;
+use os_executor::{ApprovalDecision, ApprovalProvider, ApprovalRequest};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+use tracing::{info, warn};
+
+/// How long after a turn follow-ups still refer to it
+pub const DEFAULT_FOLLOW_UP_TTL: Duration = Duration::from_secs(120);
+
+#[derive(Error, Debug)]
+pub enum SessionError {
+    #[error("Session storage error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Invalid session file {path}: {reason}")]
+    Invalid { path: PathBuf, reason: String },
+}
+
+/// One user's conversation so far
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    pub user: String,
+
+    /// What was said in the last turn
+    pub last_transcript: Option<String>,
+
+    /// The last command dispatched, what "do that again" runs
+    pub last_command: Option<Command>,
+
+    /// The command the agent asked about, what "yes" runs; kept in memory
+    /// only
+    #[serde(skip)]
+    pub pending: Option<PendingConfirmation>,
+
+    /// When the last turn ended (ms since the Unix epoch)
+    pub updated_ms: u64,
+}
+
+impl Session {
+    fn new(user: &str) -> Self {
+        Self {
+            user: user.to_string(),
+            last_transcript: None,
+            last_command: None,
+            pending: None,
+            updated_ms: 0,
+        }
+    }
+
+    /// Whether follow-ups still refer to the last turn
+    pub fn is_fresh(&self, ttl: Duration) -> bool {
+        now_ms().saturating_sub(self.updated_ms) <= ttl.as_millis() as u64
+    }
+}
+
+/// A command waiting for the user's yes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingConfirmation {
+    pub command: Command,
+
+    /// The question the agent asked
+    pub prompt: String,
+}
+
+/// A transcript answering the last turn rather than asking for something
+/// new
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FollowUp {
+    /// "do that again", "repeat that"
+    Repeat,
+
+    /// "yes", "yes, go ahead"
+    Confirm,
+
+    /// "no", "cancel", "never mind"
+    Cancel,
+}
+
+impl FollowUp {
+    const REPEAT: &'static [&'static str] = &[
+        "again",
+        "do that again",
+        "do it again",
+        "repeat that",
+        "same again",
+        "one more time",
+    ];
+    const CONFIRM: &'static [&'static str] = &[
+        "yes",
+        "yeah",
+        "yep",
+        "sure",
+        "ok",
+        "okay",
+        "confirm",
+        "do it",
+        "go ahead",
+        "yes please",
+        "yes do it",
+        "yes go ahead",
+        "ok go ahead",
+        "okay go ahead",
+    ];
+    const CANCEL: &'static [&'static str] = &[
+        "no",
+        "nope",
+        "cancel",
+        "don't",
+        "stop",
+        "never mind",
+        "no thanks",
+        "no never mind",
+        "no cancel",
+        "don't do it",
+    ];
+
+    /// The follow-up `transcript` is, after an optional wake phrase
+    pub fn parse(transcript: &str) -> Option<Self> {
+        let normalized = normalize(transcript).replace(',', " ");
+        let text = IntentParser::new().strip_wake_phrase(&normalized);
+        let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+
+        [
+            (Self::REPEAT, Self::Repeat),
+            (Self::CONFIRM, Self::Confirm),
+            (Self::CANCEL, Self::Cancel),
+        ]
+        .into_iter()
+        .find_map(|(phrases, follow_up)| phrases.contains(&text.as_str()).then_some(follow_up))
+    }
+}
+
+/// Per-user sessions, in memory or persisted to a directory
+pub struct SessionStore {
+    /// One `<user>.json` per session; `None` to keep them in memory only
+    dir: Option<PathBuf>,
+
+    ttl: Duration,
+
+    sessions: Mutex<HashMap<String, Session>>,
+
+    /// Commands the user said yes to, approved once each
+    approved: Mutex<Vec<(String, Vec<String>)>>,
+}
+
+impl SessionStore {
+    /// Sessions kept in memory, lost on restart
+    pub fn in_memory(ttl: Duration) -> Self {
+        Self {
+            dir: None,
+            ttl,
+            sessions: Mutex::new(HashMap::new()),
+            approved: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Sessions saved in `dir`, created if missing, starting from the fresh
+    /// ones already there
+    pub fn open(dir: impl AsRef<Path>, ttl: Duration) -> Result<Self, SessionError> {
+        let dir = dir.as_ref().to_path_buf();
+        std::fs::create_dir_all(&dir)?;
+
+        let mut sessions = HashMap::new();
+        for entry in std::fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+                continue;
+            }
+            let session = match read_session(&path) {
+                Ok(session) => session,
+                Err(e) => {
+                    warn!("Setting aside {}: {}", path.display(), e);
+                    if let Err(e) = std::fs::rename(&path, path.with_extension("json.invalid")) {
+                        warn!("Failed to set aside {}: {}", path.display(), e);
+                    }
+                    continue;
+                }
+            };
+            if !session.is_fresh(ttl) {
+                remove_session_file(&path);
+                continue;
+            }
+            sessions.insert(session.user.clone(), session);
+        }
+        info!("Loaded {} sessions from {}", sessions.len(), dir.display());
+
+        Ok(Self {
+            dir: Some(dir),
+            ttl,
+            sessions: Mutex::new(sessions),
+            approved: Mutex::new(Vec::new()),
+        })
+    }
+
+    /// How long after a turn follow-ups still refer to it
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// `user`'s session, unless it is stale
+    pub fn get(&self, user: &str) -> Option<Session> {
+        let sessions = self.sessions.lock().unwrap();
+        sessions
+            .get(user)
+            .filter(|session| session.is_fresh(self.ttl))
+            .cloned()
+    }
+
+    /// Change `user`'s session at the end of a turn, and save it; other
+    /// users' stale sessions are dropped
+    ///
+    /// A stale session starts over: only what `change` sets is kept.
+    pub async fn update(
+        &self,
+        user: &str,
+        change: impl FnOnce(&mut Session),
+    ) -> Result<(), SessionError> {
+        let (session, stale) = {
+            let mut sessions = self.sessions.lock().unwrap();
+            let stale: Vec<String> = sessions
+                .values()
+                .filter(|session| session.user != user && !session.is_fresh(self.ttl))
+                .map(|session| session.user.clone())
+                .collect();
+            for user in &stale {
+                sessions.remove(user);
+            }
+
+            let session = sessions
+                .entry(user.to_string())
+                .or_insert_with(|| Session::new(user));
+            if !session.is_fresh(self.ttl) {
+                *session = Session::new(user);
+            }
+            change(session);
+            session.updated_ms = now_ms();
+            (session.clone(), stale)
+        };
+
+        let Some(dir) = &self.dir else {
+            return Ok(());
+        };
+        let dir = dir.clone();
+        let user = user.to_string();
+        tokio::task::spawn_blocking(move || {
+            for user in &stale {
+                remove_session_file(&session_path(&dir, user));
+            }
+            save_session(&session_path(&dir, &user), &session)
+        })
+        .await
+        .map_err(std::io::Error::other)?
+    }
+
+    /// Let the OS executor run `command` once without asking again
+    pub fn approve(&self, command: &Command) {
+        if let Command::Os { command, args } = command {
+            let mut approved = self.approved.lock().unwrap();
+            approved.push((command.clone(), args.clone()));
+        }
+    }
+
+    /// Withdraw an approval the executor did not use
+    pub fn revoke(&self, command: &Command) {
+        if let Command::Os { command, args } = command {
+            let mut approved = self.approved.lock().unwrap();
+            approved
+                .retain(|(approved, approved_args)| approved != command || approved_args != args);
+        }
+    }
+}
+
+#[async_trait]
+impl ApprovalProvider for SessionStore {
+    async fn request_approval(&self, request: &ApprovalRequest) -> ApprovalDecision {
+        let mut approved = self.approved.lock().unwrap();
+        let position = approved
+            .iter()
+            .position(|(command, args)| *command == request.command && *args == request.args);
+        match position {
+            Some(position) => {
+                approved.remove(position);
+                ApprovalDecision::Approved
+            }
+            None => {
+                warn!("Not confirmed by the user: {}", request.prompt());
+                ApprovalDecision::Denied
+            }
+        }
+    }
+}
+
+fn read_session(path: &Path) -> Result<Session, SessionError> {
+    serde_json::from_slice(&std::fs::read(path)?).map_err(|e| SessionError::Invalid {
+        path: path.to_path_buf(),
+        reason: e.to_string(),
+    })
+}
+
+/// Write `session` beside `path` and move it over, so `path` is never left
+/// half written
+fn save_session(path: &Path, session: &Session) -> Result<(), SessionError> {
+    let json = serde_json::to_vec_pretty(session).map_err(|e| SessionError::Invalid {
+        path: path.to_path_buf(),
+        reason: e.to_string(),
+    })?;
+    let temp = path.with_extension("json.tmp");
+    std::fs::write(&temp, json)?;
+    std::fs::rename(&temp, path)?;
+    Ok(())
+}
+
+fn remove_session_file(path: &Path) {
+    match std::fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            warn!("Failed to remove stale session {}: {}", path.display(), e)
+        }
+        _ => {}
+    }
+}
+
+/// The file of `user`'s session, its name limited to safe characters
+fn session_path(dir: &Path, user: &str) -> PathBuf {
+    let name: String = user
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' | '.' => c,
+            _ => '_',
+        })
+        .collect();
+    dir.join(format!("{}.json", name.trim_start_matches('.')))
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn rm() -> Command {
+        Command::Os {
+            command: "rm".to_string(),
+            args: vec!["a.txt".to_string()],
+        }
+    }
+
+    fn is_rm(command: Option<&Command>) -> bool {
+        matches!(command, Some(Command::Os { command, .. }) if command == "rm")
+    }
+
+    fn request(command: &Command) -> ApprovalRequest {
+        let Command::Os { command, args } = command else {
+            panic!("not an OS command");
+        };
+        ApprovalRequest {
+            command: command.clone(),
+            args: args.clone(),
+            description: None,
+        }
+    }
+
+    #[test]
+    fn test_follow_ups() {
+        assert_eq!(FollowUp::parse("Do that again."), Some(FollowUp::Repeat));
+        assert_eq!(
+            FollowUp::parse("Hey Aether, repeat that"),
+            Some(FollowUp::Repeat)
+        );
+        assert_eq!(FollowUp::parse("Yes, go ahead!"), Some(FollowUp::Confirm));
+        assert_eq!(FollowUp::parse("okay"), Some(FollowUp::Confirm));
+        assert_eq!(FollowUp::parse("No, never mind"), Some(FollowUp::Cancel));
+        assert_eq!(FollowUp::parse("yes open github.com"), None);
+        assert_eq!(FollowUp::parse("run it again later"), None);
+    }
+
+    #[tokio::test]
+    async fn test_ttl() {
+        let store = SessionStore::in_memory(Duration::from_secs(60));
+        assert!(store.get("ana").is_none());
+
+        store
+            .update("ana", |session| session.last_command = Some(rm()))
+            .await
+            .unwrap();
+        assert!(is_rm(store.get("ana").unwrap().last_command.as_ref()));
+        assert!(store.get("ben").is_none());
+
+        // Gone quiet for longer than the TTL
+        store
+            .sessions
+            .lock()
+            .unwrap()
+            .get_mut("ana")
+            .unwrap()
+            .updated_ms -= 61_000;
+        assert!(store.get("ana").is_none());
+
+        // A stale session starts over
+        store
+            .update("ana", |session| {
+                session.last_transcript = Some("hello".to_string())
+            })
+            .await
+            .unwrap();
+        let session = store.get("ana").unwrap();
+        assert!(session.last_command.is_none());
+        assert_eq!(session.last_transcript.as_deref(), Some("hello"));
+    }
+
+    #[tokio::test]
+    async fn test_persistence() {
+        let dir = TempDir::new().unwrap();
+        let store = SessionStore::open(dir.path(), DEFAULT_FOLLOW_UP_TTL).unwrap();
+        store
+            .update("ana/../x", |session| {
+                session.last_command = Some(rm());
+                session.pending = Some(PendingConfirmation {
+                    command: rm(),
+                    prompt: "Do you want me to run rm a.txt?".to_string(),
+                })
+            })
+            .await
+            .unwrap();
+        assert!(dir.path().join("ana_.._x.json").exists());
+        assert!(!dir.path().join("ana_.._x.json.tmp").exists());
+
+        // The question is not saved, so "yes" after a restart runs nothing
+        let reopened = SessionStore::open(dir.path(), DEFAULT_FOLLOW_UP_TTL).unwrap();
+        let session = reopened.get("ana/../x").unwrap();
+        assert!(is_rm(session.last_command.as_ref()));
+        assert!(session.pending.is_none());
+
+        // An unreadable file is set aside rather than stopping the store
+        std::fs::write(dir.path().join("broken.json"), "{").unwrap();
+        let reopened = SessionStore::open(dir.path(), DEFAULT_FOLLOW_UP_TTL).unwrap();
+        assert!(reopened.get("ana/../x").is_some());
+        assert!(!dir.path().join("broken.json").exists());
+        assert!(dir.path().join("broken.json.invalid").exists());
+    }
+
+    #[tokio::test]
+    async fn test_stale_sessions_are_dropped() {
+        let dir = TempDir::new().unwrap();
+        let ttl = Duration::from_secs(60);
+        let store = SessionStore::open(dir.path(), ttl).unwrap();
+        store.update("ana", |_| {}).await.unwrap();
+        store.update("ben", |_| {}).await.unwrap();
+
+        // Ana's file, gone quiet for longer than the TTL, is not loaded
+        let mut session = read_session(&dir.path().join("ana.json")).unwrap();
+        session.updated_ms -= 61_000;
+        save_session(&dir.path().join("ana.json"), &session).unwrap();
+        let reopened = SessionStore::open(dir.path(), ttl).unwrap();
+        assert!(reopened.sessions.lock().unwrap().get("ana").is_none());
+        assert!(!dir.path().join("ana.json").exists());
+
+        // Ben's, gone stale since, is dropped on the next update
+        reopened
+            .sessions
+            .lock()
+            .unwrap()
+            .get_mut("ben")
+            .unwrap()
+            .updated_ms -= 61_000;
+        reopened.update("cy", |_| {}).await.unwrap();
+        assert!(reopened.sessions.lock().unwrap().get("ben").is_none());
+        assert!(!dir.path().join("ben.json").exists());
+        assert!(dir.path().join("cy.json").exists());
+    }
+
+    #[tokio::test]
+    async fn test_approval() {
+        let store = SessionStore::in_memory(DEFAULT_FOLLOW_UP_TTL);
+        let request = request(&rm());
+        assert_eq!(
+            store.request_approval(&request).await,
+            ApprovalDecision::Denied
+        );
+
+        // Once per yes
+        store.approve(&rm());
+        assert_eq!(
+            store.request_approval(&request).await,
+            ApprovalDecision::Approved
+        );
+        assert_eq!(
+            store.request_approval(&request).await,
+            ApprovalDecision::Denied
+        );
+
+        store.approve(&rm());
+        store.revoke(&rm());
+        assert_eq!(
+            store.request_approval(&request).await,
+            ApprovalDecision::Denied
+        );
+    }
+}
@@ -0,0 +1,157 @@
+//! Skills
+//!
+//! A [`Skill`] adds a capability to the agent (smart home, calendar, ...)
+//! without changing the executors or the grammar: it says which transcripts
+//! it handles, as a request of its own, and carries them out. Skills are
+//! registered in a [`SkillRegistry`] at startup and offered each transcript
+//! before the router, in the order they were registered; the first one to
+//! match gets it, as a [`Command::Skill`].
+//!
+//! ```ignore
+//! let mut executors = Executors::default();
+//! executors.skills.register(Arc::new(SmartHomeSkill::default()))?;
+//! ```
+
+use crate::intent::{normalize, IntentParser};
+use crate::router::Command;
+use std::sync::Arc;
+use tracing::info;
+
+pub use aether_skills::{Skill, SkillDescription, SkillError, SkillOutput};
+
+/// The skills the agent has, in the order they are offered transcripts
+#[derive(Clone, Default)]
+pub struct SkillRegistry {
+    skills: Vec<(String, Arc<dyn Skill>)>,
+}
+
+impl SkillRegistry {
+    /// Add `skill`, offered transcripts after those already registered
+    pub fn register(&mut self, skill: Arc<dyn Skill>) -> Result<(), SkillError> {
+        let description = skill.describe();
+        if self.get(&description.name).is_some() {
+            return Err(SkillError::Duplicate(description.name));
+        }
+        info!("Skill {}: {}", description.name, description.summary);
+        self.skills.push((description.name, skill));
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Arc<dyn Skill>> {
+        self.skills
+            .iter()
+            .find(|(registered, _)| registered == name)
+            .map(|(_, skill)| skill)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.skills.is_empty()
+    }
+
+    /// What each skill is for, in order
+    pub fn descriptions(&self) -> Vec<SkillDescription> {
+        self.skills
+            .iter()
+            .map(|(_, skill)| skill.describe())
+            .collect()
+    }
+
+    /// The command of the first skill that handles `transcript`
+    pub fn route(&self, transcript: &str) -> Option<Command> {
+        if self.skills.is_empty() {
+            return None;
+        }
+        let normalized = normalize(transcript);
+        let text = IntentParser::new().strip_wake_phrase(&normalized);
+
+        self.skills.iter().find_map(|(name, skill)| {
+            let request = skill.match_intent(text)?;
+            Some(Command::Skill {
+                skill: name.clone(),
+                request,
+            })
+        })
+    }
+
+    /// Have the skill named `name` carry out `request`
+    pub async fn execute(
+        &self,
+        name: &str,
+        request: serde_json::Value,
+    ) -> Result<SkillOutput, SkillError> {
+        let skill = self
+            .get(name)
+            .ok_or_else(|| SkillError::Unknown(name.to_string()))?;
+        skill.execute(request).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use serde_json::json;
+
+    /// Says hello to whoever is named
+    struct Greeter(&'static str);
+
+    #[async_trait]
+    impl Skill for Greeter {
+        fn describe(&self) -> SkillDescription {
+            SkillDescription {
+                name: self.0.to_string(),
+                summary: "Says hello".to_string(),
+                examples: vec!["say hello to ana".to_string()],
+            }
+        }
+
+        fn match_intent(&self, transcript: &str) -> Option<serde_json::Value> {
+            let name = transcript.strip_prefix("say hello to ")?;
+            Some(json!({ "name": name }))
+        }
+
+        async fn execute(&self, request: serde_json::Value) -> Result<SkillOutput, SkillError> {
+            let name = request["name"]
+                .as_str()
+                .ok_or_else(|| SkillError::InvalidRequest {
+                    skill: self.0.to_string(),
+                    reason: "no name".to_string(),
+                })?;
+            Ok(SkillOutput::done(format!("Hello, {}!", name)))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_registry() {
+        let mut skills = SkillRegistry::default();
+        assert!(skills.route("say hello to ana").is_none());
+
+        skills.register(Arc::new(Greeter("greeter"))).unwrap();
+        skills.register(Arc::new(Greeter("second"))).unwrap();
+        assert!(matches!(
+            skills.register(Arc::new(Greeter("greeter"))),
+            Err(SkillError::Duplicate(name)) if name == "greeter"
+        ));
+        assert_eq!(skills.descriptions().len(), 2);
+
+        // The first registered gets it
+        let Some(Command::Skill { skill, request }) = skills.route("Hey Aether, say hello to Ana.")
+        else {
+            panic!("not routed to a skill");
+        };
+        assert_eq!(skill, "greeter");
+        assert_eq!(request, json!({ "name": "ana" }));
+        assert!(skills.route("say goodbye").is_none());
+
+        let output = skills.execute(&skill, request).await.unwrap();
+        assert_eq!(output, SkillOutput::done("Hello, ana!"));
+        assert!(matches!(
+            skills.execute(&skill, json!({})).await,
+            Err(SkillError::InvalidRequest { .. })
+        ));
+        assert!(matches!(
+            skills.execute("weather", json!({})).await,
+            Err(SkillError::Unknown(_))
+        ));
+    }
+}
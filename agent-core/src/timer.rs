@@ -0,0 +1,147 @@
+//! Timers the agent keeps
+//!
+//! Each timer is a task sleeping until it fires. [`Timers`] keeps their
+//! handles, so the timers still pending can be listed and cancelled.
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+use tracing::{Instrument, Span};
+
+/// The longest timer that can be set: a day
+pub const MAX_TIMER: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// A timer that has not fired yet
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PendingTimer {
+    pub id: u64,
+    pub label: Option<String>,
+
+    /// What the timer was set for
+    pub seconds: u64,
+
+    /// Until it fires
+    pub remaining: Duration,
+}
+
+/// The pending timers
+#[derive(Clone, Default)]
+pub struct Timers {
+    inner: Arc<Mutex<Pending>>,
+}
+
+#[derive(Default)]
+struct Pending {
+    last_id: u64,
+    timers: BTreeMap<u64, Timer>,
+}
+
+struct Timer {
+    label: Option<String>,
+    seconds: u64,
+    fires_at: Instant,
+    handle: JoinHandle<()>,
+}
+
+impl Timers {
+    /// Run `fire` in `span` after `seconds`, unless cancelled first;
+    /// returns the timer's id
+    pub(crate) fn start(
+        &self,
+        seconds: u64,
+        label: Option<String>,
+        span: Span,
+        fire: impl Future<Output = ()> + Send + 'static,
+    ) -> u64 {
+        let mut pending = self.inner.lock().expect("timers lock");
+        pending.last_id += 1;
+        let id = pending.last_id;
+
+        let fires_at = Instant::now() + Duration::from_secs(seconds);
+        let timers = self.clone();
+        let timer = async move {
+            tokio::time::sleep_until(fires_at).await;
+            timers.inner.lock().expect("timers lock").timers.remove(&id);
+            fire.await;
+        };
+        let handle = tokio::spawn(timer.instrument(span));
+
+        pending.timers.insert(
+            id,
+            Timer {
+                label,
+                seconds,
+                fires_at,
+                handle,
+            },
+        );
+        id
+    }
+
+    /// The timers still pending, oldest first
+    pub fn list(&self) -> Vec<PendingTimer> {
+        let now = Instant::now();
+        let pending = self.inner.lock().expect("timers lock");
+        pending
+            .timers
+            .iter()
+            .map(|(id, timer)| PendingTimer {
+                id: *id,
+                label: timer.label.clone(),
+                seconds: timer.seconds,
+                remaining: timer.fires_at.saturating_duration_since(now),
+            })
+            .collect()
+    }
+
+    /// Cancel the timer `id`; false if it is not pending
+    pub fn cancel(&self, id: u64) -> bool {
+        let timer = self.inner.lock().expect("timers lock").timers.remove(&id);
+        match timer {
+            Some(timer) => {
+                timer.handle.abort();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[tokio::test]
+    async fn test_list_and_cancel() {
+        let timers = Timers::default();
+        let fired = Arc::new(AtomicUsize::new(0));
+        let fire = |fired: &Arc<AtomicUsize>| {
+            let fired = fired.clone();
+            async move {
+                fired.fetch_add(1, Ordering::SeqCst);
+            }
+        };
+
+        let tea = timers.start(600, Some("tea".to_string()), Span::none(), fire(&fired));
+        let pasta = timers.start(900, None, Span::none(), fire(&fired));
+        let pending = timers.list();
+        assert_eq!(pending.len(), 2);
+        assert_eq!(pending[0].id, tea);
+        assert_eq!(pending[0].label.as_deref(), Some("tea"));
+        assert!(pending[0].remaining > Duration::from_secs(590));
+
+        assert!(timers.cancel(pasta));
+        assert!(!timers.cancel(pasta));
+        assert!(timers.cancel(tea));
+
+        timers.start(0, None, Span::none(), fire(&fired));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(fired.load(Ordering::SeqCst), 1);
+        assert!(timers.list().is_empty());
+    }
+}
@@ -0,0 +1,322 @@
+//! Integration tests for agent core
+
+use aether_events::{Event, Executor};
+use aether_telemetry::TraceParent;
+use agent_core::{
+    AgentConfig, AgentCore, Command, Executors, Intent, IntentParser, KeywordRouter, Outcome,
+    SessionStore, Skill, SkillDescription, SkillError, SkillOutput, DEFAULT_FOLLOW_UP_TTL,
+};
+use async_trait::async_trait;
+use os_executor::{
+    Capability, CapabilityAuthority, CommandExecutor, CommandWhitelist, ExecutorConfig,
+    WhitelistEntry,
+};
+use std::sync::Arc;
+use std::time::Duration;
+use stt_processor::{AudioFormat, StreamingConfig, StreamingSTT, WhisperConfig, WhisperProcessor};
+use tokio::sync::mpsc;
+use tts::{ChannelSink, MockBackend, TtsEngine};
+use wakeword_detector::{DetectorConfig, WakeWordDetector};
+
+fn agent(executors: Executors) -> AgentCore {
+    let detector = WakeWordDetector::new(DetectorConfig {
+        access_key: "test_key".to_string(),
+        ..Default::default()
+    })
+    .unwrap();
+    let whisper = Arc::new(WhisperProcessor::new(WhisperConfig::default()).unwrap());
+    let stt = StreamingSTT::new(
+        whisper,
+        AudioFormat::whisper_format(),
+        StreamingConfig::default(),
+    )
+    .unwrap();
+    AgentCore::new(
+        detector,
+        stt,
+        KeywordRouter::default(),
+        executors,
+        AgentConfig::default(),
+    )
+}
+
+fn os_executors() -> Executors {
+    let config = ExecutorConfig {
+        enable_sandbox: false,
+        ..Default::default()
+    };
+    Executors {
+        os: Some(Arc::new(CommandExecutor::new(
+            config,
+            CommandWhitelist::default(),
+        ))),
+        browser: None,
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn test_os_command_turn() {
+    let agent = agent(os_executors());
+    let mut events = agent.subscribe().await.unwrap();
+
+    let turn = agent.handle_transcript("Hey Aether, run echo hello").await;
+    assert!(matches!(turn.command, Some(Command::Os { .. })));
+    match turn.outcome {
+        Some(Outcome::Os(result)) => {
+            assert!(result.success);
+            assert!(result.stdout.contains("hello"));
+        }
+        other => panic!("unexpected outcome: {:?}", other),
+    }
+
+    let planned = events.recv().await.unwrap();
+    match &planned.event {
+        Event::CommandPlanned(planned) => {
+            assert_eq!(planned.transcript, "Hey Aether, run echo hello");
+            assert_eq!(planned.executor, Executor::Os);
+        }
+        other => panic!("unexpected event: {:?}", other),
+    }
+
+    let executed = events.recv().await.unwrap();
+    match &executed.event {
+        Event::CommandExecuted(executed) => assert!(executed.success),
+        other => panic!("unexpected event: {:?}", other),
+    }
+    assert_eq!(executed.source, "agent-core");
+    assert!(executed.correlation_id.is_some());
+    assert_eq!(executed.correlation_id, planned.correlation_id);
+}
+
+#[tokio::test]
+async fn test_rejected_command() {
+    let agent = agent(os_executors());
+
+    // Not on the whitelist: routed, but refused by the executor
+    let turn = agent.handle_transcript("run rm -rf /tmp/nothing").await;
+    assert!(turn.command.is_some());
+    assert!(turn.outcome.is_none());
+    assert!(turn.error.is_some());
+}
+
+#[tokio::test]
+async fn test_capability_token() {
+    let authority = CapabilityAuthority::new("secret");
+    let config = ExecutorConfig {
+        enable_sandbox: false,
+        ..Default::default()
+    };
+    let executor = Arc::new(
+        CommandExecutor::new(config, CommandWhitelist::default())
+            .with_capability_authority(authority.clone()),
+    );
+
+    // Without a token the executor refuses
+    let mut executors = Executors {
+        os: Some(executor),
+        ..Default::default()
+    };
+    let turn = agent(executors.clone())
+        .handle_transcript("run echo hello")
+        .await;
+    assert!(turn.error.is_some());
+
+    executors.os_capability = Some(authority.issue(&Capability::new("agent", &["echo"])));
+    let agent = agent(executors);
+    let turn = agent.handle_transcript("run echo hello").await;
+    assert!(matches!(turn.outcome, Some(Outcome::Os(ref result)) if result.success));
+
+    // Outside the token's scope
+    let turn = agent.handle_transcript("run ls").await;
+    assert!(turn.error.is_some());
+}
+
+#[tokio::test]
+async fn test_confirmed_command() {
+    let sessions = Arc::new(SessionStore::in_memory(DEFAULT_FOLLOW_UP_TTL));
+    let mut whitelist = CommandWhitelist::new();
+    whitelist.add_command(
+        "echo",
+        WhitelistEntry {
+            command: "echo".to_string(),
+            max_args: Some(5),
+            requires_confirmation: true,
+            ..Default::default()
+        },
+    );
+    let config = ExecutorConfig {
+        enable_sandbox: false,
+        ..Default::default()
+    };
+    let executors = Executors {
+        os: Some(Arc::new(
+            CommandExecutor::new(config, whitelist).with_approval_provider(sessions.clone()),
+        )),
+        browser: None,
+        ..Default::default()
+    };
+    let agent = agent(executors).with_sessions(sessions);
+
+    // Asked about, not run
+    let turn = agent.handle_transcript("run echo hello").await;
+    assert_eq!(
+        turn.confirmation.as_deref(),
+        Some("Do you want me to run echo hello?")
+    );
+    assert!(turn.outcome.is_none() && turn.error.is_none());
+
+    let turn = agent.handle_transcript("Yes, go ahead.").await;
+    assert!(turn.confirmation.is_none());
+    match turn.outcome {
+        Some(Outcome::Os(result)) => assert!(result.stdout.contains("hello")),
+        other => panic!("unexpected outcome: {:?}", other),
+    }
+
+    // Approved once: doing it again asks again
+    let turn = agent.handle_transcript("do that again").await;
+    assert!(turn.confirmation.is_some());
+    let turn = agent.handle_transcript("no").await;
+    assert!(turn.command.is_none());
+    let turn = agent.handle_transcript("yes").await;
+    assert!(turn.command.is_none() && turn.outcome.is_none());
+}
+
+/// Counts how often it is asked to
+#[derive(Default)]
+struct Counter(std::sync::atomic::AtomicU64);
+
+#[async_trait]
+impl Skill for Counter {
+    fn describe(&self) -> SkillDescription {
+        SkillDescription {
+            name: "counter".to_string(),
+            summary: "Counts".to_string(),
+            examples: vec!["count".to_string()],
+        }
+    }
+
+    fn match_intent(&self, transcript: &str) -> Option<serde_json::Value> {
+        (transcript == "count").then(|| serde_json::json!({}))
+    }
+
+    async fn execute(&self, _request: serde_json::Value) -> Result<SkillOutput, SkillError> {
+        let count = self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+        Ok(SkillOutput::done(count.to_string()))
+    }
+}
+
+#[tokio::test]
+async fn test_skill_turn() {
+    let mut executors = Executors::default();
+    executors
+        .skills
+        .register(Arc::new(Counter::default()))
+        .unwrap();
+    let agent =
+        agent(executors).with_sessions(Arc::new(SessionStore::in_memory(DEFAULT_FOLLOW_UP_TTL)));
+    let mut events = agent.subscribe().await.unwrap();
+
+    let turn = agent.handle_transcript("Hey Aether, count.").await;
+    assert!(matches!(turn.command, Some(Command::Skill { ref skill, .. }) if skill == "counter"));
+
+    // Skill commands are remembered like any other
+    let turn = agent.handle_transcript("do that again").await;
+    match turn.outcome {
+        Some(Outcome::Skill { output, .. }) => assert_eq!(output.summary.as_deref(), Some("2")),
+        other => panic!("unexpected outcome: {:?}", other),
+    }
+
+    let planned = events.recv().await.unwrap();
+    match &planned.event {
+        Event::CommandPlanned(planned) => assert_eq!(planned.executor, Executor::Skill),
+        other => panic!("unexpected event: {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_timer_turn() {
+    let agent = agent(Executors::default());
+    let mut events = agent.subscribe().await.unwrap();
+
+    let turn = agent
+        .handle_transcript("Aether, set a timer for one second called tea")
+        .await;
+    assert!(matches!(
+        turn.outcome,
+        Some(Outcome::Timer { seconds: 1, .. })
+    ));
+
+    // Planned, set, then done a second later
+    let mut executed = Vec::new();
+    while executed.len() < 2 {
+        let envelope = tokio::time::timeout(Duration::from_secs(5), events.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        // The timer going off is still part of the turn's trace
+        let trace: TraceParent = envelope.traceparent.unwrap().parse().unwrap();
+        assert_eq!(trace.trace_id, turn.trace_id);
+        if let Event::CommandExecuted(event) = envelope.event {
+            assert_eq!(event.executor, Executor::Timer);
+            executed.push(event.summary.unwrap());
+        }
+    }
+    assert_eq!(
+        executed,
+        ["Timer set for 1 second.", "Timer for tea is done."]
+    );
+}
+
+#[tokio::test]
+async fn test_spoken_reply() {
+    let (speech_tx, mut speech_rx) = mpsc::channel(16);
+    let agent = agent(Executors::default()).with_tts(
+        TtsEngine::new(MockBackend::default()),
+        ChannelSink::new(speech_tx),
+    );
+
+    let turn = agent.handle_transcript("sing me a song").await;
+    assert!(turn.command.is_none());
+
+    // "Sorry, I didn't understand that.", a mock tone per word
+    let mut words = 0;
+    while let Ok(chunk) = speech_rx.try_recv() {
+        assert_eq!(chunk.sample_rate, 22050);
+        words += 1;
+    }
+    assert_eq!(words, 5);
+}
+
+#[test]
+fn test_intent_grammar() {
+    let parser = IntentParser::default().with_rule("play {song}", |slots| {
+        Some(Intent::Search {
+            query: format!("{} music video", slots.get("song")?),
+        })
+    });
+    assert_eq!(
+        parser.parse("hey aether, play never gonna give you up"),
+        Some(Intent::Search {
+            query: "never gonna give you up music video".to_string()
+        })
+    );
+    assert_eq!(
+        parser.parse("start a 90 second timer"),
+        Some(Intent::SetTimer {
+            seconds: 90,
+            label: None
+        })
+    );
+}
+
+#[tokio::test]
+async fn test_run_until_audio_ends() {
+    let agent = agent(Executors::default());
+    let (audio_tx, audio_rx) = mpsc::channel(4);
+
+    // Silence never wakes the agent
+    audio_tx.send(vec![0; 1600]).await.unwrap();
+    drop(audio_tx);
+    agent.run(audio_rx).await.unwrap();
+}
@@ -0,0 +1,211 @@
+//! Accessibility snapshots for grounding actions
+//!
+//! `AccessibilitySnapshot` lists the interactive elements of the page (or
+//! target frame) from the CDP accessibility tree: role, accessible name,
+//! value and bounding box, nested under their nearest interactive ancestor.
+//! Each is numbered by its backend DOM node id, which stays the same while
+//! the element exists, so a planner can answer with `ClickNode { node_id }`
+//! instead of writing a selector.
+
+use chromiumoxide::cdp::browser_protocol::accessibility::{AxNode, AxValue};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// Roles a user can act on
+const INTERACTIVE_ROLES: &[&str] = &[
+    "button",
+    "checkbox",
+    "combobox",
+    "link",
+    "listbox",
+    "menuitem",
+    "menuitemcheckbox",
+    "menuitemradio",
+    "option",
+    "radio",
+    "searchbox",
+    "slider",
+    "spinbutton",
+    "switch",
+    "tab",
+    "textbox",
+    "treeitem",
+];
+
+/// One interactive element, in the `data` of a snapshot as a JSON array
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AccessibleNode {
+    /// The id for `ClickNode`
+    pub id: i64,
+    pub role: String,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+    /// Viewport coordinates
+    pub bounds: Option<Bounds>,
+    /// `id` of the nearest interactive ancestor
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<i64>,
+}
+
+/// A rectangle in CSS pixels
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Bounds {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl Bounds {
+    /// The box around a CDP quad (four x, y points)
+    pub(crate) fn from_quad(points: &[f64]) -> Option<Self> {
+        if points.len() < 8 {
+            return None;
+        }
+        let xs = points.iter().step_by(2);
+        let ys = points.iter().skip(1).step_by(2);
+        let (left, right) = xs.fold((f64::MAX, f64::MIN), |(lo, hi), &x| (lo.min(x), hi.max(x)));
+        let (top, bottom) = ys.fold((f64::MAX, f64::MIN), |(lo, hi), &y| (lo.min(y), hi.max(y)));
+        Some(Self {
+            x: left,
+            y: top,
+            width: right - left,
+            height: bottom - top,
+        })
+    }
+
+    pub(crate) fn center(&self) -> (f64, f64) {
+        (self.x + self.width / 2.0, self.y + self.height / 2.0)
+    }
+}
+
+/// The interactive nodes of an accessibility tree, in tree order, without
+/// bounds
+pub(crate) fn interactive(nodes: &[AxNode]) -> Vec<AccessibleNode> {
+    let by_id: HashMap<&str, &AxNode> = nodes
+        .iter()
+        .map(|node| (node.node_id.inner().as_str(), node))
+        .collect();
+    let children: HashSet<&str> = nodes
+        .iter()
+        .flat_map(|node| node.child_ids.iter().flatten())
+        .map(|id| id.inner().as_str())
+        .collect();
+    let mut found = Vec::new();
+
+    for root in nodes
+        .iter()
+        .filter(|node| !children.contains(node.node_id.inner().as_str()))
+    {
+        visit(root, None, &by_id, &mut found);
+    }
+
+    found
+}
+
+fn visit(
+    node: &AxNode,
+    parent: Option<i64>,
+    by_id: &HashMap<&str, &AxNode>,
+    found: &mut Vec<AccessibleNode>,
+) {
+    let role = node.role.as_ref().and_then(text).unwrap_or_default();
+    let mut parent = parent;
+
+    if !node.ignored && INTERACTIVE_ROLES.contains(&role.as_str()) {
+        if let Some(id) = node.backend_dom_node_id.as_ref().map(|id| *id.inner()) {
+            found.push(AccessibleNode {
+                id,
+                role,
+                name: node.name.as_ref().and_then(text).unwrap_or_default(),
+                value: node.value.as_ref().and_then(text).filter(|v| !v.is_empty()),
+                bounds: None,
+                parent,
+            });
+            parent = Some(id);
+        }
+    }
+
+    for child in node.child_ids.iter().flatten() {
+        if let Some(child) = by_id.get(child.inner().as_str()) {
+            visit(child, parent, by_id, found);
+        }
+    }
+}
+
+/// An accessibility value as text
+fn text(value: &AxValue) -> Option<String> {
+    match value.value.as_ref()? {
+        serde_json::Value::String(text) => Some(text.clone()),
+        serde_json::Value::Null => None,
+        other => Some(other.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn node(id: &str, role: &str, name: &str, children: &[&str]) -> serde_json::Value {
+        json!({
+            "nodeId": id,
+            "ignored": false,
+            "role": { "type": "role", "value": role },
+            "name": { "type": "computedString", "value": name },
+            "childIds": children,
+            "backendDOMNodeId": id.parse::<i64>().unwrap()
+        })
+    }
+
+    #[test]
+    fn test_interactive_nodes() {
+        let mut quantity = node("4", "combobox", "Quantity", &["5"]);
+        quantity["value"] = json!({ "type": "string", "value": "1" });
+        let mut hidden = node("6", "button", "Hidden", &[]);
+        hidden["ignored"] = json!(true);
+
+        let nodes: Vec<AxNode> = serde_json::from_value(json!([
+            node("1", "RootWebArea", "Shop", &["2", "3", "4", "6"]),
+            node("2", "heading", "Lamps", &[]),
+            node("3", "link", "Desk lamp", &[]),
+            quantity,
+            node("5", "option", "1", &[]),
+            hidden
+        ]))
+        .unwrap();
+
+        let found = interactive(&nodes);
+        let summary: Vec<_> = found
+            .iter()
+            .map(|n| (n.id, n.role.as_str(), n.name.as_str(), n.parent))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (3, "link", "Desk lamp", None),
+                (4, "combobox", "Quantity", None),
+                (5, "option", "1", Some(4)),
+            ]
+        );
+        assert_eq!(found[1].value.as_deref(), Some("1"));
+    }
+
+    #[test]
+    fn test_bounds_from_quad() {
+        let bounds =
+            Bounds::from_quad(&[10.0, 20.0, 110.0, 20.0, 110.0, 60.0, 10.0, 60.0]).unwrap();
+        assert_eq!(
+            bounds,
+            Bounds {
+                x: 10.0,
+                y: 20.0,
+                width: 100.0,
+                height: 40.0
+            }
+        );
+        assert_eq!(bounds.center(), (60.0, 40.0));
+        assert!(Bounds::from_quad(&[1.0, 2.0]).is_none());
+    }
+}
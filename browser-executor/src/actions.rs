@@ -1,9 +1,42 @@
 //! Browser action primitives for web automation
 
 use chromiumoxide::element::Element;
+use chromiumoxide::js::{Evaluation, EvaluationResult};
+use chromiumoxide::layout::Point;
 use chromiumoxide::page::Page;
-use chromiumoxide::cdp::browser_protocol::page::CaptureScreenshotParams;
+use chromiumoxide::cdp::browser_protocol::accessibility::GetFullAxTreeParams;
+use chromiumoxide::cdp::browser_protocol::browser::WindowState;
+use chromiumoxide::cdp::browser_protocol::dom::{
+    BackendNodeId, DescribeNodeParams, GetContentQuadsParams, ScrollIntoViewIfNeededParams,
+    SetFileInputFilesParams,
+};
+use chromiumoxide::cdp::browser_protocol::input::{
+    DispatchMouseEventParams, DispatchMouseEventType, MouseButton,
+};
+use chromiumoxide::cdp::browser_protocol::network::{
+    ClearBrowserCookiesParams, DeleteCookiesParams, GetAllCookiesParams, GetCookiesParams,
+    SetCookiesParams,
+};
+use chromiumoxide::cdp::browser_protocol::page::{FrameId, GetFrameTreeParams};
+use chromiumoxide::cdp::js_protocol::runtime::ExecutionContextId;
+use crate::accessibility::{AccessibleNode, Bounds};
+use crate::assertion::{AssertCondition, Probe};
+use crate::console::ConsoleEntry;
+use crate::emulation::{self, Device};
+use crate::extract::ExtractSchema;
+use crate::failure::{ErrorCode, Failure};
+use crate::form::{FieldResult, FieldValue, FillOutcome, FormField};
+use crate::humanize::HumanizeConfig;
+use crate::frames::FrameTarget;
+use crate::screenshot::{ScreenshotCapturer, ScreenshotFormat, ScreenshotOptions};
+use crate::scroll::{Extent, ScrollResult};
+use crate::selector::Selector;
+use crate::storage::{self, BrowserCookie};
+use crate::tabs::TabTarget;
+use crate::wait::{UntilCondition, POLL_INTERVAL};
+use crate::window;
 use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
 use std::time::Duration;
 use thiserror::Error;
 
@@ -25,14 +58,41 @@ pub enum ActionError {
     #[error("Navigation failed: {0}")]
     NavigationFailed(String),
 
+    #[error("Navigation timed out: {0}")]
+    NavigationTimeout(String),
+
+    #[error("Network error: {0}")]
+    NetworkError(String),
+
     #[error("Browser error: {0}")]
     BrowserError(String),
+
+    #[error("Tab not found: {0}")]
+    TabNotFound(String),
+
+    #[error("Download failed: {0}")]
+    DownloadFailed(String),
+
+    #[error("Upload rejected: {0}")]
+    UploadRejected(String),
+
+    #[error("Frame not found: {0}")]
+    FrameNotFound(String),
+
+    #[error("Blocked by dialog: {0}")]
+    DialogOpen(String),
+
+    #[error("URL policy violation: {0}")]
+    PolicyViolation(String),
 }
 
 /// Result type for actions
 pub type ActionResult<T> = Result<T, ActionError>;
 
 /// Browser action types
+///
+/// With `pierce_shadow`, element actions also search open shadow roots when
+/// the page-level lookup finds nothing.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum BrowserAction {
@@ -44,54 +104,320 @@ pub enum BrowserAction {
 
     /// Click element
     Click {
-        selector: String,
+        selector: Selector,
         wait_for: Option<Duration>,
+        #[serde(default)]
+        pierce_shadow: bool,
     },
 
     /// Type text into element
     Type {
-        selector: String,
+        selector: Selector,
         text: String,
         clear_first: bool,
+        #[serde(default)]
+        pierce_shadow: bool,
+    },
+
+    /// Choose a `<select>` option by value
+    SelectOption {
+        selector: Selector,
+        value: String,
+        #[serde(default)]
+        pierce_shadow: bool,
+    },
+
+    /// Move the mouse over an element (e.g. to open a hover menu)
+    Hover {
+        selector: Selector,
+        #[serde(default)]
+        pierce_shadow: bool,
+    },
+
+    /// Drag one element onto another with the left mouse button
+    DragAndDrop {
+        source: Selector,
+        target: Selector,
+        #[serde(default)]
+        pierce_shadow: bool,
     },
 
     /// Scroll to element or position
     Scroll {
-        selector: Option<String>,
+        selector: Option<Selector>,
         x: Option<i32>,
         y: Option<i32>,
+        #[serde(default)]
+        pierce_shadow: bool,
+    },
+
+    /// Scroll to the bottom until the page stops growing, waiting
+    /// `settle_ms` after each scroll for more to load (JSON `ScrollResult`);
+    /// items are `item_selector` matches, or all elements
+    ScrollToEnd {
+        #[serde(default = "default_scroll_iterations")]
+        max_iterations: u32,
+        #[serde(default = "default_settle_ms")]
+        settle_ms: u64,
+        #[serde(default)]
+        item_selector: Option<Selector>,
+        #[serde(default)]
+        pierce_shadow: bool,
     },
 
-    /// Wait for element
+    /// Wait for element (and, with `visible`, for it to be visible)
     WaitFor {
-        selector: String,
+        selector: Selector,
         timeout: Duration,
         visible: bool,
+        #[serde(default)]
+        pierce_shadow: bool,
+    },
+
+    /// Wait for a condition, up to `timeout_ms` (defaults in `wait`)
+    WaitUntil {
+        condition: UntilCondition,
+        #[serde(default)]
+        timeout_ms: Option<u64>,
     },
 
     /// Get element text
-    GetText { selector: String },
+    GetText {
+        selector: Selector,
+        #[serde(default)]
+        pierce_shadow: bool,
+    },
 
     /// Get element attribute
     GetAttribute {
-        selector: String,
+        selector: Selector,
         attribute: String,
+        #[serde(default)]
+        pierce_shadow: bool,
     },
 
     /// Execute JavaScript
     ExecuteScript { script: String },
 
-    /// Take screenshot
-    Screenshot { full_page: bool },
+    /// Cookies sent to `urls`, or to the active page when empty (JSON array
+    /// of `BrowserCookie`)
+    GetCookies {
+        #[serde(default)]
+        urls: Vec<String>,
+    },
+
+    /// Set a cookie, for the active page unless it has a domain
+    SetCookie { cookie: BrowserCookie },
+
+    /// Delete all cookies, or those of `domain` and its subdomains; `data`
+    /// is the number deleted, when known
+    ClearCookies {
+        #[serde(default)]
+        domain: Option<String>,
+    },
+
+    /// Read `localStorage` (`sessionStorage` with `session`): the value of
+    /// `key`, or every entry as a JSON object
+    GetLocalStorage {
+        #[serde(default)]
+        key: Option<String>,
+        #[serde(default)]
+        session: bool,
+    },
+
+    /// Write `localStorage` (`sessionStorage` with `session`); a null
+    /// `value` removes `key`
+    SetLocalStorage {
+        key: String,
+        value: Option<String>,
+        #[serde(default)]
+        session: bool,
+    },
+
+    /// Take screenshot; `stitch` captures a full page a viewport at a time,
+    /// for very tall pages and lazy-loaded content
+    Screenshot {
+        full_page: bool,
+        #[serde(default)]
+        stitch: bool,
+    },
+
+    /// Take a screenshot of one element, scrolled into view (base64)
+    ScreenshotElement {
+        selector: Selector,
+        #[serde(default)]
+        format: ScreenshotFormat,
+        /// JPEG quality (0-100)
+        #[serde(default)]
+        quality: Option<u8>,
+        #[serde(default)]
+        pierce_shadow: bool,
+    },
+
+    /// Check a condition on the page; if it does not hold, `success` is
+    /// false and `data` a screenshot (base64 PNG)
+    Assert { condition: AssertCondition },
+
+    /// Show the active tab as a device preset or profile (JSON
+    /// `DeviceProfile`); reload for mobile layout changes to apply fully
+    EmulateDevice { device: Device },
+
+    /// Resize the active tab's viewport, at device pixel ratio `dpr` (the
+    /// page's current one by default); `data` is the JSON `ViewportSize`
+    SetViewport {
+        width: u32,
+        height: u32,
+        #[serde(default)]
+        dpr: Option<f64>,
+    },
+
+    /// Maximize the browser window and fit the viewport to it; `data` is the
+    /// JSON `ViewportSize`
+    Maximize,
+
+    /// Make the browser window fullscreen and fit the viewport to it; `data`
+    /// is the JSON `ViewportSize`
+    Fullscreen,
+
+    /// Fill several form fields; `data` is a JSON array of `FieldResult`
+    FillForm {
+        fields: Vec<FormField>,
+        #[serde(default)]
+        pierce_shadow: bool,
+    },
+
+    /// Extract values by schema in one pass (JSON object by schema names)
+    Extract {
+        schema: ExtractSchema,
+        #[serde(default)]
+        pierce_shadow: bool,
+    },
+
+    /// List interactive elements from the accessibility tree (JSON array of
+    /// `AccessibleNode`)
+    AccessibilitySnapshot,
+
+    /// Click the center of an element from `AccessibilitySnapshot`
+    ClickNode { node_id: i64 },
+
+    /// Set the file of an `<input type=file>` to a file in the upload roots
+    UploadFile {
+        selector: Selector,
+        path: String,
+        #[serde(default)]
+        pierce_shadow: bool,
+    },
 
-    /// Go back in history
-    GoBack,
+    /// Go back in the tab's history and wait for `wait_until`; `data` is
+    /// the URL reached
+    GoBack {
+        #[serde(default)]
+        wait_until: WaitCondition,
+    },
 
-    /// Go forward in history
-    GoForward,
+    /// Go forward in the tab's history and wait for `wait_until`; `data` is
+    /// the URL reached
+    GoForward {
+        #[serde(default)]
+        wait_until: WaitCondition,
+    },
 
     /// Reload page
     Reload,
+
+    /// Open a tab (blank unless `url` is given) and make it active
+    NewTab { url: Option<String> },
+
+    /// Make a tab active
+    SwitchTab { index_or_id: TabTarget },
+
+    /// Close a tab (the active one by default), reactivating the one used
+    /// before it
+    CloseTab { index_or_id: Option<TabTarget> },
+
+    /// List open tabs (JSON array of `TabInfo`)
+    ListTabs,
+
+    /// Target a child frame of the current frame with later actions on the
+    /// active tab
+    SwitchFrame { selector_or_index: FrameTarget },
+
+    /// Target the active tab's main frame again
+    SwitchToMainFrame,
+
+    /// Accept or dismiss the dialog left open by `DialogPolicy::Queue`
+    /// (JSON `DialogInfo`); `prompt_text` answers a prompt
+    HandleDialog {
+        accept: bool,
+        #[serde(default)]
+        prompt_text: Option<String>,
+    },
+
+    /// Download a file from an http(s) URL, or by clicking a selector, to
+    /// `save_to` in the download directory (JSON `DownloadResult`)
+    Download {
+        url_or_click_selector: String,
+        #[serde(default)]
+        save_to: Option<String>,
+    },
+
+    /// Capture the bodies of responses whose URL matches `url_pattern`, up
+    /// to `max_bytes` each; returns those captured since the last call with
+    /// the pattern (JSON array of `CapturedResponse`)
+    CaptureResponses {
+        url_pattern: String,
+        #[serde(default = "default_capture_bytes")]
+        max_bytes: usize,
+    },
+
+    /// Close the page's cookie-consent banner, if any, rejecting where
+    /// offered; `data` describes the button clicked
+    DismissConsent,
+
+    /// Save the active tab as an MHTML archive at `path` in the download
+    /// directory (JSON `ArchiveResult`)
+    SavePageArchive { path: String },
+}
+
+impl BrowserAction {
+    /// Whether the action manages tabs, or a tab's target frame, rather than
+    /// acting on the active page
+    pub fn is_tab_action(&self) -> bool {
+        matches!(
+            self,
+            BrowserAction::NewTab { .. }
+                | BrowserAction::SwitchTab { .. }
+                | BrowserAction::CloseTab { .. }
+                | BrowserAction::ListTabs
+                | BrowserAction::SwitchFrame { .. }
+                | BrowserAction::SwitchToMainFrame
+        )
+    }
+
+    /// Whether the action loads a document: `Navigate`, `Reload`, `GoBack`,
+    /// `GoForward`, or `NewTab` with a URL
+    pub fn is_navigation(&self) -> bool {
+        matches!(
+            self,
+            BrowserAction::Navigate { .. }
+                | BrowserAction::Reload
+                | BrowserAction::GoBack { .. }
+                | BrowserAction::GoForward { .. }
+                | BrowserAction::NewTab { url: Some(_) }
+        )
+    }
+}
+
+fn default_capture_bytes() -> usize {
+    1024 * 1024
+}
+
+fn default_scroll_iterations() -> u32 {
+    20
+}
+
+fn default_settle_ms() -> u64 {
+    1000
 }
 
 /// Page load wait conditions
@@ -105,7 +431,7 @@ pub enum WaitCondition {
     /// Wait for DOMContentLoaded
     DomContentLoaded,
 
-    /// Wait for no network activity
+    /// Wait for load, then for no requests in flight for the idle time
     NetworkIdle,
 
     /// Don't wait
@@ -119,12 +445,41 @@ pub struct ActionOutput {
     pub data: Option<String>,
     pub error: Option<String>,
     pub duration_ms: u64,
+
+    /// Console entries logged while the action ran, with
+    /// `ExecutorConfig::collect_console`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub console: Vec<ConsoleEntry>,
+
+    /// What went wrong, when `success` is false
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failure: Option<Failure>,
+}
+
+impl ActionOutput {
+    /// A successful action returning `data`, which took `duration`
+    pub fn ok(data: Option<String>, duration: Duration) -> Self {
+        Self {
+            success: true,
+            data,
+            error: None,
+            duration_ms: duration.as_millis() as u64,
+            console: Vec::new(),
+            failure: None,
+        }
+    }
 }
 
 /// Browser action executor
 pub struct ActionExecutor {
     page: Page,
     default_timeout: Duration,
+    navigation_timeout: Duration,
+    network_idle: Duration,
+    upload_roots: Vec<PathBuf>,
+    /// Frame that element and script actions target (main frame if `None`)
+    frame: Option<String>,
+    humanize: Option<HumanizeConfig>,
 }
 
 impl ActionExecutor {
@@ -133,181 +488,417 @@ impl ActionExecutor {
         Self {
             page,
             default_timeout,
+            navigation_timeout: Duration::from_secs(30),
+            network_idle: Duration::from_millis(500),
+            upload_roots: Vec::new(),
+            frame: None,
+            humanize: None,
         }
     }
 
+    /// Time limit for a navigation, and how long the network must be quiet
+    /// for `WaitCondition::NetworkIdle`
+    pub fn with_navigation(mut self, timeout: Duration, network_idle: Duration) -> Self {
+        self.navigation_timeout = timeout;
+        self.network_idle = network_idle;
+        self
+    }
+
+    /// Allow `UploadFile` for files inside these directories
+    pub fn with_upload_roots(mut self, roots: Vec<PathBuf>) -> Self {
+        self.upload_roots = roots;
+        self
+    }
+
+    /// Target element and script actions at a frame (see `SwitchFrame`)
+    pub fn with_frame(mut self, frame: Option<String>) -> Self {
+        self.frame = frame;
+        self
+    }
+
+    /// Pace typing and mouse movement like a person (see `humanize`)
+    pub fn with_humanize(mut self, humanize: Option<HumanizeConfig>) -> Self {
+        self.humanize = humanize;
+        self
+    }
+
     /// Execute a browser action
     pub async fn execute(&mut self, action: BrowserAction) -> ActionResult<ActionOutput> {
+        if let Some(humanize) = &self.humanize {
+            tokio::time::sleep(humanize.pause()).await;
+        }
+
         let start = std::time::Instant::now();
 
         let result = match action {
             BrowserAction::Navigate { url, wait_until } => {
                 self.navigate(&url, wait_until).await?;
-                ActionOutput {
-                    success: true,
-                    data: Some(url),
-                    error: None,
-                    duration_ms: start.elapsed().as_millis() as u64,
-                }
+                ActionOutput::ok(Some(url), start.elapsed())
             }
 
-            BrowserAction::Click { selector, wait_for } => {
-                self.click(&selector, wait_for).await?;
-                ActionOutput {
-                    success: true,
-                    data: None,
-                    error: None,
-                    duration_ms: start.elapsed().as_millis() as u64,
-                }
+            BrowserAction::Click {
+                selector,
+                wait_for,
+                pierce_shadow,
+            } => {
+                self.click(&selector, wait_for, pierce_shadow).await?;
+                ActionOutput::ok(None, start.elapsed())
             }
 
             BrowserAction::Type {
                 selector,
                 text,
                 clear_first,
+                pierce_shadow,
             } => {
-                self.type_text(&selector, &text, clear_first).await?;
-                ActionOutput {
-                    success: true,
-                    data: None,
-                    error: None,
-                    duration_ms: start.elapsed().as_millis() as u64,
-                }
+                self.type_text(&selector, &text, clear_first, pierce_shadow)
+                    .await?;
+                ActionOutput::ok(None, start.elapsed())
             }
 
-            BrowserAction::Scroll { selector, x, y } => {
-                self.scroll(selector.as_deref(), x, y).await?;
-                ActionOutput {
-                    success: true,
-                    data: None,
-                    error: None,
-                    duration_ms: start.elapsed().as_millis() as u64,
-                }
+            BrowserAction::SelectOption {
+                selector,
+                value,
+                pierce_shadow,
+            } => {
+                self.select_option(&selector, &value, pierce_shadow).await?;
+                ActionOutput::ok(Some(value), start.elapsed())
+            }
+
+            BrowserAction::Hover {
+                selector,
+                pierce_shadow,
+            } => {
+                self.hover(&selector, pierce_shadow).await?;
+                ActionOutput::ok(None, start.elapsed())
+            }
+
+            BrowserAction::DragAndDrop {
+                source,
+                target,
+                pierce_shadow,
+            } => {
+                self.drag_and_drop(&source, &target, pierce_shadow).await?;
+                ActionOutput::ok(None, start.elapsed())
+            }
+
+            BrowserAction::Scroll {
+                selector,
+                x,
+                y,
+                pierce_shadow,
+            } => {
+                self.scroll(selector.as_ref(), x, y, pierce_shadow).await?;
+                ActionOutput::ok(None, start.elapsed())
+            }
+
+            BrowserAction::ScrollToEnd {
+                max_iterations,
+                settle_ms,
+                item_selector,
+                pierce_shadow,
+            } => {
+                let settle = Duration::from_millis(settle_ms);
+                let result = self
+                    .scroll_to_end(max_iterations, settle, item_selector.as_ref(), pierce_shadow)
+                    .await?;
+                ActionOutput::ok(
+                    Some(
+                        serde_json::to_string(&result)
+                            .map_err(|e| ActionError::ActionFailed(e.to_string()))?,
+                    ),
+                    start.elapsed(),
+                )
             }
 
             BrowserAction::WaitFor {
                 selector,
                 timeout,
                 visible,
+                pierce_shadow,
             } => {
-                self.wait_for(&selector, timeout, visible).await?;
-                ActionOutput {
-                    success: true,
-                    data: None,
-                    error: None,
-                    duration_ms: start.elapsed().as_millis() as u64,
-                }
+                self.wait_for(&selector, timeout, visible, pierce_shadow)
+                    .await?;
+                ActionOutput::ok(None, start.elapsed())
             }
 
-            BrowserAction::GetText { selector } => {
-                let text = self.get_text(&selector).await?;
-                ActionOutput {
-                    success: true,
-                    data: Some(text),
-                    error: None,
-                    duration_ms: start.elapsed().as_millis() as u64,
-                }
+            BrowserAction::WaitUntil {
+                condition,
+                timeout_ms,
+            } => {
+                self.wait_until(&condition, timeout_ms.map(Duration::from_millis))
+                    .await?;
+                ActionOutput::ok(None, start.elapsed())
+            }
+
+            BrowserAction::GetText {
+                selector,
+                pierce_shadow,
+            } => {
+                let text = self.get_text(&selector, pierce_shadow).await?;
+                ActionOutput::ok(Some(text), start.elapsed())
             }
 
             BrowserAction::GetAttribute {
                 selector,
                 attribute,
+                pierce_shadow,
             } => {
-                let value = self.get_attribute(&selector, &attribute).await?;
-                ActionOutput {
-                    success: true,
-                    data: Some(value),
-                    error: None,
-                    duration_ms: start.elapsed().as_millis() as u64,
-                }
+                let value = self
+                    .get_attribute(&selector, &attribute, pierce_shadow)
+                    .await?;
+                ActionOutput::ok(Some(value), start.elapsed())
             }
 
             BrowserAction::ExecuteScript { script } => {
                 let result = self.execute_script(&script).await?;
-                ActionOutput {
-                    success: true,
-                    data: Some(result),
-                    error: None,
-                    duration_ms: start.elapsed().as_millis() as u64,
-                }
+                ActionOutput::ok(Some(result), start.elapsed())
             }
 
-            BrowserAction::Screenshot { full_page } => {
-                let screenshot = self.screenshot(full_page).await?;
-                ActionOutput {
-                    success: true,
-                    data: Some(screenshot),
-                    error: None,
-                    duration_ms: start.elapsed().as_millis() as u64,
-                }
+            BrowserAction::DismissConsent => {
+                let button = crate::consent::dismiss(&self.page).await?;
+                ActionOutput::ok(button, start.elapsed())
             }
 
-            BrowserAction::GoBack => {
-                // Note: go_back not directly supported in chromiumoxide 0.5
-                // Use JS history.back() instead
-                self.execute_script("history.back()").await?;
-                tokio::time::sleep(Duration::from_millis(500)).await;
-                ActionOutput {
-                    success: true,
-                    data: None,
-                    error: None,
-                    duration_ms: start.elapsed().as_millis() as u64,
-                }
+            BrowserAction::GetCookies { urls } => {
+                let cookies = self.get_cookies(urls).await?;
+                ActionOutput::ok(
+                    Some(
+                        serde_json::to_string(&cookies)
+                            .map_err(|e| ActionError::ActionFailed(e.to_string()))?,
+                    ),
+                    start.elapsed(),
+                )
+            }
+
+            BrowserAction::SetCookie { cookie } => {
+                self.set_cookie(&cookie).await?;
+                ActionOutput::ok(None, start.elapsed())
+            }
+
+            BrowserAction::ClearCookies { domain } => {
+                let cleared = self.clear_cookies(domain.as_deref()).await?;
+                ActionOutput::ok(cleared.map(|count| count.to_string()), start.elapsed())
+            }
+
+            BrowserAction::GetLocalStorage { key, session } => {
+                let script = storage::get_script(key.as_deref(), session);
+                let result = self.execute_script(&script).await?;
+                ActionOutput::ok(Some(result), start.elapsed())
+            }
+
+            BrowserAction::SetLocalStorage {
+                key,
+                value,
+                session,
+            } => {
+                let script = storage::set_script(&key, value.as_deref(), session);
+                self.evaluate(script.as_str()).await?;
+                ActionOutput::ok(None, start.elapsed())
+            }
+
+            BrowserAction::Screenshot { full_page, stitch } => {
+                let screenshot = self.screenshot(full_page, stitch).await?;
+                ActionOutput::ok(Some(screenshot), start.elapsed())
             }
 
-            BrowserAction::GoForward => {
-                // Note: go_forward not directly supported in chromiumoxide 0.5
-                // Use JS history.forward() instead
-                self.execute_script("history.forward()").await?;
-                tokio::time::sleep(Duration::from_millis(500)).await;
+            BrowserAction::ScreenshotElement {
+                selector,
+                format,
+                quality,
+                pierce_shadow,
+            } => {
+                let element = self
+                    .find_element(&selector, pierce_shadow, self.default_timeout)
+                    .await?;
+                let options = ScreenshotOptions {
+                    format,
+                    quality,
+                    ..Default::default()
+                };
+                let screenshot = ScreenshotCapturer::capture_found(&self.page, &element, options)
+                    .await
+                    .map_err(|e| ActionError::ActionFailed(e.to_string()))?;
+                ActionOutput::ok(Some(screenshot.to_base64()), start.elapsed())
+            }
+
+            BrowserAction::Assert { condition } => match self.assert(&condition).await? {
+                Ok(()) => ActionOutput::ok(None, start.elapsed()),
+                Err(message) => ActionOutput {
+                    success: false,
+                    error: Some(format!("Assertion failed: {}", message)),
+                    failure: Some(Failure::new(ErrorCode::AssertionFailed)),
+                    ..ActionOutput::ok(self.screenshot(false, false).await.ok(), start.elapsed())
+                },
+            },
+
+            BrowserAction::EmulateDevice { device } => {
+                emulation::emulate(&self.page, &device)
+                    .await
+                    .map_err(|e| ActionError::BrowserError(e.to_string()))?;
+                ActionOutput::ok(
+                    Some(
+                        serde_json::to_string(&device.profile())
+                            .map_err(|e| ActionError::ActionFailed(e.to_string()))?,
+                    ),
+                    start.elapsed(),
+                )
+            }
+
+            BrowserAction::SetViewport { width, height, dpr } => {
+                let size = window::set_viewport(&self.page, width, height, dpr).await?;
+                ActionOutput::ok(
+                    Some(
+                        serde_json::to_string(&size)
+                            .map_err(|e| ActionError::ActionFailed(e.to_string()))?,
+                    ),
+                    start.elapsed(),
+                )
+            }
+
+            action @ (BrowserAction::Maximize | BrowserAction::Fullscreen) => {
+                let state = match action {
+                    BrowserAction::Fullscreen => WindowState::Fullscreen,
+                    _ => WindowState::Maximized,
+                };
+                let size = window::set_window_state(&self.page, state).await?;
+                ActionOutput::ok(
+                    Some(
+                        serde_json::to_string(&size)
+                            .map_err(|e| ActionError::ActionFailed(e.to_string()))?,
+                    ),
+                    start.elapsed(),
+                )
+            }
+
+            BrowserAction::FillForm {
+                fields,
+                pierce_shadow,
+            } => {
+                let results = self.fill_form(&fields, pierce_shadow).await?;
+                let failed = results.iter().filter(|r| !r.success).count();
+                let data = serde_json::to_string(&results)
+                    .map_err(|e| ActionError::ActionFailed(e.to_string()))?;
                 ActionOutput {
-                    success: true,
-                    data: None,
-                    error: None,
-                    duration_ms: start.elapsed().as_millis() as u64,
+                    success: failed == 0,
+                    error: (failed > 0)
+                        .then(|| format!("{} of {} fields failed", failed, results.len())),
+                    ..ActionOutput::ok(Some(data), start.elapsed())
                 }
             }
 
+            BrowserAction::Extract {
+                schema,
+                pierce_shadow,
+            } => {
+                let data = self.extract(&schema, pierce_shadow).await?;
+                ActionOutput::ok(Some(data), start.elapsed())
+            }
+
+            BrowserAction::AccessibilitySnapshot => {
+                let nodes = self.accessibility_snapshot().await?;
+                ActionOutput::ok(
+                    Some(
+                        serde_json::to_string(&nodes)
+                            .map_err(|e| ActionError::ActionFailed(e.to_string()))?,
+                    ),
+                    start.elapsed(),
+                )
+            }
+
+            BrowserAction::ClickNode { node_id } => {
+                self.click_node(node_id).await?;
+                ActionOutput::ok(None, start.elapsed())
+            }
+
+            BrowserAction::UploadFile {
+                selector,
+                path,
+                pierce_shadow,
+            } => {
+                let file = self.upload_file(&selector, &path, pierce_shadow).await?;
+                ActionOutput::ok(Some(file), start.elapsed())
+            }
+
+            BrowserAction::GoBack { wait_until } => {
+                let url = self.go(-1, wait_until).await?;
+                ActionOutput::ok(Some(url), start.elapsed())
+            }
+
+            BrowserAction::GoForward { wait_until } => {
+                let url = self.go(1, wait_until).await?;
+                ActionOutput::ok(Some(url), start.elapsed())
+            }
+
             BrowserAction::Reload => {
                 self.reload().await?;
-                ActionOutput {
-                    success: true,
-                    data: None,
-                    error: None,
-                    duration_ms: start.elapsed().as_millis() as u64,
-                }
+                ActionOutput::ok(None, start.elapsed())
+            }
+
+            // A single page has no tabs to manage
+            action @ (BrowserAction::NewTab { .. }
+            | BrowserAction::SwitchTab { .. }
+            | BrowserAction::CloseTab { .. }
+            | BrowserAction::ListTabs
+            | BrowserAction::SwitchFrame { .. }
+            | BrowserAction::SwitchToMainFrame) => {
+                return Err(ActionError::ActionFailed(format!(
+                    "{:?} must run through BrowserExecutor or a pooled session",
+                    action
+                )));
+            }
+
+            // Downloads and archives need the download directory, and dialogs
+            // and captures the tab's watchers
+            action @ (BrowserAction::Download { .. }
+            | BrowserAction::SavePageArchive { .. }
+            | BrowserAction::HandleDialog { .. }
+            | BrowserAction::CaptureResponses { .. }) => {
+                return Err(ActionError::ActionFailed(format!(
+                    "{:?} must run through BrowserExecutor or a pooled session",
+                    action
+                )));
             }
         };
 
         Ok(result)
     }
 
-    /// Navigate to URL
-    async fn navigate(&mut self, url: &str, _wait_until: WaitCondition) -> ActionResult<()> {
-        self.page
-            .goto(url)
-            .await
-            .map_err(|e| ActionError::NavigationFailed(e.to_string()))?;
-
-        // Wait for page load
-        tokio::time::sleep(Duration::from_millis(500)).await;
-
-        Ok(())
+    /// Navigate to URL and wait for `wait_until`
+    async fn navigate(&mut self, url: &str, wait_until: WaitCondition) -> ActionResult<()> {
+        crate::navigation::navigate(
+            &self.page,
+            url,
+            wait_until,
+            self.navigation_timeout,
+            self.network_idle,
+        )
+        .await
     }
 
     /// Click element
-    async fn click(&mut self, selector: &str, wait_for: Option<Duration>) -> ActionResult<()> {
+    async fn click(
+        &mut self,
+        selector: &Selector,
+        wait_for: Option<Duration>,
+        pierce_shadow: bool,
+    ) -> ActionResult<()> {
         let timeout = wait_for.unwrap_or(self.default_timeout);
 
-        let element = self.find_element(selector, timeout).await?;
+        let element = self.find_element(selector, pierce_shadow, timeout).await?;
 
-        element
-            .click()
-            .await
-            .map_err(|e| ActionError::ActionFailed(e.to_string()))?;
-
-        // Small delay after click
-        tokio::time::sleep(Duration::from_millis(100)).await;
+        match &self.humanize {
+            Some(humanize) => {
+                let point = self.approach(&element, humanize).await?;
+                self.press(point, humanize).await?;
+            }
+            None => {
+                element
+                    .click()
+                    .await
+                    .map_err(|e| ActionError::ActionFailed(e.to_string()))?;
+            }
+        }
 
         Ok(())
     }
@@ -315,11 +906,14 @@ impl ActionExecutor {
     /// Type text into element
     async fn type_text(
         &mut self,
-        selector: &str,
+        selector: &Selector,
         text: &str,
         clear_first: bool,
+        pierce_shadow: bool,
     ) -> ActionResult<()> {
-        let element = self.find_element(selector, self.default_timeout).await?;
+        let element = self
+            .find_element(selector, pierce_shadow, self.default_timeout)
+            .await?;
 
         if clear_first {
             // Clear existing text
@@ -341,8 +935,181 @@ impl ActionExecutor {
         }
 
         // Type text
+        match &self.humanize {
+            Some(humanize) => {
+                let mut buffer = [0; 4];
+                for c in text.chars() {
+                    element
+                        .type_str(c.encode_utf8(&mut buffer))
+                        .await
+                        .map_err(|e| ActionError::ActionFailed(e.to_string()))?;
+                    tokio::time::sleep(humanize.keystroke()).await;
+                }
+            }
+            None => {
+                element
+                    .type_str(text)
+                    .await
+                    .map_err(|e| ActionError::ActionFailed(e.to_string()))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Choose a select option by value, firing `input` and `change`
+    async fn select_option(
+        &mut self,
+        selector: &Selector,
+        value: &str,
+        pierce_shadow: bool,
+    ) -> ActionResult<()> {
+        // Wait for the select like other element actions
+        self.find_element(selector, pierce_shadow, self.default_timeout)
+            .await?;
+
+        let field = FormField {
+            selector: selector.clone(),
+            value: FieldValue::Select {
+                value: value.to_string(),
+                by_label: false,
+            },
+        };
+        let outcome: FillOutcome = self
+            .evaluate(field.script(pierce_shadow))
+            .await?
+            .into_value()
+            .map_err(|e| ActionError::BrowserError(e.to_string()))?;
+
+        match outcome.error {
+            Some(error) => Err(ActionError::ActionFailed(format!(
+                "{}: {}",
+                selector, error
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Move the mouse over the center of an element
+    async fn hover(&mut self, selector: &Selector, pierce_shadow: bool) -> ActionResult<()> {
+        let element = self
+            .find_element(selector, pierce_shadow, self.default_timeout)
+            .await?;
+
+        match &self.humanize {
+            Some(humanize) => {
+                self.approach(&element, humanize).await?;
+            }
+            None => {
+                element
+                    .hover()
+                    .await
+                    .map_err(|e| ActionError::ActionFailed(e.to_string()))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Scroll an element into view and move the mouse to it along a
+    /// human-like path; returns where the mouse ended up
+    async fn approach(&self, element: &Element, humanize: &HumanizeConfig) -> ActionResult<Point> {
         element
-            .type_str(text)
+            .scroll_into_view()
+            .await
+            .map_err(|e| ActionError::ActionFailed(e.to_string()))?;
+        let center = element
+            .clickable_point()
+            .await
+            .map_err(|e| ActionError::ActionFailed(e.to_string()))?;
+
+        self.move_along(humanize.mouse_path(center), humanize).await
+    }
+
+    /// Move the mouse through `path`, pausing between moves; returns its end
+    async fn move_along(&self, path: Vec<Point>, humanize: &HumanizeConfig) -> ActionResult<Point> {
+        let mut end = Point { x: 0.0, y: 0.0 };
+        for point in path {
+            self.mouse(DispatchMouseEventType::MouseMoved, point, false).await?;
+            tokio::time::sleep(humanize.mouse_move()).await;
+            end = point;
+        }
+        Ok(end)
+    }
+
+    /// Press and release the left button at `point`, holding it briefly
+    async fn press(&self, point: Point, humanize: &HumanizeConfig) -> ActionResult<()> {
+        self.mouse(DispatchMouseEventType::MousePressed, point, true).await?;
+        tokio::time::sleep(humanize.mouse_move() * 6).await;
+        self.mouse(DispatchMouseEventType::MouseReleased, point, true).await
+    }
+
+    /// Press on `source`, move to `target` in steps, and release there
+    async fn drag_and_drop(
+        &mut self,
+        source: &Selector,
+        target: &Selector,
+        pierce_shadow: bool,
+    ) -> ActionResult<()> {
+        const STEPS: u32 = 10;
+
+        let source = self
+            .find_element(source, pierce_shadow, self.default_timeout)
+            .await?;
+        let target = self
+            .find_element(target, pierce_shadow, self.default_timeout)
+            .await?;
+
+        source
+            .scroll_into_view()
+            .await
+            .map_err(|e| ActionError::ActionFailed(e.to_string()))?;
+        let from = source
+            .clickable_point()
+            .await
+            .map_err(|e| ActionError::ActionFailed(e.to_string()))?;
+        let to = target
+            .clickable_point()
+            .await
+            .map_err(|e| ActionError::ActionFailed(e.to_string()))?;
+
+        self.mouse(DispatchMouseEventType::MouseMoved, from, false).await?;
+        self.mouse(DispatchMouseEventType::MousePressed, from, true).await?;
+
+        // Drag libraries often ignore a single jump to the drop point
+        for step in 1..=STEPS {
+            let fraction = f64::from(step) / f64::from(STEPS);
+            let point = Point {
+                x: from.x + (to.x - from.x) * fraction,
+                y: from.y + (to.y - from.y) * fraction,
+            };
+            self.mouse(DispatchMouseEventType::MouseMoved, point, true).await?;
+        }
+
+        self.mouse(DispatchMouseEventType::MouseReleased, to, true).await
+    }
+
+    /// Dispatch one mouse event, with the left button held if `pressed`
+    async fn mouse(
+        &self,
+        kind: DispatchMouseEventType,
+        point: Point,
+        pressed: bool,
+    ) -> ActionResult<()> {
+        let mut params = DispatchMouseEventParams::new(kind.clone(), point.x, point.y);
+        if pressed {
+            params.button = Some(MouseButton::Left);
+            // Held buttons as a bitmask; none once released
+            params.buttons = Some(if kind == DispatchMouseEventType::MouseReleased {
+                0
+            } else {
+                1
+            });
+            params.click_count = Some(1);
+        }
+
+        self.page
+            .execute(params)
             .await
             .map_err(|e| ActionError::ActionFailed(e.to_string()))?;
 
@@ -352,13 +1119,16 @@ impl ActionExecutor {
     /// Scroll to element or position
     async fn scroll(
         &mut self,
-        selector: Option<&str>,
+        selector: Option<&Selector>,
         x: Option<i32>,
         y: Option<i32>,
+        pierce_shadow: bool,
     ) -> ActionResult<()> {
         if let Some(sel) = selector {
             // Scroll to element
-            let element = self.find_element(sel, self.default_timeout).await?;
+            let element = self
+                .find_element(sel, pierce_shadow, self.default_timeout)
+                .await?;
 
             element
                 .scroll_into_view()
@@ -371,10 +1141,7 @@ impl ActionExecutor {
 
             let script = format!("window.scrollTo({}, {});", x_pos, y_pos);
 
-            self.page
-                .evaluate(script.as_str())
-                .await
-                .map_err(|e| ActionError::BrowserError(e.to_string()))?;
+            self.evaluate(script).await?;
         }
 
         // Wait for scroll to complete
@@ -383,20 +1150,113 @@ impl ActionExecutor {
         Ok(())
     }
 
-    /// Wait for element to appear
+    /// Scroll to the bottom until the page stops growing or `max_iterations`
+    /// scrolls have been made
+    async fn scroll_to_end(
+        &mut self,
+        max_iterations: u32,
+        settle: Duration,
+        items: Option<&Selector>,
+        pierce_shadow: bool,
+    ) -> ActionResult<ScrollResult> {
+        let probe = crate::scroll::probe_script(items, pierce_shadow);
+        let start: Extent = self
+            .evaluate(probe.as_str())
+            .await?
+            .into_value()
+            .map_err(|e| ActionError::BrowserError(e.to_string()))?;
+
+        let mut last = start;
+        let mut iterations = 0;
+        let mut reached_end = false;
+        while iterations < max_iterations {
+            self.evaluate(crate::scroll::SCROLL).await?;
+            tokio::time::sleep(settle).await;
+            iterations += 1;
+
+            let now: Extent = self
+                .evaluate(probe.as_str())
+                .await?
+                .into_value()
+                .map_err(|e| ActionError::BrowserError(e.to_string()))?;
+            let grew = now.grew_since(&last);
+            last = now;
+            if !grew {
+                reached_end = true;
+                break;
+            }
+        }
+
+        Ok(ScrollResult::new(start, last, iterations, reached_end))
+    }
+
+    /// Wait for element to appear, and to be visible if `visible`
     async fn wait_for(
         &mut self,
-        selector: &str,
+        selector: &Selector,
         timeout: Duration,
-        _visible: bool,
+        visible: bool,
+        pierce_shadow: bool,
     ) -> ActionResult<()> {
-        self.find_element(selector, timeout).await?;
+        if visible {
+            let condition = UntilCondition::ElementVisible {
+                selector: selector.clone(),
+                pierce_shadow,
+            };
+            return self.wait_until(&condition, Some(timeout)).await;
+        }
+        self.find_element(selector, pierce_shadow, timeout).await?;
         Ok(())
     }
 
+    /// Wait for `condition`, up to `timeout` or its default
+    async fn wait_until(
+        &mut self,
+        condition: &UntilCondition,
+        timeout: Option<Duration>,
+    ) -> ActionResult<()> {
+        if let UntilCondition::NetworkIdle { idle_ms } = condition {
+            let idle = idle_ms.map_or(self.network_idle, Duration::from_millis);
+            let timeout = timeout.unwrap_or(self.navigation_timeout);
+            return crate::navigation::network_idle(&self.page, idle, timeout).await;
+        }
+
+        let timeout = timeout.unwrap_or(self.default_timeout);
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            let holds = match (condition.assertion(), condition.script()) {
+                (Some((assertion, holds)), _) => self.assert(&assertion).await?.is_ok() == holds,
+                (None, Some(script)) => self
+                    .evaluate(script.as_str())
+                    .await?
+                    .into_value::<bool>()
+                    .map_err(|e| ActionError::BrowserError(e.to_string()))?,
+                (None, None) => {
+                    return Err(ActionError::ActionFailed(format!(
+                        "{:?} must run through BrowserExecutor or a pooled session",
+                        condition
+                    )))
+                }
+            };
+            if holds {
+                return Ok(());
+            }
+            if tokio::time::Instant::now() >= deadline {
+                return Err(ActionError::Timeout(format!(
+                    "{:?} not met within {}ms",
+                    condition,
+                    timeout.as_millis()
+                )));
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+
     /// Get element text content
-    async fn get_text(&mut self, selector: &str) -> ActionResult<String> {
-        let element = self.find_element(selector, self.default_timeout).await?;
+    async fn get_text(&mut self, selector: &Selector, pierce_shadow: bool) -> ActionResult<String> {
+        let element = self
+            .find_element(selector, pierce_shadow, self.default_timeout)
+            .await?;
 
         let text = element
             .inner_text()
@@ -408,8 +1268,15 @@ impl ActionExecutor {
     }
 
     /// Get element attribute value
-    async fn get_attribute(&mut self, selector: &str, attribute: &str) -> ActionResult<String> {
-        let element = self.find_element(selector, self.default_timeout).await?;
+    async fn get_attribute(
+        &mut self,
+        selector: &Selector,
+        attribute: &str,
+        pierce_shadow: bool,
+    ) -> ActionResult<String> {
+        let element = self
+            .find_element(selector, pierce_shadow, self.default_timeout)
+            .await?;
 
         let value = element
             .attribute(attribute)
@@ -422,11 +1289,7 @@ impl ActionExecutor {
 
     /// Execute JavaScript code
     async fn execute_script(&mut self, script: &str) -> ActionResult<String> {
-        let result = self
-            .page
-            .evaluate(script)
-            .await
-            .map_err(|e| ActionError::BrowserError(e.to_string()))?;
+        let result = self.evaluate(script).await?;
 
         let json: serde_json::Value = result
             .into_value()
@@ -435,46 +1298,287 @@ impl ActionExecutor {
         Ok(serde_json::to_string(&json).unwrap_or_default())
     }
 
-    /// Take screenshot (returns base64)
-    async fn screenshot(&mut self, full_page: bool) -> ActionResult<String> {
-        use base64::{Engine as _, engine::general_purpose};
+    /// Cookies sent to `urls`, or to the active page
+    async fn get_cookies(&self, urls: Vec<String>) -> ActionResult<Vec<BrowserCookie>> {
+        let mut params = GetCookiesParams::default();
+        if !urls.is_empty() {
+            params.urls = Some(urls);
+        }
+        let cookies = self
+            .page
+            .execute(params)
+            .await
+            .map_err(|e| ActionError::BrowserError(e.to_string()))?
+            .result
+            .cookies;
+
+        Ok(cookies.iter().map(BrowserCookie::from).collect())
+    }
+
+    async fn set_cookie(&self, cookie: &BrowserCookie) -> ActionResult<()> {
+        let page_url = self.page.url().await.ok().flatten();
+        let param = cookie.param(page_url).map_err(ActionError::ActionFailed)?;
+        self.page
+            .execute(SetCookiesParams::new(vec![param]))
+            .await
+            .map_err(|e| ActionError::BrowserError(e.to_string()))?;
+
+        Ok(())
+    }
 
-        let mut params = CaptureScreenshotParams::builder().build();
+    /// Delete all cookies, or `domain`'s; returns how many of `domain`'s
+    async fn clear_cookies(&self, domain: Option<&str>) -> ActionResult<Option<usize>> {
+        let cdp = |e: chromiumoxide::error::CdpError| ActionError::BrowserError(e.to_string());
+        let Some(domain) = domain else {
+            self.page
+                .execute(ClearBrowserCookiesParams::default())
+                .await
+                .map_err(cdp)?;
+            return Ok(None);
+        };
 
-        if full_page {
-            params.capture_beyond_viewport = Some(true);
+        let cookies = self
+            .page
+            .execute(GetAllCookiesParams::default())
+            .await
+            .map_err(cdp)?
+            .result
+            .cookies;
+        let mut cleared = 0;
+        for cookie in cookies.iter().filter(|c| storage::in_domain(&c.domain, domain)) {
+            let mut params = DeleteCookiesParams::new(cookie.name.clone());
+            params.domain = Some(cookie.domain.clone());
+            params.path = Some(cookie.path.clone());
+            self.page.execute(params).await.map_err(cdp)?;
+            cleared += 1;
         }
 
-        let screenshot = self.page
-            .screenshot(params)
+        Ok(Some(cleared))
+    }
+
+    /// Take screenshot (returns base64 PNG)
+    async fn screenshot(&mut self, full_page: bool, stitch: bool) -> ActionResult<String> {
+        let options = ScreenshotOptions {
+            full_page,
+            stitch,
+            ..Default::default()
+        };
+        let screenshot = ScreenshotCapturer::capture(&self.page, options)
             .await
             .map_err(|e| ActionError::BrowserError(e.to_string()))?;
 
-        Ok(general_purpose::STANDARD.encode(&screenshot))
+        Ok(screenshot.to_base64())
+    }
+
+    /// Fill each field, collecting per-field errors
+    async fn fill_form(
+        &mut self,
+        fields: &[FormField],
+        pierce_shadow: bool,
+    ) -> ActionResult<Vec<FieldResult>> {
+        let mut results = Vec::with_capacity(fields.len());
+
+        for field in fields {
+            if let Some(error) = field.check() {
+                results.push(FieldResult::new(&field.selector, Some(error)));
+                continue;
+            }
+
+            let outcome: FillOutcome = self
+                .evaluate(field.script(pierce_shadow))
+                .await?
+                .into_value()
+                .map_err(|e| ActionError::BrowserError(e.to_string()))?;
+            results.push(FieldResult::new(&field.selector, outcome.error));
+        }
+
+        Ok(results)
+    }
+
+    /// Extract values by `schema` (returns them as JSON)
+    async fn extract(
+        &mut self,
+        schema: &ExtractSchema,
+        pierce_shadow: bool,
+    ) -> ActionResult<String> {
+        let raw: serde_json::Value = self
+            .evaluate(crate::extract::script(schema, pierce_shadow))
+            .await?
+            .into_value()
+            .map_err(|e| ActionError::BrowserError(e.to_string()))?;
+
+        serde_json::to_string(&crate::extract::convert(schema, &raw))
+            .map_err(|e| ActionError::ActionFailed(e.to_string()))
+    }
+
+    /// Check `condition`; the inner error says why it does not hold
+    async fn assert(&mut self, condition: &AssertCondition) -> ActionResult<Result<(), String>> {
+        let probe: Option<Probe> = match condition.probe_script() {
+            Some(script) => self
+                .evaluate(script)
+                .await?
+                .into_value()
+                .map_err(|e| ActionError::BrowserError(e.to_string()))?,
+            None => None,
+        };
+        let url = self
+            .page
+            .url()
+            .await
+            .map_err(|e| ActionError::BrowserError(e.to_string()))?
+            .unwrap_or_default();
+
+        Ok(condition.check(probe.as_ref(), &url))
+    }
+
+    /// Interactive elements of the target frame, skipping any not rendered
+    async fn accessibility_snapshot(&mut self) -> ActionResult<Vec<AccessibleNode>> {
+        let params = GetFullAxTreeParams {
+            depth: None,
+            frame_id: self.frame.clone().map(FrameId::new),
+        };
+        let tree = self
+            .page
+            .execute(params)
+            .await
+            .map_err(|e| ActionError::BrowserError(e.to_string()))?
+            .result
+            .nodes;
+
+        let mut nodes = Vec::new();
+        for mut node in crate::accessibility::interactive(&tree) {
+            node.bounds = self.node_bounds(node.id).await;
+            if node.bounds.is_some() {
+                nodes.push(node);
+            }
+        }
+
+        Ok(nodes)
+    }
+
+    /// Scroll a node into view and click its center
+    async fn click_node(&mut self, node_id: i64) -> ActionResult<()> {
+        let not_found = || ActionError::ElementNotFound(format!("node {}", node_id));
+
+        self.page
+            .execute(
+                ScrollIntoViewIfNeededParams::builder()
+                    .backend_node_id(BackendNodeId::new(node_id))
+                    .build(),
+            )
+            .await
+            .map_err(|_| not_found())?;
+        let (x, y) = self
+            .node_bounds(node_id)
+            .await
+            .ok_or_else(not_found)?
+            .center();
+
+        let point = Point { x, y };
+        match &self.humanize {
+            Some(humanize) => {
+                let point = self.move_along(humanize.mouse_path(point), humanize).await?;
+                self.press(point, humanize).await?;
+            }
+            None => {
+                self.mouse(DispatchMouseEventType::MouseMoved, point, false).await?;
+                self.mouse(DispatchMouseEventType::MousePressed, point, true).await?;
+                self.mouse(DispatchMouseEventType::MouseReleased, point, true).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Where a node is in the viewport, if it is rendered
+    async fn node_bounds(&self, node_id: i64) -> Option<Bounds> {
+        let quads = self
+            .page
+            .execute(
+                GetContentQuadsParams::builder()
+                    .backend_node_id(BackendNodeId::new(node_id))
+                    .build(),
+            )
+            .await
+            .ok()?
+            .result
+            .quads;
+
+        quads
+            .first()
+            .and_then(|quad| Bounds::from_quad(quad.inner()))
+            .filter(|bounds| bounds.width > 0.0 && bounds.height > 0.0)
+    }
+
+    /// Set a file input's file (returns the resolved path)
+    async fn upload_file(
+        &mut self,
+        selector: &Selector,
+        path: &str,
+        pierce_shadow: bool,
+    ) -> ActionResult<String> {
+        let file = crate::upload::resolve(path, &self.upload_roots)?;
+        let element = self
+            .find_element(selector, pierce_shadow, self.default_timeout)
+            .await?;
+
+        let mut params = SetFileInputFilesParams::new(vec![file.to_string_lossy().to_string()]);
+        params.backend_node_id = Some(element.backend_node_id);
+
+        self.page
+            .execute(params)
+            .await
+            .map_err(|e| ActionError::ActionFailed(e.to_string()))?;
+
+        Ok(file.to_string_lossy().to_string())
     }
 
-    // Note: go_back and go_forward removed as they're not supported in chromiumoxide 0.5
-    // Using JavaScript execution instead (see execute() method)
+    /// Move `delta` entries through the tab's history and wait for
+    /// `wait_until`; returns the URL reached
+    async fn go(&mut self, delta: i64, wait_until: WaitCondition) -> ActionResult<String> {
+        crate::navigation::go(
+            &self.page,
+            delta,
+            wait_until,
+            self.navigation_timeout,
+            self.network_idle,
+        )
+        .await
+    }
 
     /// Reload current page
     async fn reload(&mut self) -> ActionResult<()> {
+        // Waits for the reloaded page to load
         self.page
             .reload()
             .await
             .map_err(|e| ActionError::NavigationFailed(e.to_string()))?;
 
-        tokio::time::sleep(Duration::from_millis(500)).await;
         Ok(())
     }
 
     /// Find element with timeout
-    async fn find_element(&self, selector: &str, timeout: Duration) -> ActionResult<Element> {
+    async fn find_element(
+        &self,
+        selector: &Selector,
+        pierce_shadow: bool,
+        timeout: Duration,
+    ) -> ActionResult<Element> {
         let deadline = tokio::time::Instant::now() + timeout;
 
         loop {
-            match self.page.find_element(selector).await {
-                Ok(element) => return Ok(element),
-                Err(_) => {
+            // Page lookups search the main document only
+            let found = if self.frame.is_some() {
+                self.find_marked(selector, pierce_shadow).await?
+            } else {
+                match self.try_find_element(selector).await {
+                    None if pierce_shadow => self.find_marked(selector, true).await?,
+                    found => found,
+                }
+            };
+            match found {
+                Some(element) => return Ok(element),
+                None => {
                     if tokio::time::Instant::now() >= deadline {
                         return Err(ActionError::Timeout(selector.to_string()));
                     }
@@ -483,4 +1587,129 @@ impl ActionExecutor {
             }
         }
     }
+
+    /// First element matching `selector`, by CSS or `DOM.performSearch`
+    async fn try_find_element(&self, selector: &Selector) -> Option<Element> {
+        match selector.xpath() {
+            None => self.page.find_element(selector.to_string()).await.ok(),
+            Some(xpath) => self.page.find_xpaths(xpath).await.ok()?.into_iter().next(),
+        }
+    }
+
+    /// First match in the target frame, searching open shadow roots too if
+    /// `pierce_shadow`
+    ///
+    /// CDP selector lookups stop at shadow and frame boundaries, so a script
+    /// finds the element and marks it with a one-off attribute, which the
+    /// plain-text pass of `DOM.performSearch` does find across them. Fails
+    /// only if the target frame is gone.
+    async fn find_marked(
+        &self,
+        selector: &Selector,
+        pierce_shadow: bool,
+    ) -> ActionResult<Option<Element>> {
+        let marker = crate::selector::marker();
+        let marked = self
+            .evaluate(selector.mark_script(&marker, pierce_shadow))
+            .await?
+            .into_value::<bool>()
+            .unwrap_or(false);
+        if !marked {
+            return Ok(None);
+        }
+
+        let Some(element) = self
+            .page
+            .find_xpaths(marker.as_str())
+            .await
+            .ok()
+            .and_then(|elements| elements.into_iter().next())
+        else {
+            return Ok(None);
+        };
+        let unmark = format!("function() {{ this.removeAttribute('{}'); }}", marker);
+        let _ = element.call_js_fn(unmark, false).await;
+        Ok(Some(element))
+    }
+
+    /// Evaluate a script or function in the target frame
+    async fn evaluate(&self, script: impl Into<Evaluation>) -> ActionResult<EvaluationResult> {
+        let mut evaluation = script.into();
+        if let Some(context) = self.frame_context().await? {
+            match &mut evaluation {
+                Evaluation::Expression(params) => params.context_id = Some(context),
+                Evaluation::Function(params) => params.execution_context_id = Some(context),
+            }
+        }
+
+        self.page
+            .evaluate(evaluation)
+            .await
+            .map_err(|e| ActionError::BrowserError(e.to_string()))
+    }
+
+    /// The target frame's main-world context, or `None` for the main frame
+    async fn frame_context(&self) -> ActionResult<Option<ExecutionContextId>> {
+        let Some(frame) = &self.frame else {
+            return Ok(None);
+        };
+
+        self.page
+            .frame_execution_context(FrameId::new(frame.clone()))
+            .await
+            .ok()
+            .flatten()
+            .map(Some)
+            .ok_or_else(|| {
+                ActionError::FrameNotFound(format!(
+                    "{} has been detached, has no document or is cross-origin \
+                     (see reach_cross_origin_frames); use SwitchToMainFrame",
+                    frame
+                ))
+            })
+    }
+
+    /// Resolve a `SwitchFrame` target to a child of the target frame
+    pub(crate) async fn child_frame(&self, target: &FrameTarget) -> ActionResult<String> {
+        let frame = match target {
+            FrameTarget::Index(index) => {
+                let tree = self
+                    .page
+                    .execute(GetFrameTreeParams::default())
+                    .await
+                    .map_err(|e| ActionError::BrowserError(e.to_string()))?
+                    .result
+                    .frame_tree;
+                crate::frames::child_frame(&tree, self.frame.as_deref(), *index)
+                    .ok_or_else(|| ActionError::FrameNotFound(format!("#{}", index)))?
+            }
+            FrameTarget::Selector(selector) => {
+                let element = self
+                    .find_element(selector, false, self.default_timeout)
+                    .await?;
+                let node = self
+                    .page
+                    .execute(
+                        DescribeNodeParams::builder()
+                            .backend_node_id(element.backend_node_id)
+                            .build(),
+                    )
+                    .await
+                    .map_err(|e| ActionError::BrowserError(e.to_string()))?
+                    .result
+                    .node;
+                node.frame_id
+                    .map(|id| id.inner().clone())
+                    .ok_or_else(|| {
+                        ActionError::FrameNotFound(format!("{} is not a frame", selector))
+                    })?
+            }
+        };
+
+        // Only switch to a frame that scripts can run in
+        let executor = ActionExecutor::new(self.page.clone(), self.default_timeout)
+            .with_frame(Some(frame.clone()));
+        executor.frame_context().await?;
+        Ok(frame)
+    }
 }
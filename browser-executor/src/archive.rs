@@ -0,0 +1,94 @@
+//! Page archives
+//!
+//! `BrowserAction::SavePageArchive` saves the active tab as a single MHTML
+//! file (CDP `Page.captureSnapshot`), with its images and styles, for reading
+//! offline. Archives go to the download directory like downloads, and `path`
+//! must stay inside it.
+
+use crate::actions::{ActionError, ActionOutput, ActionResult, BrowserAction};
+use crate::download;
+use crate::executor::ExecutorConfig;
+use chromiumoxide::cdp::browser_protocol::page::{CaptureSnapshotFormat, CaptureSnapshotParams};
+use chromiumoxide::page::Page;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use tracing::info;
+
+/// A saved archive, the `data` of a `SavePageArchive` output as JSON
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchiveResult {
+    pub url: Option<String>,
+    pub path: PathBuf,
+    pub size_bytes: u64,
+}
+
+/// Run a `SavePageArchive` action on `page`
+pub(crate) async fn run(
+    config: &ExecutorConfig,
+    page: &Page,
+    action: BrowserAction,
+) -> ActionResult<ActionOutput> {
+    let start = std::time::Instant::now();
+    let BrowserAction::SavePageArchive { path } = action else {
+        return Err(ActionError::ActionFailed(format!(
+            "Not a page archive: {:?}",
+            action
+        )));
+    };
+
+    let url = page.url().await.ok().flatten();
+    let dir = config.downloads.dir()?;
+    let destination = download::destination(&dir, Some(&path), &default_name(url.as_deref()))?;
+
+    let mhtml = page
+        .execute(CaptureSnapshotParams {
+            format: Some(CaptureSnapshotFormat::Mhtml),
+        })
+        .await
+        .map_err(|e| ActionError::BrowserError(e.to_string()))?
+        .result
+        .data;
+
+    let failed =
+        |e: std::io::Error| ActionError::ActionFailed(format!("{}: {}", destination.display(), e));
+    if let Some(parent) = destination.parent() {
+        tokio::fs::create_dir_all(parent).await.map_err(failed)?;
+    }
+    tokio::fs::write(&destination, &mhtml)
+        .await
+        .map_err(failed)?;
+    info!("Saved page archive to {}", destination.display());
+
+    let result = ArchiveResult {
+        url,
+        path: destination,
+        size_bytes: mhtml.len() as u64,
+    };
+    let data =
+        serde_json::to_string(&result).map_err(|e| ActionError::ActionFailed(e.to_string()))?;
+
+    Ok(ActionOutput::ok(Some(data), start.elapsed()))
+}
+
+/// File name for a `path` naming a directory: the page's host
+fn default_name(url: Option<&str>) -> String {
+    let host = url
+        .and_then(|url| url::Url::parse(url).ok())
+        .and_then(|url| url.host_str().map(str::to_string));
+    format!("{}.mhtml", host.unwrap_or_else(|| "page".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_name() {
+        assert_eq!(
+            default_name(Some("https://docs.rs/tokio/latest")),
+            "docs.rs.mhtml"
+        );
+        assert_eq!(default_name(Some("data:text/html,hi")), "page.mhtml");
+        assert_eq!(default_name(None), "page.mhtml");
+    }
+}
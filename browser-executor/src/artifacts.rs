@@ -0,0 +1,213 @@
+//! Failure artifacts
+//!
+//! With `ExecutorConfig::failure_artifacts` set, every tab keeps its recent
+//! console messages, uncaught exceptions and browser log entries (see
+//! [`console`](crate::console)), and each failed action saves what the page
+//! looked like into its own directory under the run's directory:
+//!
+//! - `screenshot.png`: the viewport
+//! - `dom.html`: the serialized DOM
+//! - `log.json`: the tab's last `max_log_entries` `ConsoleEntry`s
+//! - `error.txt`: the action's type, selector and URL, and its error (never
+//!   the text, values or cookies it carried)
+//!
+//! The directory is named at the end of the error, as `(artifacts: <dir>)`.
+
+use crate::actions::{ActionError, ActionOutput, ActionResult, BrowserAction};
+use crate::console::ConsoleLog;
+use crate::failure;
+use chromiumoxide::cdp::browser_protocol::page::CaptureScreenshotParams;
+use chromiumoxide::page::Page;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use std::time::Duration;
+use tracing::warn;
+
+/// Where and how much to keep, set in `ExecutorConfig::failure_artifacts`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArtifactConfig {
+    /// Parent of the per-run directories
+    pub dir: PathBuf,
+
+    /// Console entries saved in `log.json`, and at least kept per tab
+    #[serde(default = "default_max_log_entries")]
+    pub max_log_entries: usize,
+}
+
+fn default_max_log_entries() -> usize {
+    100
+}
+
+impl ArtifactConfig {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            max_log_entries: default_max_log_entries(),
+        }
+    }
+}
+
+/// Save artifacts for a failed `action`; returns their directory
+///
+/// Parts that cannot be captured (a page that stopped responding, say) are
+/// skipped, so there is always at least `error.txt`.
+pub(crate) async fn capture(
+    config: &ArtifactConfig,
+    page: &Page,
+    log: &ConsoleLog,
+    action: &BrowserAction,
+    error: &str,
+) -> std::io::Result<PathBuf> {
+    let dir = failure_dir(&config.dir, action);
+    tokio::fs::create_dir_all(&dir).await?;
+    let report = format!("{}\n\n{}\n", describe(action), error);
+    tokio::fs::write(dir.join("error.txt"), report).await?;
+
+    let log = serde_json::to_vec_pretty(&log.last(config.max_log_entries)).unwrap_or_default();
+    tokio::fs::write(dir.join("log.json"), log).await?;
+
+    // A page that hangs must not hang the error report too
+    let timeout = Duration::from_secs(5);
+    match tokio::time::timeout(timeout, page.screenshot(CaptureScreenshotParams::default())).await
+    {
+        Ok(Ok(png)) => tokio::fs::write(dir.join("screenshot.png"), png).await?,
+        Ok(Err(e)) => warn!("No failure screenshot: {}", e),
+        Err(_) => warn!("No failure screenshot: timed out"),
+    }
+    match tokio::time::timeout(timeout, page.content()).await {
+        Ok(Ok(html)) => tokio::fs::write(dir.join("dom.html"), html).await?,
+        Ok(Err(e)) => warn!("No failure DOM dump: {}", e),
+        Err(_) => warn!("No failure DOM dump: timed out"),
+    }
+
+    Ok(dir)
+}
+
+/// Save artifacts if `result` is a failure, naming their directory in its
+/// error
+///
+/// Failed assertions count too: they return an output with `success: false`.
+pub(crate) async fn record(
+    config: &ArtifactConfig,
+    page: &Page,
+    log: &ConsoleLog,
+    action: &BrowserAction,
+    result: ActionResult<ActionOutput>,
+) -> ActionResult<ActionOutput> {
+    let error = match &result {
+        Ok(output) if output.success => return result,
+        Ok(output) => output.error.clone().unwrap_or_default(),
+        Err(e) => e.to_string(),
+    };
+    let dir = match capture(config, page, log, action, &error).await {
+        Ok(dir) => dir,
+        Err(e) => {
+            warn!("Failed to save failure artifacts: {}", e);
+            return result;
+        }
+    };
+
+    let note = |message: String| format!("{} (artifacts: {})", message, dir.display());
+    match result {
+        Ok(output) => Ok(ActionOutput {
+            error: Some(note(output.error.unwrap_or_default())),
+            ..output
+        }),
+        Err(e) => Err(match e {
+            ActionError::ElementNotFound(m) => ActionError::ElementNotFound(note(m)),
+            ActionError::Timeout(m) => ActionError::Timeout(note(m)),
+            ActionError::InvalidSelector(m) => ActionError::InvalidSelector(note(m)),
+            ActionError::ActionFailed(m) => ActionError::ActionFailed(note(m)),
+            ActionError::NavigationFailed(m) => ActionError::NavigationFailed(note(m)),
+            ActionError::NavigationTimeout(m) => ActionError::NavigationTimeout(note(m)),
+            ActionError::NetworkError(m) => ActionError::NetworkError(note(m)),
+            ActionError::BrowserError(m) => ActionError::BrowserError(note(m)),
+            ActionError::TabNotFound(m) => ActionError::TabNotFound(note(m)),
+            ActionError::DownloadFailed(m) => ActionError::DownloadFailed(note(m)),
+            ActionError::UploadRejected(m) => ActionError::UploadRejected(note(m)),
+            ActionError::FrameNotFound(m) => ActionError::FrameNotFound(note(m)),
+            ActionError::DialogOpen(m) => ActionError::DialogOpen(note(m)),
+            ActionError::PolicyViolation(m) => ActionError::PolicyViolation(note(m)),
+        }),
+    }
+}
+
+/// A new directory for one failure, in the run's directory
+fn failure_dir(root: &Path, action: &BrowserAction) -> PathBuf {
+    static RUN: OnceLock<String> = OnceLock::new();
+    static FAILURES: AtomicU64 = AtomicU64::new(0);
+
+    let run = RUN.get_or_init(|| {
+        let started = chrono::Local::now().format("%Y%m%d-%H%M%S");
+        format!("run-{}-{}", started, std::process::id())
+    });
+    let number = FAILURES.fetch_add(1, Ordering::Relaxed) + 1;
+    root.join(run)
+        .join(format!("{:03}-{}", number, action_name(action)))
+}
+
+/// The action's `type`, e.g. `get_text`
+pub(crate) fn action_name(action: &BrowserAction) -> String {
+    serde_json::to_value(action)
+        .ok()
+        .and_then(|value| value["type"].as_str().map(str::to_string))
+        .unwrap_or_else(|| "action".to_string())
+}
+
+/// The action's type, and the element and URL it targets, leaving out what
+/// it typed or set
+fn describe(action: &BrowserAction) -> String {
+    let mut description = action_name(action);
+    if let Some(selector) = failure::selector(action) {
+        description.push_str(&format!(" on {}", selector));
+    }
+    if let Some(url) = failure::target_url(action) {
+        description.push_str(&format!(" to {}", url));
+    }
+    description
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failure_dir() {
+        let action = BrowserAction::GetText {
+            selector: "#price".into(),
+            pierce_shadow: false,
+        };
+        let first = failure_dir(Path::new("/tmp/artifacts"), &action);
+        let second = failure_dir(Path::new("/tmp/artifacts"), &BrowserAction::Reload);
+
+        assert_eq!(first.parent(), second.parent());
+        let run = first.parent().unwrap();
+        assert_eq!(run.parent(), Some(Path::new("/tmp/artifacts")));
+        assert!(run.file_name().unwrap().to_str().unwrap().starts_with("run-"));
+
+        let first = first.file_name().unwrap().to_str().unwrap();
+        let second = second.file_name().unwrap().to_str().unwrap();
+        assert!(first.ends_with("-get_text"));
+        assert!(second.ends_with("-reload"));
+        assert!(first < second);
+    }
+
+    #[test]
+    fn test_describe_leaves_out_values() {
+        let action = BrowserAction::Type {
+            selector: "#password".into(),
+            text: "hunter2".to_string(),
+            clear_first: true,
+            pierce_shadow: false,
+        };
+        assert_eq!(describe(&action), "type on #password");
+
+        let action = BrowserAction::Navigate {
+            url: "https://example.com/".to_string(),
+            wait_until: crate::actions::WaitCondition::Load,
+        };
+        assert_eq!(describe(&action), "navigate to https://example.com/");
+    }
+}
@@ -0,0 +1,203 @@
+//! Assertions on page state
+//!
+//! `BrowserAction::Assert` checks a condition once, without waiting (use
+//! `WaitFor` first for content still loading). A condition that does not
+//! hold is not an error: the output has `success: false`, a message saying
+//! what was expected and found, and a viewport screenshot (base64 PNG) as
+//! `data`, so a plan step fails with evidence attached.
+
+use crate::network::wildcard_match;
+use crate::selector::{Selector, RESOLVE_QUERY};
+use serde::{Deserialize, Serialize};
+
+/// A condition on the page (or the target frame, for element conditions)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AssertCondition {
+    ElementExists {
+        selector: Selector,
+        #[serde(default)]
+        pierce_shadow: bool,
+    },
+
+    /// Exists with a non-empty box, and is not hidden by CSS
+    ElementVisible {
+        selector: Selector,
+        #[serde(default)]
+        pierce_shadow: bool,
+    },
+
+    /// The element's rendered text contains `text`
+    TextContains {
+        selector: Selector,
+        text: String,
+        #[serde(default)]
+        pierce_shadow: bool,
+    },
+
+    /// The tab's URL matches `pattern`, where `*` is any run of characters
+    /// and `?` one character
+    UrlMatches { pattern: String },
+
+    AttributeEquals {
+        selector: Selector,
+        attribute: String,
+        value: String,
+        #[serde(default)]
+        pierce_shadow: bool,
+    },
+}
+
+/// What the page shows for a condition's element
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub(crate) struct Probe {
+    pub visible: bool,
+    pub text: String,
+    pub attribute: Option<String>,
+}
+
+/// Reads the first match of a query; called with the resolver, the query
+/// and the attribute to read
+const PROBE: &str = r#"(resolve, query, attribute) => {
+  const el = resolve(query)[0];
+  if (!el) return null;
+  const rect = el.getBoundingClientRect();
+  const style = getComputedStyle(el);
+  return {
+    visible: rect.width > 0 && rect.height > 0 && style.visibility !== 'hidden'
+      && style.display !== 'none' && style.opacity !== '0',
+    text: el.innerText ?? el.textContent ?? '',
+    attribute: attribute === null ? null : el.getAttribute(attribute),
+  };
+}"#;
+
+impl AssertCondition {
+    /// The condition's element, if it has one
+    pub(crate) fn selector(&self) -> Option<(&Selector, bool)> {
+        match self {
+            AssertCondition::ElementExists {
+                selector,
+                pierce_shadow,
+            }
+            | AssertCondition::ElementVisible {
+                selector,
+                pierce_shadow,
+            }
+            | AssertCondition::TextContains {
+                selector,
+                pierce_shadow,
+                ..
+            }
+            | AssertCondition::AttributeEquals {
+                selector,
+                pierce_shadow,
+                ..
+            } => Some((selector, *pierce_shadow)),
+            AssertCondition::UrlMatches { .. } => None,
+        }
+    }
+
+    /// Script returning the `Probe` of the condition's element, or null
+    pub(crate) fn probe_script(&self) -> Option<String> {
+        let (selector, pierce_shadow) = self.selector()?;
+        let attribute = match self {
+            AssertCondition::AttributeEquals { attribute, .. } => Some(attribute),
+            _ => None,
+        };
+        Some(format!(
+            "({})({}, {}, {})",
+            PROBE,
+            RESOLVE_QUERY,
+            selector.query(pierce_shadow),
+            serde_json::json!(attribute)
+        ))
+    }
+
+    /// Check the condition against what was read from the page; the error
+    /// says what was expected and what was found
+    pub(crate) fn check(&self, probe: Option<&Probe>, url: &str) -> Result<(), String> {
+        if let AssertCondition::UrlMatches { pattern } = self {
+            if wildcard_match(pattern, url) {
+                return Ok(());
+            }
+            return Err(format!("Expected URL matching {:?}, got {:?}", pattern, url));
+        }
+
+        let Some((selector, _)) = self.selector() else {
+            return Ok(());
+        };
+        let Some(probe) = probe else {
+            return Err(format!("Expected element {} to exist", selector));
+        };
+
+        match self {
+            AssertCondition::ElementVisible { .. } if !probe.visible => {
+                Err(format!("Expected element {} to be visible", selector))
+            }
+            AssertCondition::TextContains { text, .. } if !probe.text.contains(text.as_str()) => {
+                Err(format!(
+                    "Expected text of {} to contain {:?}, got {:?}",
+                    selector, text, probe.text
+                ))
+            }
+            AssertCondition::AttributeEquals {
+                attribute, value, ..
+            } if probe.attribute.as_deref() != Some(value.as_str()) => Err(format!(
+                "Expected {} of {} to equal {:?}, got {:?}",
+                attribute, selector, value, probe.attribute
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        let conditions: Vec<AssertCondition> = serde_json::from_str(
+            r##"[
+                {"type": "element_visible", "selector": "#cart"},
+                {"type": "text_contains", "selector": "#cart", "text": "3 items"},
+                {"type": "attribute_equals", "selector": {"aria_label": "Cart"},
+                 "attribute": "data-state", "value": "open"},
+                {"type": "url_matches", "pattern": "https://shop.example/cart*"}
+            ]"##,
+        )
+        .unwrap();
+        let probe = Probe {
+            visible: true,
+            text: "Cart: 2 items".to_string(),
+            attribute: Some("open".to_string()),
+        };
+        let url = "https://shop.example/cart?step=1";
+
+        let results: Vec<_> = conditions
+            .iter()
+            .map(|condition| condition.check(Some(&probe), url))
+            .collect();
+        assert_eq!(
+            results,
+            [
+                Ok(()),
+                Err(r##"Expected text of #cart to contain "3 items", got "Cart: 2 items""##
+                    .to_string()),
+                Ok(()),
+                Ok(()),
+            ]
+        );
+
+        assert_eq!(
+            conditions[0].check(None, url),
+            Err("Expected element #cart to exist".to_string())
+        );
+        let hidden = Probe::default();
+        assert!(conditions[0].check(Some(&hidden), url).is_err());
+        assert!(conditions[3]
+            .check(None, "https://shop.example/checkout")
+            .is_err());
+        assert!(conditions[3].probe_script().is_none());
+    }
+}
@@ -0,0 +1,321 @@
+//! HTTP authentication for web servers
+//!
+//! Pages answer Basic, Digest and NTLM challenges with credentials from
+//! `ExecutorConfig::credentials`, a [`CredentialProvider`] asked per origin,
+//! so they can come from a vault instead of the configuration, such as the
+//! [`CredentialVault`] file encrypted at rest. Credentials that are missing
+//! or rejected cancel the challenge, and the navigation fails with the
+//! server's 401 (see [`network`](crate::network)).
+
+use argon2::Argon2;
+use async_trait::async_trait;
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::io::Write;
+use std::path::Path;
+use thiserror::Error;
+
+/// Environment variable holding the passphrase of the credential vault
+pub const VAULT_PASSPHRASE_ENV: &str = "AETHER_VAULT_PASSPHRASE";
+
+/// Vault file format this build writes and reads
+const VAULT_VERSION: u32 = 1;
+
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+#[derive(Error, Debug)]
+pub enum VaultError {
+    #[error("Vault I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Invalid vault: {0}")]
+    Invalid(String),
+
+    #[error("Wrong passphrase, or the vault was modified")]
+    Decrypt,
+
+    #[error("Set {} to open the credential vault", VAULT_PASSPHRASE_ENV)]
+    NoPassphrase,
+}
+
+/// A username and password for one origin
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+}
+
+impl Credentials {
+    pub fn new(username: impl Into<String>, password: impl Into<String>) -> Self {
+        Self {
+            username: username.into(),
+            password: password.into(),
+        }
+    }
+}
+
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Credentials")
+            .field("username", &self.username)
+            .field("password", &"<redacted>")
+            .finish()
+    }
+}
+
+/// Source of credentials for servers that ask for them
+#[async_trait]
+pub trait CredentialProvider: Send + Sync + fmt::Debug {
+    /// Credentials for `origin` (e.g. `https://intranet.corp`), whose server
+    /// asked for `realm`; `None` cancels the challenge
+    async fn credentials(&self, origin: &str, realm: &str) -> Option<Credentials>;
+}
+
+/// Fixed credentials by origin, for tests and simple setups
+#[derive(Debug, Clone, Default)]
+pub struct StaticCredentials {
+    by_origin: HashMap<String, Credentials>,
+}
+
+impl StaticCredentials {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use `credentials` for `origin`, a URL whose path is ignored
+    pub fn with(mut self, origin: &str, credentials: Credentials) -> Self {
+        self.by_origin.insert(normalize(origin), credentials);
+        self
+    }
+}
+
+#[async_trait]
+impl CredentialProvider for StaticCredentials {
+    async fn credentials(&self, origin: &str, _realm: &str) -> Option<Credentials> {
+        self.by_origin.get(&normalize(origin)).cloned()
+    }
+}
+
+/// Credentials by origin, kept in a file encrypted at rest
+///
+/// The file holds the credentials as JSON encrypted with ChaCha20-Poly1305,
+/// under a key derived from a passphrase with Argon2id. Each save uses a
+/// fresh salt and nonce and replaces the file whole, readable by its owner
+/// only:
+///
+/// ```json
+/// { "version": 1, "salt": "9f2c...", "nonce": "41d0...", "ciphertext": "..." }
+/// ```
+#[derive(Clone, Default)]
+pub struct CredentialVault {
+    by_origin: HashMap<String, Credentials>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct VaultFile {
+    version: u32,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+impl CredentialVault {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Decrypt the vault at `path` with `passphrase`
+    pub fn open(path: impl AsRef<Path>, passphrase: &str) -> Result<Self, VaultError> {
+        let file: VaultFile = serde_json::from_slice(&std::fs::read(path)?)
+            .map_err(|e| VaultError::Invalid(e.to_string()))?;
+        if file.version != VAULT_VERSION {
+            return Err(VaultError::Invalid(format!(
+                "unsupported version {}",
+                file.version
+            )));
+        }
+        let decode =
+            |field: &str| hex::decode(field).map_err(|e| VaultError::Invalid(e.to_string()));
+        let nonce = decode(&file.nonce)?;
+        if nonce.len() != NONCE_LEN {
+            return Err(VaultError::Invalid("bad nonce".to_string()));
+        }
+
+        let ciphertext = decode(&file.ciphertext)?;
+        let plaintext = cipher(passphrase, &decode(&file.salt)?)?
+            .decrypt(Nonce::from_slice(&nonce), ciphertext.as_ref())
+            .map_err(|_| VaultError::Decrypt)?;
+        let by_origin =
+            serde_json::from_slice(&plaintext).map_err(|e| VaultError::Invalid(e.to_string()))?;
+        Ok(Self { by_origin })
+    }
+
+    /// [`open`](Self::open) with the passphrase in [`VAULT_PASSPHRASE_ENV`]
+    pub fn open_from_env(path: impl AsRef<Path>) -> Result<Self, VaultError> {
+        let passphrase =
+            std::env::var(VAULT_PASSPHRASE_ENV).map_err(|_| VaultError::NoPassphrase)?;
+        Self::open(path, &passphrase)
+    }
+
+    /// Encrypt the vault with `passphrase` into `path`
+    pub fn save(&self, path: impl AsRef<Path>, passphrase: &str) -> Result<(), VaultError> {
+        let salt: [u8; SALT_LEN] = rand::random();
+        let nonce: [u8; NONCE_LEN] = rand::random();
+        let plaintext =
+            serde_json::to_vec(&self.by_origin).map_err(|e| VaultError::Invalid(e.to_string()))?;
+        let ciphertext = cipher(passphrase, &salt)?
+            .encrypt(Nonce::from_slice(&nonce), plaintext.as_ref())
+            .map_err(|e| VaultError::Invalid(e.to_string()))?;
+
+        let file = VaultFile {
+            version: VAULT_VERSION,
+            salt: hex::encode(salt),
+            nonce: hex::encode(nonce),
+            ciphertext: hex::encode(ciphertext),
+        };
+        let json =
+            serde_json::to_vec_pretty(&file).map_err(|e| VaultError::Invalid(e.to_string()))?;
+
+        // Written beside the vault and renamed over it, so a failed save
+        // leaves the old vault
+        let path = path.as_ref();
+        let mut temp = path.as_os_str().to_owned();
+        temp.push(".tmp");
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut out = options.open(&temp)?;
+        out.write_all(&json)?;
+        out.sync_all()?;
+        std::fs::rename(&temp, path)?;
+        Ok(())
+    }
+
+    /// Use `credentials` for `origin`, a URL whose path is ignored
+    pub fn insert(&mut self, origin: &str, credentials: Credentials) {
+        self.by_origin.insert(normalize(origin), credentials);
+    }
+
+    /// Forget the credentials for `origin`; false if there were none
+    pub fn remove(&mut self, origin: &str) -> bool {
+        self.by_origin.remove(&normalize(origin)).is_some()
+    }
+
+    /// The origins with credentials, sorted
+    pub fn origins(&self) -> Vec<&str> {
+        let mut origins: Vec<&str> = self.by_origin.keys().map(String::as_str).collect();
+        origins.sort_unstable();
+        origins
+    }
+}
+
+impl fmt::Debug for CredentialVault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CredentialVault")
+            .field("origins", &self.origins())
+            .finish()
+    }
+}
+
+#[async_trait]
+impl CredentialProvider for CredentialVault {
+    async fn credentials(&self, origin: &str, _realm: &str) -> Option<Credentials> {
+        self.by_origin.get(&normalize(origin)).cloned()
+    }
+}
+
+/// The vault's cipher for `passphrase` and `salt`
+fn cipher(passphrase: &str, salt: &[u8]) -> Result<ChaCha20Poly1305, VaultError> {
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| VaultError::Invalid(e.to_string()))?;
+    Ok(ChaCha20Poly1305::new(Key::from_slice(&key)))
+}
+
+/// `scheme://host[:port]`, without default ports, paths or letter case
+/// differences
+fn normalize(origin: &str) -> String {
+    match url::Url::parse(origin) {
+        Ok(url) => url.origin().ascii_serialization(),
+        Err(_) => origin.trim_end_matches('/').to_ascii_lowercase(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_static_credentials() {
+        let provider = StaticCredentials::new()
+            .with(
+                "https://Intranet.corp:443/",
+                Credentials::new("alice", "s3cret"),
+            )
+            .with("http://wiki.corp:8080", Credentials::new("bob", "hunter2"));
+
+        let alice = provider.credentials("https://intranet.corp", "Staff").await;
+        assert_eq!(alice, Some(Credentials::new("alice", "s3cret")));
+        assert!(provider
+            .credentials("http://wiki.corp:8080", "")
+            .await
+            .is_some());
+        assert!(provider
+            .credentials("http://intranet.corp", "")
+            .await
+            .is_none());
+        assert!(provider.credentials("http://wiki.corp", "").await.is_none());
+
+        assert!(!format!("{:?}", provider).contains("s3cret"));
+    }
+
+    #[tokio::test]
+    async fn test_credential_vault() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("vault.json");
+
+        let mut vault = CredentialVault::new();
+        vault.insert(
+            "https://Intranet.corp/login",
+            Credentials::new("alice", "s3cret"),
+        );
+        vault.insert("http://wiki.corp:8080", Credentials::new("bob", "hunter2"));
+        assert!(vault.remove("http://wiki.corp:8080/"));
+        vault.save(&path, "correct horse").unwrap();
+
+        // Encrypted at rest
+        let stored = std::fs::read_to_string(&path).unwrap();
+        assert!(!stored.contains("alice") && !stored.contains("s3cret"));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        let opened = CredentialVault::open(&path, "correct horse").unwrap();
+        assert_eq!(opened.origins(), ["https://intranet.corp"]);
+        assert_eq!(
+            opened.credentials("https://intranet.corp", "Staff").await,
+            Some(Credentials::new("alice", "s3cret"))
+        );
+        assert!(!format!("{:?}", opened).contains("s3cret"));
+
+        assert!(matches!(
+            CredentialVault::open(&path, "wrong"),
+            Err(VaultError::Decrypt)
+        ));
+        let tampered = stored.replacen("\"ciphertext\": \"", "\"ciphertext\": \"00", 1);
+        std::fs::write(&path, tampered).unwrap();
+        assert!(CredentialVault::open(&path, "correct horse").is_err());
+    }
+}
@@ -0,0 +1,270 @@
+//! Response body capture
+//!
+//! `CaptureResponses` records the bodies of a tab's responses whose URL
+//! matches `url_pattern` (`*` and `?` wildcards, as in `block_patterns`), so
+//! an agent can read a site's own JSON API instead of scraping the rendered
+//! page. The first call with a pattern starts capturing and returns nothing;
+//! each later call returns the responses that finished loading since the one
+//! before, as a JSON array of [`CapturedResponse`]. Bodies are cut at
+//! `max_bytes`, and a pattern keeps at most [`MAX_KEPT`] responses between
+//! calls, dropping the oldest.
+
+use crate::actions::{ActionError, ActionOutput, ActionResult, BrowserAction};
+use crate::network::wildcard_match;
+use base64::Engine as _;
+use chromiumoxide::cdp::browser_protocol::network::{
+    EventLoadingFailed, EventLoadingFinished, EventResponseReceived, GetResponseBodyParams,
+};
+use chromiumoxide::page::Page;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use tracing::debug;
+
+/// Responses a pattern keeps until they are collected
+pub const MAX_KEPT: usize = 100;
+
+/// A response recorded by `CaptureResponses`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CapturedResponse {
+    pub url: String,
+    pub status: i64,
+    pub mime_type: String,
+
+    /// The body as text, or base64 when `base64_encoded`
+    pub body: String,
+
+    #[serde(default)]
+    pub base64_encoded: bool,
+
+    /// Whether the body was cut at `max_bytes`
+    #[serde(default)]
+    pub truncated: bool,
+}
+
+/// A response seen, its body not loaded yet
+#[derive(Debug, Clone)]
+struct Pending {
+    url: String,
+    status: i64,
+    mime_type: String,
+}
+
+#[derive(Debug)]
+struct Rule {
+    pattern: String,
+    max_bytes: usize,
+    captured: VecDeque<CapturedResponse>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    rules: Vec<Rule>,
+    pending: HashMap<String, Pending>,
+}
+
+impl State {
+    fn matches(&self, url: &str) -> bool {
+        self.rules
+            .iter()
+            .any(|rule| wildcard_match(&rule.pattern, url))
+    }
+
+    /// Give each rule matching `response` its copy of `body`
+    fn record(&mut self, response: Pending, body: &str, base64_encoded: bool) {
+        for rule in &mut self.rules {
+            if !wildcard_match(&rule.pattern, &response.url) {
+                continue;
+            }
+            let (body, truncated) = truncate(body, base64_encoded, rule.max_bytes);
+            if rule.captured.len() == MAX_KEPT {
+                rule.captured.pop_front();
+            }
+            rule.captured.push_back(CapturedResponse {
+                url: response.url.clone(),
+                status: response.status,
+                mime_type: response.mime_type.clone(),
+                body,
+                base64_encoded,
+                truncated,
+            });
+        }
+    }
+
+    /// Responses captured for `pattern` since the last call, starting to
+    /// capture if it is new
+    fn collect(&mut self, pattern: &str, max_bytes: usize) -> Vec<CapturedResponse> {
+        match self.rules.iter_mut().find(|rule| rule.pattern == pattern) {
+            Some(rule) => {
+                rule.max_bytes = max_bytes;
+                rule.captured.drain(..).collect()
+            }
+            None => {
+                self.rules.push(Rule {
+                    pattern: pattern.to_string(),
+                    max_bytes,
+                    captured: VecDeque::new(),
+                });
+                Vec::new()
+            }
+        }
+    }
+}
+
+/// A tab's response capture, idle until `CaptureResponses` gives it a pattern
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Captures {
+    state: Arc<Mutex<State>>,
+}
+
+impl Captures {
+    /// Follow `page`'s responses from now on
+    pub(crate) async fn watch(page: &Page) -> chromiumoxide::Result<Self> {
+        let mut responses = page.event_listener::<EventResponseReceived>().await?;
+        let mut finished = page.event_listener::<EventLoadingFinished>().await?;
+        let mut failed = page.event_listener::<EventLoadingFailed>().await?;
+
+        let captures = Self::default();
+        let state = captures.state.clone();
+        let page = page.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    Some(event) = responses.next() => {
+                        let mut state = state.lock().unwrap_or_else(|e| e.into_inner());
+                        if state.matches(&event.response.url) {
+                            state.pending.insert(event.request_id.as_ref().to_string(), Pending {
+                                url: event.response.url.clone(),
+                                status: event.response.status,
+                                mime_type: event.response.mime_type.clone(),
+                            });
+                        }
+                    }
+                    Some(event) = finished.next() => {
+                        let response = state
+                            .lock()
+                            .unwrap_or_else(|e| e.into_inner())
+                            .pending
+                            .remove(event.request_id.as_ref());
+                        let Some(response) = response else {
+                            continue;
+                        };
+                        let params = GetResponseBodyParams::new(event.request_id.clone());
+                        match page.execute(params).await {
+                            Ok(body) => state
+                                .lock()
+                                .unwrap_or_else(|e| e.into_inner())
+                                .record(response, &body.result.body, body.result.base64_encoded),
+                            Err(e) => debug!("No body for {}: {}", response.url, e),
+                        }
+                    }
+                    Some(event) = failed.next() => {
+                        state
+                            .lock()
+                            .unwrap_or_else(|e| e.into_inner())
+                            .pending
+                            .remove(event.request_id.as_ref());
+                    }
+                    else => break,
+                }
+            }
+        });
+
+        Ok(captures)
+    }
+
+    /// See [`State::collect`]
+    pub(crate) fn collect(&self, pattern: &str, max_bytes: usize) -> Vec<CapturedResponse> {
+        self.state
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .collect(pattern, max_bytes)
+    }
+}
+
+/// Run a `CaptureResponses` action on the tab's captures
+pub(crate) fn run(captures: &Captures, action: BrowserAction) -> ActionResult<ActionOutput> {
+    let start = std::time::Instant::now();
+    let BrowserAction::CaptureResponses {
+        url_pattern,
+        max_bytes,
+    } = action
+    else {
+        return Err(ActionError::ActionFailed(format!(
+            "Not a capture action: {:?}",
+            action
+        )));
+    };
+
+    let captured = captures.collect(&url_pattern, max_bytes);
+    let data =
+        serde_json::to_string(&captured).map_err(|e| ActionError::ActionFailed(e.to_string()))?;
+
+    Ok(ActionOutput::ok(Some(data), start.elapsed()))
+}
+
+/// `body` cut to `max_bytes` (of decoded data, for base64), and whether it
+/// was cut
+fn truncate(body: &str, base64_encoded: bool, max_bytes: usize) -> (String, bool) {
+    let engine = base64::engine::general_purpose::STANDARD;
+    if base64_encoded {
+        return match engine.decode(body) {
+            Ok(bytes) if bytes.len() > max_bytes => (engine.encode(&bytes[..max_bytes]), true),
+            _ => (body.to_string(), false),
+        };
+    }
+    if body.len() <= max_bytes {
+        return (body.to_string(), false);
+    }
+    let mut end = max_bytes;
+    while !body.is_char_boundary(end) {
+        end -= 1;
+    }
+    (body[..end].to_string(), true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(url: &str) -> Pending {
+        Pending {
+            url: url.to_string(),
+            status: 200,
+            mime_type: "application/json".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_collect() {
+        let mut state = State::default();
+        let pattern = "*/api/search*";
+        assert!(state.collect(pattern, 10).is_empty());
+        assert!(state.matches("https://shop.example/api/search?q=lamp"));
+        assert!(!state.matches("https://shop.example/app.js"));
+
+        state.record(
+            response("https://shop.example/api/search?q=lamp"),
+            r#"{"results": [1, 2, 3]}"#,
+            false,
+        );
+        let captured = state.collect(pattern, 10);
+        assert_eq!(captured.len(), 1);
+        assert_eq!(captured[0].body, r#"{"results""#);
+        assert!(captured[0].truncated);
+
+        // Collected responses are not returned again
+        assert!(state.collect(pattern, 10).is_empty());
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("héllo", false, 2), ("h".to_string(), true));
+        assert_eq!(truncate("hello", false, 10), ("hello".to_string(), false));
+
+        let (body, truncated) = truncate("AAECAwQF", true, 3);
+        assert_eq!(body, "AAEC");
+        assert!(truncated);
+    }
+}
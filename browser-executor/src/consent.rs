@@ -0,0 +1,155 @@
+//! Cookie-consent banner dismissal
+//!
+//! With `ExecutorConfig::dismiss_consent`, every `Navigate`, `Reload`,
+//! `GoBack` and `GoForward` is followed by an attempt to close the page's
+//! cookie-consent banner, so that flows do not stall behind it;
+//! `DismissConsent` makes the same attempt on demand. Only banners of common
+//! consent managers (OneTrust, Cookiebot, Didomi, Quantcast, Usercentrics,
+//! TrustArc and others) are touched, in this order:
+//!
+//! 1. the manager's own reject button
+//! 2. a button inside the manager's banner with a reject-style label in one of
+//!    several languages ("Reject all", "Alle ablehnen", "Tout refuser", ...)
+//! 3. the manager's own accept button
+//!
+//! Generic labels such as "OK" or "Agree" are never clicked, so dialogs on
+//! the page itself (a privacy settings page, say) are left alone. A consent
+//! manager that has loaded but not shown its banner yet is given up to
+//! [`CMP_WAIT`] to show it.
+//!
+//! This is a heuristic: it never customizes, and does not reach banners
+//! inside iframes.
+
+use crate::actions::{ActionError, ActionResult};
+use chromiumoxide::page::Page;
+use serde::Deserialize;
+use std::time::Duration;
+use tracing::info;
+
+/// How long a consent manager has to show its banner
+const CMP_WAIT: Duration = Duration::from_secs(3);
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Clicks the first visible reject, or else accept, button of a known
+/// consent manager; evaluates to a [`Probe`]
+const SCRIPT: &str = r#"(() => {
+  const containers = [
+    '#onetrust-consent-sdk', '#CybotCookiebotDialog', '#didomi-host', '.qc-cmp2-container',
+    '#usercentrics-root', '#usercentrics-cmp-ui', '#truste-consent-track', '.truste_box_overlay',
+    '.fc-consent-root', '.osano-cm-window', '.cky-consent-container', '#axeptio_overlay',
+    '#iubenda-cs-banner', '.klaro', '#cmplz-cookiebanner-container', '#cookie-law-info-bar',
+    '.cc-window',
+  ].join(', ');
+  const reject = [
+    '#onetrust-reject-all-handler', '#CybotCookiebotDialogBodyButtonDecline',
+    '#didomi-notice-disagree-button', '[data-testid=uc-deny-all-button]', '.fc-cta-do-not-consent',
+    '.osano-cm-denyAll', '.cky-btn-reject', '#axeptio_btn_dismiss', '.iubenda-cs-reject-btn',
+    '.klaro .cm-btn-decline', '.cmplz-deny', '#cookie_action_close_header_reject', '.cc-window .cc-deny',
+  ];
+  const accept = [
+    '#onetrust-accept-btn-handler', '#accept-recommended-btn-handler',
+    '#CybotCookiebotDialogBodyLevelButtonLevelOptinAllowAll', '#CybotCookiebotDialogBodyButtonAccept',
+    '#didomi-notice-agree-button', '.qc-cmp2-summary-buttons button[mode=primary]',
+    '[data-testid=uc-accept-all-button]', '#truste-consent-button', '.fc-cta-consent',
+    '.osano-cm-accept-all', '.cky-btn-accept', '#axeptio_btn_acceptAll', '.iubenda-cs-accept-btn',
+    '.klaro .cm-btn-accept-all', '.cmplz-accept', '#cookie_action_close_header',
+    '.cc-window .cc-allow',
+  ];
+  const texts = [
+    'reject all', 'reject all cookies', 'reject', 'reject cookies', 'decline', 'decline all',
+    'refuse all', 'deny', 'deny all', 'necessary only', 'only necessary', 'only necessary cookies',
+    'use necessary cookies only', 'only essential cookies', 'continue without accepting',
+    'alle ablehnen', 'ablehnen', 'nur notwendige', 'nur notwendige cookies',
+    'tout refuser', 'refuser', 'continuer sans accepter',
+    'rechazar todo', 'rechazar todas', 'rechazar', 'rifiuta tutto', 'rifiuta tutti', 'rifiuta',
+    'alles weigeren', 'weigeren', 'rejeitar tudo', 'rejeitar todos', 'rejeitar',
+    'odrzuć wszystkie', 'avvisa alla', 'afvis alle', 'hylkää kaikki', 'odmítnout vše',
+    'отклонить все', 'すべて拒否', '全部拒绝', '拒绝',
+  ];
+  const visible = (el) => {
+    const rect = el.getBoundingClientRect();
+    const style = getComputedStyle(el);
+    return rect.width > 0 && rect.height > 0 && style.visibility !== 'hidden' && style.display !== 'none';
+  };
+  const describe = (el) => (el.id ? '#' + el.id : el.tagName.toLowerCase()) +
+    ` "${(el.innerText || el.value || '').trim().slice(0, 40)}"`;
+  const click = (el) => { el.click(); return { status: 'dismissed', by: describe(el) }; };
+  const first = (selectors) => {
+    for (const selector of selectors) {
+      const el = Array.from(document.querySelectorAll(selector)).find(visible);
+      if (el) return el;
+    }
+    return null;
+  };
+
+  const rejectButton = first(reject);
+  if (rejectButton) return click(rejectButton);
+
+  const normalize = (text) => text.replace(/\s+/g, ' ').trim().replace(/[.!]$/, '').toLowerCase();
+  const buttons = Array.from(document.querySelectorAll(containers)).flatMap((banner) =>
+    Array.from(banner.querySelectorAll('button, a, [role=button], input[type=button], input[type=submit]')));
+  for (const text of texts) {
+    const el = buttons.find((el) => normalize(el.innerText || el.value || '') === text && visible(el));
+    if (el) return click(el);
+  }
+
+  const acceptButton = first(accept);
+  if (acceptButton) return click(acceptButton);
+
+  const cmp = window.__tcfapi || window.__cmp || window.OneTrust || window.Cookiebot ||
+    window.Didomi || window.UC_UI;
+  return { status: cmp ? 'pending' : 'none' };
+})()"#;
+
+/// What a pass of the script found
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+enum Probe {
+    /// Clicked the button described by `by`
+    Dismissed {
+        by: String,
+    },
+    /// A consent manager has loaded, its banner not yet
+    Pending,
+    None,
+}
+
+/// Close `page`'s consent banner if it has one; returns the button clicked
+pub(crate) async fn dismiss(page: &Page) -> ActionResult<Option<String>> {
+    let deadline = tokio::time::Instant::now() + CMP_WAIT;
+    loop {
+        let probe: Probe = page
+            .evaluate(SCRIPT)
+            .await
+            .map_err(|e| ActionError::BrowserError(e.to_string()))?
+            .into_value()
+            .map_err(|e| ActionError::BrowserError(e.to_string()))?;
+        match probe {
+            Probe::Dismissed { by } => {
+                info!("Dismissed the consent banner with {}", by);
+                return Ok(Some(by));
+            }
+            Probe::Pending if tokio::time::Instant::now() < deadline => {
+                tokio::time::sleep(POLL_INTERVAL).await;
+            }
+            Probe::Pending | Probe::None => return Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_probe() {
+        let probe: Probe = serde_json::from_str(
+            r##"{"status": "dismissed", "by": "#onetrust-accept-btn-handler \"Accept All\""}"##,
+        )
+        .unwrap();
+        assert!(matches!(probe, Probe::Dismissed { .. }));
+        let probe: Probe = serde_json::from_str(r#"{"status": "pending"}"#).unwrap();
+        assert_eq!(probe, Probe::Pending);
+    }
+}
@@ -0,0 +1,239 @@
+//! Console messages and page errors
+//!
+//! With `ExecutorConfig::collect_console` or `failure_artifacts` set, every
+//! tab buffers its last `console_buffer_size` console messages
+//! (`Runtime.consoleAPICalled`), uncaught exceptions
+//! (`Runtime.exceptionThrown`), responses with an HTTP error status
+//! (`Network.responseReceived`) and browser log entries such as failed
+//! requests and interventions (`Log.entryAdded`).
+//!
+//! `BrowserExecutor::get_console_logs` returns the active tab's buffer. With
+//! `collect_console`, each action's output also carries the entries logged
+//! while it ran, as `ActionOutput::console`.
+
+use chromiumoxide::cdp::browser_protocol::log::{self, EventEntryAdded, LogEntryLevel};
+use chromiumoxide::cdp::browser_protocol::network::{self, EventResponseReceived, RequestId};
+use chromiumoxide::cdp::js_protocol::runtime::{
+    ConsoleApiCalledType, EventConsoleApiCalled, EventExceptionThrown, RemoteObject,
+};
+use chromiumoxide::page::Page;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+/// HTTP errors remembered, so the browser's own log entry for one is not
+/// recorded twice
+const RECENT_HTTP_ERRORS: usize = 32;
+
+/// A console message, uncaught exception, HTTP error or browser log entry
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConsoleEntry {
+    /// `console`, `exception`, or the browser's log source (`network`,
+    /// `javascript`, `intervention`, ...)
+    pub source: String,
+    /// `debug`, `info`, `warning` or `error`
+    pub level: String,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// RFC 3339
+    pub time: String,
+}
+
+impl ConsoleEntry {
+    pub(crate) fn new(source: &str, level: &str, message: String, url: Option<String>) -> Self {
+        Self {
+            source: source.to_string(),
+            level: level.to_string(),
+            message,
+            url,
+            time: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Buffer {
+    entries: VecDeque<ConsoleEntry>,
+    /// Entries ever pushed, including those dropped since
+    pushed: u64,
+}
+
+/// A tab's recent console entries, empty unless collection is on
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ConsoleLog {
+    buffer: Arc<Mutex<Buffer>>,
+    capacity: usize,
+}
+
+impl ConsoleLog {
+    /// Record `page`'s console from now on, keeping the last `capacity`
+    pub(crate) async fn watch(page: &Page, capacity: usize) -> chromiumoxide::Result<Self> {
+        let mut console = page.event_listener::<EventConsoleApiCalled>().await?;
+        let mut exceptions = page.event_listener::<EventExceptionThrown>().await?;
+        let mut entries = page.event_listener::<EventEntryAdded>().await?;
+        let mut responses = page.event_listener::<EventResponseReceived>().await?;
+        page.execute(log::EnableParams::default()).await?;
+        page.execute(network::EnableParams::default()).await?;
+
+        let log = Self {
+            buffer: Arc::default(),
+            capacity,
+        };
+        let recorder = log.clone();
+        tokio::spawn(async move {
+            let mut http_errors: VecDeque<RequestId> = VecDeque::new();
+            loop {
+                let entry = tokio::select! {
+                    Some(event) = console.next() => {
+                        let level = match event.r#type {
+                            ConsoleApiCalledType::Error | ConsoleApiCalledType::Assert => "error",
+                            ConsoleApiCalledType::Warning => "warning",
+                            ConsoleApiCalledType::Debug => "debug",
+                            _ => "info",
+                        };
+                        let message = event.args.iter().map(describe).collect::<Vec<_>>();
+                        ConsoleEntry::new("console", level, message.join(" "), None)
+                    }
+                    Some(event) = exceptions.next() => {
+                        let details = &event.exception_details;
+                        let message = details
+                            .exception
+                            .as_ref()
+                            .and_then(|exception| exception.description.clone())
+                            .unwrap_or_else(|| details.text.clone());
+                        ConsoleEntry::new("exception", "error", message, details.url.clone())
+                    }
+                    Some(event) = responses.next() => {
+                        let response = &event.response;
+                        if response.status < 400 {
+                            continue;
+                        }
+                        if http_errors.len() == RECENT_HTTP_ERRORS {
+                            http_errors.pop_front();
+                        }
+                        http_errors.push_back(event.request_id.clone());
+                        let message = format!("HTTP {} {}", response.status, response.status_text);
+                        ConsoleEntry::new("network", "error", message, Some(response.url.clone()))
+                    }
+                    Some(event) = entries.next() => {
+                        let entry = &event.entry;
+                        let logged = entry.network_request_id.as_ref();
+                        if logged.is_some_and(|id| http_errors.contains(id)) {
+                            continue;
+                        }
+                        let level = match entry.level {
+                            LogEntryLevel::Verbose => "debug",
+                            LogEntryLevel::Info => "info",
+                            LogEntryLevel::Warning => "warning",
+                            LogEntryLevel::Error => "error",
+                        };
+                        ConsoleEntry::new(
+                            entry.source.as_ref(),
+                            level,
+                            entry.text.clone(),
+                            entry.url.clone(),
+                        )
+                    }
+                    else => break,
+                };
+                recorder.push(entry);
+            }
+        });
+
+        Ok(log)
+    }
+
+    pub(crate) fn push(&self, entry: ConsoleEntry) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut buffer = self.buffer.lock().unwrap_or_else(|e| e.into_inner());
+        if buffer.entries.len() == self.capacity {
+            buffer.entries.pop_front();
+        }
+        buffer.entries.push_back(entry);
+        buffer.pushed += 1;
+    }
+
+    pub(crate) fn entries(&self) -> Vec<ConsoleEntry> {
+        self.last(self.capacity)
+    }
+
+    /// The newest `count` entries, oldest first
+    pub(crate) fn last(&self, count: usize) -> Vec<ConsoleEntry> {
+        let buffer = self.buffer.lock().unwrap_or_else(|e| e.into_inner());
+        let skip = buffer.entries.len().saturating_sub(count);
+        buffer.entries.iter().skip(skip).cloned().collect()
+    }
+
+    /// A mark for [`since`](Self::since)
+    pub(crate) fn mark(&self) -> u64 {
+        self.buffer.lock().unwrap_or_else(|e| e.into_inner()).pushed
+    }
+
+    /// Entries pushed after `mark` and still buffered
+    pub(crate) fn since(&self, mark: u64) -> Vec<ConsoleEntry> {
+        let buffer = self.buffer.lock().unwrap_or_else(|e| e.into_inner());
+        let new = (buffer.pushed - mark).min(buffer.entries.len() as u64) as usize;
+        buffer.entries.iter().skip(buffer.entries.len() - new).cloned().collect()
+    }
+}
+
+/// A console argument as text
+fn describe(arg: &RemoteObject) -> String {
+    match &arg.value {
+        Some(serde_json::Value::String(text)) => text.clone(),
+        Some(value) => value.to_string(),
+        None => arg.description.clone().unwrap_or_default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log(capacity: usize) -> ConsoleLog {
+        ConsoleLog {
+            buffer: Arc::default(),
+            capacity,
+        }
+    }
+
+    fn push(log: &ConsoleLog, message: &str) {
+        log.push(ConsoleEntry::new("console", "info", message.to_string(), None));
+    }
+
+    fn messages(entries: Vec<ConsoleEntry>) -> Vec<String> {
+        entries.into_iter().map(|e| e.message).collect()
+    }
+
+    #[test]
+    fn test_console_log_capacity() {
+        let log = log(2);
+        for message in ["one", "two", "three"] {
+            push(&log, message);
+        }
+
+        assert_eq!(messages(log.entries()), ["two", "three"]);
+        assert_eq!(messages(log.last(1)), ["three"]);
+    }
+
+    #[test]
+    fn test_console_log_since() {
+        let log = log(3);
+        push(&log, "before");
+        let mark = log.mark();
+        assert!(log.since(mark).is_empty());
+
+        push(&log, "during 1");
+        push(&log, "during 2");
+        assert_eq!(messages(log.since(mark)), ["during 1", "during 2"]);
+
+        // Only what is still buffered
+        push(&log, "during 3");
+        push(&log, "during 4");
+        assert_eq!(messages(log.since(mark)), ["during 2", "during 3", "during 4"]);
+    }
+}
@@ -0,0 +1,224 @@
+//! JavaScript dialogs (alert, confirm, prompt, beforeunload)
+//!
+//! An open dialog blocks the page, so every tab is watched from creation and
+//! dialogs are answered by `ExecutorConfig::dialog_policy`. With
+//! `DialogPolicy::Queue` the dialog stays open for a `HandleDialog` action
+//! to decide; an action that opens one, or starts while one is open, fails
+//! with `ActionError::DialogOpen` instead of waiting for its timeout.
+
+use crate::actions::{ActionError, ActionOutput, ActionResult, BrowserAction};
+use chromiumoxide::cdp::browser_protocol::page::{
+    EventJavascriptDialogClosed, EventJavascriptDialogOpening, HandleJavaScriptDialogParams,
+};
+use chromiumoxide::page::Page;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
+use tracing::{info, warn};
+
+/// What to do when a page opens a dialog
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DialogPolicy {
+    /// Accept, with the default text for prompts
+    Accept,
+
+    /// Dismiss (cancel confirms and prompts)
+    #[default]
+    Dismiss,
+
+    /// Leave it open for a `HandleDialog` action
+    Queue,
+}
+
+/// A dialog a page opened, the `data` of a `HandleDialog` output as JSON
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DialogInfo {
+    /// `alert`, `confirm`, `prompt` or `beforeunload`
+    pub kind: String,
+    pub message: String,
+    pub default_prompt: Option<String>,
+    /// URL of the frame that opened it
+    pub url: String,
+}
+
+impl DialogInfo {
+    fn from_event(event: &EventJavascriptDialogOpening) -> Self {
+        Self {
+            kind: event.r#type.as_ref().to_string(),
+            message: event.message.clone(),
+            default_prompt: event.default_prompt.clone(),
+            url: event.url.clone(),
+        }
+    }
+}
+
+/// A tab's dialog watcher, which knows the dialog left open, if any
+#[derive(Debug, Clone)]
+pub(crate) struct Dialogs {
+    pending: watch::Receiver<Option<DialogInfo>>,
+}
+
+impl Dialogs {
+    /// Answer `page`'s dialogs by `policy` from now on
+    pub(crate) async fn watch(page: &Page, policy: DialogPolicy) -> chromiumoxide::Result<Self> {
+        let mut opening = page
+            .event_listener::<EventJavascriptDialogOpening>()
+            .await?;
+        let mut closed = page.event_listener::<EventJavascriptDialogClosed>().await?;
+        let (sender, pending) = watch::channel(None);
+        let page = page.clone();
+
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    Some(event) = opening.next() => {
+                        let dialog = DialogInfo::from_event(&event);
+                        let accept = match policy {
+                            DialogPolicy::Accept => true,
+                            DialogPolicy::Dismiss => false,
+                            DialogPolicy::Queue => {
+                                info!("{} dialog left open: {:?}", dialog.kind, dialog.message);
+                                sender.send_replace(Some(dialog));
+                                continue;
+                            }
+                        };
+
+                        let answer = if accept { "Accepting" } else { "Dismissing" };
+                        info!("{} {} dialog: {:?}", answer, dialog.kind, dialog.message);
+                        let mut params = HandleJavaScriptDialogParams::new(accept);
+                        params.prompt_text = dialog.default_prompt.filter(|_| accept);
+                        if let Err(e) = page.execute(params).await {
+                            warn!("Failed to answer {} dialog: {}", dialog.kind, e);
+                        }
+                    }
+                    Some(_) = closed.next() => {
+                        sender.send_replace(None);
+                    }
+                    else => break,
+                }
+            }
+        });
+
+        Ok(Self { pending })
+    }
+
+    /// The dialog left open for a decision
+    pub(crate) fn pending(&self) -> Option<DialogInfo> {
+        self.pending.borrow().clone()
+    }
+
+    /// Wait until a dialog is left open, and return it
+    pub(crate) async fn opened(&self) -> DialogInfo {
+        let mut pending = self.pending.clone();
+        let dialog = match pending.wait_for(Option::is_some).await {
+            Ok(dialog) => dialog.clone(),
+            // The watcher only stops with the page; nothing will open
+            Err(_) => None,
+        };
+        match dialog {
+            Some(dialog) => dialog,
+            None => std::future::pending().await,
+        }
+    }
+}
+
+/// Error for an action blocked by `dialog`
+pub(crate) fn blocked(dialog: &DialogInfo) -> ActionError {
+    ActionError::DialogOpen(format!(
+        "{} {:?}; use HandleDialog",
+        dialog.kind, dialog.message
+    ))
+}
+
+/// Run a `HandleDialog` action: accept or dismiss the open dialog
+pub(crate) async fn handle(
+    page: &Page,
+    dialogs: &Dialogs,
+    action: BrowserAction,
+) -> ActionResult<ActionOutput> {
+    let start = std::time::Instant::now();
+    let BrowserAction::HandleDialog {
+        accept,
+        prompt_text,
+    } = action
+    else {
+        return Err(ActionError::ActionFailed(format!(
+            "Not a dialog action: {:?}",
+            action
+        )));
+    };
+
+    let dialog = dialogs
+        .pending()
+        .ok_or_else(|| ActionError::ActionFailed("No dialog is open".to_string()))?;
+
+    let mut params = HandleJavaScriptDialogParams::new(accept);
+    params.prompt_text = prompt_text
+        .or_else(|| dialog.default_prompt.clone())
+        .filter(|_| accept && dialog.kind == "prompt");
+    page.execute(params)
+        .await
+        .map_err(|e| ActionError::BrowserError(e.to_string()))?;
+
+    let data =
+        serde_json::to_string(&dialog).map_err(|e| ActionError::ActionFailed(e.to_string()))?;
+
+    Ok(ActionOutput::ok(Some(data), start.elapsed()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dialog_config() {
+        let policies: Vec<DialogPolicy> =
+            serde_json::from_str(r#"["accept", "dismiss", "queue"]"#).unwrap();
+        assert_eq!(
+            policies,
+            vec![
+                DialogPolicy::Accept,
+                DialogPolicy::Dismiss,
+                DialogPolicy::Queue
+            ]
+        );
+        assert_eq!(DialogPolicy::default(), DialogPolicy::Dismiss);
+
+        let action: BrowserAction =
+            serde_json::from_str(r#"{"type": "handle_dialog", "accept": true}"#).unwrap();
+        assert!(matches!(
+            action,
+            BrowserAction::HandleDialog {
+                accept: true,
+                prompt_text: None
+            }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_pending_dialog() {
+        let (sender, pending) = watch::channel(None);
+        let dialogs = Dialogs { pending };
+        assert!(dialogs.pending().is_none());
+
+        let dialog = DialogInfo {
+            kind: "confirm".to_string(),
+            message: "Delete?".to_string(),
+            default_prompt: None,
+            url: "https://example.com".to_string(),
+        };
+        let opened = tokio::spawn({
+            let dialogs = dialogs.clone();
+            async move { dialogs.opened().await }
+        });
+        sender.send_replace(Some(dialog.clone()));
+
+        assert_eq!(opened.await.unwrap(), dialog);
+        assert_eq!(dialogs.pending(), Some(dialog.clone()));
+        assert_eq!(
+            blocked(&dialog).to_string(),
+            r#"Blocked by dialog: confirm "Delete?"; use HandleDialog"#
+        );
+    }
+}
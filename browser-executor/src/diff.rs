@@ -0,0 +1,273 @@
+//! Screenshot comparison
+//!
+//! [`Screenshot::diff`] answers "did the page change?" two ways: the share of
+//! pixels that differ by more than a small tolerance (antialiasing and JPEG
+//! noise aside), and the distance between perceptual hashes, which stays
+//! small for a page that merely shifted or re-rendered and grows with real
+//! layout changes. The result carries an annotated image: the newer
+//! screenshot faded, with changed pixels and their bounding box in red.
+
+use crate::screenshot::{Screenshot, ScreenshotError};
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImageView, ImageFormat, Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+
+/// Largest per-channel difference still counted as the same pixel
+pub const DEFAULT_TOLERANCE: u8 = 16;
+
+const CHANGED: Rgba<u8> = Rgba([255, 0, 0, 255]);
+
+/// Rectangle around the changed pixels, in image pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiffRegion {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Differences between two screenshots
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiffResult {
+    /// Pixels that differ, counting those only one image has
+    pub changed_pixels: u64,
+
+    /// Pixels compared (the larger width times the larger height)
+    pub total_pixels: u64,
+
+    /// `changed_pixels / total_pixels`
+    pub changed_ratio: f64,
+
+    /// Hamming distance between the 64-bit difference hashes (0-64; under
+    /// about 5 looks the same)
+    pub hash_distance: u32,
+
+    /// Bounding box of the changed pixels
+    pub changed_region: Option<DiffRegion>,
+
+    /// Annotated PNG
+    #[serde(skip)]
+    pub diff_image: Vec<u8>,
+}
+
+impl DiffResult {
+    /// Whether any pixel changed beyond the tolerance
+    pub fn is_changed(&self) -> bool {
+        self.changed_pixels > 0
+    }
+}
+
+impl Screenshot {
+    /// Compare with a later screenshot, with [`DEFAULT_TOLERANCE`]
+    pub fn diff(&self, other: &Screenshot) -> Result<DiffResult, ScreenshotError> {
+        self.diff_with_tolerance(other, DEFAULT_TOLERANCE)
+    }
+
+    /// Compare with a later screenshot, counting pixels as changed when a
+    /// channel differs by more than `tolerance`
+    pub fn diff_with_tolerance(
+        &self,
+        other: &Screenshot,
+        tolerance: u8,
+    ) -> Result<DiffResult, ScreenshotError> {
+        let before = decode(&self.data)?;
+        let after = decode(&other.data)?;
+
+        let (width, height) = (
+            before.width().max(after.width()),
+            before.height().max(after.height()),
+        );
+        let (before_pixels, after_pixels) = (before.to_rgba8(), after.to_rgba8());
+        let mut annotated = faded(&after_pixels, width, height);
+
+        let mut changed_pixels = 0;
+        let mut bounds: Option<(u32, u32, u32, u32)> = None;
+        for y in 0..height {
+            for x in 0..width {
+                let a = pixel(&before_pixels, x, y);
+                let b = pixel(&after_pixels, x, y);
+                let same = match (a, b) {
+                    (Some(a), Some(b)) => a
+                        .0
+                        .iter()
+                        .zip(b.0.iter())
+                        .all(|(a, b)| a.abs_diff(*b) <= tolerance),
+                    _ => false,
+                };
+                if same {
+                    continue;
+                }
+
+                changed_pixels += 1;
+                annotated.put_pixel(x, y, CHANGED);
+                bounds = Some(match bounds {
+                    None => (x, y, x, y),
+                    Some((left, top, right, bottom)) => {
+                        (left.min(x), top.min(y), right.max(x), bottom.max(y))
+                    }
+                });
+            }
+        }
+
+        let changed_region = bounds.map(|(left, top, right, bottom)| DiffRegion {
+            x: left,
+            y: top,
+            width: right - left + 1,
+            height: bottom - top + 1,
+        });
+        if let Some(region) = changed_region {
+            outline(&mut annotated, region);
+        }
+
+        let mut diff_image = Vec::new();
+        DynamicImage::ImageRgba8(annotated)
+            .write_to(&mut Cursor::new(&mut diff_image), ImageFormat::Png)
+            .map_err(|e| ScreenshotError::EncodingError(e.to_string()))?;
+
+        let total_pixels = width as u64 * height as u64;
+        Ok(DiffResult {
+            changed_pixels,
+            total_pixels,
+            changed_ratio: if total_pixels == 0 {
+                0.0
+            } else {
+                changed_pixels as f64 / total_pixels as f64
+            },
+            hash_distance: (dhash(&before) ^ dhash(&after)).count_ones(),
+            changed_region,
+            diff_image,
+        })
+    }
+}
+
+fn decode(data: &[u8]) -> Result<DynamicImage, ScreenshotError> {
+    image::load_from_memory(data).map_err(|e| ScreenshotError::ProcessingError(e.to_string()))
+}
+
+fn pixel(image: &RgbaImage, x: u32, y: u32) -> Option<Rgba<u8>> {
+    (x < image.width() && y < image.height()).then(|| *image.get_pixel(x, y))
+}
+
+/// `image` in light grey, on white where it has no pixels
+fn faded(image: &RgbaImage, width: u32, height: u32) -> RgbaImage {
+    RgbaImage::from_fn(width, height, |x, y| match pixel(image, x, y) {
+        Some(Rgba([r, g, b, _])) => {
+            let luma = (r as u32 * 299 + g as u32 * 587 + b as u32 * 114) / 1000;
+            let light = (255 - (255 - luma) / 3) as u8;
+            Rgba([light, light, light, 255])
+        }
+        None => Rgba([255, 255, 255, 255]),
+    })
+}
+
+/// A 2px red frame just outside `region`, clipped to the image
+fn outline(image: &mut RgbaImage, region: DiffRegion) {
+    let left = region.x as i64 - 2;
+    let top = region.y as i64 - 2;
+    let right = (region.x + region.width) as i64 + 1;
+    let bottom = (region.y + region.height) as i64 + 1;
+    let (width, height) = (image.width() as i64, image.height() as i64);
+
+    for y in top.max(0)..=bottom.min(height - 1) {
+        for x in left.max(0)..=right.min(width - 1) {
+            let on_edge = x < left + 2 || x > right - 2 || y < top + 2 || y > bottom - 2;
+            if on_edge {
+                image.put_pixel(x as u32, y as u32, CHANGED);
+            }
+        }
+    }
+}
+
+/// Difference hash: whether each pixel of a 9x8 greyscale thumbnail is
+/// brighter than its right neighbour
+fn dhash(image: &DynamicImage) -> u64 {
+    let thumbnail = image.resize_exact(9, 8, FilterType::Triangle).to_luma8();
+    let mut hash = 0;
+    for y in 0..8 {
+        for x in 0..8 {
+            let brighter = thumbnail.get_pixel(x, y).0[0] > thumbnail.get_pixel(x + 1, y).0[0];
+            hash = hash << 1 | brighter as u64;
+        }
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::screenshot::ScreenshotFormat;
+
+    fn screenshot(image: RgbaImage) -> Screenshot {
+        let mut data = Vec::new();
+        DynamicImage::ImageRgba8(image.clone())
+            .write_to(&mut Cursor::new(&mut data), ImageFormat::Png)
+            .unwrap();
+        Screenshot {
+            size_bytes: data.len(),
+            data,
+            format: ScreenshotFormat::Png,
+            width: image.width(),
+            height: image.height(),
+        }
+    }
+
+    fn gradient() -> RgbaImage {
+        RgbaImage::from_fn(64, 48, |x, _| Rgba([(x * 4) as u8, 100, 100, 255]))
+    }
+
+    #[test]
+    fn test_diff_identical() {
+        let before = screenshot(gradient());
+        let mut noisy = gradient();
+        noisy.put_pixel(3, 3, Rgba([13, 105, 95, 255]));
+
+        let diff = before.diff(&screenshot(noisy)).unwrap();
+
+        assert!(!diff.is_changed());
+        assert_eq!(diff.hash_distance, 0);
+        assert_eq!(diff.changed_region, None);
+        assert!(image::load_from_memory(&diff.diff_image).is_ok());
+    }
+
+    #[test]
+    fn test_diff_changed_region() {
+        let mut after = gradient();
+        for y in 10..20 {
+            for x in 30..35 {
+                after.put_pixel(x, y, Rgba([0, 0, 255, 255]));
+            }
+        }
+
+        let diff = screenshot(gradient()).diff(&screenshot(after)).unwrap();
+
+        assert_eq!(diff.changed_pixels, 50);
+        assert_eq!(diff.total_pixels, 64 * 48);
+        assert_eq!(
+            diff.changed_region,
+            Some(DiffRegion {
+                x: 30,
+                y: 10,
+                width: 5,
+                height: 10
+            })
+        );
+        let annotated = image::load_from_memory(&diff.diff_image).unwrap().to_rgba8();
+        assert_eq!(*annotated.get_pixel(32, 15), CHANGED);
+        assert_eq!(*annotated.get_pixel(28, 15), CHANGED);
+        assert_ne!(*annotated.get_pixel(10, 40), CHANGED);
+    }
+
+    #[test]
+    fn test_diff_size_and_hash() {
+        let before = screenshot(gradient());
+        let flipped = image::imageops::flip_horizontal(&gradient());
+        let taller = RgbaImage::from_fn(64, 60, |x, y| *flipped.get_pixel(x, y.min(47)));
+
+        let diff = before.diff(&screenshot(taller)).unwrap();
+
+        assert_eq!(diff.total_pixels, 64 * 60);
+        assert!(diff.changed_ratio > 0.9);
+        assert!(diff.hash_distance > 32);
+    }
+}
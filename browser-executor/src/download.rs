@@ -0,0 +1,851 @@
+//! Tracked file downloads
+//!
+//! `BrowserAction::Download` starts a download, either from a URL or by
+//! clicking an element, and follows it through CDP `Browser.downloadProgress`
+//! events for its GUID, logging every tenth of the way. Chrome saves the file
+//! under the download directory by its GUID; once complete it is moved to
+//! `save_to` (or its suggested name), numbered rather than replace a file
+//! already there, and hashed. Downloads over `max_size_mb` or the time limit
+//! are cancelled and deleted.
+//!
+//! A launched browser denies downloads no `Download` action asked for,
+//! unless `page_downloads` is set. Then downloads a click starts are saved
+//! under the download directory by GUID, cancelled over `max_size_mb`, and
+//! can be waited for with `WaitUntil` and `download_complete`; the files are
+//! deleted when the browser closes. A browser the executor attached to keeps
+//! downloading as it did.
+
+use crate::actions::{ActionError, ActionExecutor, ActionOutput, ActionResult, BrowserAction};
+use crate::executor::ExecutorConfig;
+use crate::network::Traffic;
+use chromiumoxide::browser::Browser;
+use chromiumoxide::cdp::browser_protocol::browser::{
+    CancelDownloadParams, DownloadProgressState, EventDownloadProgress, EventDownloadWillBegin,
+    SetDownloadBehaviorBehavior, SetDownloadBehaviorParams,
+};
+use chromiumoxide::page::Page;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+use tracing::{debug, info, warn};
+
+/// Download settings, set in `ExecutorConfig::downloads`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DownloadConfig {
+    /// Where downloads are saved (defaults to a `browser-executor-downloads`
+    /// directory under the system temp dir); `save_to` must stay inside it
+    pub dir: Option<PathBuf>,
+
+    /// Cancel downloads larger than this (MB)
+    pub max_size_mb: u64,
+
+    /// Time limit for a whole download (seconds)
+    pub timeout_secs: u64,
+
+    /// Let pages download without a `Download` action, for `WaitUntil`
+    /// and `download_complete` (launched browsers only)
+    pub page_downloads: bool,
+}
+
+impl Default for DownloadConfig {
+    fn default() -> Self {
+        Self {
+            dir: None,
+            max_size_mb: 100,
+            timeout_secs: 120,
+            page_downloads: false,
+        }
+    }
+}
+
+impl DownloadConfig {
+    /// The download directory, created if missing
+    pub(crate) fn dir(&self) -> ActionResult<PathBuf> {
+        let dir = self
+            .dir
+            .clone()
+            .unwrap_or_else(|| std::env::temp_dir().join("browser-executor-downloads"));
+        std::fs::create_dir_all(&dir)
+            .and_then(|_| dir.canonicalize())
+            .map_err(|e| ActionError::DownloadFailed(format!("{}: {}", dir.display(), e)))
+    }
+}
+
+/// A finished download, the `data` of a `Download` action's output as JSON
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DownloadResult {
+    pub url: String,
+    pub suggested_filename: String,
+    pub path: PathBuf,
+    pub size_bytes: u64,
+    /// Hex-encoded SHA-256 of the file
+    pub sha256: String,
+}
+
+/// A download finished outside a `Download` action, in the `data` of a
+/// `WaitUntil` for `download_complete` (as a JSON array)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FinishedDownload {
+    pub url: String,
+    pub suggested_filename: String,
+    /// The file, named by the download's GUID (none when attached to a
+    /// browser saving elsewhere)
+    pub path: Option<PathBuf>,
+}
+
+/// Finished downloads kept for `download_complete`; older ones are dropped
+const MAX_FINISHED: usize = 100;
+
+/// The browser's downloads, followed from launch
+#[derive(Debug, Clone)]
+pub(crate) struct Downloads {
+    state: Arc<watch::Sender<DownloadState>>,
+}
+
+#[derive(Debug, Default)]
+struct DownloadState {
+    /// In progress: URL and suggested name by GUID
+    active: HashMap<String, (String, String)>,
+    /// Finished and not yet collected
+    finished: Vec<FinishedDownload>,
+    /// GUIDs of downloads a `Download` action took care of
+    claimed: HashSet<String>,
+    /// `Download` actions running, allowing downloads while any is
+    allowing: usize,
+    /// Files saved by downloads no action claimed, deleted on close
+    saved: Vec<PathBuf>,
+    /// Where downloads are saved by GUID, if the executor chose
+    dir: Option<PathBuf>,
+}
+
+impl Default for Downloads {
+    fn default() -> Self {
+        Self {
+            state: Arc::new(watch::channel(DownloadState::default()).0),
+        }
+    }
+}
+
+impl Downloads {
+    /// Set what `browser` does with downloads no action asked for and
+    /// follow its downloads, cancelling unclaimed ones over the size limit
+    /// through `page`
+    pub(crate) async fn watch(
+        config: &ExecutorConfig,
+        browser: &Browser,
+        page: Page,
+    ) -> chromiumoxide::Result<Self> {
+        let mut begins = browser.event_listener::<EventDownloadWillBegin>().await?;
+        let mut progress = browser.event_listener::<EventDownloadProgress>().await?;
+        browser.execute(unrequested(config)).await?;
+
+        let dir = match config.remote_debugging_url {
+            Some(_) => None,
+            None => config.downloads.dir().ok(),
+        };
+        let downloads = Self::default();
+        downloads.state.send_modify(|state| state.dir = dir.clone());
+
+        let max_bytes = config.downloads.max_size_mb * 1024 * 1024;
+        let state = downloads.state.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    Some(event) = begins.next() => state.send_modify(|state| {
+                        let download = (event.url.clone(), event.suggested_filename.clone());
+                        state.active.insert(event.guid.clone(), download);
+                    }),
+                    Some(event) = progress.next() => {
+                        let bytes = event.received_bytes.max(event.total_bytes);
+                        if bytes > max_bytes as f64 && state.borrow().unclaimed(&event.guid) {
+                            warn!("Cancelling download {}: over size limit", event.guid);
+                            if let Err(e) = page
+                                .execute(CancelDownloadParams::new(event.guid.clone()))
+                                .await
+                            {
+                                warn!("Failed to cancel download {}: {}", event.guid, e);
+                            }
+                        }
+                        let path = dir.as_ref().map(|dir| dir.join(&event.guid));
+                        state.send_modify(|state| state.progress(&event.guid, &event.state, path));
+                    }
+                    else => break,
+                }
+            }
+        });
+        Ok(downloads)
+    }
+
+    /// Let `browser` download to `dir` until the matching `release`
+    async fn allow(&self, browser: &Browser, dir: &Path) -> chromiumoxide::Result<()> {
+        self.state.send_modify(|state| state.allowing += 1);
+        browser
+            .execute(SetDownloadBehaviorParams {
+                behavior: SetDownloadBehaviorBehavior::AllowAndName,
+                browser_context_id: None,
+                download_path: Some(dir.to_string_lossy().to_string()),
+                events_enabled: Some(true),
+            })
+            .await?;
+        Ok(())
+    }
+
+    /// End an `allow`, going back to what the browser does with downloads
+    /// no action asked for once no `Download` action is running
+    async fn release(&self, config: &ExecutorConfig, browser: &Browser) {
+        let mut last = false;
+        self.state.send_modify(|state| {
+            state.allowing = state.allowing.saturating_sub(1);
+            last = state.allowing == 0;
+        });
+        if last {
+            if let Err(e) = browser.execute(unrequested(config)).await {
+                warn!("Failed to reset download behavior: {}", e);
+            }
+        }
+    }
+
+    /// Delete the files of downloads no action claimed, and of those still
+    /// in progress
+    pub(crate) fn clean_up(&self) {
+        let mut files = Vec::new();
+        self.state.send_modify(|state| {
+            files = std::mem::take(&mut state.saved);
+            if let Some(dir) = &state.dir {
+                files.extend(state.active.keys().map(|guid| dir.join(guid)));
+            }
+        });
+        for file in files {
+            match std::fs::remove_file(&file) {
+                Ok(()) => debug!("Deleted download {}", file.display()),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => warn!("Failed to delete download {}: {}", file.display(), e),
+            }
+        }
+    }
+
+    /// Leave a download to the `Download` action that started it; false if
+    /// another action has
+    fn claim(&self, guid: &str) -> bool {
+        let mut claimed = false;
+        self.state.send_modify(|state| {
+            claimed = state.claimed.insert(guid.to_string());
+        });
+        claimed
+    }
+
+    /// Wait until no download is in progress and at least one has finished
+    /// since the last call; returns those that have
+    async fn finished(&self) -> Vec<FinishedDownload> {
+        let mut state = self.state.subscribe();
+        let _ = state
+            .wait_for(|state| state.active.is_empty() && !state.finished.is_empty())
+            .await;
+
+        let mut finished = Vec::new();
+        self.state.send_if_modified(|state| {
+            finished = std::mem::take(&mut state.finished);
+            false
+        });
+        finished
+    }
+}
+
+/// What a browser does with downloads no `Download` action asked for:
+/// saves them by GUID with `page_downloads`, denies them otherwise, or, when
+/// attached, does what it did
+fn unrequested(config: &ExecutorConfig) -> SetDownloadBehaviorParams {
+    let dir = match config.remote_debugging_url {
+        Some(_) => None,
+        None => config.downloads.dir().ok(),
+    };
+    let behavior = match (&dir, config.downloads.page_downloads) {
+        (None, _) => SetDownloadBehaviorBehavior::Default,
+        (Some(_), true) => SetDownloadBehaviorBehavior::AllowAndName,
+        (Some(_), false) => SetDownloadBehaviorBehavior::Deny,
+    };
+    let download_path = match behavior {
+        SetDownloadBehaviorBehavior::AllowAndName => dir,
+        _ => None,
+    };
+    SetDownloadBehaviorParams {
+        behavior,
+        browser_context_id: None,
+        download_path: download_path.map(|dir| dir.to_string_lossy().to_string()),
+        events_enabled: Some(true),
+    }
+}
+
+impl DownloadState {
+    /// Whether the download is in progress with no action claiming it
+    fn unclaimed(&self, guid: &str) -> bool {
+        self.active.contains_key(guid) && !self.claimed.contains(guid)
+    }
+
+    fn progress(&mut self, guid: &str, progress: &DownloadProgressState, path: Option<PathBuf>) {
+        let completed = match progress {
+            DownloadProgressState::InProgress => return,
+            DownloadProgressState::Completed => true,
+            DownloadProgressState::Canceled => false,
+        };
+        let Some((url, suggested_filename)) = self.active.remove(guid) else {
+            return;
+        };
+        if self.claimed.remove(guid) {
+            return;
+        }
+        if let Some(path) = &path {
+            self.saved.push(path.clone());
+        }
+        if completed {
+            if self.finished.len() >= MAX_FINISHED {
+                self.finished.remove(0);
+            }
+            self.finished.push(FinishedDownload {
+                url,
+                suggested_filename,
+                path,
+            });
+        }
+    }
+}
+
+/// Run a `WaitUntil` for `download_complete`, within `timeout_ms` or the
+/// download time limit
+pub(crate) async fn wait(
+    config: &ExecutorConfig,
+    downloads: &Downloads,
+    timeout_ms: Option<u64>,
+) -> ActionResult<ActionOutput> {
+    let start = std::time::Instant::now();
+    let timeout = timeout_ms
+        .map(Duration::from_millis)
+        .unwrap_or(Duration::from_secs(config.downloads.timeout_secs));
+
+    let finished = tokio::time::timeout(timeout, downloads.finished())
+        .await
+        .map_err(|_| {
+            ActionError::Timeout(format!(
+                "No download finished within {}ms",
+                timeout.as_millis()
+            ))
+        })?;
+    let data =
+        serde_json::to_string(&finished).map_err(|e| ActionError::ActionFailed(e.to_string()))?;
+
+    Ok(ActionOutput::ok(Some(data), start.elapsed()))
+}
+
+/// How far a `Download` action got
+#[derive(Debug, Default)]
+struct Started {
+    /// Set once the download began
+    guid: Option<String>,
+    received_bytes: f64,
+    /// 0 while unknown
+    total_bytes: f64,
+}
+
+impl Started {
+    /// Tenths of the download received, 0 while the size is unknown
+    fn tenths(&self) -> u32 {
+        if self.total_bytes > 0.0 {
+            (self.received_bytes * 10.0 / self.total_bytes) as u32
+        } else {
+            0
+        }
+    }
+}
+
+impl std::fmt::Display for Started {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mb = |bytes: f64| bytes / (1024.0 * 1024.0);
+        match (&self.guid, self.total_bytes > 0.0) {
+            (None, _) => write!(f, "not started"),
+            (Some(_), true) => write!(
+                f,
+                "{:.1} of {:.1} MB received",
+                mb(self.received_bytes),
+                mb(self.total_bytes)
+            ),
+            (Some(_), false) => write!(f, "{:.1} MB received", mb(self.received_bytes)),
+        }
+    }
+}
+
+/// Run a `Download` action on `page` within the download time limit,
+/// counting the file in `traffic`
+pub(crate) async fn run(
+    config: &ExecutorConfig,
+    browser: &Browser,
+    page: Page,
+    traffic: &Traffic,
+    downloads: &Downloads,
+    action: BrowserAction,
+) -> ActionResult<ActionOutput> {
+    let BrowserAction::Download {
+        url_or_click_selector,
+        save_to,
+    } = action
+    else {
+        return Err(ActionError::ActionFailed(format!(
+            "Not a download: {:?}",
+            action
+        )));
+    };
+
+    let start = std::time::Instant::now();
+    let dir = config.downloads.dir()?;
+    downloads
+        .allow(browser, &dir)
+        .await
+        .map_err(|e| ActionError::BrowserError(e.to_string()))?;
+
+    let timeout = Duration::from_secs(config.downloads.timeout_secs);
+    let mut started = Started::default();
+    let download = download(
+        config,
+        browser,
+        page,
+        downloads,
+        &url_or_click_selector,
+        save_to.as_deref(),
+        &mut started,
+    );
+
+    let result = tokio::time::timeout(timeout, download).await;
+    downloads.release(config, browser).await;
+    let result = match result {
+        Ok(result) => result?,
+        Err(_) => {
+            if let Some(guid) = &started.guid {
+                cancel(browser, &dir, guid).await;
+            }
+            return Err(ActionError::DownloadFailed(format!(
+                "Download timeout after {}s: {}",
+                timeout.as_secs(),
+                started
+            )));
+        }
+    };
+    traffic.add(result.size_bytes);
+    let data =
+        serde_json::to_string(&result).map_err(|e| ActionError::ActionFailed(e.to_string()))?;
+
+    Ok(ActionOutput::ok(Some(data), start.elapsed()))
+}
+
+async fn download(
+    config: &ExecutorConfig,
+    browser: &Browser,
+    page: Page,
+    downloads: &Downloads,
+    url_or_click_selector: &str,
+    save_to: Option<&str>,
+    started: &mut Started,
+) -> ActionResult<DownloadResult> {
+    let dir = config.downloads.dir()?;
+    let max_bytes = config.downloads.max_size_mb * 1024 * 1024;
+    let cdp = |e: chromiumoxide::error::CdpError| ActionError::BrowserError(e.to_string());
+
+    // Subscribe first so the download cannot begin unseen
+    let mut begins = browser
+        .event_listener::<EventDownloadWillBegin>()
+        .await
+        .map_err(cdp)?;
+    let mut progress = browser
+        .event_listener::<EventDownloadProgress>()
+        .await
+        .map_err(cdp)?;
+
+    trigger(config, page.clone(), url_or_click_selector).await?;
+
+    let wait = Duration::from_secs(config.default_timeout_secs);
+    let begin = tokio::time::timeout(wait, started_on(&page, downloads, &mut begins))
+        .await
+        .ok()
+        .flatten()
+        .ok_or_else(|| {
+            let message = format!("No download started by {}", url_or_click_selector);
+            ActionError::DownloadFailed(message)
+        })?;
+    info!("Downloading {} ({})", begin.url, begin.suggested_filename);
+    started.guid = Some(begin.guid.clone());
+
+    let destination = destination(&dir, save_to, &begin.suggested_filename)?;
+    let mut logged_tenths = 0;
+
+    while let Some(event) = progress.next().await {
+        if event.guid != begin.guid {
+            continue;
+        }
+        started.received_bytes = event.received_bytes;
+        started.total_bytes = event.total_bytes;
+        debug!(
+            "Download {}: {}/{} bytes",
+            begin.guid, event.received_bytes, event.total_bytes
+        );
+        let tenths = started.tenths();
+        if tenths > logged_tenths && matches!(event.state, DownloadProgressState::InProgress) {
+            logged_tenths = tenths;
+            info!("Downloading {}: {}", begin.suggested_filename, started);
+        }
+
+        if event.received_bytes.max(event.total_bytes) > max_bytes as f64 {
+            warn!("Cancelling download of {}: over size limit", begin.url);
+            cancel(browser, &dir, &begin.guid).await;
+            return Err(ActionError::DownloadFailed(format!(
+                "{} exceeds the {} MB limit",
+                begin.url, config.downloads.max_size_mb
+            )));
+        }
+
+        match event.state {
+            DownloadProgressState::InProgress => {}
+            DownloadProgressState::Completed => break,
+            DownloadProgressState::Canceled => {
+                remove_partial(&dir.join(&begin.guid));
+                return Err(ActionError::DownloadFailed(format!(
+                    "Download of {} was cancelled",
+                    begin.url
+                )));
+            }
+        }
+    }
+
+    // Saved by GUID until complete
+    let partial = dir.join(&begin.guid);
+    let (path, size_bytes, sha256) = finish(partial, destination.clone())
+        .await
+        .map_err(|e| ActionError::DownloadFailed(format!("{}: {}", destination.display(), e)))?;
+
+    Ok(DownloadResult {
+        url: begin.url.clone(),
+        suggested_filename: begin.suggested_filename.clone(),
+        path,
+        size_bytes,
+        sha256,
+    })
+}
+
+/// The next download started from one of `page`'s frames that no other
+/// `Download` action took, claimed for this one
+async fn started_on(
+    page: &Page,
+    downloads: &Downloads,
+    begins: &mut (impl futures::Stream<Item = Arc<EventDownloadWillBegin>> + Unpin),
+) -> Option<Arc<EventDownloadWillBegin>> {
+    while let Some(begin) = begins.next().await {
+        let frames = page.frames().await.unwrap_or_default();
+        if !frames.contains(&begin.frame_id) {
+            debug!("Download {} is from another page", begin.guid);
+            continue;
+        }
+        if downloads.claim(&begin.guid) {
+            return Some(begin);
+        }
+    }
+    None
+}
+
+/// Cancel the download `guid` and delete what it saved in `dir`
+async fn cancel(browser: &Browser, dir: &Path, guid: &str) {
+    if let Err(e) = browser
+        .execute(CancelDownloadParams::new(guid.to_string()))
+        .await
+    {
+        warn!("Failed to cancel download {}: {}", guid, e);
+    }
+    remove_partial(&dir.join(guid));
+}
+
+fn remove_partial(path: &Path) {
+    match std::fs::remove_file(path) {
+        Ok(()) => debug!("Deleted partial download {}", path.display()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => warn!("Failed to delete download {}: {}", path.display(), e),
+    }
+}
+
+/// Start the download: open an http(s) URL as a link, or click a selector
+async fn trigger(
+    config: &ExecutorConfig,
+    page: Page,
+    url_or_click_selector: &str,
+) -> ActionResult<()> {
+    let is_url = url::Url::parse(url_or_click_selector)
+        .map(|url| matches!(url.scheme(), "http" | "https"))
+        .unwrap_or(false);
+
+    if is_url {
+        // A link with `download` saves rather than navigates where allowed
+        let href = serde_json::to_string(url_or_click_selector)
+            .map_err(|e| ActionError::ActionFailed(e.to_string()))?;
+        page.evaluate(format!(
+            "(() => {{ const a = document.createElement('a'); a.href = {}; a.download = ''; \
+             document.body.appendChild(a); a.click(); a.remove(); }})()",
+            href
+        ))
+        .await
+        .map_err(|e| ActionError::BrowserError(e.to_string()))?;
+    } else {
+        ActionExecutor::new(page, Duration::from_secs(config.default_timeout_secs))
+            .execute(BrowserAction::Click {
+                selector: url_or_click_selector.into(),
+                wait_for: None,
+                pierce_shadow: false,
+            })
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Where a download ends up: `save_to` inside `dir`, or its suggested name
+///
+/// A `save_to` ending in `/`, or naming an existing directory, gets the
+/// suggested name appended.
+pub(crate) fn destination(
+    dir: &Path,
+    save_to: Option<&str>,
+    suggested: &str,
+) -> ActionResult<PathBuf> {
+    // Only the last component of a server-suggested name is trusted
+    let name = Path::new(suggested)
+        .file_name()
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("download"));
+
+    let Some(save_to) = save_to else {
+        return Ok(dir.join(name));
+    };
+
+    let requested = Path::new(save_to);
+    if requested
+        .components()
+        .any(|component| component == Component::ParentDir)
+        || (requested.is_absolute() && !requested.starts_with(dir))
+    {
+        return Err(ActionError::DownloadFailed(format!(
+            "save_to must stay inside {}: {}",
+            dir.display(),
+            save_to
+        )));
+    }
+
+    let path = dir.join(requested);
+    if save_to.ends_with('/') || path.is_dir() {
+        Ok(path.join(name))
+    } else {
+        Ok(path)
+    }
+}
+
+/// Move the finished file to `to`, or to `name (1).ext`, `name (2).ext`, ...
+/// when taken; returns where, its size and SHA-256
+async fn finish(from: PathBuf, to: PathBuf) -> std::io::Result<(PathBuf, u64, String)> {
+    tokio::task::spawn_blocking(move || {
+        if let Some(parent) = to.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        // A link fails where a rename would replace the file there
+        let mut path = to.clone();
+        let mut copies = 0;
+        loop {
+            match std::fs::hard_link(&from, &path) {
+                Ok(()) => break,
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                    copies += 1;
+                    path = numbered(&to, copies);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        std::fs::remove_file(&from)?;
+
+        let mut file = std::fs::File::open(&path)?;
+        let mut hasher = Sha256::new();
+        let size = std::io::copy(&mut file, &mut hasher)?;
+        Ok((path, size, hex::encode(hasher.finalize())))
+    })
+    .await
+    .map_err(std::io::Error::other)?
+}
+
+/// `report (n).pdf` for `report.pdf`
+fn numbered(path: &Path, n: u32) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(extension) => format!("{} ({}).{}", stem, n, extension.to_string_lossy()),
+        None => format!("{} ({})", stem, n),
+    };
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_destination() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        std::fs::create_dir(root.join("reports")).unwrap();
+
+        let resolve = |save_to| destination(&root, save_to, "../../q3 report.pdf");
+        assert_eq!(resolve(None).unwrap(), root.join("q3 report.pdf"));
+        assert_eq!(resolve(Some("a.pdf")).unwrap(), root.join("a.pdf"));
+        assert_eq!(
+            resolve(Some("reports")).unwrap(),
+            root.join("reports/q3 report.pdf")
+        );
+        assert_eq!(
+            resolve(Some("new/")).unwrap(),
+            root.join("new/q3 report.pdf")
+        );
+
+        let inside = root.join("b.pdf").to_string_lossy().to_string();
+        assert_eq!(resolve(Some(&inside)).unwrap(), root.join("b.pdf"));
+        assert!(resolve(Some("../escape.pdf")).is_err());
+        assert!(resolve(Some("/etc/passwd")).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_downloads_finished() {
+        let downloads = Downloads::default();
+        let begin = |guid: &str, url: &str| {
+            downloads.state.send_modify(|state| {
+                let download = (url.to_string(), format!("{}.csv", guid));
+                state.active.insert(guid.to_string(), download);
+            })
+        };
+        let progress = |guid: &str, progress| {
+            let path = Some(PathBuf::from("/downloads").join(guid));
+            downloads
+                .state
+                .send_modify(|state| state.progress(guid, &progress, path));
+        };
+
+        begin("a", "https://example.com/a.csv");
+        begin("b", "https://example.com/b.csv");
+        begin("c", "https://example.com/c.csv");
+        downloads.claim("c");
+        progress("a", DownloadProgressState::Completed);
+        progress("c", DownloadProgressState::Completed);
+
+        // Waits for b
+        let waiter = downloads.clone();
+        let finished = tokio::spawn(async move { waiter.finished().await });
+        tokio::task::yield_now().await;
+        assert!(!finished.is_finished());
+        progress("b", DownloadProgressState::Canceled);
+
+        let finished = finished.await.unwrap();
+        assert_eq!(
+            finished,
+            [FinishedDownload {
+                url: "https://example.com/a.csv".to_string(),
+                suggested_filename: "a.csv".to_string(),
+                path: Some(PathBuf::from("/downloads/a")),
+            }]
+        );
+        assert!(downloads.state.borrow().finished.is_empty());
+    }
+
+    #[test]
+    fn test_downloads_clean_up() {
+        let dir = tempfile::tempdir().unwrap();
+        let downloads = Downloads::default();
+        downloads
+            .state
+            .send_modify(|state| state.dir = Some(dir.path().to_path_buf()));
+
+        let download = |guid: &str| {
+            std::fs::write(dir.path().join(guid), guid).unwrap();
+            downloads.state.send_modify(|state| {
+                let url = format!("https://example.com/{}", guid);
+                state
+                    .active
+                    .insert(guid.to_string(), (url, guid.to_string()));
+            });
+        };
+        let complete = |guid: &str| {
+            let path = Some(dir.path().join(guid));
+            downloads
+                .state
+                .send_modify(|state| state.progress(guid, &DownloadProgressState::Completed, path));
+        };
+
+        for i in 0..MAX_FINISHED + 5 {
+            download(&i.to_string());
+            complete(&i.to_string());
+        }
+        let finished = downloads.state.borrow().finished.clone();
+        assert_eq!(finished.len(), MAX_FINISHED);
+        assert_eq!(finished[0].suggested_filename, "5");
+
+        download("claimed");
+        assert!(downloads.claim("claimed"));
+        assert!(!downloads.claim("claimed"));
+        download("partial");
+        complete("claimed");
+
+        downloads.clean_up();
+        let left: Vec<_> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(left, ["claimed"]);
+    }
+
+    #[tokio::test]
+    async fn test_finish_hashes_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let partial = dir.path().join("guid");
+        std::fs::write(&partial, "hello").unwrap();
+
+        let to = dir.path().join("out/hello.txt");
+        let (path, size, sha256) = finish(partial.clone(), to.clone()).await.unwrap();
+
+        assert_eq!(path, to);
+        assert_eq!(size, 5);
+        assert_eq!(
+            sha256,
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+        assert!(to.exists() && !partial.exists());
+
+        // The same name again is numbered, not replaced
+        std::fs::write(&partial, "hello again").unwrap();
+        let (path, size, _) = finish(partial.clone(), to.clone()).await.unwrap();
+        assert_eq!(path, dir.path().join("out/hello (1).txt"));
+        assert_eq!(size, 11);
+        assert_eq!(std::fs::read_to_string(&to).unwrap(), "hello");
+        assert_eq!(
+            numbered(Path::new("/d/README"), 2),
+            PathBuf::from("/d/README (2)")
+        );
+    }
+
+    #[test]
+    fn test_started() {
+        let mut started = Started::default();
+        assert_eq!(started.to_string(), "not started");
+
+        started.guid = Some("g".to_string());
+        started.received_bytes = 3.0 * 1024.0 * 1024.0;
+        assert_eq!(started.tenths(), 0);
+        assert_eq!(started.to_string(), "3.0 MB received");
+
+        started.total_bytes = 10.0 * 1024.0 * 1024.0;
+        assert_eq!(started.tenths(), 3);
+        assert_eq!(started.to_string(), "3.0 of 10.0 MB received");
+    }
+}
@@ -0,0 +1,231 @@
+//! Device emulation
+//!
+//! Pages can be shown as a phone, tablet or desktop: viewport size, device
+//! pixel ratio, mobile layout, touch events and user agent are set through
+//! CDP `Emulation.setDeviceMetricsOverride`, `setTouchEmulationEnabled` and
+//! `setUserAgentOverride`. `ExecutorConfig::device` applies a device to
+//! every tab of a session; the `EmulateDevice` action switches the active
+//! tab.
+
+use chromiumoxide::cdp::browser_protocol::emulation::{
+    ScreenOrientation, ScreenOrientationType, SetDeviceMetricsOverrideParams,
+    SetTouchEmulationEnabledParams, SetUserAgentOverrideParams,
+};
+use chromiumoxide::page::Page;
+use serde::{Deserialize, Serialize};
+
+/// A named device profile
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DevicePreset {
+    /// iPhone 15 Pro: 393x852 at 3x
+    #[serde(rename = "iphone")]
+    IPhone,
+
+    /// Pixel 8: 412x915 at 2.625x
+    #[serde(rename = "pixel")]
+    Pixel,
+
+    /// iPad Air: 820x1180 at 2x
+    #[serde(rename = "ipad")]
+    IPad,
+
+    /// Desktop Chrome at 1920x1080
+    #[serde(rename = "desktop-1080p")]
+    Desktop1080p,
+}
+
+/// Screen and input properties of an emulated device
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeviceProfile {
+    /// Viewport width (CSS pixels)
+    pub width: u32,
+
+    /// Viewport height (CSS pixels)
+    pub height: u32,
+
+    #[serde(default = "default_scale_factor")]
+    pub device_scale_factor: f64,
+
+    /// Mobile layout: meta viewport, overlay scrollbars, text autosizing
+    #[serde(default)]
+    pub mobile: bool,
+
+    /// Emit touch events
+    #[serde(default)]
+    pub touch: bool,
+
+    /// Replaces the session's user agent when set
+    #[serde(default)]
+    pub user_agent: Option<String>,
+}
+
+fn default_scale_factor() -> f64 {
+    1.0
+}
+
+/// A device, by preset name or as a full profile
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Device {
+    Preset(DevicePreset),
+    Custom(DeviceProfile),
+}
+
+impl DevicePreset {
+    pub fn profile(self) -> DeviceProfile {
+        let (width, height, device_scale_factor, mobile, user_agent) = match self {
+            DevicePreset::IPhone => (
+                393,
+                852,
+                3.0,
+                true,
+                "Mozilla/5.0 (iPhone; CPU iPhone OS 17_0 like Mac OS X) AppleWebKit/605.1.15 \
+                 (KHTML, like Gecko) Version/17.0 Mobile/15E148 Safari/604.1",
+            ),
+            DevicePreset::Pixel => (
+                412,
+                915,
+                2.625,
+                true,
+                "Mozilla/5.0 (Linux; Android 14; Pixel 8) AppleWebKit/537.36 \
+                 (KHTML, like Gecko) Chrome/120.0.0.0 Mobile Safari/537.36",
+            ),
+            DevicePreset::IPad => (
+                820,
+                1180,
+                2.0,
+                true,
+                "Mozilla/5.0 (iPad; CPU OS 17_0 like Mac OS X) AppleWebKit/605.1.15 \
+                 (KHTML, like Gecko) Version/17.0 Mobile/15E148 Safari/604.1",
+            ),
+            DevicePreset::Desktop1080p => (
+                1920,
+                1080,
+                1.0,
+                false,
+                "Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 \
+                 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36",
+            ),
+        };
+
+        DeviceProfile {
+            width,
+            height,
+            device_scale_factor,
+            mobile,
+            touch: mobile,
+            user_agent: Some(user_agent.to_string()),
+        }
+    }
+}
+
+impl Device {
+    pub fn profile(&self) -> DeviceProfile {
+        match self {
+            Device::Preset(preset) => preset.profile(),
+            Device::Custom(profile) => profile.clone(),
+        }
+    }
+}
+
+impl From<DevicePreset> for Device {
+    fn from(preset: DevicePreset) -> Self {
+        Device::Preset(preset)
+    }
+}
+
+/// Make `page` look like `device` until emulated otherwise
+///
+/// A mobile layout or touch change only takes full effect on the next load.
+pub(crate) async fn emulate(page: &Page, device: &Device) -> chromiumoxide::Result<()> {
+    let profile = device.profile();
+    page.execute(metrics(
+        profile.width,
+        profile.height,
+        profile.device_scale_factor,
+        profile.mobile,
+    ))
+    .await?;
+    page.execute(SetTouchEmulationEnabledParams::new(profile.touch))
+        .await?;
+    if let Some(user_agent) = profile.user_agent {
+        page.execute(SetUserAgentOverrideParams::new(user_agent))
+            .await?;
+    }
+    Ok(())
+}
+
+/// Device metrics for a viewport, in the orientation its shape suggests
+pub(crate) fn metrics(
+    width: u32,
+    height: u32,
+    device_scale_factor: f64,
+    mobile: bool,
+) -> SetDeviceMetricsOverrideParams {
+    let orientation = if width > height {
+        ScreenOrientation::new(ScreenOrientationType::LandscapePrimary, 90)
+    } else {
+        ScreenOrientation::new(ScreenOrientationType::PortraitPrimary, 0)
+    };
+
+    let mut params =
+        SetDeviceMetricsOverrideParams::new(width, height, device_scale_factor, mobile);
+    params.screen_width = Some(width.into());
+    params.screen_height = Some(height.into());
+    params.screen_orientation = Some(orientation);
+    params
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actions::BrowserAction;
+
+    #[test]
+    fn test_device_config() {
+        let devices: Vec<Device> = serde_json::from_str(
+            r#"["iphone", "desktop-1080p", {"width": 800, "height": 600, "touch": true}]"#,
+        )
+        .unwrap();
+
+        assert_eq!(devices[0], Device::Preset(DevicePreset::IPhone));
+        assert_eq!(devices[1].profile().width, 1920);
+        assert!(!devices[1].profile().mobile);
+        assert_eq!(
+            devices[2].profile(),
+            DeviceProfile {
+                width: 800,
+                height: 600,
+                device_scale_factor: 1.0,
+                mobile: false,
+                touch: true,
+                user_agent: None,
+            }
+        );
+
+        let pixel = DevicePreset::Pixel.profile();
+        assert!(pixel.mobile && pixel.touch);
+        assert!(pixel.user_agent.unwrap().contains("Android"));
+
+        let action: BrowserAction =
+            serde_json::from_str(r#"{"type": "emulate_device", "device": "ipad"}"#).unwrap();
+        assert!(matches!(
+            action,
+            BrowserAction::EmulateDevice {
+                device: Device::Preset(DevicePreset::IPad)
+            }
+        ));
+    }
+
+    #[test]
+    fn test_metrics_orientation() {
+        let portrait = metrics(393, 852, 3.0, true);
+        assert_eq!(
+            portrait.screen_orientation.unwrap().r#type,
+            ScreenOrientationType::PortraitPrimary
+        );
+        let landscape = metrics(1920, 1080, 1.0, false);
+        assert_eq!(landscape.screen_orientation.unwrap().angle, 90);
+        assert_eq!(landscape.screen_width, Some(1920));
+    }
+}
@@ -1,15 +1,46 @@
 //! Browser executor with resource limits and error recovery
 
-use crate::actions::{ActionExecutor, ActionOutput, ActionResult, BrowserAction};
+use crate::actions::{ActionError, ActionExecutor, ActionOutput, ActionResult, BrowserAction};
+use crate::archive;
+use crate::artifacts::{self, ArtifactConfig};
+use crate::consent;
+use crate::console::{ConsoleEntry, ConsoleLog};
+use crate::auth::CredentialProvider;
+use crate::capture::{self, Captures};
+use crate::dialog::{self, DialogPolicy, Dialogs};
+use crate::download::{self, DownloadConfig, Downloads};
+use crate::emulation::{self, Device};
+use crate::failure::{ErrorCode, Failure};
+use crate::health::{self, Health};
+use crate::humanize::HumanizeConfig;
+use crate::jail;
+use crate::monitor::{self, Breach};
+use crate::network::{self, NetworkRules, Traffic};
+use crate::plan::ActionPlan;
+use crate::pool::PoolStats;
+use crate::proxy::ProxyConfig;
+use crate::record;
+use crate::remote::{self, TabScope};
+use crate::sandbox::SandboxedProcess;
+use crate::stealth;
+use crate::tabs::{ActiveTab, Popups, TabWatchers, Tabs};
+use crate::telemetry;
+use crate::url_policy::{UrlPolicy, Violations};
+use crate::wait::UntilCondition;
 use chromiumoxide::browser::{Browser, BrowserConfig};
+use chromiumoxide::cdp::browser_protocol::network::Cookie;
+use chromiumoxide::error::CdpError;
+use chromiumoxide::handler::viewport::Viewport;
 use chromiumoxide::page::Page;
 use futures::StreamExt;
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use thiserror::Error;
 use tokio::sync::RwLock;
-use tracing::{debug, error, info, warn};
+use tracing::{debug, error, info, warn, Instrument};
 
 /// Browser executor errors
 #[derive(Error, Debug)]
@@ -31,20 +62,73 @@ pub enum ExecutorError {
 
     #[error("Action failed: {0}")]
     ActionFailed(String),
+
+    /// An action that failed, classified in `failure`
+    #[error("Action failed: {error}")]
+    Action {
+        error: ActionError,
+        failure: Failure,
+    },
+}
+
+impl ExecutorError {
+    /// The error's code, with the selector and URL involved for a failed
+    /// action
+    pub fn failure(&self) -> Failure {
+        let code = match self {
+            ExecutorError::Action { failure, .. } => return failure.clone(),
+            ExecutorError::LaunchFailed(_) => ErrorCode::LaunchFailed,
+            ExecutorError::BrowserCrashed(_) => ErrorCode::Crash,
+            ExecutorError::PageError(_) => ErrorCode::BrowserError,
+            ExecutorError::Timeout(_) => ErrorCode::Timeout,
+            ExecutorError::ResourceLimitExceeded(_) => ErrorCode::ResourceLimit,
+            ExecutorError::ActionFailed(_) => ErrorCode::ActionFailed,
+        };
+        Failure::new(code)
+    }
+
+    /// The error for a failed `action`, on a page at `page_url`
+    pub(crate) fn action(
+        error: ActionError,
+        action: &BrowserAction,
+        page_url: Option<String>,
+    ) -> Self {
+        let failure = Failure::of(error.code(), action, page_url);
+        ExecutorError::Action { error, failure }
+    }
 }
 
 /// Browser executor configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExecutorConfig {
-    /// Maximum memory usage (MB)
+    /// Maximum memory usage of the browser's processes, their resident
+    /// memory added up (MB; 0: no limit)
     pub max_memory_mb: u64,
 
+    /// Maximum CPU usage of the browser's processes, sustained (percent of
+    /// one core)
+    #[serde(default)]
+    pub max_cpu_percent: Option<f32>,
+
+    /// How often the browser's memory and CPU are sampled (milliseconds;
+    /// 0: not monitored)
+    #[serde(default = "default_monitor_interval_ms")]
+    pub monitor_interval_ms: u64,
+
     /// Maximum execution time (seconds)
     pub max_execution_time_secs: u64,
 
     /// Default action timeout (seconds)
     pub default_timeout_secs: u64,
 
+    /// Navigation timeout (seconds)
+    #[serde(default = "default_navigation_timeout_secs")]
+    pub navigation_timeout_secs: u64,
+
+    /// Quiet time for `WaitCondition::NetworkIdle` (milliseconds)
+    #[serde(default = "default_network_idle_ms")]
+    pub network_idle_ms: u64,
+
     /// Headless mode
     pub headless: bool,
 
@@ -57,31 +141,162 @@ pub struct ExecutorConfig {
     /// User agent string
     pub user_agent: Option<String>,
 
+    /// Hide common headless tells from bot detection (see `stealth`)
+    #[serde(default)]
+    pub stealth: bool,
+
+    /// Close cookie-consent banners after each navigation (see `consent`)
+    #[serde(default)]
+    pub dismiss_consent: bool,
+
+    /// Human-like typing, mouse movement and pauses between actions
+    #[serde(default)]
+    pub humanize: Option<HumanizeConfig>,
+
     /// Viewport width
     pub viewport_width: u32,
 
     /// Viewport height
     pub viewport_height: u32,
 
-    /// Enable sandboxing
+    /// Device to emulate in every tab, in place of the viewport and user
+    /// agent above
+    #[serde(default)]
+    pub device: Option<Device>,
+
+    /// Run the browser inside nsjail (Linux; launching fails where nsjail
+    /// is not installed). Defaults to whether it is installed.
     pub enable_sandbox: bool,
+
+    /// Request blocking and header injection
+    #[serde(default)]
+    pub network_rules: NetworkRules,
+
+    /// Proxy for all browser traffic
+    #[serde(default)]
+    pub proxy: Option<ProxyConfig>,
+
+    /// Credentials for servers asking for HTTP authentication
+    #[serde(skip)]
+    pub credentials: Option<Arc<dyn CredentialProvider>>,
+
+    /// Download directory and limits
+    #[serde(default)]
+    pub downloads: DownloadConfig,
+
+    /// Directories `UploadFile` may read from (none: uploads refused)
+    #[serde(default)]
+    pub upload_roots: Vec<PathBuf>,
+
+    /// How alert, confirm and prompt dialogs are answered
+    #[serde(default)]
+    pub dialog_policy: DialogPolicy,
+
+    /// Where to save a screenshot, DOM dump and recent errors when an action
+    /// fails (none: nothing saved)
+    #[serde(default)]
+    pub failure_artifacts: Option<ArtifactConfig>,
+
+    /// Attach the console entries logged during each action to its output
+    #[serde(default)]
+    pub collect_console: bool,
+
+    /// Console entries kept per tab, with `collect_console` or
+    /// `failure_artifacts`
+    #[serde(default = "default_console_buffer_size")]
+    pub console_buffer_size: usize,
+
+    /// Where pages may navigate, redirects included (empty: anywhere)
+    #[serde(default)]
+    pub url_policy: UrlPolicy,
+
+    /// DevTools address of a running browser to attach to instead of
+    /// launching one (`http://127.0.0.1:9222` or its `ws://` endpoint)
+    #[serde(default)]
+    pub remote_debugging_url: Option<String>,
+
+    /// Which of an attached browser's existing tabs may be controlled
+    #[serde(default)]
+    pub remote_tabs: TabScope,
+
+    /// Launch without site isolation, so `SwitchFrame` can reach cross-origin
+    /// iframes (weakens the browser's protection between sites)
+    #[serde(default)]
+    pub reach_cross_origin_frames: bool,
+}
+
+fn default_monitor_interval_ms() -> u64 {
+    1000
+}
+
+fn default_navigation_timeout_secs() -> u64 {
+    30
+}
+
+fn default_network_idle_ms() -> u64 {
+    500
+}
+
+fn default_console_buffer_size() -> usize {
+    200
 }
 
 impl Default for ExecutorConfig {
     fn default() -> Self {
         Self {
-            max_memory_mb: 512,
+            max_memory_mb: 2048,
+            max_cpu_percent: None,
+            monitor_interval_ms: default_monitor_interval_ms(),
             max_execution_time_secs: 30,
             default_timeout_secs: 10,
+            navigation_timeout_secs: default_navigation_timeout_secs(),
+            network_idle_ms: default_network_idle_ms(),
             headless: true,
             disable_images: false,
             disable_javascript: false,
             user_agent: Some(
                 "Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36".to_string(),
             ),
+            stealth: false,
+            dismiss_consent: false,
+            humanize: None,
             viewport_width: 1920,
             viewport_height: 1080,
-            enable_sandbox: true,
+            device: None,
+            enable_sandbox: SandboxedProcess::is_available(),
+            network_rules: NetworkRules::default(),
+            proxy: None,
+            credentials: None,
+            downloads: DownloadConfig::default(),
+            upload_roots: Vec::new(),
+            dialog_policy: DialogPolicy::default(),
+            failure_artifacts: None,
+            collect_console: false,
+            console_buffer_size: default_console_buffer_size(),
+            url_policy: UrlPolicy::default(),
+            remote_debugging_url: None,
+            remote_tabs: TabScope::default(),
+            reach_cross_origin_frames: false,
+        }
+    }
+}
+
+impl From<&aether_config::BrowserExecutorSection> for ExecutorConfig {
+    fn from(section: &aether_config::BrowserExecutorSection) -> Self {
+        let defaults = Self::default();
+        Self {
+            headless: section.headless,
+            max_memory_mb: section.max_memory_mb,
+            max_execution_time_secs: section.max_execution_time_secs,
+            default_timeout_secs: section.default_timeout_secs,
+            navigation_timeout_secs: section.navigation_timeout_secs,
+            viewport_width: section.viewport_width,
+            viewport_height: section.viewport_height,
+            user_agent: section.user_agent.clone().or(defaults.user_agent),
+            stealth: section.stealth,
+            enable_sandbox: section.enable_sandbox,
+            remote_debugging_url: section.remote_debugging_url.clone(),
+            ..defaults
         }
     }
 }
@@ -95,14 +310,47 @@ pub struct ExecutorStats {
     pub crashes: u64,
     pub restarts: u64,
     pub total_execution_time_ms: u64,
+
+    /// Highest memory use of the browser's processes seen (MB)
+    #[serde(default)]
+    pub peak_memory_mb: u64,
+
+    /// Highest CPU use of the browser's processes seen (percent of one core)
+    #[serde(default)]
+    pub peak_cpu_percent: f32,
+
+    /// Browsers killed for going over `max_memory_mb` or `max_cpu_percent`
+    #[serde(default)]
+    pub limit_restarts: u64,
+
+    /// Instance counts, for stats from a [`BrowserPool`](crate::BrowserPool)
+    #[serde(default)]
+    pub pool: Option<PoolStats>,
+}
+
+impl ExecutorStats {
+    /// Keep the highest memory (MB) and CPU (percent) use seen
+    pub(crate) fn record_peak(&mut self, memory_mb: u64, cpu_percent: f32) {
+        self.peak_memory_mb = self.peak_memory_mb.max(memory_mb);
+        self.peak_cpu_percent = self.peak_cpu_percent.max(cpu_percent);
+    }
 }
 
+/// How long a restart waits on the old browser for its cookies
+const SAVE_COOKIES_TIMEOUT: Duration = Duration::from_secs(2);
+
 /// Browser executor with automatic recovery
 pub struct BrowserExecutor {
     config: ExecutorConfig,
     browser: Arc<RwLock<Option<Browser>>>,
-    current_page: Arc<RwLock<Option<Page>>>,
+    tabs: Arc<RwLock<Option<Tabs>>>,
     stats: Arc<RwLock<ExecutorStats>>,
+    breach: Breach,
+    /// The current browser's health
+    health: Mutex<Health>,
+    /// The active tab's URL after the last successful action, restored after
+    /// a restart
+    last_url: Mutex<Option<String>>,
 }
 
 impl BrowserExecutor {
@@ -111,8 +359,11 @@ impl BrowserExecutor {
         let executor = Self {
             config,
             browser: Arc::new(RwLock::new(None)),
-            current_page: Arc::new(RwLock::new(None)),
+            tabs: Arc::new(RwLock::new(None)),
             stats: Arc::new(RwLock::new(ExecutorStats::default())),
+            breach: Breach::default(),
+            health: Mutex::new(Health::default()),
+            last_url: Mutex::new(None),
         };
 
         executor.launch_browser().await?;
@@ -120,8 +371,16 @@ impl BrowserExecutor {
         Ok(executor)
     }
 
-    /// Execute a browser action
+    /// Execute a browser action, in a `browser_action` span
     pub async fn execute(&self, action: BrowserAction) -> Result<ActionOutput, ExecutorError> {
+        let span = telemetry::action_span(&action);
+        let start = std::time::Instant::now();
+        let result = self.execute_action(action).instrument(span.clone()).await;
+        telemetry::record(&span, &result, start.elapsed());
+        result
+    }
+
+    async fn execute_action(&self, action: BrowserAction) -> Result<ActionOutput, ExecutorError> {
         let start = std::time::Instant::now();
 
         // Update stats
@@ -130,14 +389,18 @@ impl BrowserExecutor {
             stats.total_actions += 1;
         }
 
-        // Ensure browser is running
-        if !self.is_browser_alive().await {
-            warn!("Browser not alive, restarting...");
-            self.restart_browser().await?;
-        }
+        self.recover().await?;
 
-        // Execute action
-        let result = self.execute_with_timeout(action.clone()).await;
+        // Execute action, failing as soon as the browser crashes
+        let health = self.health();
+        let result = tokio::select! {
+            result = self.execute_with_timeout(action.clone()) => result,
+            reason = health.failed() => Err(crate::actions::ActionError::BrowserError(reason)),
+        };
+        if result.is_ok() {
+            let url = self.get_current_url().await;
+            *self.last_url.lock().unwrap_or_else(|e| e.into_inner()) = url;
+        }
 
         // Update stats
         {
@@ -151,10 +414,18 @@ impl BrowserExecutor {
         }
 
         match result {
-            Ok(output) => Ok(output),
+            Ok(output) if output.success => Ok(output),
+            Ok(output) => Ok(with_failure(output, &action, self.get_current_url().await)),
             Err(e) => {
                 error!("Action failed: {}", e);
-                Err(ExecutorError::ActionFailed(e.to_string()))
+                if let Some(reason) = self.breach.get() {
+                    Err(ExecutorError::ResourceLimitExceeded(reason))
+                } else if let Some(reason) = health.failure() {
+                    Err(ExecutorError::BrowserCrashed(reason))
+                } else {
+                    let url = self.get_current_url().await;
+                    Err(ExecutorError::action(e, &action, url))
+                }
             }
         }
     }
@@ -164,163 +435,246 @@ impl BrowserExecutor {
         &self,
         action: BrowserAction,
     ) -> ActionResult<ActionOutput> {
-        let timeout = Duration::from_secs(self.config.max_execution_time_secs);
-
-        tokio::time::timeout(timeout, self.execute_action(action))
-            .await
-            .map_err(|_| {
-                crate::actions::ActionError::ActionFailed("Execution timeout".to_string())
-            })?
-    }
-
-    /// Execute action on current page
-    async fn execute_action(&self, action: BrowserAction) -> ActionResult<ActionOutput> {
-        let page_lock = self.current_page.read().await;
+        let browser_lock = self.browser.read().await;
+        let mut tabs_lock = self.tabs.write().await;
 
-        let page = page_lock
-            .as_ref()
-            .ok_or(crate::actions::ActionError::BrowserError(
-                "No page available".to_string(),
-            ))?
-            .clone();
+        let (Some(browser), Some(tabs)) = (browser_lock.as_ref(), tabs_lock.as_mut()) else {
+            return Err(crate::actions::ActionError::BrowserError(
+                "No browser available".to_string(),
+            ));
+        };
+        tabs.add_popups(&self.config, browser).await;
 
-        drop(page_lock);
+        if action.is_tab_action() {
+            return run_tab_action(&self.config, browser, tabs, action).await;
+        }
 
-        let mut executor = ActionExecutor::new(
-            page,
-            Duration::from_secs(self.config.default_timeout_secs),
-        );
+        let tab = tabs.active();
+        drop(tabs_lock);
 
-        executor.execute(action).await
+        run_action(&self.config, browser, tab, action).await
     }
 
     /// Launch browser
     async fn launch_browser(&self) -> Result<(), ExecutorError> {
-        info!("Launching browser...");
-
-        let mut config_builder = BrowserConfig::builder();
-
-        // Set headless mode
-        if self.config.headless {
-            config_builder = config_builder.with_head();
-        }
-
-        // Set viewport
-        config_builder = config_builder.viewport(chromiumoxide::handler::viewport::Viewport {
-            width: self.config.viewport_width,
-            height: self.config.viewport_height,
-            device_scale_factor: Some(1.0),
-            emulating_mobile: false,
-            is_landscape: false,
-            has_touch: false,
-        });
-
-        // Build config
-        let config = config_builder.build().map_err(|e| {
-            ExecutorError::LaunchFailed(format!("Failed to build config: {}", e))
-        })?;
-
-        // Launch browser
-        let (browser, mut handler) = Browser::launch(config)
-            .await
-            .map_err(|e| ExecutorError::LaunchFailed(e.to_string()))?;
-
-        // Spawn handler
-        let _handle = tokio::task::spawn(async move {
-            while let Some(event) = handler.next().await {
-                debug!("Browser event: {:?}", event);
+        let Launched {
+            browser,
+            tabs,
+            health,
+            pid,
+        } = launch(&self.config, None).await?;
+
+        match pid {
+            Some(pid) if self.config.monitor_interval_ms > 0 => {
+                let stats = self.stats.clone();
+                let record = move |memory: u64, cpu: f32| {
+                    let stats = stats.clone();
+                    async move { stats.write().await.record_peak(memory, cpu) }
+                };
+                monitor::spawn(pid, &self.config, self.breach.clone(), record);
             }
-        });
-
-        // Create new page
-        let page = browser
-            .new_page("about:blank")
-            .await
-            .map_err(|e| ExecutorError::PageError(e.to_string()))?;
-
-        // Set user agent if specified
-        if let Some(user_agent) = &self.config.user_agent {
-            page.set_user_agent(user_agent)
-                .await
-                .map_err(|e| ExecutorError::PageError(e.to_string()))?;
+            Some(_) => {}
+            None if self.config.remote_debugging_url.is_some() => {}
+            None => warn!("Browser process unknown; memory and CPU are not monitored"),
         }
 
-        // Store browser and page
+        // Store browser and tabs
         *self.browser.write().await = Some(browser);
-        *self.current_page.write().await = Some(page);
-
-        info!("Browser launched successfully");
+        *self.tabs.write().await = Some(tabs);
+        *self.health.lock().unwrap_or_else(|e| e.into_inner()) = health;
 
         Ok(())
     }
 
+    /// The current browser's health
+    fn health(&self) -> Health {
+        self.health.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
     /// Check if browser is alive
     async fn is_browser_alive(&self) -> bool {
+        if let Some(reason) = self.health().failure() {
+            warn!("Browser unhealthy: {}", reason);
+            return false;
+        }
+
         let browser_lock = self.browser.read().await;
 
         if let Some(_browser) = browser_lock.as_ref() {
             // Try to get page
-            let page_lock = self.current_page.read().await;
-            page_lock.is_some()
+            let tabs_lock = self.tabs.read().await;
+            tabs_lock.is_some()
         } else {
             false
         }
     }
 
-    /// Restart browser after crash
+    /// Relaunch a browser that crashed, was closed or was killed for going
+    /// over its limits, as the next action would; nothing while it is
+    /// healthy
+    pub async fn recover(&self) -> Result<(), ExecutorError> {
+        // Replace a browser killed for going over its limits
+        if let Some(reason) = self.breach.take() {
+            warn!("{}; restarting browser", reason);
+            self.stats.write().await.limit_restarts += 1;
+            self.restart_browser().await?;
+        }
+
+        // Ensure browser is running
+        if !self.is_browser_alive().await {
+            warn!("Browser not alive, restarting...");
+            self.stats.write().await.crashes += 1;
+            self.restart_browser().await?;
+        }
+        Ok(())
+    }
+
+    /// Restart browser after a crash or a limit breach
     async fn restart_browser(&self) -> Result<(), ExecutorError> {
         warn!("Restarting browser...");
 
-        // Update crash stats
-        {
-            let mut stats = self.stats.write().await;
-            stats.crashes += 1;
-            stats.restarts += 1;
-        }
+        self.stats.write().await.restarts += 1;
 
-        // Close existing browser
+        // Close existing browser, keeping its cookies if it still answers
+        let cookies = self.save_cookies().await;
         self.close_browser().await;
 
         // Launch new browser
         self.launch_browser().await?;
+        self.restore(&cookies).await;
 
         info!("Browser restarted successfully");
 
         Ok(())
     }
 
+    /// The current browser's cookies, if it answers in time
+    async fn save_cookies(&self) -> Vec<Cookie> {
+        let browser_lock = self.browser.read().await;
+        let Some(browser) = browser_lock.as_ref() else {
+            return Vec::new();
+        };
+
+        match tokio::time::timeout(SAVE_COOKIES_TIMEOUT, health::cookies(browser)).await {
+            Ok(Ok(cookies)) => cookies,
+            Ok(Err(e)) => {
+                warn!("Failed to save cookies before restart: {}", e);
+                Vec::new()
+            }
+            Err(_) => {
+                warn!("Failed to save cookies before restart: browser not answering");
+                Vec::new()
+            }
+        }
+    }
+
+    /// Give a restarted browser the old one's cookies, and its active tab
+    /// the last URL
+    async fn restore(&self, cookies: &[Cookie]) {
+        if let Some(browser) = self.browser.read().await.as_ref() {
+            if let Err(e) = health::restore_cookies(browser, cookies).await {
+                warn!("Failed to restore cookies: {}", e);
+            }
+        }
+
+        let url = self.last_url.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        let Some(url) = url.filter(|url| url != "about:blank") else {
+            return;
+        };
+        let tabs_lock = self.tabs.read().await;
+        let Some(tabs) = tabs_lock.as_ref() else {
+            return;
+        };
+
+        let timeout = Duration::from_secs(self.config.navigation_timeout_secs);
+        match tokio::time::timeout(timeout, tabs.current().goto(url.as_str())).await {
+            Ok(Ok(_)) => info!("Restored {}", url),
+            Ok(Err(e)) => warn!("Failed to restore {}: {}", url, e),
+            Err(_) => warn!("Failed to restore {}: navigation timeout", url),
+        }
+    }
+
     /// Close browser
     async fn close_browser(&self) {
         debug!("Closing browser...");
 
-        // Clear page
-        *self.current_page.write().await = None;
+        // Clear tabs
+        let tabs = self.tabs.write().await.take();
+        if let Some(tabs) = &tabs {
+            tabs.downloads().clean_up();
+        }
 
-        // Close browser
+        // Close browser, or only our tabs of one we attached to
         let mut browser_lock = self.browser.write().await;
         if let Some(mut browser) = browser_lock.take() {
-            if let Err(e) = browser.close().await {
+            if self.config.remote_debugging_url.is_some() {
+                if let Some(tabs) = tabs {
+                    tabs.close_opened().await;
+                }
+            } else if let Err(e) = browser.close().await {
                 warn!("Failed to close browser gracefully: {}", e);
             }
         }
     }
 
+    /// Whether the browser is running and none of its tabs has crashed
+    pub async fn is_healthy(&self) -> bool {
+        self.health().failure().is_none() && self.browser.read().await.is_some()
+    }
+
     /// Get executor statistics
     pub async fn get_stats(&self) -> ExecutorStats {
         self.stats.read().await.clone()
     }
 
-    /// Get current page URL
+    /// Get the active tab's URL
     pub async fn get_current_url(&self) -> Option<String> {
-        let page_lock = self.current_page.read().await;
+        let tabs_lock = self.tabs.read().await;
 
-        if let Some(page) = page_lock.as_ref() {
-            page.url().await.ok().flatten()
+        if let Some(tabs) = tabs_lock.as_ref() {
+            tabs.current().url().await.ok().flatten()
         } else {
             None
         }
     }
 
+    /// Get the active tab's buffered console entries, oldest first
+    ///
+    /// Empty unless `collect_console` or `failure_artifacts` is set.
+    pub async fn get_console_logs(&self) -> Vec<ConsoleEntry> {
+        let tabs_lock = self.tabs.read().await;
+
+        match tabs_lock.as_ref() {
+            Some(tabs) => tabs.active().console.entries(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Bytes received over the network by the current browser's tabs,
+    /// downloads included
+    pub async fn bytes_received(&self) -> u64 {
+        let tabs_lock = self.tabs.read().await;
+
+        match tabs_lock.as_ref() {
+            Some(tabs) => tabs.bytes_received(),
+            None => 0,
+        }
+    }
+
+    /// Record what someone does in the active tab until `stop` completes,
+    /// as a plan that replays it (see [`record`](crate::record))
+    pub async fn record(
+        &self,
+        stop: impl Future<Output = ()>,
+    ) -> Result<ActionPlan, ExecutorError> {
+        let page = match self.tabs.read().await.as_ref() {
+            Some(tabs) => tabs.current().clone(),
+            None => return Err(ExecutorError::PageError("No page available".to_string())),
+        };
+
+        record::record(&page, stop).await
+    }
+
     /// Close and cleanup
     pub async fn shutdown(&self) {
         info!("Shutting down browser executor...");
@@ -328,6 +682,455 @@ impl BrowserExecutor {
     }
 }
 
+/// `output` of a failed `action`, its failure filled in with the selector
+/// and URL involved
+pub(crate) fn with_failure(
+    mut output: ActionOutput,
+    action: &BrowserAction,
+    page_url: Option<String>,
+) -> ActionOutput {
+    let code = output
+        .failure
+        .as_ref()
+        .map_or(ErrorCode::ActionFailed, |failure| failure.code);
+    output.failure = Some(Failure::of(code, action, page_url));
+    output
+}
+
+/// Run an action on the active tab within the configured time limits and URL
+/// policy, collecting its console entries and saving failure artifacts if
+/// configured
+///
+/// Downloads have their own time limit, from `config.downloads`. An action
+/// blocked by a dialog left open fails as soon as the dialog is seen.
+pub(crate) async fn run_action(
+    config: &ExecutorConfig,
+    browser: &Browser,
+    tab: ActiveTab,
+    action: BrowserAction,
+) -> ActionResult<ActionOutput> {
+    let (page, console) = (tab.page.clone(), tab.console.clone());
+    let mark = console.mark();
+
+    let mut result = match &config.failure_artifacts {
+        Some(artifact_config) => {
+            let result = perform(config, browser, tab, action.clone()).await;
+            artifacts::record(artifact_config, &page, &console, &action, result).await
+        }
+        None => perform(config, browser, tab, action).await,
+    };
+    if let (true, Ok(output)) = (config.collect_console, &mut result) {
+        output.console = console.since(mark);
+    }
+    result
+}
+
+async fn perform(
+    config: &ExecutorConfig,
+    browser: &Browser,
+    tab: ActiveTab,
+    action: BrowserAction,
+) -> ActionResult<ActionOutput> {
+    check_policy(config, &action)?;
+
+    // A load the policy blocked fails the action with the reason, rather
+    // than the bare network error it causes
+    let violations = tab.violations.clone();
+    violations.take();
+    let result = dispatch(config, browser, tab, action).await;
+    match violations.take() {
+        Some(reason) if result.is_err() => {
+            Err(crate::actions::ActionError::PolicyViolation(reason))
+        }
+        _ => result,
+    }
+}
+
+async fn dispatch(
+    config: &ExecutorConfig,
+    browser: &Browser,
+    tab: ActiveTab,
+    action: BrowserAction,
+) -> ActionResult<ActionOutput> {
+    let ActiveTab {
+        page,
+        frame,
+        dialogs,
+        traffic,
+        captures,
+        downloads,
+        ..
+    } = tab;
+
+    if let BrowserAction::HandleDialog { .. } = action {
+        return dialog::handle(&page, &dialogs, action).await;
+    }
+    if let Some(open) = dialogs.pending() {
+        return Err(dialog::blocked(&open));
+    }
+    if let BrowserAction::CaptureResponses { .. } = action {
+        return capture::run(&captures, action);
+    }
+    if let BrowserAction::Download { .. } = action {
+        return download::run(config, browser, page, &traffic, &downloads, action).await;
+    }
+    if let BrowserAction::WaitUntil {
+        condition: UntilCondition::DownloadComplete,
+        timeout_ms,
+    } = action
+    {
+        return download::wait(config, &downloads, timeout_ms).await;
+    }
+
+    let timeout = Duration::from_secs(config.max_execution_time_secs);
+    if let BrowserAction::SavePageArchive { .. } = action {
+        return tokio::time::timeout(timeout, archive::run(config, &page, action))
+            .await
+            .map_err(|_| execution_timeout(timeout))?;
+    }
+
+    let consent_page = (config.dismiss_consent && action.is_navigation()).then(|| page.clone());
+    let mut executor = ActionExecutor::new(page, Duration::from_secs(config.default_timeout_secs))
+        .with_navigation(
+            Duration::from_secs(config.navigation_timeout_secs),
+            Duration::from_millis(config.network_idle_ms),
+        )
+        .with_upload_roots(config.upload_roots.clone())
+        .with_frame(frame)
+        .with_humanize(config.humanize.clone());
+
+    let run = async {
+        tokio::select! {
+            result = executor.execute(action) => result,
+            open = dialogs.opened() => Err(dialog::blocked(&open)),
+        }
+    };
+
+    let output = tokio::time::timeout(timeout, run)
+        .await
+        .map_err(|_| execution_timeout(timeout))??;
+
+    // A banner left in place only stalls later actions
+    if let Some(page) = consent_page {
+        match tokio::time::timeout(timeout, consent::dismiss(&page)).await {
+            Ok(Err(e)) => debug!("Consent banner check failed: {}", e),
+            Err(_) => debug!("Consent banner check timed out"),
+            Ok(Ok(_)) => {}
+        }
+    }
+    Ok(output)
+}
+
+/// Check the URL an action loads against the URL policy, and the sites whose
+/// cookies it reads or clears
+///
+/// An attached browser's cookies are the user's own sessions, so there they
+/// are only reachable for sites in `remote_tabs` or explicitly allowed by the
+/// policy, and can only be cleared a domain at a time.
+fn check_policy(config: &ExecutorConfig, action: &BrowserAction) -> ActionResult<()> {
+    config.url_policy.check_action(action)?;
+
+    let remote = config.remote_debugging_url.is_some();
+    let urls = match action {
+        BrowserAction::GetCookies { urls } => urls.clone(),
+        BrowserAction::ClearCookies {
+            domain: Some(domain),
+        } => vec![format!("https://{}/", domain.trim_start_matches('.'))],
+        BrowserAction::ClearCookies { domain: None } if remote => {
+            return Err(ActionError::PolicyViolation(
+                "Clearing every cookie of an attached browser is refused; name a domain"
+                    .to_string(),
+            ));
+        }
+        _ => return Ok(()),
+    };
+
+    for url in &urls {
+        config.url_policy.check(url).map_err(|reason| {
+            warn!("Blocked by URL policy: {}", reason);
+            ActionError::PolicyViolation(reason)
+        })?;
+
+        let allowed = !config.url_policy.allow.is_empty();
+        if remote && !allowed && !config.remote_tabs.includes(url) {
+            return Err(ActionError::PolicyViolation(format!(
+                "Cookies for {} are outside remote_tabs",
+                url
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Run a tab action within the configured time limits and URL policy
+pub(crate) async fn run_tab_action(
+    config: &ExecutorConfig,
+    browser: &Browser,
+    tabs: &mut Tabs,
+    action: BrowserAction,
+) -> ActionResult<ActionOutput> {
+    check_policy(config, &action)?;
+    let timeout = Duration::from_secs(config.max_execution_time_secs);
+
+    tokio::time::timeout(timeout, tabs.apply(config, browser, action))
+        .await
+        .map_err(|_| execution_timeout(timeout))?
+}
+
+/// The action ran past `max_execution_time_secs`
+fn execution_timeout(limit: Duration) -> ActionError {
+    ActionError::Timeout(format!("Action exceeded {}s", limit.as_secs()))
+}
+
+/// A launched (or attached) browser with its tabs, a blank one active, and
+/// its health
+pub(crate) struct Launched {
+    pub(crate) browser: Browser,
+    pub(crate) tabs: Tabs,
+    pub(crate) health: Health,
+    /// The browser's process (nsjail's, when jailed), if known
+    pub(crate) pid: Option<u32>,
+}
+
+/// The viewport every tab starts with
+pub(crate) fn viewport(config: &ExecutorConfig) -> Viewport {
+    Viewport {
+        width: config.viewport_width,
+        height: config.viewport_height,
+        device_scale_factor: Some(1.0),
+        emulating_mobile: false,
+        is_landscape: false,
+        has_touch: false,
+    }
+}
+
+/// Features disabled by default (chromiumoxide's default flags)
+const DISABLED_FEATURES: &[&str] = &["TranslateUI"];
+
+/// Launch flags from `config`, besides headless mode, viewport and profile
+fn launch_args(config: &ExecutorConfig) -> Result<Vec<String>, ExecutorError> {
+    let mut args = Vec::new();
+    let mut disabled_features = DISABLED_FEATURES.to_vec();
+
+    // Keep cross-origin iframes in the page's process, where frame-scoped
+    // actions can reach them
+    if config.reach_cross_origin_frames {
+        args.push("--disable-site-isolation-trials".to_string());
+        disabled_features.extend(["site-per-process", "IsolateOrigins"]);
+    }
+
+    // Stealth sessions don't announce automation
+    if config.stealth {
+        args.push(stealth::LAUNCH_ARG.to_string());
+    }
+
+    // Route traffic through the proxy
+    if let Some(proxy) = &config.proxy {
+        args.extend(proxy.launch_args().map_err(ExecutorError::LaunchFailed)?);
+    }
+
+    // Only the last --disable-features counts, so there is one with every
+    // feature
+    if disabled_features.len() > DISABLED_FEATURES.len() {
+        args.push(format!(
+            "--disable-features={}",
+            disabled_features.join(",")
+        ));
+    }
+
+    Ok(args)
+}
+
+/// Launch a browser configured from `config`, in nsjail if it asks for a
+/// sandbox, or attach to the one at `config.remote_debugging_url`
+///
+/// `user_data_dir` gives the browser its own profile instead of a shared one.
+pub(crate) async fn launch(
+    config: &ExecutorConfig,
+    user_data_dir: Option<&Path>,
+) -> Result<Launched, ExecutorError> {
+    info!("Launching browser...");
+
+    let (mut browser, mut handler, jail) = if let Some(url) = &config.remote_debugging_url {
+        let (browser, handler) = remote::connect(url).await?;
+        (browser, handler, None)
+    } else if jail::wanted(config)? {
+        let args = launch_args(config)?;
+        let (browser, handler, jail) = jail::launch(config, user_data_dir, args).await?;
+        (browser, handler, Some(jail))
+    } else {
+        let mut config_builder = BrowserConfig::builder();
+
+        // chromiumoxide launches headless unless asked for a window
+        if !config.headless {
+            config_builder = config_builder.with_head();
+        }
+
+        // Set viewport
+        config_builder = config_builder
+            .viewport(viewport(config))
+            .args(launch_args(config)?);
+
+        // Separate profile (cookies, storage, cache)
+        if let Some(dir) = user_data_dir {
+            config_builder = config_builder.user_data_dir(dir);
+        }
+
+        // Build config
+        let browser_config = config_builder.build().map_err(|e| {
+            ExecutorError::LaunchFailed(format!("Failed to build config: {}", e))
+        })?;
+
+        // Launch browser
+        let (browser, handler) = Browser::launch(browser_config)
+            .await
+            .map_err(|e| ExecutorError::LaunchFailed(e.to_string()))?;
+        (browser, handler, None)
+    };
+    let pid = match &jail {
+        Some(jail) => jail.pid(),
+        None => browser.get_mut_child().and_then(|child| child.id()),
+    };
+
+    // Spawn handler; the connection failing or closing ends the browser,
+    // and the jail with it
+    let health = Health::default();
+    let connection = health.clone();
+    let _handle = tokio::task::spawn(async move {
+        let _jail = jail;
+        while let Some(event) = handler.next().await {
+            match event {
+                Err(e @ (CdpError::Ws(_) | CdpError::Io(_))) => {
+                    connection.fail(format!("Connection error: {}", e));
+                    break;
+                }
+                event => debug!("Browser event: {:?}", event),
+            }
+        }
+        connection.fail("Connection to the browser closed".to_string());
+    });
+    health
+        .watch(&browser)
+        .await
+        .map_err(|e| ExecutorError::LaunchFailed(e.to_string()))?;
+
+    // Create new page
+    let page = browser
+        .new_page("about:blank")
+        .await
+        .map_err(|e| ExecutorError::PageError(e.to_string()))?;
+    let downloads = Downloads::watch(config, &browser, page.clone())
+        .await
+        .map_err(|e| ExecutorError::LaunchFailed(e.to_string()))?;
+
+    let watchers = prepare_page(config, &page)
+        .await
+        .map_err(|e| ExecutorError::PageError(e.to_string()))?;
+
+    let popups = Popups::watch(config, &browser)
+        .await
+        .map_err(|e| ExecutorError::LaunchFailed(e.to_string()))?;
+
+    let own = page.target_id().clone();
+    let mut tabs = Tabs::new(page, watchers, downloads, popups);
+    if config.remote_debugging_url.is_some() {
+        for tab in remote::tabs_in_scope(&browser, &config.remote_tabs).await {
+            if *tab.target_id() == own {
+                continue;
+            }
+            let watchers = observe_page(config, &tab)
+                .await
+                .map_err(|e| ExecutorError::PageError(e.to_string()))?;
+            tabs.adopt(tab, watchers);
+        }
+    }
+
+    info!("Browser launched successfully");
+
+    Ok(Launched {
+        browser,
+        tabs,
+        health,
+        pid,
+    })
+}
+
+/// Apply per-page settings from `config` to a new, still blank page, start
+/// answering its dialogs and, if needed, buffering its console
+pub(crate) async fn prepare_page(
+    config: &ExecutorConfig,
+    page: &Page,
+) -> chromiumoxide::Result<TabWatchers> {
+    // Set user agent if specified
+    if let Some(user_agent) = &config.user_agent {
+        page.set_user_agent(user_agent).await?;
+    }
+    if let Some(device) = &config.device {
+        emulation::emulate(page, device).await?;
+    }
+    if config.stealth {
+        // A device's user agent is kept; the default one is no browser's
+        let user_agent = match &config.device {
+            Some(device) => device.profile().user_agent,
+            None => config
+                .user_agent
+                .clone()
+                .filter(|user_agent| user_agent.contains("Chrome/")),
+        };
+        stealth::apply(page, user_agent).await?;
+    }
+
+    let violations = Violations::default();
+    network::intercept(
+        page,
+        &config.network_rules,
+        &config.url_policy,
+        violations.clone(),
+        config.proxy.as_ref(),
+        config.credentials.clone(),
+    )
+    .await?;
+    Ok(TabWatchers {
+        dialogs: Dialogs::watch(page, config.dialog_policy).await?,
+        console: watch_console(config, page).await?,
+        violations,
+        traffic: Traffic::watch(page).await?,
+        captures: Captures::watch(page).await?,
+    })
+}
+
+/// Watch an attached browser's existing tab without changing it
+///
+/// The user's tab keeps its user agent, emulation and network: nothing is
+/// injected or intercepted, and dialogs are left for `HandleDialog`.
+pub(crate) async fn observe_page(
+    config: &ExecutorConfig,
+    page: &Page,
+) -> chromiumoxide::Result<TabWatchers> {
+    Ok(TabWatchers {
+        dialogs: Dialogs::watch(page, DialogPolicy::Queue).await?,
+        console: watch_console(config, page).await?,
+        violations: Violations::default(),
+        traffic: Traffic::watch(page).await?,
+        captures: Captures::watch(page).await?,
+    })
+}
+
+/// Buffer `page`'s console if anything reads it: `console_buffer_size`
+/// entries, but at least the `max_log_entries` failure artifacts save
+async fn watch_console(config: &ExecutorConfig, page: &Page) -> chromiumoxide::Result<ConsoleLog> {
+    let saved = config
+        .failure_artifacts
+        .as_ref()
+        .map(|artifacts| artifacts.max_log_entries);
+    if !config.collect_console && saved.is_none() {
+        return Ok(ConsoleLog::default());
+    }
+
+    let capacity = config.console_buffer_size.max(saved.unwrap_or(0));
+    ConsoleLog::watch(page, capacity).await
+}
+
 impl Drop for BrowserExecutor {
     fn drop(&mut self) {
         debug!("BrowserExecutor dropped");
@@ -346,6 +1149,118 @@ mod tests {
         assert!(executor.is_ok());
     }
 
+    #[test]
+    fn test_config_from_section() {
+        let section = aether_config::BrowserExecutorSection {
+            headless: false,
+            viewport_width: 1280,
+            ..Default::default()
+        };
+
+        let config = ExecutorConfig::from(&section);
+        assert!(!config.headless);
+        assert_eq!(config.viewport_width, 1280);
+        assert_eq!(config.user_agent, ExecutorConfig::default().user_agent);
+        assert_eq!(
+            config.navigation_timeout_secs,
+            ExecutorConfig::default().navigation_timeout_secs
+        );
+    }
+
+    #[test]
+    fn test_execution_timeout_is_retryable() {
+        use crate::failure::{ErrorCode, Recovery};
+
+        let error = execution_timeout(Duration::from_secs(30));
+        assert_eq!(error.code(), ErrorCode::Timeout);
+        assert_eq!(error.code().recovery(), Recovery::Retry);
+    }
+
+    #[test]
+    fn test_launch_args() {
+        let config = ExecutorConfig {
+            stealth: true,
+            ..Default::default()
+        };
+        let args = launch_args(&config).unwrap();
+        assert!(!args.iter().any(|arg| arg.contains("site-isolation")));
+        assert!(!args.iter().any(|arg| arg.starts_with("--disable-features")));
+
+        let config = ExecutorConfig {
+            reach_cross_origin_frames: true,
+            ..config
+        };
+        let args = launch_args(&config).unwrap();
+        assert!(args.contains(&"--disable-site-isolation-trials".to_string()));
+        let features: Vec<_> = args
+            .iter()
+            .filter(|arg| arg.starts_with("--disable-features="))
+            .collect();
+        assert_eq!(
+            features,
+            ["--disable-features=TranslateUI,site-per-process,IsolateOrigins"]
+        );
+    }
+
+    #[test]
+    fn test_cookie_policy() {
+        let get = |url: &str| BrowserAction::GetCookies {
+            urls: vec![url.to_string()],
+        };
+        let clear = |domain: Option<&str>| BrowserAction::ClearCookies {
+            domain: domain.map(str::to_string),
+        };
+
+        let config = ExecutorConfig {
+            url_policy: UrlPolicy {
+                deny: vec!["bank.example".to_string()],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert!(check_policy(&config, &get("https://shop.example/")).is_ok());
+        assert!(check_policy(&config, &get("https://www.bank.example/")).is_err());
+        assert!(check_policy(&config, &clear(Some(".bank.example"))).is_err());
+        assert!(check_policy(&config, &clear(None)).is_ok());
+
+        // An attached browser holds the user's own sessions
+        let config = ExecutorConfig {
+            remote_debugging_url: Some("http://127.0.0.1:9222".to_string()),
+            remote_tabs: TabScope::Matching(vec!["shop.example".to_string()]),
+            ..config
+        };
+        assert!(check_policy(&config, &get("https://shop.example/")).is_ok());
+        assert!(check_policy(&config, &get("https://mail.example/")).is_err());
+        assert!(check_policy(&config, &clear(Some("shop.example"))).is_ok());
+        assert!(check_policy(&config, &clear(None)).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_tabs() {
+        let executor = BrowserExecutor::new(ExecutorConfig::default()).await.unwrap();
+        let first = executor.get_current_url().await;
+
+        let id = executor
+            .execute(BrowserAction::NewTab {
+                url: Some("data:text/html,<title>Docs</title>".to_string()),
+            })
+            .await
+            .unwrap()
+            .data
+            .unwrap();
+
+        let tabs = executor.execute(BrowserAction::ListTabs).await.unwrap();
+        let tabs: Vec<crate::TabInfo> = serde_json::from_str(&tabs.data.unwrap()).unwrap();
+        assert_eq!(tabs.len(), 2);
+        assert!(tabs[1].active && tabs[1].id == id);
+
+        executor.execute(BrowserAction::CloseTab { index_or_id: None }).await.unwrap();
+        assert_eq!(executor.get_current_url().await, first);
+
+        let last = executor.execute(BrowserAction::CloseTab { index_or_id: None }).await;
+        assert!(last.is_err());
+    }
+
     #[tokio::test]
     async fn test_browser_alive() {
         let config = ExecutorConfig::default();
@@ -0,0 +1,235 @@
+//! Structured data extraction
+//!
+//! `BrowserAction::Extract` reads many values in one action: a schema maps
+//! output names to selectors, each giving an element's text or an attribute,
+//! optionally for every match (`list`) and with nested `fields` read relative
+//! to each match. One injected script collects the raw strings, which are
+//! then typed here, so `"$1,299.00"` can come back as the number `1299.0`.
+
+use crate::selector::{Selector, RESOLVE_QUERY};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+
+/// Output names mapped to the fields that produce them
+pub type ExtractSchema = BTreeMap<String, ExtractField>;
+
+/// One value (or list of values) to extract
+///
+/// ```json
+/// {
+///   "results": {
+///     "selector": ".result", "list": true,
+///     "fields": {
+///       "title": { "selector": "h3" },
+///       "price": { "selector": ".price", "type": "number" },
+///       "link": { "selector": "a", "attribute": "href" }
+///     }
+///   }
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExtractField {
+    /// Relative to the enclosing match for nested fields
+    pub selector: Selector,
+
+    /// Read this attribute instead of the text; `href` and `src` are
+    /// resolved to absolute URLs
+    #[serde(default)]
+    pub attribute: Option<String>,
+
+    /// Every match as an array, instead of the first match
+    #[serde(default)]
+    pub list: bool,
+
+    #[serde(default, rename = "type")]
+    pub kind: ValueType,
+
+    /// Extract an object from each match instead of a single value
+    #[serde(default)]
+    pub fields: Option<ExtractSchema>,
+}
+
+/// JSON type of an extracted value; unconvertible or missing values are `null`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ValueType {
+    /// Trimmed text, or the attribute as is
+    #[default]
+    String,
+
+    /// The digits, `.` and `-` in the text (so `,` separates thousands)
+    Number,
+
+    /// Like `Number`, but `null` unless whole
+    Integer,
+
+    /// Whether the element (or attribute) exists
+    Boolean,
+}
+
+/// Collects raw values; called with the resolver and the schema as built
+/// by `script`, returns an object of strings, nulls, arrays and objects
+const EXTRACT: &str = r#"(resolve, schema) => {
+  const value = (el, field) => {
+    if (field.fields) return extract(el, field.fields);
+    if (!field.attribute) return (el.innerText ?? el.textContent ?? '').trim();
+    if (!el.hasAttribute(field.attribute)) return null;
+    const url = ['href', 'src'].includes(field.attribute) && el[field.attribute];
+    return typeof url === 'string' ? url : el.getAttribute(field.attribute);
+  };
+  const extract = (scope, fields) => {
+    const out = {};
+    for (const [name, field] of Object.entries(fields)) {
+      const found = resolve(field.query, scope);
+      out[name] = field.list
+        ? found.map((el) => value(el, field))
+        : found.length ? value(found[0], field) : null;
+    }
+    return out;
+  };
+  return extract(document, schema);
+}"#;
+
+/// Script that extracts the raw values for `schema`
+pub(crate) fn script(schema: &ExtractSchema, pierce_shadow: bool) -> String {
+    format!(
+        "({})({}, {})",
+        EXTRACT,
+        RESOLVE_QUERY,
+        wire(schema, pierce_shadow)
+    )
+}
+
+/// The schema as the script reads it, with selectors as queries
+fn wire(schema: &ExtractSchema, pierce_shadow: bool) -> Value {
+    let fields = schema.iter().map(|(name, field)| {
+        let wired = serde_json::json!({
+            "query": field.selector.query(pierce_shadow),
+            "attribute": field.attribute,
+            "list": field.list,
+            "fields": field.fields.as_ref().map(|fields| wire(fields, pierce_shadow)),
+        });
+        (name.clone(), wired)
+    });
+    Value::Object(fields.collect())
+}
+
+/// Type the script's raw output by `schema`
+pub(crate) fn convert(schema: &ExtractSchema, raw: &Value) -> Value {
+    let Some(raw) = raw.as_object() else {
+        return Value::Null;
+    };
+
+    let fields = schema.iter().map(|(name, field)| {
+        let raw = raw.get(name).unwrap_or(&Value::Null);
+        let value = match raw {
+            Value::Array(items) if field.list => {
+                Value::Array(items.iter().map(|item| field.convert(item)).collect())
+            }
+            _ if field.list => Value::Array(Vec::new()),
+            raw => field.convert(raw),
+        };
+        (name.clone(), value)
+    });
+    Value::Object(fields.collect::<Map<_, _>>())
+}
+
+impl ExtractField {
+    /// Type one raw value
+    fn convert(&self, raw: &Value) -> Value {
+        if let Some(fields) = &self.fields {
+            return convert(fields, raw);
+        }
+
+        let text = raw.as_str();
+        match self.kind {
+            ValueType::Boolean => Value::Bool(text.is_some()),
+            ValueType::String => text.map_or(Value::Null, Value::from),
+            ValueType::Number => text.and_then(parse_number).map_or(Value::Null, Value::from),
+            ValueType::Integer => text
+                .and_then(parse_number)
+                .filter(|number| number.fract() == 0.0)
+                .map_or(Value::Null, |number| Value::from(number as i64)),
+        }
+    }
+}
+
+/// The number in `text`, ignoring currency signs, units and `,` separators
+fn parse_number(text: &str) -> Option<f64> {
+    let number: String = text
+        .chars()
+        .filter(|c| c.is_ascii_digit() || matches!(c, '.' | '-'))
+        .collect();
+    number.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn results_schema() -> ExtractSchema {
+        serde_json::from_value(json!({
+            "heading": { "selector": "h1" },
+            "results": {
+                "selector": ".result",
+                "list": true,
+                "fields": {
+                    "title": { "selector": "h3" },
+                    "price": { "selector": ".price", "type": "number" },
+                    "stock": { "selector": ".stock", "type": "integer" },
+                    "sale": { "selector": ".sale", "type": "boolean" },
+                    "link": { "selector": "a", "attribute": "href" }
+                }
+            },
+            "tags": { "selector": { "xpath": "//li" }, "list": true }
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_convert() {
+        let raw = json!({
+            "heading": "Results",
+            "results": [
+                {
+                    "title": "Lamp",
+                    "price": "$1,299.50",
+                    "stock": "12 left",
+                    "sale": "",
+                    "link": "https://shop.example/lamp"
+                },
+                { "title": "Desk", "price": "n/a", "stock": "2.5", "sale": null, "link": null }
+            ],
+            "tags": null
+        });
+
+        assert_eq!(
+            convert(&results_schema(), &raw),
+            json!({
+                "heading": "Results",
+                "results": [
+                    {
+                        "title": "Lamp",
+                        "price": 1299.5,
+                        "stock": 12,
+                        "sale": true,
+                        "link": "https://shop.example/lamp"
+                    },
+                    { "title": "Desk", "price": null, "stock": null, "sale": false, "link": null }
+                ],
+                "tags": []
+            })
+        );
+    }
+
+    #[test]
+    fn test_script() {
+        let script = script(&results_schema(), false);
+        assert!(script.contains(r#""query":{"css":".price","pierce":false}"#));
+        assert!(script.contains(r#""query":{"pierce":false,"xpath":"//li"}"#));
+        assert_eq!(parse_number("-3 °C"), Some(-3.0));
+        assert_eq!(parse_number("free"), None);
+    }
+}
@@ -0,0 +1,264 @@
+//! Machine-readable failures
+//!
+//! A failed action carries a [`Failure`] next to its error message: an
+//! [`ErrorCode`], the selector and URL involved, and a [`Recovery`] hint, so
+//! a planner can decide whether to retry the step, plan a different one, or
+//! ask the user, without parsing the message. Outputs with `success: false`
+//! (failed assertions) have it in `ActionOutput::failure`; errors have it in
+//! [`ExecutorError::failure`](crate::ExecutorError::failure); plan steps in
+//! `StepTrace::failure`.
+//!
+//! ```json
+//! { "code": "selector_not_found", "recovery": "replan", "selector": "#buy",
+//!   "url": "https://shop.example/item/42" }
+//! ```
+
+use crate::actions::{ActionError, BrowserAction};
+use crate::frames::FrameTarget;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// What kind of failure an action had
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// No element matches the selector
+    SelectorNotFound,
+    InvalidSelector,
+
+    /// An element or condition wait ran out of time
+    Timeout,
+
+    /// A page did not finish loading in time
+    TimeoutNavigation,
+
+    /// The page answered with an HTTP error, or navigation failed otherwise
+    NavigationFailed,
+
+    /// The page could not be reached (DNS, connection, TLS)
+    NetworkError,
+
+    /// The URL policy or network rules refused the URL
+    BlockedByPolicy,
+
+    /// A dialog left open blocks the page
+    DialogUnhandled,
+    TabNotFound,
+    FrameNotFound,
+    DownloadFailed,
+    UploadRejected,
+
+    /// `Assert` found its condition false
+    AssertionFailed,
+
+    /// The browser crashed or stopped responding
+    Crash,
+
+    /// A memory, CPU or plan budget limit was reached
+    ResourceLimit,
+    LaunchFailed,
+
+    /// The browser reported an error
+    BrowserError,
+
+    /// Any other failure
+    ActionFailed,
+}
+
+/// What a planner should do about a failure
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Recovery {
+    /// The same step may succeed on another try
+    Retry,
+
+    /// The step will keep failing; try different steps
+    Replan,
+
+    /// Only the user can resolve it (a blocked site, a limit)
+    AskUser,
+}
+
+impl ErrorCode {
+    pub fn recovery(self) -> Recovery {
+        match self {
+            ErrorCode::Timeout
+            | ErrorCode::TimeoutNavigation
+            | ErrorCode::NetworkError
+            | ErrorCode::DownloadFailed
+            | ErrorCode::Crash
+            | ErrorCode::LaunchFailed
+            | ErrorCode::BrowserError => Recovery::Retry,
+            ErrorCode::SelectorNotFound
+            | ErrorCode::InvalidSelector
+            | ErrorCode::NavigationFailed
+            | ErrorCode::DialogUnhandled
+            | ErrorCode::TabNotFound
+            | ErrorCode::FrameNotFound
+            | ErrorCode::AssertionFailed
+            | ErrorCode::ActionFailed => Recovery::Replan,
+            ErrorCode::BlockedByPolicy | ErrorCode::UploadRejected | ErrorCode::ResourceLimit => {
+                Recovery::AskUser
+            }
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = serde_json::to_value(self).unwrap_or_default();
+        write!(f, "{}", name.as_str().unwrap_or("action_failed"))
+    }
+}
+
+/// A classified failure
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Failure {
+    pub code: ErrorCode,
+    pub recovery: Recovery,
+
+    /// The element the action targeted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub selector: Option<String>,
+
+    /// The URL the action loaded, or the page's URL when it failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+}
+
+impl Failure {
+    pub fn new(code: ErrorCode) -> Self {
+        Self {
+            code,
+            recovery: code.recovery(),
+            selector: None,
+            url: None,
+        }
+    }
+
+    /// A failure of `action`, on a page at `page_url`
+    pub(crate) fn of(code: ErrorCode, action: &BrowserAction, page_url: Option<String>) -> Self {
+        Self {
+            selector: selector(action),
+            url: target_url(action).or(page_url),
+            ..Self::new(code)
+        }
+    }
+}
+
+impl ActionError {
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            ActionError::ElementNotFound(_) => ErrorCode::SelectorNotFound,
+            ActionError::Timeout(_) => ErrorCode::Timeout,
+            ActionError::InvalidSelector(_) => ErrorCode::InvalidSelector,
+            ActionError::ActionFailed(_) => ErrorCode::ActionFailed,
+            ActionError::NavigationFailed(_) => ErrorCode::NavigationFailed,
+            ActionError::NavigationTimeout(_) => ErrorCode::TimeoutNavigation,
+            ActionError::NetworkError(_) => ErrorCode::NetworkError,
+            ActionError::BrowserError(_) => ErrorCode::BrowserError,
+            ActionError::TabNotFound(_) => ErrorCode::TabNotFound,
+            ActionError::DownloadFailed(_) => ErrorCode::DownloadFailed,
+            ActionError::UploadRejected(_) => ErrorCode::UploadRejected,
+            ActionError::FrameNotFound(_) => ErrorCode::FrameNotFound,
+            ActionError::DialogOpen(_) => ErrorCode::DialogUnhandled,
+            ActionError::PolicyViolation(_) => ErrorCode::BlockedByPolicy,
+        }
+    }
+}
+
+/// The element `action` targets, if any (the dragged one for `DragAndDrop`)
+pub(crate) fn selector(action: &BrowserAction) -> Option<String> {
+    let selector = match action {
+        BrowserAction::Click { selector, .. }
+        | BrowserAction::Type { selector, .. }
+        | BrowserAction::SelectOption { selector, .. }
+        | BrowserAction::Hover { selector, .. }
+        | BrowserAction::WaitFor { selector, .. }
+        | BrowserAction::GetText { selector, .. }
+        | BrowserAction::GetAttribute { selector, .. }
+        | BrowserAction::ScreenshotElement { selector, .. }
+        | BrowserAction::UploadFile { selector, .. }
+        | BrowserAction::SwitchFrame {
+            selector_or_index: FrameTarget::Selector(selector),
+        } => selector.to_string(),
+        BrowserAction::DragAndDrop { source, .. } => source.to_string(),
+        BrowserAction::Scroll {
+            selector: Some(selector),
+            ..
+        } => selector.to_string(),
+        BrowserAction::Assert { condition } => condition.selector()?.0.to_string(),
+        BrowserAction::WaitUntil { condition, .. } => {
+            condition.assertion()?.0.selector()?.0.to_string()
+        }
+        BrowserAction::Download {
+            url_or_click_selector,
+            ..
+        } if !url_or_click_selector.starts_with("http") => url_or_click_selector.clone(),
+        _ => return None,
+    };
+    Some(selector)
+}
+
+/// The URL `action` loads, if any
+pub(crate) fn target_url(action: &BrowserAction) -> Option<String> {
+    match action {
+        BrowserAction::Navigate { url, .. } | BrowserAction::NewTab { url: Some(url) } => {
+            Some(url.clone())
+        }
+        BrowserAction::Download {
+            url_or_click_selector,
+            ..
+        } if url_or_click_selector.starts_with("http") => Some(url_or_click_selector.clone()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actions::WaitCondition;
+    use crate::selector::Selector;
+
+    #[test]
+    fn test_failure_of_action() {
+        let click = BrowserAction::Click {
+            selector: Selector::Css("#buy".to_string()),
+            wait_for: None,
+            pierce_shadow: false,
+        };
+        let failure = Failure::of(
+            ActionError::ElementNotFound("#buy".to_string()).code(),
+            &click,
+            Some("https://shop.example/item/42".to_string()),
+        );
+        assert_eq!(
+            serde_json::to_value(&failure).unwrap(),
+            serde_json::json!({
+                "code": "selector_not_found",
+                "recovery": "replan",
+                "selector": "#buy",
+                "url": "https://shop.example/item/42"
+            })
+        );
+
+        let navigate = BrowserAction::Navigate {
+            url: "https://shop.example".to_string(),
+            wait_until: WaitCondition::Load,
+        };
+        let failure = Failure::of(ErrorCode::TimeoutNavigation, &navigate, None);
+        assert_eq!(failure.url.as_deref(), Some("https://shop.example"));
+        assert_eq!(failure.selector, None);
+        assert_eq!(failure.recovery, Recovery::Retry);
+    }
+
+    #[test]
+    fn test_codes() {
+        assert_eq!(ErrorCode::BlockedByPolicy.to_string(), "blocked_by_policy");
+        assert_eq!(ErrorCode::ResourceLimit.recovery(), Recovery::AskUser);
+        assert_eq!(
+            ActionError::DialogOpen("alert".to_string()).code(),
+            ErrorCode::DialogUnhandled
+        );
+    }
+}
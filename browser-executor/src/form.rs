@@ -0,0 +1,194 @@
+//! Structured form filling
+//!
+//! `BrowserAction::FillForm` sets a list of fields in one action. Each field
+//! is set by an injected script that uses the element's native value setter
+//! and fires `input` and `change`, so frameworks that track their own state
+//! see the change. Fields are independent: one that fails is reported in the
+//! output and the rest are still filled.
+
+use crate::selector::{Selector, RESOLVE_QUERY};
+use serde::{Deserialize, Serialize};
+
+/// A form field and the value to give it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FormField {
+    /// The input, select or checkbox; for radios, any selector matching the
+    /// group (e.g. `input[name=size]`)
+    pub selector: Selector,
+
+    #[serde(flatten)]
+    pub value: FieldValue,
+}
+
+/// What to set, by kind of field
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum FieldValue {
+    /// Text-like `<input>` or `<textarea>`
+    Text {
+        value: String,
+    },
+
+    /// `<select>` option, by value or by visible label
+    Select {
+        value: String,
+        #[serde(default)]
+        by_label: bool,
+    },
+
+    Checkbox {
+        checked: bool,
+    },
+
+    /// The radio in the group with this value
+    Radio {
+        value: String,
+    },
+
+    /// `<input type=date>`, as `YYYY-MM-DD`
+    Date {
+        value: String,
+    },
+}
+
+/// Outcome for one field, in the `data` of a `FillForm` output
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FieldResult {
+    pub selector: String,
+    pub success: bool,
+    pub error: Option<String>,
+}
+
+impl FieldResult {
+    pub(crate) fn new(selector: &Selector, error: Option<String>) -> Self {
+        Self {
+            selector: selector.to_string(),
+            success: error.is_none(),
+            error,
+        }
+    }
+}
+
+/// What the fill script reports back
+#[derive(Debug, Deserialize)]
+pub(crate) struct FillOutcome {
+    pub(crate) error: Option<String>,
+}
+
+/// Sets one field; called with the resolver, the selector's query and the
+/// field as JSON, returns `{ error }`
+const FILL_FIELD: &str = r#"(resolve, query, field) => {
+  const elements = resolve(query);
+  const el = elements[0];
+  if (!el) return { error: 'Element not found' };
+
+  const fire = (target) => {
+    target.dispatchEvent(new Event('input', { bubbles: true }));
+    target.dispatchEvent(new Event('change', { bubbles: true }));
+  };
+  const setValue = (target, value) => {
+    const setter = Object.getOwnPropertyDescriptor(Object.getPrototypeOf(target), 'value');
+    if (setter && setter.set) setter.set.call(target, value); else target.value = value;
+  };
+
+  switch (field.kind) {
+    case 'text':
+    case 'date': {
+      if (!('value' in el) || el.tagName === 'SELECT') return { error: 'Not a text input' };
+      el.focus();
+      setValue(el, field.value);
+      fire(el);
+      if (el.value !== field.value) return { error: 'Value not accepted' };
+      return { error: null };
+    }
+    case 'select': {
+      if (el.tagName !== 'SELECT') return { error: 'Not a select' };
+      const option = Array.from(el.options).find((o) =>
+        field.by_label ? o.label.trim() === field.value : o.value === field.value);
+      if (!option) return { error: 'No option ' + field.value };
+      setValue(el, option.value);
+      fire(el);
+      return { error: null };
+    }
+    case 'checkbox': {
+      if (el.type !== 'checkbox') return { error: 'Not a checkbox' };
+      if (el.checked !== field.checked) el.click();
+      return { error: null };
+    }
+    case 'radio': {
+      const radio = elements.find((e) => e.type === 'radio' && e.value === field.value);
+      if (!radio) return { error: 'No radio with value ' + field.value };
+      if (!radio.checked) radio.click();
+      return { error: null };
+    }
+  }
+  return { error: 'Unknown field kind ' + field.kind };
+}"#;
+
+impl FormField {
+    /// Problems detectable without the page
+    pub(crate) fn check(&self) -> Option<String> {
+        match &self.value {
+            FieldValue::Date { value }
+                if chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d").is_err() =>
+            {
+                Some(format!("Invalid date {:?}, expected YYYY-MM-DD", value))
+            }
+            _ => None,
+        }
+    }
+
+    /// Script that fills this field
+    pub(crate) fn script(&self, pierce_shadow: bool) -> String {
+        let query = self.selector.query(pierce_shadow);
+        let field = serde_json::to_string(&self.value).unwrap_or_default();
+        format!("({})({}, {}, {})", FILL_FIELD, RESOLVE_QUERY, query, field)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actions::BrowserAction;
+
+    #[test]
+    fn test_fill_form_deserialize() {
+        let action: BrowserAction = serde_json::from_str(
+            r##"{"type": "fill_form", "fields": [
+                {"selector": "#name", "kind": "text", "value": "Ada"},
+                {"selector": "#country", "kind": "select", "value": "France", "by_label": true},
+                {"selector": "#terms", "kind": "checkbox", "checked": true},
+                {"selector": "input[name=size]", "kind": "radio", "value": "m"},
+                {"selector": "#born", "kind": "date", "value": "1815-12-10"}
+            ]}"##,
+        )
+        .unwrap();
+
+        let BrowserAction::FillForm { fields, .. } = action else {
+            panic!("expected FillForm");
+        };
+        assert_eq!(fields.len(), 5);
+        assert_eq!(
+            fields[1].value,
+            FieldValue::Select {
+                value: "France".to_string(),
+                by_label: true
+            }
+        );
+        assert!(fields.iter().all(|field| field.check().is_none()));
+        assert!(fields[0]
+            .script(false)
+            .ends_with(r##"{"css":"#name","pierce":false}, {"kind":"text","value":"Ada"})"##));
+    }
+
+    #[test]
+    fn test_invalid_date() {
+        let field = FormField {
+            selector: "#born".into(),
+            value: FieldValue::Date {
+                value: "10/12/1815".to_string(),
+            },
+        };
+        assert!(field.check().unwrap().contains("YYYY-MM-DD"));
+    }
+}
@@ -0,0 +1,119 @@
+//! Iframe targeting
+//!
+//! `SwitchFrame` makes a child frame of the current one the target of later
+//! element and script actions on the tab, until `SwitchToMainFrame`. Scripts
+//! run in the frame's execution context, and elements are found by marking
+//! them from that context (see `Selector::mark_script`), since CDP selector
+//! lookups only search the main document. Cross-origin frames run in their
+//! own process and can only be reached this way when the browser is launched
+//! without site isolation (`ExecutorConfig::reach_cross_origin_frames`).
+//!
+//! `Navigate` and `Screenshot` always act on the whole tab.
+
+use crate::selector::Selector;
+use chromiumoxide::cdp::browser_protocol::page::FrameTree;
+use serde::{Deserialize, Serialize};
+
+/// An iframe, by position among the current frame's child frames (document
+/// order) or by a selector for its `<iframe>` element
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum FrameTarget {
+    Index(usize),
+    Selector(Selector),
+}
+
+/// The `index`th child of `parent` (the main frame if `None`) in `tree`
+pub(crate) fn child_frame(tree: &FrameTree, parent: Option<&str>, index: usize) -> Option<String> {
+    let parent = match parent {
+        Some(id) => find(tree, id)?,
+        None => tree,
+    };
+    parent
+        .child_frames
+        .as_ref()?
+        .get(index)
+        .map(|child| child.frame.id.inner().clone())
+}
+
+fn find<'a>(tree: &'a FrameTree, id: &str) -> Option<&'a FrameTree> {
+    if tree.frame.id.inner() == id {
+        return Some(tree);
+    }
+    tree.child_frames
+        .iter()
+        .flatten()
+        .find_map(|child| find(child, id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actions::BrowserAction;
+    use serde_json::json;
+
+    fn tree(id: &str, children: Vec<serde_json::Value>) -> serde_json::Value {
+        json!({
+            "frame": {
+                "id": id,
+                "loaderId": "L",
+                "url": "about:blank",
+                "domainAndRegistry": "",
+                "securityOrigin": "null",
+                "mimeType": "text/html",
+                "secureContextType": "InsecureScheme",
+                "crossOriginIsolatedContextType": "NotIsolated",
+                "gatedAPIFeatures": []
+            },
+            "childFrames": children
+        })
+    }
+
+    #[test]
+    fn test_child_frame() {
+        let tree: FrameTree = serde_json::from_value(tree(
+            "main",
+            vec![
+                tree("login", vec![tree("captcha", vec![])]),
+                tree("ads", vec![]),
+            ],
+        ))
+        .unwrap();
+
+        assert_eq!(child_frame(&tree, None, 1).as_deref(), Some("ads"));
+        assert_eq!(
+            child_frame(&tree, Some("login"), 0).as_deref(),
+            Some("captcha")
+        );
+        assert_eq!(child_frame(&tree, None, 2), None);
+        assert_eq!(child_frame(&tree, Some("captcha"), 0), None);
+        assert_eq!(child_frame(&tree, Some("gone"), 0), None);
+    }
+
+    #[test]
+    fn test_frame_actions_deserialize() {
+        let actions: Vec<BrowserAction> = serde_json::from_str(
+            r#"[
+                {"type": "switch_frame", "selector_or_index": 0},
+                {"type": "switch_frame", "selector_or_index": "iframe#login"},
+                {"type": "switch_frame", "selector_or_index": {"xpath": "//iframe"}},
+                {"type": "switch_to_main_frame"}
+            ]"#,
+        )
+        .unwrap();
+
+        assert!(matches!(
+            &actions[0],
+            BrowserAction::SwitchFrame {
+                selector_or_index: FrameTarget::Index(0)
+            }
+        ));
+        assert!(matches!(
+            &actions[1],
+            BrowserAction::SwitchFrame {
+                selector_or_index: FrameTarget::Selector(Selector::Css(css))
+            } if css == "iframe#login"
+        ));
+        assert!(actions.iter().all(BrowserAction::is_tab_action));
+    }
+}
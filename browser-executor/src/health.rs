@@ -0,0 +1,135 @@
+//! Browser crash detection
+//!
+//! Each launched browser has a [`Health`] that turns unhealthy as soon as a
+//! tab's renderer crashes (`Target.targetCrashed`) or the DevTools connection
+//! fails or closes, instead of when the next action times out. An action
+//! running at that moment fails with `ExecutorError::BrowserCrashed`, and the
+//! next one restarts the browser, restoring the active tab's URL and the
+//! cookies where the old browser still answers.
+
+use chromiumoxide::browser::Browser;
+use chromiumoxide::cdp::browser_protocol::network::{Cookie, CookieParam, TimeSinceEpoch};
+use chromiumoxide::cdp::browser_protocol::storage::{GetCookiesParams, SetCookiesParams};
+use chromiumoxide::cdp::browser_protocol::target::EventTargetCrashed;
+use futures::StreamExt;
+use std::sync::Arc;
+use tokio::sync::watch;
+use tracing::warn;
+
+/// Whether a browser still works, and why not
+#[derive(Debug, Clone)]
+pub(crate) struct Health {
+    failure: Arc<watch::Sender<Option<String>>>,
+}
+
+impl Default for Health {
+    fn default() -> Self {
+        Self {
+            failure: Arc::new(watch::channel(None).0),
+        }
+    }
+}
+
+impl Health {
+    /// Mark the browser unhealthy; the first reason is kept
+    pub(crate) fn fail(&self, reason: String) {
+        self.failure.send_if_modified(|failure| {
+            if failure.is_some() {
+                return false;
+            }
+            warn!("Browser unhealthy: {}", reason);
+            *failure = Some(reason);
+            true
+        });
+    }
+
+    /// Why the browser is unhealthy, if it is
+    pub(crate) fn failure(&self) -> Option<String> {
+        self.failure.borrow().clone()
+    }
+
+    /// Wait for the browser to turn unhealthy; returns why
+    pub(crate) async fn failed(&self) -> String {
+        let mut failure = self.failure.subscribe();
+        let reason = match failure.wait_for(Option::is_some).await {
+            Ok(reason) => reason.clone(),
+            Err(_) => None,
+        };
+        reason.unwrap_or_default()
+    }
+
+    /// Mark the browser unhealthy when one of its tabs crashes
+    pub(crate) async fn watch(&self, browser: &Browser) -> chromiumoxide::Result<()> {
+        let mut crashes = browser.event_listener::<EventTargetCrashed>().await?;
+        let health = self.clone();
+        tokio::spawn(async move {
+            if let Some(event) = crashes.next().await {
+                health.fail(format!(
+                    "Tab {} crashed ({}, code {})",
+                    event.target_id.as_ref(),
+                    event.status,
+                    event.error_code
+                ));
+            }
+        });
+        Ok(())
+    }
+}
+
+/// The browser's cookies, if it still answers
+pub(crate) async fn cookies(browser: &Browser) -> chromiumoxide::Result<Vec<Cookie>> {
+    Ok(browser
+        .execute(GetCookiesParams::default())
+        .await?
+        .result
+        .cookies)
+}
+
+/// Give `browser` the `cookies` of another one
+pub(crate) async fn restore_cookies(
+    browser: &Browser,
+    cookies: &[Cookie],
+) -> chromiumoxide::Result<()> {
+    let params: Vec<CookieParam> = cookies.iter().filter_map(cookie_param).collect();
+    if !params.is_empty() {
+        browser.execute(SetCookiesParams::new(params)).await?;
+    }
+    Ok(())
+}
+
+fn cookie_param(cookie: &Cookie) -> Option<CookieParam> {
+    let mut builder = CookieParam::builder()
+        .name(cookie.name.clone())
+        .value(cookie.value.clone())
+        .domain(cookie.domain.clone())
+        .path(cookie.path.clone())
+        .secure(cookie.secure)
+        .http_only(cookie.http_only)
+        .priority(cookie.priority.clone());
+    if let Some(same_site) = &cookie.same_site {
+        builder = builder.same_site(same_site.clone());
+    }
+    if !cookie.session {
+        builder = builder.expires(TimeSinceEpoch::new(cookie.expires));
+    }
+    builder.build().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_health() {
+        let health = Health::default();
+        assert_eq!(health.failure(), None);
+
+        let waiter = health.clone();
+        let failed = tokio::spawn(async move { waiter.failed().await });
+        health.fail("Tab crashed".to_string());
+        health.fail("Connection closed".to_string());
+
+        assert_eq!(failed.await.unwrap(), "Tab crashed");
+        assert_eq!(health.failure().as_deref(), Some("Tab crashed"));
+    }
+}
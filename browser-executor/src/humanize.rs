@@ -0,0 +1,169 @@
+//! Human-like input pacing
+//!
+//! With `ExecutorConfig::humanize` set, actions on the active tab start after
+//! a short random pause, `Type` types a character at a time with jittered
+//! delays, and `Click`, `ClickNode` and `Hover` move the mouse to a point
+//! near the element's center along a curved path instead of jumping there.
+//! This makes bot detection by input timing harder, and screen recordings
+//! of a session easier to follow.
+
+use chromiumoxide::layout::Point;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Pacing for typing, mouse movement and pauses between actions
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HumanizeConfig {
+    /// Average delay between typed characters (ms)
+    #[serde(default = "default_typing_delay_ms")]
+    pub typing_delay_ms: u64,
+
+    /// Most a character's delay differs from the average (ms)
+    #[serde(default = "default_typing_jitter_ms")]
+    pub typing_jitter_ms: u64,
+
+    /// Mouse moves on the way to an element
+    #[serde(default = "default_mouse_steps")]
+    pub mouse_steps: u32,
+
+    /// Shortest pause before an action (ms)
+    #[serde(default = "default_pause_min_ms")]
+    pub pause_min_ms: u64,
+
+    /// Longest pause before an action (ms)
+    #[serde(default = "default_pause_max_ms")]
+    pub pause_max_ms: u64,
+}
+
+fn default_typing_delay_ms() -> u64 {
+    90
+}
+
+fn default_typing_jitter_ms() -> u64 {
+    60
+}
+
+fn default_mouse_steps() -> u32 {
+    20
+}
+
+fn default_pause_min_ms() -> u64 {
+    150
+}
+
+fn default_pause_max_ms() -> u64 {
+    600
+}
+
+impl Default for HumanizeConfig {
+    fn default() -> Self {
+        Self {
+            typing_delay_ms: default_typing_delay_ms(),
+            typing_jitter_ms: default_typing_jitter_ms(),
+            mouse_steps: default_mouse_steps(),
+            pause_min_ms: default_pause_min_ms(),
+            pause_max_ms: default_pause_max_ms(),
+        }
+    }
+}
+
+impl HumanizeConfig {
+    /// A random pause before an action
+    pub(crate) fn pause(&self) -> Duration {
+        let max = self.pause_max_ms.max(self.pause_min_ms);
+        Duration::from_millis(rand::thread_rng().gen_range(self.pause_min_ms..=max))
+    }
+
+    /// A random delay after a typed character
+    pub(crate) fn keystroke(&self) -> Duration {
+        let low = self.typing_delay_ms.saturating_sub(self.typing_jitter_ms);
+        let high = self.typing_delay_ms + self.typing_jitter_ms;
+        Duration::from_millis(rand::thread_rng().gen_range(low..=high))
+    }
+
+    /// A random delay between two mouse moves
+    pub(crate) fn mouse_move(&self) -> Duration {
+        Duration::from_millis(rand::thread_rng().gen_range(5..=15))
+    }
+
+    /// Points on a curve from a random spot some way off to near `target`,
+    /// ending there; `mouse_steps` of them (at least one)
+    pub(crate) fn mouse_path(&self, target: Point) -> Vec<Point> {
+        let mut rng = rand::thread_rng();
+        let angle = rng.gen_range(0.0..std::f64::consts::TAU);
+        let distance = rng.gen_range(100.0..300.0);
+        let start = Point {
+            x: (target.x + angle.cos() * distance).max(0.0),
+            y: (target.y + angle.sin() * distance).max(0.0),
+        };
+        let end = Point {
+            x: target.x + rng.gen_range(-2.0..=2.0),
+            y: target.y + rng.gen_range(-2.0..=2.0),
+        };
+
+        // A cubic Bezier with control points pulled off the straight line
+        let mut control = || {
+            let along = rng.gen_range(0.2..0.8);
+            Point {
+                x: start.x + (end.x - start.x) * along + rng.gen_range(-60.0..60.0),
+                y: start.y + (end.y - start.y) * along + rng.gen_range(-60.0..60.0),
+            }
+        };
+        let (first, second) = (control(), control());
+        bezier(start, first, second, end, self.mouse_steps.max(1))
+    }
+}
+
+/// `steps` points along a cubic Bezier curve, after `start` and up to `end`
+fn bezier(start: Point, first: Point, second: Point, end: Point, steps: u32) -> Vec<Point> {
+    (1..=steps)
+        .map(|step| {
+            let t = f64::from(step) / f64::from(steps);
+            let u = 1.0 - t;
+            let weights = [u * u * u, 3.0 * u * u * t, 3.0 * u * t * t, t * t * t];
+            let points = [start, first, second, end];
+            Point {
+                x: weights.iter().zip(&points).map(|(w, p)| w * p.x).sum(),
+                y: weights.iter().zip(&points).map(|(w, p)| w * p.y).sum(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_humanize_config() {
+        let config: HumanizeConfig = serde_json::from_str(r#"{"typing_delay_ms": 40}"#).unwrap();
+        assert_eq!(config.typing_delay_ms, 40);
+        assert_eq!(config.mouse_steps, 20);
+
+        for _ in 0..100 {
+            let delay = config.keystroke().as_millis() as u64;
+            assert!(delay <= 100);
+            let pause = config.pause().as_millis() as u64;
+            assert!((150..=600).contains(&pause));
+        }
+    }
+
+    #[test]
+    fn test_mouse_path() {
+        let target = Point { x: 400.0, y: 300.0 };
+        let path = HumanizeConfig::default().mouse_path(target);
+
+        assert_eq!(path.len(), 20);
+        let end = path.last().unwrap();
+        assert!((end.x - target.x).abs() <= 2.0 && (end.y - target.y).abs() <= 2.0);
+
+        // Curved: not all on the line through the ends
+        let start = path[0];
+        let off_line = path.iter().any(|p| {
+            let cross = (end.x - start.x) * (p.y - start.y) - (end.y - start.y) * (p.x - start.x);
+            cross.abs() > 1.0
+        });
+        assert!(off_line);
+    }
+}
@@ -0,0 +1,237 @@
+//! Running the browser in nsjail
+//!
+//! With `ExecutorConfig::enable_sandbox` on Linux, the browser is started
+//! inside nsjail ([`SandboxConfig::browser`]) instead of by chromiumoxide,
+//! and the executor connects to the DevTools address it prints. The jail
+//! shares the host's network so that connection reaches it, and has its own
+//! `/tmp`, where the profile is kept unless one is given. Chrome's own sandbox
+//! stays on, nesting its namespaces inside the jail's. Where nsjail is not
+//! installed, launching fails rather than running the browser unsandboxed.
+
+use crate::executor::{self, ExecutorConfig, ExecutorError};
+use crate::sandbox::{MountPoint, SandboxConfig, SandboxedProcess};
+use chromiumoxide::browser::Browser;
+use chromiumoxide::handler::{Handler, HandlerConfig};
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::process::ChildStderr;
+use std::time::Duration;
+use tokio::sync::oneshot;
+use tracing::{debug, info};
+
+/// How long the browser has to print its DevTools address
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Flags chromiumoxide would otherwise pass, and the jail's own
+const ARGS: &[&str] = &[
+    "--remote-debugging-port=0",
+    "--disable-dev-shm-usage",
+    "--no-first-run",
+    "--no-default-browser-check",
+    "--disable-background-networking",
+    "--disable-breakpad",
+    "--disable-sync",
+    "--password-store=basic",
+    "--use-mock-keychain",
+    "--hide-scrollbars",
+    "--mute-audio",
+];
+
+/// The profile, in the jail's private `/tmp`, when none is given
+const JAIL_PROFILE: &str = "/tmp/profile";
+
+/// Where X servers listen, mounted into the jail for a visible browser
+const X11_SOCKETS: &str = "/tmp/.X11-unix";
+
+/// A jailed browser, killed when dropped; its `/tmp` goes with it
+pub(crate) struct Jail {
+    process: SandboxedProcess,
+}
+
+impl Jail {
+    /// nsjail's process id; the browser's processes descend from it
+    pub(crate) fn pid(&self) -> Option<u32> {
+        self.process.id()
+    }
+}
+
+impl Drop for Jail {
+    fn drop(&mut self) {
+        let _ = self.process.kill();
+    }
+}
+
+/// Whether `config` has the browser run in a jail; an error if it asks for
+/// one and nsjail is not available
+pub(crate) fn wanted(config: &ExecutorConfig) -> Result<bool, ExecutorError> {
+    if !config.enable_sandbox {
+        return Ok(false);
+    }
+    if !SandboxedProcess::is_available() {
+        return Err(ExecutorError::LaunchFailed(
+            "enable_sandbox is set but nsjail is not available".to_string(),
+        ));
+    }
+    Ok(true)
+}
+
+/// Start a browser in a jail and connect to it
+///
+/// `args` are passed on to the browser. Without `user_data_dir`, the profile
+/// is kept in the jail's `/tmp` and goes with it.
+pub(crate) async fn launch(
+    config: &ExecutorConfig,
+    user_data_dir: Option<&Path>,
+    args: Vec<String>,
+) -> Result<(Browser, Handler, Jail), ExecutorError> {
+    let executable = chromiumoxide::browser::default_executable()
+        .map_err(|e| ExecutorError::LaunchFailed(format!("No browser found: {}", e)))?;
+
+    let profile = user_data_dir.map_or_else(|| PathBuf::from(JAIL_PROFILE), Path::to_path_buf);
+
+    let mut sandbox = SandboxConfig::browser(user_data_dir);
+    // The browser's own directory, e.g. /opt/google/chrome
+    if let Some(dir) = executable.parent() {
+        if !sandbox
+            .mount_points
+            .iter()
+            .any(|mount| dir.starts_with(&mount.source))
+        {
+            sandbox.mount_points.push(MountPoint {
+                source: dir.to_path_buf(),
+                target: dir.to_path_buf(),
+                readonly: true,
+            });
+        }
+    }
+    // Where the browser saves downloads
+    if let Ok(dir) = config.downloads.dir() {
+        sandbox.mount_points.push(MountPoint {
+            source: dir.clone(),
+            target: dir,
+            readonly: false,
+        });
+    }
+    // A visible browser needs the X display and its cookie
+    if !config.headless {
+        let xauthority = std::env::var_os("XAUTHORITY").map(PathBuf::from);
+        let display = [Some(PathBuf::from(X11_SOCKETS)), xauthority];
+        for path in display.into_iter().flatten().filter(|path| path.exists()) {
+            sandbox.mount_points.push(MountPoint {
+                source: path.clone(),
+                target: path,
+                readonly: true,
+            });
+        }
+        sandbox.env.extend(
+            ["DISPLAY", "XAUTHORITY"]
+                .into_iter()
+                .filter(|var| std::env::var_os(var).is_some())
+                .map(str::to_string),
+        );
+    }
+    let mut jail = Jail {
+        process: SandboxedProcess::new(sandbox),
+    };
+
+    let mut flags: Vec<String> = ARGS.iter().map(|arg| arg.to_string()).collect();
+    flags.push(format!("--user-data-dir={}", profile.display()));
+    flags.push(format!(
+        "--window-size={},{}",
+        config.viewport_width, config.viewport_height
+    ));
+    if config.headless {
+        flags.push("--headless".to_string());
+    }
+    flags.extend(args);
+    flags.push("about:blank".to_string());
+
+    jail.process
+        .execute_async(&executable.to_string_lossy(), &flags)
+        .map_err(|e| ExecutorError::LaunchFailed(e.to_string()))?;
+    let stderr = jail
+        .process
+        .take_stderr()
+        .ok_or_else(|| ExecutorError::LaunchFailed("No output from nsjail".to_string()))?;
+
+    let (address, listening) = oneshot::channel();
+    std::thread::spawn(move || read_output(stderr, address));
+    let url = tokio::time::timeout(STARTUP_TIMEOUT, listening)
+        .await
+        .map_err(|_| {
+            ExecutorError::LaunchFailed("Browser did not start in the sandbox in time".to_string())
+        })?
+        .map_err(|_| {
+            ExecutorError::LaunchFailed("Browser exited in the sandbox on startup".to_string())
+        })?;
+
+    let handler_config = HandlerConfig {
+        viewport: Some(executor::viewport(config)),
+        ..Default::default()
+    };
+    let (browser, handler) = Browser::connect_with_config(url, handler_config)
+        .await
+        .map_err(|e| ExecutorError::LaunchFailed(e.to_string()))?;
+
+    info!("Browser running in nsjail (pid {:?})", jail.pid());
+    Ok((browser, handler, jail))
+}
+
+/// Pass on the DevTools address the browser prints, then keep draining its
+/// output so it never blocks on a full pipe
+fn read_output(stderr: ChildStderr, address: oneshot::Sender<String>) {
+    let mut address = Some(address);
+    for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+        match (devtools_address(&line), address.take()) {
+            (Some(url), Some(sender)) => {
+                let _ = sender.send(url.to_string());
+            }
+            (_, sender) => {
+                address = sender;
+                debug!("Jailed browser: {}", line);
+            }
+        }
+    }
+}
+
+/// The address in the browser's `DevTools listening on ws://...` line
+fn devtools_address(line: &str) -> Option<&str> {
+    line.trim()
+        .strip_prefix("DevTools listening on ")
+        .filter(|url| url.starts_with("ws://"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_devtools_address() {
+        assert_eq!(
+            devtools_address(
+                "DevTools listening on ws://127.0.0.1:40015/devtools/browser/7c1e5d5f\n"
+            ),
+            Some("ws://127.0.0.1:40015/devtools/browser/7c1e5d5f")
+        );
+        assert_eq!(devtools_address("[I][2024-01-01] Mode: STANDALONE_ONCE"), None);
+    }
+
+    #[test]
+    fn test_wanted() {
+        let config = ExecutorConfig {
+            enable_sandbox: false,
+            ..Default::default()
+        };
+        assert!(!wanted(&config).unwrap());
+
+        // Asked for, the sandbox is never skipped
+        let config = ExecutorConfig {
+            enable_sandbox: true,
+            ..config
+        };
+        match wanted(&config) {
+            Ok(jailed) => assert!(jailed && SandboxedProcess::is_available()),
+            Err(e) => assert!(e.to_string().contains("nsjail")),
+        }
+    }
+}
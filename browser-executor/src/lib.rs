@@ -2,20 +2,130 @@
 //!
 //! This library provides secure browser automation capabilities with:
 //! - Headless Chrome automation via chromiumoxide
-//! - Resource limits (CPU, memory, time)
-//! - Process isolation with nsjail (Linux)
-//! - Screenshot capture
+//! - Attaching to a running Chrome over CDP, limited to the tabs in scope
+//! - Pool of isolated browser instances for concurrent sessions
+//! - Multiple tabs per browser, addressed by index or id
+//! - Request blocking, header injection and domain allowlists
+//! - Response body capture by URL pattern, for sites' own JSON APIs
+//! - URL allow/deny policies for navigations, redirects included
+//! - HTTP(S) and SOCKS5 proxies, with proxy authentication
+//! - HTTP authentication with credentials from a pluggable provider, or
+//!   from a vault encrypted at rest
+//! - Resource limits (CPU, memory, time), enforced by a process monitor
+//! - Process isolation with nsjail (Linux), for the browser itself too
+//! - CSS, XPath, text and ARIA label element selectors
+//! - Iframe targeting for element and script actions
+//! - Dialog handling by policy, or action by action
+//! - Device emulation with phone, tablet and desktop presets
+//! - Viewport resizing, window maximizing and fullscreen after launch
+//! - Cookie-consent banners accepted after navigation, in several languages
+//! - Stealth mode hiding common headless tells from bot detection
+//! - Human-like typing, mouse paths and pauses between actions
+//! - Screenshot capture, including at several viewports
+//! - Screenshot diffs by pixel and perceptual hash, with an annotated image
+//! - Tracked downloads with size limits and checksums
+//! - MHTML page archives
+//! - Form filling with per-field results
+//! - Schema-driven extraction of typed JSON
+//! - Waits for elements, text, URLs, scripts, network idle and downloads
+//! - Scrolling lazy-loaded feeds to the end, counting the items loaded
+//! - Cookie and web storage actions for saving and restoring sessions
+//! - Assertions on elements, text and URLs, with screenshots on failure
+//! - Console message and page error collection, per tab or per action
+//! - Failure artifacts: screenshot, DOM dump and recent console entries
+//! - Accessibility snapshots with clicks by node id
+//! - File uploads restricted to configured roots
+//! - Action plans with variables, conditions, retries and resource budgets
+//! - Independent plans run in parallel across a browser pool
+//! - Recording a person's clicks, typing and navigations as a replayable plan
+//! - Machine-readable error codes with recovery hints for failed actions
+//! - Spans per action and plan, exported over OTLP with the `otel` feature
 //! - Error recovery and automatic browser restart
+//! - Crash detection from CDP events, restoring URL and cookies on restart
 
+pub mod accessibility;
 pub mod actions;
+pub mod archive;
+pub mod artifacts;
+pub mod assertion;
+pub mod auth;
+pub mod capture;
+mod consent;
+pub mod console;
+pub mod dialog;
+pub mod diff;
+pub mod download;
+pub mod emulation;
 pub mod executor;
+pub mod extract;
+pub mod failure;
+pub mod form;
+pub mod frames;
+mod health;
+pub mod humanize;
+mod jail;
+pub mod monitor;
+mod navigation;
+pub mod network;
+pub mod plan;
+pub mod pool;
+pub mod proxy;
+pub mod record;
+pub mod remote;
 pub mod sandbox;
+pub mod selector;
 pub mod screenshot;
+pub mod scroll;
+mod stealth;
+pub mod storage;
+pub mod tabs;
+pub mod telemetry;
+mod upload;
+pub mod url_policy;
+pub mod wait;
+pub mod window;
 
+pub use accessibility::{AccessibleNode, Bounds};
 pub use actions::{ActionExecutor, ActionOutput, ActionResult, BrowserAction, WaitCondition};
+pub use archive::ArchiveResult;
+pub use artifacts::ArtifactConfig;
+pub use assertion::AssertCondition;
+pub use auth::{
+    CredentialProvider, CredentialVault, Credentials, StaticCredentials, VaultError,
+    VAULT_PASSPHRASE_ENV,
+};
+pub use capture::CapturedResponse;
+pub use console::ConsoleEntry;
+pub use dialog::{DialogInfo, DialogPolicy};
+pub use diff::{DiffRegion, DiffResult};
+pub use download::{DownloadConfig, DownloadResult, FinishedDownload};
+pub use emulation::{Device, DevicePreset, DeviceProfile};
+pub use form::{FieldResult, FieldValue, FormField};
+pub use frames::FrameTarget;
+pub use humanize::HumanizeConfig;
 pub use executor::{BrowserExecutor, ExecutorConfig, ExecutorStats};
+pub use extract::{ExtractField, ExtractSchema, ValueType};
+pub use failure::{ErrorCode, Failure, Recovery};
+pub use network::{HeaderRule, NetworkRules};
+pub use plan::{
+    ActionPlan, ActionTarget, BatchItem, BatchResult, PlanBudget, PlanResult, PlanRunner,
+    PlanStep, StepTrace,
+};
+pub use pool::{BrowserPool, PoolConfig, PoolStats, PooledSession};
+pub use proxy::{ProxyConfig, ProxyScheme};
+pub use remote::TabScope;
 pub use sandbox::{MountPoint, SandboxConfig, SandboxedProcess};
 pub use screenshot::{Screenshot, ScreenshotCapturer, ScreenshotFormat, ScreenshotOptions};
+pub use scroll::ScrollResult;
+pub use selector::Selector;
+pub use storage::{BrowserCookie, SameSite};
+pub use tabs::{TabInfo, TabTarget};
+pub use telemetry::{remote_span, TraceParent};
+#[cfg(feature = "otel")]
+pub use telemetry::{init_telemetry, shutdown_telemetry};
+pub use url_policy::UrlPolicy;
+pub use wait::UntilCondition;
+pub use window::ViewportSize;
 
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
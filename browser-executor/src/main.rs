@@ -1,11 +1,16 @@
 //! Browser executor CLI
 
+use aether_config::{ConfigArgs, ConfigCommand};
 use browser_executor::{
-    init_logging, BrowserAction, BrowserExecutor, ExecutorConfig, WaitCondition,
+    init_logging, remote_span, ActionPlan, BrowserAction, BrowserExecutor, CredentialVault,
+    Credentials, ExecutorConfig, PlanRunner, TraceParent, VaultError, WaitCondition,
+    VAULT_PASSPHRASE_ENV,
 };
 use clap::{Parser, Subcommand};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use tokio;
+use tracing::{Instrument, Span};
 
 #[derive(Parser)]
 #[command(name = "browser-executor")]
@@ -14,17 +19,34 @@ struct Cli {
     #[command(subcommand)]
     command: Commands,
 
-    /// Headless mode
-    #[arg(long, default_value_t = true)]
-    headless: bool,
+    /// Headless mode (overrides browser_executor.headless)
+    #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+    headless: Option<bool>,
 
-    /// Maximum memory (MB)
-    #[arg(long, default_value_t = 512)]
-    max_memory: u64,
+    /// Maximum memory in MB (overrides browser_executor.max_memory_mb)
+    #[arg(long)]
+    max_memory: Option<u64>,
 
-    /// Maximum execution time (seconds)
-    #[arg(long, default_value_t = 30)]
-    max_time: u64,
+    /// Maximum execution time in seconds (overrides
+    /// browser_executor.max_execution_time_secs)
+    #[arg(long)]
+    max_time: Option<u64>,
+
+    /// Config file, TOML or YAML (default: AETHER_CONFIG)
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+
+    /// Override a config value, e.g. `--set browser_executor.stealth=true`
+    #[arg(long = "set", global = true, value_name = "KEY=VALUE")]
+    overrides: Vec<String>,
+
+    /// W3C traceparent of the caller's span; `execute` continues its trace
+    #[arg(long, env = "TRACEPARENT")]
+    traceparent: Option<TraceParent>,
+
+    /// OTLP/gRPC endpoint to export spans to (built with the `otel` feature)
+    #[arg(long, env = "OTEL_EXPORTER_OTLP_ENDPOINT")]
+    otlp_endpoint: Option<String>,
 }
 
 #[derive(Subcommand)]
@@ -63,27 +85,108 @@ enum Commands {
         /// Capture full page
         #[arg(long)]
         full_page: bool,
+        /// Capture the full page a viewport at a time (very tall pages,
+        /// lazy-loaded content)
+        #[arg(long)]
+        stitch: bool,
     },
 
-    /// Execute a sequence of actions from JSON file
+    /// Execute a sequence of actions, or an action plan, from JSON file
     Execute {
-        /// JSON file with actions
+        /// JSON file with an array of actions or an `ActionPlan` object
         file: PathBuf,
     },
+
+    /// Record clicks, typing and navigations in a visible browser as an
+    /// action plan, until Ctrl+C
+    Record {
+        /// Output file for the plan (JSON)
+        #[arg(short, long)]
+        output: PathBuf,
+        /// URL to start at
+        #[arg(long)]
+        url: Option<String>,
+        /// Attach to a running Chrome (e.g. http://127.0.0.1:9222) instead
+        /// of launching one
+        #[arg(long)]
+        attach: Option<String>,
+    },
+
+    /// Manage the credential vault (browser_executor.credential_vault),
+    /// with the passphrase in AETHER_VAULT_PASSPHRASE
+    Vault {
+        #[command(subcommand)]
+        command: VaultCommand,
+    },
+
+    /// Check the configuration
+    Validate,
+
+    /// Print the configuration after all layers, as TOML
+    PrintEffectiveConfig,
+}
+
+#[derive(Subcommand)]
+enum VaultCommand {
+    /// Store credentials for an origin, reading the password from stdin
+    Add {
+        /// Origin, e.g. https://intranet.corp
+        origin: String,
+        username: String,
+    },
+
+    /// Forget the credentials for an origin
+    Remove { origin: String },
+
+    /// List the origins with credentials
+    List,
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    init_logging();
-
     let cli = Cli::parse();
+    init_tracing(cli.otlp_endpoint.as_deref()).map_err(|e| e as Box<dyn std::error::Error>)?;
+
+    let mut config_args = ConfigArgs {
+        file: cli.config,
+        overrides: cli.overrides,
+    };
+    if let Some(headless) = cli.headless {
+        config_args.set("browser_executor.headless", headless);
+    }
+    if let Some(max_memory) = cli.max_memory {
+        let max_memory = format!("browser_executor.max_memory_mb={}", max_memory);
+        config_args.overrides.push(max_memory);
+    }
+    if let Some(max_time) = cli.max_time {
+        let max_time = format!("browser_executor.max_execution_time_secs={}", max_time);
+        config_args.overrides.push(max_time);
+    }
 
-    let config = ExecutorConfig {
-        max_memory_mb: cli.max_memory,
-        max_execution_time_secs: cli.max_time,
-        headless: cli.headless,
-        ..Default::default()
+    let config_command = match cli.command {
+        Commands::Validate => Some(ConfigCommand::Validate),
+        Commands::PrintEffectiveConfig => Some(ConfigCommand::PrintEffectiveConfig),
+        _ => None,
     };
+    if let Some(command) = config_command {
+        command.run(&config_args)?;
+        return Ok(());
+    }
+
+    let section = config_args.load()?.browser_executor;
+    if let Commands::Vault { command } = &cli.command {
+        return manage_vault(section.credential_vault.as_deref(), command);
+    }
+
+    let mut config = ExecutorConfig::from(&section);
+    if let Some(vault) = &section.credential_vault {
+        config.credentials = Some(Arc::new(CredentialVault::open_from_env(vault)?));
+    }
+    if let Commands::Record { attach, .. } = &cli.command {
+        // Someone has to see the browser to use it
+        config.headless = false;
+        config.remote_debugging_url = attach.clone();
+    }
 
     let executor = BrowserExecutor::new(config).await?;
 
@@ -118,8 +221,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             // Click element
             println!("Clicking: {}", selector);
             let click_action = BrowserAction::Click {
-                selector: selector.clone(),
+                selector: selector.clone().into(),
                 wait_for: None,
+                pierce_shadow: false,
             };
 
             let result = executor.execute(click_action).await?;
@@ -148,9 +252,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             // Type text
             println!("Typing into: {}", selector);
             let type_action = BrowserAction::Type {
-                selector: selector.clone(),
+                selector: selector.clone().into(),
                 text: text.clone(),
                 clear_first: false,
+                pierce_shadow: false,
             };
 
             let result = executor.execute(type_action).await?;
@@ -166,6 +271,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             url,
             output,
             full_page,
+            stitch,
         } => {
             println!("Navigating to: {}", url);
 
@@ -178,7 +284,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
             // Take screenshot
             println!("Taking screenshot...");
-            let screenshot_action = BrowserAction::Screenshot { full_page };
+            let screenshot_action = BrowserAction::Screenshot { full_page, stitch };
 
             let result = executor.execute(screenshot_action).await?;
 
@@ -196,28 +302,42 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         Commands::Execute { file } => {
             println!("Executing actions from: {}", file.display());
 
-            let json = std::fs::read_to_string(file)?;
-            let actions: Vec<BrowserAction> = serde_json::from_str(&json)?;
-
-            println!("Executing {} actions...", actions.len());
-
-            for (idx, action) in actions.iter().enumerate() {
-                println!("\n[{}/{}] {:?}", idx + 1, actions.len(), action);
-
-                let result = executor.execute(action.clone()).await?;
+            let json: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(file)?)?;
+            if json.is_object() {
+                let plan: ActionPlan = serde_json::from_value(json)?;
+                run_plan(&executor, &plan, cli.traceparent).await;
+            } else {
+                let actions: Vec<BrowserAction> = serde_json::from_value(json)?;
+                run_actions(&executor, &actions, cli.traceparent).await?;
+            }
+        }
 
-                if result.success {
-                    println!("✓ Action succeeded");
-                    if let Some(data) = result.data {
-                        println!("  Data: {}", data);
-                    }
-                } else {
-                    eprintln!("✗ Action failed: {:?}", result.error);
-                }
+        Commands::Record { output, url, .. } => {
+            if let Some(url) = url {
+                println!("Navigating to: {}", url);
+                let nav_action = BrowserAction::Navigate {
+                    url,
+                    wait_until: WaitCondition::Load,
+                };
+                executor.execute(nav_action).await?;
             }
 
-            println!("\n✓ All actions completed");
+            println!("Recording; press Ctrl+C to stop");
+            let stop = async {
+                let _ = tokio::signal::ctrl_c().await;
+            };
+            let plan = executor.record(stop).await?;
+
+            std::fs::write(&output, serde_json::to_string_pretty(&plan)?)?;
+            println!(
+                "✓ Recorded {} steps to {}",
+                plan.steps.len(),
+                output.display()
+            );
         }
+
+        // Handled above
+        Commands::Vault { .. } | Commands::Validate | Commands::PrintEffectiveConfig => {}
     }
 
     // Print stats
@@ -232,6 +352,129 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     );
 
     executor.shutdown().await;
+    #[cfg(feature = "otel")]
+    browser_executor::shutdown_telemetry();
+
+    Ok(())
+}
+
+/// Export spans when given an endpoint, otherwise only log
+#[cfg(feature = "otel")]
+fn init_tracing(endpoint: Option<&str>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    match endpoint {
+        Some(endpoint) => browser_executor::init_telemetry("browser-executor", endpoint),
+        None => {
+            init_logging();
+            Ok(())
+        }
+    }
+}
+
+#[cfg(not(feature = "otel"))]
+fn init_tracing(endpoint: Option<&str>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    init_logging();
+    if endpoint.is_some() {
+        tracing::warn!("Built without the otel feature; spans are not exported");
+    }
+    Ok(())
+}
+
+/// Run a flat list of actions, reporting each
+async fn run_actions(
+    executor: &BrowserExecutor,
+    actions: &[BrowserAction],
+    parent: Option<TraceParent>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    println!("Executing {} actions...", actions.len());
+    let span = parent.as_ref().map_or_else(Span::none, remote_span);
+
+    for (idx, action) in actions.iter().enumerate() {
+        println!("\n[{}/{}] {:?}", idx + 1, actions.len(), action);
+
+        let result = executor
+            .execute(action.clone())
+            .instrument(span.clone())
+            .await?;
+
+        if result.success {
+            println!("✓ Action succeeded");
+            if let Some(data) = result.data {
+                println!("  Data: {}", data);
+            }
+        } else {
+            eprintln!("✗ Action failed: {:?}", result.error);
+        }
+    }
+
+    println!("\n✓ All actions completed");
 
     Ok(())
 }
+
+/// Run an action plan, reporting each step it ran
+async fn run_plan(executor: &BrowserExecutor, plan: &ActionPlan, parent: Option<TraceParent>) {
+    println!("Running plan of {} steps...", plan.steps.len());
+
+    let mut runner = PlanRunner::new(executor);
+    if let Some(parent) = parent {
+        runner = runner.with_trace_parent(parent);
+    }
+    let result = runner.run(plan).await;
+    for step in &result.trace {
+        println!(
+            "\n[{}] {:?} after {} attempt(s), {}ms",
+            step.step, step.outcome, step.attempts, step.duration_ms
+        );
+        if let Some(data) = &step.data {
+            println!("  Data: {}", data);
+        }
+        if let Some(error) = &step.error {
+            eprintln!("  Error: {}", error);
+        }
+    }
+
+    if result.success {
+        println!("\n✓ Plan completed");
+    } else {
+        eprintln!("\n✗ Plan failed");
+    }
+}
+
+/// Add, remove or list the credentials in the vault at `path`
+fn manage_vault(
+    path: Option<&str>,
+    command: &VaultCommand,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let path = path.ok_or("Set browser_executor.credential_vault to use a vault")?;
+    let passphrase = std::env::var(VAULT_PASSPHRASE_ENV).map_err(|_| VaultError::NoPassphrase)?;
+    let mut vault = if Path::new(path).exists() {
+        CredentialVault::open(path, &passphrase)?
+    } else {
+        CredentialVault::new()
+    };
+
+    match command {
+        VaultCommand::Add { origin, username } => {
+            // From stdin, so the password stays out of the shell history
+            let mut password = String::new();
+            std::io::stdin().read_line(&mut password)?;
+            let password = password.trim_end_matches(&['\r', '\n'][..]);
+            vault.insert(origin, Credentials::new(username.as_str(), password));
+            vault.save(path, &passphrase)?;
+            println!("✓ Saved credentials for {}", origin);
+        }
+        VaultCommand::Remove { origin } => {
+            if !vault.remove(origin) {
+                return Err(format!("No credentials for {}", origin).into());
+            }
+            vault.save(path, &passphrase)?;
+            println!("✓ Removed credentials for {}", origin);
+        }
+        VaultCommand::List => {
+            for origin in vault.origins() {
+                println!("{}", origin);
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,208 @@
+//! Browser resource monitoring
+//!
+//! `ExecutorConfig::max_memory_mb` and `max_cpu_percent` are enforced by
+//! sampling the browser's process tree (renderer, GPU and utility processes
+//! included) every `monitor_interval_ms`, for an executor's browser and for
+//! each pooled one. When its resident memory is over the limit, or its CPU
+//! has been over the limit for [`CPU_SAMPLES`] samples in a row, the tree is
+//! killed: the action running fails with
+//! `ExecutorError::ResourceLimitExceeded`, and the next one restarts the
+//! browser. Peak usage is kept in `ExecutorStats`.
+//!
+//! Memory is the resident memory of every process in the tree added up, so
+//! pages the processes share are counted once for each of them. A browser
+//! with a few tabs open easily reaches 1 GB this way, hence the 2048 MB
+//! default.
+
+use crate::executor::ExecutorConfig;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use sysinfo::{Pid, ProcessRefreshKind, System};
+use tracing::{debug, warn};
+
+/// Samples in a row CPU must be over `max_cpu_percent` for, so the bursts of
+/// page loads pass
+pub const CPU_SAMPLES: u32 = 5;
+
+/// Memory and CPU used by a process tree
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct Usage {
+    memory_mb: u64,
+    /// Percent of one core, so over 100 on several
+    cpu_percent: f32,
+}
+
+/// Why the monitor killed the browser, until the executor restarts it
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Breach {
+    reason: Arc<Mutex<Option<String>>>,
+}
+
+impl Breach {
+    fn record(&self, reason: String) {
+        *self.reason.lock().unwrap_or_else(|e| e.into_inner()) = Some(reason);
+    }
+
+    pub(crate) fn get(&self) -> Option<String> {
+        self.reason.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    pub(crate) fn take(&self) -> Option<String> {
+        self.reason.lock().unwrap_or_else(|e| e.into_inner()).take()
+    }
+}
+
+/// Watch the browser process `pid` and its descendants until they exit,
+/// passing each sample's memory (MB) and CPU (percent) to `record` and
+/// killing them when they breach `config`'s limits
+pub(crate) fn spawn<F, R>(pid: u32, config: &ExecutorConfig, breach: Breach, record: F)
+where
+    F: Fn(u64, f32) -> R + Send + 'static,
+    R: Future<Output = ()> + Send,
+{
+    let interval = Duration::from_millis(config.monitor_interval_ms);
+    let max_memory_mb = config.max_memory_mb;
+    let max_cpu_percent = config.max_cpu_percent;
+
+    tokio::spawn(async move {
+        let root = Pid::from_u32(pid);
+        let mut system = System::new();
+        let mut cpu_over = 0;
+
+        loop {
+            tokio::time::sleep(interval).await;
+            let Some((usage, tree)) = sample(&mut system, root) else {
+                debug!("Browser process {} exited; monitor stopped", pid);
+                break;
+            };
+
+            record(usage.memory_mb, usage.cpu_percent).await;
+
+            cpu_over = match max_cpu_percent {
+                Some(max) if usage.cpu_percent > max => cpu_over + 1,
+                _ => 0,
+            };
+            let reason = if max_memory_mb > 0 && usage.memory_mb > max_memory_mb {
+                format!(
+                    "Browser used {}MB of memory, over the {}MB limit",
+                    usage.memory_mb, max_memory_mb
+                )
+            } else if cpu_over >= CPU_SAMPLES {
+                format!(
+                    "Browser used over {:.0}% CPU for {} samples",
+                    max_cpu_percent.unwrap_or_default(),
+                    CPU_SAMPLES
+                )
+            } else {
+                continue;
+            };
+
+            warn!("{}; killing it", reason);
+            // Recorded first, so the failing action can tell why
+            breach.record(reason);
+            for pid in tree {
+                if let Some(process) = system.process(pid) {
+                    process.kill();
+                }
+            }
+            break;
+        }
+    });
+}
+
+/// The usage of `root` and its descendants, with their pids; none once
+/// `root` has exited
+fn sample(system: &mut System, root: Pid) -> Option<(Usage, Vec<Pid>)> {
+    system.refresh_processes_specifics(ProcessRefreshKind::new().with_memory().with_cpu());
+    let processes = system.processes();
+    processes.get(&root)?;
+
+    // Linux lists threads too, sharing their process's memory
+    let threads: HashSet<Pid> = processes
+        .iter()
+        .filter_map(|(pid, process)| Some((pid, process.tasks()?)))
+        .flat_map(|(pid, tasks)| tasks.iter().filter(move |task| *task != pid))
+        .copied()
+        .collect();
+    let parents = processes
+        .iter()
+        .filter(|(pid, _)| !threads.contains(pid))
+        .map(|(pid, process)| (*pid, process.parent()));
+    let tree = descendants(root, parents);
+
+    let (memory, cpu_percent) = tree
+        .iter()
+        .filter_map(|pid| processes.get(pid))
+        .fold((0, 0.0), |(memory, cpu), process| {
+            (memory + process.memory(), cpu + process.cpu_usage())
+        });
+    let usage = Usage {
+        memory_mb: memory / (1024 * 1024),
+        cpu_percent,
+    };
+    Some((usage, tree))
+}
+
+/// `root` and every process descending from it, given each process's parent
+fn descendants<P: Copy + Eq + Hash>(
+    root: P,
+    parents: impl IntoIterator<Item = (P, Option<P>)>,
+) -> Vec<P> {
+    let mut children: HashMap<P, Vec<P>> = HashMap::new();
+    for (pid, parent) in parents {
+        if let Some(parent) = parent {
+            children.entry(parent).or_default().push(pid);
+        }
+    }
+
+    let mut tree = vec![root];
+    let mut seen = HashSet::from([root]);
+    let mut next = 0;
+    while let Some(&pid) = tree.get(next) {
+        for &child in children.get(&pid).into_iter().flatten() {
+            if seen.insert(child) {
+                tree.push(child);
+            }
+        }
+        next += 1;
+    }
+    tree
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_descendants() {
+        // 10 is the browser, 11-13 its renderers and a helper's child
+        let parents = [
+            (1, None),
+            (10, Some(1)),
+            (11, Some(10)),
+            (12, Some(10)),
+            (13, Some(12)),
+            (20, Some(1)),
+        ];
+
+        let mut tree = descendants(10, parents);
+        tree.sort();
+
+        assert_eq!(tree, [10, 11, 12, 13]);
+        assert_eq!(descendants(99, parents), [99]);
+    }
+
+    #[test]
+    fn test_breach() {
+        let breach = Breach::default();
+        assert_eq!(breach.get(), None);
+
+        breach.record("over".to_string());
+        assert_eq!(breach.get().as_deref(), Some("over"));
+        assert_eq!(breach.take().as_deref(), Some("over"));
+        assert_eq!(breach.take(), None);
+    }
+}
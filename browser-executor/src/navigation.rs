@@ -0,0 +1,464 @@
+//! Navigation with real wait conditions
+//!
+//! `Navigate` follows CDP lifecycle and network events for the navigation's
+//! loader: `DomContentLoaded` and `Load` wait for the matching lifecycle
+//! event, and `NetworkIdle` waits for `load` and then for no request to be in
+//! flight for the configured idle time. A document answered with an HTTP
+//! error status fails the navigation. `GoBack` and `GoForward` move through
+//! the browser's session history and wait the same way, for the loader the
+//! main frame navigates with. `WaitUntil` waits for network idle on an
+//! already loaded page the same way.
+
+use crate::actions::{ActionError, ActionResult, WaitCondition};
+use chromiumoxide::cdp::browser_protocol::network::{
+    EnableParams, EventLoadingFailed, EventLoadingFinished, EventRequestWillBeSent,
+    EventResponseReceived, ResourceType,
+};
+use chromiumoxide::cdp::browser_protocol::page::{
+    EventFrameNavigated, EventLifecycleEvent, EventNavigatedWithinDocument,
+    GetNavigationHistoryParams, NavigateParams, NavigateToHistoryEntryParams, NavigationType,
+    SetLifecycleEventsEnabledParams,
+};
+use chromiumoxide::page::Page;
+use futures::StreamExt;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Navigate `page` to `url` and wait for `wait_until`
+pub(crate) async fn navigate(
+    page: &Page,
+    url: &str,
+    wait_until: WaitCondition,
+    timeout: Duration,
+    network_idle: Duration,
+) -> ActionResult<()> {
+    let command = async {
+        let response = page
+            .execute(NavigateParams::new(url))
+            .await
+            .map_err(failed)?
+            .result;
+        if let Some(error) = response.error_text {
+            return Err(navigation_error(url, error));
+        }
+        Ok(match response.loader_id {
+            // Same-document navigation (e.g. a fragment): nothing loads
+            None => Started::SameDocument,
+            Some(loader) => Started::Loader(loader.inner().clone()),
+        })
+    };
+    follow(page, command, url, wait_until, timeout, network_idle).await
+}
+
+/// Go `delta` entries back (negative) or forward in `page`'s session
+/// history and wait for `wait_until`; returns the URL reached
+///
+/// Uses the browser's own history rather than `history.back()`, so pages
+/// that override `window.history` cannot interfere, and a page restored
+/// from the back/forward cache counts as loaded at once.
+pub(crate) async fn go(
+    page: &Page,
+    delta: i64,
+    wait_until: WaitCondition,
+    timeout: Duration,
+    network_idle: Duration,
+) -> ActionResult<String> {
+    let history = page
+        .execute(GetNavigationHistoryParams::default())
+        .await
+        .map_err(failed)?
+        .result;
+    let entry = usize::try_from(history.current_index + delta)
+        .ok()
+        .and_then(|index| history.entries.get(index))
+        .ok_or_else(|| {
+            let direction = if delta < 0 { "back" } else { "forward" };
+            ActionError::NavigationFailed(format!("No history entry to go {} to", direction))
+        })?;
+
+    let command = async {
+        page.execute(NavigateToHistoryEntryParams::new(entry.id))
+            .await
+            .map_err(failed)?;
+        Ok(Started::Pending)
+    };
+    follow(page, command, &entry.url, wait_until, timeout, network_idle).await?;
+
+    let url = page.url().await.ok().flatten();
+    Ok(url.unwrap_or_else(|| entry.url.clone()))
+}
+
+fn failed(e: chromiumoxide::error::CdpError) -> ActionError {
+    ActionError::NavigationFailed(e.to_string())
+}
+
+/// How a navigation command went
+enum Started {
+    /// A document is loading
+    Loader(String),
+    /// Nothing loads
+    SameDocument,
+    /// The loader is known once the main frame navigates
+    Pending,
+}
+
+/// Send the navigation `command`, then wait for `wait_until`
+///
+/// Events are subscribed to before the command is sent, so none is missed.
+async fn follow(
+    page: &Page,
+    command: impl Future<Output = ActionResult<Started>>,
+    url: &str,
+    wait_until: WaitCondition,
+    timeout: Duration,
+    network_idle: Duration,
+) -> ActionResult<()> {
+    let mut lifecycle = page
+        .event_listener::<EventLifecycleEvent>()
+        .await
+        .map_err(failed)?;
+    let mut started = page
+        .event_listener::<EventRequestWillBeSent>()
+        .await
+        .map_err(failed)?;
+    let mut finished = page
+        .event_listener::<EventLoadingFinished>()
+        .await
+        .map_err(failed)?;
+    let mut errored = page
+        .event_listener::<EventLoadingFailed>()
+        .await
+        .map_err(failed)?;
+    let mut responses = page
+        .event_listener::<EventResponseReceived>()
+        .await
+        .map_err(failed)?;
+    let mut navigated = page
+        .event_listener::<EventFrameNavigated>()
+        .await
+        .map_err(failed)?;
+    let mut within_document = page
+        .event_listener::<EventNavigatedWithinDocument>()
+        .await
+        .map_err(failed)?;
+    let main_frame = page.mainframe().await.map_err(failed)?;
+    page.execute(EnableParams::default())
+        .await
+        .map_err(failed)?;
+    page.execute(SetLifecycleEventsEnabledParams::new(true))
+        .await
+        .map_err(failed)?;
+
+    let mut wait = NavigationWait::new(wait_until, network_idle);
+    tokio::pin!(command);
+    let mut sent = false;
+    // Waiting for the main frame to navigate to learn the loader
+    let mut pending = false;
+    let deadline = tokio::time::sleep(timeout);
+    tokio::pin!(deadline);
+
+    loop {
+        let idle_at = wait.idle_at();
+        let idle = tokio::time::sleep_until(idle_at.unwrap_or_else(Instant::now));
+
+        tokio::select! {
+            outcome = &mut command, if !sent => {
+                sent = true;
+                match outcome? {
+                    Started::SameDocument => return Ok(()),
+                    _ if matches!(wait_until, WaitCondition::None) => return Ok(()),
+                    Started::Loader(loader) => wait.set_loader(&loader),
+                    Started::Pending => pending = true,
+                }
+            }
+            Some(event) = navigated.next() => {
+                if pending && event.frame.parent_id.is_none() {
+                    pending = false;
+                    if event.r#type == NavigationType::BackForwardCacheRestore {
+                        return Ok(());
+                    }
+                    wait.set_loader(event.frame.loader_id.inner());
+                }
+            }
+            Some(event) = within_document.next() => {
+                if pending && main_frame.as_ref() == Some(&event.frame_id) {
+                    return Ok(());
+                }
+            }
+            Some(event) = lifecycle.next() => {
+                wait.lifecycle(event.loader_id.inner(), &event.name);
+            }
+            Some(event) = started.next() => wait.request_started(event.request_id.inner()),
+            Some(event) = finished.next() => wait.request_ended(event.request_id.inner()),
+            Some(event) = errored.next() => wait.request_ended(event.request_id.inner()),
+            Some(event) = responses.next() => {
+                if event.r#type == ResourceType::Document {
+                    wait.document(
+                        event.loader_id.inner(),
+                        event.response.status,
+                        &event.response.url,
+                    );
+                }
+            }
+            _ = idle, if idle_at.is_some() => {}
+            _ = &mut deadline => {
+                return Err(ActionError::NavigationTimeout(format!(
+                    "{} did not reach {:?} within {}s",
+                    url,
+                    wait_until,
+                    timeout.as_secs()
+                )));
+            }
+        }
+
+        if let Some(result) = wait.check(Instant::now()) {
+            return result;
+        }
+    }
+}
+
+/// The error for a navigation Chrome could not complete: `net::ERR_ABORTED`
+/// (another navigation took over) fails it, a blocked request is a policy
+/// violation, and the rest (DNS, connection, TLS) are network errors
+fn navigation_error(url: &str, error: String) -> ActionError {
+    match error.as_str() {
+        "net::ERR_ABORTED" => ActionError::NavigationFailed(format!("{}: {}", url, error)),
+        "net::ERR_BLOCKED_BY_CLIENT" | "net::ERR_BLOCKED_BY_ADMINISTRATOR" => {
+            ActionError::PolicyViolation(format!("{} was blocked: {}", url, error))
+        }
+        _ => ActionError::NetworkError(format!("{}: {}", url, error)),
+    }
+}
+
+/// Wait for no request to be in flight on `page` for `network_idle`,
+/// counting requests started from now on
+pub(crate) async fn network_idle(
+    page: &Page,
+    network_idle: Duration,
+    timeout: Duration,
+) -> ActionResult<()> {
+    let failed = |e: chromiumoxide::error::CdpError| ActionError::BrowserError(e.to_string());
+
+    let mut started = page
+        .event_listener::<EventRequestWillBeSent>()
+        .await
+        .map_err(failed)?;
+    let mut finished = page
+        .event_listener::<EventLoadingFinished>()
+        .await
+        .map_err(failed)?;
+    let mut errored = page
+        .event_listener::<EventLoadingFailed>()
+        .await
+        .map_err(failed)?;
+    page.execute(EnableParams::default())
+        .await
+        .map_err(failed)?;
+
+    let mut wait = NavigationWait::loaded(network_idle);
+    let deadline = tokio::time::sleep(timeout);
+    tokio::pin!(deadline);
+
+    loop {
+        if let Some(result) = wait.check(Instant::now()) {
+            return result;
+        }
+        let idle_at = wait.idle_at();
+        let idle = tokio::time::sleep_until(idle_at.unwrap_or_else(Instant::now));
+
+        tokio::select! {
+            Some(event) = started.next() => wait.request_started(event.request_id.inner()),
+            Some(event) = finished.next() => wait.request_ended(event.request_id.inner()),
+            Some(event) = errored.next() => wait.request_ended(event.request_id.inner()),
+            _ = idle, if idle_at.is_some() => {}
+            _ = &mut deadline => {
+                return Err(ActionError::Timeout(format!(
+                    "Network not idle for {}ms within {}ms",
+                    network_idle.as_millis(),
+                    timeout.as_millis()
+                )));
+            }
+        }
+    }
+}
+
+/// What has been seen of a navigation, and whether it is done
+pub(crate) struct NavigationWait {
+    condition: WaitCondition,
+    network_idle: Duration,
+    /// The navigation's loader, once `Page.navigate` returns
+    loader: Option<String>,
+    /// Lifecycle event names by loader (events may precede the loader id)
+    lifecycle: HashMap<String, HashSet<String>>,
+    /// Main document status and URL by loader
+    documents: HashMap<String, (i64, String)>,
+    inflight: HashSet<String>,
+    /// When the last in-flight request ended
+    idle_since: Instant,
+}
+
+impl NavigationWait {
+    pub(crate) fn new(condition: WaitCondition, network_idle: Duration) -> Self {
+        Self {
+            condition,
+            network_idle,
+            loader: None,
+            lifecycle: HashMap::new(),
+            documents: HashMap::new(),
+            inflight: HashSet::new(),
+            idle_since: Instant::now(),
+        }
+    }
+
+    /// For a page already loaded, where only the network is waited for
+    pub(crate) fn loaded(network_idle: Duration) -> Self {
+        let mut wait = Self::new(WaitCondition::NetworkIdle, network_idle);
+        wait.set_loader("");
+        wait.lifecycle("", "load");
+        wait
+    }
+
+    pub(crate) fn set_loader(&mut self, loader: &str) {
+        self.loader = Some(loader.to_string());
+    }
+
+    pub(crate) fn lifecycle(&mut self, loader: &str, name: &str) {
+        self.lifecycle
+            .entry(loader.to_string())
+            .or_default()
+            .insert(name.to_string());
+    }
+
+    pub(crate) fn document(&mut self, loader: &str, status: i64, url: &str) {
+        self.documents
+            .insert(loader.to_string(), (status, url.to_string()));
+    }
+
+    pub(crate) fn request_started(&mut self, id: &str) {
+        self.inflight.insert(id.to_string());
+    }
+
+    pub(crate) fn request_ended(&mut self, id: &str) {
+        if self.inflight.remove(id) && self.inflight.is_empty() {
+            self.idle_since = Instant::now();
+        }
+    }
+
+    /// When the network will have been idle long enough, if it is idle
+    pub(crate) fn idle_at(&self) -> Option<Instant> {
+        let waiting = matches!(self.condition, WaitCondition::NetworkIdle) && self.seen("load");
+        (waiting && self.inflight.is_empty()).then(|| self.idle_since + self.network_idle)
+    }
+
+    fn seen(&self, name: &str) -> bool {
+        self.loader
+            .as_ref()
+            .and_then(|loader| self.lifecycle.get(loader))
+            .is_some_and(|names| names.contains(name))
+    }
+
+    /// The navigation's outcome, once known
+    pub(crate) fn check(&self, now: Instant) -> Option<ActionResult<()>> {
+        let loader = self.loader.as_ref()?;
+
+        if let Some((status, url)) = self.documents.get(loader) {
+            if *status >= 400 {
+                return Some(Err(ActionError::NavigationFailed(format!(
+                    "HTTP {} for {}",
+                    status, url
+                ))));
+            }
+        }
+
+        let done = match self.condition {
+            WaitCondition::None => true,
+            WaitCondition::DomContentLoaded => self.seen("DOMContentLoaded"),
+            WaitCondition::Load => self.seen("load"),
+            WaitCondition::NetworkIdle => self.idle_at().is_some_and(|at| now >= at),
+        };
+        done.then_some(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wait_for_lifecycle_events() {
+        let mut wait = NavigationWait::new(WaitCondition::DomContentLoaded, Duration::ZERO);
+        // Events can arrive before Page.navigate returns the loader
+        wait.lifecycle("L1", "init");
+        assert!(wait.check(Instant::now()).is_none());
+        wait.set_loader("L1");
+        assert!(wait.check(Instant::now()).is_none());
+
+        wait.lifecycle("other-frame", "DOMContentLoaded");
+        assert!(wait.check(Instant::now()).is_none());
+        wait.lifecycle("L1", "DOMContentLoaded");
+        assert!(matches!(wait.check(Instant::now()), Some(Ok(()))));
+    }
+
+    #[test]
+    fn test_navigation_error() {
+        let url = "https://nowhere.invalid";
+        let error = navigation_error(url, "net::ERR_NAME_NOT_RESOLVED".to_string());
+        assert!(matches!(error, ActionError::NetworkError(_)));
+        let error = navigation_error(url, "net::ERR_BLOCKED_BY_CLIENT".to_string());
+        assert!(matches!(error, ActionError::PolicyViolation(_)));
+        let error = navigation_error(url, "net::ERR_ABORTED".to_string());
+        assert!(matches!(error, ActionError::NavigationFailed(_)));
+    }
+
+    #[test]
+    fn test_network_idle() {
+        let idle = Duration::from_millis(500);
+        let mut wait = NavigationWait::new(WaitCondition::NetworkIdle, idle);
+        wait.set_loader("L1");
+        wait.request_started("doc");
+        wait.request_started("img");
+        wait.request_ended("doc");
+        wait.lifecycle("L1", "load");
+        assert!(wait.idle_at().is_none());
+
+        wait.request_ended("img");
+        let at = wait.idle_at().unwrap();
+        assert!(wait.check(at - Duration::from_millis(1)).is_none());
+        assert!(matches!(wait.check(at), Some(Ok(()))));
+
+        // A new request restarts the idle period
+        wait.request_started("xhr");
+        assert!(wait.check(at + idle).is_none());
+    }
+
+    #[test]
+    fn test_loaded_page_network_idle() {
+        let idle = Duration::from_millis(500);
+        let mut wait = NavigationWait::loaded(idle);
+        wait.request_started("xhr");
+        assert!(wait.idle_at().is_none());
+
+        // Requests from before the wait are unknown, and ignored
+        wait.request_ended("earlier");
+        assert!(wait.idle_at().is_none());
+        wait.request_ended("xhr");
+        let at = wait.idle_at().unwrap();
+        assert!(matches!(wait.check(at), Some(Ok(()))));
+    }
+
+    #[test]
+    fn test_http_error_fails() {
+        let mut wait = NavigationWait::new(WaitCondition::Load, Duration::ZERO);
+        wait.set_loader("L1");
+        wait.document("L1", 404, "https://example.com/missing");
+
+        match wait.check(Instant::now()) {
+            Some(Err(ActionError::NavigationFailed(message))) => {
+                assert_eq!(message, "HTTP 404 for https://example.com/missing")
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+}
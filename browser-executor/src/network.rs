@@ -0,0 +1,471 @@
+//! Request interception and blocking rules
+//!
+//! With any [`NetworkRules`] set, every page pauses its requests through the
+//! CDP Fetch domain. Requests matching a block pattern, or to a host outside
+//! `allowed_domains`, fail as blocked by the client; the rest continue with
+//! the configured headers added. Blocking ads and trackers makes pages load
+//! faster and keeps automation from leaking to third parties.
+//!
+//! Pages also intercept when the configured proxy needs credentials, or a
+//! [`CredentialProvider`] is set, to answer auth challenges, and when there
+//! is a [`UrlPolicy`] for document requests.
+
+use crate::auth::{CredentialProvider, Credentials};
+use crate::proxy::ProxyConfig;
+use crate::url_policy::{UrlPolicy, Violations};
+use chromiumoxide::cdp::browser_protocol::fetch::{
+    AuthChallengeResponse, AuthChallengeResponseResponse, AuthChallengeSource,
+    ContinueRequestParams, ContinueWithAuthParams, EnableParams, EventAuthRequired,
+    EventRequestPaused, FailRequestParams, HeaderEntry, RequestId,
+};
+use chromiumoxide::cdp::browser_protocol::network::{
+    ErrorReason, EventLoadingFinished, ResourceType,
+};
+use chromiumoxide::page::Page;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tracing::{debug, warn};
+
+/// Auth challenges a page remembers answering, to tell a rejection from a
+/// first challenge; older ones are forgotten
+const ANSWERED_CHALLENGES: usize = 64;
+
+/// Interception rules, set in `ExecutorConfig::network_rules`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NetworkRules {
+    /// URL patterns to block; `*` matches any run of characters and `?` one
+    /// character, e.g. `*://*.doubleclick.net/*`
+    #[serde(default)]
+    pub block_patterns: Vec<String>,
+
+    /// If set, only hosts in these domains (or their subdomains) are reached
+    #[serde(default)]
+    pub allowed_domains: Option<Vec<String>>,
+
+    /// Headers added to outgoing requests
+    #[serde(default)]
+    pub headers: Vec<HeaderRule>,
+}
+
+/// A header to inject, optionally only for some domains
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HeaderRule {
+    pub name: String,
+    pub value: String,
+
+    /// Domains (with subdomains) to send it to; all requests if unset
+    #[serde(default)]
+    pub domains: Option<Vec<String>>,
+}
+
+impl NetworkRules {
+    /// Whether there is nothing to intercept
+    pub fn is_empty(&self) -> bool {
+        self.block_patterns.is_empty() && self.allowed_domains.is_none() && self.headers.is_empty()
+    }
+
+    /// Whether a request to `url` is refused
+    pub fn is_blocked(&self, url: &str) -> bool {
+        if self
+            .block_patterns
+            .iter()
+            .any(|pattern| wildcard_match(pattern, url))
+        {
+            return true;
+        }
+
+        // Hostless URLs (data:, about:, blob:) never leave the browser
+        match (&self.allowed_domains, host(url)) {
+            (Some(domains), Some(host)) => !in_domains(&host, domains),
+            _ => false,
+        }
+    }
+
+    /// Headers to add to a request to `url`
+    pub fn headers_for(&self, url: &str) -> Vec<&HeaderRule> {
+        let host = host(url);
+        self.headers
+            .iter()
+            .filter(|rule| match (&rule.domains, &host) {
+                (None, _) => true,
+                (Some(domains), Some(host)) => in_domains(host, domains),
+                (Some(_), None) => false,
+            })
+            .collect()
+    }
+}
+
+pub(crate) fn host(url: &str) -> Option<String> {
+    url::Url::parse(url)
+        .ok()?
+        .host_str()
+        .map(|host| host.to_ascii_lowercase())
+}
+
+pub(crate) fn in_domains(host: &str, domains: &[String]) -> bool {
+    domains.iter().any(|domain| {
+        let domain = domain.trim_start_matches('.').to_ascii_lowercase();
+        host == domain || host.ends_with(&format!(".{}", domain))
+    })
+}
+
+/// Match `text` against a pattern where `*` is any run and `?` any character
+pub(crate) fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Position of the last `*` and of the text it was tried against
+    let mut star: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                // Let the `*` swallow one more character
+                Some((star_p, star_t)) => {
+                    p = star_p + 1;
+                    t = star_t + 1;
+                    star = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Start applying `rules` to every request `page` makes and `policy` to its
+/// document requests, recording what the policy blocks in `violations`, and
+/// answering auth challenges from `proxy` if it has credentials and from
+/// servers if there is a `provider`
+///
+/// Does nothing when none applies, so pages are only paused when needed.
+pub(crate) async fn intercept(
+    page: &Page,
+    rules: &NetworkRules,
+    policy: &UrlPolicy,
+    violations: Violations,
+    proxy: Option<&ProxyConfig>,
+    provider: Option<Arc<dyn CredentialProvider>>,
+) -> chromiumoxide::Result<()> {
+    let credentials = proxy
+        .and_then(ProxyConfig::credentials)
+        .map(|(username, password)| (username.to_string(), password.to_string()));
+    let handle_auth = credentials.is_some() || provider.is_some();
+    if rules.is_empty() && policy.is_empty() && !handle_auth {
+        return Ok(());
+    }
+
+    // Listen before enabling so no paused request goes unanswered
+    let mut paused = page.event_listener::<EventRequestPaused>().await?;
+    let rules = Arc::new(rules.clone());
+    let policy = Arc::new(policy.clone());
+    let page_handle = page.clone();
+
+    tokio::spawn(async move {
+        while let Some(event) = paused.next().await {
+            let result = resolve(&page_handle, &rules, &policy, &violations, &event).await;
+            if let Err(e) = result {
+                warn!("Failed to resolve request {}: {}", event.request.url, e);
+            }
+        }
+    });
+
+    if handle_auth {
+        let mut challenges = page.event_listener::<EventAuthRequired>().await?;
+        let page_handle = page.clone();
+
+        tokio::spawn(async move {
+            let mut answered = VecDeque::new();
+            while let Some(event) = challenges.next().await {
+                let response =
+                    answer(&event, &credentials, provider.as_deref(), &mut answered).await;
+                let params = ContinueWithAuthParams::new(event.request_id.clone(), response);
+                if let Err(e) = page_handle.execute(params).await {
+                    warn!("Failed to answer auth for {}: {}", event.request.url, e);
+                }
+            }
+        });
+    }
+
+    page.execute(EnableParams {
+        handle_auth_requests: handle_auth.then_some(true),
+        ..Default::default()
+    })
+    .await?;
+    Ok(())
+}
+
+/// The response to an auth challenge: proxy challenges get the proxy's
+/// credentials and server challenges the provider's, or the default handling
+/// when there are none configured
+async fn answer(
+    event: &EventAuthRequired,
+    proxy_credentials: &Option<(String, String)>,
+    provider: Option<&dyn CredentialProvider>,
+    answered: &mut VecDeque<(RequestId, bool)>,
+) -> AuthChallengeResponse {
+    let challenge = &event.auth_challenge;
+    let from_proxy = challenge.source == Some(AuthChallengeSource::Proxy);
+    let configured = if from_proxy {
+        proxy_credentials.is_some()
+    } else {
+        provider.is_some()
+    };
+    if !configured {
+        return AuthChallengeResponse::new(AuthChallengeResponseResponse::Default);
+    }
+
+    let cancel = AuthChallengeResponse::new(AuthChallengeResponseResponse::CancelAuth);
+    // A second challenge for the same request means the credentials were
+    // rejected; cancel instead of looping
+    let key = (event.request_id.clone(), from_proxy);
+    if answered.contains(&key) {
+        let source = if from_proxy { "Proxy" } else { &challenge.origin };
+        warn!("{} rejected the configured credentials", source);
+        return cancel;
+    }
+    if answered.len() == ANSWERED_CHALLENGES {
+        answered.pop_front();
+    }
+    answered.push_back(key);
+
+    let credentials = match (proxy_credentials, provider) {
+        (Some((username, password)), _) if from_proxy => {
+            Some(Credentials::new(username, password))
+        }
+        (_, Some(provider)) => {
+            provider
+                .credentials(&challenge.origin, &challenge.realm)
+                .await
+        }
+        _ => None,
+    };
+    let Some(credentials) = credentials else {
+        warn!("No credentials for {}", challenge.origin);
+        return cancel;
+    };
+
+    AuthChallengeResponse {
+        response: AuthChallengeResponseResponse::ProvideCredentials,
+        username: Some(credentials.username),
+        password: Some(credentials.password),
+    }
+}
+
+/// Fail or continue one paused request
+async fn resolve(
+    page: &Page,
+    rules: &NetworkRules,
+    policy: &UrlPolicy,
+    violations: &Violations,
+    event: &EventRequestPaused,
+) -> chromiumoxide::Result<()> {
+    let url = &event.request.url;
+
+    let policy_check = match event.resource_type {
+        ResourceType::Document => policy.check(url),
+        _ => Ok(()),
+    };
+    if let Err(reason) = policy_check {
+        warn!("Blocked by URL policy: {}", reason);
+        violations.record(reason);
+        page.execute(FailRequestParams::new(
+            event.request_id.clone(),
+            ErrorReason::BlockedByClient,
+        ))
+        .await?;
+        return Ok(());
+    }
+
+    if rules.is_blocked(url) {
+        debug!("Blocked request to {}", url);
+        page.execute(FailRequestParams::new(
+            event.request_id.clone(),
+            ErrorReason::BlockedByClient,
+        ))
+        .await?;
+        return Ok(());
+    }
+
+    let mut params = ContinueRequestParams::new(event.request_id.clone());
+    let injected = rules.headers_for(url);
+    if !injected.is_empty() {
+        // CDP replaces all headers, so send the original ones too
+        let mut headers: Vec<HeaderEntry> = event
+            .request
+            .headers
+            .inner()
+            .as_object()
+            .into_iter()
+            .flatten()
+            .filter(|(name, _)| {
+                !injected
+                    .iter()
+                    .any(|rule| rule.name.eq_ignore_ascii_case(name))
+            })
+            .map(|(name, value)| HeaderEntry::new(name, value.as_str().unwrap_or_default()))
+            .collect();
+        headers.extend(
+            injected
+                .iter()
+                .map(|rule| HeaderEntry::new(&rule.name, &rule.value)),
+        );
+        params.headers = Some(headers);
+    }
+
+    page.execute(params).await?;
+    Ok(())
+}
+
+/// Bytes a tab has received, as sent over the wire, including downloads
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Traffic {
+    bytes: Arc<AtomicU64>,
+}
+
+impl Traffic {
+    /// Count what `page` loads from now on
+    pub(crate) async fn watch(page: &Page) -> chromiumoxide::Result<Self> {
+        let mut finished = page.event_listener::<EventLoadingFinished>().await?;
+        let traffic = Self::default();
+        let counter = traffic.clone();
+        tokio::spawn(async move {
+            while let Some(event) = finished.next().await {
+                counter.add(event.encoded_data_length.max(0.0) as u64);
+            }
+        });
+        Ok(traffic)
+    }
+
+    pub(crate) fn add(&self, bytes: u64) {
+        self.bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub(crate) fn bytes(&self) -> u64 {
+        self.bytes.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wildcard_match() {
+        assert!(wildcard_match(
+            "*://*.doubleclick.net/*",
+            "https://ad.doubleclick.net/pixel?id=1"
+        ));
+        assert!(!wildcard_match(
+            "*://*.doubleclick.net/*",
+            "https://doubleclick.network/"
+        ));
+        assert!(wildcard_match("*.png", "https://a.com/logo.png"));
+        assert!(wildcard_match("https://a.com/?", "https://a.com/x"));
+        assert!(!wildcard_match("https://a.com/?", "https://a.com/xy"));
+        assert!(wildcard_match("*", ""));
+    }
+
+    #[test]
+    fn test_rules() {
+        let rules: NetworkRules = serde_json::from_str(
+            r#"{
+                "block_patterns": ["*://*.tracker.io/*"],
+                "allowed_domains": ["example.com", "tracker.io"],
+                "headers": [
+                    {"name": "X-Agent", "value": "aether"},
+                    {"name": "Authorization", "value": "Bearer t", "domains": ["api.example.com"]}
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        assert!(!rules.is_blocked("https://www.example.com/"));
+        assert!(!rules.is_blocked("https://EXAMPLE.com/"));
+        assert!(!rules.is_blocked("data:text/html,hi"));
+        assert!(rules.is_blocked("https://cdn.tracker.io/t.js"));
+        assert!(rules.is_blocked("https://example.com.evil.net/"));
+
+        let names = |url| -> Vec<String> {
+            rules
+                .headers_for(url)
+                .iter()
+                .map(|rule| rule.name.clone())
+                .collect()
+        };
+        assert_eq!(names("https://example.com/"), ["X-Agent"]);
+        assert_eq!(
+            names("https://api.example.com/v1"),
+            ["X-Agent", "Authorization"]
+        );
+
+        assert!(NetworkRules::default().is_empty());
+        assert!(!rules.is_empty());
+    }
+
+    fn challenge(id: &str, source: &str, origin: &str) -> EventAuthRequired {
+        serde_json::from_value(serde_json::json!({
+            "requestId": id,
+            "request": {
+                "url": format!("{}/", origin),
+                "method": "GET",
+                "headers": {},
+                "initialPriority": "VeryHigh",
+                "referrerPolicy": "no-referrer"
+            },
+            "frameId": "F",
+            "resourceType": "Document",
+            "authChallenge": { "source": source, "origin": origin, "scheme": "basic", "realm": "" }
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_auth_answers() {
+        use crate::auth::{Credentials, StaticCredentials};
+        use AuthChallengeResponseResponse::*;
+
+        let provider = StaticCredentials::new()
+            .with("https://intranet.corp", Credentials::new("alice", "s3cret"));
+        let proxy = Some(("proxy-user".to_string(), "pw".to_string()));
+        let mut answered = VecDeque::new();
+
+        let server = challenge("1", "Server", "https://intranet.corp");
+        let response = answer(&server, &proxy, Some(&provider), &mut answered).await;
+        assert_eq!(response.response, ProvideCredentials);
+        assert_eq!(response.username.as_deref(), Some("alice"));
+        let response = answer(&server, &proxy, Some(&provider), &mut answered).await;
+        assert_eq!(response.response, CancelAuth);
+
+        let proxied = challenge("1", "Proxy", "http://proxy.corp:3128");
+        let response = answer(&proxied, &proxy, Some(&provider), &mut answered).await;
+        assert_eq!(response.username.as_deref(), Some("proxy-user"));
+
+        let unknown = challenge("2", "Server", "https://other.corp");
+        let response = answer(&unknown, &proxy, Some(&provider), &mut answered).await;
+        assert_eq!(response.response, CancelAuth);
+        let response = answer(&unknown, &None, None, &mut answered).await;
+        assert_eq!(response.response, Default);
+
+        // Only the latest challenges are remembered
+        for id in 0..ANSWERED_CHALLENGES {
+            let server = challenge(&format!("r{}", id), "Server", "https://intranet.corp");
+            answer(&server, &proxy, Some(&provider), &mut answered).await;
+        }
+        assert_eq!(answered.len(), ANSWERED_CHALLENGES);
+        let response = answer(&server, &proxy, Some(&provider), &mut answered).await;
+        assert_eq!(response.response, ProvideCredentials);
+    }
+}
@@ -0,0 +1,1166 @@
+//! Action plans
+//!
+//! An [`ActionPlan`] is a list of named steps run by a [`PlanRunner`]. A
+//! step runs one action, or branches on a condition. Strings in actions and
+//! conditions may reference variables as `${name}`, or a field of a JSON
+//! value as `${name.field.0}`; each step's output (its `data`, parsed as JSON
+//! when it is JSON) becomes a variable named after the step. A step can be
+//! retried, limited in time, and given `on_failure` steps that run when it
+//! still fails, after which the plan continues; otherwise a failed step ends
+//! the plan. The result traces every step run.
+//!
+//! A plan's `budget` bounds it as a whole: bytes received, wall time and
+//! navigations. Reaching a limit ends the plan with a `Resource limit
+//! exceeded` error, skipping retries and `on_failure` steps, so a runaway
+//! agent loop cannot keep a browser busy forever.
+//!
+//! ```json
+//! {
+//!   "variables": { "query": "desk lamp" },
+//!   "steps": [
+//!     { "name": "open", "action": { "type": "navigate", "url": "https://shop.example" } },
+//!     { "name": "search", "retries": 2, "action": {
+//!         "type": "type", "selector": "#q", "text": "${query}\n", "clear_first": true } },
+//!     { "name": "price", "action": { "type": "get_text", "selector": ".price" } },
+//!     { "name": "check", "if": { "value": "${price}", "contains": "$" },
+//!       "then": [{ "name": "add", "action": { "type": "click", "selector": "#add" } }] }
+//!   ]
+//! }
+//! ```
+
+use crate::actions::{ActionOutput, BrowserAction};
+use crate::executor::{BrowserExecutor, ExecutorError};
+use crate::failure::{ErrorCode, Failure};
+use crate::pool::{BrowserPool, PooledSession};
+use crate::telemetry::{self, TraceParent};
+use async_trait::async_trait;
+use futures::future::BoxFuture;
+use futures::{stream, FutureExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::time::{Duration, Instant};
+use tracing::field::{self, Empty};
+use tracing::{info, info_span, warn, Instrument};
+
+/// Steps to run, with the variables they start from
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ActionPlan {
+    #[serde(default)]
+    pub variables: BTreeMap<String, Value>,
+    pub steps: Vec<PlanStep>,
+
+    #[serde(default, skip_serializing_if = "PlanBudget::is_unlimited")]
+    pub budget: PlanBudget,
+}
+
+/// Limits on a whole plan (none by default)
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlanBudget {
+    /// Bytes received over the network, downloads included
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_bytes: Option<u64>,
+
+    /// Wall time for the whole plan
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_duration_secs: Option<u64>,
+
+    /// Actions that load a document (see [`BrowserAction::is_navigation`])
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_navigations: Option<u32>,
+}
+
+impl PlanBudget {
+    pub fn is_unlimited(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// One step of a plan
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlanStep {
+    /// Names the step in the trace and its output variable
+    pub name: String,
+
+    #[serde(flatten)]
+    pub body: StepBody,
+
+    /// Extra attempts after the first fails
+    #[serde(default)]
+    pub retries: u32,
+
+    /// Pause between attempts
+    #[serde(default = "default_retry_delay_ms")]
+    pub retry_delay_ms: u64,
+
+    /// Time limit for each attempt
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+
+    /// Steps run when this one fails; if they succeed, the plan continues
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub on_failure: Vec<PlanStep>,
+}
+
+pub(crate) fn default_retry_delay_ms() -> u64 {
+    500
+}
+
+/// What a step does
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum StepBody {
+    /// A `BrowserAction` as JSON, with `${...}` references
+    Action { action: Value },
+
+    /// `then` if the condition holds, `else` otherwise
+    Branch {
+        #[serde(rename = "if")]
+        condition: Condition,
+        #[serde(default)]
+        then: Vec<PlanStep>,
+        #[serde(default, rename = "else")]
+        otherwise: Vec<PlanStep>,
+    },
+}
+
+/// A test on a value, e.g. `{ "value": "${price.total}", "greater_than": 100 }`
+///
+/// The test's operand may reference variables too.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Condition {
+    /// A `${...}` reference, or text with references in it
+    pub value: String,
+
+    #[serde(flatten)]
+    pub test: ConditionTest,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConditionTest {
+    /// Equal as JSON, or as text (so `"3"` equals `3`)
+    Equals(Value),
+    NotEquals(Value),
+
+    /// The text contains it, or an array has it as an element
+    Contains(String),
+
+    /// Numbers, or text that parses as one
+    GreaterThan(Value),
+    LessThan(Value),
+
+    /// Whether the value is set and not null
+    Exists(bool),
+}
+
+/// Runs actions for a plan: a `BrowserExecutor` or a pooled session
+#[async_trait]
+pub trait ActionTarget: Send + Sync {
+    async fn execute(&self, action: BrowserAction) -> Result<ActionOutput, ExecutorError>;
+
+    /// Bytes received over the network so far, for [`PlanBudget::max_bytes`]
+    async fn bytes_received(&self) -> u64 {
+        0
+    }
+}
+
+#[async_trait]
+impl ActionTarget for BrowserExecutor {
+    async fn execute(&self, action: BrowserAction) -> Result<ActionOutput, ExecutorError> {
+        BrowserExecutor::execute(self, action).await
+    }
+
+    async fn bytes_received(&self) -> u64 {
+        BrowserExecutor::bytes_received(self).await
+    }
+}
+
+#[async_trait]
+impl ActionTarget for PooledSession {
+    async fn execute(&self, action: BrowserAction) -> Result<ActionOutput, ExecutorError> {
+        PooledSession::execute(self, action).await
+    }
+
+    async fn bytes_received(&self) -> u64 {
+        PooledSession::bytes_received(self).await
+    }
+}
+
+/// The outcome of a plan
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlanResult {
+    pub success: bool,
+
+    /// Every step run, in order
+    pub trace: Vec<StepTrace>,
+
+    /// Variables at the end, including step outputs
+    pub variables: BTreeMap<String, Value>,
+
+    pub duration_ms: u64,
+}
+
+/// One step as it ran
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StepTrace {
+    pub step: String,
+    pub outcome: StepOutcome,
+
+    /// Actions tried (0 for branches and unrunnable actions)
+    pub attempts: u32,
+
+    /// The action as run, with references replaced
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub action: Option<Value>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+
+    /// The last error, classified
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failure: Option<Failure>,
+
+    pub duration_ms: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StepOutcome {
+    Succeeded,
+    Failed,
+
+    /// Failed, but its `on_failure` steps succeeded
+    Recovered,
+
+    /// A branch that took `then`
+    Then,
+
+    /// A branch that took `else`
+    Else,
+}
+
+/// The outcome of plans run together by [`PlanRunner::run_many`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BatchResult {
+    /// One per plan, in the order given
+    pub plans: Vec<BatchItem>,
+
+    pub succeeded: usize,
+    pub failed: usize,
+
+    /// Whether the deadline passed before every plan finished
+    pub timed_out: bool,
+
+    pub duration_ms: u64,
+}
+
+/// One plan of a batch
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BatchItem {
+    pub success: bool,
+
+    /// The plan's result; none when it got no browser or the deadline cut it
+    /// short
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<PlanResult>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Runs plans against an [`ActionTarget`]
+pub struct PlanRunner<'a> {
+    target: &'a dyn ActionTarget,
+    variables: BTreeMap<String, Value>,
+    trace: Vec<StepTrace>,
+    budget: Option<PlanBudget>,
+    spent: Spent,
+    parent: Option<TraceParent>,
+}
+
+/// What a plan has used of its budget
+struct Spent {
+    start: Instant,
+    bytes_at_start: u64,
+    navigations: u32,
+    /// A limit was reached, ending the plan
+    exhausted: bool,
+}
+
+impl<'a> PlanRunner<'a> {
+    pub fn new(target: &'a dyn ActionTarget) -> Self {
+        Self {
+            target,
+            variables: BTreeMap::new(),
+            trace: Vec::new(),
+            budget: None,
+            spent: Spent {
+                start: Instant::now(),
+                bytes_at_start: 0,
+                navigations: 0,
+                exhausted: false,
+            },
+            parent: None,
+        }
+    }
+
+    /// Set a variable, overriding the plan's value for it
+    pub fn with_variable(mut self, name: impl Into<String>, value: Value) -> Self {
+        self.variables.insert(name.into(), value);
+        self
+    }
+
+    /// Set the budget, overriding the plan's
+    pub fn with_budget(mut self, budget: PlanBudget) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+
+    /// Continue the caller's trace: the plan's span becomes a child of
+    /// `parent`
+    pub fn with_trace_parent(mut self, parent: TraceParent) -> Self {
+        self.parent = Some(parent);
+        self
+    }
+
+    /// Run independent plans on `pool`'s browsers, up to `concurrency` at
+    /// once, each with a session of its own
+    ///
+    /// Plans still running or waiting when `deadline` passes fail with a
+    /// timeout. A plan that fails, or gets no browser, does not stop the
+    /// others.
+    pub async fn run_many(
+        pool: &BrowserPool,
+        plans: &[ActionPlan],
+        concurrency: usize,
+        deadline: Duration,
+    ) -> BatchResult {
+        run_batch(plans, concurrency, deadline, || pool.checkout()).await
+    }
+
+    /// Run `plan` until it ends, a step fails for good or its budget runs
+    /// out, in a `plan` span
+    pub async fn run(self, plan: &ActionPlan) -> PlanResult {
+        let span = info_span!(
+            "plan",
+            steps = plan.steps.len(),
+            trace_id = self.parent.as_ref().map(|parent| parent.trace_id.as_str()),
+            success = Empty,
+            duration_ms = Empty,
+        );
+        if let Some(parent) = &self.parent {
+            telemetry::set_parent(&span, parent);
+        }
+
+        let result = self.run_plan(plan).instrument(span.clone()).await;
+        span.record("success", result.success);
+        span.record("duration_ms", result.duration_ms);
+        result
+    }
+
+    async fn run_plan(mut self, plan: &ActionPlan) -> PlanResult {
+        let start = Instant::now();
+        for (name, value) in &plan.variables {
+            self.variables
+                .entry(name.clone())
+                .or_insert_with(|| value.clone());
+        }
+        let budget = self.budget.get_or_insert_with(|| plan.budget.clone());
+        let bytes_at_start = if budget.max_bytes.is_some() {
+            self.target.bytes_received().await
+        } else {
+            0
+        };
+        self.spent = Spent {
+            start,
+            bytes_at_start,
+            navigations: 0,
+            exhausted: false,
+        };
+
+        let success = self.run_steps(&plan.steps).await;
+        info!(
+            "Plan {} after {} steps",
+            if success { "succeeded" } else { "failed" },
+            self.trace.len()
+        );
+
+        PlanResult {
+            success,
+            trace: self.trace,
+            variables: self.variables,
+            duration_ms: start.elapsed().as_millis() as u64,
+        }
+    }
+
+    /// Run `steps` in order; false if one failed without recovering
+    fn run_steps<'s>(&'s mut self, steps: &'s [PlanStep]) -> BoxFuture<'s, bool> {
+        async move {
+            for step in steps {
+                let span = info_span!(
+                    "plan_step",
+                    step = %step.name,
+                    retries = Empty,
+                    outcome = Empty
+                );
+                let index = self.trace.len();
+                let succeeded = self.run_step(step).instrument(span.clone()).await;
+                if let Some(trace) = self.trace.get(index) {
+                    span.record("retries", trace.attempts.saturating_sub(1));
+                    span.record("outcome", field::debug(trace.outcome));
+                }
+                if !succeeded {
+                    return false;
+                }
+            }
+            true
+        }
+        .boxed()
+    }
+
+    async fn run_step(&mut self, step: &PlanStep) -> bool {
+        let start = Instant::now();
+        let index = self.trace.len();
+        let succeeded = match &step.body {
+            StepBody::Action { action } => {
+                let trace = self.run_action(step, action).await;
+                let succeeded = trace.outcome == StepOutcome::Succeeded;
+                self.trace.push(trace);
+                succeeded
+            }
+            StepBody::Branch {
+                condition,
+                then,
+                otherwise,
+            } => {
+                self.trace.push(StepTrace::new(step, StepOutcome::Failed));
+                match condition.holds(&self.variables) {
+                    Ok(true) => {
+                        self.trace[index].outcome = StepOutcome::Then;
+                        self.run_steps(then).await
+                    }
+                    Ok(false) => {
+                        self.trace[index].outcome = StepOutcome::Else;
+                        self.run_steps(otherwise).await
+                    }
+                    Err(error) => {
+                        self.trace[index].error = Some(error);
+                        self.trace[index].failure = Some(Failure::new(ErrorCode::ActionFailed));
+                        false
+                    }
+                }
+            }
+        };
+        self.trace[index].duration_ms = start.elapsed().as_millis() as u64;
+        if succeeded {
+            return true;
+        }
+
+        match &self.trace[index].error {
+            Some(error) => warn!("Step {} failed: {}", step.name, error),
+            None => warn!("Step {} failed", step.name),
+        }
+        if self.spent.exhausted
+            || step.on_failure.is_empty()
+            || !self.run_steps(&step.on_failure).await
+        {
+            return false;
+        }
+        self.trace[index].outcome = StepOutcome::Recovered;
+        true
+    }
+
+    /// Run an action step with its retries, recording its output
+    async fn run_action(&mut self, step: &PlanStep, action: &Value) -> StepTrace {
+        let failed = |error: String| StepTrace {
+            error: Some(error),
+            failure: Some(Failure::new(ErrorCode::ActionFailed)),
+            ..StepTrace::new(step, StepOutcome::Failed)
+        };
+        let rendered = match render(action, &self.variables) {
+            Ok(rendered) => rendered,
+            Err(error) => return failed(error),
+        };
+        let parsed: BrowserAction = match serde_json::from_value(rendered.clone()) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                return StepTrace {
+                    action: Some(rendered),
+                    ..failed(format!("Invalid action: {}", e))
+                }
+            }
+        };
+
+        let mut trace = StepTrace {
+            action: Some(rendered),
+            ..StepTrace::new(step, StepOutcome::Failed)
+        };
+        for attempt in 1..=step.retries + 1 {
+            if attempt > 1 {
+                tokio::time::sleep(Duration::from_millis(step.retry_delay_ms)).await;
+            }
+            if let Some(error) = self.over_budget(Some(&parsed)).await {
+                self.spent.exhausted = true;
+                trace.error = Some(error.to_string());
+                trace.failure = Some(error.failure());
+                break;
+            }
+            trace.attempts = attempt;
+            if parsed.is_navigation() {
+                self.spent.navigations += 1;
+            }
+
+            // The step's time limit, or the plan's if that ends sooner
+            let step_limit = step.timeout_secs.map(|secs| {
+                let error = ExecutorError::Timeout(format!("Step took over {}s", secs));
+                (Duration::from_secs(secs), error)
+            });
+            let limit = match (step_limit, self.time_left()) {
+                (Some(step_limit), Some(plan_limit)) if step_limit.0 <= plan_limit.0 => {
+                    Some(step_limit)
+                }
+                (_, Some(plan_limit)) => Some(plan_limit),
+                (step_limit, None) => step_limit,
+            };
+
+            let run = self.target.execute(parsed.clone());
+            let result = match limit {
+                Some((limit, error)) => tokio::time::timeout(limit, run)
+                    .await
+                    .unwrap_or(Err(error)),
+                None => run.await,
+            };
+
+            // A limit reached while the action ran ends the plan too
+            let exceeded = match &result {
+                Err(e @ ExecutorError::ResourceLimitExceeded(_)) => {
+                    Some((e.to_string(), e.failure()))
+                }
+                _ => self
+                    .over_budget(None)
+                    .await
+                    .map(|e| (e.to_string(), e.failure())),
+            };
+            if let Some((error, failure)) = exceeded {
+                self.spent.exhausted = true;
+                trace.error = Some(error);
+                trace.failure = Some(failure);
+                break;
+            }
+
+            match result {
+                Ok(output) if output.success => {
+                    self.variables
+                        .insert(step.name.clone(), output_value(output.data.as_deref()));
+                    trace.outcome = StepOutcome::Succeeded;
+                    trace.data = output.data;
+                    trace.error = None;
+                    trace.failure = None;
+                    break;
+                }
+                Ok(output) => {
+                    trace.data = output.data;
+                    trace.error = Some(
+                        output
+                            .error
+                            .unwrap_or_else(|| "Action reported failure".to_string()),
+                    );
+                    trace.failure = output.failure;
+                }
+                Err(e) => {
+                    trace.error = Some(e.to_string());
+                    trace.failure = Some(e.failure());
+                }
+            }
+        }
+        trace
+    }
+
+    /// The limit the plan has reached, if any, checking `next` as the action
+    /// about to run
+    async fn over_budget(&self, next: Option<&BrowserAction>) -> Option<ExecutorError> {
+        let budget = self.budget.as_ref()?;
+        let exceeded = |message: String| Some(ExecutorError::ResourceLimitExceeded(message));
+
+        if let Some(secs) = budget.max_duration_secs {
+            if self.spent.start.elapsed() >= Duration::from_secs(secs) {
+                return exceeded(format!("Plan ran for over {}s", secs));
+            }
+        }
+        if let Some(max) = budget.max_navigations {
+            if next.is_some_and(BrowserAction::is_navigation) && self.spent.navigations >= max {
+                return exceeded(format!("Plan made {} navigations, the most allowed", max));
+            }
+        }
+        if let Some(max) = budget.max_bytes {
+            let received = self
+                .target
+                .bytes_received()
+                .await
+                .saturating_sub(self.spent.bytes_at_start);
+            if received > max {
+                return exceeded(format!("Plan received {} bytes, over {}", received, max));
+            }
+        }
+        None
+    }
+
+    /// Time left in the plan's budget, with the error for running out of it
+    fn time_left(&self) -> Option<(Duration, ExecutorError)> {
+        let secs = self.budget.as_ref()?.max_duration_secs?;
+        let left = Duration::from_secs(secs).saturating_sub(self.spent.start.elapsed());
+        let error = ExecutorError::ResourceLimitExceeded(format!("Plan ran for over {}s", secs));
+        Some((left, error))
+    }
+}
+
+/// [`PlanRunner::run_many`], with the targets `checkout` gives
+async fn run_batch<T, F, Fut>(
+    plans: &[ActionPlan],
+    concurrency: usize,
+    deadline: Duration,
+    checkout: F,
+) -> BatchResult
+where
+    T: ActionTarget,
+    F: Fn() -> Fut,
+    Fut: Future<Output = Result<T, ExecutorError>>,
+{
+    let start = Instant::now();
+    let until = tokio::time::Instant::now() + deadline;
+    let checkout = &checkout;
+    let span = info_span!("plan_batch", plans = plans.len(), concurrency);
+
+    // Unordered, so a slow plan does not hold up starting the next ones
+    let mut outcomes: Vec<(usize, BatchItem, bool)> = stream::iter(plans.iter().enumerate())
+        .map(|(index, plan)| async move {
+            let run = async {
+                let target = checkout().await?;
+                Ok::<_, ExecutorError>(PlanRunner::new(&target).run(plan).await)
+            };
+            match tokio::time::timeout_at(until, run).await {
+                Ok(Ok(result)) => (index, BatchItem::finished(result), false),
+                Ok(Err(e)) => (index, BatchItem::failed(e), false),
+                Err(_) => {
+                    let error = ExecutorError::Timeout(format!(
+                        "Plans took over {}ms",
+                        deadline.as_millis()
+                    ));
+                    (index, BatchItem::failed(error), true)
+                }
+            }
+        })
+        .buffer_unordered(concurrency.max(1))
+        .collect()
+        .instrument(span)
+        .await;
+    outcomes.sort_by_key(|(index, _, _)| *index);
+
+    let timed_out = outcomes.iter().any(|(_, _, timed_out)| *timed_out);
+    let plans: Vec<BatchItem> = outcomes.into_iter().map(|(_, item, _)| item).collect();
+    let succeeded = plans.iter().filter(|item| item.success).count();
+    let result = BatchResult {
+        failed: plans.len() - succeeded,
+        succeeded,
+        plans,
+        timed_out,
+        duration_ms: start.elapsed().as_millis() as u64,
+    };
+    info!(
+        "Ran {} plans: {} succeeded, {} failed",
+        result.plans.len(),
+        result.succeeded,
+        result.failed
+    );
+    result
+}
+
+impl BatchItem {
+    fn finished(result: PlanResult) -> Self {
+        Self {
+            success: result.success,
+            result: Some(result),
+            error: None,
+        }
+    }
+
+    fn failed(error: ExecutorError) -> Self {
+        Self {
+            success: false,
+            result: None,
+            error: Some(error.to_string()),
+        }
+    }
+}
+
+impl StepTrace {
+    fn new(step: &PlanStep, outcome: StepOutcome) -> Self {
+        Self {
+            step: step.name.clone(),
+            outcome,
+            attempts: 0,
+            action: None,
+            data: None,
+            error: None,
+            failure: None,
+            duration_ms: 0,
+        }
+    }
+}
+
+impl Condition {
+    /// Whether the condition holds; fails for references to missing variables
+    /// in text or in the test, or values that are not numbers for comparisons
+    fn holds(&self, variables: &BTreeMap<String, Value>) -> Result<bool, String> {
+        let value = match reference(&self.value) {
+            Some(path) => lookup(variables, path).cloned(),
+            None => Some(render_text(&self.value, variables)?),
+        };
+
+        let number = |value: Option<&Value>, what: &str| {
+            value
+                .and_then(|value| match value {
+                    Value::Number(number) => number.as_f64(),
+                    Value::String(text) => text.trim().parse().ok(),
+                    _ => None,
+                })
+                .ok_or_else(|| format!("{} is not a number", what))
+        };
+        let bound = |bound: &Value| {
+            let bound = render(bound, variables)?;
+            number(Some(&bound), &text(&bound))
+        };
+
+        Ok(match &self.test {
+            ConditionTest::Equals(expected) => {
+                loose_eq(value.as_ref(), &render(expected, variables)?)
+            }
+            ConditionTest::NotEquals(expected) => {
+                !loose_eq(value.as_ref(), &render(expected, variables)?)
+            }
+            ConditionTest::Contains(part) => {
+                let part = text(&render_text(part, variables)?);
+                match &value {
+                    Some(Value::Array(items)) => items.iter().any(|item| text(item) == part),
+                    Some(value) => text(value).contains(part.as_str()),
+                    None => false,
+                }
+            }
+            ConditionTest::GreaterThan(limit) => {
+                number(value.as_ref(), &self.value)? > bound(limit)?
+            }
+            ConditionTest::LessThan(limit) => number(value.as_ref(), &self.value)? < bound(limit)?,
+            ConditionTest::Exists(exists) => {
+                value.as_ref().is_some_and(|value| !value.is_null()) == *exists
+            }
+        })
+    }
+}
+
+fn loose_eq(value: Option<&Value>, expected: &Value) -> bool {
+    match value {
+        Some(value) => value == expected || text(value) == text(expected),
+        None => expected.is_null(),
+    }
+}
+
+/// A step's output as a variable: its data as JSON, or as text
+fn output_value(data: Option<&str>) -> Value {
+    match data {
+        Some(data) => serde_json::from_str(data).unwrap_or_else(|_| Value::from(data)),
+        None => Value::Null,
+    }
+}
+
+/// Replace `${...}` references in the strings of `value`
+///
+/// A string that is a single reference becomes the referenced value as is,
+/// so numbers, booleans and objects keep their type.
+fn render(value: &Value, variables: &BTreeMap<String, Value>) -> Result<Value, String> {
+    Ok(match value {
+        Value::String(template) => match reference(template) {
+            Some(path) => lookup(variables, path)
+                .cloned()
+                .ok_or_else(|| format!("Undefined variable: {}", path))?,
+            None => render_text(template, variables)?,
+        },
+        Value::Array(items) => Value::Array(
+            items
+                .iter()
+                .map(|item| render(item, variables))
+                .collect::<Result<_, _>>()?,
+        ),
+        Value::Object(fields) => Value::Object(
+            fields
+                .iter()
+                .map(|(name, field)| Ok((name.clone(), render(field, variables)?)))
+                .collect::<Result<_, String>>()?,
+        ),
+        other => other.clone(),
+    })
+}
+
+/// `template` with each reference replaced by its value as text
+fn render_text(template: &str, variables: &BTreeMap<String, Value>) -> Result<Value, String> {
+    let mut out = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("${") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let end = after
+            .find('}')
+            .ok_or_else(|| format!("Unclosed reference in {:?}", template))?;
+        let path = &after[..end];
+        let value =
+            lookup(variables, path).ok_or_else(|| format!("Undefined variable: {}", path))?;
+        out.push_str(&text(value));
+        rest = &after[end + 1..];
+    }
+    out.push_str(rest);
+    Ok(Value::String(out))
+}
+
+/// The path of a string that is exactly one `${path}` reference
+fn reference(template: &str) -> Option<&str> {
+    template
+        .strip_prefix("${")?
+        .strip_suffix('}')
+        .filter(|path| !path.contains(['{', '}']))
+}
+
+/// The value at `name.field.0`
+fn lookup<'v>(variables: &'v BTreeMap<String, Value>, path: &str) -> Option<&'v Value> {
+    let mut parts = path.trim().split('.');
+    let mut value = variables.get(parts.next()?)?;
+    for part in parts {
+        value = match value {
+            Value::Object(fields) => fields.get(part)?,
+            Value::Array(items) => items.get(part.parse::<usize>().ok()?)?,
+            _ => return None,
+        };
+    }
+    Some(value)
+}
+
+/// A value as text: strings without quotes, anything else as JSON
+fn text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::sync::Mutex;
+
+    /// Answers `ExecuteScript` with the script's text as data, failing for
+    /// scripts starting with `fail` (the first `n` times for `fail n`) and
+    /// taking `n` ms for `sleep n`, and `Navigate` as the script
+    /// `navigate <url>`; each action receives `bytes_per_action` bytes
+    #[derive(Default)]
+    struct Scripted {
+        run: Mutex<Vec<String>>,
+        bytes_per_action: u64,
+    }
+
+    #[async_trait]
+    impl ActionTarget for Scripted {
+        async fn execute(&self, action: BrowserAction) -> Result<ActionOutput, ExecutorError> {
+            let script = match action {
+                BrowserAction::ExecuteScript { script } => script,
+                BrowserAction::Navigate { url, .. } => format!("navigate {}", url),
+                _ => return Err(ExecutorError::ActionFailed("Unexpected action".to_string())),
+            };
+            if let Some(ms) = script.strip_prefix("sleep ") {
+                tokio::time::sleep(Duration::from_millis(ms.parse().unwrap())).await;
+            }
+            let mut run = self.run.lock().unwrap();
+            run.push(script.clone());
+            let attempts = run.iter().filter(|s| **s == script).count();
+
+            let failures = script
+                .strip_prefix("fail")
+                .map(|n| n.trim().parse().unwrap_or(usize::MAX));
+            if failures.is_some_and(|failures| attempts <= failures) {
+                return Err(ExecutorError::ActionFailed(script));
+            }
+            Ok(ActionOutput::ok(Some(script), Duration::ZERO))
+        }
+
+        async fn bytes_received(&self) -> u64 {
+            self.run.lock().unwrap().len() as u64 * self.bytes_per_action
+        }
+    }
+
+    fn script(name: &str, script: &str) -> Value {
+        json!({ "name": name, "action": { "type": "execute_script", "script": script } })
+    }
+
+    async fn run(target: &Scripted, plan: Value) -> PlanResult {
+        let plan: ActionPlan = serde_json::from_value(plan).unwrap();
+        PlanRunner::new(target).run(&plan).await
+    }
+
+    #[test]
+    fn test_render() {
+        let variables: BTreeMap<String, Value> = serde_json::from_value(json!({
+            "query": "lamp",
+            "price": { "total": 12.5, "items": [3, 4] }
+        }))
+        .unwrap();
+
+        let rendered = render(
+            &json!({ "text": "${query} x${price.items.1}", "limit": "${price.total}" }),
+            &variables,
+        )
+        .unwrap();
+        assert_eq!(rendered, json!({ "text": "lamp x4", "limit": 12.5 }));
+        assert_eq!(
+            render(&json!("${missing}"), &variables).unwrap_err(),
+            "Undefined variable: missing"
+        );
+        assert!(render(&json!("${query"), &variables).is_err());
+    }
+
+    #[test]
+    fn test_conditions() {
+        let variables: BTreeMap<String, Value> = serde_json::from_value(json!({
+            "price": "12.50",
+            "tags": ["new", "sale"],
+            "count": 3
+        }))
+        .unwrap();
+        let holds = |condition: Value| {
+            serde_json::from_value::<Condition>(condition)
+                .unwrap()
+                .holds(&variables)
+        };
+
+        assert_eq!(
+            holds(json!({ "value": "${price}", "greater_than": 10 })),
+            Ok(true)
+        );
+        assert_eq!(
+            holds(json!({ "value": "${count}", "equals": "3" })),
+            Ok(true)
+        );
+        assert_eq!(
+            holds(json!({ "value": "${tags}", "contains": "sale" })),
+            Ok(true)
+        );
+        assert_eq!(
+            holds(json!({ "value": "n=${count}", "not_equals": "n=3" })),
+            Ok(false)
+        );
+        assert_eq!(
+            holds(json!({ "value": "${gone}", "exists": false })),
+            Ok(true)
+        );
+        assert!(holds(json!({ "value": "${tags}", "less_than": 1 })).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_outputs_and_branches() {
+        let target = Scripted::default();
+        let result = run(
+            &target,
+            json!({
+                "variables": { "limit": 10 },
+                "steps": [
+                    script("price", "7"),
+                    {
+                        "name": "check",
+                        "if": { "value": "${price}", "greater_than": "${limit}" },
+                        "then": [script("expensive", "skip ${price}")],
+                        "else": [script("cheap", "buy ${price}")]
+                    }
+                ]
+            }),
+        )
+        .await;
+
+        assert!(result.success);
+        assert_eq!(*target.run.lock().unwrap(), ["7", "buy 7"]);
+        assert_eq!(result.variables["price"], json!(7));
+        let outcomes: Vec<_> = result
+            .trace
+            .iter()
+            .map(|t| (t.step.as_str(), t.outcome))
+            .collect();
+        assert_eq!(
+            outcomes,
+            [
+                ("price", StepOutcome::Succeeded),
+                ("check", StepOutcome::Else),
+                ("cheap", StepOutcome::Succeeded)
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_retries_and_failures() {
+        let target = Scripted::default();
+        let mut flaky = script("flaky", "fail 2");
+        flaky["retries"] = json!(2);
+        flaky["retry_delay_ms"] = json!(0);
+        let mut broken = script("broken", "fail");
+        broken["on_failure"] = json!([script("recover", "reload")]);
+
+        let result = run(
+            &target,
+            json!({ "steps": [flaky, broken, script("last", "fail"), script("never", "x")] }),
+        )
+        .await;
+
+        assert!(!result.success);
+        let outcomes: Vec<_> = result
+            .trace
+            .iter()
+            .map(|t| (t.step.as_str(), t.outcome, t.attempts))
+            .collect();
+        assert_eq!(
+            outcomes,
+            [
+                ("flaky", StepOutcome::Succeeded, 3),
+                ("broken", StepOutcome::Recovered, 1),
+                ("recover", StepOutcome::Succeeded, 1),
+                ("last", StepOutcome::Failed, 1)
+            ]
+        );
+        assert_eq!(
+            result.trace[3].error.as_deref(),
+            Some("Action failed: fail")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_budget() {
+        let navigate = |name: &str| {
+            json!({ "name": name, "action": { "type": "navigate", "url": "https://example.com" } })
+        };
+        let target = Scripted::default();
+        let result = run(
+            &target,
+            json!({
+                "budget": { "max_navigations": 2 },
+                "steps": [navigate("one"), script("read", "x"), navigate("two"), navigate("three")]
+            }),
+        )
+        .await;
+
+        assert!(!result.success);
+        assert_eq!(target.run.lock().unwrap().len(), 3);
+        let last = result.trace.last().unwrap();
+        assert_eq!((last.step.as_str(), last.attempts), ("three", 0));
+        assert_eq!(
+            last.error.as_deref(),
+            Some("Resource limit exceeded: Plan made 2 navigations, the most allowed")
+        );
+        assert_eq!(
+            last.failure.as_ref().unwrap().code,
+            ErrorCode::ResourceLimit
+        );
+
+        // Exceeded while running: no retries, no recovery
+        let target = Scripted {
+            bytes_per_action: 1000,
+            ..Default::default()
+        };
+        let mut heavy = script("heavy", "fail");
+        heavy["retries"] = json!(3);
+        heavy["retry_delay_ms"] = json!(0);
+        heavy["on_failure"] = json!([script("recover", "reload")]);
+        let plan: ActionPlan =
+            serde_json::from_value(json!({ "steps": [script("first", "x"), heavy] })).unwrap();
+        let budget = PlanBudget {
+            max_bytes: Some(1500),
+            ..Default::default()
+        };
+        let result = PlanRunner::new(&target).with_budget(budget).run(&plan).await;
+
+        assert!(!result.success);
+        assert_eq!(*target.run.lock().unwrap(), ["x", "fail"]);
+        assert_eq!(result.trace.len(), 2);
+        assert_eq!(
+            result.trace[1].error.as_deref(),
+            Some("Resource limit exceeded: Plan received 2000 bytes, over 1500")
+        );
+
+        let target = Scripted::default();
+        let result = run(
+            &target,
+            json!({ "budget": { "max_duration_secs": 0 }, "steps": [script("late", "x")] }),
+        )
+        .await;
+        assert!(!result.success);
+        assert!(target.run.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_run_batch() {
+        let plan = |steps: Vec<Value>| -> ActionPlan {
+            serde_json::from_value(json!({ "steps": steps })).unwrap()
+        };
+        let plans = [
+            plan(vec![script("price", "sleep 50")]),
+            plan(vec![script("price", "fail")]),
+            plan(vec![script("price", "sleep 10000")]),
+            plan(vec![script("price", "$12")]),
+        ];
+
+        let result = run_batch(&plans, 2, Duration::from_millis(500), || async {
+            Ok(Scripted::default())
+        })
+        .await;
+
+        assert_eq!((result.succeeded, result.failed), (2, 2));
+        assert!(result.timed_out);
+        assert!(result.duration_ms < 5000);
+        let [slow, failed, stuck, quick] = &result.plans[..] else {
+            panic!("Expected 4 results");
+        };
+        assert!(slow.success);
+        assert!(!failed.success && failed.result.is_some());
+        assert!(stuck.result.is_none());
+        let error = stuck.error.as_deref().unwrap();
+        assert!(error.starts_with("Timeout exceeded"), "{}", error);
+        assert_eq!(
+            quick.result.as_ref().unwrap().variables["price"],
+            json!("$12")
+        );
+
+        // A plan without a browser fails alone
+        let result = run_batch(&plans[3..], 1, Duration::from_secs(5), || async {
+            Err::<Scripted, _>(ExecutorError::LaunchFailed("Pool is shut down".to_string()))
+        })
+        .await;
+        assert_eq!(result.failed, 1);
+        assert!(!result.timed_out);
+
+        // A stuck plan holds one slot; the rest still run in the other
+        let plans = [
+            plan(vec![script("price", "sleep 10000")]),
+            plan(vec![script("price", "$1")]),
+            plan(vec![script("price", "$2")]),
+            plan(vec![script("price", "$3")]),
+        ];
+        let result = run_batch(&plans, 2, Duration::from_millis(500), || async {
+            Ok(Scripted::default())
+        })
+        .await;
+        assert_eq!((result.succeeded, result.failed), (3, 1));
+        let prices: Vec<_> = result.plans[1..]
+            .iter()
+            .map(|item| item.result.as_ref().unwrap().variables["price"].clone())
+            .collect();
+        assert_eq!(prices, [json!("$1"), json!("$2"), json!("$3")]);
+    }
+}
@@ -0,0 +1,526 @@
+//! Pool of browser instances for concurrent sessions
+//!
+//! A [`BrowserExecutor`](crate::BrowserExecutor) drives a single page, so
+//! concurrent tasks queue behind each other. A [`BrowserPool`] keeps up to
+//! `size` browsers, each with its own user-data-dir, and hands them out as
+//! [`PooledSession`]s. A session returns its browser to the pool when dropped;
+//! browsers left idle longer than `idle_timeout_secs` are closed.
+
+use crate::actions::{ActionOutput, BrowserAction};
+use crate::console::ConsoleEntry;
+use crate::executor::{self, ExecutorConfig, ExecutorError, ExecutorStats};
+use crate::health::Health;
+use crate::monitor::{self, Breach};
+use crate::tabs::Tabs;
+use crate::telemetry;
+use chromiumoxide::browser::Browser;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn, Instrument};
+
+/// Pool configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PoolConfig {
+    /// Maximum number of browsers (and concurrent sessions)
+    pub size: usize,
+
+    /// Close browsers idle for this long (seconds)
+    pub idle_timeout_secs: u64,
+
+    /// How long `checkout` waits for a free browser (seconds)
+    pub checkout_timeout_secs: u64,
+
+    /// Directory holding each browser's user-data-dir (defaults to a
+    /// `browser-executor-pool` directory under the system temp dir)
+    pub user_data_root: Option<PathBuf>,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            size: 4,
+            idle_timeout_secs: 300,
+            checkout_timeout_secs: 30,
+            user_data_root: None,
+        }
+    }
+}
+
+/// Browser counts reported in [`ExecutorStats::pool`]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PoolStats {
+    /// Maximum number of browsers
+    pub size: usize,
+
+    /// Browsers checked out
+    pub in_use: usize,
+
+    /// Browsers waiting in the pool
+    pub idle: usize,
+
+    /// Browsers launched so far
+    pub launched: u64,
+
+    /// Sessions handed out so far
+    pub checkouts: u64,
+
+    /// Browsers closed for being idle too long
+    pub evictions: u64,
+}
+
+/// A browser with its tabs and profile directory
+struct Instance {
+    id: u64,
+    browser: Browser,
+    tabs: tokio::sync::Mutex<Tabs>,
+    health: Health,
+    /// Why the monitor killed the browser, if it did
+    breach: Breach,
+    /// Attached to a running browser, which is not ours to close
+    attached: bool,
+    user_data_dir: PathBuf,
+    last_used: Instant,
+}
+
+impl Instance {
+    /// Close the browser (only our tabs, if attached) and delete its profile
+    /// and downloads
+    async fn close(mut self) {
+        self.tabs.get_mut().downloads().clean_up();
+        if self.attached {
+            self.tabs.into_inner().close_opened().await;
+        } else {
+            if let Err(e) = self.browser.close().await {
+                warn!("Failed to close pooled browser {}: {}", self.id, e);
+            }
+            let _ = self.browser.wait().await;
+        }
+        remove_profile(&self.user_data_dir);
+    }
+
+    /// Whether the browser process has exited, or crashed otherwise
+    fn has_exited(&mut self) -> bool {
+        matches!(self.browser.try_wait(), Ok(Some(_)))
+            || self.health.failure().is_some()
+            || self.breach.get().is_some()
+    }
+}
+
+/// Count a browser that exited: killed by the monitor, or crashed
+fn count_exit(stats: &mut ExecutorStats, instance: &Instance) {
+    match instance.breach.take() {
+        Some(reason) => {
+            warn!("Pooled browser {}: {}", instance.id, reason);
+            stats.limit_restarts += 1;
+        }
+        None => stats.crashes += 1,
+    }
+}
+
+fn remove_profile(dir: &Path) {
+    if let Err(e) = std::fs::remove_dir_all(dir) {
+        debug!("Failed to remove {}: {}", dir.display(), e);
+    }
+}
+
+#[derive(Default)]
+struct PoolState {
+    idle: Vec<Instance>,
+    stats: ExecutorStats,
+}
+
+struct PoolInner {
+    config: ExecutorConfig,
+    pool_config: PoolConfig,
+    permits: Arc<Semaphore>,
+    state: Mutex<PoolState>,
+    next_id: AtomicU64,
+}
+
+impl PoolInner {
+    fn state(&self) -> std::sync::MutexGuard<'_, PoolState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn user_data_root(&self) -> PathBuf {
+        self.pool_config
+            .user_data_root
+            .clone()
+            .unwrap_or_else(|| std::env::temp_dir().join("browser-executor-pool"))
+    }
+
+    async fn launch_instance(self: &Arc<Self>) -> Result<Instance, ExecutorError> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let user_data_dir =
+            self.user_data_root()
+                .join(format!("session-{}-{}", std::process::id(), id));
+        std::fs::create_dir_all(&user_data_dir)
+            .map_err(|e| ExecutorError::LaunchFailed(e.to_string()))?;
+
+        match executor::launch(&self.config, Some(&user_data_dir)).await {
+            Ok(launched) => {
+                self.state().stats.pool_mut().launched += 1;
+                let breach = Breach::default();
+                if let Some(pid) = launched.pid.filter(|_| self.config.monitor_interval_ms > 0) {
+                    let pool = Arc::downgrade(self);
+                    monitor::spawn(pid, &self.config, breach.clone(), move |memory, cpu| {
+                        if let Some(inner) = pool.upgrade() {
+                            inner.state().stats.record_peak(memory, cpu);
+                        }
+                        std::future::ready(())
+                    });
+                }
+                Ok(Instance {
+                    id,
+                    browser: launched.browser,
+                    tabs: tokio::sync::Mutex::new(launched.tabs),
+                    health: launched.health,
+                    breach,
+                    attached: self.config.remote_debugging_url.is_some(),
+                    user_data_dir,
+                    last_used: Instant::now(),
+                })
+            }
+            Err(e) => {
+                remove_profile(&user_data_dir);
+                Err(e)
+            }
+        }
+    }
+
+    /// Close browsers idle for longer than the idle timeout
+    async fn evict_idle(&self) -> usize {
+        let timeout = Duration::from_secs(self.pool_config.idle_timeout_secs);
+        let expired: Vec<Instance> = {
+            let mut state = self.state();
+            let (expired, keep) = std::mem::take(&mut state.idle)
+                .into_iter()
+                .partition(|instance| instance.last_used.elapsed() >= timeout);
+            state.idle = keep;
+            state.stats.pool_mut().evictions += expired.len() as u64;
+            expired
+        };
+
+        let count = expired.len();
+        for instance in expired {
+            debug!("Evicting idle browser {}", instance.id);
+            instance.close().await;
+        }
+        count
+    }
+}
+
+impl ExecutorStats {
+    fn pool_mut(&mut self) -> &mut PoolStats {
+        self.pool.get_or_insert_with(PoolStats::default)
+    }
+}
+
+/// Pool of browsers handed out as sessions
+pub struct BrowserPool {
+    inner: Arc<PoolInner>,
+    reaper: JoinHandle<()>,
+}
+
+impl BrowserPool {
+    /// Create a pool; browsers are launched on demand
+    ///
+    /// Must be called within a Tokio runtime, which runs idle eviction.
+    pub fn new(config: ExecutorConfig, pool_config: PoolConfig) -> Self {
+        let inner = Arc::new(PoolInner {
+            permits: Arc::new(Semaphore::new(pool_config.size)),
+            config,
+            pool_config,
+            state: Mutex::new(PoolState::default()),
+            next_id: AtomicU64::new(0),
+        });
+
+        let reaper = tokio::spawn(reap(Arc::downgrade(&inner)));
+
+        Self { inner, reaper }
+    }
+
+    /// Take a browser from the pool, launching one if none is idle
+    ///
+    /// Waits up to `checkout_timeout_secs` while all browsers are in use.
+    pub async fn checkout(&self) -> Result<PooledSession, ExecutorError> {
+        let timeout = Duration::from_secs(self.inner.pool_config.checkout_timeout_secs);
+        let permit = tokio::time::timeout(timeout, self.inner.permits.clone().acquire_owned())
+            .await
+            .map_err(|_| ExecutorError::Timeout("No browser free in pool".to_string()))?
+            .map_err(|_| ExecutorError::LaunchFailed("Pool is shut down".to_string()))?;
+
+        // Most recently used first, so surplus browsers age out
+        let idle = self.inner.state().idle.pop();
+        let instance = match idle {
+            Some(mut instance) => {
+                if instance.has_exited() {
+                    warn!("Pooled browser {} exited, replacing it", instance.id);
+                    remove_profile(&instance.user_data_dir);
+                    count_exit(&mut self.inner.state().stats, &instance);
+                    self.inner.launch_instance().await?
+                } else {
+                    instance
+                }
+            }
+            None => self.inner.launch_instance().await?,
+        };
+
+        self.inner.state().stats.pool_mut().checkouts += 1;
+        debug!("Checked out browser {}", instance.id);
+
+        Ok(PooledSession {
+            instance: Some(instance),
+            inner: self.inner.clone(),
+            _permit: permit,
+        })
+    }
+
+    /// Close browsers idle for longer than the idle timeout now, rather than
+    /// waiting for the next periodic check; returns how many were closed
+    pub async fn evict_idle(&self) -> usize {
+        self.inner.evict_idle().await
+    }
+
+    /// Action counts across all sessions, with pool counts in `pool`
+    pub async fn get_stats(&self) -> ExecutorStats {
+        let mut stats = {
+            let state = self.inner.state();
+            let mut stats = state.stats.clone();
+            let pool = stats.pool_mut();
+            pool.idle = state.idle.len();
+            stats
+        };
+
+        let pool = stats.pool_mut();
+        pool.size = self.inner.pool_config.size;
+        pool.in_use = self.inner.pool_config.size - self.inner.permits.available_permits();
+        stats
+    }
+
+    /// Refuse new checkouts and close idle browsers
+    ///
+    /// Browsers still checked out are closed when their sessions end.
+    pub async fn shutdown(&self) {
+        info!("Shutting down browser pool...");
+        self.inner.permits.close();
+
+        let idle = std::mem::take(&mut self.inner.state().idle);
+        for instance in idle {
+            instance.close().await;
+        }
+    }
+}
+
+impl Drop for BrowserPool {
+    fn drop(&mut self) {
+        self.reaper.abort();
+    }
+}
+
+/// Periodically evict idle browsers until the pool is dropped
+async fn reap(pool: Weak<PoolInner>) {
+    let interval = match pool.upgrade() {
+        Some(inner) => Duration::from_secs(inner.pool_config.idle_timeout_secs.clamp(1, 30)),
+        None => return,
+    };
+
+    loop {
+        tokio::time::sleep(interval).await;
+        match pool.upgrade() {
+            Some(inner) => {
+                inner.evict_idle().await;
+            }
+            None => return,
+        }
+    }
+}
+
+/// A browser checked out of a [`BrowserPool`], returned when dropped
+pub struct PooledSession {
+    instance: Option<Instance>,
+    inner: Arc<PoolInner>,
+    _permit: OwnedSemaphorePermit,
+}
+
+impl PooledSession {
+    fn instance(&self) -> &Instance {
+        self.instance
+            .as_ref()
+            .expect("instance is present until drop")
+    }
+
+    /// Identifies the browser within its pool
+    pub fn id(&self) -> u64 {
+        self.instance().id
+    }
+
+    /// The browser's profile directory
+    pub fn user_data_dir(&self) -> &Path {
+        &self.instance().user_data_dir
+    }
+
+    /// Execute a browser action on this session's active tab, in a
+    /// `browser_action` span
+    pub async fn execute(&self, action: BrowserAction) -> Result<ActionOutput, ExecutorError> {
+        let span = telemetry::action_span(&action);
+        let start = Instant::now();
+        let result = self.execute_action(action).instrument(span.clone()).await;
+        telemetry::record(&span, &result, start.elapsed());
+        result
+    }
+
+    async fn execute_action(&self, action: BrowserAction) -> Result<ActionOutput, ExecutorError> {
+        let start = Instant::now();
+        let instance = self.instance();
+        let config = &self.inner.config;
+
+        let mut tabs = instance.tabs.lock().await;
+        tabs.add_popups(config, &instance.browser).await;
+        let result = if action.is_tab_action() {
+            executor::run_tab_action(config, &instance.browser, &mut tabs, action.clone()).await
+        } else {
+            let tab = tabs.active();
+            drop(tabs);
+            executor::run_action(config, &instance.browser, tab, action.clone()).await
+        };
+
+        {
+            let mut state = self.inner.state();
+            state.stats.total_actions += 1;
+            state.stats.total_execution_time_ms += start.elapsed().as_millis() as u64;
+            match &result {
+                Ok(_) => state.stats.successful_actions += 1,
+                Err(_) => state.stats.failed_actions += 1,
+            }
+        }
+
+        match result {
+            Ok(output) if output.success => Ok(output),
+            Ok(output) => {
+                let url = self.get_current_url().await;
+                Ok(executor::with_failure(output, &action, url))
+            }
+            Err(e) => match instance.breach.get() {
+                Some(reason) => Err(ExecutorError::ResourceLimitExceeded(reason)),
+                None => {
+                    let url = self.get_current_url().await;
+                    Err(ExecutorError::action(e, &action, url))
+                }
+            },
+        }
+    }
+
+    /// Get the active tab's URL
+    pub async fn get_current_url(&self) -> Option<String> {
+        let page = self.instance().tabs.lock().await.current().clone();
+        page.url().await.ok().flatten()
+    }
+
+    /// Get the active tab's buffered console entries, oldest first
+    pub async fn get_console_logs(&self) -> Vec<ConsoleEntry> {
+        self.instance().tabs.lock().await.active().console.entries()
+    }
+
+    /// Bytes received over the network by the session's tabs, downloads
+    /// included
+    pub async fn bytes_received(&self) -> u64 {
+        self.instance().tabs.lock().await.bytes_received()
+    }
+}
+
+impl Drop for PooledSession {
+    fn drop(&mut self) {
+        let Some(mut instance) = self.instance.take() else {
+            return;
+        };
+
+        if instance.has_exited() {
+            warn!("Pooled browser {} exited during its session", instance.id);
+            remove_profile(&instance.user_data_dir);
+            count_exit(&mut self.inner.state().stats, &instance);
+            return;
+        }
+
+        // After shutdown nothing would close it from the idle list
+        if self.inner.permits.is_closed() {
+            if let Ok(handle) = tokio::runtime::Handle::try_current() {
+                handle.spawn(instance.close());
+            }
+            return;
+        }
+
+        instance.last_used = Instant::now();
+        debug!("Returned browser {} to the pool", instance.id);
+        self.inner.state().idle.push(instance);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actions::WaitCondition;
+
+    #[test]
+    fn test_stats_serialize_pool() {
+        let mut stats = ExecutorStats::default();
+        stats.pool_mut().checkouts = 2;
+
+        let json = serde_json::to_value(&stats).unwrap();
+        assert_eq!(json["pool"]["checkouts"], 2);
+
+        let restored: ExecutorStats = serde_json::from_str(
+            r#"{
+            "total_actions": 0, "successful_actions": 0, "failed_actions": 0,
+            "crashes": 0, "restarts": 0, "total_execution_time_ms": 0
+        }"#,
+        )
+        .unwrap();
+        assert!(restored.pool.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_sessions() {
+        let root = tempfile::tempdir().unwrap();
+        let pool = BrowserPool::new(
+            ExecutorConfig::default(),
+            PoolConfig {
+                size: 2,
+                checkout_timeout_secs: 1,
+                user_data_root: Some(root.path().to_path_buf()),
+                ..Default::default()
+            },
+        );
+
+        let first = pool.checkout().await.unwrap();
+        let second = pool.checkout().await.unwrap();
+        assert_ne!(first.user_data_dir(), second.user_data_dir());
+        assert!(matches!(
+            pool.checkout().await,
+            Err(ExecutorError::Timeout(_))
+        ));
+
+        let (a, b) = tokio::join!(
+            first.execute(BrowserAction::Navigate {
+                url: "about:blank".to_string(),
+                wait_until: WaitCondition::Load,
+            }),
+            second.execute(BrowserAction::Reload),
+        );
+        assert!(a.is_ok() && b.is_ok());
+
+        let id = first.id();
+        drop(first);
+        assert_eq!(pool.checkout().await.unwrap().id(), id);
+
+        let stats = pool.get_stats().await;
+        let pool_stats = stats.pool.unwrap();
+        assert_eq!((pool_stats.launched, pool_stats.checkouts), (2, 3));
+        assert_eq!(stats.total_actions, 2);
+    }
+}
@@ -0,0 +1,129 @@
+//! Proxy configuration
+//!
+//! `ExecutorConfig::proxy` routes all browser traffic through an HTTP(S) or
+//! SOCKS5 proxy via Chrome's `--proxy-server` and `--proxy-bypass-list`
+//! flags. Chrome ignores credentials in the proxy URL, so for authenticated
+//! proxies pages answer the proxy's auth challenges over CDP instead (see
+//! [`network`](crate::network)).
+
+use serde::{Deserialize, Serialize};
+
+/// Proxy protocol
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProxyScheme {
+    #[default]
+    Http,
+    Https,
+    Socks5,
+}
+
+impl ProxyScheme {
+    fn name(self) -> &'static str {
+        match self {
+            ProxyScheme::Http => "http",
+            ProxyScheme::Https => "https",
+            ProxyScheme::Socks5 => "socks5",
+        }
+    }
+}
+
+/// Proxy to send browser traffic through
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProxyConfig {
+    #[serde(default)]
+    pub scheme: ProxyScheme,
+    pub host: String,
+    pub port: u16,
+
+    /// Credentials for proxies that require authentication (HTTP(S) only)
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+
+    /// Hosts reached directly, in Chrome's bypass syntax (e.g. `*.internal`,
+    /// `10.0.0.0/8`, `<local>`)
+    #[serde(default)]
+    pub bypass: Vec<String>,
+}
+
+impl ProxyConfig {
+    /// Proxy server URL, without credentials
+    pub fn server(&self) -> String {
+        format!("{}://{}:{}", self.scheme.name(), self.host, self.port)
+    }
+
+    /// Username and password, if the proxy needs them
+    pub fn credentials(&self) -> Option<(&str, &str)> {
+        let username = self.username.as_deref()?;
+        Some((username, self.password.as_deref().unwrap_or_default()))
+    }
+
+    /// Chrome flags for this proxy
+    ///
+    /// Fails for settings Chrome cannot honor, rather than silently
+    /// connecting without them.
+    pub fn launch_args(&self) -> Result<Vec<String>, String> {
+        if self.host.is_empty() || self.host.contains(['/', ' ', '@']) {
+            return Err(format!("Invalid proxy host: {:?}", self.host));
+        }
+        if self.scheme == ProxyScheme::Socks5 && self.credentials().is_some() {
+            return Err("Chrome does not support SOCKS5 proxy authentication".to_string());
+        }
+
+        let mut args = vec![format!("--proxy-server={}", self.server())];
+        if !self.bypass.is_empty() {
+            args.push(format!("--proxy-bypass-list={}", self.bypass.join(";")));
+        }
+        Ok(args)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_launch_args() {
+        let proxy: ProxyConfig = serde_json::from_str(
+            r#"{
+                "host": "proxy.corp",
+                "port": 3128,
+                "username": "alice",
+                "password": "secret",
+                "bypass": ["*.internal", "<local>"]
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(proxy.credentials(), Some(("alice", "secret")));
+        assert_eq!(
+            proxy.launch_args().unwrap(),
+            [
+                "--proxy-server=http://proxy.corp:3128",
+                "--proxy-bypass-list=*.internal;<local>",
+            ]
+        );
+
+        let socks = ProxyConfig {
+            scheme: ProxyScheme::Socks5,
+            ..proxy.clone()
+        };
+        assert!(socks.launch_args().is_err());
+        let socks = ProxyConfig {
+            username: None,
+            ..socks
+        };
+        assert_eq!(
+            socks.launch_args().unwrap()[0],
+            "--proxy-server=socks5://proxy.corp:3128"
+        );
+
+        let bad_host = ProxyConfig {
+            host: "user:pw@proxy".to_string(),
+            ..proxy
+        };
+        assert!(bad_host.launch_args().is_err());
+    }
+}
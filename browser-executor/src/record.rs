@@ -0,0 +1,394 @@
+//! Recording plans from what a person does
+//!
+//! `BrowserExecutor::record` watches the active tab of a visible browser (one
+//! launched with `headless: false`, or attached to with
+//! `remote_debugging_url`) while someone uses it, and turns their clicks,
+//! typing, choices and navigations into an [`ActionPlan`] the executor can
+//! replay. A script added to every document reports each interaction through
+//! a CDP binding (`Runtime.addBinding`), naming the element by the steadiest
+//! selector it has: a unique id, `name`, `data-testid` or `aria-label`,
+//! otherwise its CSS path.
+//!
+//! A field's final value becomes a `Type` step, ending in a newline when it
+//! was submitted with Enter. Main-frame navigations that do not follow an
+//! interaction (typed in the address bar, say) become `Navigate` steps; the
+//! others replay by themselves. Password fields are recorded as a
+//! `${password}` reference, with an empty `password` variable to fill in,
+//! never their value. Interactions inside iframes are not recorded.
+
+use crate::actions::{BrowserAction, WaitCondition};
+use crate::executor::ExecutorError;
+use crate::plan::{self, ActionPlan, PlanStep, StepBody};
+use chromiumoxide::cdp::browser_protocol::page::{
+    AddScriptToEvaluateOnNewDocumentParams, EventFrameNavigated,
+    RemoveScriptToEvaluateOnNewDocumentParams,
+};
+use chromiumoxide::cdp::js_protocol::runtime::{
+    AddBindingParams, EventBindingCalled, RemoveBindingParams,
+};
+use chromiumoxide::page::Page;
+use futures::StreamExt;
+use serde::Deserialize;
+use std::future::Future;
+use std::time::{Duration, Instant};
+use tracing::{debug, info};
+
+/// The binding the recording script reports through
+const BINDING: &str = "__browserExecutorRecord";
+
+/// How long after an interaction a navigation counts as its result
+const FOLLOW_WINDOW: Duration = Duration::from_secs(5);
+
+/// Variable standing in for recorded passwords
+const PASSWORD_VARIABLE: &str = "password";
+
+/// Runs in the main frame of every document, reporting trusted input events
+const SCRIPT: &str = r#"(() => {
+  if (window !== top || window.__browserExecutorRecording) return;
+  window.__browserExecutorRecording = true;
+  const send = (event) => { try { __browserExecutorRecord(JSON.stringify(event)); } catch (e) {} };
+  const unique = (sel) => { try { return document.querySelectorAll(sel).length === 1; } catch (e) { return false; } };
+  const selector = (el) => {
+    if (el.id && unique('#' + CSS.escape(el.id))) return '#' + CSS.escape(el.id);
+    const tag = el.tagName.toLowerCase();
+    for (const attr of ['name', 'data-testid', 'aria-label']) {
+      const value = el.getAttribute(attr);
+      const sel = value && `${tag}[${attr}="${value.replace(/["\\]/g, '\\$&')}"]`;
+      if (sel && unique(sel)) return sel;
+    }
+    const path = [];
+    for (let node = el; node && node !== document.documentElement; node = node.parentElement) {
+      if (node.id && unique('#' + CSS.escape(node.id))) { path.unshift('#' + CSS.escape(node.id)); break; }
+      let part = node.tagName.toLowerCase();
+      const siblings = node.parentElement ? Array.from(node.parentElement.children) : [];
+      const same = siblings.filter((other) => other.tagName === node.tagName);
+      if (same.length > 1) part += `:nth-of-type(${same.indexOf(node) + 1})`;
+      path.unshift(part);
+    }
+    return path.join(' > ');
+  };
+  const typed = (el) => el instanceof HTMLTextAreaElement || (el instanceof HTMLInputElement &&
+    !['checkbox', 'radio', 'button', 'submit', 'reset', 'file', 'image', 'range', 'color'].includes(el.type));
+  const input = (el, submit) => send({ kind: 'input', selector: selector(el), value: el.value,
+    secret: el.type === 'password', submit });
+  addEventListener('click', (e) => {
+    if (!e.isTrusted || !(e.target instanceof Element)) return;
+    const el = e.target.closest('a, button, input, select, textarea, label, [role=button]') || e.target;
+    // Fields are recorded by their value
+    if (typed(el) || el instanceof HTMLSelectElement) return;
+    send({ kind: 'click', selector: selector(el) });
+  }, true);
+  addEventListener('change', (e) => {
+    if (!e.isTrusted) return;
+    if (e.target instanceof HTMLSelectElement) {
+      send({ kind: 'select', selector: selector(e.target), value: e.target.value });
+    } else if (typed(e.target)) {
+      input(e.target, false);
+    }
+  }, true);
+  addEventListener('keydown', (e) => {
+    if (e.isTrusted && e.key === 'Enter' && e.target instanceof HTMLInputElement && typed(e.target)) {
+      input(e.target, true);
+    }
+  }, true);
+})()"#;
+
+/// An interaction the recording script reported
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub(crate) enum Interaction {
+    Click {
+        selector: String,
+    },
+    Input {
+        selector: String,
+        value: String,
+        #[serde(default)]
+        secret: bool,
+        #[serde(default)]
+        submit: bool,
+    },
+    Select {
+        selector: String,
+        value: String,
+    },
+}
+
+impl Interaction {
+    /// What was done and to what, without the values entered (which may be
+    /// passwords), for logging
+    fn describe(&self) -> String {
+        match self {
+            Interaction::Click { selector } => format!("click on {}", selector),
+            Interaction::Input { selector, .. } => format!("input into {}", selector),
+            Interaction::Select { selector, .. } => format!("select in {}", selector),
+        }
+    }
+}
+
+/// The actions recorded so far
+#[derive(Debug, Default)]
+pub(crate) struct Recording {
+    actions: Vec<BrowserAction>,
+    last_interaction: Option<Instant>,
+    password: bool,
+}
+
+impl Recording {
+    /// The main frame navigated to `url` at `at`
+    pub(crate) fn navigated(&mut self, url: &str, at: Instant) {
+        let follows = self
+            .last_interaction
+            .is_some_and(|last| at.saturating_duration_since(last) < FOLLOW_WINDOW);
+        if follows || url.is_empty() || url == "about:blank" {
+            return;
+        }
+        if let Some(BrowserAction::Navigate { url: last, .. }) = self.actions.last() {
+            if last == url {
+                return;
+            }
+        }
+        self.actions.push(BrowserAction::Navigate {
+            url: url.to_string(),
+            wait_until: WaitCondition::Load,
+        });
+    }
+
+    /// The user did `interaction` at `at`
+    pub(crate) fn interacted(&mut self, interaction: Interaction, at: Instant) {
+        self.last_interaction = Some(at);
+        let action = match interaction {
+            Interaction::Click { selector } => BrowserAction::Click {
+                selector: selector.into(),
+                wait_for: None,
+                pierce_shadow: false,
+            },
+            Interaction::Select { selector, value } => BrowserAction::SelectOption {
+                selector: selector.into(),
+                value,
+                pierce_shadow: false,
+            },
+            Interaction::Input {
+                selector,
+                value,
+                secret,
+                submit,
+            } => {
+                let mut text = if secret {
+                    self.password = true;
+                    format!("${{{}}}", PASSWORD_VARIABLE)
+                } else {
+                    value
+                };
+                // A field reports its value on Enter and again on change;
+                // the last report replaces the step, keeping the submit
+                if let Some(BrowserAction::Type {
+                    selector: last,
+                    text: last_text,
+                    ..
+                }) = self.actions.last()
+                {
+                    if last.to_string() == selector {
+                        if !submit && last_text.strip_suffix('\n') == Some(text.as_str()) {
+                            return;
+                        }
+                        self.actions.pop();
+                    }
+                }
+                if submit {
+                    text.push('\n');
+                }
+                BrowserAction::Type {
+                    selector: selector.into(),
+                    text,
+                    clear_first: true,
+                    pierce_shadow: false,
+                }
+            }
+        };
+        self.actions.push(action);
+    }
+
+    /// The recorded actions as a plan, one step each
+    pub(crate) fn into_plan(self) -> ActionPlan {
+        let steps = self
+            .actions
+            .iter()
+            .enumerate()
+            .map(|(idx, action)| {
+                let action = serde_json::to_value(action).unwrap_or_default();
+                let kind = action["type"].as_str().unwrap_or("step");
+                PlanStep {
+                    name: format!("{}_{}", kind, idx + 1),
+                    body: StepBody::Action { action },
+                    retries: 0,
+                    retry_delay_ms: plan::default_retry_delay_ms(),
+                    timeout_secs: None,
+                    on_failure: Vec::new(),
+                }
+            })
+            .collect();
+
+        let mut variables = std::collections::BTreeMap::new();
+        if self.password {
+            variables.insert(PASSWORD_VARIABLE.to_string(), serde_json::Value::from(""));
+        }
+        ActionPlan {
+            variables,
+            steps,
+            budget: Default::default(),
+        }
+    }
+}
+
+/// Record what happens in `page` until `stop` completes
+pub(crate) async fn record(
+    page: &Page,
+    stop: impl Future<Output = ()>,
+) -> Result<ActionPlan, ExecutorError> {
+    let page_error = |e: chromiumoxide::error::CdpError| ExecutorError::PageError(e.to_string());
+
+    let mut calls = page
+        .event_listener::<EventBindingCalled>()
+        .await
+        .map_err(page_error)?;
+    let mut navigations = page
+        .event_listener::<EventFrameNavigated>()
+        .await
+        .map_err(page_error)?;
+    page.execute(AddBindingParams::new(BINDING))
+        .await
+        .map_err(page_error)?;
+    let script = page
+        .execute(AddScriptToEvaluateOnNewDocumentParams::new(SCRIPT))
+        .await
+        .map_err(page_error)?
+        .result
+        .identifier;
+    page.evaluate(SCRIPT).await.map_err(page_error)?;
+
+    let mut recording = Recording::default();
+    if let Some(url) = page.url().await.ok().flatten() {
+        recording.navigated(&url, Instant::now());
+    }
+    info!("Recording; interact with the browser");
+
+    tokio::pin!(stop);
+    loop {
+        tokio::select! {
+            _ = &mut stop => break,
+            Some(call) = calls.next() => {
+                if call.name != BINDING {
+                    continue;
+                }
+                match serde_json::from_str::<Interaction>(&call.payload) {
+                    Ok(interaction) => {
+                        debug!("Recorded {}", interaction.describe());
+                        recording.interacted(interaction, Instant::now());
+                    }
+                    // The payload and serde's message may quote a password
+                    Err(e) => debug!(
+                        "Ignoring recording payload of {} bytes at column {}",
+                        call.payload.len(),
+                        e.column()
+                    ),
+                }
+            }
+            Some(event) = navigations.next() => {
+                if event.frame.parent_id.is_none() {
+                    recording.navigated(&event.frame.url, Instant::now());
+                }
+            }
+            else => break,
+        }
+    }
+
+    // The page keeps working without the recorder
+    let _ = page
+        .execute(RemoveScriptToEvaluateOnNewDocumentParams::new(script))
+        .await;
+    let _ = page.execute(RemoveBindingParams::new(BINDING)).await;
+
+    let plan = recording.into_plan();
+    info!("Recorded {} steps", plan.steps.len());
+    Ok(plan)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input(selector: &str, value: &str, submit: bool) -> Interaction {
+        Interaction::Input {
+            selector: selector.to_string(),
+            value: value.to_string(),
+            secret: false,
+            submit,
+        }
+    }
+
+    #[test]
+    fn test_recording() {
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        let mut recording = Recording::default();
+
+        recording.navigated("https://shop.example/", at(0));
+        recording.interacted(input("#q", "desk", false), at(10));
+        recording.interacted(input("#q", "desk lamp", true), at(11));
+        recording.interacted(input("#q", "desk lamp", false), at(11));
+        // The search results load
+        recording.navigated("https://shop.example/search?q=desk+lamp", at(12));
+        recording.interacted(
+            Interaction::Select {
+                selector: "select[name=\"sort\"]".to_string(),
+                value: "price".to_string(),
+            },
+            at(20),
+        );
+        recording.interacted(
+            Interaction::Click {
+                selector: "#results > li:nth-of-type(2) > a".to_string(),
+            },
+            at(30),
+        );
+        // Typed in the address bar a while later
+        recording.navigated("https://shop.example/cart", at(60));
+
+        let plan = recording.into_plan();
+        let names: Vec<&str> = plan.steps.iter().map(|step| step.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "navigate_1",
+                "type_2",
+                "select_option_3",
+                "click_4",
+                "navigate_5"
+            ]
+        );
+        let StepBody::Action { action } = &plan.steps[1].body else {
+            panic!("not an action");
+        };
+        assert_eq!(action["text"], "desk lamp\n");
+        assert_eq!(action["clear_first"], true);
+        assert!(plan.variables.is_empty());
+    }
+
+    #[test]
+    fn test_recording_password() {
+        let mut recording = Recording::default();
+        let interaction: Interaction = serde_json::from_str(
+            r##"{"kind": "input", "selector": "#password", "value": "hunter2", "secret": true}"##,
+        )
+        .unwrap();
+        assert_eq!(interaction.describe(), "input into #password");
+        recording.interacted(interaction, Instant::now());
+
+        let plan = recording.into_plan();
+        let json = serde_json::to_string(&plan).unwrap();
+        assert!(!json.contains("hunter2"));
+        assert!(json.contains("${password}"));
+        assert_eq!(plan.variables["password"], "");
+    }
+}
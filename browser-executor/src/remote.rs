@@ -0,0 +1,193 @@
+//! Attaching to a running browser
+//!
+//! With `ExecutorConfig::remote_debugging_url`, the executor connects to a
+//! Chrome started with `--remote-debugging-port` (the user's own browser,
+//! signed in to their accounts) instead of launching one. It opens a tab of
+//! its own to work in, and controls the browser's existing tabs only where
+//! `ExecutorConfig::remote_tabs` allows: they are listed by `ListTabs` and
+//! reachable with `SwitchTab`, and no others are. Only the tabs it opens get
+//! its settings (user agent, emulation, stealth, request interception and
+//! dialog policy); adopted tabs are watched but left as they are. On
+//! shutdown it closes the tabs it opened and disconnects, leaving the browser
+//! and its other tabs running.
+//!
+//! Settings that only apply when launching (headless mode, viewport, proxy,
+//! sandbox, profile) are ignored, and the browser's processes are not
+//! monitored.
+
+use crate::executor::ExecutorError;
+use crate::url_policy;
+use chromiumoxide::browser::Browser;
+use chromiumoxide::handler::{Handler, HandlerConfig};
+use chromiumoxide::page::Page;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tracing::{debug, info};
+
+/// How long the browser has to answer `/json/version`
+const VERSION_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Which of an attached browser's existing tabs the executor may control
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TabScope {
+    /// None: only tabs the executor opens
+    #[default]
+    Own,
+
+    /// Those whose URL matches one of the patterns, as in
+    /// [`UrlPolicy`](crate::UrlPolicy)
+    Matching(Vec<String>),
+
+    /// All of them
+    All,
+}
+
+impl TabScope {
+    /// Whether an existing tab showing `url` may be controlled
+    pub fn includes(&self, url: &str) -> bool {
+        match self {
+            TabScope::Own => false,
+            TabScope::Matching(patterns) => {
+                patterns.iter().any(|pattern| url_policy::matches(pattern, url))
+            }
+            TabScope::All => true,
+        }
+    }
+}
+
+/// Connect to the browser at `url`, an `http://` DevTools address or the
+/// browser's `ws://` endpoint
+pub(crate) async fn connect(url: &str) -> Result<(Browser, Handler), ExecutorError> {
+    let endpoint = websocket_url(url).await.map_err(|e| {
+        ExecutorError::LaunchFailed(format!("No browser at {}: {}", url, e))
+    })?;
+
+    // The user's tabs keep their own size
+    let handler_config = HandlerConfig {
+        viewport: None,
+        ..Default::default()
+    };
+    let (browser, handler) = Browser::connect_with_config(endpoint.as_str(), handler_config)
+        .await
+        .map_err(|e| ExecutorError::LaunchFailed(e.to_string()))?;
+
+    info!("Attached to the browser at {}", endpoint);
+    Ok((browser, handler))
+}
+
+/// The existing tabs of `browser` that `scope` lets the executor control
+pub(crate) async fn tabs_in_scope(browser: &Browser, scope: &TabScope) -> Vec<Page> {
+    if *scope == TabScope::Own {
+        return Vec::new();
+    }
+
+    let pages = match browser.pages().await {
+        Ok(pages) => pages,
+        Err(e) => {
+            debug!("Failed to list the browser's tabs: {}", e);
+            return Vec::new();
+        }
+    };
+    let mut in_scope = Vec::new();
+    for page in pages {
+        let url = page.url().await.ok().flatten().unwrap_or_default();
+        if scope.includes(&url) {
+            in_scope.push(page);
+        }
+    }
+    in_scope
+}
+
+/// The browser's WebSocket endpoint, asked of its HTTP server if need be
+async fn websocket_url(url: &str) -> Result<String, String> {
+    if url.starts_with("ws://") || url.starts_with("wss://") {
+        return Ok(url.to_string());
+    }
+
+    let parsed = url::Url::parse(url).map_err(|e| e.to_string())?;
+    if parsed.scheme() != "http" {
+        return Err(format!("unsupported scheme {}", parsed.scheme()));
+    }
+    let host = parsed.host_str().ok_or("no host")?;
+    let port = parsed.port_or_known_default().unwrap_or(80);
+
+    let request = async {
+        let mut stream = TcpStream::connect((host, port))
+            .await
+            .map_err(|e| e.to_string())?;
+        let request = format!(
+            "GET /json/version HTTP/1.1\r\nHost: {}:{}\r\nConnection: close\r\n\r\n",
+            host, port
+        );
+        stream
+            .write_all(request.as_bytes())
+            .await
+            .map_err(|e| e.to_string())?;
+
+        // Read until the body parses, in case the connection is kept open
+        let mut response = Vec::new();
+        let mut chunk = [0; 4096];
+        loop {
+            let read = stream.read(&mut chunk).await.map_err(|e| e.to_string())?;
+            response.extend_from_slice(&chunk[..read]);
+            let parsed = debugger_url(&String::from_utf8_lossy(&response));
+            if parsed.is_ok() || read == 0 {
+                return parsed;
+            }
+        }
+    };
+    tokio::time::timeout(VERSION_TIMEOUT, request)
+        .await
+        .map_err(|_| "no answer from /json/version".to_string())?
+}
+
+/// `webSocketDebuggerUrl` from a `/json/version` response
+fn debugger_url(response: &str) -> Result<String, String> {
+    let body = response
+        .split_once("\r\n\r\n")
+        .map_or(response, |(_, body)| body);
+    let version: serde_json::Value = serde_json::from_str(body).map_err(|e| e.to_string())?;
+    version["webSocketDebuggerUrl"]
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| "no webSocketDebuggerUrl in /json/version".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tab_scope() {
+        let scope: TabScope = serde_json::from_str(r#"{"matching": ["mail.example.com"]}"#).unwrap();
+        assert!(scope.includes("https://mail.example.com/inbox"));
+        assert!(!scope.includes("https://bank.example.net/"));
+
+        assert_eq!(serde_json::from_str::<TabScope>(r#""all""#).unwrap(), TabScope::All);
+        assert!(!TabScope::default().includes("https://mail.example.com/"));
+    }
+
+    #[test]
+    fn test_debugger_url() {
+        let response = "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\r\n{\n\
+            \"Browser\": \"Chrome/120.0.6099.109\",\n\
+            \"webSocketDebuggerUrl\": \"ws://127.0.0.1:9222/devtools/browser/9f3c\"\n}";
+        assert_eq!(
+            debugger_url(response).as_deref(),
+            Ok("ws://127.0.0.1:9222/devtools/browser/9f3c")
+        );
+
+        // Not all of it read yet
+        assert!(debugger_url(&response[..60]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_websocket_url() {
+        let url = "ws://127.0.0.1:9222/devtools/browser/9f3c";
+        assert_eq!(websocket_url(url).await.as_deref(), Ok(url));
+        assert!(websocket_url("ftp://127.0.0.1:9222").await.is_err());
+    }
+}
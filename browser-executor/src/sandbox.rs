@@ -1,8 +1,11 @@
 //! Sandbox wrapper using nsjail for process isolation (Linux only)
+//!
+//! With `ExecutorConfig::enable_sandbox`, the executor runs the browser itself
+//! in a jail set up by [`SandboxConfig::browser`].
 
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
-use std::process::{Command, Stdio};
+use std::path::{Path, PathBuf};
+use std::process::{ChildStderr, Command, Stdio};
 use thiserror::Error;
 
 /// Sandbox errors
@@ -27,13 +30,13 @@ pub enum SandboxError {
 /// Sandbox configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SandboxConfig {
-    /// Maximum memory (MB)
+    /// Maximum memory (MB; 0: no limit)
     pub max_memory_mb: u64,
 
-    /// Maximum CPU time (seconds)
+    /// Maximum CPU time (seconds; 0: no limit)
     pub max_cpu_time_secs: u64,
 
-    /// Maximum wall time (seconds)
+    /// Maximum wall time (seconds; 0: no limit)
     pub max_wall_time_secs: u64,
 
     /// Maximum file size (MB)
@@ -45,12 +48,25 @@ pub struct SandboxConfig {
     /// Allowed mount points
     pub mount_points: Vec<MountPoint>,
 
+    /// Directories given a private, empty tmpfs (mounted before
+    /// `mount_points`, which may mount inside them)
+    #[serde(default)]
+    pub tmpfs_mounts: Vec<PathBuf>,
+
+    /// Environment variables passed on from the host (nsjail clears the rest)
+    #[serde(default)]
+    pub env: Vec<String>,
+
     /// Hostname inside sandbox
     pub hostname: String,
 
     /// Enable network access
     pub enable_network: bool,
 
+    /// Mount `/proc` inside the sandbox
+    #[serde(default)]
+    pub enable_proc: bool,
+
     /// Working directory
     pub working_dir: PathBuf,
 }
@@ -80,8 +96,61 @@ impl Default for SandboxConfig {
                     readonly: false,
                 },
             ],
+            tmpfs_mounts: Vec::new(),
+            env: Vec::new(),
+            hostname: "browser-sandbox".to_string(),
+            enable_network: true,
+            enable_proc: false,
+            working_dir: PathBuf::from("/tmp"),
+        }
+    }
+}
+
+impl SandboxConfig {
+    /// Settings for a browser writing its profile to `user_data_dir`, or to
+    /// the jail's own `/tmp`
+    ///
+    /// The browser shares the host's network, so the DevTools connection
+    /// reaches it, and sees `/proc`, the system libraries, fonts and
+    /// certificates (read-only), and private `/tmp` and `/dev/shm` that vanish
+    /// with the jail. It has no CPU, wall time or address space limit:
+    /// browsers reserve far more address space than they use, and the
+    /// executor enforces memory and time limits itself.
+    pub fn browser(user_data_dir: Option<&Path>) -> Self {
+        let read_only = ["/lib", "/lib64", "/usr", "/bin", "/etc", "/opt"];
+        let writable = ["/dev/null", "/dev/zero", "/dev/urandom"];
+
+        let mut mount_points: Vec<MountPoint> = read_only
+            .iter()
+            .map(|path| (path, true))
+            .chain(writable.iter().map(|path| (path, false)))
+            .filter(|(path, _)| Path::new(path).exists())
+            .map(|(path, readonly)| MountPoint {
+                source: PathBuf::from(path),
+                target: PathBuf::from(path),
+                readonly,
+            })
+            .collect();
+        if let Some(dir) = user_data_dir {
+            mount_points.push(MountPoint {
+                source: dir.to_path_buf(),
+                target: dir.to_path_buf(),
+                readonly: false,
+            });
+        }
+
+        Self {
+            max_memory_mb: 0,
+            max_cpu_time_secs: 0,
+            max_wall_time_secs: 0,
+            max_file_size_mb: 1024,
+            max_open_files: 8192,
+            mount_points,
+            tmpfs_mounts: vec![PathBuf::from("/tmp"), PathBuf::from("/dev/shm")],
+            env: Vec::new(),
             hostname: "browser-sandbox".to_string(),
             enable_network: true,
+            enable_proc: true,
             working_dir: PathBuf::from("/tmp"),
         }
     }
@@ -160,6 +229,16 @@ impl SandboxedProcess {
         }
     }
 
+    /// The running process's id (nsjail's, with the command as its child)
+    pub fn id(&self) -> Option<u32> {
+        self.child.as_ref().map(std::process::Child::id)
+    }
+
+    /// Take the running process's standard error, to read it as it runs
+    pub fn take_stderr(&mut self) -> Option<ChildStderr> {
+        self.child.as_mut()?.stderr.take()
+    }
+
     /// Wait for async process to complete
     pub fn wait(&mut self) -> Result<std::process::Output, SandboxError> {
         if let Some(child) = self.child.take() {
@@ -206,11 +285,17 @@ impl SandboxedProcess {
 
         // Memory limit
         nsjail_args.push("--rlimit_as".to_string());
-        nsjail_args.push((self.config.max_memory_mb * 1024 * 1024).to_string());
+        nsjail_args.push(match self.config.max_memory_mb {
+            0 => "inf".to_string(),
+            mb => (mb * 1024 * 1024).to_string(),
+        });
 
         // CPU time limit
         nsjail_args.push("--rlimit_cpu".to_string());
-        nsjail_args.push(self.config.max_cpu_time_secs.to_string());
+        nsjail_args.push(match self.config.max_cpu_time_secs {
+            0 => "inf".to_string(),
+            secs => secs.to_string(),
+        });
 
         // File size limit
         nsjail_args.push("--rlimit_fsize".to_string());
@@ -229,6 +314,12 @@ impl SandboxedProcess {
             nsjail_args.push("--disable_clone_newnet".to_string());
         }
 
+        // Private tmpfs first, so mount points can be placed inside them
+        for dir in &self.config.tmpfs_mounts {
+            nsjail_args.push("--tmpfsmount".to_string());
+            nsjail_args.push(dir.to_string_lossy().to_string());
+        }
+
         // Mount points
         for mount in &self.config.mount_points {
             let mount_spec = if mount.readonly {
@@ -248,7 +339,15 @@ impl SandboxedProcess {
         nsjail_args.push("nogroup".to_string());
 
         // Disable proc
-        nsjail_args.push("--disable_proc".to_string());
+        if !self.config.enable_proc {
+            nsjail_args.push("--disable_proc".to_string());
+        }
+
+        // Host environment passed on
+        for var in &self.config.env {
+            nsjail_args.push("--env".to_string());
+            nsjail_args.push(var.clone());
+        }
 
         // Command separator
         nsjail_args.push("--".to_string());
@@ -315,4 +414,32 @@ mod tests {
         assert!(args.contains(&"--hostname".to_string()));
         assert!(args.contains(&"echo".to_string()));
     }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_browser_nsjail_args() {
+        let config = SandboxConfig::browser(Some(Path::new("/var/lib/profiles/session-1")));
+        let sandbox = SandboxedProcess::new(config);
+
+        let args = sandbox
+            .build_nsjail_args("chromium", &["--remote-debugging-port=0".to_string()])
+            .unwrap();
+
+        let value = |flag: &str| {
+            let index = args.iter().position(|arg| arg == flag).unwrap();
+            args[index + 1].as_str()
+        };
+        assert_eq!(value("--rlimit_as"), "inf");
+        assert_eq!(value("--time_limit"), "0");
+        assert!(args.contains(&"--disable_clone_newnet".to_string()));
+        assert!(!args.contains(&"--disable_proc".to_string()));
+        assert!(args.contains(&"/var/lib/profiles/session-1:/var/lib/profiles/session-1".to_string()));
+
+        // /tmp is the jail's own, mounted before anything inside it
+        assert!(!args.contains(&"/tmp:/tmp".to_string()));
+        let tmpfs = args.iter().position(|arg| arg == "--tmpfsmount").unwrap();
+        let bind = args.iter().position(|arg| arg == "--bindmount").unwrap();
+        assert_eq!(args[tmpfs + 1], "/tmp");
+        assert!(tmpfs < bind);
+    }
 }
@@ -1,14 +1,26 @@
 //! Screenshot capture and image processing
+//!
+//! Full-page screenshots are captured in one go, past the viewport. Very tall
+//! pages can come back blank or fail that way, and lazy-loaded content only
+//! appears once scrolled to, so `stitch` instead scrolls through the page a
+//! viewport at a time and composites the tiles. Either way the height is
+//! capped at `max_height`.
 
 use chromiumoxide::cdp::browser_protocol::page::{
-    CaptureScreenshotFormat, CaptureScreenshotParams,
+    CaptureScreenshotFormat, CaptureScreenshotParams, GetLayoutMetricsParams,
 };
+use crate::emulation;
+use chromiumoxide::element::Element;
 use chromiumoxide::page::Page;
-use image::ImageFormat;
+use image::{DynamicImage, ImageFormat, RgbaImage};
 use serde::{Deserialize, Serialize};
 use std::io::Cursor;
+use std::time::Duration;
 use thiserror::Error;
 
+/// Time for lazy-loaded content to appear after each scroll when stitching
+const STITCH_SETTLE: Duration = Duration::from_millis(250);
+
 /// Screenshot errors
 #[derive(Error, Debug)]
 pub enum ScreenshotError {
@@ -71,6 +83,18 @@ pub struct ScreenshotOptions {
 
     /// Return as base64 string
     pub as_base64: bool,
+
+    /// For `full_page`: scroll through the page and stitch viewport tiles
+    #[serde(default)]
+    pub stitch: bool,
+
+    /// For `full_page`: tallest page captured (CSS pixels); the rest is cut off
+    #[serde(default = "default_max_height")]
+    pub max_height: u32,
+}
+
+fn default_max_height() -> u32 {
+    20_000
 }
 
 impl Default for ScreenshotOptions {
@@ -81,11 +105,13 @@ impl Default for ScreenshotOptions {
             quality: Some(80),
             clip: None,
             as_base64: true,
+            stitch: false,
+            max_height: default_max_height(),
         }
     }
 }
 
-/// Screenshot clip region
+/// Screenshot clip region, in CSS pixels from the top left of the page
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct ScreenshotClip {
     pub x: f64,
@@ -215,8 +241,9 @@ impl ScreenshotCapturer {
             }
         }
 
-        // Set clip region
+        // Set clip region, which may extend past the viewport
         if let Some(clip) = options.clip {
+            params.capture_beyond_viewport = Some(true);
             params.clip = Some(
                 chromiumoxide::cdp::browser_protocol::page::Viewport::builder()
                     .x(clip.x)
@@ -229,17 +256,31 @@ impl ScreenshotCapturer {
         }
 
         // Capture screenshot
-        let data = if options.full_page {
-            // Capture full page by setting capture_beyond_viewport
-            params.capture_beyond_viewport = Some(true);
-            page.screenshot(params)
-                .await
-                .map_err(|e| ScreenshotError::CaptureFailed(e.to_string()))?
-        } else {
-            page.screenshot(params)
+        if options.full_page && options.stitch {
+            return Self::capture_stitched(page, &options).await;
+        }
+        if options.full_page && params.clip.is_none() {
+            // Capture the whole content, which is past the viewport
+            let metrics = page
+                .execute(GetLayoutMetricsParams::default())
                 .await
-                .map_err(|e| ScreenshotError::CaptureFailed(e.to_string()))?
-        };
+                .map_err(|e| ScreenshotError::CaptureFailed(e.to_string()))?;
+            let content = &metrics.result.css_content_size;
+            params.capture_beyond_viewport = Some(true);
+            params.clip = Some(
+                chromiumoxide::cdp::browser_protocol::page::Viewport::builder()
+                    .x(0.0)
+                    .y(0.0)
+                    .width(content.width)
+                    .height(content.height.min(options.max_height as f64))
+                    .scale(1.0)
+                    .build()?,
+            );
+        }
+        let data = page
+            .screenshot(params)
+            .await
+            .map_err(|e| ScreenshotError::CaptureFailed(e.to_string()))?;
 
         // Get image dimensions
         let img = image::load_from_memory(&data)
@@ -258,6 +299,78 @@ impl ScreenshotCapturer {
         })
     }
 
+    /// Capture the page a viewport at a time, from the top, and stitch the
+    /// tiles together
+    ///
+    /// The page's scroll position is restored afterwards.
+    async fn capture_stitched(
+        page: &Page,
+        options: &ScreenshotOptions,
+    ) -> Result<Screenshot, ScreenshotError> {
+        let failed =
+            |e: chromiumoxide::error::CdpError| ScreenshotError::CaptureFailed(e.to_string());
+        let (scroll_x, scroll_y, viewport_height): (f64, f64, f64) = page
+            .evaluate("[scrollX, scrollY, innerHeight]")
+            .await
+            .map_err(failed)?
+            .into_value()
+            .map_err(|e| ScreenshotError::CaptureFailed(e.to_string()))?;
+        let max_height = options.max_height as f64;
+
+        let mut tiles = Vec::new();
+        let mut bottom = 0.0;
+        loop {
+            // Where the browser actually scrolled to: less than asked at the end
+            let top: f64 = page
+                .evaluate(format!("scrollTo(0, {}); scrollY", bottom))
+                .await
+                .map_err(failed)?
+                .into_value()
+                .map_err(|e| ScreenshotError::CaptureFailed(e.to_string()))?;
+            tokio::time::sleep(STITCH_SETTLE).await;
+
+            let tile = page
+                .screenshot(CaptureScreenshotParams::default())
+                .await
+                .map_err(failed)?;
+            let tile = image::load_from_memory(&tile)
+                .map_err(|e| ScreenshotError::ProcessingError(e.to_string()))?
+                .to_rgba8();
+            tiles.push((top, tile));
+
+            // Lazy-loaded content may have made the page taller
+            let height: f64 = page
+                .evaluate("document.documentElement.scrollHeight")
+                .await
+                .map_err(failed)?
+                .into_value()
+                .map_err(|e| ScreenshotError::CaptureFailed(e.to_string()))?;
+            let end = top + viewport_height;
+            if end >= height.min(max_height) || end <= bottom {
+                bottom = end.max(bottom);
+                break;
+            }
+            bottom = end;
+        }
+
+        page.evaluate(format!("scrollTo({}, {})", scroll_x, scroll_y))
+            .await
+            .map_err(failed)?;
+
+        let scale = tiles[0].1.height() as f64 / viewport_height;
+        let image = stitch(&tiles, scale, bottom.min(max_height));
+        let (width, height) = image.dimensions();
+        let data = encode(DynamicImage::ImageRgba8(image), options)?;
+
+        Ok(Screenshot {
+            size_bytes: data.len(),
+            data,
+            format: options.format,
+            width,
+            height,
+        })
+    }
+
     /// Capture element screenshot
     pub async fn capture_element(
         page: &Page,
@@ -265,68 +378,142 @@ impl ScreenshotCapturer {
         options: ScreenshotOptions,
     ) -> Result<Screenshot, ScreenshotError> {
         // Find element
-        let _element = page
+        let element = page
             .find_element(selector)
             .await
             .map_err(|e| ScreenshotError::CaptureFailed(format!("Element not found: {}", e)))?;
 
-        // Get element bounding box using JS
-        let script = format!(
-            "document.querySelector('{}').getBoundingClientRect()",
-            selector.replace("'", "\\'")
-        );
+        Self::capture_found(page, &element, options).await
+    }
 
-        let rect_json: serde_json::Value = page
-            .evaluate(script.as_str())
+    /// Capture a screenshot of an element already found, in any frame
+    ///
+    /// The element is scrolled into view first (so lazy content renders), and
+    /// parts of it still outside the viewport are captured too.
+    pub async fn capture_found(
+        page: &Page,
+        element: &Element,
+        options: ScreenshotOptions,
+    ) -> Result<Screenshot, ScreenshotError> {
+        let failed =
+            |e: chromiumoxide::error::CdpError| ScreenshotError::CaptureFailed(e.to_string());
+        element.scroll_into_view().await.map_err(failed)?;
+
+        // The box is relative to the viewport, the clip to the page
+        let bounds = element.bounding_box().await.map_err(failed)?;
+        if bounds.width <= 0.0 || bounds.height <= 0.0 {
+            return Err(ScreenshotError::CaptureFailed(
+                "Element has an empty box".to_string(),
+            ));
+        }
+        let metrics = page
+            .execute(GetLayoutMetricsParams::default())
             .await
-            .map_err(|e| ScreenshotError::CaptureFailed(e.to_string()))?
-            .into_value()
-            .map_err(|e| ScreenshotError::CaptureFailed(e.to_string()))?;
-
-        // Parse bounding box
-        let rect: serde_json::Value = serde_json::from_str(&rect_json.to_string())
-            .map_err(|e| ScreenshotError::ProcessingError(e.to_string()))?;
+            .map_err(failed)?;
+        let scroll = &metrics.result.css_layout_viewport;
 
         let clip = ScreenshotClip {
-            x: rect["x"].as_f64().unwrap_or(0.0),
-            y: rect["y"].as_f64().unwrap_or(0.0),
-            width: rect["width"].as_f64().unwrap_or(0.0),
-            height: rect["height"].as_f64().unwrap_or(0.0),
+            x: bounds.x + scroll.page_x as f64,
+            y: bounds.y + scroll.page_y as f64,
+            width: bounds.width,
+            height: bounds.height,
             scale: Some(1.0),
         };
 
-        // Capture with clip
-        let mut opts = options;
-        opts.clip = Some(clip);
-
-        Self::capture(page, opts).await
+        Self::capture(page, ScreenshotOptions {
+            full_page: false,
+            clip: Some(clip),
+            ..options
+        })
+        .await
     }
 
     /// Capture multiple screenshots at different viewports
+    ///
+    /// Each viewport is emulated in turn (see `emulation`), keeping the
+    /// page's device pixel ratio; the viewport size and ratio in effect
+    /// before are restored afterwards.
     pub async fn capture_responsive(
         page: &Page,
         viewports: Vec<(u32, u32)>, // (width, height)
         options: ScreenshotOptions,
     ) -> Result<Vec<Screenshot>, ScreenshotError> {
+        let failed =
+            |e: chromiumoxide::error::CdpError| ScreenshotError::CaptureFailed(e.to_string());
+        let (width, height, scale): (u32, u32, f64) = page
+            .evaluate("[innerWidth, innerHeight, devicePixelRatio]")
+            .await
+            .map_err(failed)?
+            .into_value()
+            .map_err(|e| ScreenshotError::CaptureFailed(e.to_string()))?;
+
         let mut screenshots = Vec::new();
+        let mut result = Ok(());
+        for (viewport_width, viewport_height) in viewports {
+            result = Self::capture_at(page, viewport_width, viewport_height, scale, &options)
+                .await
+                .map(|screenshot| screenshots.push(screenshot));
+            if result.is_err() {
+                break;
+            }
+        }
 
-        for (_width, _height) in viewports {
-            // Note: set_viewport() is not available in chromiumoxide API
-            // Viewport changes would require emulation configuration at browser launch
-            // For now, capture at default viewport size
+        page.execute(emulation::metrics(width, height, scale, false))
+            .await
+            .map_err(failed)?;
+        result.map(|_| screenshots)
+    }
 
-            // Wait for render
-            tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+    async fn capture_at(
+        page: &Page,
+        width: u32,
+        height: u32,
+        scale: f64,
+        options: &ScreenshotOptions,
+    ) -> Result<Screenshot, ScreenshotError> {
+        page.execute(emulation::metrics(width, height, scale, false))
+            .await
+            .map_err(|e| ScreenshotError::CaptureFailed(e.to_string()))?;
 
-            // Capture
-            let screenshot = Self::capture(page, options.clone()).await?;
-            screenshots.push(screenshot);
-        }
+        // Wait for relayout
+        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
 
-        Ok(screenshots)
+        Self::capture(page, options.clone()).await
     }
 }
 
+/// Composite tiles captured at `top` (CSS pixels) into one image `height`
+/// CSS pixels tall, at `scale` device pixels per CSS pixel
+///
+/// Later tiles overwrite earlier ones where they overlap, as the last tile
+/// does when the page's end is less than a viewport further on.
+fn stitch(tiles: &[(f64, RgbaImage)], scale: f64, height: f64) -> RgbaImage {
+    let width = tiles.iter().map(|(_, tile)| tile.width()).max().unwrap_or(0);
+    let mut image = RgbaImage::new(width, (height * scale).round() as u32);
+    for (top, tile) in tiles {
+        let y = (top * scale).round() as i64;
+        image::imageops::overlay(&mut image, tile, 0, y);
+    }
+    image
+}
+
+/// Encode in the options' format and JPEG quality
+fn encode(image: DynamicImage, options: &ScreenshotOptions) -> Result<Vec<u8>, ScreenshotError> {
+    let mut buffer = Vec::new();
+    match options.format {
+        ScreenshotFormat::Png => image
+            .write_to(&mut Cursor::new(&mut buffer), ImageFormat::Png)
+            .map_err(|e| ScreenshotError::EncodingError(e.to_string()))?,
+        ScreenshotFormat::Jpeg => {
+            let quality = options.quality.unwrap_or(80).min(100);
+            image::codecs::jpeg::JpegEncoder::new_with_quality(&mut buffer, quality)
+                .encode_image(&image.to_rgb8())
+                .map_err(|e| ScreenshotError::EncodingError(e.to_string()))?
+        }
+    }
+    Ok(buffer)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -358,4 +545,19 @@ mod tests {
         let data_url = screenshot.to_data_url();
         assert!(data_url.starts_with("data:image/png;base64,"));
     }
+
+    #[test]
+    fn test_stitch() {
+        let tile = |shade| RgbaImage::from_pixel(4, 6, image::Rgba([shade, 0, 0, 255]));
+        // 3 CSS pixels per viewport at scale 2; the last tile stops early
+        let tiles = vec![(0.0, tile(10)), (3.0, tile(20)), (4.0, tile(30))];
+
+        let image = stitch(&tiles, 2.0, 7.0);
+
+        assert_eq!(image.dimensions(), (4, 14));
+        assert_eq!(image.get_pixel(0, 5).0[0], 10);
+        assert_eq!(image.get_pixel(0, 7).0[0], 20);
+        assert_eq!(image.get_pixel(0, 8).0[0], 30);
+        assert_eq!(image.get_pixel(3, 13).0[0], 30);
+    }
 }
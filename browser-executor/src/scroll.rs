@@ -0,0 +1,129 @@
+//! Scrolling through lazy-loaded content
+//!
+//! `BrowserAction::ScrollToEnd` scrolls to the bottom of the page, waits
+//! `settle_ms` for more content to load, and repeats until the page stops
+//! growing or `max_iterations` scrolls have been made. Feeds and search
+//! result pages that load more as the reader nears the end are then fully
+//! loaded for `Extract` or `GetText`. Items are counted with `item_selector`
+//! (every element by default), so the result says how many were loaded.
+
+use crate::selector::{Selector, RESOLVE_QUERY};
+use serde::{Deserialize, Serialize};
+
+/// Scrolls the page's scrolling element to its bottom
+pub(crate) const SCROLL: &str =
+    "(() => { const el = document.scrollingElement || document.body; \
+     el.scrollTo(0, el.scrollHeight); })()";
+
+/// The `data` of a `ScrollToEnd` action
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScrollResult {
+    /// Scrolls made
+    pub iterations: u32,
+
+    /// Items added while scrolling
+    pub items_loaded: u64,
+
+    /// Items on the page at the end
+    pub total_items: u64,
+
+    /// Page height at the end (CSS pixels)
+    pub height: u64,
+
+    /// Whether the page stopped growing before `max_iterations`
+    pub reached_end: bool,
+}
+
+/// The page's height and item count at one point
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+pub(crate) struct Extent {
+    pub(crate) height: u64,
+    pub(crate) items: u64,
+}
+
+impl Extent {
+    /// Whether the page has more content than at `before`
+    pub(crate) fn grew_since(&self, before: &Extent) -> bool {
+        self.height > before.height || self.items > before.items
+    }
+}
+
+impl ScrollResult {
+    pub(crate) fn new(start: Extent, end: Extent, iterations: u32, reached_end: bool) -> Self {
+        Self {
+            iterations,
+            items_loaded: end.items.saturating_sub(start.items),
+            total_items: end.items,
+            height: end.height,
+            reached_end,
+        }
+    }
+}
+
+/// Script that measures the page as an [`Extent`], counting `items` matches
+/// or, without a selector, every element
+pub(crate) fn probe_script(items: Option<&Selector>, pierce_shadow: bool) -> String {
+    let count = match items {
+        Some(selector) => format!(
+            "({})({}).length",
+            RESOLVE_QUERY,
+            selector.query(pierce_shadow)
+        ),
+        None => "document.getElementsByTagName('*').length".to_string(),
+    };
+    format!(
+        "(() => {{ const el = document.scrollingElement || document.body; \
+         return {{ height: Math.round(el.scrollHeight), items: {} }}; }})()",
+        count
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scroll_result() {
+        let start = Extent {
+            height: 2000,
+            items: 20,
+        };
+        let end = Extent {
+            height: 6000,
+            items: 60,
+        };
+        assert!(end.grew_since(&start));
+        assert!(!start.grew_since(&start));
+
+        let result = ScrollResult::new(start, end, 5, true);
+        assert_eq!(result.items_loaded, 40);
+        assert_eq!(result.total_items, 60);
+        assert_eq!(result.height, 6000);
+    }
+
+    #[test]
+    fn test_probe_script() {
+        let selector = Selector::Css("article.post".to_string());
+        let script = probe_script(Some(&selector), false);
+        assert!(script.contains(r#""css":"article.post""#));
+        assert!(script.contains(".length"));
+
+        let script = probe_script(None, false);
+        assert!(script.contains("getElementsByTagName('*')"));
+    }
+
+    #[test]
+    fn test_scroll_to_end_defaults() {
+        let action: crate::BrowserAction =
+            serde_json::from_str(r#"{"type": "scroll_to_end", "item_selector": ".post"}"#).unwrap();
+        assert!(matches!(
+            action,
+            crate::BrowserAction::ScrollToEnd {
+                max_iterations: 20,
+                settle_ms: 1000,
+                item_selector: Some(Selector::Css(_)),
+                pierce_shadow: false,
+            }
+        ));
+    }
+}
@@ -0,0 +1,227 @@
+//! Element selectors
+//!
+//! Element actions take a [`Selector`]: a plain string is a CSS selector, and
+//! objects select by XPath, visible text or ARIA label. Text and ARIA label
+//! selectors are turned into XPath, so everything but CSS is resolved with
+//! CDP `DOM.performSearch` (or `document.evaluate` in injected scripts).
+//!
+//! Neither lookup enters shadow roots. Actions with `pierce_shadow` fall back
+//! to a script that repeats the query in every open shadow root.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// How to find an element
+///
+/// ```json
+/// "button.primary"
+/// { "xpath": "//form//button[2]" }
+/// { "text": "Submit", "exact": true }
+/// { "aria_label": "Close dialog" }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Selector {
+    Css(String),
+
+    XPath {
+        xpath: String,
+    },
+
+    /// The innermost element whose whitespace-normalized text contains (or,
+    /// with `exact`, equals) `text`
+    Text {
+        text: String,
+        #[serde(default)]
+        exact: bool,
+    },
+
+    AriaLabel {
+        aria_label: String,
+    },
+}
+
+impl Selector {
+    /// The XPath expression for every selector but CSS
+    pub fn xpath(&self) -> Option<String> {
+        match self {
+            Selector::Css(_) => None,
+            Selector::XPath { xpath } => Some(xpath.clone()),
+            Selector::Text { text, exact } => {
+                let text = xpath_literal(text);
+                let test = if *exact {
+                    format!("normalize-space(.) = {}", text)
+                } else {
+                    format!("contains(normalize-space(.), {})", text)
+                };
+                // Innermost match, so `body` does not contain every button
+                Some(format!(
+                    "//*[not(self::script or self::style) and {test} and not(.//*[{test}])]",
+                    test = test
+                ))
+            }
+            Selector::AriaLabel { aria_label } => {
+                Some(format!("//*[@aria-label = {}]", xpath_literal(aria_label)))
+            }
+        }
+    }
+
+    /// `{ "css": ... }` or `{ "xpath": ... }`, plus whether to search open
+    /// shadow roots, for resolving in page scripts
+    pub(crate) fn query(&self, pierce_shadow: bool) -> serde_json::Value {
+        match (self, self.xpath()) {
+            (Selector::Css(css), _) => serde_json::json!({ "css": css, "pierce": pierce_shadow }),
+            (_, xpath) => serde_json::json!({ "xpath": xpath, "pierce": pierce_shadow }),
+        }
+    }
+
+    /// Script that sets the `marker` attribute on the first match; evaluates
+    /// to whether anything matched
+    pub(crate) fn mark_script(&self, marker: &str, pierce_shadow: bool) -> String {
+        format!(
+            "(() => {{ const el = ({})({})[0]; \
+             if (el) el.setAttribute('{}', ''); return !!el; }})()",
+            RESOLVE_QUERY,
+            self.query(pierce_shadow),
+            marker
+        )
+    }
+}
+
+/// A page-unique attribute name for `Selector::mark_script`
+pub(crate) fn marker() -> String {
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    format!(
+        "data-browser-executor-{}-{}",
+        std::process::id(),
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+impl fmt::Display for Selector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Selector::Css(css) => write!(f, "{}", css),
+            Selector::XPath { xpath } => write!(f, "xpath {}", xpath),
+            Selector::Text { text, exact: true } => write!(f, "text {:?}", text),
+            Selector::Text { text, exact: false } => write!(f, "text containing {:?}", text),
+            Selector::AriaLabel { aria_label } => write!(f, "aria-label {:?}", aria_label),
+        }
+    }
+}
+
+impl From<&str> for Selector {
+    fn from(css: &str) -> Self {
+        Selector::Css(css.to_string())
+    }
+}
+
+impl From<String> for Selector {
+    fn from(css: String) -> Self {
+        Selector::Css(css)
+    }
+}
+
+/// Quote `s` as an XPath 1.0 string literal, which has no escapes
+fn xpath_literal(s: &str) -> String {
+    if !s.contains('"') {
+        format!("\"{}\"", s)
+    } else if !s.contains('\'') {
+        format!("'{}'", s)
+    } else {
+        let parts: Vec<String> = s.split('"').map(|part| format!("\"{}\"", part)).collect();
+        format!("concat({})", parts.join(", '\"', "))
+    }
+}
+
+/// Resolves a `query()` to matching elements in a page script, within
+/// `scope` (an element, or the document by default)
+///
+/// Shadow roots are only searched when the scope has no match. In them, and
+/// in element scopes, absolute XPaths are made relative, since `/` is always
+/// the document.
+pub(crate) const RESOLVE_QUERY: &str = r#"(query, scope = document) => {
+  const search = (root) => {
+    if (query.css) return Array.from(root.querySelectorAll(query.css));
+    const xpath = root !== document && query.xpath.startsWith('/')
+      ? '.' + query.xpath : query.xpath;
+    const found = document.evaluate(xpath, root, null,
+      XPathResult.ORDERED_NODE_SNAPSHOT_TYPE, null);
+    return Array.from({ length: found.snapshotLength }, (_, i) => found.snapshotItem(i));
+  };
+  const matches = search(scope);
+  if (matches.length || !query.pierce) return matches;
+
+  const roots = [];
+  const collect = (root) => root.querySelectorAll('*').forEach((el) => {
+    if (el.shadowRoot) {
+      roots.push(el.shadowRoot);
+      collect(el.shadowRoot);
+    }
+  });
+  collect(scope);
+  return roots.flatMap(search);
+}"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_selector_forms() {
+        let selectors: Vec<Selector> = serde_json::from_str(
+            r#"[
+                "button.primary",
+                {"xpath": "//button"},
+                {"text": "Submit"},
+                {"text": "Sign in", "exact": true},
+                {"aria_label": "Close"}
+            ]"#,
+        )
+        .unwrap();
+
+        assert_eq!(selectors[0], Selector::from("button.primary"));
+        assert_eq!(selectors[0].xpath(), None);
+        assert_eq!(selectors[1].xpath().unwrap(), "//button");
+        assert_eq!(
+            selectors[2],
+            Selector::Text {
+                text: "Submit".to_string(),
+                exact: false
+            }
+        );
+        assert!(selectors[3]
+            .xpath()
+            .unwrap()
+            .contains(r#"normalize-space(.) = "Sign in""#));
+        assert_eq!(
+            selectors[4].xpath().unwrap(),
+            r#"//*[@aria-label = "Close"]"#
+        );
+        assert_eq!(selectors[4].to_string(), r#"aria-label "Close""#);
+        assert_eq!(selectors[1].query(false)["xpath"], "//button");
+        assert_eq!(selectors[0].query(false)["css"], "button.primary");
+    }
+
+    #[test]
+    fn test_mark_script() {
+        let first = marker();
+        assert_ne!(first, marker());
+        assert!(first.starts_with("data-browser-executor-"));
+
+        let script = Selector::from("#save").mark_script(&first, true);
+        assert!(script.contains(r##"{"css":"#save","pierce":true}"##));
+        assert!(script.contains(&format!("el.setAttribute('{}', '')", first)));
+    }
+
+    #[test]
+    fn test_xpath_literal() {
+        assert_eq!(xpath_literal("Save"), r#""Save""#);
+        assert_eq!(xpath_literal(r#"Say "hi""#), r#"'Say "hi"'"#);
+        assert_eq!(
+            xpath_literal(r#"It's "it""#),
+            r#"concat("It's ", '"', "it", '"', "")"#
+        );
+    }
+}
@@ -0,0 +1,180 @@
+//! Stealth mode
+//!
+//! Many sites block headless Chrome by what it gives away. With
+//! `ExecutorConfig::stealth`, each tab hides the common tells before any of
+//! the page's scripts run:
+//!
+//! - `navigator.webdriver` is undefined, and Blink's `AutomationControlled`
+//!   feature is off
+//! - the user agent says `Chrome`, not `HeadlessChrome`, and the client hints
+//!   (`Sec-CH-UA*`, `navigator.userAgentData`) agree with it
+//! - `navigator.plugins`, `mimeTypes`, `languages` and `window.chrome` look
+//!   like a desktop browser's, and so do WebGL's vendor and renderer
+//! - the window is larger than the viewport, by a browser's toolbars
+//!
+//! This defeats simple checks, not fingerprinting services.
+
+use chromiumoxide::cdp::browser_protocol::browser::GetVersionParams;
+use chromiumoxide::cdp::browser_protocol::emulation::{
+    SetUserAgentOverrideParams, UserAgentBrandVersion, UserAgentMetadata,
+};
+use chromiumoxide::cdp::browser_protocol::page::AddScriptToEvaluateOnNewDocumentParams;
+use chromiumoxide::page::Page;
+
+/// Launch flag for stealth sessions
+pub(crate) const LAUNCH_ARG: &str = "--disable-blink-features=AutomationControlled";
+
+const ACCEPT_LANGUAGE: &str = "en-US,en;q=0.9";
+
+/// Runs in every frame before the page's own scripts
+const SCRIPT: &str = r#"(() => {
+    const getter = (object, name, value) =>
+        Object.defineProperty(object, name, { get: () => value, configurable: true });
+
+    getter(Navigator.prototype, 'webdriver', undefined);
+    getter(Navigator.prototype, 'languages', Object.freeze(['en-US', 'en']));
+
+    const mimeTypes = [
+        { type: 'application/pdf', suffixes: 'pdf', description: 'Portable Document Format' },
+        { type: 'text/pdf', suffixes: 'pdf', description: 'Portable Document Format' },
+    ];
+    const plugins = ['PDF Viewer', 'Chrome PDF Viewer', 'Chromium PDF Viewer',
+        'Microsoft Edge PDF Viewer', 'WebKit built-in PDF'].map((name) => {
+        const plugin = Object.create(Plugin.prototype);
+        getter(plugin, 'name', name);
+        getter(plugin, 'filename', 'internal-pdf-viewer');
+        getter(plugin, 'description', 'Portable Document Format');
+        getter(plugin, 'length', mimeTypes.length);
+        mimeTypes.forEach((mime, i) => getter(plugin, i, mime));
+        return plugin;
+    });
+    const list = (proto, items, key) => {
+        const object = Object.create(proto);
+        items.forEach((item, i) => {
+            getter(object, i, item);
+            getter(object, item[key], item);
+        });
+        getter(object, 'length', items.length);
+        object.item = (i) => items[i] ?? null;
+        object.namedItem = (name) => items.find((item) => item[key] === name) ?? null;
+        object[Symbol.iterator] = function* () { yield* items; };
+        return object;
+    };
+    getter(Navigator.prototype, 'plugins', list(PluginArray.prototype, plugins, 'name'));
+    getter(Navigator.prototype, 'mimeTypes', list(MimeTypeArray.prototype, mimeTypes, 'type'));
+
+    if (!window.chrome) {
+        window.chrome = { app: { isInstalled: false }, runtime: {}, loadTimes() {}, csi() {} };
+    }
+
+    // Headless answers "denied" while Notification.permission says "default"
+    const query = navigator.permissions && navigator.permissions.query;
+    if (query) {
+        navigator.permissions.query = (parameters) =>
+            parameters && parameters.name === 'notifications'
+                ? Promise.resolve({ state: Notification.permission, onchange: null })
+                : query.call(navigator.permissions, parameters);
+    }
+
+    for (const context of [WebGLRenderingContext, window.WebGL2RenderingContext]) {
+        if (!context) continue;
+        const getParameter = context.prototype.getParameter;
+        context.prototype.getParameter = function (parameter) {
+            if (parameter === 37445) return 'Intel Inc.';
+            if (parameter === 37446) return 'Intel Iris OpenGL Engine';
+            return getParameter.call(this, parameter);
+        };
+    }
+
+    if (window.outerWidth === 0 || window.outerWidth === window.innerWidth) {
+        getter(window, 'outerWidth', window.innerWidth);
+        getter(window, 'outerHeight', window.innerHeight + 85);
+    }
+})();"#;
+
+/// The platform as user agents and client hints name it:
+/// (user agent token, `Sec-CH-UA-Platform`, `navigator.platform`)
+fn platform() -> (&'static str, &'static str, &'static str) {
+    if cfg!(target_os = "windows") {
+        ("Windows NT 10.0; Win64; x64", "Windows", "Win32")
+    } else if cfg!(target_os = "macos") {
+        ("Macintosh; Intel Mac OS X 10_15_7", "macOS", "MacIntel")
+    } else {
+        ("X11; Linux x86_64", "Linux", "Linux x86_64")
+    }
+}
+
+/// The version from a `Browser.getVersion` product, e.g.
+/// `HeadlessChrome/120.0.6099.109`
+fn chrome_version(product: &str) -> &str {
+    product.rsplit('/').next().unwrap_or(product)
+}
+
+/// A desktop Chrome user agent for `version`, reduced as Chrome reports it
+/// (`Chrome/120.0.0.0`)
+fn chrome_user_agent(version: &str) -> String {
+    let major = version.split('.').next().unwrap_or(version);
+    format!(
+        "Mozilla/5.0 ({}) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/{}.0.0.0 Safari/537.36",
+        platform().0,
+        major
+    )
+}
+
+/// Hide the usual headless tells from `page`
+///
+/// `user_agent` (a device's, say) is kept; without one, a desktop Chrome user
+/// agent for the browser's version is used. Client hints are only set for
+/// desktop Chrome user agents.
+pub(crate) async fn apply(page: &Page, user_agent: Option<String>) -> chromiumoxide::Result<()> {
+    page.execute(AddScriptToEvaluateOnNewDocumentParams::new(SCRIPT))
+        .await?;
+
+    let product = page.execute(GetVersionParams::default()).await?.result.product;
+    let version = chrome_version(&product);
+    let major = version.split('.').next().unwrap_or(version);
+
+    let user_agent = user_agent.unwrap_or_else(|| chrome_user_agent(version));
+    let (_, hint_platform, navigator_platform) = platform();
+    let mut params = SetUserAgentOverrideParams::new(user_agent.clone());
+    params.accept_language = Some(ACCEPT_LANGUAGE.to_string());
+    if !user_agent.contains("Chrome/") || user_agent.contains("Mobile") {
+        page.execute(params).await?;
+        return Ok(());
+    }
+
+    let brand = |name: &str, version: &str| UserAgentBrandVersion::new(name, version);
+    let mut metadata = UserAgentMetadata::new(hint_platform, "", "x86", "", false);
+    metadata.brands = Some(vec![
+        brand("Not_A Brand", "8"),
+        brand("Chromium", major),
+        brand("Google Chrome", major),
+    ]);
+    metadata.full_version_list = Some(vec![
+        brand("Not_A Brand", "8.0.0.0"),
+        brand("Chromium", version),
+        brand("Google Chrome", version),
+    ]);
+    metadata.bitness = Some("64".to_string());
+
+    params.platform = Some(navigator_platform.to_string());
+    params.user_agent_metadata = Some(metadata);
+    page.execute(params).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chrome_user_agent() {
+        let version = chrome_version("HeadlessChrome/120.0.6099.109");
+        assert_eq!(version, "120.0.6099.109");
+
+        let user_agent = chrome_user_agent(version);
+        assert!(user_agent.contains(" Chrome/120.0.0.0 Safari/537.36"));
+        assert!(user_agent.contains(platform().0));
+        assert!(!user_agent.contains("Headless"));
+    }
+}
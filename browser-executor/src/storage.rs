@@ -0,0 +1,194 @@
+//! Cookies and web storage
+//!
+//! `GetCookies` returns cookies as [`BrowserCookie`]s, which `SetCookie`
+//! takes back, so a flow can save a signed-in session and restore it in a
+//! later run, or set a consent cookie instead of clicking through a banner.
+//! `ClearCookies` removes all of the browser's cookies, or a domain's.
+//!
+//! `GetLocalStorage` and `SetLocalStorage` read and write the target frame's
+//! `localStorage`, or with `session` its `sessionStorage`; both are scoped to
+//! the page's origin.
+
+use chromiumoxide::cdp::browser_protocol::network::{
+    Cookie, CookieParam, CookieSameSite, TimeSinceEpoch,
+};
+use serde::{Deserialize, Serialize};
+
+/// A cookie, as `GetCookies` returns and `SetCookie` takes it
+///
+/// ```json
+/// { "name": "consent", "value": "yes", "domain": ".example.com", "expires": 1767225600 }
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BrowserCookie {
+    pub name: String,
+    pub value: String,
+
+    /// `.example.com` includes subdomains; without one, `SetCookie` sets a
+    /// host-only cookie for the active page
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub domain: Option<String>,
+
+    #[serde(default = "default_path")]
+    pub path: String,
+
+    /// Expiry, in seconds since the Unix epoch; a session cookie without
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires: Option<f64>,
+
+    #[serde(default)]
+    pub http_only: bool,
+
+    #[serde(default)]
+    pub secure: bool,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub same_site: Option<SameSite>,
+}
+
+fn default_path() -> String {
+    "/".to_string()
+}
+
+/// A cookie's `SameSite` attribute
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SameSite {
+    Strict,
+    Lax,
+    None,
+}
+
+impl From<&Cookie> for BrowserCookie {
+    fn from(cookie: &Cookie) -> Self {
+        Self {
+            name: cookie.name.clone(),
+            value: cookie.value.clone(),
+            domain: Some(cookie.domain.clone()),
+            path: cookie.path.clone(),
+            expires: (!cookie.session).then_some(cookie.expires),
+            http_only: cookie.http_only,
+            secure: cookie.secure,
+            same_site: cookie.same_site.as_ref().map(|same_site| match same_site {
+                CookieSameSite::Strict => SameSite::Strict,
+                CookieSameSite::Lax => SameSite::Lax,
+                CookieSameSite::None => SameSite::None,
+            }),
+        }
+    }
+}
+
+impl BrowserCookie {
+    /// The CDP parameter setting this cookie; `page_url` scopes it when it
+    /// has no domain
+    pub(crate) fn param(&self, page_url: Option<String>) -> Result<CookieParam, String> {
+        let mut builder = CookieParam::builder()
+            .name(self.name.clone())
+            .value(self.value.clone())
+            .path(self.path.clone())
+            .secure(self.secure)
+            .http_only(self.http_only);
+        builder = match (&self.domain, page_url) {
+            (Some(domain), _) => builder.domain(domain.clone()),
+            (None, Some(url)) if url.starts_with("http") => builder.url(url),
+            (None, _) => {
+                return Err(format!(
+                    "Cookie {} needs a domain outside an http(s) page",
+                    self.name
+                ))
+            }
+        };
+        if let Some(expires) = self.expires {
+            builder = builder.expires(TimeSinceEpoch::new(expires));
+        }
+        if let Some(same_site) = self.same_site {
+            builder = builder.same_site(match same_site {
+                SameSite::Strict => CookieSameSite::Strict,
+                SameSite::Lax => CookieSameSite::Lax,
+                SameSite::None => CookieSameSite::None,
+            });
+        }
+        builder.build()
+    }
+}
+
+/// Whether a cookie for `cookie_domain` belongs to `domain` or a subdomain
+pub(crate) fn in_domain(cookie_domain: &str, domain: &str) -> bool {
+    let cookie_domain = cookie_domain.trim_start_matches('.').to_ascii_lowercase();
+    let domain = domain.trim_start_matches('.').to_ascii_lowercase();
+    cookie_domain == domain || cookie_domain.ends_with(&format!(".{}", domain))
+}
+
+/// Script evaluating to the storage's entries as an object, or with `key`
+/// to that entry's value (null if unset)
+pub(crate) fn get_script(key: Option<&str>, session: bool) -> String {
+    format!(
+        "(() => {{ const storage = {}; const key = {}; \
+         if (key !== null) return storage.getItem(key); \
+         const entries = {{}}; \
+         for (let i = 0; i < storage.length; i++) \
+           entries[storage.key(i)] = storage.getItem(storage.key(i)); \
+         return entries; }})()",
+        area(session),
+        serde_json::json!(key)
+    )
+}
+
+/// Script setting `key` to `value`, or removing it for `None`
+pub(crate) fn set_script(key: &str, value: Option<&str>, session: bool) -> String {
+    format!(
+        "(() => {{ const storage = {}; const value = {}; \
+         if (value === null) storage.removeItem({key}); else storage.setItem({key}, value); }})()",
+        area(session),
+        serde_json::json!(value),
+        key = serde_json::json!(key)
+    )
+}
+
+fn area(session: bool) -> &'static str {
+    if session {
+        "window.sessionStorage"
+    } else {
+        "window.localStorage"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_browser_cookie() {
+        let cookie: BrowserCookie =
+            serde_json::from_str(r#"{"name": "consent", "value": "yes", "same_site": "lax"}"#)
+                .unwrap();
+        assert_eq!(cookie.path, "/");
+        assert_eq!(cookie.same_site, Some(SameSite::Lax));
+        assert!(cookie.expires.is_none());
+
+        assert!(cookie
+            .param(Some("https://example.com/".to_string()))
+            .is_ok());
+        assert!(cookie.param(Some("about:blank".to_string())).is_err());
+    }
+
+    #[test]
+    fn test_in_domain() {
+        assert!(in_domain(".example.com", "example.com"));
+        assert!(in_domain("shop.example.com", "example.com"));
+        assert!(in_domain("example.com", ".Example.com"));
+        assert!(!in_domain("notexample.com", "example.com"));
+    }
+
+    #[test]
+    fn test_storage_scripts() {
+        let script = get_script(Some("cart"), true);
+        assert!(script.contains("window.sessionStorage"));
+        assert!(script.contains(r#"const key = "cart""#));
+        assert!(get_script(None, false).contains("const key = null"));
+
+        let script = set_script("it's", None, false);
+        assert!(script.contains("window.localStorage"));
+        assert!(script.contains(r#"removeItem("it's")"#));
+    }
+}
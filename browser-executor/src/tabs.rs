@@ -0,0 +1,470 @@
+//! Tabs of one browser, addressed by index or CDP target id
+//!
+//! `NewTab`, `SwitchTab`, `CloseTab` and `ListTabs` act on the set of open tabs
+//! rather than a single page. All other actions run on the active tab.
+//! Closing the active tab reactivates the one used before it, so "open the
+//! docs in a new tab and come back" is `NewTab` followed by `CloseTab`.
+//!
+//! Each tab also remembers the frame chosen with `SwitchFrame`, and has its
+//! own dialog watcher, console log, response capture and record of URL
+//! policy violations.
+//!
+//! Pages a tab opens itself (`window.open`, links with a target) are held
+//! at their start where the executor launched the browser, then prepared
+//! before the next action as `NewTab` prepares its page: they follow the
+//! URL policy and network rules and get their own watchers. One already on
+//! a URL the policy refuses is closed.
+//!
+//! When attached to a running browser (see [`remote`](crate::remote)), its
+//! existing tabs in scope are adopted alongside the executor's own, and left
+//! open when the executor lets go of the browser.
+
+use crate::actions::{ActionError, ActionExecutor, ActionOutput, ActionResult, BrowserAction};
+use crate::capture::Captures;
+use crate::console::ConsoleLog;
+use crate::dialog::Dialogs;
+use crate::download::Downloads;
+use crate::executor::{self, ExecutorConfig};
+use crate::frames::FrameTarget;
+use crate::network::Traffic;
+use crate::url_policy::Violations;
+use chromiumoxide::browser::Browser;
+use chromiumoxide::cdp::browser_protocol::target::{
+    EventTargetCreated, SetAutoAttachParams, TargetId,
+};
+use chromiumoxide::cdp::js_protocol::runtime::RunIfWaitingForDebuggerParams;
+use chromiumoxide::page::Page;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::warn;
+
+/// A tab, by position in [`ListTabs`](BrowserAction::ListTabs) order or by id
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum TabTarget {
+    Index(usize),
+    Id(String),
+}
+
+/// One entry of the `ListTabs` output
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TabInfo {
+    pub id: String,
+    pub index: usize,
+    pub url: Option<String>,
+    pub title: Option<String>,
+    pub active: bool,
+}
+
+/// The active tab, as non-tab actions see it
+#[derive(Clone)]
+pub(crate) struct ActiveTab {
+    pub(crate) page: Page,
+    /// Target frame, if not the main frame
+    pub(crate) frame: Option<String>,
+    pub(crate) dialogs: Dialogs,
+    pub(crate) console: ConsoleLog,
+    pub(crate) violations: Violations,
+    pub(crate) traffic: Traffic,
+    pub(crate) captures: Captures,
+    /// The browser's downloads
+    pub(crate) downloads: Downloads,
+}
+
+/// What watches a tab from its creation: its dialogs, console, URL policy
+/// violations, traffic and captured responses
+#[derive(Clone)]
+pub(crate) struct TabWatchers {
+    pub(crate) dialogs: Dialogs,
+    pub(crate) console: ConsoleLog,
+    pub(crate) violations: Violations,
+    pub(crate) traffic: Traffic,
+    pub(crate) captures: Captures,
+}
+
+/// Pages opened by other pages, with their openers, not yet prepared
+#[derive(Clone, Default)]
+pub(crate) struct Popups {
+    opened: Arc<Mutex<Vec<(TargetId, TargetId)>>>,
+}
+
+impl Popups {
+    /// Follow the pages opened in `browser` by other pages; unless attached
+    /// to a running browser, whose user's own tabs must not wait for the
+    /// executor, each is held at its start until prepared
+    pub(crate) async fn watch(
+        config: &ExecutorConfig,
+        browser: &Browser,
+    ) -> chromiumoxide::Result<Self> {
+        let mut created = browser.event_listener::<EventTargetCreated>().await?;
+        let mut auto_attach = SetAutoAttachParams::new(true, config.remote_debugging_url.is_none());
+        auto_attach.flatten = Some(true);
+        browser.execute(auto_attach).await?;
+
+        let popups = Self::default();
+        let opened = popups.opened.clone();
+        tokio::spawn(async move {
+            while let Some(event) = created.next().await {
+                let target = &event.target_info;
+                if let (true, Some(opener)) = (target.r#type == "page", &target.opener_id) {
+                    let popup = (target.target_id.clone(), opener.clone());
+                    opened.lock().unwrap_or_else(|e| e.into_inner()).push(popup);
+                }
+            }
+        });
+        Ok(popups)
+    }
+
+    fn take(&self) -> Vec<(TargetId, TargetId)> {
+        std::mem::take(&mut *self.opened.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+}
+
+/// Open tabs in creation order, with their activation history
+pub(crate) struct Tabs {
+    pages: Vec<(String, Page)>,
+    /// Tab ids, least recently active first; the last one is active
+    recent: Vec<String>,
+    /// Target frame by tab id, for tabs not targeting their main frame
+    frames: HashMap<String, String>,
+    watchers: HashMap<String, TabWatchers>,
+    /// Tabs the browser already had, which the executor did not open
+    adopted: HashSet<String>,
+    downloads: Downloads,
+    popups: Popups,
+    /// Bytes received by tabs since closed
+    closed_bytes: u64,
+}
+
+impl Tabs {
+    pub(crate) fn new(
+        page: Page,
+        watchers: TabWatchers,
+        downloads: Downloads,
+        popups: Popups,
+    ) -> Self {
+        let id = page.target_id().as_ref().to_string();
+        Self {
+            pages: vec![(id.clone(), page)],
+            recent: vec![id.clone()],
+            frames: HashMap::new(),
+            watchers: HashMap::from([(id, watchers)]),
+            adopted: HashSet::new(),
+            downloads,
+            popups,
+            closed_bytes: 0,
+        }
+    }
+
+    /// Add a tab the browser already had, behind the active one
+    pub(crate) fn adopt(&mut self, page: Page, watchers: TabWatchers) {
+        let id = page.target_id().as_ref().to_string();
+        self.recent.insert(0, id.clone());
+        self.watchers.insert(id.clone(), watchers);
+        self.adopted.insert(id.clone());
+        self.pages.push((id, page));
+    }
+
+    /// Prepare the pages the tabs opened since the last call and add them
+    /// behind the active tab; pages the user's own tabs opened are left be
+    pub(crate) async fn add_popups(&mut self, config: &ExecutorConfig, browser: &Browser) {
+        let opened = self.popups.take();
+        if opened.is_empty() {
+            return;
+        }
+        let pages = match browser.pages().await {
+            Ok(pages) => pages,
+            Err(e) => {
+                warn!("Failed to list the opened tabs: {}", e);
+                return;
+            }
+        };
+
+        for (id, opener) in opened {
+            if !self.watchers.contains_key(opener.as_ref()) {
+                continue;
+            }
+            // Gone if it closed itself already
+            let Some(page) = pages.iter().find(|page| *page.target_id() == id) else {
+                continue;
+            };
+            if let Err(e) = self.add_popup(config, page.clone()).await {
+                warn!("Failed to prepare opened tab {}: {}", id.as_ref(), e);
+            }
+        }
+    }
+
+    async fn add_popup(
+        &mut self,
+        config: &ExecutorConfig,
+        page: Page,
+    ) -> chromiumoxide::Result<()> {
+        let watchers = executor::prepare_page(config, &page).await?;
+        page.execute(RunIfWaitingForDebuggerParams::default())
+            .await?;
+
+        // Loaded before it was prepared, where it was not held
+        let url = page.url().await?.unwrap_or_default();
+        if let Err(reason) = config.url_policy.check(&url) {
+            warn!("Blocked by URL policy, closing opened tab: {}", reason);
+            return page.close().await;
+        }
+
+        let id = page.target_id().as_ref().to_string();
+        self.recent.insert(0, id.clone());
+        self.watchers.insert(id.clone(), watchers);
+        self.pages.push((id, page));
+        Ok(())
+    }
+
+    /// Close the tabs the executor opened, leaving adopted ones open
+    pub(crate) async fn close_opened(&self) {
+        for (id, page) in &self.pages {
+            if self.adopted.contains(id) {
+                continue;
+            }
+            if let Err(e) = page.clone().close().await {
+                warn!("Failed to close tab {}: {}", id, e);
+            }
+        }
+    }
+
+    /// The browser's downloads
+    pub(crate) fn downloads(&self) -> &Downloads {
+        &self.downloads
+    }
+
+    /// The active tab's page
+    pub(crate) fn current(&self) -> &Page {
+        let active = self.active_id();
+        self.pages
+            .iter()
+            .find(|(id, _)| id == active)
+            .map(|(_, page)| page)
+            .expect("the active tab is open")
+    }
+
+    /// The active tab's target frame, if not its main frame
+    pub(crate) fn current_frame(&self) -> Option<String> {
+        self.frames.get(self.active_id()).cloned()
+    }
+
+    /// The active tab's page, target frame and watchers
+    pub(crate) fn active(&self) -> ActiveTab {
+        let watchers = self.watchers[self.active_id()].clone();
+        ActiveTab {
+            page: self.current().clone(),
+            frame: self.current_frame(),
+            dialogs: watchers.dialogs,
+            console: watchers.console,
+            violations: watchers.violations,
+            traffic: watchers.traffic,
+            captures: watchers.captures,
+            downloads: self.downloads.clone(),
+        }
+    }
+
+    /// Bytes received by all tabs, open or closed
+    pub(crate) fn bytes_received(&self) -> u64 {
+        let open: u64 = self.watchers.values().map(|w| w.traffic.bytes()).sum();
+        self.closed_bytes + open
+    }
+
+    fn active_id(&self) -> &str {
+        self.recent.last().expect("at least one tab is open")
+    }
+
+    fn position(&self, target: &TabTarget) -> ActionResult<usize> {
+        match target {
+            TabTarget::Index(index) if *index < self.pages.len() => Ok(*index),
+            TabTarget::Id(id) => self
+                .pages
+                .iter()
+                .position(|(tab, _)| tab == id)
+                .ok_or_else(|| ActionError::TabNotFound(id.clone())),
+            TabTarget::Index(index) => Err(ActionError::TabNotFound(format!("#{}", index))),
+        }
+    }
+
+    fn activate(&mut self, id: &str) {
+        self.recent.retain(|tab| tab != id);
+        self.recent.push(id.to_string());
+    }
+
+    /// Run a tab action (see [`BrowserAction::is_tab_action`])
+    pub(crate) async fn apply(
+        &mut self,
+        config: &ExecutorConfig,
+        browser: &Browser,
+        action: BrowserAction,
+    ) -> ActionResult<ActionOutput> {
+        let start = std::time::Instant::now();
+
+        let data = match action {
+            BrowserAction::NewTab { url } => self.open(config, browser, url.as_deref()).await?,
+            BrowserAction::SwitchTab { index_or_id } => self.switch(&index_or_id).await?,
+            BrowserAction::CloseTab { index_or_id } => self.close(index_or_id.as_ref()).await?,
+            BrowserAction::ListTabs => serde_json::to_string(&self.list().await)
+                .map_err(|e| ActionError::ActionFailed(e.to_string()))?,
+            BrowserAction::SwitchFrame { selector_or_index } => {
+                self.switch_frame(config, &selector_or_index).await?
+            }
+            BrowserAction::SwitchToMainFrame => {
+                let active = self.active_id().to_string();
+                self.frames.remove(&active);
+                active
+            }
+            other => {
+                return Err(ActionError::ActionFailed(format!(
+                    "Not a tab action: {:?}",
+                    other
+                )))
+            }
+        };
+
+        Ok(ActionOutput::ok(Some(data), start.elapsed()))
+    }
+
+    /// Open a tab and make it active; returns its id
+    async fn open(
+        &mut self,
+        config: &ExecutorConfig,
+        browser: &Browser,
+        url: Option<&str>,
+    ) -> ActionResult<String> {
+        let page = browser
+            .new_page("about:blank")
+            .await
+            .map_err(|e| ActionError::BrowserError(e.to_string()))?;
+
+        // Before navigating, so the first request already follows the rules
+        let watchers = executor::prepare_page(config, &page)
+            .await
+            .map_err(|e| ActionError::BrowserError(e.to_string()))?;
+        if let Some(url) = url {
+            page.goto(url)
+                .await
+                .map_err(|e| match watchers.violations.take() {
+                    Some(reason) => ActionError::PolicyViolation(reason),
+                    None => ActionError::NavigationFailed(e.to_string()),
+                })?;
+        }
+
+        let id = page.target_id().as_ref().to_string();
+        self.pages.push((id.clone(), page));
+        self.watchers.insert(id.clone(), watchers);
+        self.activate(&id);
+        Ok(id)
+    }
+
+    /// Bring a tab to the front; returns its id
+    async fn switch(&mut self, target: &TabTarget) -> ActionResult<String> {
+        let (id, page) = &self.pages[self.position(target)?];
+        page.bring_to_front()
+            .await
+            .map_err(|e| ActionError::BrowserError(e.to_string()))?;
+
+        let id = id.clone();
+        self.activate(&id);
+        Ok(id)
+    }
+
+    /// Close a tab (the active one by default); returns the active tab's id
+    async fn close(&mut self, target: Option<&TabTarget>) -> ActionResult<String> {
+        let index = match target {
+            Some(target) => self.position(target)?,
+            None => self.position(&TabTarget::Id(self.active_id().to_string()))?,
+        };
+        if self.pages.len() == 1 {
+            return Err(ActionError::ActionFailed(
+                "Cannot close the last tab".to_string(),
+            ));
+        }
+
+        let (id, page) = self.pages.remove(index);
+        self.recent.retain(|tab| *tab != id);
+        self.frames.remove(&id);
+        if let Some(watchers) = self.watchers.remove(&id) {
+            self.closed_bytes += watchers.traffic.bytes();
+        }
+        page.close()
+            .await
+            .map_err(|e| ActionError::BrowserError(e.to_string()))?;
+
+        let active = self.active_id().to_string();
+        self.switch(&TabTarget::Id(active)).await
+    }
+
+    /// Target a child of the active tab's current frame; returns its id
+    async fn switch_frame(
+        &mut self,
+        config: &ExecutorConfig,
+        target: &FrameTarget,
+    ) -> ActionResult<String> {
+        let timeout = Duration::from_secs(config.default_timeout_secs);
+        let frame = ActionExecutor::new(self.current().clone(), timeout)
+            .with_frame(self.current_frame())
+            .child_frame(target)
+            .await?;
+
+        let active = self.active_id().to_string();
+        self.frames.insert(active, frame.clone());
+        Ok(frame)
+    }
+
+    async fn list(&self) -> Vec<TabInfo> {
+        let active = self.active_id();
+        let mut tabs = Vec::with_capacity(self.pages.len());
+
+        for (index, (id, page)) in self.pages.iter().enumerate() {
+            tabs.push(TabInfo {
+                id: id.clone(),
+                index,
+                url: page.url().await.ok().flatten(),
+                title: page.get_title().await.ok().flatten(),
+                active: id == active,
+            });
+        }
+
+        tabs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tab_actions_deserialize() {
+        let actions: Vec<BrowserAction> = serde_json::from_str(
+            r#"[
+                {"type": "new_tab", "url": "https://docs.rs"},
+                {"type": "switch_tab", "index_or_id": 0},
+                {"type": "switch_tab", "index_or_id": "A1B2"},
+                {"type": "close_tab"},
+                {"type": "list_tabs"}
+            ]"#,
+        )
+        .unwrap();
+
+        assert!(actions.iter().all(BrowserAction::is_tab_action));
+        assert!(matches!(
+            &actions[1],
+            BrowserAction::SwitchTab {
+                index_or_id: TabTarget::Index(0)
+            }
+        ));
+        assert!(matches!(
+            &actions[2],
+            BrowserAction::SwitchTab { index_or_id: TabTarget::Id(id) } if id == "A1B2"
+        ));
+        assert!(matches!(
+            &actions[3],
+            BrowserAction::CloseTab { index_or_id: None }
+        ));
+        assert!(!BrowserAction::Reload.is_tab_action());
+    }
+}
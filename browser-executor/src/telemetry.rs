@@ -0,0 +1,91 @@
+//! Tracing spans for actions and plans, exported with OpenTelemetry
+//!
+//! Every action runs in a `browser_action` span recording its type,
+//! selector, URL, `duration_ms`, `success` and, when it fails, `error.code`.
+//! A plan runs in a `plan` span, each of its steps in a `plan_step` span
+//! recording the step's `retries` and `outcome`, so its actions nest under
+//! it; `run_many` adds a `plan_batch` span around its plans.
+//!
+//! The spans go to whatever `tracing` subscriber is installed. With the
+//! `otel` feature, [`init_telemetry`] installs one exporting them over OTLP
+//! (to Jaeger, say). A caller passes its W3C `traceparent` to
+//! `PlanRunner::with_trace_parent`, or runs actions inside
+//! [`remote_span`], so the browser's spans join the caller's trace and a
+//! voice command can be followed end to end. Without the feature, spans
+//! only carry the caller's `trace_id` for log correlation.
+
+use crate::actions::{ActionOutput, BrowserAction};
+use crate::artifacts::action_name;
+use crate::executor::ExecutorError;
+use crate::failure;
+use std::time::Duration;
+use tracing::field::{self, Empty};
+use tracing::{info_span, Span};
+
+pub use aether_telemetry::{remote_span, TraceParent};
+
+/// Make `parent` the parent of `span`, which must not have been entered yet
+pub(crate) fn set_parent(span: &Span, parent: &TraceParent) {
+    aether_telemetry::set_parent(span, parent);
+}
+
+/// The span an action runs in
+pub(crate) fn action_span(action: &BrowserAction) -> Span {
+    let selector = failure::selector(action);
+    let url = failure::target_url(action);
+    info_span!(
+        "browser_action",
+        action = %action_name(action),
+        selector = selector.as_deref(),
+        url = url.as_deref(),
+        duration_ms = Empty,
+        success = Empty,
+        error.code = Empty,
+        otel.status_code = Empty,
+    )
+}
+
+/// Record an action's outcome on its span
+pub(crate) fn record(
+    span: &Span,
+    result: &Result<ActionOutput, ExecutorError>,
+    duration: Duration,
+) {
+    span.record("duration_ms", duration.as_millis() as u64);
+    let failure = match result {
+        Ok(output) => {
+            span.record("success", output.success);
+            output.failure.clone()
+        }
+        Err(e) => {
+            span.record("success", false);
+            Some(e.failure())
+        }
+    };
+    if let Some(failure) = failure {
+        span.record("error.code", field::display(failure.code));
+        span.record("otel.status_code", "ERROR");
+        if let Some(url) = &failure.url {
+            span.record("url", url.as_str());
+        }
+    }
+}
+
+/// Export spans over OTLP/gRPC to `endpoint` (e.g. `http://localhost:4317`)
+/// as `service_name`, and log to stderr
+///
+/// Call [`shutdown_telemetry`] before exiting to flush the last spans.
+#[cfg(feature = "otel")]
+pub fn init_telemetry(
+    service_name: &str,
+    endpoint: &str,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    aether_telemetry::init_with_endpoint(service_name, "browser_executor=info", Some(endpoint))?;
+    Ok(())
+}
+
+/// Export the spans not yet sent
+#[cfg(feature = "otel")]
+pub fn shutdown_telemetry() {
+    aether_telemetry::shutdown();
+}
@@ -0,0 +1,66 @@
+//! Upload path validation
+//!
+//! `BrowserAction::UploadFile` hands a local file to a page, so it could leak
+//! anything the process can read. Only regular files inside one of
+//! `ExecutorConfig::upload_roots` are accepted; with no roots configured,
+//! uploads are refused. Paths are canonicalized first, so `..` and symlinks
+//! cannot point outside a root.
+
+use crate::actions::{ActionError, ActionResult};
+use std::path::{Path, PathBuf};
+
+/// Resolve `path` to a file inside `roots`, or refuse it
+pub(crate) fn resolve(path: &str, roots: &[PathBuf]) -> ActionResult<PathBuf> {
+    let rejected = |reason: &str| ActionError::UploadRejected(format!("{}: {}", path, reason));
+
+    if roots.is_empty() {
+        return Err(rejected("no upload roots configured"));
+    }
+
+    let file = Path::new(path)
+        .canonicalize()
+        .map_err(|e| rejected(&e.to_string()))?;
+    if !file.is_file() {
+        return Err(rejected("not a file"));
+    }
+
+    let inside = roots
+        .iter()
+        .filter_map(|root| root.canonicalize().ok())
+        .any(|root| file.starts_with(root));
+    if !inside {
+        return Err(rejected("outside the upload roots"));
+    }
+
+    Ok(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("uploads");
+        std::fs::create_dir(&root).unwrap();
+        std::fs::write(root.join("cv.pdf"), "%PDF").unwrap();
+        std::fs::write(dir.path().join("secret.txt"), "s").unwrap();
+        let roots = vec![root.clone()];
+
+        let cv = root.join("cv.pdf");
+        assert_eq!(
+            resolve(&cv.to_string_lossy(), &roots).unwrap(),
+            cv.canonicalize().unwrap()
+        );
+
+        let escape = root.join("../secret.txt");
+        assert!(matches!(
+            resolve(&escape.to_string_lossy(), &roots),
+            Err(ActionError::UploadRejected(_))
+        ));
+        assert!(resolve(&root.to_string_lossy(), &roots).is_err());
+        assert!(resolve(&root.join("missing.pdf").to_string_lossy(), &roots).is_err());
+        assert!(resolve(&cv.to_string_lossy(), &[]).is_err());
+    }
+}
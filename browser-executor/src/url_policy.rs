@@ -0,0 +1,174 @@
+//! Where pages may go
+//!
+//! `ExecutorConfig::url_policy` limits the documents a session loads. The
+//! target of `Navigate`, `NewTab` and URL `Download` actions is checked before
+//! the action runs, and every document request (redirects, link clicks,
+//! scripted navigations, iframes) is checked again as the page makes it, so
+//! a redirect cannot lead outside the policy either. Blocked attempts are
+//! logged, and the action fails with `ActionError::PolicyViolation`.
+//!
+//! Unlike [`NetworkRules`](crate::NetworkRules), the policy leaves a page's
+//! images, scripts and API calls alone.
+
+use crate::actions::{ActionError, BrowserAction};
+use crate::network::{host, in_domains, wildcard_match};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use tracing::warn;
+
+/// Allow and deny patterns for document URLs
+///
+/// A pattern with `://` matches whole URLs, with `*` and `?` wildcards as in
+/// `NetworkRules::block_patterns`; any other pattern is a domain, matching
+/// its subdomains too (`*.example.com` matches subdomains only). Deny
+/// patterns win; with any allow patterns, a URL must match one of them.
+/// `about:blank` is always allowed.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UrlPolicy {
+    #[serde(default)]
+    pub allow: Vec<String>,
+
+    #[serde(default)]
+    pub deny: Vec<String>,
+}
+
+impl UrlPolicy {
+    /// Whether every URL is allowed
+    pub fn is_empty(&self) -> bool {
+        self.allow.is_empty() && self.deny.is_empty()
+    }
+
+    /// Whether documents may be loaded from `url`
+    pub fn allows(&self, url: &str) -> bool {
+        self.check(url).is_ok()
+    }
+
+    /// Why documents may not be loaded from `url`, if they may not
+    pub fn check(&self, url: &str) -> Result<(), String> {
+        if url == "about:blank" {
+            return Ok(());
+        }
+        if let Some(pattern) = self.deny.iter().find(|pattern| matches(pattern, url)) {
+            return Err(format!("{} is denied by {}", url, pattern));
+        }
+        if !self.allow.is_empty() && !self.allow.iter().any(|pattern| matches(pattern, url)) {
+            return Err(format!("{} is not allowed", url));
+        }
+        Ok(())
+    }
+
+    /// Check the URL an action is about to load, if it names one
+    pub(crate) fn check_action(&self, action: &BrowserAction) -> Result<(), ActionError> {
+        let url = match action {
+            BrowserAction::Navigate { url, .. } => url,
+            BrowserAction::NewTab { url: Some(url) } => url,
+            BrowserAction::Download {
+                url_or_click_selector,
+                ..
+            } if url_or_click_selector.starts_with("http://")
+                || url_or_click_selector.starts_with("https://") =>
+            {
+                url_or_click_selector
+            }
+            _ => return Ok(()),
+        };
+
+        self.check(url).map_err(|reason| {
+            warn!("Blocked by URL policy: {}", reason);
+            ActionError::PolicyViolation(reason)
+        })
+    }
+}
+
+/// Whether `url` matches `pattern`, a URL with wildcards or a domain
+pub(crate) fn matches(pattern: &str, url: &str) -> bool {
+    if pattern.contains("://") {
+        return wildcard_match(pattern, url);
+    }
+
+    let Some(host) = host(url) else {
+        return false;
+    };
+    let pattern = pattern.to_ascii_lowercase();
+    if pattern.contains(['*', '?']) {
+        wildcard_match(&pattern, &host)
+    } else {
+        in_domains(&host, &[pattern])
+    }
+}
+
+/// The last document request a tab's policy blocked, so the action that led
+/// to it fails with the reason rather than a bare network error
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Violations {
+    last: Arc<Mutex<Option<String>>>,
+}
+
+impl Violations {
+    pub(crate) fn record(&self, reason: String) {
+        *self.last.lock().unwrap_or_else(|e| e.into_inner()) = Some(reason);
+    }
+
+    pub(crate) fn take(&self) -> Option<String> {
+        self.last.lock().unwrap_or_else(|e| e.into_inner()).take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_url_policy() {
+        let policy: UrlPolicy = serde_json::from_str(
+            r#"{
+                "allow": ["example.com", "*.docs.rs", "https://github.com/rust-lang/*"],
+                "deny": ["admin.example.com", "*://*/logout*"]
+            }"#,
+        )
+        .unwrap();
+
+        assert!(policy.allows("https://example.com/"));
+        assert!(policy.allows("https://WWW.example.com/a"));
+        assert!(policy.allows("https://serde.docs.rs/"));
+        assert!(!policy.allows("https://docs.rs/"));
+        assert!(policy.allows("https://github.com/rust-lang/rust"));
+        assert!(!policy.allows("https://github.com/evil/rust"));
+        assert!(!policy.allows("https://example.com.evil.net/"));
+        assert!(!policy.allows("data:text/html,hi"));
+        assert!(policy.allows("about:blank"));
+
+        assert_eq!(
+            policy.check("https://admin.example.com/"),
+            Err("https://admin.example.com/ is denied by admin.example.com".to_string())
+        );
+        assert!(!policy.allows("https://example.com/logout?next=/"));
+
+        assert!(UrlPolicy::default().is_empty());
+        assert!(UrlPolicy::default().allows("data:text/html,hi"));
+    }
+
+    #[test]
+    fn test_check_action() {
+        let policy = UrlPolicy {
+            allow: vec!["example.com".to_string()],
+            deny: Vec::new(),
+        };
+
+        let navigate = BrowserAction::Navigate {
+            url: "https://evil.net/".to_string(),
+            wait_until: Default::default(),
+        };
+        assert!(matches!(
+            policy.check_action(&navigate),
+            Err(ActionError::PolicyViolation(_))
+        ));
+
+        let click_download = BrowserAction::Download {
+            url_or_click_selector: "#export".to_string(),
+            save_to: None,
+        };
+        assert!(policy.check_action(&click_download).is_ok());
+        assert!(policy.check_action(&BrowserAction::Reload).is_ok());
+    }
+}
@@ -0,0 +1,154 @@
+//! Waiting for page conditions
+//!
+//! `BrowserAction::WaitUntil` waits for a condition instead of a fixed
+//! sleep, failing with `ActionError::Timeout` when it does not hold in time.
+//! Element, text, URL and script conditions are checked every
+//! [`POLL_INTERVAL`] (element conditions in the target frame, as
+//! `Assert` checks them). `NetworkIdle` follows the tab's requests, counting
+//! those started after the wait began, and `DownloadComplete` the browser's
+//! downloads since the last action that collected them.
+//!
+//! Without `timeout_ms`, a wait has the default element timeout, the
+//! navigation timeout for `NetworkIdle`, and the download time limit for
+//! `DownloadComplete`.
+
+use crate::assertion::AssertCondition;
+use crate::selector::Selector;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// How often polled conditions are checked
+pub const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// What `WaitUntil` waits for
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum UntilCondition {
+    /// Exists with a non-empty box, and is not hidden by CSS
+    ElementVisible {
+        selector: Selector,
+        #[serde(default)]
+        pierce_shadow: bool,
+    },
+
+    /// Missing, or hidden
+    ElementHidden {
+        selector: Selector,
+        #[serde(default)]
+        pierce_shadow: bool,
+    },
+
+    /// `text` is in the rendered text of the element (the whole page by
+    /// default)
+    TextAppears {
+        text: String,
+        #[serde(default)]
+        selector: Option<Selector>,
+        #[serde(default)]
+        pierce_shadow: bool,
+    },
+
+    /// The tab's URL matches `pattern`, where `*` is any run of characters
+    /// and `?` one character
+    UrlMatches { pattern: String },
+
+    /// A JavaScript expression evaluates to a truthy value
+    ScriptTruthy { expression: String },
+
+    /// No request in flight for `idle_ms` (default
+    /// `ExecutorConfig::network_idle_ms`)
+    NetworkIdle {
+        #[serde(default)]
+        idle_ms: Option<u64>,
+    },
+
+    /// Downloads the page started have finished, at least one of them (JSON
+    /// array of `FinishedDownload`)
+    DownloadComplete,
+}
+
+impl UntilCondition {
+    /// The assertion checked for an element, text or URL condition, and
+    /// whether it must hold (rather than fail)
+    pub(crate) fn assertion(&self) -> Option<(AssertCondition, bool)> {
+        let assertion = match self {
+            UntilCondition::ElementVisible {
+                selector,
+                pierce_shadow,
+            }
+            | UntilCondition::ElementHidden {
+                selector,
+                pierce_shadow,
+            } => AssertCondition::ElementVisible {
+                selector: selector.clone(),
+                pierce_shadow: *pierce_shadow,
+            },
+            UntilCondition::TextAppears {
+                text,
+                selector,
+                pierce_shadow,
+            } => AssertCondition::TextContains {
+                selector: selector
+                    .clone()
+                    .unwrap_or_else(|| Selector::Css("body".to_string())),
+                text: text.clone(),
+                pierce_shadow: *pierce_shadow,
+            },
+            UntilCondition::UrlMatches { pattern } => AssertCondition::UrlMatches {
+                pattern: pattern.clone(),
+            },
+            _ => return None,
+        };
+        let holds = !matches!(self, UntilCondition::ElementHidden { .. });
+        Some((assertion, holds))
+    }
+
+    /// Script evaluating to whether a `ScriptTruthy` condition holds
+    pub(crate) fn script(&self) -> Option<String> {
+        match self {
+            UntilCondition::ScriptTruthy { expression } => {
+                Some(format!("(() => !!({}))()", expression))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_conditions() {
+        let conditions: Vec<UntilCondition> = serde_json::from_str(
+            r##"[
+                {"type": "element_hidden", "selector": "#spinner"},
+                {"type": "text_appears", "text": "Saved"},
+                {"type": "url_matches", "pattern": "*/done"},
+                {"type": "script_truthy", "expression": "window.ready"},
+                {"type": "network_idle"},
+                {"type": "download_complete"}
+            ]"##,
+        )
+        .unwrap();
+
+        let (hidden, holds) = conditions[0].assertion().unwrap();
+        assert!(matches!(hidden, AssertCondition::ElementVisible { .. }));
+        assert!(!holds);
+
+        let (text, holds) = conditions[1].assertion().unwrap();
+        assert!(holds);
+        assert_eq!(
+            text.selector().unwrap().0,
+            &Selector::Css("body".to_string())
+        );
+
+        assert!(conditions[2].assertion().is_some());
+        assert_eq!(
+            conditions[3].script().as_deref(),
+            Some("(() => !!(window.ready))()")
+        );
+        assert_eq!(conditions[4], UntilCondition::NetworkIdle { idle_ms: None });
+        assert!(conditions[5].assertion().is_none() && conditions[5].script().is_none());
+    }
+}
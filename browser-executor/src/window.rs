@@ -0,0 +1,151 @@
+//! Window and viewport control
+//!
+//! The launch viewport (`ExecutorConfig::viewport_width` and
+//! `viewport_height`) can be changed while a tab is open. `SetViewport`
+//! resizes the active tab's viewport through CDP
+//! `Emulation.setDeviceMetricsOverride`, as a desktop page, keeping the
+//! page's device pixel ratio unless `dpr` is given. `Maximize` and
+//! `Fullscreen` set the state of the tab's window with
+//! `Browser.setWindowBounds` and drop the viewport override, so the page
+//! fills the window. Each returns the [`ViewportSize`] in effect afterwards.
+//!
+//! A headless browser has no screen to fill: its window keeps its size, and
+//! `Maximize` and `Fullscreen` only drop the override.
+
+use crate::actions::{ActionError, ActionResult};
+use crate::emulation;
+use chromiumoxide::cdp::browser_protocol::browser::{
+    Bounds, GetWindowForTargetParams, SetWindowBoundsParams, WindowState,
+};
+use chromiumoxide::cdp::browser_protocol::emulation::ClearDeviceMetricsOverrideParams;
+use chromiumoxide::page::Page;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// The largest viewport side CDP accepts
+const MAX_SIDE: u32 = 10_000_000;
+
+/// Time for the window manager to resize the window
+const RESIZE_SETTLE: Duration = Duration::from_millis(250);
+
+/// A page's viewport, in CSS pixels
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ViewportSize {
+    pub width: u32,
+    pub height: u32,
+
+    /// Device pixel ratio
+    pub dpr: f64,
+}
+
+/// Give `page` a `width`x`height` viewport at `dpr`, or at its current ratio
+pub(crate) async fn set_viewport(
+    page: &Page,
+    width: u32,
+    height: u32,
+    dpr: Option<f64>,
+) -> ActionResult<ViewportSize> {
+    check(width, height, dpr).map_err(ActionError::ActionFailed)?;
+    let dpr = match dpr {
+        Some(dpr) => dpr,
+        None => size(page).await?.dpr,
+    };
+    page.execute(emulation::metrics(width, height, dpr, false))
+        .await
+        .map_err(|e| ActionError::BrowserError(e.to_string()))?;
+    size(page).await
+}
+
+/// Put the window of `page` in `state` and let the page fill it
+pub(crate) async fn set_window_state(
+    page: &Page,
+    state: WindowState,
+) -> ActionResult<ViewportSize> {
+    let failed = |e: chromiumoxide::error::CdpError| ActionError::BrowserError(e.to_string());
+    let window = page
+        .execute(
+            GetWindowForTargetParams::builder()
+                .target_id(page.target_id().clone())
+                .build(),
+        )
+        .await
+        .map_err(failed)?;
+
+    // A maximized, minimized or fullscreen window has to be made normal
+    // before it can change to another of those states
+    let current = window.result.bounds.window_state;
+    let mut params = SetWindowBoundsParams::new(
+        window.result.window_id,
+        Bounds::builder().window_state(WindowState::Normal).build(),
+    );
+    if current.is_some_and(|current| current != WindowState::Normal && current != state) {
+        page.execute(params.clone()).await.map_err(failed)?;
+    }
+    params.bounds = Bounds::builder().window_state(state).build();
+    page.execute(params).await.map_err(failed)?;
+
+    page.execute(ClearDeviceMetricsOverrideParams::default())
+        .await
+        .map_err(failed)?;
+    tokio::time::sleep(RESIZE_SETTLE).await;
+    size(page).await
+}
+
+/// The viewport `page` has now
+async fn size(page: &Page) -> ActionResult<ViewportSize> {
+    let (width, height, dpr): (u32, u32, f64) = page
+        .evaluate("[innerWidth, innerHeight, devicePixelRatio]")
+        .await
+        .map_err(|e| ActionError::BrowserError(e.to_string()))?
+        .into_value()
+        .map_err(|e| ActionError::BrowserError(e.to_string()))?;
+    Ok(ViewportSize { width, height, dpr })
+}
+
+/// Whether CDP takes a `width`x`height` viewport at `dpr`
+fn check(width: u32, height: u32, dpr: Option<f64>) -> Result<(), String> {
+    if width == 0 || height == 0 || width > MAX_SIDE || height > MAX_SIDE {
+        return Err(format!(
+            "Viewport {}x{} must be 1 to {} pixels on each side",
+            width, height, MAX_SIDE
+        ));
+    }
+    match dpr {
+        Some(dpr) if !(dpr.is_finite() && dpr > 0.0) => {
+            Err(format!("Device pixel ratio {} must be positive", dpr))
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actions::BrowserAction;
+
+    #[test]
+    fn test_check() {
+        assert!(check(1280, 720, None).is_ok());
+        assert!(check(393, 852, Some(3.0)).is_ok());
+        assert!(check(0, 720, None).is_err());
+        assert!(check(1280, 720, Some(0.0)).is_err());
+        assert!(check(1280, 720, Some(f64::NAN)).is_err());
+    }
+
+    #[test]
+    fn test_window_actions() {
+        let action: BrowserAction =
+            serde_json::from_str(r#"{"type": "set_viewport", "width": 1280, "height": 720}"#)
+                .unwrap();
+        assert!(matches!(
+            action,
+            BrowserAction::SetViewport {
+                width: 1280,
+                height: 720,
+                dpr: None
+            }
+        ));
+        let action: BrowserAction = serde_json::from_str(r#"{"type": "fullscreen"}"#).unwrap();
+        assert!(matches!(action, BrowserAction::Fullscreen));
+    }
+}
@@ -1,8 +1,12 @@
 //! Integration tests for browser executor
 
+use base64::Engine as _;
 use browser_executor::{
-    BrowserAction, BrowserExecutor, ExecutorConfig, ScreenshotFormat, ScreenshotOptions,
-    WaitCondition,
+    AccessibleNode, ArchiveResult, AssertCondition, BrowserAction, BrowserCookie, BrowserExecutor,
+    CapturedResponse, Credentials, DevicePreset, DialogPolicy, DownloadConfig, ErrorCode,
+    ExecutorConfig, FrameTarget, NetworkRules, ScreenshotFormat, ScreenshotOptions, ScrollResult,
+    Selector, StaticCredentials, TabInfo, TabScope, TabTarget, UntilCondition, UrlPolicy,
+    ViewportSize, WaitCondition,
 };
 use std::time::Duration;
 
@@ -56,7 +60,8 @@ async fn test_get_text() {
 
     // Get text
     let text_action = BrowserAction::GetText {
-        selector: "h1".to_string(),
+        selector: "h1".into(),
+        pierce_shadow: false,
     };
 
     let result = executor.execute(text_action).await;
@@ -84,7 +89,10 @@ async fn test_screenshot() {
     executor.execute(nav_action).await.unwrap();
 
     // Take screenshot
-    let screenshot_action = BrowserAction::Screenshot { full_page: false };
+    let screenshot_action = BrowserAction::Screenshot {
+        full_page: false,
+        stitch: false,
+    };
 
     let result = executor.execute(screenshot_action).await;
     assert!(result.is_ok());
@@ -99,6 +107,39 @@ async fn test_screenshot() {
     assert!(decoded.is_ok());
 }
 
+#[tokio::test]
+async fn test_screenshot_element() {
+    let executor = BrowserExecutor::new(ExecutorConfig::default())
+        .await
+        .unwrap();
+
+    // Below the fold, and taller than the viewport
+    let nav_action = BrowserAction::Navigate {
+        url: "data:text/html,<body style=margin:0><div style=height:3000px></div>\
+              <div id=chart style=width:300px;height:1500px;background:red></div>"
+            .to_string(),
+        wait_until: WaitCondition::Load,
+    };
+    executor.execute(nav_action).await.unwrap();
+
+    let output = executor
+        .execute(BrowserAction::ScreenshotElement {
+            selector: "#chart".into(),
+            format: ScreenshotFormat::Png,
+            quality: None,
+            pierce_shadow: false,
+        })
+        .await
+        .unwrap();
+    let png = base64::engine::general_purpose::STANDARD
+        .decode(output.data.unwrap())
+        .unwrap();
+    let image = image::load_from_memory(&png).unwrap().to_rgb8();
+
+    assert_eq!(image.dimensions(), (300, 1500));
+    assert_eq!(image.get_pixel(150, 1400).0, [255, 0, 0]);
+}
+
 #[tokio::test]
 async fn test_executor_stats() {
     let config = ExecutorConfig {
@@ -140,12 +181,16 @@ async fn test_multiple_actions() {
 
     // Get text
     let text_action = BrowserAction::GetText {
-        selector: "h1".to_string(),
+        selector: "h1".into(),
+        pierce_shadow: false,
     };
     executor.execute(text_action).await.unwrap();
 
     // Screenshot
-    let screenshot_action = BrowserAction::Screenshot { full_page: false };
+    let screenshot_action = BrowserAction::Screenshot {
+        full_page: false,
+        stitch: false,
+    };
     executor.execute(screenshot_action).await.unwrap();
 
     // Verify stats
@@ -175,3 +220,1202 @@ async fn test_get_current_url() {
     assert!(current_url.is_some());
     assert!(current_url.unwrap().contains("example.com"));
 }
+
+#[tokio::test]
+async fn test_network_rules_block_requests() {
+    let config = ExecutorConfig {
+        headless: true,
+        network_rules: NetworkRules {
+            block_patterns: vec!["*://*.iana.org/*".to_string()],
+            allowed_domains: Some(vec!["example.com".to_string()]),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    let executor = BrowserExecutor::new(config).await.unwrap();
+
+    // Allowed domain loads
+    let nav_action = BrowserAction::Navigate {
+        url: "https://example.com".to_string(),
+        wait_until: WaitCondition::Load,
+    };
+    assert!(executor.execute(nav_action).await.is_ok());
+
+    // Blocked pattern and domains outside the allowlist fail
+    for url in ["https://www.iana.org/domains", "https://example.org"] {
+        let nav_action = BrowserAction::Navigate {
+            url: url.to_string(),
+            wait_until: WaitCondition::Load,
+        };
+        assert!(executor.execute(nav_action).await.is_err(), "{}", url);
+    }
+}
+
+#[tokio::test]
+async fn test_fill_form() {
+    let config = ExecutorConfig {
+        headless: true,
+        ..Default::default()
+    };
+
+    let executor = BrowserExecutor::new(config).await.unwrap();
+
+    let nav_action = BrowserAction::Navigate {
+        url: "data:text/html,<input id=name><select id=c><option value=fr>France</option>\
+              </select><input id=t type=checkbox><input id=d type=date>"
+            .to_string(),
+        wait_until: WaitCondition::Load,
+    };
+    executor.execute(nav_action).await.unwrap();
+
+    // One bad selector fails alone; the other fields are still filled
+    let fill_action: BrowserAction = serde_json::from_str(
+        r##"{"type": "fill_form", "fields": [
+            {"selector": "#name", "kind": "text", "value": "Ada"},
+            {"selector": "#c", "kind": "select", "value": "France", "by_label": true},
+            {"selector": "#t", "kind": "checkbox", "checked": true},
+            {"selector": "#d", "kind": "date", "value": "1815-12-10"},
+            {"selector": "#missing", "kind": "text", "value": "x"}
+        ]}"##,
+    )
+    .unwrap();
+    let output = executor.execute(fill_action).await.unwrap();
+    assert!(!output.success);
+    assert_eq!(output.error.as_deref(), Some("1 of 5 fields failed"));
+
+    let script_action = BrowserAction::ExecuteScript {
+        script: "[name.value, c.value, t.checked, d.value].join()".to_string(),
+    };
+    let values = executor.execute(script_action).await.unwrap();
+    assert_eq!(values.data.unwrap(), r#""Ada,fr,true,1815-12-10""#);
+}
+
+#[tokio::test]
+async fn test_select_hover_and_drag() {
+    let config = ExecutorConfig {
+        headless: true,
+        ..Default::default()
+    };
+
+    let executor = BrowserExecutor::new(config).await.unwrap();
+
+    // Records the hovered element and where the mouse went down and up
+    let nav_action = BrowserAction::Navigate {
+        url: "data:text/html,<select id=s><option value=a>A</option><option value=b>B</option>\
+              </select><div id=menu style='height:50px'>menu</div>\
+              <div id=src style='height:50px'>src</div><div id=dst style='height:50px'>dst</div>\
+              <script>log=[];menu.onmouseover=()=>log.push('hover');\
+              onmousedown=e=>log.push('down:'+e.target.id);\
+              onmouseup=e=>log.push('up:'+e.target.id)</script>"
+            .to_string(),
+        wait_until: WaitCondition::Load,
+    };
+    executor.execute(nav_action).await.unwrap();
+
+    let actions = vec![
+        BrowserAction::SelectOption {
+            selector: "#s".into(),
+            value: "b".to_string(),
+            pierce_shadow: false,
+        },
+        BrowserAction::Hover {
+            selector: "#menu".into(),
+            pierce_shadow: false,
+        },
+        BrowserAction::DragAndDrop {
+            source: "#src".into(),
+            target: "#dst".into(),
+            pierce_shadow: false,
+        },
+    ];
+    for action in actions {
+        executor.execute(action).await.unwrap();
+    }
+
+    let missing_option = BrowserAction::SelectOption {
+        selector: "#s".into(),
+        value: "z".to_string(),
+        pierce_shadow: false,
+    };
+    assert!(executor.execute(missing_option).await.is_err());
+
+    let script_action = BrowserAction::ExecuteScript {
+        script: "s.value + ',' + log.join()".to_string(),
+    };
+    let values = executor.execute(script_action).await.unwrap();
+    assert_eq!(values.data.unwrap(), r#""b,hover,down:src,up:dst""#);
+}
+
+#[tokio::test]
+async fn test_xpath_text_and_aria_selectors() {
+    let config = ExecutorConfig {
+        headless: true,
+        ..Default::default()
+    };
+
+    let executor = BrowserExecutor::new(config).await.unwrap();
+
+    let nav_action = BrowserAction::Navigate {
+        url:
+            "data:text/html,<div><button onclick='this.textContent=\"Sent\"'>Submit order</button>\
+              <a aria-label='Close dialog' href='%23closed'>x</a></div>"
+                .to_string(),
+        wait_until: WaitCondition::Load,
+    };
+    executor.execute(nav_action).await.unwrap();
+
+    let click_action: BrowserAction = serde_json::from_str(
+        r#"{"type": "click", "selector": {"text": "Submit"}, "wait_for": null}"#,
+    )
+    .unwrap();
+    executor.execute(click_action).await.unwrap();
+
+    let text_action = BrowserAction::GetText {
+        selector: Selector::XPath {
+            xpath: "//div/button".to_string(),
+        },
+        pierce_shadow: false,
+    };
+    let text = executor.execute(text_action).await.unwrap();
+    assert_eq!(text.data.unwrap(), "Sent");
+
+    let attribute_action = BrowserAction::GetAttribute {
+        selector: Selector::AriaLabel {
+            aria_label: "Close dialog".to_string(),
+        },
+        attribute: "href".to_string(),
+        pierce_shadow: false,
+    };
+    let href = executor.execute(attribute_action).await.unwrap();
+    assert_eq!(href.data.unwrap(), "#closed");
+}
+
+#[tokio::test]
+async fn test_pierce_shadow() {
+    let config = ExecutorConfig {
+        headless: true,
+        ..Default::default()
+    };
+
+    let executor = BrowserExecutor::new(config).await.unwrap();
+
+    // A button and a select inside nested open shadow roots
+    let nav_action = BrowserAction::Navigate {
+        url: "data:text/html,<div id=outer></div><script>\
+              const inner = outer.attachShadow({mode: 'open'});\
+              inner.innerHTML = '<div id=host></div>';\
+              const deep = inner.querySelector('%23host').attachShadow({mode: 'open'});\
+              deep.innerHTML = '<button id=buy onclick=\"this.textContent=1\">Buy</button>\
+              <select id=qty><option>1</option><option>2</option></select>';\
+              </script>"
+            .to_string(),
+        wait_until: WaitCondition::Load,
+    };
+    executor.execute(nav_action).await.unwrap();
+
+    let no_pierce = BrowserAction::Click {
+        selector: "#buy".into(),
+        wait_for: Some(std::time::Duration::from_millis(300)),
+        pierce_shadow: false,
+    };
+    assert!(executor.execute(no_pierce).await.is_err());
+
+    let actions = vec![
+        BrowserAction::Click {
+            selector: "#buy".into(),
+            wait_for: None,
+            pierce_shadow: true,
+        },
+        BrowserAction::SelectOption {
+            selector: Selector::XPath {
+                xpath: "//select".to_string(),
+            },
+            value: "2".to_string(),
+            pierce_shadow: true,
+        },
+    ];
+    for action in actions {
+        executor.execute(action).await.unwrap();
+    }
+
+    let text_action = BrowserAction::GetText {
+        selector: Selector::Text {
+            text: "1".to_string(),
+            exact: true,
+        },
+        pierce_shadow: true,
+    };
+    let text = executor.execute(text_action).await.unwrap();
+    assert_eq!(text.data.unwrap(), "1");
+}
+
+#[tokio::test]
+async fn test_switch_frame() {
+    let config = ExecutorConfig {
+        headless: true,
+        ..Default::default()
+    };
+
+    let executor = BrowserExecutor::new(config).await.unwrap();
+
+    // A login form in an iframe, with a nested iframe inside it
+    let nav_action = BrowserAction::Navigate {
+        url: "data:text/html,<h1>Main</h1><iframe id=login srcdoc=\"<h1>Login</h1>\
+              <input id=user><button onclick='this.textContent=user.value'>Go</button>\
+              <iframe srcdoc='<h1>Nested</h1>'></iframe>\"></iframe>"
+            .to_string(),
+        wait_until: WaitCondition::Load,
+    };
+    executor.execute(nav_action).await.unwrap();
+
+    let get_heading = || BrowserAction::GetText {
+        selector: "h1".into(),
+        pierce_shadow: false,
+    };
+
+    let switch_action: BrowserAction =
+        serde_json::from_str(r##"{"type": "switch_frame", "selector_or_index": "#login"}"##)
+            .unwrap();
+    executor.execute(switch_action).await.unwrap();
+
+    let actions = vec![
+        BrowserAction::Type {
+            selector: "#user".into(),
+            text: "ada".to_string(),
+            clear_first: false,
+            pierce_shadow: false,
+        },
+        BrowserAction::Click {
+            selector: Selector::Text {
+                text: "Go".to_string(),
+                exact: true,
+            },
+            wait_for: None,
+            pierce_shadow: false,
+        },
+    ];
+    for action in actions {
+        executor.execute(action).await.unwrap();
+    }
+
+    let button_action = BrowserAction::GetText {
+        selector: "button".into(),
+        pierce_shadow: false,
+    };
+    let button = executor.execute(button_action).await.unwrap();
+    assert_eq!(button.data.unwrap(), "ada");
+
+    let heading = executor.execute(get_heading()).await.unwrap();
+    assert_eq!(heading.data.unwrap(), "Login");
+
+    let nested_action = BrowserAction::SwitchFrame {
+        selector_or_index: FrameTarget::Index(0),
+    };
+    executor.execute(nested_action).await.unwrap();
+    let heading = executor.execute(get_heading()).await.unwrap();
+    assert_eq!(heading.data.unwrap(), "Nested");
+
+    executor
+        .execute(BrowserAction::SwitchToMainFrame)
+        .await
+        .unwrap();
+    let heading = executor.execute(get_heading()).await.unwrap();
+    assert_eq!(heading.data.unwrap(), "Main");
+}
+
+#[tokio::test]
+async fn test_dialog_policies() {
+    // Dismissed by default: confirm() returns false instead of hanging
+    let executor = BrowserExecutor::new(ExecutorConfig::default())
+        .await
+        .unwrap();
+    let confirm_action = BrowserAction::ExecuteScript {
+        script: "confirm('Delete?')".to_string(),
+    };
+    let answer = executor.execute(confirm_action.clone()).await.unwrap();
+    assert_eq!(answer.data.unwrap(), "false");
+
+    let config = ExecutorConfig {
+        dialog_policy: DialogPolicy::Queue,
+        ..Default::default()
+    };
+    let executor = BrowserExecutor::new(config).await.unwrap();
+
+    let nav_action = BrowserAction::Navigate {
+        url: "data:text/html,<button onclick=\"answer=prompt('Name?','guest')\">Ask</button>"
+            .to_string(),
+        wait_until: WaitCondition::Load,
+    };
+    executor.execute(nav_action).await.unwrap();
+
+    let click_action = BrowserAction::Click {
+        selector: "button".into(),
+        wait_for: None,
+        pierce_shadow: false,
+    };
+    let blocked = executor.execute(click_action).await.unwrap_err();
+    assert!(blocked
+        .to_string()
+        .contains("Blocked by dialog: prompt \"Name?\""));
+    let failure = blocked.failure();
+    assert_eq!(failure.code, ErrorCode::DialogUnhandled);
+    assert_eq!(failure.selector.as_deref(), Some("button"));
+
+    let handle_action = BrowserAction::HandleDialog {
+        accept: true,
+        prompt_text: Some("ada".to_string()),
+    };
+    let dialog = executor.execute(handle_action).await.unwrap();
+    assert!(dialog.data.unwrap().contains(r#""default_prompt":"guest""#));
+
+    let script_action = BrowserAction::ExecuteScript {
+        script: "answer".to_string(),
+    };
+    let answer = executor.execute(script_action).await.unwrap();
+    assert_eq!(answer.data.unwrap(), r#""ada""#);
+}
+
+#[tokio::test]
+async fn test_extract() {
+    let executor = BrowserExecutor::new(ExecutorConfig::default())
+        .await
+        .unwrap();
+
+    let nav_action = BrowserAction::Navigate {
+        url: "data:text/html,<h1>Lamps</h1>\
+              <div class=result><h3>Desk lamp</h3><span class=price>$1,299.50</span>\
+              <a href=https://shop.example/desk>view</a></div>\
+              <div class=result><h3>Floor lamp</h3><span class=price>$89</span></div>"
+            .to_string(),
+        wait_until: WaitCondition::Load,
+    };
+    executor.execute(nav_action).await.unwrap();
+
+    let extract_action: BrowserAction = serde_json::from_str(
+        r#"{"type": "extract", "schema": {
+            "heading": {"selector": "h1"},
+            "results": {"selector": ".result", "list": true, "fields": {
+                "title": {"selector": "h3"},
+                "price": {"selector": ".price", "type": "number"},
+                "link": {"selector": "a", "attribute": "href"}
+            }}
+        }}"#,
+    )
+    .unwrap();
+    let output = executor.execute(extract_action).await.unwrap();
+
+    let data: serde_json::Value = serde_json::from_str(&output.data.unwrap()).unwrap();
+    assert_eq!(
+        data,
+        serde_json::json!({
+            "heading": "Lamps",
+            "results": [
+                {"title": "Desk lamp", "price": 1299.5, "link": "https://shop.example/desk"},
+                {"title": "Floor lamp", "price": 89.0, "link": null}
+            ]
+        })
+    );
+}
+
+#[tokio::test]
+async fn test_accessibility_snapshot_and_click_node() {
+    let executor = BrowserExecutor::new(ExecutorConfig::default())
+        .await
+        .unwrap();
+
+    let nav_action = BrowserAction::Navigate {
+        url: "data:text/html,<h1>Shop</h1><a href=%23desk>Desk lamp</a>\
+              <button onclick='this.textContent=\"Added\"'>Add to cart</button>\
+              <button style=display:none>Hidden</button>"
+            .to_string(),
+        wait_until: WaitCondition::Load,
+    };
+    executor.execute(nav_action).await.unwrap();
+
+    let snapshot = executor
+        .execute(BrowserAction::AccessibilitySnapshot)
+        .await
+        .unwrap();
+    let nodes: Vec<AccessibleNode> = serde_json::from_str(&snapshot.data.unwrap()).unwrap();
+    let names: Vec<_> = nodes.iter().map(|node| node.name.as_str()).collect();
+    assert_eq!(names, vec!["Desk lamp", "Add to cart"]);
+    assert!(nodes.iter().all(|node| node.bounds.is_some()));
+
+    let click_action = BrowserAction::ClickNode {
+        node_id: nodes[1].id,
+    };
+    executor.execute(click_action).await.unwrap();
+
+    let text_action = BrowserAction::GetText {
+        selector: "button".into(),
+        pierce_shadow: false,
+    };
+    let text = executor.execute(text_action).await.unwrap();
+    assert_eq!(text.data.unwrap(), "Added");
+}
+
+#[tokio::test]
+async fn test_save_page_archive() {
+    let dir = std::env::temp_dir().join(format!("browser-executor-archive-{}", std::process::id()));
+    let config = ExecutorConfig {
+        downloads: DownloadConfig {
+            dir: Some(dir.clone()),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let executor = BrowserExecutor::new(config).await.unwrap();
+
+    let nav_action = BrowserAction::Navigate {
+        url: "https://example.com".to_string(),
+        wait_until: WaitCondition::Load,
+    };
+    executor.execute(nav_action).await.unwrap();
+
+    let archive_action = BrowserAction::SavePageArchive {
+        path: "archives/".to_string(),
+    };
+    let output = executor.execute(archive_action).await.unwrap();
+    let archive: ArchiveResult = serde_json::from_str(&output.data.unwrap()).unwrap();
+    assert!(archive.path.ends_with("archives/example.com.mhtml"));
+
+    let mhtml = std::fs::read_to_string(&archive.path).unwrap();
+    assert_eq!(mhtml.len() as u64, archive.size_bytes);
+    assert!(mhtml.contains("Example Domain"));
+
+    let escape_action = BrowserAction::SavePageArchive {
+        path: "../outside.mhtml".to_string(),
+    };
+    assert!(executor.execute(escape_action).await.is_err());
+
+    let _ = std::fs::remove_dir_all(dir);
+}
+
+#[tokio::test]
+async fn test_emulate_device() {
+    let config = ExecutorConfig {
+        device: Some(DevicePreset::Pixel.into()),
+        ..Default::default()
+    };
+    let executor = BrowserExecutor::new(config).await.unwrap();
+
+    let nav_action = BrowserAction::Navigate {
+        url: "data:text/html,<meta name=viewport content='width=device-width'>".to_string(),
+        wait_until: WaitCondition::Load,
+    };
+    executor.execute(nav_action.clone()).await.unwrap();
+
+    let script_action = BrowserAction::ExecuteScript {
+        script: "[innerWidth, devicePixelRatio, navigator.maxTouchPoints > 0, \
+                 navigator.userAgent.includes('Android')].join()"
+            .to_string(),
+    };
+    let pixel = executor.execute(script_action.clone()).await.unwrap();
+    assert_eq!(pixel.data.unwrap(), r#""412,2.625,true,true""#);
+
+    let emulate_action = BrowserAction::EmulateDevice {
+        device: DevicePreset::Desktop1080p.into(),
+    };
+    executor.execute(emulate_action).await.unwrap();
+    executor.execute(nav_action).await.unwrap();
+
+    let desktop = executor.execute(script_action).await.unwrap();
+    assert_eq!(desktop.data.unwrap(), r#""1920,1,false,false""#);
+}
+
+#[tokio::test]
+async fn test_set_viewport() {
+    let executor = BrowserExecutor::new(ExecutorConfig::default())
+        .await
+        .unwrap();
+    let nav_action = BrowserAction::Navigate {
+        url: "data:text/html,<h1>Viewport</h1>".to_string(),
+        wait_until: WaitCondition::Load,
+    };
+    executor.execute(nav_action).await.unwrap();
+
+    let viewport_action = BrowserAction::SetViewport {
+        width: 1280,
+        height: 720,
+        dpr: Some(2.0),
+    };
+    let output = executor.execute(viewport_action).await.unwrap();
+    let size: ViewportSize = serde_json::from_str(&output.data.unwrap()).unwrap();
+    assert_eq!(
+        size,
+        ViewportSize {
+            width: 1280,
+            height: 720,
+            dpr: 2.0
+        }
+    );
+
+    // The ratio is kept when not given
+    let viewport_action = BrowserAction::SetViewport {
+        width: 600,
+        height: 800,
+        dpr: None,
+    };
+    let output = executor.execute(viewport_action).await.unwrap();
+    let size: ViewportSize = serde_json::from_str(&output.data.unwrap()).unwrap();
+    assert_eq!((size.width, size.height, size.dpr), (600, 800, 2.0));
+
+    let invalid_action = BrowserAction::SetViewport {
+        width: 0,
+        height: 800,
+        dpr: None,
+    };
+    assert!(executor.execute(invalid_action).await.is_err());
+
+    // Headless, the window keeps its size and the viewport returns to it
+    let output = executor.execute(BrowserAction::Maximize).await.unwrap();
+    let size: ViewportSize = serde_json::from_str(&output.data.unwrap()).unwrap();
+    assert!(size.width > 0 && size.height > 0);
+    assert_eq!(size.dpr, 1.0);
+}
+
+/// Serve a page behind Basic auth for `alice:s3cret` on a local port
+async fn basic_auth_server() -> String {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        while let Ok((mut socket, _)) = listener.accept().await {
+            let mut request = vec![0; 4096];
+            let read = socket.read(&mut request).await.unwrap_or(0);
+            let request = String::from_utf8_lossy(&request[..read]);
+            let response = if request.contains("Authorization: Basic YWxpY2U6czNjcmV0") {
+                "HTTP/1.1 200 OK\r\nContent-Length: 7\r\n\r\nWelcome"
+            } else {
+                "HTTP/1.1 401 Unauthorized\r\nWWW-Authenticate: Basic realm=\"Staff\"\r\n\
+                 Content-Length: 0\r\n\r\n"
+            };
+            let _ = socket.write_all(response.as_bytes()).await;
+        }
+    });
+    format!("http://{}", address)
+}
+
+#[tokio::test]
+async fn test_http_auth() {
+    let origin = basic_auth_server().await;
+    let provider = StaticCredentials::new().with(&origin, Credentials::new("alice", "s3cret"));
+    let config = ExecutorConfig {
+        credentials: Some(std::sync::Arc::new(provider)),
+        ..Default::default()
+    };
+    let executor = BrowserExecutor::new(config).await.unwrap();
+
+    let nav_action = BrowserAction::Navigate {
+        url: format!("{}/", origin),
+        wait_until: WaitCondition::Load,
+    };
+    executor.execute(nav_action).await.unwrap();
+
+    let text_action = BrowserAction::GetText {
+        selector: "body".into(),
+        pierce_shadow: false,
+    };
+    let text = executor.execute(text_action).await.unwrap();
+    assert_eq!(text.data.unwrap(), "Welcome");
+
+    let anonymous = BrowserExecutor::new(ExecutorConfig {
+        credentials: Some(std::sync::Arc::new(StaticCredentials::new())),
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    let nav_action = BrowserAction::Navigate {
+        url: format!("{}/", origin),
+        wait_until: WaitCondition::Load,
+    };
+    assert!(anonymous.execute(nav_action).await.is_err());
+}
+
+#[tokio::test]
+async fn test_assert() {
+    let executor = BrowserExecutor::new(ExecutorConfig::default())
+        .await
+        .unwrap();
+
+    let nav_action = BrowserAction::Navigate {
+        url: "data:text/html,<div id=cart data-state=open>Cart: 2 items</div>\
+              <p id=note hidden>Saved</p>"
+            .to_string(),
+        wait_until: WaitCondition::Load,
+    };
+    executor.execute(nav_action).await.unwrap();
+
+    let holds: Vec<AssertCondition> = serde_json::from_str(
+        r##"[
+            {"type": "element_visible", "selector": "#cart"},
+            {"type": "element_exists", "selector": "#note"},
+            {"type": "text_contains", "selector": "#cart", "text": "2 items"},
+            {"type": "attribute_equals", "selector": "#cart", "attribute": "data-state",
+             "value": "open"},
+            {"type": "url_matches", "pattern": "data:text/html,*"}
+        ]"##,
+    )
+    .unwrap();
+    for condition in holds {
+        let output = executor
+            .execute(BrowserAction::Assert { condition })
+            .await
+            .unwrap();
+        assert!(output.success, "{:?}", output.error);
+    }
+
+    let hidden = AssertCondition::ElementVisible {
+        selector: "#note".into(),
+        pierce_shadow: false,
+    };
+    let output = executor
+        .execute(BrowserAction::Assert { condition: hidden })
+        .await
+        .unwrap();
+    assert!(!output.success);
+    assert_eq!(
+        output.error.as_deref(),
+        Some("Assertion failed: Expected element #note to be visible")
+    );
+    let screenshot = base64::engine::general_purpose::STANDARD
+        .decode(output.data.unwrap())
+        .unwrap();
+    assert!(screenshot.starts_with(b"\x89PNG"));
+}
+
+#[tokio::test]
+async fn test_stealth() {
+    let executor = BrowserExecutor::new(ExecutorConfig {
+        stealth: true,
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+
+    let nav_action = BrowserAction::Navigate {
+        url: "data:text/html,<title>Bot check</title>".to_string(),
+        wait_until: WaitCondition::Load,
+    };
+    executor.execute(nav_action).await.unwrap();
+
+    let output = executor
+        .execute(BrowserAction::ExecuteScript {
+            script: "[navigator.webdriver === undefined, navigator.userAgent, \
+                     navigator.plugins.length > 0, !!window.chrome, \
+                     navigator.userAgentData.brands.some(b => b.brand === 'Google Chrome')]"
+                .to_string(),
+        })
+        .await
+        .unwrap();
+    let (hidden, user_agent, plugins, chrome, brands): (bool, String, bool, bool, bool) =
+        serde_json::from_str(&output.data.unwrap()).unwrap();
+
+    assert!(hidden && plugins && chrome && brands);
+    assert!(user_agent.contains(" Chrome/"));
+    assert!(!user_agent.contains("Headless"));
+}
+
+#[tokio::test]
+async fn test_collect_console() {
+    let executor = BrowserExecutor::new(ExecutorConfig {
+        collect_console: true,
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+
+    let nav_action = BrowserAction::Navigate {
+        url: "data:text/html,<script>console.log('loaded')</script>".to_string(),
+        wait_until: WaitCondition::Load,
+    };
+    executor.execute(nav_action).await.unwrap();
+
+    let output = executor
+        .execute(BrowserAction::ExecuteScript {
+            script: "console.warn('low stock', 2); setTimeout(() => { throw new Error('boom') })"
+                .to_string(),
+        })
+        .await
+        .unwrap();
+    // Only what this action logged, never the page load's entries
+    assert!(output.console.iter().all(|e| e.message != "loaded"));
+
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    let logs = executor.get_console_logs().await;
+    let messages: Vec<_> = logs.iter().map(|e| e.message.as_str()).collect();
+    assert_eq!(messages[..2], ["loaded", "low stock 2"]);
+    assert_eq!((logs[1].source.as_str(), logs[1].level.as_str()), ("console", "warning"));
+    assert!(logs
+        .iter()
+        .any(|e| e.source == "exception" && e.message.contains("boom")));
+}
+
+/// Serve a page at `/` and a redirect to `target` anywhere else, on a local
+/// port
+async fn redirect_server(target: &'static str) -> String {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        while let Ok((mut socket, _)) = listener.accept().await {
+            let mut request = vec![0; 4096];
+            let read = socket.read(&mut request).await.unwrap_or(0);
+            let request = String::from_utf8_lossy(&request[..read]);
+            let response = if request.starts_with("GET / ") {
+                "HTTP/1.1 200 OK\r\nContent-Length: 4\r\n\r\nHome".to_string()
+            } else {
+                format!(
+                    "HTTP/1.1 302 Found\r\nLocation: {}\r\nContent-Length: 0\r\n\r\n",
+                    target
+                )
+            };
+            let _ = socket.write_all(response.as_bytes()).await;
+        }
+    });
+    format!("http://{}", address)
+}
+
+#[tokio::test]
+async fn test_url_policy() {
+    let origin = redirect_server("https://example.org/").await;
+    let executor = BrowserExecutor::new(ExecutorConfig {
+        url_policy: UrlPolicy {
+            allow: vec!["127.0.0.1".to_string()],
+            deny: Vec::new(),
+        },
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+
+    let nav_action = BrowserAction::Navigate {
+        url: format!("{}/", origin),
+        wait_until: WaitCondition::Load,
+    };
+    executor.execute(nav_action).await.unwrap();
+
+    // Refused before navigating, and after a redirect
+    for url in ["https://example.org/".to_string(), format!("{}/away", origin)] {
+        let nav_action = BrowserAction::Navigate {
+            url: url.clone(),
+            wait_until: WaitCondition::Load,
+        };
+        let error = executor.execute(nav_action).await.unwrap_err();
+        assert!(error.to_string().contains("URL policy violation"), "{}: {}", url, error);
+        assert_eq!(error.failure().code, ErrorCode::BlockedByPolicy);
+    }
+}
+
+#[tokio::test]
+async fn test_url_policy_in_opened_tabs() {
+    let origin = redirect_server("https://example.org/").await;
+    let executor = BrowserExecutor::new(ExecutorConfig {
+        url_policy: UrlPolicy {
+            allow: vec!["127.0.0.1".to_string()],
+            deny: Vec::new(),
+        },
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+
+    let nav_action = BrowserAction::Navigate {
+        url: format!("{}/", origin),
+        wait_until: WaitCondition::Load,
+    };
+    executor.execute(nav_action).await.unwrap();
+    let script_action = BrowserAction::ExecuteScript {
+        script: "document.body.innerHTML = '<a id=home target=_blank href=/>home</a>' + \
+            '<a id=away target=_blank href=/away>away</a>'"
+            .to_string(),
+    };
+    executor.execute(script_action).await.unwrap();
+
+    // Links opening tabs of their own, one redirected out of the policy
+    for selector in ["#home", "#away"] {
+        let click_action = BrowserAction::Click {
+            selector: selector.into(),
+            wait_for: None,
+            pierce_shadow: false,
+        };
+        executor.execute(click_action).await.unwrap();
+    }
+    tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+
+    let output = executor.execute(BrowserAction::ListTabs).await.unwrap();
+    let tabs: Vec<TabInfo> = serde_json::from_str(&output.data.unwrap()).unwrap();
+    let home = format!("{}/", origin);
+    assert_eq!(
+        tabs.iter()
+            .filter(|tab| tab.url.as_ref() == Some(&home))
+            .count(),
+        2
+    );
+    for tab in &tabs {
+        let switch_action = BrowserAction::SwitchTab {
+            index_or_id: TabTarget::Id(tab.id.clone()),
+        };
+        executor.execute(switch_action).await.unwrap();
+        let script_action = BrowserAction::ExecuteScript {
+            script: "document.body ? document.body.innerText : ''".to_string(),
+        };
+        let text = executor.execute(script_action).await.unwrap().data.unwrap();
+        assert!(!text.contains("Example Domain"), "{:?}: {}", tab.url, text);
+    }
+}
+
+#[tokio::test]
+async fn test_crash_recovery() {
+    let executor = BrowserExecutor::new(ExecutorConfig::default()).await.unwrap();
+    let url = "data:text/html,<title>Before</title>";
+    let nav_action = BrowserAction::Navigate {
+        url: url.to_string(),
+        wait_until: WaitCondition::Load,
+    };
+    executor.execute(nav_action).await.unwrap();
+
+    // Crash the renderer; the action fails fast instead of timing out
+    let crash_action = BrowserAction::Navigate {
+        url: "chrome://crash".to_string(),
+        wait_until: WaitCondition::Load,
+    };
+    let start = std::time::Instant::now();
+    assert!(executor.execute(crash_action).await.is_err());
+    assert!(start.elapsed() < std::time::Duration::from_secs(10));
+    assert!(!executor.is_healthy().await);
+
+    // The next action restarts the browser on the last good URL
+    let script_action = BrowserAction::ExecuteScript {
+        script: "document.title".to_string(),
+    };
+    let output = executor.execute(script_action).await.unwrap();
+    assert_eq!(output.data.unwrap(), r#""Before""#);
+    assert!(executor.is_healthy().await);
+    assert_eq!(executor.get_stats().await.restarts, 1);
+}
+
+#[tokio::test]
+async fn test_recover_after_crash() {
+    let executor = BrowserExecutor::new(ExecutorConfig::default()).await.unwrap();
+    let crash_action = BrowserAction::Navigate {
+        url: "chrome://crash".to_string(),
+        wait_until: WaitCondition::Load,
+    };
+    assert!(executor.execute(crash_action).await.is_err());
+    assert!(!executor.is_healthy().await);
+
+    // Relaunched without an action
+    executor.recover().await.unwrap();
+    assert!(executor.is_healthy().await);
+    assert_eq!(executor.get_stats().await.restarts, 1);
+
+    executor.recover().await.unwrap();
+    assert_eq!(executor.get_stats().await.restarts, 1);
+}
+
+#[tokio::test]
+async fn test_attach_to_running_browser() {
+    use chromiumoxide::browser::{Browser, BrowserConfig};
+    use futures::StreamExt;
+
+    // The user's browser, with a tab in scope and one out of it
+    let origin = redirect_server("https://example.org/").await;
+    let (mut browser, mut handler) = Browser::launch(BrowserConfig::builder().build().unwrap())
+        .await
+        .unwrap();
+    tokio::spawn(async move { while handler.next().await.is_some() {} });
+    let mail = browser.new_page(format!("{}/", origin)).await.unwrap();
+    browser
+        .new_page("data:text/plain,bank")
+        .await
+        .unwrap();
+
+    let config = ExecutorConfig {
+        remote_debugging_url: Some(browser.websocket_address().clone()),
+        remote_tabs: TabScope::Matching(vec!["127.0.0.1".to_string()]),
+        ..Default::default()
+    };
+    let executor = BrowserExecutor::new(config).await.unwrap();
+
+    let output = executor.execute(BrowserAction::ListTabs).await.unwrap();
+    let tabs: Vec<TabInfo> = serde_json::from_str(&output.data.unwrap()).unwrap();
+    assert_eq!(tabs.len(), 2);
+    assert!(tabs[0].active);
+    assert_eq!(tabs[1].id, mail.target_id().as_ref());
+
+    let switch_action = BrowserAction::SwitchTab {
+        index_or_id: TabTarget::Index(1),
+    };
+    executor.execute(switch_action).await.unwrap();
+    let script_action = BrowserAction::ExecuteScript {
+        script: "document.body.textContent".to_string(),
+    };
+    let output = executor.execute(script_action).await.unwrap();
+    assert_eq!(output.data.unwrap(), r#""Home""#);
+
+    // Only the executor's own tab is closed
+    executor.shutdown().await;
+    let content = mail.content().await.unwrap();
+    assert!(content.contains("Home"));
+    let _ = browser.close().await;
+}
+
+#[tokio::test]
+async fn test_scroll_to_end() {
+    let executor = BrowserExecutor::new(ExecutorConfig::default()).await.unwrap();
+
+    // A feed loading 10 more posts near the bottom, up to 50
+    let nav_action = BrowserAction::Navigate {
+        url: "data:text/html,<div id=feed></div><script>\
+              const more = () => { for (let i = 0; i < 10; i++) {\
+                const post = document.createElement('article');\
+                post.style.height = '400px'; feed.append(post); } };\
+              more();\
+              addEventListener('scroll', () => {\
+                if (feed.children.length < 50 &&\
+                    innerHeight + scrollY >= document.body.scrollHeight - 10)\
+                  setTimeout(more, 100); });\
+              </script>"
+            .to_string(),
+        wait_until: WaitCondition::Load,
+    };
+    executor.execute(nav_action).await.unwrap();
+
+    let scroll_action = BrowserAction::ScrollToEnd {
+        max_iterations: 10,
+        settle_ms: 500,
+        item_selector: Some("article".into()),
+        pierce_shadow: false,
+    };
+    let output = executor.execute(scroll_action).await.unwrap();
+    let result: ScrollResult = serde_json::from_str(&output.data.unwrap()).unwrap();
+
+    assert!(result.reached_end);
+    assert_eq!(result.items_loaded, 40);
+    assert_eq!(result.total_items, 50);
+    assert_eq!(result.iterations, 5);
+}
+
+#[tokio::test]
+async fn test_wait_until() {
+    let executor = BrowserExecutor::new(ExecutorConfig::default()).await.unwrap();
+
+    // A save that finishes 300ms after the click
+    let nav_action = BrowserAction::Navigate {
+        url: "data:text/html,<button onclick=\"spinner.hidden = false; setTimeout(() => {\
+              spinner.hidden = true; result.textContent = 'Saved'; window.saved = true;\
+              history.pushState(null, '', '#done'); }, 300)\">Save</button>\
+              <p id=spinner hidden>Saving</p><p id=result></p>"
+            .to_string(),
+        wait_until: WaitCondition::Load,
+    };
+    executor.execute(nav_action).await.unwrap();
+
+    let click_action = BrowserAction::Click {
+        selector: "button".into(),
+        wait_for: None,
+        pierce_shadow: false,
+    };
+    executor.execute(click_action).await.unwrap();
+
+    let conditions = [
+        UntilCondition::ElementVisible {
+            selector: "#spinner".into(),
+            pierce_shadow: false,
+        },
+        UntilCondition::ElementHidden {
+            selector: "#spinner".into(),
+            pierce_shadow: false,
+        },
+        UntilCondition::TextAppears {
+            text: "Saved".to_string(),
+            selector: None,
+            pierce_shadow: false,
+        },
+        UntilCondition::ScriptTruthy {
+            expression: "window.saved".to_string(),
+        },
+        UntilCondition::UrlMatches {
+            pattern: "*#done".to_string(),
+        },
+        UntilCondition::NetworkIdle { idle_ms: None },
+    ];
+    for condition in conditions {
+        let wait_action = BrowserAction::WaitUntil {
+            condition,
+            timeout_ms: Some(2000),
+        };
+        executor.execute(wait_action).await.unwrap();
+    }
+
+    let never_action = BrowserAction::WaitUntil {
+        condition: UntilCondition::TextAppears {
+            text: "Failed".to_string(),
+            selector: Some("#result".into()),
+            pierce_shadow: false,
+        },
+        timeout_ms: Some(300),
+    };
+    assert!(executor.execute(never_action).await.is_err());
+}
+
+#[tokio::test]
+async fn test_cookies_and_storage() {
+    let executor = BrowserExecutor::new(ExecutorConfig::default()).await.unwrap();
+
+    let nav_action = BrowserAction::Navigate {
+        url: "https://example.com".to_string(),
+        wait_until: WaitCondition::Load,
+    };
+    executor.execute(nav_action).await.unwrap();
+
+    let set_action = BrowserAction::SetCookie {
+        cookie: serde_json::from_str(r#"{"name": "consent", "value": "all"}"#).unwrap(),
+    };
+    executor.execute(set_action).await.unwrap();
+
+    let get_action = BrowserAction::GetCookies { urls: Vec::new() };
+    let output = executor.execute(get_action.clone()).await.unwrap();
+    let cookies: Vec<BrowserCookie> = serde_json::from_str(&output.data.unwrap()).unwrap();
+    assert_eq!(cookies.len(), 1);
+    assert_eq!(cookies[0].value, "all");
+    assert_eq!(cookies[0].domain.as_deref(), Some("example.com"));
+
+    let store_action = BrowserAction::SetLocalStorage {
+        key: "theme".to_string(),
+        value: Some("dark".to_string()),
+        session: false,
+    };
+    executor.execute(store_action).await.unwrap();
+
+    let read_action = BrowserAction::GetLocalStorage {
+        key: None,
+        session: false,
+    };
+    let output = executor.execute(read_action).await.unwrap();
+    assert_eq!(output.data.unwrap(), r#"{"theme":"dark"}"#);
+
+    let read_action = BrowserAction::GetLocalStorage {
+        key: Some("theme".to_string()),
+        session: true,
+    };
+    let output = executor.execute(read_action).await.unwrap();
+    assert_eq!(output.data.unwrap(), "null");
+
+    let clear_action = BrowserAction::ClearCookies {
+        domain: Some("example.com".to_string()),
+    };
+    let output = executor.execute(clear_action).await.unwrap();
+    assert_eq!(output.data.unwrap(), "1");
+
+    let output = executor.execute(get_action).await.unwrap();
+    assert_eq!(output.data.unwrap(), "[]");
+}
+
+#[tokio::test]
+async fn test_dismiss_consent() {
+    let config = ExecutorConfig {
+        dismiss_consent: true,
+        ..Default::default()
+    };
+    let executor = BrowserExecutor::new(config).await.unwrap();
+
+    // A consent manager's banner, and a dialog of the page's own
+    let nav_action = BrowserAction::Navigate {
+        url: "data:text/html,<div id=onetrust-consent-sdk><p>We use cookies.</p>\
+              <button onclick=\"document.title='accepted'\">Accept all</button>\
+              <button onclick=\"document.title='rejected';this.parentElement.remove()\">\
+              Alle ablehnen</button></div>\
+              <div class=privacy-settings><button>OK</button></div>"
+            .to_string(),
+        wait_until: WaitCondition::Load,
+    };
+    executor.execute(nav_action).await.unwrap();
+
+    let script_action = BrowserAction::ExecuteScript {
+        script: "document.title + ' ' + document.querySelectorAll('button').length".to_string(),
+    };
+    let output = executor.execute(script_action).await.unwrap();
+    assert_eq!(output.data.unwrap(), r#""rejected 1""#);
+
+    // Nothing left to dismiss: the other button is not a consent manager's
+    let output = executor.execute(BrowserAction::DismissConsent).await.unwrap();
+    assert_eq!(output.data, None);
+}
+
+#[tokio::test]
+async fn test_capture_responses() {
+    let origin = redirect_server("https://example.org/").await;
+    let executor = BrowserExecutor::new(ExecutorConfig::default()).await.unwrap();
+
+    let capture_action = BrowserAction::CaptureResponses {
+        url_pattern: format!("{}/", origin),
+        max_bytes: 3,
+    };
+    let output = executor.execute(capture_action.clone()).await.unwrap();
+    assert_eq!(output.data.unwrap(), "[]");
+
+    let nav_action = BrowserAction::Navigate {
+        url: format!("{}/", origin),
+        wait_until: WaitCondition::Load,
+    };
+    executor.execute(nav_action).await.unwrap();
+
+    let output = executor.execute(capture_action.clone()).await.unwrap();
+    let captured: Vec<CapturedResponse> = serde_json::from_str(&output.data.unwrap()).unwrap();
+    assert_eq!(captured.len(), 1);
+    assert_eq!(captured[0].status, 200);
+    assert_eq!(captured[0].body, "Hom");
+    assert!(captured[0].truncated);
+
+    let output = executor.execute(capture_action).await.unwrap();
+    assert_eq!(output.data.unwrap(), "[]");
+}
+
+#[tokio::test]
+async fn test_history_navigation() {
+    let executor = BrowserExecutor::new(ExecutorConfig::default())
+        .await
+        .unwrap();
+
+    let first = "data:text/html,<h1>First</h1>".to_string();
+    // A page that breaks the history API does not stop GoBack
+    let second = "data:text/html,<h1>Second</h1>\
+                  <script>history.back = history.forward = () => {};</script>"
+        .to_string();
+    for url in [&first, &second] {
+        let nav_action = BrowserAction::Navigate {
+            url: url.clone(),
+            wait_until: WaitCondition::Load,
+        };
+        executor.execute(nav_action).await.unwrap();
+    }
+
+    let back_action = BrowserAction::GoBack {
+        wait_until: WaitCondition::Load,
+    };
+    let output = executor.execute(back_action).await.unwrap();
+    assert_eq!(output.data.as_deref(), Some(first.as_str()));
+
+    let forward_action = BrowserAction::GoForward {
+        wait_until: WaitCondition::DomContentLoaded,
+    };
+    let output = executor.execute(forward_action.clone()).await.unwrap();
+    assert_eq!(output.data.as_deref(), Some(second.as_str()));
+
+    let text_action = BrowserAction::GetText {
+        selector: "h1".into(),
+        pierce_shadow: false,
+    };
+    let output = executor.execute(text_action).await.unwrap();
+    assert_eq!(output.data.unwrap(), "Second");
+
+    let error = executor.execute(forward_action).await.unwrap_err();
+    assert!(error
+        .to_string()
+        .contains("No history entry to go forward to"));
+}
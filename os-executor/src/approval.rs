@@ -0,0 +1,88 @@
+//! Confirmation workflow for risky commands
+//!
+//! Whitelist entries with `requires_confirmation` are only run after an
+//! [`ApprovalProvider`] approves them, e.g. by asking the user
+//! "Do you want me to delete these files?" and waiting for a spoken yes/no.
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+/// A command awaiting the user's approval
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApprovalRequest {
+    /// Command as requested
+    pub command: String,
+
+    /// Arguments as requested
+    pub args: Vec<String>,
+
+    /// Whitelist entry description, if any
+    pub description: Option<String>,
+}
+
+impl ApprovalRequest {
+    /// Question suitable for speaking to the user
+    pub fn prompt(&self) -> String {
+        let argv = std::iter::once(self.command.as_str())
+            .chain(self.args.iter().map(String::as_str))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        match &self.description {
+            Some(description) => format!(
+                "Do you want me to run {} ({})?",
+                argv,
+                description.to_lowercase()
+            ),
+            None => format!("Do you want me to run {}?", argv),
+        }
+    }
+}
+
+/// User's answer to an [`ApprovalRequest`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ApprovalDecision {
+    Approved,
+    Denied,
+}
+
+/// Asks the user to confirm a command before it runs
+///
+/// Implementations should deny on timeout or when no clear answer is given.
+#[async_trait]
+pub trait ApprovalProvider: Send + Sync {
+    async fn request_approval(&self, request: &ApprovalRequest) -> ApprovalDecision;
+}
+
+/// Provider that returns a fixed decision (for tests and non-interactive use)
+#[derive(Debug, Clone, Copy)]
+pub struct StaticApproval(pub ApprovalDecision);
+
+#[async_trait]
+impl ApprovalProvider for StaticApproval {
+    async fn request_approval(&self, _request: &ApprovalRequest) -> ApprovalDecision {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prompt() {
+        let mut request = ApprovalRequest {
+            command: "rm".to_string(),
+            args: vec!["a.txt".to_string(), "b.txt".to_string()],
+            description: None,
+        };
+        assert_eq!(request.prompt(), "Do you want me to run rm a.txt b.txt?");
+
+        request.description = Some("Delete files".to_string());
+        assert_eq!(
+            request.prompt(),
+            "Do you want me to run rm a.txt b.txt (delete files)?"
+        );
+    }
+}
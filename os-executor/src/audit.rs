@@ -0,0 +1,544 @@
+//! Audit log of executed commands
+//!
+//! Every command the executor runs (or refuses to run) is appended to a JSON
+//! lines file. Files rotate by size, and records can optionally be chained with
+//! HMAC-SHA256 so that edits or deletions inside the log are detectable.
+
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use thiserror::Error;
+use tracing::{debug, warn};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Hex characters kept from the output SHA-256 (128 bits)
+const OUTPUT_HASH_LEN: usize = 32;
+
+/// Audit log errors
+#[derive(Error, Debug)]
+pub enum AuditError {
+    #[error("Audit log I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Invalid audit record: {0}")]
+    Serialization(#[from] serde_json::Error),
+
+    #[error("HMAC chaining is not enabled for this log")]
+    ChainingDisabled,
+
+    #[error("Audit chain broken at record {0}")]
+    ChainBroken(usize),
+}
+
+/// Audit log configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditConfig {
+    /// Active log file; rotated files get `.1`, `.2`, ... suffixes (`.1` is newest)
+    pub path: PathBuf,
+
+    /// Rotate once the active file reaches this size (bytes)
+    #[serde(default = "default_max_file_bytes")]
+    pub max_file_bytes: u64,
+
+    /// Rotated files to keep
+    #[serde(default = "default_max_files")]
+    pub max_files: usize,
+
+    /// User recorded with each entry (defaults to `$USER` / `%USERNAME%`)
+    #[serde(default)]
+    pub user: Option<String>,
+
+    /// Key for HMAC chaining (None = no tamper evidence)
+    #[serde(default, skip_serializing)]
+    pub hmac_key: Option<Vec<u8>>,
+}
+
+fn default_max_file_bytes() -> u64 {
+    10 * 1024 * 1024 // 10MB
+}
+
+fn default_max_files() -> usize {
+    5
+}
+
+impl AuditConfig {
+    /// Create a config logging to `path` with default rotation and no chaining
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            max_file_bytes: default_max_file_bytes(),
+            max_files: default_max_files(),
+            user: None,
+            hmac_key: None,
+        }
+    }
+}
+
+/// What happened when a command was executed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// Command (or pipeline) as requested
+    pub command: String,
+
+    /// Arguments as requested
+    pub args: Vec<String>,
+
+    /// Exit code, if the command ran to completion
+    pub exit_code: Option<i32>,
+
+    /// Wall-clock duration (milliseconds)
+    pub duration_ms: u64,
+
+    /// Sandbox backend in use ("disabled" when sandboxing is off)
+    pub sandbox_backend: String,
+
+    /// Truncated SHA-256 of stdout followed by stderr (see [`hash_output`])
+    pub output_hash: Option<String>,
+
+    /// Error that stopped the command, if any
+    pub error: Option<String>,
+}
+
+/// A stored audit record
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    /// When the record was written (UTC)
+    pub timestamp: DateTime<Utc>,
+
+    /// User the executor runs on behalf of
+    pub user: String,
+
+    #[serde(flatten)]
+    pub entry: AuditEntry,
+
+    /// HMAC of the previous record
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prev_hmac: Option<String>,
+
+    /// HMAC over `prev_hmac` and this record (without the `hmac` field)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hmac: Option<String>,
+}
+
+/// Filter for [`AuditLog::query`]; unset fields match everything
+#[derive(Debug, Clone, Default)]
+pub struct AuditQuery {
+    pub command: Option<String>,
+    pub user: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+
+    /// Only records with an error or a non-zero exit code
+    pub failures_only: bool,
+
+    /// Return at most this many of the newest matches
+    pub limit: Option<usize>,
+}
+
+impl AuditQuery {
+    fn matches(&self, record: &AuditRecord) -> bool {
+        self.command
+            .as_ref()
+            .is_none_or(|c| *c == record.entry.command)
+            && self.user.as_ref().is_none_or(|u| *u == record.user)
+            && self.since.is_none_or(|since| record.timestamp >= since)
+            && self.until.is_none_or(|until| record.timestamp <= until)
+            && (!self.failures_only
+                || record.entry.error.is_some()
+                || record.entry.exit_code != Some(0))
+    }
+}
+
+struct Writer {
+    file: File,
+    size: u64,
+    last_hmac: Option<String>,
+}
+
+/// Append-only, size-rotated audit log
+pub struct AuditLog {
+    config: AuditConfig,
+    user: String,
+    writer: Mutex<Writer>,
+}
+
+impl AuditLog {
+    /// Open (or create) the log, continuing any existing HMAC chain
+    pub fn open(config: AuditConfig) -> Result<Self, AuditError> {
+        if let Some(parent) = config.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&config.path)?;
+        let size = file.metadata()?.len();
+
+        // The newest record is in the active file, or in `.1` right after rotation
+        let last_hmac = match last_record(&config.path)? {
+            Some(record) => record.hmac,
+            None => last_record(&rotated_path(&config.path, 1))?.and_then(|r| r.hmac),
+        };
+
+        let user = config
+            .user
+            .clone()
+            .or_else(|| std::env::var("USER").ok())
+            .or_else(|| std::env::var("USERNAME").ok())
+            .unwrap_or_else(|| "unknown".to_string());
+
+        Ok(Self {
+            config,
+            user,
+            writer: Mutex::new(Writer {
+                file,
+                size,
+                last_hmac,
+            }),
+        })
+    }
+
+    /// Append an entry, rotating the file if it is full
+    pub fn record(&self, entry: AuditEntry) -> Result<AuditRecord, AuditError> {
+        let mut writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+
+        let mut record = AuditRecord {
+            timestamp: Utc::now(),
+            user: self.user.clone(),
+            entry,
+            prev_hmac: None,
+            hmac: None,
+        };
+
+        if let Some(key) = &self.config.hmac_key {
+            record.prev_hmac = writer.last_hmac.clone();
+            record.hmac = Some(chain_hmac(key, &record)?);
+        }
+
+        let mut line = serde_json::to_vec(&record)?;
+        line.push(b'\n');
+
+        if writer.size > 0 && writer.size + line.len() as u64 > self.config.max_file_bytes {
+            self.rotate(&mut writer)?;
+        }
+
+        writer.file.write_all(&line)?;
+        writer.file.flush()?;
+        writer.size += line.len() as u64;
+        writer.last_hmac = record.hmac.clone();
+
+        debug!("Audited command: {}", record.entry.command);
+
+        Ok(record)
+    }
+
+    /// Records matching `query`, oldest first
+    pub fn query(&self, query: &AuditQuery) -> Result<Vec<AuditRecord>, AuditError> {
+        let mut matches: Vec<AuditRecord> = self
+            .read_all()?
+            .into_iter()
+            .filter(|record| query.matches(record))
+            .collect();
+
+        if let Some(limit) = query.limit {
+            let skip = matches.len().saturating_sub(limit);
+            matches.drain(..skip);
+        }
+
+        Ok(matches)
+    }
+
+    /// Check the HMAC chain across all retained files
+    ///
+    /// Returns the number of verified records. The chain starts at the oldest
+    /// retained record, so pruning whole rotated files is not reported.
+    pub fn verify(&self) -> Result<usize, AuditError> {
+        let key = self
+            .config
+            .hmac_key
+            .as_ref()
+            .ok_or(AuditError::ChainingDisabled)?;
+
+        let records = self.read_all()?;
+        let mut prev: Option<&Option<String>> = None;
+
+        for (i, record) in records.iter().enumerate() {
+            let linked = prev.is_none_or(|prev| *prev == record.prev_hmac);
+            let expected = chain_hmac(
+                key,
+                &AuditRecord {
+                    hmac: None,
+                    ..record.clone()
+                },
+            )?;
+
+            if !linked || record.hmac.as_deref() != Some(expected.as_str()) {
+                return Err(AuditError::ChainBroken(i));
+            }
+
+            prev = Some(&record.hmac);
+        }
+
+        Ok(records.len())
+    }
+
+    /// All retained records, oldest first
+    fn read_all(&self) -> Result<Vec<AuditRecord>, AuditError> {
+        // Hold the lock so a concurrent rotation can't reorder files mid-read
+        let _writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+
+        let mut records = Vec::new();
+        for n in (1..=self.config.max_files).rev() {
+            read_records(&rotated_path(&self.config.path, n), &mut records)?;
+        }
+        read_records(&self.config.path, &mut records)?;
+
+        Ok(records)
+    }
+
+    fn rotate(&self, writer: &mut Writer) -> Result<(), AuditError> {
+        let path = &self.config.path;
+
+        if self.config.max_files == 0 {
+            writer.file.set_len(0)?;
+        } else {
+            let oldest = rotated_path(path, self.config.max_files);
+            if oldest.exists() {
+                fs::remove_file(&oldest)?;
+            }
+
+            for n in (1..self.config.max_files).rev() {
+                let from = rotated_path(path, n);
+                if from.exists() {
+                    fs::rename(&from, rotated_path(path, n + 1))?;
+                }
+            }
+
+            fs::rename(path, rotated_path(path, 1))?;
+            writer.file = OpenOptions::new().create(true).append(true).open(path)?;
+        }
+
+        writer.size = 0;
+        debug!("Rotated audit log {:?}", path);
+
+        Ok(())
+    }
+}
+
+/// Truncated SHA-256 of a command's output, for the audit trail
+pub fn hash_output(stdout: &str, stderr: &str) -> String {
+    let mut hasher = OutputHasher::default();
+    hasher.stdout(stdout);
+    hasher.stderr(stderr);
+    hasher.finish()
+}
+
+/// Incremental form of [`hash_output`] for streamed output
+///
+/// Stdout is hashed as it arrives; stderr is buffered so it can be hashed
+/// after stdout, giving the same result as hashing the collected output.
+#[derive(Default)]
+pub struct OutputHasher {
+    stdout: Sha256,
+    stderr: String,
+}
+
+impl OutputHasher {
+    /// Feed stdout bytes
+    pub fn stdout(&mut self, text: &str) {
+        self.stdout.update(text.as_bytes());
+    }
+
+    /// Feed stderr bytes
+    pub fn stderr(&mut self, text: &str) {
+        self.stderr.push_str(text);
+    }
+
+    /// Truncated hex digest
+    pub fn finish(mut self) -> String {
+        self.stdout.update(self.stderr.as_bytes());
+
+        let mut hash = hex::encode(self.stdout.finalize());
+        hash.truncate(OUTPUT_HASH_LEN);
+        hash
+    }
+}
+
+fn chain_hmac(key: &[u8], record: &AuditRecord) -> Result<String, AuditError> {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(&serde_json::to_vec(record)?);
+    Ok(hex::encode(mac.finalize().into_bytes()))
+}
+
+fn rotated_path(path: &Path, n: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}", n));
+    PathBuf::from(name)
+}
+
+fn read_records(path: &Path, records: &mut Vec<AuditRecord>) -> Result<(), AuditError> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+
+    for line in content.lines().filter(|line| !line.trim().is_empty()) {
+        records.push(serde_json::from_str(line)?);
+    }
+
+    Ok(())
+}
+
+fn last_record(path: &Path) -> Result<Option<AuditRecord>, AuditError> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+
+    match content.lines().rev().find(|line| !line.trim().is_empty()) {
+        Some(line) => match serde_json::from_str(line) {
+            Ok(record) => Ok(Some(record)),
+            Err(e) => {
+                // A torn final write shouldn't stop the executor from starting
+                warn!("Ignoring unreadable last audit record in {:?}: {}", path, e);
+                Ok(None)
+            }
+        },
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(command: &str, exit_code: i32) -> AuditEntry {
+        AuditEntry {
+            command: command.to_string(),
+            args: vec!["-l".to_string()],
+            exit_code: Some(exit_code),
+            duration_ms: 3,
+            sandbox_backend: "none".to_string(),
+            output_hash: Some(hash_output("out", "")),
+            error: None,
+        }
+    }
+
+    fn chained_config(dir: &Path) -> AuditConfig {
+        AuditConfig {
+            user: Some("tester".to_string()),
+            hmac_key: Some(b"secret".to_vec()),
+            ..AuditConfig::new(dir.join("audit.jsonl"))
+        }
+    }
+
+    #[test]
+    fn test_record_and_query() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = AuditLog::open(chained_config(dir.path())).unwrap();
+
+        log.record(entry("ls", 0)).unwrap();
+        log.record(entry("cat", 1)).unwrap();
+        log.record(entry("ls", 0)).unwrap();
+
+        let all = log.query(&AuditQuery::default()).unwrap();
+        assert_eq!(all.len(), 3);
+        assert_eq!(all[0].user, "tester");
+
+        let ls = AuditQuery {
+            command: Some("ls".to_string()),
+            limit: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(log.query(&ls).unwrap().len(), 1);
+
+        let failures = AuditQuery {
+            failures_only: true,
+            ..Default::default()
+        };
+        let failed = log.query(&failures).unwrap();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].entry.command, "cat");
+    }
+
+    #[test]
+    fn test_chain_verifies_across_reopen_and_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = AuditConfig {
+            max_file_bytes: 400,
+            ..chained_config(dir.path())
+        };
+
+        let log = AuditLog::open(config.clone()).unwrap();
+        for _ in 0..3 {
+            log.record(entry("ls", 0)).unwrap();
+        }
+        drop(log);
+
+        let log = AuditLog::open(config.clone()).unwrap();
+        log.record(entry("wc", 0)).unwrap();
+
+        assert!(rotated_path(&config.path, 1).exists());
+        assert_eq!(log.verify().unwrap(), 4);
+    }
+
+    #[test]
+    fn test_tampering_detected() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = chained_config(dir.path());
+        let log = AuditLog::open(config.clone()).unwrap();
+
+        log.record(entry("ls", 0)).unwrap();
+        log.record(entry("cat", 1)).unwrap();
+        log.record(entry("wc", 0)).unwrap();
+
+        let content = fs::read_to_string(&config.path).unwrap();
+        fs::write(
+            &config.path,
+            content.replace("\"exit_code\":1", "\"exit_code\":0"),
+        )
+        .unwrap();
+        assert!(matches!(log.verify(), Err(AuditError::ChainBroken(1))));
+
+        // Deleting a record breaks the link to the next one
+        let lines: Vec<&str> = content.lines().collect();
+        fs::write(&config.path, format!("{}\n{}\n", lines[0], lines[2])).unwrap();
+        assert!(matches!(log.verify(), Err(AuditError::ChainBroken(1))));
+    }
+
+    #[test]
+    fn test_verify_requires_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = AuditLog::open(AuditConfig::new(dir.path().join("audit.jsonl"))).unwrap();
+
+        let record = log.record(entry("ls", 0)).unwrap();
+        assert!(record.hmac.is_none());
+        assert!(matches!(log.verify(), Err(AuditError::ChainingDisabled)));
+    }
+
+    #[test]
+    fn test_output_hash_is_truncated() {
+        let hash = hash_output("hello", "");
+        assert_eq!(hash.len(), OUTPUT_HASH_LEN);
+        assert_ne!(hash, hash_output("hello", "x"));
+    }
+
+    #[test]
+    fn test_incremental_hash_matches() {
+        let mut hasher = OutputHasher::default();
+        hasher.stderr("warning\n");
+        hasher.stdout("a\n");
+        hasher.stdout("b\n");
+
+        assert_eq!(hasher.finish(), hash_output("a\nb\n", "warning\n"));
+    }
+}
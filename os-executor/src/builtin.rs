@@ -0,0 +1,502 @@
+//! Built-in commands
+//!
+//! Common file queries run in-process instead of spawning `ls`, `cat` or
+//! `find`. They behave the same on every platform, need no external binary,
+//! and resolve every path through a [`PathScope`], so they cannot reach
+//! outside the allowed roots, through symlinks or otherwise.
+//!
+//! On Windows, `reg_query` and `list_services` answer registry and service
+//! questions through the Windows API, limited to allowlisted registry keys.
+
+use crate::path_scope::PathScope;
+use crate::registry::RegistryKey;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use thiserror::Error;
+use tracing::debug;
+
+/// Built-in command errors
+#[derive(Error, Debug)]
+pub enum BuiltinError {
+    #[error("Usage: {0}")]
+    Usage(&'static str),
+
+    #[error("Path outside allowed roots: {}", .0.display())]
+    OutsideRoots(PathBuf),
+
+    #[error("Invalid pattern {pattern:?}: {reason}")]
+    InvalidPattern { pattern: String, reason: String },
+
+    #[error("{} is {size} bytes, limit is {limit}", .path.display())]
+    FileTooLarge {
+        path: PathBuf,
+        size: u64,
+        limit: usize,
+    },
+
+    #[error("Output exceeds {0} bytes")]
+    OutputLimit(usize),
+
+    #[error("Timed out")]
+    TimedOut,
+
+    #[error("Invalid registry key: {0}")]
+    InvalidRegistryKey(String),
+
+    #[error("Registry key not allowed: {0}")]
+    RegistryNotAllowed(String),
+
+    #[error("{0} is only available on Windows")]
+    Unsupported(&'static str),
+
+    #[error("{target}: {source}")]
+    WindowsApi {
+        target: String,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("{}: {source}", .path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+}
+
+/// A command implemented by the executor itself
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Builtin {
+    /// `list_dir [-a] [path]`: directory entries, directories with a trailing `/`
+    ListDir,
+
+    /// `read_file <path>`: file contents (lossy UTF-8)
+    ReadFile,
+
+    /// `find_files <pattern> [path]`: files below `path` whose name matches a glob
+    FindFiles,
+
+    /// `disk_usage [path...]`: total size in bytes of each path, like `du -sb`
+    DiskUsage,
+
+    /// `reg_query <key> [value]`: subkeys and values of an allowlisted
+    /// registry key, or one value's data (Windows)
+    RegQuery,
+
+    /// `list_services [name]`: services and their state, optionally only those
+    /// whose name contains `name` (Windows)
+    ListServices,
+}
+
+/// Limits and path scope for a built-in run
+#[derive(Debug, Clone)]
+pub struct BuiltinContext {
+    /// Paths must resolve inside these roots
+    pub scope: PathScope,
+
+    /// Output limit (bytes); also caps the size of files read
+    pub max_output_bytes: usize,
+
+    /// Directory walks stop with [`BuiltinError::TimedOut`] after this
+    pub deadline: Option<Instant>,
+
+    /// Registry keys `reg_query` may read, including their subkeys
+    pub registry_allowlist: Vec<String>,
+}
+
+impl Builtin {
+    /// Name used in the default whitelist
+    pub fn name(&self) -> &'static str {
+        match self {
+            Builtin::ListDir => "list_dir",
+            Builtin::ReadFile => "read_file",
+            Builtin::FindFiles => "find_files",
+            Builtin::DiskUsage => "disk_usage",
+            Builtin::RegQuery => "reg_query",
+            Builtin::ListServices => "list_services",
+        }
+    }
+
+    /// Run the command and return its stdout
+    pub fn run(&self, args: &[String], ctx: &BuiltinContext) -> Result<String, BuiltinError> {
+        let mut out = Output::new(ctx);
+
+        match self {
+            Builtin::ListDir => {
+                let (all, path) = match args {
+                    [] => (false, "."),
+                    [flag] if flag == "-a" => (true, "."),
+                    [path] => (false, path.as_str()),
+                    [flag, path] if flag == "-a" => (true, path.as_str()),
+                    _ => return Err(BuiltinError::Usage("list_dir [-a] [path]")),
+                };
+                list_dir(&scoped(ctx, path)?, all, &mut out)?;
+            }
+            Builtin::ReadFile => {
+                let [path] = args else {
+                    return Err(BuiltinError::Usage("read_file <path>"));
+                };
+                read_file(&scoped(ctx, path)?, &mut out)?;
+            }
+            Builtin::FindFiles => {
+                let (pattern, path) = match args {
+                    [pattern] => (pattern, "."),
+                    [pattern, path] => (pattern, path.as_str()),
+                    _ => return Err(BuiltinError::Usage("find_files <pattern> [path]")),
+                };
+                let pattern =
+                    glob::Pattern::new(pattern).map_err(|e| BuiltinError::InvalidPattern {
+                        pattern: pattern.clone(),
+                        reason: e.to_string(),
+                    })?;
+                let root = scoped(ctx, path)?;
+                walk(&root, &mut out, &mut |path, _, out| {
+                    let matched = path
+                        .file_name()
+                        .is_some_and(|name| pattern.matches(&name.to_string_lossy()));
+                    if matched {
+                        out.line(&path.display().to_string())?;
+                    }
+                    Ok(())
+                })?;
+            }
+            Builtin::DiskUsage => {
+                let paths: Vec<&str> = match args {
+                    [] => vec!["."],
+                    args => args.iter().map(String::as_str).collect(),
+                };
+                for path in paths {
+                    let root = scoped(ctx, path)?;
+                    let mut total = 0;
+                    walk(&root, &mut out, &mut |_, metadata, _| {
+                        total += metadata.len();
+                        Ok(())
+                    })?;
+                    out.line(&format!("{}\t{}", total, path))?;
+                }
+            }
+            Builtin::RegQuery => {
+                let (key, value) = match args {
+                    [key] => (key, None),
+                    [key, value] => (key, Some(value.as_str())),
+                    _ => return Err(BuiltinError::Usage("reg_query <key> [value]")),
+                };
+                let key: RegistryKey = key.parse().map_err(BuiltinError::InvalidRegistryKey)?;
+                if !key.is_allowed(&ctx.registry_allowlist) {
+                    return Err(BuiltinError::RegistryNotAllowed(key.to_string()));
+                }
+                reg_query(&key, value, &mut out)?;
+            }
+            Builtin::ListServices => {
+                let filter = match args {
+                    [] => None,
+                    [name] => Some(name.to_lowercase()),
+                    _ => return Err(BuiltinError::Usage("list_services [name]")),
+                };
+                list_services(filter.as_deref(), &mut out)?;
+            }
+        }
+
+        Ok(out.text)
+    }
+}
+
+/// Output buffer enforcing the size limit and deadline
+struct Output {
+    text: String,
+    limit: usize,
+    deadline: Option<Instant>,
+}
+
+impl Output {
+    fn new(ctx: &BuiltinContext) -> Self {
+        Self {
+            text: String::new(),
+            limit: ctx.max_output_bytes,
+            deadline: ctx.deadline,
+        }
+    }
+
+    fn line(&mut self, line: &str) -> Result<(), BuiltinError> {
+        if self.text.len() + line.len() + 1 > self.limit {
+            return Err(BuiltinError::OutputLimit(self.limit));
+        }
+
+        self.text.push_str(line);
+        self.text.push('\n');
+        Ok(())
+    }
+
+    fn check_deadline(&self) -> Result<(), BuiltinError> {
+        match self.deadline {
+            Some(deadline) if Instant::now() >= deadline => Err(BuiltinError::TimedOut),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Resolve a path argument and check it against the scope
+fn scoped(ctx: &BuiltinContext, path: &str) -> Result<PathBuf, BuiltinError> {
+    let resolved = ctx.scope.resolve(path);
+    if ctx.scope.contains(&resolved) {
+        Ok(resolved)
+    } else {
+        Err(BuiltinError::OutsideRoots(resolved))
+    }
+}
+
+fn io_error(path: &Path) -> impl FnOnce(io::Error) -> BuiltinError + '_ {
+    move |source| BuiltinError::Io {
+        path: path.to_path_buf(),
+        source,
+    }
+}
+
+fn list_dir(path: &Path, all: bool, out: &mut Output) -> Result<(), BuiltinError> {
+    let mut names = Vec::new();
+    for entry in fs::read_dir(path).map_err(io_error(path))? {
+        let entry = entry.map_err(io_error(path))?;
+        let mut name = entry.file_name().to_string_lossy().to_string();
+        if !all && name.starts_with('.') {
+            continue;
+        }
+        if entry.file_type().is_ok_and(|t| t.is_dir()) {
+            name.push('/');
+        }
+        names.push(name);
+    }
+
+    names.sort();
+    for name in names {
+        out.line(&name)?;
+    }
+
+    Ok(())
+}
+
+fn read_file(path: &Path, out: &mut Output) -> Result<(), BuiltinError> {
+    let file = fs::File::open(path).map_err(io_error(path))?;
+    let size = file.metadata().map_err(io_error(path))?.len();
+    if size > out.limit as u64 {
+        return Err(BuiltinError::FileTooLarge {
+            path: path.to_path_buf(),
+            size,
+            limit: out.limit,
+        });
+    }
+
+    // The file may grow after the size check
+    let mut bytes = Vec::new();
+    file.take(out.limit as u64 + 1)
+        .read_to_end(&mut bytes)
+        .map_err(io_error(path))?;
+    if bytes.len() > out.limit {
+        return Err(BuiltinError::OutputLimit(out.limit));
+    }
+
+    out.text = String::from_utf8_lossy(&bytes).into_owned();
+    Ok(())
+}
+
+#[cfg(windows)]
+fn reg_query(key: &RegistryKey, value: Option<&str>, out: &mut Output) -> Result<(), BuiltinError> {
+    let lines = crate::registry::query(key, value).map_err(|source| BuiltinError::WindowsApi {
+        target: key.to_string(),
+        source,
+    })?;
+    for line in lines {
+        out.line(&line)?;
+    }
+    Ok(())
+}
+
+#[cfg(not(windows))]
+fn reg_query(_: &RegistryKey, _: Option<&str>, _: &mut Output) -> Result<(), BuiltinError> {
+    Err(BuiltinError::Unsupported("reg_query"))
+}
+
+#[cfg(windows)]
+fn list_services(filter: Option<&str>, out: &mut Output) -> Result<(), BuiltinError> {
+    let services = crate::services::list().map_err(|source| BuiltinError::WindowsApi {
+        target: "Service Control Manager".to_string(),
+        source,
+    })?;
+
+    let matches = |service: &crate::services::ServiceInfo| {
+        filter.is_none_or(|filter| {
+            service.name.to_lowercase().contains(filter)
+                || service.display_name.to_lowercase().contains(filter)
+        })
+    };
+    for service in services.iter().filter(|service| matches(service)) {
+        out.line(&format!(
+            "{}\t{}\t{}",
+            service.name, service.state, service.display_name
+        ))?;
+    }
+    Ok(())
+}
+
+#[cfg(not(windows))]
+fn list_services(_: Option<&str>, _: &mut Output) -> Result<(), BuiltinError> {
+    Err(BuiltinError::Unsupported("list_services"))
+}
+
+/// Visit `root` and everything below it without following symlinks
+///
+/// Unreadable subdirectories are skipped; an unreadable `root` is an error.
+fn walk<F>(root: &Path, out: &mut Output, visit: &mut F) -> Result<(), BuiltinError>
+where
+    F: FnMut(&Path, &fs::Metadata, &mut Output) -> Result<(), BuiltinError>,
+{
+    let metadata = fs::symlink_metadata(root).map_err(io_error(root))?;
+    visit(root, &metadata, out)?;
+    if !metadata.is_dir() {
+        return Ok(());
+    }
+
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        out.check_deadline()?;
+
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) if dir != root => {
+                debug!("Skipping {}: {}", dir.display(), e);
+                continue;
+            }
+            Err(e) => return Err(io_error(&dir)(e)),
+        };
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let Ok(metadata) = fs::symlink_metadata(&path) else {
+                continue;
+            };
+            visit(&path, &metadata, out)?;
+            if metadata.is_dir() {
+                pending.push(path);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn context(root: &Path, max_output_bytes: usize) -> BuiltinContext {
+        BuiltinContext {
+            scope: PathScope::new(&[root.to_string_lossy().to_string()], root),
+            max_output_bytes,
+            deadline: None,
+            registry_allowlist: crate::registry::default_allowlist(),
+        }
+    }
+
+    #[test]
+    fn test_builtins() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        fs::create_dir(root.join("docs")).unwrap();
+        fs::write(root.join("docs/a.txt"), "hello").unwrap();
+        fs::write(root.join("docs/b.md"), "# b").unwrap();
+        fs::write(root.join(".hidden"), "").unwrap();
+        let ctx = context(&root, 1024);
+
+        assert_eq!(Builtin::ListDir.run(&[], &ctx).unwrap(), "docs/\n");
+        assert_eq!(
+            Builtin::ListDir.run(&args(&["-a"]), &ctx).unwrap(),
+            ".hidden\ndocs/\n"
+        );
+        assert_eq!(
+            Builtin::ReadFile.run(&args(&["docs/a.txt"]), &ctx).unwrap(),
+            "hello"
+        );
+        assert_eq!(
+            Builtin::FindFiles.run(&args(&["*.txt"]), &ctx).unwrap(),
+            format!("{}\n", root.join("docs/a.txt").display())
+        );
+        assert_eq!(
+            Builtin::DiskUsage.run(&args(&["docs"]), &ctx).unwrap(),
+            format!(
+                "{}\tdocs\n",
+                8 + fs::metadata(root.join("docs")).unwrap().len()
+            )
+        );
+        assert!(matches!(
+            Builtin::ReadFile.run(&args(&["missing"]), &ctx),
+            Err(BuiltinError::Io { .. })
+        ));
+        assert!(matches!(
+            Builtin::ReadFile.run(&[], &ctx),
+            Err(BuiltinError::Usage(_))
+        ));
+    }
+
+    #[test]
+    fn test_registry_scope() {
+        let ctx = context(Path::new("/"), 1024);
+
+        assert!(matches!(
+            Builtin::RegQuery.run(&args(&[r"HKLM\SOFTWARE\Microsoft"]), &ctx),
+            Err(BuiltinError::RegistryNotAllowed(key)) if key == r"HKLM\SOFTWARE\Microsoft"
+        ));
+        assert!(matches!(
+            Builtin::RegQuery.run(&args(&[r"HKXX\SOFTWARE"]), &ctx),
+            Err(BuiltinError::InvalidRegistryKey(_))
+        ));
+        assert!(matches!(
+            Builtin::ListServices.run(&args(&["a", "b"]), &ctx),
+            Err(BuiltinError::Usage(_))
+        ));
+
+        #[cfg(not(windows))]
+        assert!(matches!(
+            Builtin::RegQuery.run(&args(&[r"HKLM\SYSTEM\CurrentControlSet\Services"]), &ctx),
+            Err(BuiltinError::Unsupported("reg_query"))
+        ));
+    }
+
+    #[test]
+    fn test_limits_and_scope() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap().join("root");
+        fs::create_dir(&root).unwrap();
+        fs::write(root.join("big"), "x".repeat(100)).unwrap();
+        fs::write(dir.path().join("secret"), "s").unwrap();
+        let ctx = context(&root, 10);
+
+        assert!(matches!(
+            Builtin::ReadFile.run(&args(&["big"]), &ctx),
+            Err(BuiltinError::FileTooLarge { size: 100, .. })
+        ));
+        assert!(matches!(
+            Builtin::ReadFile.run(&args(&["../secret"]), &ctx),
+            Err(BuiltinError::OutsideRoots(_))
+        ));
+        assert!(matches!(
+            Builtin::DiskUsage.run(&args(&["/"]), &ctx),
+            Err(BuiltinError::OutsideRoots(_))
+        ));
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(dir.path(), root.join("up")).unwrap();
+            assert!(matches!(
+                Builtin::ListDir.run(&args(&["up"]), &ctx),
+                Err(BuiltinError::OutsideRoots(_))
+            ));
+        }
+    }
+}
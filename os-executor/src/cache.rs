@@ -0,0 +1,289 @@
+//! Command result cache
+//!
+//! Repeated read-only questions ("what's my disk usage?") would re-run the
+//! same command each time. For whitelist entries marked `cacheable`,
+//! successful results are kept for a TTL, keyed on command, arguments,
+//! working directory and the limits they ran under, and returned by the
+//! executor instead of running the command again.
+
+use crate::executor::CommandResult;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+/// Cache settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheConfig {
+    /// TTL for entries without `cache_ttl_secs`
+    pub default_ttl_secs: u64,
+
+    /// Results kept at once; the oldest is evicted first
+    pub max_entries: usize,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            default_ttl_secs: 30,
+            max_entries: 256,
+        }
+    }
+}
+
+/// Cache hit/miss counters
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheStats {
+    /// Lookups answered from the cache
+    pub hits: u64,
+
+    /// Lookups that ran the command
+    pub misses: u64,
+
+    /// Results currently cached (including expired ones not yet evicted)
+    pub entries: usize,
+}
+
+/// Limits a result was produced under, so a request with narrower limits
+/// (e.g. from a capability token) runs the command instead of reusing it
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct CacheLimits {
+    pub timeout_secs: u64,
+    pub max_output_bytes: usize,
+    pub max_memory_mb: Option<u64>,
+    pub allow_network: Option<bool>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct CacheKey {
+    command: String,
+    args: Vec<String>,
+    cwd: PathBuf,
+    limits: CacheLimits,
+}
+
+struct Cached {
+    result: CommandResult,
+    inserted: Instant,
+    expires: Instant,
+}
+
+#[derive(Default)]
+struct CacheState {
+    entries: HashMap<CacheKey, Cached>,
+    hits: u64,
+    misses: u64,
+}
+
+/// Successful results of cacheable commands, shared by an executor
+pub struct ResultCache {
+    config: CacheConfig,
+    state: Mutex<CacheState>,
+}
+
+impl ResultCache {
+    /// Create an empty cache
+    pub fn new(config: CacheConfig) -> Self {
+        Self {
+            config,
+            state: Mutex::new(CacheState::default()),
+        }
+    }
+
+    /// TTL for an entry's `cache_ttl_secs` override
+    pub(crate) fn ttl(&self, ttl_secs: Option<u64>) -> Duration {
+        Duration::from_secs(ttl_secs.unwrap_or(self.config.default_ttl_secs))
+    }
+
+    /// Unexpired result for the request, marked `cached`; counts a hit or miss
+    pub(crate) fn get(
+        &self,
+        command: &str,
+        args: &[String],
+        cwd: &Path,
+        limits: &CacheLimits,
+    ) -> Option<CommandResult> {
+        let key = CacheKey {
+            command: command.to_string(),
+            args: args.to_vec(),
+            cwd: cwd.to_path_buf(),
+            limits: limits.clone(),
+        };
+
+        let mut state = self.lock();
+        let now = Instant::now();
+        let result = match state.entries.get(&key) {
+            Some(cached) if cached.expires > now => Some(cached.result.clone()),
+            Some(_) => {
+                state.entries.remove(&key);
+                None
+            }
+            None => None,
+        };
+
+        match result {
+            Some(mut result) => {
+                state.hits += 1;
+                debug!("Cache hit for {}", command);
+                result.cached = true;
+                Some(result)
+            }
+            None => {
+                state.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// Store a successful result for `ttl`
+    pub(crate) fn insert(
+        &self,
+        result: &CommandResult,
+        cwd: &Path,
+        limits: &CacheLimits,
+        ttl: Duration,
+    ) {
+        if !result.success || ttl.is_zero() || self.config.max_entries == 0 {
+            return;
+        }
+
+        let key = CacheKey {
+            command: result.command.clone(),
+            args: result.args.clone(),
+            cwd: cwd.to_path_buf(),
+            limits: limits.clone(),
+        };
+
+        let mut state = self.lock();
+        let now = Instant::now();
+
+        if !state.entries.contains_key(&key) && state.entries.len() >= self.config.max_entries {
+            state.entries.retain(|_, cached| cached.expires > now);
+        }
+        while !state.entries.contains_key(&key) && state.entries.len() >= self.config.max_entries {
+            let oldest = state
+                .entries
+                .iter()
+                .min_by_key(|(_, cached)| cached.inserted)
+                .map(|(key, _)| key.clone());
+            match oldest {
+                Some(oldest) => state.entries.remove(&oldest),
+                None => break,
+            };
+        }
+
+        state.entries.insert(
+            key,
+            Cached {
+                result: result.clone(),
+                inserted: now,
+                expires: now + ttl,
+            },
+        );
+    }
+
+    /// Drop every cached result, e.g. after files were changed
+    pub fn clear(&self) {
+        self.lock().entries.clear();
+    }
+
+    /// Hit/miss counters and current size
+    pub fn stats(&self) -> CacheStats {
+        let state = self.lock();
+        CacheStats {
+            hits: state.hits,
+            misses: state.misses,
+            entries: state.entries.len(),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, CacheState> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(command: &str, success: bool) -> CommandResult {
+        CommandResult {
+            command: command.to_string(),
+            args: vec!["-s".to_string()],
+            stdout: format!("{} output", command),
+            stderr: String::new(),
+            exit_code: if success { 0 } else { 1 },
+            duration_ms: 5,
+            success,
+            peak_memory_bytes: None,
+            cpu_usage_usec: None,
+            max_rss_kb: None,
+            user_cpu_ms: None,
+            sys_cpu_ms: None,
+            was_killed_by: None,
+            summary: None,
+            cached: false,
+            risk: None,
+        }
+    }
+
+    #[test]
+    fn test_hits_expiry_and_eviction() {
+        let cache = ResultCache::new(CacheConfig {
+            default_ttl_secs: 60,
+            max_entries: 2,
+        });
+        let args = vec!["-s".to_string()];
+        let home = Path::new("/home/user");
+        let ttl = cache.ttl(None);
+        let limits = CacheLimits {
+            timeout_secs: 30,
+            max_output_bytes: 1024,
+            max_memory_mb: None,
+            allow_network: None,
+        };
+
+        assert!(cache.get("du", &args, home, &limits).is_none());
+        cache.insert(&result("du", true), home, &limits, ttl);
+        cache.insert(&result("false", false), home, &limits, ttl);
+
+        let hit = cache.get("du", &args, home, &limits).unwrap();
+        assert!(hit.cached);
+        assert_eq!(hit.stdout, "du output");
+        assert!(cache.get("du", &args, Path::new("/tmp"), &limits).is_none());
+        assert!(cache.get("false", &args, home, &limits).is_none());
+
+        // Narrower limits don't reuse a result produced under wider ones
+        let narrow = CacheLimits {
+            max_output_bytes: 4,
+            ..limits
+        };
+        assert!(cache.get("du", &args, home, &narrow).is_none());
+
+        // Expired entries are not returned
+        cache.insert(&result("df", true), home, &limits, Duration::from_millis(1));
+        std::thread::sleep(Duration::from_millis(5));
+        assert!(cache.get("df", &args, home, &limits).is_none());
+
+        // The oldest entry makes room
+        cache.insert(&result("df", true), home, &limits, ttl);
+        cache.insert(&result("free", true), home, &limits, ttl);
+        assert!(cache.get("du", &args, home, &limits).is_none());
+        assert!(cache.get("free", &args, home, &limits).is_some());
+
+        assert_eq!(
+            cache.stats(),
+            CacheStats {
+                hits: 2,
+                misses: 6,
+                entries: 2
+            }
+        );
+        cache.clear();
+        assert_eq!(cache.stats().entries, 0);
+    }
+}
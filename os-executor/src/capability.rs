@@ -0,0 +1,244 @@
+//! Capability tokens
+//!
+//! Agent components that call the executor should only be able to run what
+//! they need. A [`CapabilityAuthority`] issues tokens naming the commands a
+//! caller may run and ceilings on their resources, signed with HMAC-SHA256 so
+//! a caller cannot widen its own scope. Tokens are presented to
+//! [`CommandExecutor::execute_with_capability`](crate::CommandExecutor::execute_with_capability)
+//! or [`CommandExecutor::authorized`](crate::CommandExecutor::authorized); an
+//! executor with an authority refuses requests without one.
+
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Capability token errors
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum CapabilityError {
+    #[error("Malformed capability token: {0}")]
+    Malformed(String),
+
+    #[error("Capability token signature is invalid")]
+    InvalidSignature,
+
+    #[error("Capability token expired at {0}")]
+    Expired(DateTime<Utc>),
+}
+
+/// What a token holder may execute
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Capability {
+    /// Component the token was issued to (recorded in logs)
+    pub subject: String,
+
+    /// Whitelisted commands the holder may run (`*` for any)
+    pub commands: Vec<String>,
+
+    /// Ceiling on command timeouts
+    #[serde(default)]
+    pub max_timeout_secs: Option<u64>,
+
+    /// Ceiling on command memory
+    #[serde(default)]
+    pub max_memory_mb: Option<u64>,
+
+    /// Ceiling on captured output
+    #[serde(default)]
+    pub max_output_bytes: Option<usize>,
+
+    /// Whether commands may keep network access their entry allows
+    #[serde(default)]
+    pub allow_network: bool,
+
+    /// Token is rejected after this time
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl Capability {
+    /// Capability for `subject` to run `commands`, with no resource ceilings
+    pub fn new(subject: &str, commands: &[&str]) -> Self {
+        Self {
+            subject: subject.to_string(),
+            commands: commands.iter().map(|c| c.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Whether `command` is in scope
+    pub fn allows(&self, command: &str) -> bool {
+        self.commands.iter().any(|c| c == "*" || c == command)
+    }
+}
+
+/// Signed, serialized [`Capability`]
+///
+/// The format is `<hex payload>.<hex signature>`; it can be passed between
+/// processes as a plain string.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct CapabilityToken(String);
+
+impl CapabilityToken {
+    /// Token text
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for CapabilityToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl FromStr for CapabilityToken {
+    type Err = CapabilityError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.split_once('.').is_none() {
+            return Err(CapabilityError::Malformed("missing signature".to_string()));
+        }
+
+        Ok(Self(s.trim().to_string()))
+    }
+}
+
+/// Issues and verifies capability tokens with a shared key
+#[derive(Clone)]
+pub struct CapabilityAuthority {
+    key: Vec<u8>,
+}
+
+impl fmt::Debug for CapabilityAuthority {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CapabilityAuthority")
+            .finish_non_exhaustive()
+    }
+}
+
+impl CapabilityAuthority {
+    /// Create an authority signing with `key`
+    pub fn new(key: impl Into<Vec<u8>>) -> Self {
+        Self { key: key.into() }
+    }
+
+    /// Sign a capability
+    pub fn issue(&self, capability: &Capability) -> CapabilityToken {
+        let payload = serde_json::to_vec(capability).expect("capability serializes to JSON");
+        let payload = hex::encode(payload);
+        let signature = hex::encode(self.sign(&payload));
+
+        CapabilityToken(format!("{}.{}", payload, signature))
+    }
+
+    /// Check a token's signature and expiry and return its capability
+    pub fn verify(&self, token: &CapabilityToken) -> Result<Capability, CapabilityError> {
+        self.verify_at(token, Utc::now())
+    }
+
+    /// [`verify`](Self::verify) as of `now`
+    pub fn verify_at(
+        &self,
+        token: &CapabilityToken,
+        now: DateTime<Utc>,
+    ) -> Result<Capability, CapabilityError> {
+        let (payload, signature) = token
+            .0
+            .split_once('.')
+            .ok_or_else(|| CapabilityError::Malformed("missing signature".to_string()))?;
+
+        let signature = hex::decode(signature)
+            .map_err(|_| CapabilityError::Malformed("signature is not hex".to_string()))?;
+
+        let mut mac = self.mac();
+        mac.update(payload.as_bytes());
+        mac.verify_slice(&signature)
+            .map_err(|_| CapabilityError::InvalidSignature)?;
+
+        let payload = hex::decode(payload)
+            .map_err(|_| CapabilityError::Malformed("payload is not hex".to_string()))?;
+        let capability: Capability = serde_json::from_slice(&payload)
+            .map_err(|e| CapabilityError::Malformed(e.to_string()))?;
+
+        match capability.expires_at {
+            Some(expires_at) if expires_at <= now => Err(CapabilityError::Expired(expires_at)),
+            _ => Ok(capability),
+        }
+    }
+
+    fn mac(&self) -> HmacSha256 {
+        HmacSha256::new_from_slice(&self.key).expect("HMAC accepts keys of any length")
+    }
+
+    fn sign(&self, payload: &str) -> Vec<u8> {
+        let mut mac = self.mac();
+        mac.update(payload.as_bytes());
+        mac.finalize().into_bytes().to_vec()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn test_issue_and_verify() {
+        let authority = CapabilityAuthority::new("secret");
+        let capability = Capability {
+            max_timeout_secs: Some(2),
+            ..Capability::new("file-browser", &["ls", "cat"])
+        };
+
+        let token: CapabilityToken = authority.issue(&capability).to_string().parse().unwrap();
+        let verified = authority.verify(&token).unwrap();
+
+        assert_eq!(verified, capability);
+        assert!(verified.allows("ls"));
+        assert!(!verified.allows("rm"));
+        assert!(Capability::new("admin", &["*"]).allows("rm"));
+    }
+
+    #[test]
+    fn test_tampered_or_expired_tokens_rejected() {
+        let authority = CapabilityAuthority::new("secret");
+        let token = authority.issue(&Capability::new("file-browser", &["ls"]));
+
+        // Widening the scope invalidates the signature
+        let (_, signature) = token.as_str().split_once('.').unwrap();
+        let widened =
+            hex::encode(serde_json::to_vec(&Capability::new("file-browser", &["*"])).unwrap());
+        let forged = CapabilityToken(format!("{}.{}", widened, signature));
+        assert_eq!(
+            authority.verify(&forged),
+            Err(CapabilityError::InvalidSignature)
+        );
+
+        assert_eq!(
+            CapabilityAuthority::new("other").verify(&token),
+            Err(CapabilityError::InvalidSignature)
+        );
+        assert!(matches!(
+            "not-a-token".parse::<CapabilityToken>(),
+            Err(CapabilityError::Malformed(_))
+        ));
+
+        let now = Utc::now();
+        let expiring = authority.issue(&Capability {
+            expires_at: Some(now + Duration::minutes(5)),
+            ..Capability::new("file-browser", &["ls"])
+        });
+        assert!(authority.verify_at(&expiring, now).is_ok());
+        assert!(matches!(
+            authority.verify_at(&expiring, now + Duration::minutes(10)),
+            Err(CapabilityError::Expired(_))
+        ));
+    }
+}
@@ -0,0 +1,264 @@
+//! cgroup v2 resource controller (Linux)
+//!
+//! Each sandboxed command gets a transient cgroup under a delegated parent
+//! directory. Limits are written to `memory.max`, `cpu.max` and `pids.max`, and
+//! the child joins the group from `pre_exec`, so every process it forks is
+//! accounted and limited too. The group is killed and removed when dropped.
+
+use crate::sandbox::{CgroupUsage, SandboxConfig, SandboxError};
+use std::fs::{self, File, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tracing::{debug, warn};
+
+/// `cpu.max` scheduling period (microseconds)
+const CPU_PERIOD_USEC: u64 = 100_000;
+
+/// Attempts to remove a cgroup while its last processes exit
+const REMOVE_RETRIES: usize = 20;
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Transient cgroup for one command
+pub struct Cgroup {
+    path: PathBuf,
+}
+
+impl Cgroup {
+    /// Check that `root` is a cgroup v2 directory
+    pub fn is_available(root: &Path) -> bool {
+        root.join("cgroup.controllers").is_file()
+    }
+
+    /// Create a cgroup under `root` with limits from the sandbox config
+    ///
+    /// Controllers missing from `root` are skipped with a warning rather than
+    /// failing the command.
+    pub fn create(root: &Path, config: &SandboxConfig) -> Result<Self, SandboxError> {
+        if !Self::is_available(root) {
+            return Err(SandboxError::ResourceLimitFailed(format!(
+                "{} is not a cgroup v2 directory",
+                root.display()
+            )));
+        }
+
+        let available = fs::read_to_string(root.join("cgroup.controllers")).unwrap_or_default();
+        let enabled = enable_controllers(root, &available);
+
+        let name = format!(
+            "os-executor-{}-{}",
+            std::process::id(),
+            NEXT_ID.fetch_add(1, Ordering::Relaxed)
+        );
+        let path = root.join(name);
+        fs::create_dir(&path).map_err(|e| {
+            SandboxError::ResourceLimitFailed(format!("Failed to create {}: {}", path.display(), e))
+        })?;
+
+        let cgroup = Self { path };
+
+        if let Some(mem_mb) = config.max_memory_mb {
+            cgroup.set_limit(
+                &enabled,
+                "memory",
+                "memory.max",
+                &(mem_mb * 1024 * 1024).to_string(),
+            )?;
+        }
+
+        if let Some(percent) = config.cpu_rate_percent {
+            let cpus = std::thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(1);
+            let value = cpu_max_value(percent, cpus);
+            cgroup.set_limit(&enabled, "cpu", "cpu.max", &value)?;
+        }
+
+        if let Some(count) = config.max_processes {
+            cgroup.set_limit(&enabled, "pids", "pids.max", &count.to_string())?;
+        }
+
+        debug!("Created cgroup {}", cgroup.path.display());
+
+        Ok(cgroup)
+    }
+
+    /// Cgroup directory
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Open `cgroup.procs` for the child to join from `pre_exec`
+    ///
+    /// Writing `0` to this file moves the writing process into the group, so
+    /// the child can join without knowing its own pid.
+    pub fn procs_file(&self) -> Result<File, SandboxError> {
+        OpenOptions::new()
+            .write(true)
+            .open(self.path.join("cgroup.procs"))
+            .map_err(|e| {
+                SandboxError::ResourceLimitFailed(format!("Failed to open cgroup.procs: {}", e))
+            })
+    }
+
+    /// Read accumulated usage
+    pub fn usage(&self) -> CgroupUsage {
+        let peak_memory_bytes = fs::read_to_string(self.path.join("memory.peak"))
+            .ok()
+            .and_then(|s| s.trim().parse().ok());
+
+        let cpu_usage_usec = fs::read_to_string(self.path.join("cpu.stat"))
+            .ok()
+            .and_then(|s| parse_stat(&s, "usage_usec"));
+
+        let oom_kills = fs::read_to_string(self.path.join("memory.events"))
+            .ok()
+            .and_then(|s| parse_stat(&s, "oom_kill"));
+
+        CgroupUsage {
+            peak_memory_bytes,
+            cpu_usage_usec,
+            oom_kills,
+        }
+    }
+
+    fn set_limit(
+        &self,
+        enabled: &[&str],
+        controller: &str,
+        file: &str,
+        value: &str,
+    ) -> Result<(), SandboxError> {
+        if !enabled.contains(&controller) {
+            warn!(
+                "cgroup controller '{}' unavailable; {} not applied",
+                controller, file
+            );
+            return Ok(());
+        }
+
+        fs::write(self.path.join(file), value).map_err(|e| {
+            SandboxError::ResourceLimitFailed(format!("Failed to write {}: {}", file, e))
+        })
+    }
+}
+
+impl Drop for Cgroup {
+    fn drop(&mut self) {
+        // Kill stragglers (cgroup.kill needs kernel 5.14+), then remove the group
+        let _ = fs::write(self.path.join("cgroup.kill"), "1");
+
+        for _ in 0..REMOVE_RETRIES {
+            match fs::remove_dir(&self.path) {
+                Ok(()) => {
+                    debug!("Removed cgroup {}", self.path.display());
+                    return;
+                }
+                Err(e) if e.raw_os_error() == Some(libc::EBUSY) => {
+                    std::thread::sleep(Duration::from_millis(5));
+                }
+                Err(e) => {
+                    warn!("Failed to remove cgroup {}: {}", self.path.display(), e);
+                    return;
+                }
+            }
+        }
+
+        warn!(
+            "cgroup {} still busy; leaving it in place",
+            self.path.display()
+        );
+    }
+}
+
+/// Enable the controllers we use for child groups, returning those now active
+fn enable_controllers<'a>(root: &Path, available: &str) -> Vec<&'a str> {
+    let mut enabled = Vec::new();
+
+    for controller in ["memory", "cpu", "pids"] {
+        if !available.split_whitespace().any(|c| c == controller) {
+            continue;
+        }
+
+        match fs::write(
+            root.join("cgroup.subtree_control"),
+            format!("+{}", controller),
+        ) {
+            Ok(()) => enabled.push(controller),
+            Err(e) => warn!("Failed to enable cgroup controller '{}': {}", controller, e),
+        }
+    }
+
+    enabled
+}
+
+/// `cpu.max` value capping the group at `percent` of all CPUs
+fn cpu_max_value(percent: u32, cpu_count: usize) -> String {
+    let quota = CPU_PERIOD_USEC * percent as u64 * cpu_count.max(1) as u64 / 100;
+    format!("{} {}", quota.max(1000), CPU_PERIOD_USEC)
+}
+
+/// Value of a `key value` line in a cgroup stat file
+fn parse_stat(content: &str, key: &str) -> Option<u64> {
+    content.lines().find_map(|line| {
+        let (k, v) = line.split_once(' ')?;
+        if k == key {
+            v.trim().parse().ok()
+        } else {
+            None
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// cgroup v2 mount for tests, if the environment has one
+    fn test_root() -> Option<PathBuf> {
+        ["/sys/fs/cgroup", "/sys/fs/cgroup/unified"]
+            .iter()
+            .map(PathBuf::from)
+            .find(|p| Cgroup::is_available(p))
+    }
+
+    #[test]
+    fn test_cpu_max_value() {
+        assert_eq!(cpu_max_value(50, 1), "50000 100000");
+        assert_eq!(cpu_max_value(50, 4), "200000 100000");
+        // The kernel rejects quotas below 1ms
+        assert_eq!(cpu_max_value(0, 1), "1000 100000");
+    }
+
+    #[test]
+    fn test_parse_stat() {
+        let stat = "usage_usec 1234\nuser_usec 1000\nsystem_usec 234\n";
+        assert_eq!(parse_stat(stat, "usage_usec"), Some(1234));
+        assert_eq!(parse_stat(stat, "system_usec"), Some(234));
+        assert_eq!(parse_stat(stat, "missing"), None);
+    }
+
+    #[test]
+    fn test_create_and_remove() {
+        let Some(root) = test_root() else {
+            println!("No cgroup v2 mount; skipping");
+            return;
+        };
+
+        let cgroup = match Cgroup::create(&root, &SandboxConfig::default()) {
+            Ok(cgroup) => cgroup,
+            Err(e) => {
+                println!("cgroup creation not permitted here: {}; skipping", e);
+                return;
+            }
+        };
+
+        let path = cgroup.path().to_path_buf();
+        assert!(path.exists());
+        assert!(cgroup.procs_file().is_ok());
+
+        drop(cgroup);
+        assert!(!path.exists());
+    }
+}
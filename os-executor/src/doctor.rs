@@ -0,0 +1,455 @@
+//! Installation self-check
+//!
+//! `os-executor doctor` verifies that the protections the executor relies on
+//! actually work on this machine: which jails are installed, whether rlimits
+//! and privilege dropping take effect in a child process, whether every
+//! whitelist regex compiles, and whether basic escape attempts are refused.
+//! The [`DoctorReport`] serializes to JSON for scripts.
+
+use crate::executor::{CommandExecutor, ExecutorConfig, ExecutorError};
+use crate::platform::Platform;
+use crate::sandbox::SandboxBackend;
+use crate::whitelist::CommandWhitelist;
+use serde::{Deserialize, Serialize};
+
+/// Outcome of one check
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Pass,
+    /// Works, but with weaker protection than intended
+    Warn,
+    Fail,
+    /// Not applicable on this platform or configuration
+    Skip,
+}
+
+/// One named check and what it found
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Check {
+    pub name: String,
+    pub status: CheckStatus,
+    pub detail: String,
+}
+
+impl Check {
+    fn new(name: &str, status: CheckStatus, detail: impl Into<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            status,
+            detail: detail.into(),
+        }
+    }
+}
+
+/// Results of every check, in the order they ran
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DoctorReport {
+    pub version: String,
+    pub platform: String,
+    pub checks: Vec<Check>,
+}
+
+impl DoctorReport {
+    /// Whether no check failed (warnings and skips are fine)
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(|c| c.status != CheckStatus::Fail)
+    }
+
+    /// Look up a check by name
+    pub fn check(&self, name: &str) -> Option<&Check> {
+        self.checks.iter().find(|c| c.name == name)
+    }
+}
+
+/// Run every check against `config` and `whitelist`
+pub async fn run(config: &ExecutorConfig, whitelist: &CommandWhitelist) -> DoctorReport {
+    let mut checks = vec![
+        check_backends(),
+        check_rlimits(config),
+        check_privilege_drop(config),
+        check_whitelist(whitelist),
+    ];
+    checks.extend(check_escapes(config, whitelist).await);
+
+    DoctorReport {
+        version: crate::VERSION.to_string(),
+        platform: Platform::current().name().to_string(),
+        checks,
+    }
+}
+
+fn check_backends() -> Check {
+    if !cfg!(target_os = "linux") {
+        return Check::new("jail_backends", CheckStatus::Skip, "jails are Linux-only");
+    }
+
+    let available: Vec<&str> = [
+        SandboxBackend::Nsjail,
+        SandboxBackend::Bubblewrap,
+        SandboxBackend::Firejail,
+    ]
+    .iter()
+    .filter(|backend| backend.is_available())
+    .map(|backend| backend.name())
+    .collect();
+
+    if available.is_empty() {
+        Check::new(
+            "jail_backends",
+            CheckStatus::Warn,
+            "no nsjail, bubblewrap or firejail; only in-process limits apply",
+        )
+    } else {
+        Check::new(
+            "jail_backends",
+            CheckStatus::Pass,
+            format!("available: {}", available.join(", ")),
+        )
+    }
+}
+
+/// Apply the configured CPU limit in a child and read it back with `ulimit`
+#[cfg(unix)]
+fn check_rlimits(config: &ExecutorConfig) -> Check {
+    use std::os::unix::process::CommandExt;
+
+    let cpu_secs = config.sandbox.max_cpu_time_secs.unwrap_or(5);
+    let mut command = std::process::Command::new("sh");
+    command.args(["-c", "ulimit -t"]);
+    unsafe {
+        command.pre_exec(move || {
+            let rlimit = libc::rlimit {
+                rlim_cur: cpu_secs,
+                rlim_max: cpu_secs + 1,
+            };
+            if libc::setrlimit(libc::RLIMIT_CPU, &rlimit) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+
+    match command.output() {
+        Ok(output) if String::from_utf8_lossy(&output.stdout).trim() == cpu_secs.to_string() => {
+            Check::new(
+                "rlimits",
+                CheckStatus::Pass,
+                format!("CPU limit of {}s applied", cpu_secs),
+            )
+        }
+        Ok(output) => Check::new(
+            "rlimits",
+            CheckStatus::Fail,
+            format!(
+                "child reported CPU limit {:?}, expected {}",
+                String::from_utf8_lossy(&output.stdout).trim(),
+                cpu_secs
+            ),
+        ),
+        Err(e) => Check::new("rlimits", CheckStatus::Fail, e.to_string()),
+    }
+}
+
+#[cfg(windows)]
+fn check_rlimits(config: &ExecutorConfig) -> Check {
+    match crate::job_object::JobObject::from_config(&config.sandbox) {
+        Ok(_) => Check::new("rlimits", CheckStatus::Pass, "job object limits applied"),
+        Err(e) => Check::new("rlimits", CheckStatus::Fail, e.to_string()),
+    }
+}
+
+/// Drop to the configured user in a child and read its uid back with `id -u`
+#[cfg(unix)]
+fn check_privilege_drop(config: &ExecutorConfig) -> Check {
+    use crate::sandbox::Credentials;
+    use std::os::unix::process::CommandExt;
+
+    const NAME: &str = "privilege_drop";
+
+    if config.sandbox.drop_to_user.is_none() && config.sandbox.drop_to_group.is_none() {
+        return Check::new(NAME, CheckStatus::Warn, "no drop_to_user configured");
+    }
+    if !nix::unistd::geteuid().is_root() {
+        return Check::new(
+            NAME,
+            CheckStatus::Warn,
+            "not running as root; commands keep the current user's privileges",
+        );
+    }
+
+    let credentials = match Credentials::resolve(&config.sandbox) {
+        Ok(Some(credentials)) => credentials,
+        Ok(None) => return Check::new(NAME, CheckStatus::Warn, "no drop_to_user configured"),
+        Err(e) => return Check::new(NAME, CheckStatus::Fail, e.to_string()),
+    };
+
+    let mut command = std::process::Command::new("id");
+    command.arg("-u");
+    unsafe {
+        command.pre_exec(move || credentials.apply());
+    }
+
+    match command.output() {
+        Ok(output)
+            if String::from_utf8_lossy(&output.stdout).trim() == credentials.uid.to_string() =>
+        {
+            Check::new(
+                NAME,
+                CheckStatus::Pass,
+                format!("children run as uid {}", credentials.uid),
+            )
+        }
+        Ok(output) => Check::new(
+            NAME,
+            CheckStatus::Fail,
+            format!(
+                "child ran as uid {:?}, expected {}",
+                String::from_utf8_lossy(&output.stdout).trim(),
+                credentials.uid
+            ),
+        ),
+        Err(e) => Check::new(NAME, CheckStatus::Fail, e.to_string()),
+    }
+}
+
+#[cfg(windows)]
+fn check_privilege_drop(_config: &ExecutorConfig) -> Check {
+    Check::new(
+        "privilege_drop",
+        CheckStatus::Skip,
+        "privilege dropping is Unix-only",
+    )
+}
+
+fn check_whitelist(whitelist: &CommandWhitelist) -> Check {
+    let mut commands = whitelist.commands();
+    commands.sort();
+
+    let invalid: Vec<String> = commands
+        .iter()
+        .filter_map(|command| Some((command, whitelist.get(command)?)))
+        .flat_map(|(command, entry)| {
+            entry
+                .allowed_arg_patterns
+                .iter()
+                .flatten()
+                .filter_map(move |pattern| {
+                    regex::Regex::new(pattern)
+                        .err()
+                        .map(|_| format!("{}: {}", command, pattern))
+                })
+        })
+        .collect();
+
+    if invalid.is_empty() {
+        Check::new(
+            "whitelist_patterns",
+            CheckStatus::Pass,
+            format!("{} commands, all patterns compile", commands.len()),
+        )
+    } else {
+        Check::new(
+            "whitelist_patterns",
+            CheckStatus::Fail,
+            format!("invalid patterns: {}", invalid.join("; ")),
+        )
+    }
+}
+
+/// Try a few things a confused or hostile request might do
+async fn check_escapes(config: &ExecutorConfig, whitelist: &CommandWhitelist) -> Vec<Check> {
+    let root = std::env::temp_dir().to_string_lossy().to_string();
+    let executor = CommandExecutor::new(
+        ExecutorConfig {
+            working_dir: Some(root.clone()),
+            allowed_roots: Some(vec![root]),
+            ..config.clone()
+        },
+        whitelist.clone(),
+    );
+
+    let mut checks = Vec::new();
+
+    let echo = whitelist.get("echo").map(|_| "echo").unwrap_or("list_dir");
+    checks.push(refused(
+        "shell_injection",
+        executor.execute(echo, &["x; id".to_string()]).await,
+        "shell metacharacters",
+        |e| matches!(e, ExecutorError::InvalidArguments(m) if m.contains("shell metacharacters")),
+    ));
+    checks.push(refused(
+        "unlisted_command",
+        executor
+            .execute("sh", &["-c".to_string(), "id".to_string()])
+            .await,
+        "a command outside the whitelist",
+        |e| matches!(e, ExecutorError::CommandNotWhitelisted(_)),
+    ));
+
+    checks.push(if whitelist.get("read_file").is_some() {
+        refused(
+            "path_traversal",
+            executor
+                .execute("read_file", &["../etc/passwd".to_string()])
+                .await,
+            "reading outside the allowed roots",
+            |e| {
+                matches!(e, ExecutorError::InvalidArguments(m)
+                    if m.contains("outside allowed roots"))
+            },
+        )
+    } else {
+        Check::new(
+            "path_traversal",
+            CheckStatus::Skip,
+            "read_file not whitelisted",
+        )
+    });
+
+    checks.push(check_shadow(config, whitelist).await);
+    checks
+}
+
+/// Pass only if `result` is the error `denied` expects; any other error
+/// means the protection was never reached
+fn refused<T>(
+    name: &str,
+    result: Result<T, ExecutorError>,
+    what: &str,
+    denied: impl Fn(&ExecutorError) -> bool,
+) -> Check {
+    match result {
+        Err(e) if denied(&e) => {
+            Check::new(name, CheckStatus::Pass, format!("{} refused: {}", what, e))
+        }
+        Err(e) => Check::new(
+            name,
+            CheckStatus::Fail,
+            format!("{} failed for another reason: {}", what, e),
+        ),
+        Ok(_) => Check::new(name, CheckStatus::Fail, format!("{} was allowed", what)),
+    }
+}
+
+/// A sandboxed command must not read a root-only file
+async fn check_shadow(config: &ExecutorConfig, whitelist: &CommandWhitelist) -> Check {
+    const NAME: &str = "sandboxed_read";
+    const SHADOW: &str = "/etc/shadow";
+
+    if !std::path::Path::new(SHADOW).exists() || whitelist.get("cat").is_none() {
+        return Check::new(NAME, CheckStatus::Skip, "no cat or /etc/shadow");
+    }
+    if !config.enable_sandbox {
+        return Check::new(NAME, CheckStatus::Skip, "sandbox disabled");
+    }
+    // Without root, /etc/shadow is unreadable with or without the sandbox
+    #[cfg(unix)]
+    if !nix::unistd::geteuid().is_root() {
+        return Check::new(NAME, CheckStatus::Skip, "not running as root");
+    }
+
+    // Without path scoping, only the sandbox stands in the way
+    let executor = CommandExecutor::new(
+        ExecutorConfig {
+            allowed_roots: Some(vec!["/".to_string()]),
+            ..config.clone()
+        },
+        whitelist.clone(),
+    );
+
+    match executor.execute("cat", &[SHADOW.to_string()]).await {
+        Ok(result) if result.success => Check::new(
+            NAME,
+            CheckStatus::Fail,
+            format!("sandboxed cat could read {}", SHADOW),
+        ),
+        Ok(_) => Check::new(
+            NAME,
+            CheckStatus::Pass,
+            format!("sandboxed cat cannot read {}", SHADOW),
+        ),
+        Err(e) => Check::new(NAME, CheckStatus::Skip, format!("not run: {}", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::whitelist::WhitelistEntry;
+
+    #[tokio::test]
+    async fn test_doctor_report() {
+        let config = ExecutorConfig {
+            enable_sandbox: false,
+            ..Default::default()
+        };
+        let mut whitelist = CommandWhitelist::default();
+
+        let report = run(&config, &whitelist).await;
+        assert!(report.passed(), "{:?}", report.checks);
+        for name in ["shell_injection", "unlisted_command", "path_traversal"] {
+            assert_eq!(
+                report.check(name).unwrap().status,
+                CheckStatus::Pass,
+                "{}",
+                name
+            );
+        }
+        assert_eq!(
+            report.check("sandboxed_read").unwrap().status,
+            CheckStatus::Skip
+        );
+        #[cfg(unix)]
+        assert_eq!(report.check("rlimits").unwrap().status, CheckStatus::Pass);
+
+        whitelist.add_command(
+            "bad",
+            WhitelistEntry {
+                command: "bad".to_string(),
+                allowed_arg_patterns: Some(vec!["(".to_string()]),
+                ..Default::default()
+            },
+        );
+        let report = run(&config, &whitelist).await;
+        assert!(!report.passed());
+        assert!(report
+            .check("whitelist_patterns")
+            .unwrap()
+            .detail
+            .contains("bad: ("));
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["checks"][0]["name"], "jail_backends");
+    }
+
+    #[test]
+    fn test_refused_needs_the_expected_denial() {
+        let denied = |e: &ExecutorError| matches!(e, ExecutorError::CommandNotWhitelisted(_));
+
+        let check = refused::<()>(
+            "unlisted_command",
+            Err(ExecutorError::CommandNotWhitelisted("sh".to_string())),
+            "a command outside the whitelist",
+            denied,
+        );
+        assert_eq!(check.status, CheckStatus::Pass);
+
+        let check = refused::<()>(
+            "unlisted_command",
+            Err(ExecutorError::SandboxError("bwrap missing".to_string())),
+            "a command outside the whitelist",
+            denied,
+        );
+        assert_eq!(check.status, CheckStatus::Fail);
+        assert!(check.detail.contains("bwrap missing"));
+
+        let check = refused(
+            "unlisted_command",
+            Ok(()),
+            "a command outside the whitelist",
+            denied,
+        );
+        assert_eq!(check.status, CheckStatus::Fail);
+    }
+}
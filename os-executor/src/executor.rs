@@ -1,17 +1,42 @@
 //! Command executor with timeout and resource limits
 
+use crate::approval::{ApprovalDecision, ApprovalProvider, ApprovalRequest};
+use crate::audit::{hash_output, AuditEntry, AuditLog, OutputHasher};
+use crate::builtin::{Builtin, BuiltinContext, BuiltinError};
+use crate::cache::{CacheLimits, ResultCache};
+use crate::capability::{Capability, CapabilityAuthority, CapabilityToken};
+use crate::globbing::{GlobError, GlobExpander};
+use crate::jail::Jail;
+use crate::path_scope::PathScope;
 use crate::platform::Platform;
-use crate::sandbox::{Sandbox, SandboxConfig};
-use crate::whitelist::{CommandWhitelist, WhitelistEntry};
+use crate::policy::{Policy, PolicyAction};
+use crate::postprocess::OutputPostProcessor;
+use crate::redact::{RedactionConfig, Redactor};
+use crate::registry;
+use crate::risk::{self, RiskAssessment, RiskLevel};
+use crate::sandbox::{
+    CgroupUsage, ProcessUsage, Sandbox, SandboxBackend, SandboxConfig, SandboxGuard,
+};
+#[cfg(unix)]
+use crate::sandbox::Credentials;
+use crate::seccomp::SeccompProfile;
+use crate::whitelist::{SharedWhitelist, WhitelistEntry};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::future::Future;
+use std::path::PathBuf;
 use std::process::Stdio;
-use std::time::Duration;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use thiserror::Error;
-use tokio::io::{AsyncBufReadExt, BufReader};
-use tokio::process::Command as TokioCommand;
-use tokio::time::timeout;
-use tracing::{debug, info};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStderr, ChildStdout, Command as TokioCommand};
+use tokio::sync::{mpsc, watch};
+use tokio::task::JoinHandle;
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_stream::Stream;
+use tracing::field::{self, Empty};
+use tracing::{debug, info, info_span, warn, Instrument};
 
 /// Executor errors
 #[derive(Error, Debug)]
@@ -36,6 +61,15 @@ pub enum ExecutorError {
 
     #[error("Resource limit exceeded: {0}")]
     ResourceLimitExceeded(String),
+
+    #[error("Command cancelled")]
+    Cancelled,
+
+    #[error("Confirmation denied: {0}")]
+    ConfirmationDenied(String),
+
+    #[error("Denied by policy: {0}")]
+    PolicyDenied(String),
 }
 
 /// Command execution result
@@ -61,6 +95,417 @@ pub struct CommandResult {
 
     /// Whether command succeeded
     pub success: bool,
+
+    /// Peak memory of the command's cgroup, including children (bytes)
+    #[serde(default)]
+    pub peak_memory_bytes: Option<u64>,
+
+    /// CPU time consumed by the command's cgroup (microseconds)
+    #[serde(default)]
+    pub cpu_usage_usec: Option<u64>,
+
+    /// Peak resident set size of the command (KiB)
+    #[serde(default)]
+    pub max_rss_kb: Option<u64>,
+
+    /// CPU time the command spent in user mode (milliseconds)
+    #[serde(default)]
+    pub user_cpu_ms: Option<u64>,
+
+    /// CPU time the command spent in the kernel (milliseconds)
+    #[serde(default)]
+    pub sys_cpu_ms: Option<u64>,
+
+    /// Why the command was killed, if it did not exit on its own
+    #[serde(default)]
+    pub was_killed_by: Option<KillReason>,
+
+    /// Condensed output from the command's post-processor, e.g. for speaking
+    #[serde(default)]
+    pub summary: Option<String>,
+
+    /// Whether this result was returned from the result cache
+    #[serde(default)]
+    pub cached: bool,
+
+    /// Risk level the request was scored at
+    #[serde(default)]
+    pub risk: Option<RiskLevel>,
+}
+
+/// Why a command was killed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KillReason {
+    /// Exceeded its CPU time limit
+    Timeout,
+
+    /// Killed by the OOM killer for exceeding its memory limit
+    Oom,
+
+    /// Terminated by another signal
+    Signal(i32),
+}
+
+impl std::fmt::Display for KillReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KillReason::Timeout => write!(f, "CPU time limit exceeded"),
+            KillReason::Oom => write!(f, "out of memory"),
+            KillReason::Signal(signal) => write!(f, "signal {}", signal),
+        }
+    }
+}
+
+/// A line of output from a streaming execution
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OutputLine {
+    /// Line from standard output (without the trailing newline)
+    Stdout(String),
+
+    /// Line from standard error (without the trailing newline)
+    Stderr(String),
+
+    /// Process exited; always the last item of a successful stream
+    Exited { exit_code: i32, duration_ms: u64 },
+}
+
+impl OutputLine {
+    /// Line text (empty for `Exited`)
+    pub fn text(&self) -> &str {
+        match self {
+            OutputLine::Stdout(line) | OutputLine::Stderr(line) => line,
+            OutputLine::Exited { .. } => "",
+        }
+    }
+}
+
+/// Resource limits applied to a command
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResourceLimits {
+    /// CPU time limit (`RLIMIT_CPU`, seconds)
+    pub cpu_time_secs: Option<u64>,
+
+    /// Address space limit (`RLIMIT_AS`, or Job Object memory on Windows; MB)
+    pub memory_mb: Option<u64>,
+
+    /// Process cap (cgroup `pids.max` / Job Object)
+    pub max_processes: Option<u32>,
+
+    /// Hard CPU rate cap (percent of one CPU)
+    pub cpu_rate_percent: Option<u32>,
+
+    /// cgroup v2 root the command's cgroup is created under
+    pub cgroup_root: Option<String>,
+}
+
+/// What [`CommandExecutor::plan`] would run, without running it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExecutionPlan {
+    /// Command as requested
+    pub command: String,
+
+    /// Arguments as requested
+    pub args: Vec<String>,
+
+    /// Binary the command resolved to
+    pub resolved_path: String,
+
+    /// Exact argv that would be spawned (including any jail wrapper)
+    pub argv: Vec<String>,
+
+    /// Sandbox backend ("disabled" when sandboxing is off)
+    pub sandbox_backend: String,
+
+    /// Resource limits (all unset when sandboxing is off)
+    pub limits: ResourceLimits,
+
+    /// Syscall filter (Linux, sandboxed only)
+    pub seccomp_profile: SeccompProfile,
+
+    /// Readable paths when confined (None = unconfined)
+    pub allowed_paths: Option<Vec<String>>,
+
+    /// Writable paths when confined
+    pub writable_paths: Option<Vec<String>>,
+
+    /// Whether the command runs without network access (its own network
+    /// namespace, or a jail without network)
+    #[serde(default)]
+    pub network_isolated: bool,
+
+    /// Environment variables set on top of the inherited environment
+    pub env: HashMap<String, String>,
+
+    /// Working directory (None = the executor's own)
+    pub working_dir: Option<String>,
+
+    /// Timeout (seconds)
+    pub timeout_secs: u64,
+
+    /// Output limit (bytes)
+    pub max_output_bytes: usize,
+
+    /// Whether the approval provider will be asked first
+    pub requires_confirmation: bool,
+
+    /// Risk score of the request
+    #[serde(default)]
+    pub risk: RiskAssessment,
+}
+
+impl ExecutionPlan {
+    /// One-sentence description suitable for showing or speaking to the user
+    pub fn summary(&self) -> String {
+        let sandbox = match self.sandbox_backend.as_str() {
+            "disabled" => "without a sandbox".to_string(),
+            "none" => "in the built-in sandbox".to_string(),
+            backend => format!("in a {} sandbox", backend),
+        };
+
+        format!(
+            "I will run {} {}, stopping it after {} seconds{}.",
+            self.argv_display(),
+            sandbox,
+            self.timeout_secs,
+            if self.requires_confirmation {
+                ", once you confirm"
+            } else {
+                ""
+            }
+        )
+    }
+
+    fn argv_display(&self) -> String {
+        std::iter::once(self.command.as_str())
+            .chain(self.args.iter().map(String::as_str))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// One command in a [`Pipeline`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PipelineStage {
+    pub command: String,
+    pub args: Vec<String>,
+}
+
+/// Whitelisted commands chained stdout-to-stdin without a shell
+///
+/// Every stage is validated against the whitelist, and every stage after the
+/// first must set `allow_stdin`. The timeout and output limit apply to the
+/// pipeline as a whole.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Pipeline {
+    pub stages: Vec<PipelineStage>,
+}
+
+impl Pipeline {
+    /// Create an empty pipeline
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append a stage
+    pub fn pipe(mut self, command: impl Into<String>, args: &[&str]) -> Self {
+        self.stages.push(PipelineStage {
+            command: command.into(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+        });
+        self
+    }
+}
+
+impl std::fmt::Display for Pipeline {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let stages: Vec<String> = self
+            .stages
+            .iter()
+            .map(|stage| {
+                std::iter::once(stage.command.as_str())
+                    .chain(stage.args.iter().map(String::as_str))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect();
+
+        write!(f, "{}", stages.join(" | "))
+    }
+}
+
+/// Buffered lines between the reader task and the stream consumer
+const STREAMING_CHANNEL_CAPACITY: usize = 64;
+
+/// Raw output of a finished process
+struct ProcessOutput {
+    stdout: String,
+    stderr: String,
+    exit_code: i32,
+    peak_memory_bytes: Option<u64>,
+    cpu_usage_usec: Option<u64>,
+    usage: ProcessUsage,
+    was_killed_by: Option<KillReason>,
+}
+
+impl ProcessOutput {
+    /// Build the result, masking secrets in the output
+    fn into_result(
+        self,
+        command: String,
+        args: Vec<String>,
+        duration_ms: u64,
+        redactor: &Redactor,
+    ) -> CommandResult {
+        CommandResult {
+            command,
+            args,
+            stdout: redactor.redact(&self.stdout).into_owned(),
+            stderr: redactor.redact(&self.stderr).into_owned(),
+            success: self.exit_code == 0,
+            exit_code: self.exit_code,
+            duration_ms,
+            peak_memory_bytes: self.peak_memory_bytes,
+            cpu_usage_usec: self.cpu_usage_usec,
+            max_rss_kb: self.usage.max_rss_kb,
+            user_cpu_ms: self.usage.user_cpu_ms,
+            sys_cpu_ms: self.usage.sys_cpu_ms,
+            was_killed_by: self.was_killed_by,
+            summary: None,
+            cached: false,
+            risk: None,
+        }
+    }
+}
+
+/// Cancels a running command; cheap to clone and share between tasks
+#[derive(Debug, Clone)]
+pub struct Canceller {
+    tx: Arc<watch::Sender<bool>>,
+}
+
+impl Canceller {
+    /// Kill the command's whole process tree
+    ///
+    /// The command's result becomes [`ExecutorError::Cancelled`]. Has no
+    /// effect once the command has finished.
+    pub fn cancel(&self) {
+        self.tx.send_replace(true);
+    }
+
+    /// Whether cancellation has been requested
+    pub fn is_cancelled(&self) -> bool {
+        *self.tx.borrow()
+    }
+}
+
+/// Handle to a command started with [`CommandExecutor::start`]
+pub struct ExecutionHandle {
+    pid: Option<u32>,
+    canceller: Canceller,
+    task: JoinHandle<Result<CommandResult, ExecutorError>>,
+}
+
+impl ExecutionHandle {
+    /// OS process id of the command (the process group leader on Unix)
+    pub fn pid(&self) -> Option<u32> {
+        self.pid
+    }
+
+    /// Kill the command's whole process tree
+    pub fn cancel(&self) {
+        self.canceller.cancel();
+    }
+
+    /// Canceller that can be handed to another task
+    pub fn canceller(&self) -> Canceller {
+        self.canceller.clone()
+    }
+
+    /// Wait for the command to finish
+    pub async fn wait(self) -> Result<CommandResult, ExecutorError> {
+        self.task
+            .await
+            .map_err(|e| ExecutorError::ExecutionFailed(e.to_string()))?
+    }
+}
+
+/// A [`CommandExecutor`] acting for the holder of a [`CapabilityToken`]
+///
+/// Made with [`CommandExecutor::authorized`]. Each request verifies the token
+/// and runs within its scope and ceilings.
+pub struct AuthorizedExecutor<'a> {
+    executor: &'a CommandExecutor,
+    token: CapabilityToken,
+}
+
+impl AuthorizedExecutor<'_> {
+    /// The token requests are made with
+    pub fn token(&self) -> &CapabilityToken {
+        &self.token
+    }
+
+    /// [`CommandExecutor::execute`] within the token's scope
+    pub async fn execute(
+        &self,
+        command: &str,
+        args: &[String],
+    ) -> Result<CommandResult, ExecutorError> {
+        self.executor
+            .execute_with_capability(command, args, &self.token)
+            .await
+    }
+
+    /// [`CommandExecutor::execute_with_stdin`] within the token's scope
+    pub async fn execute_with_stdin<R>(
+        &self,
+        command: &str,
+        args: &[String],
+        input: R,
+    ) -> Result<CommandResult, ExecutorError>
+    where
+        R: AsyncRead + Unpin,
+    {
+        let run = self
+            .executor
+            .run_with_stdin(command, args, input, Some(&self.token));
+        self.executor.traced(command, args, run).await
+    }
+
+    /// [`CommandExecutor::execute_pipeline`]; the token must cover every stage
+    pub async fn execute_pipeline(
+        &self,
+        pipeline: &Pipeline,
+    ) -> Result<CommandResult, ExecutorError> {
+        self.executor.pipeline_as(pipeline, Some(&self.token)).await
+    }
+
+    /// [`CommandExecutor::start`] within the token's scope
+    pub async fn start(
+        &self,
+        command: &str,
+        args: &[String],
+    ) -> Result<ExecutionHandle, ExecutorError> {
+        self.executor
+            .start_as(command, args, Some(&self.token))
+            .await
+    }
+
+    /// [`CommandExecutor::execute_streaming`] within the token's scope
+    pub async fn execute_streaming(
+        &self,
+        command: &str,
+        args: &[String],
+    ) -> Result<impl Stream<Item = Result<OutputLine, ExecutorError>>, ExecutorError> {
+        self.executor
+            .streaming_as(command, args, Some(&self.token))
+            .await
+    }
+
+    /// [`CommandExecutor::plan`] within the token's scope
+    pub fn plan(&self, command: &str, args: &[String]) -> Result<ExecutionPlan, ExecutorError> {
+        self.executor.plan_as(command, args, Some(&self.token))
+    }
 }
 
 /// Executor configuration
@@ -83,6 +528,53 @@ pub struct ExecutorConfig {
 
     /// Enable shell execution (DANGEROUS)
     pub allow_shell: bool,
+
+    /// Sandbox settings used when `enable_sandbox` is set
+    #[serde(default)]
+    pub sandbox: SandboxConfig,
+
+    /// Maximum input accepted by `execute_with_stdin` (bytes)
+    #[serde(default = "default_max_stdin_bytes")]
+    pub max_stdin_bytes: usize,
+
+    /// Directories path arguments must resolve inside (`~` expands to
+    /// `$HOME`). `None` confines them to the working directory; `["/"]`
+    /// allows any path.
+    #[serde(default)]
+    pub allowed_roots: Option<Vec<String>>,
+
+    /// Maximum number of paths glob patterns may expand to in one request
+    /// (entries with `expand_globs`)
+    #[serde(default = "default_max_glob_paths")]
+    pub max_glob_paths: usize,
+
+    /// Secrets masked in stdout/stderr before they are returned or audited
+    #[serde(default)]
+    pub redaction: RedactionConfig,
+
+    /// Registry keys the `reg_query` builtin may read, with their subkeys
+    /// (Windows)
+    #[serde(default = "registry::default_allowlist")]
+    pub registry_allowlist: Vec<String>,
+
+    /// Run commands in a fresh per-executor directory below this one instead
+    /// of `working_dir`. Relative path arguments must stay inside it, and it
+    /// is deleted when the executor is dropped.
+    #[serde(default)]
+    pub jail_root: Option<String>,
+
+    /// Also chroot sandboxed commands into the jail (Unix, as root, in-process
+    /// backend only). The jail must then contain the commands' binaries.
+    #[serde(default)]
+    pub jail_chroot: bool,
+}
+
+fn default_max_stdin_bytes() -> usize {
+    1024 * 1024 // 1MB
+}
+
+fn default_max_glob_paths() -> usize {
+    256
 }
 
 impl Default for ExecutorConfig {
@@ -94,6 +586,28 @@ impl Default for ExecutorConfig {
             working_dir: None,
             env_vars: HashMap::new(),
             allow_shell: false,
+            sandbox: SandboxConfig::default(),
+            max_stdin_bytes: default_max_stdin_bytes(),
+            allowed_roots: None,
+            max_glob_paths: default_max_glob_paths(),
+            redaction: RedactionConfig::default(),
+            registry_allowlist: registry::default_allowlist(),
+            jail_root: None,
+            jail_chroot: false,
+        }
+    }
+}
+
+impl From<&aether_config::OsExecutorSection> for ExecutorConfig {
+    fn from(section: &aether_config::OsExecutorSection) -> Self {
+        Self {
+            max_timeout_secs: section.max_timeout_secs,
+            enable_sandbox: section.enable_sandbox,
+            max_output_bytes: section.max_output_bytes,
+            working_dir: section.working_dir.clone(),
+            allow_shell: section.allow_shell,
+            allowed_roots: section.allowed_roots.clone(),
+            ..Default::default()
         }
     }
 }
@@ -101,336 +615,3377 @@ impl Default for ExecutorConfig {
 /// Command executor
 pub struct CommandExecutor {
     config: ExecutorConfig,
-    whitelist: CommandWhitelist,
+    whitelist: SharedWhitelist,
     sandbox: Option<Sandbox>,
     platform: Platform,
+    auditor: Option<Auditor>,
+    approval: Option<Arc<dyn ApprovalProvider>>,
+    policy: Option<Policy>,
+    capabilities: Option<CapabilityAuthority>,
+    post_processors: HashMap<String, Arc<dyn OutputPostProcessor>>,
+    cache: Option<Arc<ResultCache>>,
+    redactor: Arc<Redactor>,
+    /// Why the redaction patterns could not be compiled (every command then
+    /// fails)
+    redaction_error: Option<String>,
+    /// Session directory, or why it could not be created (every command then
+    /// fails)
+    jail: Option<Result<Arc<Jail>, String>>,
+}
+
+/// Audit log handle shared with background execution tasks
+#[derive(Clone)]
+struct Auditor {
+    log: Arc<AuditLog>,
+    sandbox_backend: &'static str,
+}
+
+impl Auditor {
+    fn record(
+        &self,
+        command: &str,
+        args: &[String],
+        result: &Result<CommandResult, ExecutorError>,
+        start_time: Instant,
+    ) {
+        match result {
+            Ok(result) => self.record_outcome(
+                command,
+                args,
+                Ok((result.exit_code, hash_output(&result.stdout, &result.stderr))),
+                result.duration_ms,
+            ),
+            Err(e) => self.record_outcome(
+                command,
+                args,
+                Err(e),
+                start_time.elapsed().as_millis() as u64,
+            ),
+        }
+    }
+
+    /// Record an exit code and output hash, or the error that stopped the command
+    fn record_outcome(
+        &self,
+        command: &str,
+        args: &[String],
+        outcome: Result<(i32, String), &ExecutorError>,
+        duration_ms: u64,
+    ) {
+        let (exit_code, output_hash, error) = match outcome {
+            Ok((exit_code, output_hash)) => (Some(exit_code), Some(output_hash), None),
+            Err(e) => (None, None, Some(e.to_string())),
+        };
+
+        let entry = AuditEntry {
+            command: command.to_string(),
+            args: args.to_vec(),
+            exit_code,
+            duration_ms,
+            sandbox_backend: self.sandbox_backend.to_string(),
+            output_hash,
+            error,
+        };
+
+        if let Err(e) = self.log.record(entry) {
+            warn!("Failed to write audit record for {}: {}", command, e);
+        }
+    }
 }
 
 impl CommandExecutor {
     /// Create new executor
-    pub fn new(config: ExecutorConfig, whitelist: CommandWhitelist) -> Self {
+    ///
+    /// Pass a [`CommandWhitelist`](crate::CommandWhitelist), or a
+    /// [`SharedWhitelist`] from a [`WhitelistWatcher`](crate::WhitelistWatcher)
+    /// to pick up edits to the whitelist file without a restart.
+    pub fn new(config: ExecutorConfig, whitelist: impl Into<SharedWhitelist>) -> Self {
+        let jail = config.jail_root.as_ref().map(|root| {
+            Jail::create(root)
+                .map(Arc::new)
+                .map_err(|e| format!("Failed to create jail in {}: {}", root, e))
+        });
+
+        let mut sandbox_config = config.sandbox.clone();
+        if let Some(Ok(jail)) = &jail {
+            // Sandboxed commands run as `drop_to_user` and must be able to write there
+            #[cfg(unix)]
+            if config.enable_sandbox && nix::unistd::geteuid().is_root() {
+                if let Ok(Some(credentials)) = Credentials::resolve(&sandbox_config) {
+                    if let Err(e) = jail.chown(credentials.uid, credentials.gid) {
+                        warn!("Failed to hand jail to uid {}: {}", credentials.uid, e);
+                    }
+                }
+            }
+
+            if config.jail_chroot {
+                sandbox_config.chroot_dir = Some(jail.path().to_string_lossy().to_string());
+            }
+        }
+
         let sandbox = if config.enable_sandbox {
-            Some(Sandbox::new(SandboxConfig::default()))
+            Some(Sandbox::new(sandbox_config))
         } else {
             None
         };
 
+        let (redactor, redaction_error) = match Redactor::new(&config.redaction) {
+            Ok(redactor) => (redactor, None),
+            Err(e) => (
+                Redactor::default(),
+                Some(format!("Invalid redaction pattern: {}", e)),
+            ),
+        };
+
         Self {
             config,
-            whitelist,
+            whitelist: whitelist.into(),
             sandbox,
             platform: Platform::current(),
+            auditor: None,
+            approval: None,
+            policy: None,
+            capabilities: None,
+            post_processors: HashMap::new(),
+            cache: None,
+            redactor: Arc::new(redactor),
+            redaction_error,
+            jail,
         }
     }
 
+    /// Ask `provider` before running entries with `requires_confirmation`
+    pub fn with_approval_provider(mut self, provider: Arc<dyn ApprovalProvider>) -> Self {
+        self.approval = Some(provider);
+        self
+    }
+
+    /// Evaluate `policy` for every request, after the whitelist check
+    ///
+    /// Denied requests fail with [`ExecutorError::PolicyDenied`]; requests the
+    /// policy marks `confirm` go through the approval provider.
+    pub fn with_policy(mut self, policy: Policy) -> Self {
+        self.policy = Some(policy);
+        self
+    }
+
+    /// Require a token verified by `authority` for every command
+    ///
+    /// Once set, requests without a token (`execute`, `start`, ...) fail with
+    /// [`ExecutorError::PermissionDenied`]; token holders go through
+    /// [`execute_with_capability`](Self::execute_with_capability) or
+    /// [`authorized`](Self::authorized).
+    pub fn with_capability_authority(mut self, authority: CapabilityAuthority) -> Self {
+        self.capabilities = Some(authority);
+        self
+    }
+
+    /// Summarize `command`'s successful results with `processor`, in place of
+    /// the entry's `post_process`
+    pub fn with_post_processor(
+        mut self,
+        command: &str,
+        processor: Arc<dyn OutputPostProcessor>,
+    ) -> Self {
+        self.post_processors.insert(command.to_string(), processor);
+        self
+    }
+
+    /// Serve repeated runs of `cacheable` entries from `cache`
+    ///
+    /// Requests are still validated and confirmed; only successful results
+    /// from `execute` and `execute_with_capability` are cached.
+    pub fn with_result_cache(mut self, cache: Arc<ResultCache>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Record every command run (or refused) by this executor in `log`
+    pub fn with_audit_log(mut self, log: Arc<AuditLog>) -> Self {
+        let sandbox_backend = match &self.sandbox {
+            Some(sandbox) => sandbox.backend().name(),
+            None => "disabled",
+        };
+
+        self.auditor = Some(Auditor {
+            log,
+            sandbox_backend,
+        });
+        self
+    }
+
     /// Execute command
     pub async fn execute(
         &self,
         command: &str,
         args: &[String],
     ) -> Result<CommandResult, ExecutorError> {
-        let start_time = std::time::Instant::now();
+        self.traced(command, args, self.run_command(command, args, None))
+            .await
+    }
 
-        // Validate command is whitelisted
-        let whitelist_entry = self
-            .whitelist
-            .get(command)
-            .ok_or_else(|| ExecutorError::CommandNotWhitelisted(command.to_string()))?;
+    /// Run commands on behalf of the holder of `token`
+    ///
+    /// Every request made through the returned executor is checked against the
+    /// token as for [`execute_with_capability`](Self::execute_with_capability).
+    pub fn authorized(&self, token: CapabilityToken) -> AuthorizedExecutor<'_> {
+        AuthorizedExecutor {
+            executor: self,
+            token,
+        }
+    }
 
-        // Validate arguments
-        self.validate_args(args, whitelist_entry)?;
+    /// Execute command on behalf of the holder of `token`
+    ///
+    /// The token must verify against the executor's
+    /// [`CapabilityAuthority`] and name the command; its resource ceilings
+    /// tighten the whitelist entry's limits. Calls outside the token's scope
+    /// fail with [`ExecutorError::PermissionDenied`].
+    pub async fn execute_with_capability(
+        &self,
+        command: &str,
+        args: &[String],
+        token: &CapabilityToken,
+    ) -> Result<CommandResult, ExecutorError> {
+        self.traced(command, args, self.run_command(command, args, Some(token)))
+            .await
+    }
 
-        info!(
-            "Executing command: {} with {} args",
+    /// Execute command with `input` piped to its stdin
+    ///
+    /// The entry must set `allow_stdin`. Input is read fully before the command
+    /// is spawned and rejected if it exceeds `max_stdin_bytes`.
+    pub async fn execute_with_stdin<R>(
+        &self,
+        command: &str,
+        args: &[String],
+        input: R,
+    ) -> Result<CommandResult, ExecutorError>
+    where
+        R: AsyncRead + Unpin,
+    {
+        self.traced(
             command,
-            args.len()
-        );
-
-        // Execute with timeout
-        let result = timeout(
-            Duration::from_secs(self.config.max_timeout_secs),
-            self.execute_internal(command, args, whitelist_entry),
+            args,
+            self.run_with_stdin(command, args, input, None),
         )
         .await
-        .map_err(|_| ExecutorError::TimeoutExceeded(self.config.max_timeout_secs))?;
+    }
 
-        let duration_ms = start_time.elapsed().as_millis() as u64;
+    /// Execute a pipeline of whitelisted commands
+    ///
+    /// Stages run concurrently, connected by OS pipes. The result carries the
+    /// last stage's stdout, every stage's stderr, and the exit code of the
+    /// last stage that failed (like `set -o pipefail`). Cgroup usage is summed
+    /// across stages.
+    pub async fn execute_pipeline(
+        &self,
+        pipeline: &Pipeline,
+    ) -> Result<CommandResult, ExecutorError> {
+        self.pipeline_as(pipeline, None).await
+    }
 
-        match result {
-            Ok((stdout, stderr, exit_code)) => {
-                let success = exit_code == 0;
+    async fn pipeline_as(
+        &self,
+        pipeline: &Pipeline,
+        token: Option<&CapabilityToken>,
+    ) -> Result<CommandResult, ExecutorError> {
+        let start_time = Instant::now();
+        let result = self.run_pipeline(pipeline, token).await;
+        self.audit(&pipeline.to_string(), &[], &result, start_time);
+        result
+    }
 
-                Ok(CommandResult {
-                    command: command.to_string(),
-                    args: args.to_vec(),
-                    stdout,
-                    stderr,
-                    exit_code,
-                    duration_ms,
-                    success,
-                })
+    async fn run_command(
+        &self,
+        command: &str,
+        args: &[String],
+        token: Option<&CapabilityToken>,
+    ) -> Result<CommandResult, ExecutorError> {
+        let (whitelist_entry, args) = self.validate_request(command, args, token)?;
+        let args = args.as_slice();
+        self.confirm(command, args, &whitelist_entry).await?;
+
+        // Keyed on the entry's limits, which a capability may have narrowed
+        let cache = match &self.cache {
+            Some(cache) if whitelist_entry.cacheable => {
+                Some((cache, self.base_dir()?, self.cache_limits(&whitelist_entry)))
+            }
+            _ => None,
+        };
+        if let Some((cache, cwd, limits)) = &cache {
+            if let Some(result) = cache.get(command, args, cwd, limits) {
+                return Ok(result);
             }
-            Err(e) => Err(e),
         }
+
+        let mut result = self.run_entry(command, args, &whitelist_entry).await?;
+        result.risk = Some(self.assess_risk(command, args, &whitelist_entry).level);
+
+        if let Some((cache, cwd, limits)) = &cache {
+            cache.insert(
+                &result,
+                cwd,
+                limits,
+                cache.ttl(whitelist_entry.cache_ttl_secs),
+            );
+        }
+
+        Ok(result)
     }
 
-    /// Execute command internally
-    async fn execute_internal(
+    /// Run a validated, confirmed request
+    async fn run_entry(
         &self,
         command: &str,
         args: &[String],
-        _entry: &WhitelistEntry,
-    ) -> Result<(String, String, i32), ExecutorError> {
-        // Resolve full command path
-        let cmd_path = self.resolve_command_path(command)?;
+        whitelist_entry: &WhitelistEntry,
+    ) -> Result<CommandResult, ExecutorError> {
+        let start_time = Instant::now();
 
-        debug!("Resolved command path: {}", cmd_path);
+        if let Some(builtin) = whitelist_entry.builtin {
+            return self.run_builtin(builtin, command, args, whitelist_entry).await;
+        }
 
-        // Build command
-        let mut cmd = if self.config.enable_sandbox && self.sandbox.is_some() {
-            // Execute through sandbox
-            self.build_sandboxed_command(&cmd_path, args)?
-        } else {
-            // Direct execution
-            let mut c = TokioCommand::new(&cmd_path);
-            c.args(args);
-            c
-        };
+        info!(
+            "Executing command: {} with {} args",
+            command,
+            args.len()
+        );
 
-        // Set working directory
-        if let Some(ref wd) = self.config.working_dir {
-            cmd.current_dir(wd);
-        }
+        let (child, sandbox_guard) =
+            self.spawn_process(command, args, whitelist_entry, Stdio::null())?;
 
-        // Set environment variables
-        for (key, value) in &self.config.env_vars {
-            cmd.env(key, value);
-        }
+        // Execute with timeout
+        let output = collect_output(
+            child,
+            sandbox_guard,
+            self.output_limit_for(whitelist_entry),
+            self.timeout_for(whitelist_entry),
+            None,
+        )
+        .await?;
 
-        // Configure stdio
-        cmd.stdin(Stdio::null())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped());
+        let duration_ms = start_time.elapsed().as_millis() as u64;
 
-        // Spawn process
-        let mut child = cmd
-            .spawn()
+        let result =
+            output.into_result(command.to_string(), args.to_vec(), duration_ms, &self.redactor);
+        Ok(summarize(self.post_processor_for(command, whitelist_entry), result))
+    }
+
+    /// Run a built-in command on a blocking thread
+    ///
+    /// Paths are confined to `allowed_roots`, or to the working directory when
+    /// none are configured. Missing or unreadable files give exit code 1 with
+    /// the error on stderr, as the external commands would.
+    async fn run_builtin(
+        &self,
+        builtin: Builtin,
+        command: &str,
+        args: &[String],
+        entry: &WhitelistEntry,
+    ) -> Result<CommandResult, ExecutorError> {
+        let start_time = Instant::now();
+        let timeout_secs = self.timeout_for(entry);
+        let base_dir = self.base_dir()?;
+        let ctx = BuiltinContext {
+            scope: PathScope::new(&self.path_roots(&base_dir), base_dir),
+            max_output_bytes: self.output_limit_for(entry),
+            deadline: Some(start_time + Duration::from_secs(timeout_secs)),
+            registry_allowlist: self.config.registry_allowlist.clone(),
+        };
+
+        info!("Running builtin: {} with {} args", command, args.len());
+
+        let run_args = args.to_vec();
+        let output = tokio::task::spawn_blocking(move || builtin.run(&run_args, &ctx))
+            .await
             .map_err(|e| ExecutorError::ExecutionFailed(e.to_string()))?;
 
-        // Read stdout
-        let stdout_handle = child.stdout.take().ok_or_else(|| {
-            ExecutorError::ExecutionFailed("Failed to capture stdout".to_string())
-        })?;
+        let (stdout, stderr, exit_code) = match output {
+            Ok(stdout) => (stdout, String::new(), 0),
+            Err(e @ (BuiltinError::Io { .. } | BuiltinError::WindowsApi { .. })) => {
+                (String::new(), format!("{}: {}\n", command, e), 1)
+            }
+            Err(BuiltinError::TimedOut) => {
+                return Err(ExecutorError::TimeoutExceeded(timeout_secs))
+            }
+            Err(e @ (BuiltinError::FileTooLarge { .. } | BuiltinError::OutputLimit(_))) => {
+                return Err(ExecutorError::ResourceLimitExceeded(e.to_string()))
+            }
+            Err(e @ BuiltinError::RegistryNotAllowed(_)) => {
+                return Err(ExecutorError::PermissionDenied(e.to_string()))
+            }
+            Err(e @ BuiltinError::Unsupported(_)) => {
+                return Err(ExecutorError::ExecutionFailed(e.to_string()))
+            }
+            Err(e) => return Err(ExecutorError::InvalidArguments(e.to_string())),
+        };
 
-        let stderr_handle = child.stderr.take().ok_or_else(|| {
-            ExecutorError::ExecutionFailed("Failed to capture stderr".to_string())
+        let output = ProcessOutput {
+            stdout,
+            stderr,
+            exit_code,
+            peak_memory_bytes: None,
+            cpu_usage_usec: None,
+            usage: ProcessUsage::default(),
+            was_killed_by: None,
+        };
+        let duration_ms = start_time.elapsed().as_millis() as u64;
+
+        let result =
+            output.into_result(command.to_string(), args.to_vec(), duration_ms, &self.redactor);
+        Ok(summarize(self.post_processor_for(command, entry), result))
+    }
+
+    async fn run_with_stdin<R>(
+        &self,
+        command: &str,
+        args: &[String],
+        input: R,
+        token: Option<&CapabilityToken>,
+    ) -> Result<CommandResult, ExecutorError>
+    where
+        R: AsyncRead + Unpin,
+    {
+        let start_time = Instant::now();
+
+        let (whitelist_entry, args) = self.validate_request(command, args, token)?;
+        let args = args.as_slice();
+
+        if !whitelist_entry.allow_stdin {
+            return Err(ExecutorError::PermissionDenied(format!(
+                "{} does not accept stdin",
+                command
+            )));
+        }
+
+        let input = read_limited(input, self.config.max_stdin_bytes).await?;
+        self.confirm(command, args, &whitelist_entry).await?;
+
+        info!(
+            "Executing command: {} with {} args and {} bytes of stdin",
+            command,
+            args.len(),
+            input.len()
+        );
+
+        let (mut child, sandbox_guard) =
+            self.spawn_process(command, args, &whitelist_entry, Stdio::piped())?;
+
+        let mut stdin = child.stdin.take().ok_or_else(|| {
+            ExecutorError::ExecutionFailed("Failed to capture stdin".to_string())
         })?;
 
-        // Read output streams
-        let stdout_task = tokio::spawn(async move {
-            let reader = BufReader::new(stdout_handle);
-            let mut lines = reader.lines();
-            let mut output = String::new();
+        // Written concurrently with output collection so full pipes can't deadlock.
+        // Dropping stdin afterwards signals EOF.
+        tokio::spawn(async move {
+            if let Err(e) = stdin.write_all(&input).await {
+                // Commands like `head` may exit without consuming all input
+                debug!("Stopped writing stdin: {}", e);
+            }
+        });
+
+        let output = collect_output(
+            child,
+            sandbox_guard,
+            self.output_limit_for(&whitelist_entry),
+            self.timeout_for(&whitelist_entry),
+            None,
+        )
+        .await?;
+
+        let duration_ms = start_time.elapsed().as_millis() as u64;
+
+        let mut result = output.into_result(
+            command.to_string(),
+            args.to_vec(),
+            duration_ms,
+            &self.redactor,
+        );
+        result.risk = Some(self.assess_risk(command, args, &whitelist_entry).level);
+        Ok(summarize(self.post_processor_for(command, &whitelist_entry), result))
+    }
+
+    /// Start command in the background and return a handle to cancel or await it
+    ///
+    /// Validation and spawn errors are returned immediately; the timeout and
+    /// output limits apply as for [`execute`](Self::execute).
+    pub async fn start(
+        &self,
+        command: &str,
+        args: &[String],
+    ) -> Result<ExecutionHandle, ExecutorError> {
+        self.start_as(command, args, None).await
+    }
+
+    async fn start_as(
+        &self,
+        command: &str,
+        args: &[String],
+        token: Option<&CapabilityToken>,
+    ) -> Result<ExecutionHandle, ExecutorError> {
+        let start_time = Instant::now();
+
+        let (child, sandbox_guard, entry) = match self.launch(command, args, token).await {
+            Ok(spawned) => spawned,
+            Err(e) => {
+                self.audit_error(command, args, &e, start_time);
+                return Err(e);
+            }
+        };
+        let pid = child.id();
+        let risk = self.assess_risk(command, args, &entry).level;
+
+        info!(
+            "Started command: {} with {} args",
+            command,
+            args.len()
+        );
 
-            while let Ok(Some(line)) = lines.next_line().await {
-                output.push_str(&line);
-                output.push('\n');
+        let (cancel_tx, cancel_rx) = watch::channel(false);
+        let max_output_bytes = self.output_limit_for(&entry);
+        let timeout_secs = self.timeout_for(&entry);
+        let auditor = self.auditor.clone();
+        let redactor = self.redactor.clone();
+        let command = command.to_string();
+        let args = args.to_vec();
+
+        let post_processor = self.post_processor_for(&command, &entry);
+
+        let task = tokio::spawn(async move {
+            let result = collect_output(
+                child,
+                sandbox_guard,
+                max_output_bytes,
+                timeout_secs,
+                Some(cancel_rx),
+            )
+            .await
+            .map(|output| {
+                let duration_ms = start_time.elapsed().as_millis() as u64;
+                let mut result =
+                    output.into_result(command.clone(), args.clone(), duration_ms, &redactor);
+                result.risk = Some(risk);
+                summarize(post_processor, result)
+            });
+
+            if let Some(auditor) = auditor {
+                auditor.record(&command, &args, &result, start_time);
             }
 
-            output
+            result
         });
 
-        let stderr_task = tokio::spawn(async move {
-            let reader = BufReader::new(stderr_handle);
-            let mut lines = reader.lines();
-            let mut output = String::new();
+        Ok(ExecutionHandle {
+            pid,
+            canceller: Canceller {
+                tx: Arc::new(cancel_tx),
+            },
+            task,
+        })
+    }
+
+    async fn run_pipeline(
+        &self,
+        pipeline: &Pipeline,
+        token: Option<&CapabilityToken>,
+    ) -> Result<CommandResult, ExecutorError> {
+        let start_time = Instant::now();
+
+        if pipeline.stages.is_empty() {
+            return Err(ExecutorError::InvalidArguments(
+                "Pipeline has no stages".to_string(),
+            ));
+        }
 
-            while let Ok(Some(line)) = lines.next_line().await {
-                output.push_str(&line);
-                output.push('\n');
+        let mut entries = Vec::with_capacity(pipeline.stages.len());
+        let mut stage_args = Vec::with_capacity(pipeline.stages.len());
+        let mut risk = RiskLevel::Low;
+        for (i, stage) in pipeline.stages.iter().enumerate() {
+            let (entry, args) = self.validate_request(&stage.command, &stage.args, token)?;
+            risk = risk.max(self.assess_risk(&stage.command, &args, &entry).level);
+
+            if i > 0 && !entry.allow_stdin {
+                return Err(ExecutorError::PermissionDenied(format!(
+                    "{} does not accept stdin",
+                    stage.command
+                )));
             }
 
-            output
-        });
+            entries.push(entry);
+            stage_args.push(args);
+        }
 
-        // Wait for process
-        let status = child
-            .wait()
-            .await
-            .map_err(|e| ExecutorError::ExecutionFailed(e.to_string()))?;
+        for ((stage, args), entry) in pipeline.stages.iter().zip(&stage_args).zip(&entries) {
+            self.confirm(&stage.command, args, entry).await?;
+        }
 
-        // Collect output
-        let stdout = stdout_task
-            .await
-            .map_err(|e| ExecutorError::ExecutionFailed(e.to_string()))?;
-        let stderr = stderr_task
-            .await
-            .map_err(|e| ExecutorError::ExecutionFailed(e.to_string()))?;
+        info!("Executing pipeline: {}", pipeline);
+
+        // The pipeline runs as long as its slowest stage may; its output is the
+        // last stage's
+        let timeout_secs = entries
+            .iter()
+            .map(|entry| self.timeout_for(entry))
+            .max()
+            .unwrap_or(self.config.max_timeout_secs);
+        let max_output_bytes = entries
+            .last()
+            .map_or(self.config.max_output_bytes, |entry| self.output_limit_for(entry));
+
+        let mut children: Vec<(Child, Option<SandboxGuard>)> = Vec::new();
+        let mut stderr_tasks = Vec::new();
+        let mut stdout_task = None;
+        let mut next_stdin = Stdio::null();
+
+        let stages = pipeline.stages.iter().zip(&stage_args).zip(&entries);
+        for (i, ((stage, args), entry)) in stages.enumerate() {
+            let stdin = std::mem::replace(&mut next_stdin, Stdio::null());
+            let spawned = self
+                .spawn_process(&stage.command, args, entry, stdin)
+                .and_then(|(mut child, guard)| {
+                    let (stdout, stderr) = Self::take_output_pipes(&mut child)?;
+                    Ok((child, guard, stdout, stderr))
+                });
+
+            let (child, guard, stdout, stderr) = match spawned {
+                Ok(spawned) => spawned,
+                Err(e) => {
+                    kill_stages(&mut children);
+                    return Err(e);
+                }
+            };
+
+            stderr_tasks.push(spawn_line_reader(stderr));
+
+            if i + 1 == pipeline.stages.len() {
+                stdout_task = Some(spawn_line_reader(stdout));
+            } else {
+                match stdout.try_into() {
+                    Ok(stdin) => next_stdin = stdin,
+                    Err(e) => {
+                        children.push((child, guard));
+                        kill_stages(&mut children);
+                        return Err(ExecutorError::ExecutionFailed(e.to_string()));
+                    }
+                }
+            }
+
+            children.push((child, guard));
+        }
+
+        let outcome = {
+            let finished = async {
+                let mut exit_code = 0;
+                for (child, _) in children.iter_mut() {
+                    let status = child
+                        .wait()
+                        .await
+                        .map_err(|e| ExecutorError::ExecutionFailed(e.to_string()))?;
+                    let code = status.code().unwrap_or(-1);
+                    if code != 0 {
+                        exit_code = code;
+                    }
+                }
+
+                let stdout = match stdout_task.as_mut() {
+                    Some(task) => task
+                        .await
+                        .map_err(|e| ExecutorError::ExecutionFailed(e.to_string()))?,
+                    None => String::new(),
+                };
+
+                let mut stderr = String::new();
+                for task in stderr_tasks.iter_mut() {
+                    stderr.push_str(
+                        &task
+                            .await
+                            .map_err(|e| ExecutorError::ExecutionFailed(e.to_string()))?,
+                    );
+                }
+
+                Ok((exit_code, stdout, stderr))
+            };
+
+            tokio::select! {
+                result = finished => result,
+                _ = tokio::time::sleep(Duration::from_secs(timeout_secs)) => {
+                    Err(ExecutorError::TimeoutExceeded(timeout_secs))
+                }
+            }
+        };
+
+        let (exit_code, stdout, stderr) = match outcome {
+            Ok(done) => done,
+            Err(e) => {
+                kill_stages(&mut children);
+                return Err(e);
+            }
+        };
 
         // Check output size limits
-        if stdout.len() + stderr.len() > self.config.max_output_bytes {
+        if stdout.len() + stderr.len() > max_output_bytes {
             return Err(ExecutorError::ResourceLimitExceeded(
                 "Output exceeds maximum size".to_string(),
             ));
         }
 
-        let exit_code = status.code().unwrap_or(-1);
+        let mut peak_memory_bytes = None;
+        let mut cpu_usage_usec = None;
+        for usage in children
+            .iter()
+            .filter_map(|(_, guard)| guard.as_ref()?.cgroup_usage())
+        {
+            if let Some(peak) = usage.peak_memory_bytes {
+                *peak_memory_bytes.get_or_insert(0) += peak;
+            }
+            if let Some(cpu) = usage.cpu_usage_usec {
+                *cpu_usage_usec.get_or_insert(0) += cpu;
+            }
+        }
+
+        let output = ProcessOutput {
+            stdout,
+            stderr,
+            exit_code,
+            peak_memory_bytes,
+            cpu_usage_usec,
+            usage: ProcessUsage::default(),
+            was_killed_by: None,
+        };
+        let duration_ms = start_time.elapsed().as_millis() as u64;
 
-        Ok((stdout, stderr, exit_code))
+        let mut result = output.into_result(
+            pipeline.to_string(),
+            Vec::new(),
+            duration_ms,
+            &self.redactor,
+        );
+        // The riskiest stage
+        result.risk = Some(risk);
+        Ok(result)
     }
 
-    /// Build sandboxed command
-    fn build_sandboxed_command(
+    /// Describe how a command would be run, without running it
+    ///
+    /// The command is validated against the whitelist as for `execute`, but no
+    /// process, cgroup or approval request is created.
+    pub fn plan(&self, command: &str, args: &[String]) -> Result<ExecutionPlan, ExecutorError> {
+        self.plan_as(command, args, None)
+    }
+
+    fn plan_as(
         &self,
         command: &str,
         args: &[String],
-    ) -> Result<TokioCommand, ExecutorError> {
-        if let Some(ref sandbox) = self.sandbox {
-            sandbox
-                .wrap_command(command, args)
-                .map_err(|e| ExecutorError::SandboxError(e.to_string()))
-        } else {
-            Err(ExecutorError::SandboxError(
-                "Sandbox not available".to_string(),
-            ))
-        }
-    }
+        token: Option<&CapabilityToken>,
+    ) -> Result<ExecutionPlan, ExecutorError> {
+        let (entry, args) = &self.validate_request(command, args, token)?;
+        let (resolved_path, exec_args) = self.executable_for(command, args, entry)?;
+
+        let mut plan = ExecutionPlan {
+            command: command.to_string(),
+            args: args.to_vec(),
+            argv: std::iter::once(resolved_path.clone())
+                .chain(exec_args.iter().cloned())
+                .collect(),
+            resolved_path,
+            sandbox_backend: "disabled".to_string(),
+            limits: ResourceLimits::default(),
+            seccomp_profile: SeccompProfile::Unrestricted,
+            allowed_paths: None,
+            writable_paths: None,
+            network_isolated: false,
+            env: self.config.env_vars.clone(),
+            working_dir: self
+                .working_dir()?
+                .map(|dir| dir.to_string_lossy().to_string()),
+            timeout_secs: self.timeout_for(entry),
+            max_output_bytes: self.output_limit_for(entry),
+            requires_confirmation: self.needs_confirmation(command, args, entry),
+            risk: self.assess_risk(command, args, entry),
+        };
 
-    /// Resolve command path
-    fn resolve_command_path(&self, command: &str) -> Result<String, ExecutorError> {
-        // Check if it's already an absolute path
-        if std::path::Path::new(command).is_absolute() {
-            return Ok(command.to_string());
+        // Builtins run in-process, confined by path scope instead of a sandbox
+        if entry.builtin.is_some() {
+            plan.sandbox_backend = "builtin".to_string();
+            return Ok(plan);
         }
 
-        // Try to find in PATH
-        if let Ok(path) = which::which(command) {
-            return Ok(path.to_string_lossy().to_string());
+        if let (true, Some(sandbox)) = (self.config.enable_sandbox, &self.sandbox) {
+            let config = sandbox.config_for(entry);
+            let backend = sandbox.backend();
+
+            plan.argv = sandbox.argv(&plan.resolved_path, &exec_args, entry);
+            plan.sandbox_backend = backend.name().to_string();
+            plan.limits = ResourceLimits {
+                cpu_time_secs: config.max_cpu_time_secs,
+                memory_mb: config.max_memory_mb,
+                max_processes: config.max_processes,
+                cpu_rate_percent: config.cpu_rate_percent,
+                cgroup_root: config.cgroup_root.clone(),
+            };
+
+            // Landlock and seccomp enforce the paths and profile in-process; the
+            // jails get them as mounts and their own syscall filters
+            if cfg!(target_os = "linux") {
+                plan.allowed_paths = entry.allowed_paths.clone();
+                plan.writable_paths = entry.writable_paths.clone();
+                plan.seccomp_profile = entry.seccomp_profile;
+            }
+
+            #[cfg(target_os = "linux")]
+            {
+                plan.network_isolated = match backend {
+                    SandboxBackend::None => Sandbox::isolates_network(entry),
+                    _ => !entry.network_allowed(),
+                };
+            }
         }
 
-        // Use command as-is if not found (will fail at execution)
-        Ok(command.to_string())
+        Ok(plan)
     }
 
-    /// Validate arguments
-    fn validate_args(
+    /// Execute command, yielding stdout/stderr lines as they arrive
+    ///
+    /// The stream ends with [`OutputLine::Exited`], or with an error if the
+    /// output exceeds `max_output_bytes`, the timeout expires, or reading fails;
+    /// the process is killed in those cases. Dropping the stream cancels the
+    /// command.
+    pub async fn execute_streaming(
         &self,
+        command: &str,
         args: &[String],
-        entry: &WhitelistEntry,
-    ) -> Result<(), ExecutorError> {
-        // Check max args
-        if let Some(max) = entry.max_args {
-            if args.len() > max {
-                return Err(ExecutorError::InvalidArguments(format!(
-                    "Too many arguments: {} > {}",
-                    args.len(),
-                    max
-                )));
-            }
-        }
+    ) -> Result<impl Stream<Item = Result<OutputLine, ExecutorError>>, ExecutorError> {
+        self.streaming_as(command, args, None).await
+    }
 
-        // Validate argument patterns
-        for (i, arg) in args.iter().enumerate() {
-            // Check for shell injection attempts
-            if self.contains_shell_metacharacters(arg) && !self.config.allow_shell {
-                return Err(ExecutorError::InvalidArguments(format!(
-                    "Argument {} contains shell metacharacters: {}",
-                    i, arg
-                )));
+    async fn streaming_as(
+        &self,
+        command: &str,
+        args: &[String],
+        token: Option<&CapabilityToken>,
+    ) -> Result<impl Stream<Item = Result<OutputLine, ExecutorError>>, ExecutorError> {
+        let start_time = Instant::now();
+
+        let spawned = self.launch(command, args, token).await.and_then(|(mut child, guard, entry)| {
+            let (stdout, stderr) = Self::take_output_pipes(&mut child)?;
+            Ok((child, guard, entry, stdout, stderr))
+        });
+
+        let (mut child, sandbox_guard, entry, stdout, stderr) = match spawned {
+            Ok(spawned) => spawned,
+            Err(e) => {
+                self.audit_error(command, args, &e, start_time);
+                return Err(e);
             }
+        };
+
+        info!(
+            "Streaming command: {} with {} args",
+            command,
+            args.len()
+        );
 
-            // Validate against allowed patterns
-            if let Some(ref patterns) = entry.allowed_arg_patterns {
-                let mut matches = false;
-                for pattern in patterns {
-                    if let Ok(re) = regex::Regex::new(pattern) {
-                        if re.is_match(arg) {
-                            matches = true;
-                            break;
+        let max_output_bytes = self.output_limit_for(&entry);
+        let timeout_secs = self.timeout_for(&entry);
+        let auditor = self.auditor.clone();
+        let redactor = self.redactor.clone();
+        let command = command.to_string();
+        let args = args.to_vec();
+        let (tx, rx) = mpsc::channel(STREAMING_CHANNEL_CAPACITY);
+
+        tokio::spawn(async move {
+            let mut stdout = BufReader::new(stdout).lines();
+            let mut stderr = BufReader::new(stderr).lines();
+            let mut stdout_open = true;
+            let mut stderr_open = true;
+            let mut output_bytes = 0usize;
+            let mut hasher = OutputHasher::default();
+            let mut stdout_redactor = redactor.lines();
+            let mut stderr_redactor = redactor.lines();
+
+            let deadline = tokio::time::sleep(Duration::from_secs(timeout_secs));
+            tokio::pin!(deadline);
+
+            let outcome = loop {
+                let line = tokio::select! {
+                    line = stdout.next_line(), if stdout_open => match line {
+                        Ok(Some(line)) => OutputLine::Stdout(line),
+                        Ok(None) => {
+                            stdout_open = false;
+                            continue;
+                        }
+                        Err(e) => break Err(ExecutorError::ExecutionFailed(e.to_string())),
+                    },
+                    line = stderr.next_line(), if stderr_open => match line {
+                        Ok(Some(line)) => OutputLine::Stderr(line),
+                        Ok(None) => {
+                            stderr_open = false;
+                            continue;
                         }
+                        Err(e) => break Err(ExecutorError::ExecutionFailed(e.to_string())),
+                    },
+                    status = child.wait(), if !stdout_open && !stderr_open => match status {
+                        Ok(status) => break Ok(status.code().unwrap_or(-1)),
+                        Err(e) => break Err(ExecutorError::ExecutionFailed(e.to_string())),
+                    },
+                    _ = &mut deadline => break Err(ExecutorError::TimeoutExceeded(timeout_secs)),
+                    _ = tx.closed() => {
+                        debug!("Output stream dropped; cancelling command");
+                        break Err(ExecutorError::Cancelled);
                     }
+                };
+
+                // Count the newline too, matching the buffered output
+                output_bytes += line.text().len() + 1;
+                if output_bytes > max_output_bytes {
+                    break Err(ExecutorError::ResourceLimitExceeded(
+                        "Output exceeds maximum size".to_string(),
+                    ));
                 }
 
-                if !matches && !patterns.is_empty() {
-                    return Err(ExecutorError::InvalidArguments(format!(
-                        "Argument {} does not match allowed patterns: {}",
-                        i, arg
-                    )));
+                // Mask secrets; lines inside a private key block are dropped
+                let line = match line {
+                    OutputLine::Stdout(text) => match stdout_redactor.redact(&text) {
+                        Some(text) => OutputLine::Stdout(text),
+                        None => continue,
+                    },
+                    OutputLine::Stderr(text) => match stderr_redactor.redact(&text) {
+                        Some(text) => OutputLine::Stderr(text),
+                        None => continue,
+                    },
+                    exited => exited,
+                };
+
+                match &line {
+                    OutputLine::Stdout(text) => {
+                        hasher.stdout(text);
+                        hasher.stdout("\n");
+                    }
+                    OutputLine::Stderr(text) => {
+                        hasher.stderr(text);
+                        hasher.stderr("\n");
+                    }
+                    OutputLine::Exited { .. } => {}
+                }
+
+                if tx.send(Ok(line)).await.is_err() {
+                    debug!("Output stream dropped; cancelling command");
+                    break Err(ExecutorError::Cancelled);
                 }
+            };
+
+            // No-op if the process already exited
+            kill_process_tree(&mut child, sandbox_guard.as_ref());
+
+            let duration_ms = start_time.elapsed().as_millis() as u64;
+
+            if let Some(auditor) = &auditor {
+                let audited = match &outcome {
+                    Ok(exit_code) => Ok((*exit_code, hasher.finish())),
+                    Err(e) => Err(e),
+                };
+                auditor.record_outcome(&command, &args, audited, duration_ms);
             }
-        }
 
-        Ok(())
+            // The receiver is gone if the stream was dropped
+            let _ = match outcome {
+                Ok(exit_code) => {
+                    tx.send(Ok(OutputLine::Exited {
+                        exit_code,
+                        duration_ms,
+                    }))
+                    .await
+                }
+                Err(e) => tx.send(Err(e)).await,
+            };
+        });
+
+        Ok(ReceiverStream::new(rx))
     }
 
-    /// Check for shell metacharacters
-    fn contains_shell_metacharacters(&self, s: &str) -> bool {
-        let metacharacters = [
-            ';', '&', '|', '>', '<', '`', '$', '(', ')', '{', '}', '[', ']', '\\', '\n', '*', '?',
-        ];
+    /// Run a command in an `os_command` span recording its outcome, which
+    /// joins the caller's trace, then audit it
+    async fn traced(
+        &self,
+        command: &str,
+        args: &[String],
+        run: impl Future<Output = Result<CommandResult, ExecutorError>>,
+    ) -> Result<CommandResult, ExecutorError> {
+        let start_time = Instant::now();
+        let span = info_span!(
+            "os_command",
+            command,
+            exit_code = Empty,
+            success = Empty,
+            duration_ms = Empty,
+            error = Empty,
+            otel.status_code = Empty,
+        );
+        let result = run.instrument(span.clone()).await;
 
-        s.chars().any(|c| metacharacters.contains(&c))
-    }
+        span.record("duration_ms", start_time.elapsed().as_millis() as u64);
+        match &result {
+            Ok(result) => {
+                span.record("exit_code", result.exit_code);
+                span.record("success", result.success);
+            }
+            Err(e) => {
+                span.record("success", false);
+                span.record("error", field::display(e));
+                span.record("otel.status_code", "ERROR");
+            }
+        }
 
-    /// Get platform info
-    pub fn platform(&self) -> &Platform {
-        &self.platform
+        self.audit(command, args, &result, start_time);
+        result
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
 
-    #[test]
-    fn test_executor_config_default() {
-        let config = ExecutorConfig::default();
-        assert_eq!(config.max_timeout_secs, 5);
-        assert!(config.enable_sandbox);
-        assert!(!config.allow_shell);
+    fn audit(
+        &self,
+        command: &str,
+        args: &[String],
+        result: &Result<CommandResult, ExecutorError>,
+        start_time: Instant,
+    ) {
+        if let Some(auditor) = &self.auditor {
+            auditor.record(command, args, result, start_time);
+        }
     }
 
-    #[test]
-    fn test_shell_metacharacter_detection() {
-        let config = ExecutorConfig::default();
-        let whitelist = CommandWhitelist::default();
-        let executor = CommandExecutor::new(config, whitelist);
+    fn audit_error(
+        &self,
+        command: &str,
+        args: &[String],
+        error: &ExecutorError,
+        start_time: Instant,
+    ) {
+        if let Some(auditor) = &self.auditor {
+            let duration_ms = start_time.elapsed().as_millis() as u64;
+            auditor.record_outcome(command, args, Err(error), duration_ms);
+        }
+    }
 
-        assert!(executor.contains_shell_metacharacters("test; rm -rf /"));
-        assert!(executor.contains_shell_metacharacters("test && malicious"));
-        assert!(executor.contains_shell_metacharacters("test | grep"));
-        assert!(!executor.contains_shell_metacharacters("test"));
-        assert!(!executor.contains_shell_metacharacters("test.txt"));
+    /// Validate, confirm and spawn a command with no stdin
+    async fn launch(
+        &self,
+        command: &str,
+        args: &[String],
+        token: Option<&CapabilityToken>,
+    ) -> Result<(Child, Option<SandboxGuard>, WhitelistEntry), ExecutorError> {
+        let (entry, args) = self.validate_request(command, args, token)?;
+        self.confirm(command, &args, &entry).await?;
+        let (child, guard) = self.spawn_process(command, &args, &entry, Stdio::null())?;
+        Ok((child, guard, entry))
     }
 
-    #[tokio::test]
-    async fn test_simple_command_echo() {
-        let mut whitelist = CommandWhitelist::default();
-        whitelist.add_command(
-            "echo",
+    /// Validate, confirm and spawn an interactive command with piped stdin
+    pub(crate) async fn spawn_session(
+        &self,
+        command: &str,
+        args: &[String],
+        token: Option<&CapabilityToken>,
+    ) -> Result<(Child, Option<SandboxGuard>, WhitelistEntry), ExecutorError> {
+        let start_time = Instant::now();
+        let result = async {
+            let (entry, args) = self.validate_request(command, args, token)?;
+            if !entry.interactive {
+                return Err(ExecutorError::PermissionDenied(format!(
+                    "{} cannot run as an interactive session",
+                    command
+                )));
+            }
+
+            self.confirm(command, &args, &entry).await?;
+            let (child, guard) = self.spawn_process(command, &args, &entry, Stdio::piped())?;
+            Ok((child, guard, entry))
+        }
+        .await;
+
+        if let Err(e) = &result {
+            self.audit_error(command, args, e, start_time);
+        }
+        result
+    }
+
+    /// Record how a session ended
+    pub(crate) fn audit_session(
+        &self,
+        command: &str,
+        args: &[String],
+        outcome: Result<(i32, String), &ExecutorError>,
+        duration_ms: u64,
+    ) {
+        if let Some(auditor) = &self.auditor {
+            auditor.record_outcome(command, args, outcome, duration_ms);
+        }
+    }
+
+    /// The capability a request runs under: `token`'s, which must cover
+    /// `command`, or none if no authority is configured
+    fn authorize(
+        &self,
+        command: &str,
+        token: Option<&CapabilityToken>,
+    ) -> Result<Option<Capability>, ExecutorError> {
+        match (token, &self.capabilities) {
+            (Some(token), _) => self.verify_capability(command, token).map(Some),
+            (None, Some(_)) => Err(ExecutorError::PermissionDenied(format!(
+                "{} requires a capability token",
+                command
+            ))),
+            (None, None) => Ok(None),
+        }
+    }
+
+    /// Check a token and that it covers `command`
+    fn verify_capability(
+        &self,
+        command: &str,
+        token: &CapabilityToken,
+    ) -> Result<Capability, ExecutorError> {
+        let authority = self.capabilities.as_ref().ok_or_else(|| {
+            ExecutorError::PermissionDenied(
+                "No capability authority is configured".to_string(),
+            )
+        })?;
+
+        let capability = authority
+            .verify(token)
+            .map_err(|e| ExecutorError::PermissionDenied(e.to_string()))?;
+
+        if !capability.allows(command) {
+            return Err(ExecutorError::PermissionDenied(format!(
+                "{} is outside the capability issued to {}",
+                command, capability.subject
+            )));
+        }
+
+        debug!("Capability for {} covers {}", capability.subject, command);
+        Ok(capability)
+    }
+
+    /// Lower the entry's limits to the capability's ceilings
+    fn restrict_to(&self, capability: &Capability, entry: &mut WhitelistEntry) {
+        if let Some(max) = capability.max_timeout_secs {
+            entry.timeout_secs = Some(self.timeout_for(entry).min(max));
+        }
+
+        if let Some(max) = capability.max_output_bytes {
+            entry.max_output_bytes = Some(self.output_limit_for(entry).min(max));
+        }
+
+        if let Some(max) = capability.max_memory_mb {
+            let current = entry.max_memory_mb.or(self.config.sandbox.max_memory_mb);
+            entry.max_memory_mb = Some(current.map_or(max, |current| current.min(max)));
+        }
+
+        if !capability.allow_network {
+            entry.allow_network = Some(false);
+        }
+    }
+
+    /// Limits a cached result for the entry must have run under
+    fn cache_limits(&self, entry: &WhitelistEntry) -> CacheLimits {
+        CacheLimits {
+            timeout_secs: self.timeout_for(entry),
+            max_output_bytes: self.output_limit_for(entry),
+            max_memory_mb: entry.max_memory_mb.or(self.config.sandbox.max_memory_mb),
+            allow_network: entry.allow_network,
+        }
+    }
+
+    /// Timeout for a command, preferring the entry's override
+    fn timeout_for(&self, entry: &WhitelistEntry) -> u64 {
+        entry.timeout_secs.unwrap_or(self.config.max_timeout_secs)
+    }
+
+    /// Output limit for a command, preferring the entry's override
+    pub(crate) fn output_limit_for(&self, entry: &WhitelistEntry) -> usize {
+        entry.max_output_bytes.unwrap_or(self.config.max_output_bytes)
+    }
+
+    /// Risk of the command that actually runs for this request
+    fn assess_risk(
+        &self,
+        command: &str,
+        args: &[String],
+        entry: &WhitelistEntry,
+    ) -> RiskAssessment {
+        risk::assess_resolved(command, args, entry, self.platform)
+    }
+
+    /// Whether the entry or the policy requires confirmation for this request
+    fn needs_confirmation(&self, command: &str, args: &[String], entry: &WhitelistEntry) -> bool {
+        entry.requires_confirmation
+            || self.policy.as_ref().is_some_and(|p| {
+                let risk = self.assess_risk(command, args, entry).level;
+                let dir = self.base_dir().ok();
+                p.evaluate_with_risk(command, args, risk, dir.as_deref())
+                    .action
+                    == PolicyAction::Confirm
+            })
+    }
+
+    /// Ask the approval provider about requests that require confirmation
+    ///
+    /// Fails closed: without a provider, such commands are refused.
+    async fn confirm(
+        &self,
+        command: &str,
+        args: &[String],
+        entry: &WhitelistEntry,
+    ) -> Result<(), ExecutorError> {
+        if !self.needs_confirmation(command, args, entry) {
+            return Ok(());
+        }
+
+        let provider = self.approval.as_ref().ok_or_else(|| {
+            ExecutorError::ConfirmationDenied(format!(
+                "{} requires confirmation but no approval provider is configured",
+                command
+            ))
+        })?;
+
+        let request = ApprovalRequest {
+            command: command.to_string(),
+            args: args.to_vec(),
+            description: entry.description.clone(),
+        };
+
+        match provider.request_approval(&request).await {
+            ApprovalDecision::Approved => {
+                info!("User approved: {}", request.prompt());
+                Ok(())
+            }
+            ApprovalDecision::Denied => {
+                info!("User denied: {}", request.prompt());
+                Err(ExecutorError::ConfirmationDenied(command.to_string()))
+            }
+        }
+    }
+
+    /// Check the capability, look up the whitelist entry, expand globs and
+    /// validate arguments
+    ///
+    /// The entry is copied out of the current whitelist, so a reload while the
+    /// command runs does not affect it, and lowered to the capability's
+    /// ceilings. Returns the arguments to run with.
+    fn validate_request(
+        &self,
+        command: &str,
+        args: &[String],
+        token: Option<&CapabilityToken>,
+    ) -> Result<(WhitelistEntry, Vec<String>), ExecutorError> {
+        if let Some(e) = &self.redaction_error {
+            return Err(ExecutorError::ExecutionFailed(e.clone()));
+        }
+        let capability = self.authorize(command, token)?;
+
+        // Validate command is whitelisted
+        let mut whitelist_entry = self
+            .whitelist
+            .snapshot()
+            .get(command)
+            .cloned()
+            .ok_or_else(|| ExecutorError::CommandNotWhitelisted(command.to_string()))?;
+
+        if let Some(capability) = &capability {
+            self.restrict_to(capability, &mut whitelist_entry);
+        }
+
+        // Commands confined to declared paths may still use the jail
+        if let Some(jail) = self.jail_dir() {
+            let jail = jail.to_string_lossy().to_string();
+            let declared = [
+                &mut whitelist_entry.allowed_paths,
+                &mut whitelist_entry.writable_paths,
+            ];
+            for paths in declared.into_iter().flatten() {
+                paths.push(jail.clone());
+            }
+        }
+
+        // Expanded paths are validated like typed ones
+        let args = if whitelist_entry.expand_globs {
+            self.expand_globs(args)?
+        } else {
+            args.to_vec()
+        };
+
+        // Validate arguments
+        self.validate_args(&args, &whitelist_entry)?;
+
+        if let Some(policy) = &self.policy {
+            let risk = self.assess_risk(command, &args, &whitelist_entry).level;
+            let decision = policy.evaluate_with_risk(command, &args, risk, Some(&self.base_dir()?));
+            if decision.action == PolicyAction::Deny {
+                return Err(ExecutorError::PolicyDenied(decision.explanation()));
+            }
+        }
+
+        Ok((whitelist_entry, args))
+    }
+
+    /// Replace glob patterns with the paths they match inside the path roots
+    fn expand_globs(&self, args: &[String]) -> Result<Vec<String>, ExecutorError> {
+        let base_dir = self.base_dir()?;
+        let scope = PathScope::new(&self.path_roots(&base_dir), &base_dir);
+
+        GlobExpander::new(scope, base_dir, self.config.max_glob_paths)
+            .expand(args)
+            .map_err(|e| match e {
+                GlobError::TooManyPaths(_) => ExecutorError::ResourceLimitExceeded(e.to_string()),
+                e => ExecutorError::InvalidArguments(e.to_string()),
+            })
+    }
+
+    /// Roots that path arguments, builtins and glob matches are confined to:
+    /// `allowed_roots`, else the working directory
+    fn path_roots(&self, base_dir: &std::path::Path) -> Vec<String> {
+        match self.config.allowed_roots {
+            Some(ref roots) => roots.clone(),
+            None => vec![base_dir.to_string_lossy().to_string()],
+        }
+    }
+
+    /// Resolve, sandbox and spawn the command with piped output
+    ///
+    /// On Unix the command leads a new session, so its whole process tree can
+    /// be killed with one signal. The child is also killed if dropped.
+    fn spawn_process(
+        &self,
+        command: &str,
+        args: &[String],
+        entry: &WhitelistEntry,
+        stdin: Stdio,
+    ) -> Result<(Child, Option<SandboxGuard>), ExecutorError> {
+        if entry.builtin.is_some() {
+            return Err(ExecutorError::ExecutionFailed(format!(
+                "{} is a builtin and only runs through execute",
+                command
+            )));
+        }
+
+        // Resolve full command path
+        let (cmd_path, args) = self.executable_for(command, args, entry)?;
+        let args = args.as_slice();
+
+        debug!("Resolved command path: {}", cmd_path);
+
+        // Without the sandbox, a memory ceiling is enforced with an rlimit
+        let sandboxed = self.config.enable_sandbox && self.sandbox.is_some();
+        let memory_limit_mb = entry.max_memory_mb.filter(|_| !sandboxed);
+        #[cfg(not(unix))]
+        if let Some(mb) = memory_limit_mb {
+            return Err(ExecutorError::SandboxError(format!(
+                "{} is limited to {} MB, which needs the sandbox",
+                command, mb
+            )));
+        }
+
+        // Build command
+        let (mut cmd, mut sandbox_guard) = if sandboxed {
+            // Execute through sandbox
+            let (c, guard) = self.build_sandboxed_command(&cmd_path, args, entry)?;
+            (c, Some(guard))
+        } else {
+            // Direct execution
+            let mut c = TokioCommand::new(&cmd_path);
+            c.args(args);
+            (c, None)
+        };
+
+        // Set working directory
+        if let Some(wd) = self.working_dir()? {
+            cmd.current_dir(wd);
+        }
+
+        // Set environment variables
+        for (key, value) in &self.config.env_vars {
+            cmd.env(key, value);
+        }
+
+        // Configure stdio
+        cmd.stdin(stdin)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+
+        #[cfg(unix)]
+        unsafe {
+            cmd.pre_exec(move || {
+                if libc::setsid() == -1 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                if let Some(mb) = memory_limit_mb {
+                    let bytes = (mb as libc::rlim_t).saturating_mul(1024 * 1024);
+                    let limit = libc::rlimit {
+                        rlim_cur: bytes,
+                        rlim_max: bytes,
+                    };
+                    if libc::setrlimit(libc::RLIMIT_AS, &limit) == -1 {
+                        return Err(std::io::Error::last_os_error());
+                    }
+                }
+                Ok(())
+            });
+        }
+
+        // Spawn process
+        let mut child = cmd
+            .spawn()
+            .map_err(|e| ExecutorError::ExecutionFailed(e.to_string()))?;
+
+        // Post-spawn sandboxing (Job Objects on Windows); the guard lives until the child exits
+        if let (Some(sandbox), Some(guard)) = (&self.sandbox, sandbox_guard.as_mut()) {
+            if let Err(e) = sandbox.attach(&child, entry, guard) {
+                let _ = child.start_kill();
+                return Err(ExecutorError::SandboxError(e.to_string()));
+            }
+        }
+
+        Ok((child, sandbox_guard))
+    }
+
+    /// Take the child's stdout and stderr pipes
+    fn take_output_pipes(child: &mut Child) -> Result<(ChildStdout, ChildStderr), ExecutorError> {
+        let stdout = child.stdout.take().ok_or_else(|| {
+            ExecutorError::ExecutionFailed("Failed to capture stdout".to_string())
+        })?;
+
+        let stderr = child.stderr.take().ok_or_else(|| {
+            ExecutorError::ExecutionFailed("Failed to capture stderr".to_string())
+        })?;
+
+        Ok((stdout, stderr))
+    }
+
+    /// Build sandboxed command
+    fn build_sandboxed_command(
+        &self,
+        command: &str,
+        args: &[String],
+        entry: &WhitelistEntry,
+    ) -> Result<(TokioCommand, SandboxGuard), ExecutorError> {
+        if let Some(ref sandbox) = self.sandbox {
+            sandbox
+                .wrap_command(command, args, entry)
+                .map_err(|e| ExecutorError::SandboxError(e.to_string()))
+        } else {
+            Err(ExecutorError::SandboxError(
+                "Sandbox not available".to_string(),
+            ))
+        }
+    }
+
+    /// Resolved executable and full argument list for this platform
+    ///
+    /// PowerShell entries run their cmdlet in a constrained session. Otherwise
+    /// the entry's platform-specific executable is used if it has one, with
+    /// its arguments placed before the user's.
+    fn executable_for(
+        &self,
+        command: &str,
+        args: &[String],
+        entry: &WhitelistEntry,
+    ) -> Result<(String, Vec<String>), ExecutorError> {
+        if let Some(builtin) = entry.builtin {
+            return Ok((format!("builtin:{}", builtin.name()), args.to_vec()));
+        }
+
+        if let Some(cmdlet) = &entry.powershell {
+            let argv = cmdlet
+                .argv(args)
+                .map_err(|e| ExecutorError::InvalidArguments(e.to_string()))?;
+            return Ok((self.resolve_command_path(crate::powershell::program())?, argv));
+        }
+
+        match entry.executable_for(self.platform) {
+            Some(executable) => Ok((
+                self.resolve_command_path(&executable.program)?,
+                executable.args.iter().chain(args).cloned().collect(),
+            )),
+            None => Ok((self.resolve_command_path(command)?, args.to_vec())),
+        }
+    }
+
+    /// Directory commands run in: the configured working directory or our own
+    fn base_dir(&self) -> Result<PathBuf, ExecutorError> {
+        match self.working_dir()? {
+            Some(dir) => Ok(dir),
+            None => std::env::current_dir()
+                .map_err(|e| ExecutorError::ExecutionFailed(e.to_string())),
+        }
+    }
+
+    /// Directory commands run in: the jail, else `working_dir` (None = the
+    /// executor's own)
+    fn working_dir(&self) -> Result<Option<PathBuf>, ExecutorError> {
+        match &self.jail {
+            Some(Ok(jail)) => Ok(Some(jail.path().to_path_buf())),
+            Some(Err(e)) => Err(ExecutorError::SandboxError(e.clone())),
+            None => Ok(self.config.working_dir.as_ref().map(PathBuf::from)),
+        }
+    }
+
+    /// The jail directory, if `jail_root` is configured and it was created
+    pub fn jail_dir(&self) -> Option<&std::path::Path> {
+        match &self.jail {
+            Some(Ok(jail)) => Some(jail.path()),
+            _ => None,
+        }
+    }
+
+    /// Resolve command path
+    fn resolve_command_path(&self, command: &str) -> Result<String, ExecutorError> {
+        // Check if it's already an absolute path
+        if std::path::Path::new(command).is_absolute() {
+            return Ok(command.to_string());
+        }
+
+        // Try to find in PATH
+        if let Ok(path) = which::which(command) {
+            return Ok(path.to_string_lossy().to_string());
+        }
+
+        // Use command as-is if not found (will fail at execution)
+        Ok(command.to_string())
+    }
+
+    /// Validate arguments
+    fn validate_args(
+        &self,
+        args: &[String],
+        entry: &WhitelistEntry,
+    ) -> Result<(), ExecutorError> {
+        // Check max args
+        if let Some(max) = entry.max_args {
+            if args.len() > max {
+                return Err(ExecutorError::InvalidArguments(format!(
+                    "Too many arguments: {} > {}",
+                    args.len(),
+                    max
+                )));
+            }
+        }
+
+        if let Some(ref cmdlet) = entry.powershell {
+            // PowerShell syntax differs (Windows paths contain `\`); values are
+            // passed as literals, so only parameters and quoting are checked
+            cmdlet
+                .script(args)
+                .map_err(|e| ExecutorError::InvalidArguments(e.to_string()))?;
+        } else {
+            // Validate argument patterns
+            for (i, arg) in args.iter().enumerate() {
+                // Registry keys contain `\`; reg_query parses them itself and
+                // never involves a shell. Neither does find_files, which
+                // matches its glob pattern in-process.
+                let checked = match entry.builtin {
+                    Some(Builtin::RegQuery) => arg.replace('\\', "/"),
+                    Some(Builtin::FindFiles) if i == 0 => arg.replace(['*', '?', '[', ']'], ""),
+                    _ => arg.clone(),
+                };
+
+                // Check for shell injection attempts
+                if self.contains_shell_metacharacters(&checked) && !self.config.allow_shell {
+                    return Err(ExecutorError::InvalidArguments(format!(
+                        "Argument {} contains shell metacharacters: {}",
+                        i, arg
+                    )));
+                }
+
+                // cmd.exe aliases re-parse the command line, expanding %VAR% and ^ escapes
+                if entry
+                    .executable_for(self.platform)
+                    .is_some_and(|executable| executable.is_cmd())
+                    && arg.contains(['%', '^', '"', '!'])
+                {
+                    return Err(ExecutorError::InvalidArguments(format!(
+                        "Argument {} contains cmd.exe metacharacters: {}",
+                        i, arg
+                    )));
+                }
+
+                // Validate against allowed patterns
+                if let Some(ref patterns) = entry.allowed_arg_patterns {
+                    let mut matches = false;
+                    for pattern in patterns {
+                        if let Ok(re) = regex::Regex::new(pattern) {
+                            if re.is_match(arg) {
+                                matches = true;
+                                break;
+                            }
+                        }
+                    }
+
+                    if !matches && !patterns.is_empty() {
+                        return Err(ExecutorError::InvalidArguments(format!(
+                            "Argument {} does not match allowed patterns: {}",
+                            i, arg
+                        )));
+                    }
+                }
+            }
+        }
+
+        // Path arguments must resolve (through symlinks) inside the allowed roots
+        let base_dir = self.base_dir()?;
+        let scope = PathScope::new(&self.path_roots(&base_dir), base_dir);
+        if let Err((i, path)) = scope.check(args) {
+            return Err(ExecutorError::InvalidArguments(format!(
+                "Argument {} resolves outside allowed roots: {}",
+                i,
+                path.display()
+            )));
+        }
+
+        // Relative paths must not climb out of the jail
+        if self.jail.is_some() {
+            let jail = self.base_dir()?;
+            let scope = PathScope::new(&[jail.to_string_lossy().to_string()], &jail);
+            if let Err((i, path)) = scope.check_relative(args) {
+                return Err(ExecutorError::InvalidArguments(format!(
+                    "Argument {} resolves outside the jail: {}",
+                    i,
+                    path.display()
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check for shell metacharacters
+    fn contains_shell_metacharacters(&self, s: &str) -> bool {
+        let metacharacters = [
+            ';', '&', '|', '>', '<', '`', '$', '(', ')', '{', '}', '[', ']', '\\', '\n', '*', '?',
+        ];
+
+        s.chars().any(|c| metacharacters.contains(&c))
+    }
+
+    /// Get platform info
+    pub fn platform(&self) -> &Platform {
+        &self.platform
+    }
+
+    /// Post-processor for `command`: a registered one, else the entry's
+    fn post_processor_for(
+        &self,
+        command: &str,
+        entry: &WhitelistEntry,
+    ) -> Option<Arc<dyn OutputPostProcessor>> {
+        match self.post_processors.get(command) {
+            Some(processor) => Some(processor.clone()),
+            None => entry
+                .post_process
+                .clone()
+                .map(|p| Arc::new(p) as Arc<dyn OutputPostProcessor>),
+        }
+    }
+
+    /// Redactor applied to captured output
+    pub(crate) fn redactor(&self) -> &Redactor {
+        &self.redactor
+    }
+}
+
+/// Wait for the process and collect its output
+///
+/// The process tree is killed if the timeout expires or `cancel` is signalled.
+async fn collect_output(
+    mut child: Child,
+    sandbox_guard: Option<SandboxGuard>,
+    max_output_bytes: usize,
+    timeout_secs: u64,
+    cancel: Option<watch::Receiver<bool>>,
+) -> Result<ProcessOutput, ExecutorError> {
+    let (stdout_handle, stderr_handle) = CommandExecutor::take_output_pipes(&mut child)?;
+
+    // Read output streams
+    let mut stdout_task = spawn_line_reader(stdout_handle);
+    let mut stderr_task = spawn_line_reader(stderr_handle);
+
+    let cancelled = async {
+        match cancel {
+            Some(mut rx) => {
+                // A dropped canceller can never fire
+                if rx.wait_for(|cancelled| *cancelled).await.is_err() {
+                    std::future::pending::<()>().await;
+                }
+            }
+            None => std::future::pending().await,
+        }
+    };
+
+    #[cfg(target_os = "linux")]
+    let pid = child.id();
+
+    let outcome = {
+        let finished = async {
+            // Read rusage while the exited child is still unreaped
+            #[cfg(target_os = "linux")]
+            let rusage = match pid {
+                Some(pid) => tokio::task::spawn_blocking(move || wait_rusage(pid))
+                    .await
+                    .ok()
+                    .flatten(),
+                None => None,
+            };
+            #[cfg(not(target_os = "linux"))]
+            let rusage: Option<ProcessUsage> = None;
+
+            // Wait for process
+            let status = child
+                .wait()
+                .await
+                .map_err(|e| ExecutorError::ExecutionFailed(e.to_string()))?;
+
+            // Collect output
+            let stdout = (&mut stdout_task)
+                .await
+                .map_err(|e| ExecutorError::ExecutionFailed(e.to_string()))?;
+            let stderr = (&mut stderr_task)
+                .await
+                .map_err(|e| ExecutorError::ExecutionFailed(e.to_string()))?;
+
+            Ok((status, rusage, stdout, stderr))
+        };
+
+        tokio::select! {
+            result = finished => result,
+            _ = tokio::time::sleep(Duration::from_secs(timeout_secs)) => {
+                Err(ExecutorError::TimeoutExceeded(timeout_secs))
+            }
+            _ = cancelled => Err(ExecutorError::Cancelled),
+        }
+    };
+
+    let (status, rusage, stdout, stderr) = match outcome {
+        Ok(done) => done,
+        Err(e) => {
+            kill_process_tree(&mut child, sandbox_guard.as_ref());
+            stdout_task.abort();
+            stderr_task.abort();
+            return Err(e);
+        }
+    };
+
+    // Check output size limits
+    if stdout.len() + stderr.len() > max_output_bytes {
+        return Err(ExecutorError::ResourceLimitExceeded(
+            "Output exceeds maximum size".to_string(),
+        ));
+    }
+
+    let exit_code = status.code().unwrap_or(-1);
+
+    let usage = sandbox_guard
+        .as_ref()
+        .and_then(|guard| guard.cgroup_usage())
+        .unwrap_or_default();
+
+    let process_usage = rusage
+        .or_else(|| sandbox_guard.as_ref().and_then(|guard| guard.job_usage()))
+        .unwrap_or_default();
+
+    Ok(ProcessOutput {
+        stdout,
+        stderr,
+        exit_code,
+        peak_memory_bytes: usage.peak_memory_bytes,
+        cpu_usage_usec: usage.cpu_usage_usec,
+        usage: process_usage,
+        was_killed_by: kill_reason(&status, &usage),
+    })
+}
+
+/// Fill in the summary of a successful result
+fn summarize(
+    processor: Option<Arc<dyn OutputPostProcessor>>,
+    mut result: CommandResult,
+) -> CommandResult {
+    if let Some(processor) = processor.filter(|_| result.success) {
+        result.summary = processor.summarize(&result);
+    }
+    result
+}
+
+/// Block until `pid` exits and return its resource usage, leaving it unreaped
+///
+/// `waitid` with `WNOWAIT` fills in the rusage of a zombie without reaping it,
+/// so tokio still collects the exit status afterwards.
+#[cfg(target_os = "linux")]
+fn wait_rusage(pid: u32) -> Option<ProcessUsage> {
+    let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+    let mut rusage: libc::rusage = unsafe { std::mem::zeroed() };
+
+    // The libc wrapper has no rusage argument
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_waitid,
+            libc::P_PID,
+            pid as libc::id_t,
+            &mut info as *mut libc::siginfo_t,
+            libc::WEXITED | libc::WNOWAIT,
+            &mut rusage as *mut libc::rusage,
+        )
+    };
+    if ret != 0 {
+        debug!("waitid({}) failed: {}", pid, std::io::Error::last_os_error());
+        return None;
+    }
+
+    let to_ms = |time: libc::timeval| time.tv_sec as u64 * 1000 + time.tv_usec as u64 / 1000;
+
+    Some(ProcessUsage {
+        // Linux reports ru_maxrss in KiB
+        max_rss_kb: Some(rusage.ru_maxrss as u64),
+        user_cpu_ms: Some(to_ms(rusage.ru_utime)),
+        sys_cpu_ms: Some(to_ms(rusage.ru_stime)),
+    })
+}
+
+/// Classify a signal death
+///
+/// `RLIMIT_CPU` sends `SIGXCPU`; a `SIGKILL` is attributed to the OOM killer
+/// when the command's cgroup recorded an OOM kill.
+fn kill_reason(status: &std::process::ExitStatus, usage: &CgroupUsage) -> Option<KillReason> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+
+        let signal = status.signal()?;
+        Some(match signal {
+            libc::SIGXCPU => KillReason::Timeout,
+            libc::SIGKILL if usage.oom_kills.is_some_and(|kills| kills > 0) => KillReason::Oom,
+            signal => KillReason::Signal(signal),
+        })
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = (status, usage);
+        None
+    }
+}
+
+/// Read a pipe to the end in the background, normalizing line endings
+fn spawn_line_reader<R>(pipe: R) -> JoinHandle<String>
+where
+    R: AsyncRead + Unpin + Send + 'static,
+{
+    tokio::spawn(async move {
+        let reader = BufReader::new(pipe);
+        let mut lines = reader.lines();
+        let mut output = String::new();
+
+        while let Ok(Some(line)) = lines.next_line().await {
+            output.push_str(&line);
+            output.push('\n');
+        }
+
+        output
+    })
+}
+
+/// Read all of `input`, failing if it exceeds `max_bytes`
+async fn read_limited<R: AsyncRead + Unpin>(
+    input: R,
+    max_bytes: usize,
+) -> Result<Vec<u8>, ExecutorError> {
+    let mut buf = Vec::new();
+    input
+        .take(max_bytes as u64 + 1)
+        .read_to_end(&mut buf)
+        .await
+        .map_err(|e| ExecutorError::ExecutionFailed(format!("Failed to read stdin: {}", e)))?;
+
+    if buf.len() > max_bytes {
+        return Err(ExecutorError::ResourceLimitExceeded(format!(
+            "Input exceeds maximum size of {} bytes",
+            max_bytes
+        )));
+    }
+
+    Ok(buf)
+}
+
+/// Kill every spawned pipeline stage
+fn kill_stages(children: &mut [(Child, Option<SandboxGuard>)]) {
+    for (child, guard) in children.iter_mut() {
+        kill_process_tree(child, guard.as_ref());
+    }
+}
+
+/// Kill the command and everything it spawned
+pub(crate) fn kill_process_tree(child: &mut Child, sandbox_guard: Option<&SandboxGuard>) {
+    #[cfg(unix)]
+    if let Some(pid) = child.id() {
+        use nix::sys::signal::{killpg, Signal};
+        use nix::unistd::Pid;
+
+        // The child is a session leader, so its pid is the process group id
+        if let Err(e) = killpg(Pid::from_raw(pid as i32), Signal::SIGKILL) {
+            debug!("killpg({}) failed: {}", pid, e);
+        }
+    }
+
+    if let Some(guard) = sandbox_guard {
+        guard.terminate();
+    }
+
+    // No-op if the process already exited
+    let _ = child.start_kill();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(unix)]
+    use crate::approval::StaticApproval;
+    use crate::seccomp::SeccompProfile;
+    use crate::whitelist::CommandWhitelist;
+
+    #[test]
+    fn test_executor_config_default() {
+        let config = ExecutorConfig::default();
+        assert_eq!(config.max_timeout_secs, 5);
+        assert!(config.enable_sandbox);
+        assert!(!config.allow_shell);
+    }
+
+    #[test]
+    fn test_executor_config_from_section() {
+        // The section's defaults are the executor's
+        let section = aether_config::OsExecutorSection::default();
+        let config = ExecutorConfig::from(&section);
+        let defaults = ExecutorConfig::default();
+        assert_eq!(config.max_timeout_secs, defaults.max_timeout_secs);
+        assert_eq!(config.max_output_bytes, defaults.max_output_bytes);
+        assert_eq!(config.enable_sandbox, defaults.enable_sandbox);
+    }
+
+    #[test]
+    fn test_shell_metacharacter_detection() {
+        let config = ExecutorConfig::default();
+        let whitelist = CommandWhitelist::default();
+        let executor = CommandExecutor::new(config, whitelist);
+
+        assert!(executor.contains_shell_metacharacters("test; rm -rf /"));
+        assert!(executor.contains_shell_metacharacters("test && malicious"));
+        assert!(executor.contains_shell_metacharacters("test | grep"));
+        assert!(!executor.contains_shell_metacharacters("test"));
+        assert!(!executor.contains_shell_metacharacters("test.txt"));
+    }
+
+    #[tokio::test]
+    async fn test_simple_command_echo() {
+        let mut whitelist = CommandWhitelist::default();
+        whitelist.add_command(
+            "echo",
+            WhitelistEntry {
+                command: "echo".to_string(),
+                description: Some("Echo text".to_string()),
+                max_args: Some(10),
+                allowed_arg_patterns: None,
+                requires_sudo: false,
+                seccomp_profile: SeccompProfile::Unrestricted,
+                ..Default::default()
+            },
+        );
+
+        let config = ExecutorConfig {
+            enable_sandbox: false, // Disable sandbox for simple test
+            ..Default::default()
+        };
+
+        let executor = CommandExecutor::new(config, whitelist);
+
+        let result = executor
+            .execute("echo", &["Hello".to_string(), "World".to_string()])
+            .await;
+
+        assert!(result.is_ok());
+        let cmd_result = result.unwrap();
+        assert!(cmd_result.success);
+        assert!(cmd_result.stdout.contains("Hello"));
+    }
+
+    #[cfg(unix)]
+    fn unsandboxed_executor(command: &str) -> CommandExecutor {
+        let mut whitelist = CommandWhitelist::new();
+        whitelist.add_command(
+            command,
+            WhitelistEntry {
+                command: command.to_string(),
+                max_args: Some(5),
+                ..Default::default()
+            },
+        );
+
+        let config = ExecutorConfig {
+            enable_sandbox: false,
+            ..Default::default()
+        };
+
+        CommandExecutor::new(config, whitelist)
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_streaming_yields_lines_then_exit() {
+        use tokio_stream::StreamExt;
+
+        let executor = unsandboxed_executor("seq");
+        let stream = executor
+            .execute_streaming("seq", &["3".to_string()])
+            .await
+            .unwrap();
+        let items: Vec<OutputLine> = stream.map(Result::unwrap).collect().await;
+
+        assert_eq!(
+            &items[..3],
+            &[
+                OutputLine::Stdout("1".to_string()),
+                OutputLine::Stdout("2".to_string()),
+                OutputLine::Stdout("3".to_string()),
+            ]
+        );
+        assert!(matches!(items[3], OutputLine::Exited { exit_code: 0, .. }));
+        assert_eq!(items.len(), 4);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_streaming_enforces_output_limit() {
+        use tokio_stream::StreamExt;
+
+        let mut executor = unsandboxed_executor("yes");
+        executor.config.max_output_bytes = 100;
+
+        let mut stream = executor.execute_streaming("yes", &[]).await.unwrap();
+        let mut lines = 0;
+        let error = loop {
+            match stream.next().await {
+                Some(Ok(OutputLine::Stdout(_))) => lines += 1,
+                Some(Err(e)) => break e,
+                other => panic!("unexpected stream item: {:?}", other),
+            }
+        };
+
+        assert_eq!(lines, 50);
+        assert!(matches!(error, ExecutorError::ResourceLimitExceeded(_)));
+        assert!(stream.next().await.is_none());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_entry_limits_override_config() {
+        let mut whitelist = CommandWhitelist::new();
+        whitelist.add_command(
+            "sleep",
+            WhitelistEntry {
+                command: "sleep".to_string(),
+                timeout_secs: Some(1),
+                ..Default::default()
+            },
+        );
+        whitelist.add_command(
+            "seq",
+            WhitelistEntry {
+                command: "seq".to_string(),
+                max_output_bytes: Some(10),
+                ..Default::default()
+            },
+        );
+
+        let config = ExecutorConfig {
+            enable_sandbox: false,
+            max_timeout_secs: 30,
+            ..Default::default()
+        };
+        let executor = CommandExecutor::new(config, whitelist);
+
+        let plan = executor.plan("sleep", &["5".to_string()]).unwrap();
+        assert_eq!(plan.timeout_secs, 1);
+
+        let start = Instant::now();
+        let result = executor.execute("sleep", &["5".to_string()]).await;
+        assert!(matches!(result, Err(ExecutorError::TimeoutExceeded(1))));
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        let result = executor.execute("seq", &["100".to_string()]).await;
+        assert!(matches!(result, Err(ExecutorError::ResourceLimitExceeded(_))));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_secrets_redacted_in_output() {
+        use tokio_stream::StreamExt;
+
+        let executor = unsandboxed_executor("echo");
+        let args = vec!["API_KEY=supersecretvalue".to_string()];
+
+        let result = executor.execute("echo", &args).await.unwrap();
+        assert_eq!(result.stdout, "API_KEY=[REDACTED]\n");
+
+        let stream = executor.execute_streaming("echo", &args).await.unwrap();
+        let items: Vec<OutputLine> = stream.map(Result::unwrap).collect().await;
+        assert_eq!(items[0], OutputLine::Stdout("API_KEY=[REDACTED]".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_invalid_redaction_pattern_fails_commands() {
+        let mut whitelist = CommandWhitelist::new();
+        whitelist.add_command(
+            "echo",
+            WhitelistEntry {
+                command: "echo".to_string(),
+                ..Default::default()
+            },
+        );
+        let config = ExecutorConfig {
+            enable_sandbox: false,
+            redaction: RedactionConfig {
+                builtin: true,
+                patterns: vec!["(".to_string()],
+            },
+            ..Default::default()
+        };
+        let executor = CommandExecutor::new(config, whitelist);
+
+        let result = executor.execute("echo", &["hi".to_string()]).await;
+        assert!(
+            matches!(result, Err(ExecutorError::ExecutionFailed(e)) if e.contains("redaction"))
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_streaming_rejects_unlisted_command() {
+        let executor = unsandboxed_executor("seq");
+        let result = executor.execute_streaming("rm", &[]).await;
+
+        assert!(matches!(result, Err(ExecutorError::CommandNotWhitelisted(_))));
+    }
+
+    #[cfg(unix)]
+    fn stdin_executor(command: &str) -> CommandExecutor {
+        let mut whitelist = CommandWhitelist::new();
+        whitelist.add_command(
+            command,
+            WhitelistEntry {
+                command: command.to_string(),
+                max_args: Some(5),
+                allow_stdin: true,
+                ..Default::default()
+            },
+        );
+
+        let config = ExecutorConfig {
+            enable_sandbox: false,
+            max_stdin_bytes: 64,
+            ..Default::default()
+        };
+
+        CommandExecutor::new(config, whitelist)
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_execute_with_stdin() {
+        let executor = stdin_executor("wc");
+        let result = executor
+            .execute_with_stdin("wc", &["-l".to_string()], &b"one\ntwo\nthree\n"[..])
+            .await
+            .unwrap();
+
+        assert!(result.success);
+        assert_eq!(result.stdout.trim(), "3");
+        assert_eq!(result.risk, Some(RiskLevel::Low));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_stdin_size_limit() {
+        let executor = stdin_executor("wc");
+        let input = [b'x'; 65];
+        let result = executor.execute_with_stdin("wc", &[], &input[..]).await;
+
+        assert!(matches!(result, Err(ExecutorError::ResourceLimitExceeded(_))));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_stdin_requires_allow_stdin() {
+        let executor = unsandboxed_executor("wc");
+        let result = executor.execute_with_stdin("wc", &[], &b"data"[..]).await;
+
+        assert!(matches!(result, Err(ExecutorError::PermissionDenied(_))));
+    }
+
+    #[cfg(unix)]
+    fn pipeline_executor() -> CommandExecutor {
+        let mut whitelist = CommandWhitelist::new();
+        for (command, allow_stdin) in [("ls", false), ("wc", true), ("false", true)] {
+            whitelist.add_command(
+                command,
+                WhitelistEntry {
+                    command: command.to_string(),
+                    max_args: Some(5),
+                    allow_stdin,
+                    ..Default::default()
+                },
+            );
+        }
+
+        let config = ExecutorConfig {
+            enable_sandbox: false,
+            ..Default::default()
+        };
+
+        CommandExecutor::new(config, whitelist)
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_pipeline_pipes_between_stages() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["a", "b", "c"] {
+            std::fs::write(dir.path().join(name), "").unwrap();
+        }
+        let path = dir.path().to_str().unwrap();
+
+        let pipeline = Pipeline::new().pipe("ls", &[path]).pipe("wc", &["-l"]);
+        let result = pipeline_executor()
+            .execute_pipeline(&pipeline)
+            .await
+            .unwrap();
+
+        assert!(result.success);
+        assert_eq!(result.stdout.trim(), "3");
+        assert_eq!(result.command, format!("ls {} | wc -l", path));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_pipeline_reports_failed_stage() {
+        let pipeline = Pipeline::new().pipe("ls", &[]).pipe("false", &[]).pipe("wc", &[]);
+        let result = pipeline_executor()
+            .execute_pipeline(&pipeline)
+            .await
+            .unwrap();
+
+        assert!(!result.success);
+        assert_eq!(result.exit_code, 1);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_pipeline_validates_every_stage() {
+        let executor = pipeline_executor();
+
+        let not_whitelisted = Pipeline::new().pipe("ls", &[]).pipe("rm", &[]);
+        assert!(matches!(
+            executor.execute_pipeline(&not_whitelisted).await,
+            Err(ExecutorError::CommandNotWhitelisted(_))
+        ));
+
+        let no_stdin = Pipeline::new().pipe("wc", &[]).pipe("ls", &[]);
+        assert!(matches!(
+            executor.execute_pipeline(&no_stdin).await,
+            Err(ExecutorError::PermissionDenied(_))
+        ));
+
+        assert!(matches!(
+            executor.execute_pipeline(&Pipeline::new()).await,
+            Err(ExecutorError::InvalidArguments(_))
+        ));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_executions_are_audited() {
+        use crate::audit::{AuditConfig, AuditQuery};
+        use tokio_stream::StreamExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let config = AuditConfig::new(dir.path().join("audit.jsonl"));
+        let log = Arc::new(AuditLog::open(config).unwrap());
+        let executor = unsandboxed_executor("seq").with_audit_log(log.clone());
+
+        let result = executor.execute("seq", &["2".to_string()]).await.unwrap();
+        let _ = executor.execute("rm", &["-rf".to_string()]).await;
+        let stream = executor
+            .execute_streaming("seq", &["2".to_string()])
+            .await
+            .unwrap();
+        let _: Vec<_> = stream.collect().await;
+
+        let records = log.query(&AuditQuery::default()).unwrap();
+        assert_eq!(records.len(), 3);
+
+        assert_eq!(records[0].entry.exit_code, Some(0));
+        assert_eq!(records[0].entry.sandbox_backend, "disabled");
+        assert_eq!(
+            records[0].entry.output_hash.as_deref(),
+            Some(hash_output(&result.stdout, &result.stderr).as_str())
+        );
+
+        assert_eq!(records[1].entry.command, "rm");
+        assert!(records[1].entry.error.is_some());
+
+        // Streamed output hashes the same as buffered output
+        assert_eq!(records[2].entry.output_hash, records[0].entry.output_hash);
+    }
+
+    /// Approval provider that records prompts and answers with a fixed decision
+    #[cfg(unix)]
+    struct RecordingApproval {
+        decision: ApprovalDecision,
+        prompts: std::sync::Mutex<Vec<String>>,
+    }
+
+    #[cfg(unix)]
+    #[async_trait::async_trait]
+    impl ApprovalProvider for RecordingApproval {
+        async fn request_approval(&self, request: &ApprovalRequest) -> ApprovalDecision {
+            self.prompts.lock().unwrap().push(request.prompt());
+            self.decision
+        }
+    }
+
+    #[cfg(unix)]
+    fn confirming_executor() -> CommandExecutor {
+        let mut whitelist = CommandWhitelist::new();
+        whitelist.add_command(
+            "echo",
+            WhitelistEntry {
+                command: "echo".to_string(),
+                max_args: Some(5),
+                requires_confirmation: true,
+                ..Default::default()
+            },
+        );
+
+        let config = ExecutorConfig {
+            enable_sandbox: false,
+            ..Default::default()
+        };
+
+        CommandExecutor::new(config, whitelist)
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_confirmation_approved() {
+        let approval = Arc::new(RecordingApproval {
+            decision: ApprovalDecision::Approved,
+            prompts: Default::default(),
+        });
+        let executor = confirming_executor().with_approval_provider(approval.clone());
+
+        let result = executor.execute("echo", &["hi".to_string()]).await.unwrap();
+
+        assert!(result.success);
+        assert_eq!(
+            *approval.prompts.lock().unwrap(),
+            vec!["Do you want me to run echo hi?".to_string()]
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_confirmation_denied() {
+        let executor = confirming_executor()
+            .with_approval_provider(Arc::new(StaticApproval(ApprovalDecision::Denied)));
+
+        let result = executor.execute("echo", &["hi".to_string()]).await;
+        assert!(matches!(result, Err(ExecutorError::ConfirmationDenied(_))));
+
+        let result = executor.start("echo", &["hi".to_string()]).await;
+        assert!(matches!(result, Err(ExecutorError::ConfirmationDenied(_))));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_confirmation_without_provider_is_refused() {
+        let result = confirming_executor()
+            .execute("echo", &["hi".to_string()])
+            .await;
+
+        assert!(matches!(result, Err(ExecutorError::ConfirmationDenied(_))));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_policy_denies_and_requires_confirmation() {
+        let policy = Policy::from_yaml(
+            r#"
+rules:
+  - name: no-secrets
+    command: cat
+    paths: ["/etc/shadow"]
+    action: deny
+  - name: ask-before-echo
+    command: echo
+    args_contain: ["^loud$"]
+    action: confirm
+"#,
+        )
+        .unwrap();
+
+        let config = ExecutorConfig {
+            enable_sandbox: false,
+            allowed_roots: Some(vec!["/".to_string()]),
+            ..Default::default()
+        };
+        let executor = CommandExecutor::new(config, CommandWhitelist::default())
+            .with_policy(policy)
+            .with_approval_provider(Arc::new(StaticApproval(ApprovalDecision::Denied)));
+
+        let result = executor.execute("cat", &["/etc/shadow".to_string()]).await;
+        assert!(matches!(result, Err(ExecutorError::PolicyDenied(_))));
+
+        let result = executor.execute("echo", &["loud".to_string()]).await;
+        assert!(matches!(result, Err(ExecutorError::ConfirmationDenied(_))));
+        assert!(executor.plan("echo", &["loud".to_string()]).unwrap().requires_confirmation);
+
+        let result = executor.execute("echo", &["quiet".to_string()]).await.unwrap();
+        assert!(result.success);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_high_risk_requests_need_confirmation() {
+        let policy = Policy::from_yaml(
+            "rules:\n  - {name: risky, command: '*', risk: medium, action: confirm}",
+        )
+        .unwrap();
+
+        let config = ExecutorConfig {
+            enable_sandbox: false,
+            allowed_roots: Some(vec!["/".to_string()]),
+            ..Default::default()
+        };
+        let executor = CommandExecutor::new(config, CommandWhitelist::default())
+            .with_policy(policy)
+            .with_approval_provider(Arc::new(StaticApproval(ApprovalDecision::Denied)));
+
+        let etc = vec!["/etc".to_string()];
+        let plan = executor.plan("ls", &etc).unwrap();
+        assert_eq!(plan.risk.level, RiskLevel::Medium);
+        assert!(plan.requires_confirmation);
+        let result = executor.execute("ls", &etc).await;
+        assert!(matches!(result, Err(ExecutorError::ConfirmationDenied(_))));
+
+        let dir = tempfile::tempdir().unwrap();
+        let result = executor
+            .execute("ls", &[dir.path().to_string_lossy().to_string()])
+            .await
+            .unwrap();
+        assert_eq!(result.risk, Some(RiskLevel::Low));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_capability_scopes_execution() {
+        use crate::capability::Capability;
+
+        let authority = CapabilityAuthority::new("secret");
+        let executor = unsandboxed_executor("sleep").with_capability_authority(authority.clone());
+        let sleep = vec!["5".to_string()];
+
+        let token = authority.issue(&Capability {
+            max_timeout_secs: Some(1),
+            ..Capability::new("timer", &["sleep"])
+        });
+        let result = executor.execute_with_capability("sleep", &sleep, &token).await;
+        assert!(matches!(result, Err(ExecutorError::TimeoutExceeded(1))));
+
+        let token = authority.issue(&Capability::new("timer", &["echo"]));
+        let result = executor.execute_with_capability("sleep", &sleep, &token).await;
+        assert!(matches!(result, Err(ExecutorError::PermissionDenied(_))));
+
+        let forged = CapabilityAuthority::new("guess").issue(&Capability::new("timer", &["*"]));
+        let result = executor.execute_with_capability("sleep", &sleep, &forged).await;
+        assert!(matches!(result, Err(ExecutorError::PermissionDenied(_))));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_capability_required_on_every_entry_point() {
+        use crate::capability::Capability;
+
+        let authority = CapabilityAuthority::new("secret");
+        let executor = unsandboxed_executor("echo").with_capability_authority(authority.clone());
+        let args = vec!["hi".to_string()];
+        fn denied<T>(result: Result<T, ExecutorError>) -> bool {
+            matches!(result, Err(ExecutorError::PermissionDenied(_)))
+        }
+
+        assert!(denied(executor.execute("echo", &args).await));
+        assert!(denied(
+            executor.execute_with_stdin("echo", &args, &b""[..]).await
+        ));
+        assert!(denied(
+            executor
+                .execute_pipeline(&Pipeline::new().pipe("echo", &["hi"]))
+                .await
+        ));
+        assert!(denied(executor.start("echo", &args).await));
+        assert!(denied(executor.execute_streaming("echo", &args).await));
+        assert!(denied(executor.plan("echo", &args)));
+
+        let token = authority.issue(&Capability::new("greeter", &["echo"]));
+        let greeter = executor.authorized(token);
+        assert_eq!(greeter.execute("echo", &args).await.unwrap().stdout, "hi\n");
+        let handle = greeter.start("echo", &args).await.unwrap();
+        assert!(handle.wait().await.unwrap().success);
+        assert!(greeter.plan("echo", &args).is_ok());
+        assert!(denied(greeter.execute("ls", &[]).await));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_memory_ceiling_without_sandbox() {
+        use crate::capability::Capability;
+
+        let authority = CapabilityAuthority::new("secret");
+        let executor = unsandboxed_executor("sh").with_capability_authority(authority.clone());
+        let token = authority.issue(&Capability {
+            max_memory_mb: Some(64),
+            ..Capability::new("limited", &["sh"])
+        });
+
+        // The address-space limit applies although no sandbox is in use
+        let args = vec!["-c".to_string(), "ulimit -v".to_string()];
+        let result = executor
+            .execute_with_capability("sh", &args, &token)
+            .await
+            .unwrap();
+        assert_eq!(result.stdout.trim(), (64 * 1024).to_string());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_allowed_roots_confine_path_arguments() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("notes.txt"), "hello").unwrap();
+
+        let config = ExecutorConfig {
+            enable_sandbox: false,
+            working_dir: Some(dir.path().to_string_lossy().to_string()),
+            allowed_roots: Some(vec![dir.path().to_string_lossy().to_string()]),
+            ..Default::default()
+        };
+        let executor = CommandExecutor::new(config, CommandWhitelist::default());
+
+        let result = executor.execute("cat", &["notes.txt".to_string()]).await.unwrap();
+        assert_eq!(result.stdout.trim(), "hello");
+
+        let result = executor
+            .execute("cat", &["../../etc/shadow".to_string()])
+            .await;
+        assert!(matches!(result, Err(ExecutorError::InvalidArguments(_))));
+
+        // Without roots, the working directory is the only one
+        let config = ExecutorConfig {
+            enable_sandbox: false,
+            working_dir: Some(dir.path().to_string_lossy().to_string()),
+            ..Default::default()
+        };
+        let executor = CommandExecutor::new(config, CommandWhitelist::default());
+        let result = executor.execute("cat", &["notes.txt".to_string()]).await;
+        assert!(result.is_ok());
+        let result = executor
+            .execute("cat", &["/etc/hostname".to_string()])
+            .await;
+        assert!(matches!(result, Err(ExecutorError::InvalidArguments(_))));
+    }
+
+    #[tokio::test]
+    async fn test_glob_arguments_expand_in_process() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["a.txt", "b.txt", "c.md"] {
+            std::fs::write(dir.path().join(name), name).unwrap();
+        }
+
+        let config = ExecutorConfig {
+            enable_sandbox: false,
+            working_dir: Some(dir.path().to_string_lossy().to_string()),
+            max_glob_paths: 2,
+            ..Default::default()
+        };
+        let executor = CommandExecutor::new(config, CommandWhitelist::default());
+
+        let result = executor.execute("cat", &["*.txt".to_string()]).await.unwrap();
+        assert_eq!(result.args, vec!["./a.txt", "./b.txt"]);
+        assert_eq!(result.stdout.trim(), "a.txtb.txt");
+
+        let plan = executor.plan("ls", &["[c].md".to_string()]).unwrap();
+        assert_eq!(plan.args, vec!["./c.md"]);
+
+        // Not opted in: the pattern is refused as a metacharacter
+        let result = executor.execute("echo", &["*.txt".to_string()]).await;
+        assert!(matches!(result, Err(ExecutorError::InvalidArguments(_))));
+
+        let result = executor.execute("ls", &["*".to_string()]).await;
+        assert!(matches!(result, Err(ExecutorError::ResourceLimitExceeded(_))));
+
+        let result = executor.execute("ls", &["../*".to_string()]).await;
+        assert!(matches!(result, Err(ExecutorError::InvalidArguments(_))));
+    }
+
+    #[tokio::test]
+    async fn test_builtin_commands_run_in_process() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("notes.txt"), "hello").unwrap();
+
+        let config = ExecutorConfig {
+            enable_sandbox: false,
+            working_dir: Some(dir.path().to_string_lossy().to_string()),
+            ..Default::default()
+        };
+        let executor = CommandExecutor::new(config, CommandWhitelist::default());
+
+        let result = executor.execute("read_file", &["notes.txt".to_string()]).await.unwrap();
+        assert_eq!(result.stdout, "hello");
+
+        let result = executor.execute("list_dir", &[]).await.unwrap();
+        assert_eq!(result.stdout, "notes.txt\n");
+
+        let result = executor.execute("read_file", &["missing".to_string()]).await.unwrap();
+        assert_eq!(result.exit_code, 1);
+        assert!(result.stderr.starts_with("read_file: "));
+
+        let result = executor.execute("read_file", &["../x".to_string()]).await;
+        assert!(matches!(result, Err(ExecutorError::InvalidArguments(_))));
+
+        // As documented: the pattern is a glob, matched in-process
+        let result = executor
+            .execute("find_files", &["*.txt".to_string()])
+            .await
+            .unwrap();
+        assert!(result.stdout.contains("notes.txt"), "{}", result.stdout);
+        let result = executor
+            .execute("find_files", &["[a-z]*.md".to_string()])
+            .await
+            .unwrap();
+        assert!(result.stdout.is_empty());
+        let result = executor
+            .execute("find_files", &["*.txt".to_string(), "*".to_string()])
+            .await;
+        assert!(matches!(result, Err(ExecutorError::InvalidArguments(_))));
+
+        assert_eq!(executor.plan("list_dir", &[]).unwrap().sandbox_backend, "builtin");
+        assert!(executor.start("list_dir", &[]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_registry_query_is_scoped() {
+        let mut whitelist = CommandWhitelist::default();
+        whitelist.add_windows_builtin_commands();
+        let config = ExecutorConfig {
+            enable_sandbox: false,
+            registry_allowlist: vec![r"HKLM\SOFTWARE\Vendor".to_string()],
+            ..Default::default()
+        };
+        let executor = CommandExecutor::new(config, whitelist);
+
+        // Backslashes are fine in registry keys, other metacharacters are not
+        let result = executor
+            .execute("reg_query", &[r"HKLM\SOFTWARE\Other".to_string()])
+            .await;
+        assert!(matches!(result, Err(ExecutorError::PermissionDenied(_))));
+        let result = executor
+            .execute("reg_query", &[r"HKLM\SOFTWARE\Vendor;x".to_string()])
+            .await;
+        assert!(matches!(result, Err(ExecutorError::InvalidArguments(_))));
+
+        #[cfg(not(windows))]
+        {
+            let result = executor
+                .execute("reg_query", &[r"HKLM\SOFTWARE\Vendor\App".to_string()])
+                .await;
+            assert!(matches!(result, Err(ExecutorError::ExecutionFailed(_))));
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_jail_confines_working_directory() {
+        let root = tempfile::tempdir().unwrap();
+        let config = ExecutorConfig {
+            enable_sandbox: false,
+            jail_root: Some(root.path().to_string_lossy().to_string()),
+            ..Default::default()
+        };
+        let executor = CommandExecutor::new(config, CommandWhitelist::default());
+        let jail = executor.jail_dir().unwrap().to_path_buf();
+        assert!(jail.starts_with(root.path().canonicalize().unwrap()));
+        std::fs::write(jail.join("notes.txt"), "hi").unwrap();
+
+        let result = executor.execute("cat", &["notes.txt".to_string()]).await.unwrap();
+        assert_eq!(result.stdout.trim(), "hi");
+        assert_eq!(
+            executor.plan("cat", &[]).unwrap().working_dir,
+            Some(jail.to_string_lossy().to_string())
+        );
+
+        let result = executor.execute("cat", &["../notes.txt".to_string()]).await;
+        assert!(matches!(result, Err(ExecutorError::InvalidArguments(_))));
+
+        drop(executor);
+        assert!(!jail.exists());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_cacheable_results_are_reused() {
+        use crate::cache::{CacheConfig, ResultCache};
+
+        let mut whitelist = CommandWhitelist::new();
+        for (name, cacheable) in [("date", true), ("echo", false)] {
+            whitelist.add_command(
+                name,
+                WhitelistEntry {
+                    command: name.to_string(),
+                    cacheable,
+                    ..Default::default()
+                },
+            );
+        }
+        let config = ExecutorConfig {
+            enable_sandbox: false,
+            ..Default::default()
+        };
+        let cache = Arc::new(ResultCache::new(CacheConfig::default()));
+        let executor = CommandExecutor::new(config, whitelist).with_result_cache(cache.clone());
+
+        let args = vec!["+%s%N".to_string()];
+        let first = executor.execute("date", &args).await.unwrap();
+        let second = executor.execute("date", &args).await.unwrap();
+        assert!(!first.cached && second.cached);
+        assert_eq!(first.stdout, second.stdout);
+
+        let other = executor.execute("date", &["+%N".to_string()]).await.unwrap();
+        assert!(!other.cached);
+
+        executor.execute("echo", &[]).await.unwrap();
+        assert!(!executor.execute("echo", &[]).await.unwrap().cached);
+
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses, stats.entries), (1, 2, 2));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_cache_respects_capability_limits() {
+        use crate::cache::{CacheConfig, ResultCache};
+        use crate::capability::Capability;
+
+        let mut whitelist = CommandWhitelist::new();
+        whitelist.add_command(
+            "seq",
             WhitelistEntry {
-                command: "echo".to_string(),
-                description: Some("Echo text".to_string()),
-                max_args: Some(10),
-                allowed_arg_patterns: None,
-                requires_sudo: false,
+                command: "seq".to_string(),
+                cacheable: true,
+                ..Default::default()
             },
         );
+        let config = ExecutorConfig {
+            enable_sandbox: false,
+            ..Default::default()
+        };
+        let authority = CapabilityAuthority::new("secret");
+        let cache = Arc::new(ResultCache::new(CacheConfig::default()));
+        let executor = CommandExecutor::new(config, whitelist)
+            .with_result_cache(cache.clone())
+            .with_capability_authority(authority.clone());
+        let args = vec!["100".to_string()];
+
+        // A wide capability fills the cache
+        let wide = authority.issue(&Capability::new("wide", &["seq"]));
+        let first = executor
+            .execute_with_capability("seq", &args, &wide)
+            .await
+            .unwrap();
+        let second = executor
+            .execute_with_capability("seq", &args, &wide)
+            .await
+            .unwrap();
+        assert!(!first.cached && second.cached);
+
+        // A narrower one still runs the command and hits its limit
+        let narrow = authority.issue(&Capability {
+            max_output_bytes: Some(10),
+            ..Capability::new("narrow", &["seq"])
+        });
+        let result = executor
+            .execute_with_capability("seq", &args, &narrow)
+            .await;
+        assert!(matches!(
+            result,
+            Err(ExecutorError::ResourceLimitExceeded(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_post_processors_summarize_output() {
+        use crate::postprocess::PostProcess;
+
+        struct Shout;
+
+        impl OutputPostProcessor for Shout {
+            fn summarize(&self, result: &CommandResult) -> Option<String> {
+                Some(result.stdout.trim().to_uppercase())
+            }
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        for i in 0..12 {
+            std::fs::write(dir.path().join(format!("{:02}.txt", i)), "").unwrap();
+        }
 
         let config = ExecutorConfig {
-            enable_sandbox: false, // Disable sandbox for simple test
+            enable_sandbox: false,
+            working_dir: Some(dir.path().to_string_lossy().to_string()),
+            ..Default::default()
+        };
+        let mut whitelist = CommandWhitelist::default();
+        whitelist.add_command(
+            "list_dir",
+            WhitelistEntry {
+                command: "list_dir".to_string(),
+                builtin: Some(Builtin::ListDir),
+                post_process: Some(PostProcess::Truncate { head: 2, tail: 1 }),
+                ..Default::default()
+            },
+        );
+        let executor = CommandExecutor::new(config, whitelist)
+            .with_post_processor("read_file", Arc::new(Shout));
+
+        let result = executor.execute("list_dir", &[]).await.unwrap();
+        assert_eq!(result.stdout.lines().count(), 12);
+        assert_eq!(
+            result.summary.as_deref(),
+            Some("00.txt\n01.txt\n... 9 more lines\n11.txt")
+        );
+
+        let result = executor.execute("read_file", &["00.txt".to_string()]).await.unwrap();
+        assert_eq!(result.summary.as_deref(), Some(""));
+
+        // Failures get no summary
+        let result = executor.execute("read_file", &["missing".to_string()]).await.unwrap();
+        assert_eq!(result.summary, None);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_platform_executable_replaces_command() {
+        use crate::whitelist::PlatformExecutable;
+
+        let mut whitelist = CommandWhitelist::new();
+        for (name, program) in [("greet", "echo"), ("shell", "cmd")] {
+            whitelist.add_command(
+                name,
+                WhitelistEntry {
+                    command: name.to_string(),
+                    platforms: HashMap::from([(
+                        Platform::current(),
+                        PlatformExecutable {
+                            program: program.to_string(),
+                            args: vec!["hello".to_string()],
+                        },
+                    )]),
+                    ..Default::default()
+                },
+            );
+        }
+
+        let config = ExecutorConfig {
+            enable_sandbox: false,
+            ..Default::default()
+        };
+        let executor = CommandExecutor::new(config, whitelist);
+
+        let result = executor.execute("greet", &["world".to_string()]).await.unwrap();
+        assert_eq!(result.stdout.trim(), "hello world");
+        assert_eq!(result.command, "greet");
+
+        let plan = executor.plan("greet", &["world".to_string()]).unwrap();
+        assert!(plan.resolved_path.ends_with("echo"));
+        assert_eq!(plan.argv[1..], ["hello", "world"]);
+
+        assert!(matches!(
+            executor.plan("shell", &["%PATH%".to_string()]),
+            Err(ExecutorError::InvalidArguments(_))
+        ));
+    }
+
+    #[test]
+    fn test_powershell_entries_run_constrained_cmdlets() {
+        let mut whitelist = CommandWhitelist::new();
+        whitelist.add_powershell_commands();
+
+        let config = ExecutorConfig {
+            enable_sandbox: false,
+            ..Default::default()
+        };
+        let executor = CommandExecutor::new(config, whitelist);
+
+        let plan = executor
+            .plan("Get-Content", &["-Path".to_string(), r"C:\notes.txt".to_string()])
+            .unwrap();
+        assert!(plan.resolved_path.contains(crate::powershell::program()));
+        assert_eq!(plan.argv[1..3], ["-NoProfile", "-NonInteractive"]);
+        assert!(plan
+            .argv
+            .last()
+            .unwrap()
+            .ends_with(r"Get-Content -Path 'C:\notes.txt'"));
+
+        assert!(matches!(
+            executor.plan("Get-Content", &["-Encoding".to_string(), "utf8".to_string()]),
+            Err(ExecutorError::InvalidArguments(_))
+        ));
+    }
+
+    #[test]
+    fn test_plan_without_sandbox() {
+        let mut config = ExecutorConfig {
+            enable_sandbox: false,
+            working_dir: Some("/tmp".to_string()),
+            ..Default::default()
+        };
+        config.env_vars.insert("LANG".to_string(), "C".to_string());
+        let executor = CommandExecutor::new(config, CommandWhitelist::default());
+
+        let plan = executor.plan("echo", &["hi".to_string()]).unwrap();
+
+        assert_eq!(plan.argv.last().map(String::as_str), Some("hi"));
+        assert_eq!(plan.argv[0], plan.resolved_path);
+        assert_eq!(plan.sandbox_backend, "disabled");
+        assert_eq!(plan.limits, ResourceLimits::default());
+        assert_eq!(plan.working_dir.as_deref(), Some("/tmp"));
+        assert_eq!(plan.env.get("LANG").map(String::as_str), Some("C"));
+        assert!(plan.summary().starts_with("I will run echo hi without a sandbox"));
+    }
+
+    #[test]
+    fn test_plan_rejects_invalid_commands() {
+        let executor = CommandExecutor::new(ExecutorConfig::default(), CommandWhitelist::default());
+
+        assert!(matches!(
+            executor.plan("rm", &[]),
+            Err(ExecutorError::CommandNotWhitelisted(_))
+        ));
+        assert!(matches!(
+            executor.plan("cat", &["a; rm -rf /".to_string()]),
+            Err(ExecutorError::InvalidArguments(_))
+        ));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_plan_with_in_process_sandbox() {
+        let executor = sandboxed_executor("cat", SeccompProfile::ReadOnly);
+        let plan = executor.plan("cat", &["notes.txt".to_string()]).unwrap();
+
+        assert_eq!(plan.sandbox_backend, "none");
+        assert_eq!(plan.argv, vec![plan.resolved_path.clone(), "notes.txt".to_string()]);
+        assert_eq!(plan.seccomp_profile, SeccompProfile::ReadOnly);
+        assert_eq!(plan.limits.max_processes, Some(16));
+        assert!(plan.limits.memory_mb.is_some());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_cancel_running_command() {
+        let executor = unsandboxed_executor("sleep");
+        let started = std::time::Instant::now();
+
+        let handle = executor.start("sleep", &["30".to_string()]).await.unwrap();
+        assert!(handle.pid().is_some());
+
+        let canceller = handle.canceller();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            canceller.cancel();
+        });
+
+        let result = handle.wait().await;
+        assert!(matches!(result, Err(ExecutorError::Cancelled)));
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_start_then_wait_returns_result() {
+        let executor = unsandboxed_executor("echo");
+        let handle = executor.start("echo", &["done".to_string()]).await.unwrap();
+        let result = handle.wait().await.unwrap();
+
+        assert!(result.success);
+        assert_eq!(result.stdout, "done\n");
+    }
+
+    /// Live (non-zombie) processes whose process group id is `pgid`
+    #[cfg(target_os = "linux")]
+    fn process_group_members(pgid: u32) -> Vec<u32> {
+        std::fs::read_dir("/proc")
+            .unwrap()
+            .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse::<u32>().ok())
+            .filter(|pid| {
+                let Ok(stat) = std::fs::read_to_string(format!("/proc/{}/stat", pid)) else {
+                    return false;
+                };
+                // Fields after the parenthesised command name: state ppid pgrp ...
+                let Some(close) = stat.rfind(')') else {
+                    return false;
+                };
+                let fields: Vec<&str> = stat[close + 1..].split_whitespace().collect();
+                fields.len() > 2 && fields[0] != "Z" && fields[2] == pgid.to_string()
+            })
+            .collect()
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_cancel_kills_process_tree() {
+        let mut whitelist = CommandWhitelist::new();
+        whitelist.add_command(
+            "sh",
+            WhitelistEntry {
+                command: "sh".to_string(),
+                max_args: Some(2),
+                ..Default::default()
+            },
+        );
+
+        let config = ExecutorConfig {
+            enable_sandbox: false,
+            allow_shell: true,
             ..Default::default()
         };
+        let executor = CommandExecutor::new(config, whitelist);
+
+        let handle = executor
+            .start(
+                "sh",
+                &["-c".to_string(), "sleep 30 & sleep 30 & wait".to_string()],
+            )
+            .await
+            .unwrap();
+        let pgid = handle.pid().unwrap();
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(process_group_members(pgid).len() >= 3);
+
+        handle.cancel();
+        assert!(matches!(handle.wait().await, Err(ExecutorError::Cancelled)));
+
+        // SIGKILL delivery is asynchronous
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        while !process_group_members(pgid).is_empty() {
+            assert!(std::time::Instant::now() < deadline, "process tree survived cancel");
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    fn sandboxed_executor(command: &str, profile: SeccompProfile) -> CommandExecutor {
+        let mut whitelist = CommandWhitelist::new();
+        whitelist.add_command(
+            command,
+            WhitelistEntry {
+                command: command.to_string(),
+                description: None,
+                max_args: Some(5),
+                allowed_arg_patterns: None,
+                requires_sudo: false,
+                seccomp_profile: profile,
+                ..Default::default()
+            },
+        );
+
+        CommandExecutor::new(in_process_sandbox_config(), whitelist)
+    }
+
+    #[cfg(all(target_os = "linux", feature = "root-tests"))]
+    #[tokio::test]
+    async fn test_drops_privileges_when_root() {
+        let nobody = nix::unistd::User::from_name("nobody").unwrap().unwrap();
+        let mut config = in_process_sandbox_config();
+        config.sandbox.drop_to_user = Some("nobody".to_string());
+        let mut whitelist = CommandWhitelist::new();
+        whitelist.add_command(
+            "id",
+            WhitelistEntry {
+                command: "id".to_string(),
+                ..Default::default()
+            },
+        );
+        let executor = CommandExecutor::new(config, whitelist);
+
+        let uid = executor.execute("id", &["-u".to_string()]).await.unwrap();
+        assert_eq!(uid.stdout.trim(), nobody.uid.to_string());
+
+        // Supplementary groups are cleared: only the primary group remains
+        let groups = executor.execute("id", &["-G".to_string()]).await.unwrap();
+        assert_eq!(groups.stdout.trim(), nobody.gid.to_string());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_drop_to_user_is_only_looked_up_as_root() {
+        let mut config = in_process_sandbox_config();
+        config.sandbox.drop_to_user = Some("no-such-user-aetheros".to_string());
+        let executor = CommandExecutor::new(config, CommandWhitelist::default());
+
+        let result = executor.execute("echo", &["hi".to_string()]).await;
+        if nix::unistd::geteuid().is_root() {
+            assert!(matches!(result, Err(ExecutorError::SandboxError(_))));
+        } else {
+            assert_eq!(result.unwrap().stdout.trim(), "hi");
+        }
+    }
+
+    #[cfg(all(target_os = "linux", feature = "root-tests"))]
+    #[tokio::test]
+    async fn test_jail_chroot() {
+        let root = tempfile::tempdir().unwrap();
+        let run = |jail_chroot| {
+            let root = root.path().to_string_lossy().to_string();
+            async move {
+                let config = ExecutorConfig {
+                    jail_root: Some(root),
+                    jail_chroot,
+                    ..in_process_sandbox_config()
+                };
+                let executor = CommandExecutor::new(config, CommandWhitelist::default());
+                std::fs::write(executor.jail_dir().unwrap().join("notes.txt"), "hi").unwrap();
+                executor.execute("cat", &["notes.txt".to_string()]).await
+            }
+        };
 
+        assert_eq!(run(false).await.unwrap().stdout.trim(), "hi");
+        // The jail holds no cat binary to exec once the root changes
+        assert!(run(true).await.is_err());
+    }
+
+    #[cfg(all(target_os = "linux", feature = "root-tests"))]
+    #[tokio::test]
+    async fn test_network_denied_commands_get_own_namespace() {
+        let own = std::fs::read_link("/proc/self/ns/net").unwrap();
+        let run = |allow_network| async move {
+            let mut whitelist = CommandWhitelist::new();
+            whitelist.add_command(
+                "readlink",
+                WhitelistEntry {
+                    command: "readlink".to_string(),
+                    allow_network: Some(allow_network),
+                    ..Default::default()
+                },
+            );
+            let executor = CommandExecutor::new(in_process_sandbox_config(), whitelist);
+            let args = ["/proc/self/ns/net".to_string()];
+
+            assert_eq!(
+                executor.plan("readlink", &args).unwrap().network_isolated,
+                !allow_network
+            );
+            let result = executor.execute("readlink", &args).await.unwrap();
+            std::path::PathBuf::from(result.stdout.trim())
+        };
+
+        assert_ne!(run(false).await, own);
+        assert_eq!(run(true).await, own);
+    }
+
+    /// Sandbox config that skips external jails, so in-process confinement is tested
+    #[cfg(target_os = "linux")]
+    fn in_process_sandbox_config() -> ExecutorConfig {
+        let mut config = ExecutorConfig::default();
+        config.sandbox.backend = Some(SandboxBackend::None);
+        // Keep the test user so temp files stay readable under root
+        config.sandbox.drop_to_user = None;
+        // The tests name files outside the working directory
+        config.allowed_roots = Some(vec!["/".to_string()]);
+        config
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_read_only_profile_allows_reads() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("input.txt");
+        std::fs::write(&file, "sandboxed\n").unwrap();
+
+        let executor = sandboxed_executor("cat", SeccompProfile::ReadOnly);
+        let result = executor
+            .execute("cat", &[file.to_string_lossy().to_string()])
+            .await
+            .unwrap();
+
+        assert!(result.success);
+        assert_eq!(result.stdout, "sandboxed\n");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_read_only_profile_blocks_writes() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("created.txt");
+        let args = [file.to_string_lossy().to_string()];
+
+        let executor = sandboxed_executor("touch", SeccompProfile::ReadOnly);
+        let result = executor.execute("touch", &args).await.unwrap();
+        assert!(!result.success);
+        assert!(!file.exists());
+
+        let executor = sandboxed_executor("touch", SeccompProfile::Unrestricted);
+        let result = executor.execute("touch", &args).await.unwrap();
+        assert!(result.success);
+        assert!(file.exists());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_read_only_profile_blocks_nested_exec() {
+        let executor = sandboxed_executor("env", SeccompProfile::ReadOnly);
+        let result = executor.execute("env", &["true".to_string()]).await.unwrap();
+        assert!(!result.success);
+
+        let executor = sandboxed_executor("env", SeccompProfile::NoNetwork);
+        let result = executor.execute("env", &["true".to_string()]).await.unwrap();
+        assert!(result.success);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_landlock_confines_reads_to_allowed_paths() {
+        let allowed = tempfile::tempdir().unwrap();
+        let denied = tempfile::tempdir().unwrap();
+        let allowed_file = allowed.path().join("ok.txt");
+        let denied_file = denied.path().join("secret.txt");
+        std::fs::write(&allowed_file, "ok").unwrap();
+        std::fs::write(&denied_file, "secret").unwrap();
+
+        let mut whitelist = CommandWhitelist::new();
+        whitelist.add_command(
+            "cat",
+            WhitelistEntry {
+                command: "cat".to_string(),
+                max_args: Some(1),
+                allowed_paths: Some(vec![allowed.path().to_string_lossy().to_string()]),
+                ..Default::default()
+            },
+        );
+        let executor = CommandExecutor::new(in_process_sandbox_config(), whitelist);
+
+        let result = executor
+            .execute("cat", &[allowed_file.to_string_lossy().to_string()])
+            .await
+            .unwrap();
+        assert!(result.success);
+        assert_eq!(result.stdout, "ok\n");
+
+        let result = executor
+            .execute("cat", &[denied_file.to_string_lossy().to_string()])
+            .await
+            .unwrap();
+
+        // Without kernel support the command runs unconfined
+        let enforced = crate::landlock_fs::kernel_abi().is_some();
+        assert_eq!(result.success, !enforced);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_cgroup_usage_reported() {
+        let Some(root) = ["/sys/fs/cgroup", "/sys/fs/cgroup/unified"]
+            .into_iter()
+            .find(|p| std::path::Path::new(p).join("cgroup.procs").exists())
+        else {
+            return;
+        };
+
+        let mut config = in_process_sandbox_config();
+        config.sandbox.cgroup_root = Some(root.to_string());
+        let executor = CommandExecutor::new(config, CommandWhitelist::default());
+
+        match executor.execute("echo", &["cgroup".to_string()]).await {
+            Ok(result) => {
+                assert!(result.success);
+                assert!(result.cpu_usage_usec.is_some());
+            }
+            // Creating cgroups needs a delegated, writable hierarchy
+            Err(ExecutorError::SandboxError(e)) => println!("cgroup unavailable: {}", e),
+            Err(e) => panic!("unexpected error: {}", e),
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_resource_usage_and_cpu_limit_kill() {
+        let mut config = in_process_sandbox_config();
+        config.sandbox.max_cpu_time_secs = Some(1);
+        let mut whitelist = CommandWhitelist::new();
+        for name in ["echo", "sha256sum"] {
+            whitelist.add_command(
+                name,
+                WhitelistEntry {
+                    command: name.to_string(),
+                    ..Default::default()
+                },
+            );
+        }
         let executor = CommandExecutor::new(config, whitelist);
 
+        let result = executor.execute("echo", &["hi".to_string()]).await.unwrap();
+        assert!(result.max_rss_kb.is_some_and(|rss| rss > 0));
+        assert!(result.user_cpu_ms.is_some());
+        assert!(result.sys_cpu_ms.is_some());
+        assert_eq!(result.was_killed_by, None);
+
+        // Hashes forever without output until RLIMIT_CPU fires
         let result = executor
-            .execute("echo", &["Hello".to_string(), "World".to_string()])
-            .await;
+            .execute("sha256sum", &["/dev/zero".to_string()])
+            .await
+            .unwrap();
+        assert!(!result.success);
+        assert_eq!(result.was_killed_by, Some(KillReason::Timeout));
+        assert!(result.user_cpu_ms.unwrap() + result.sys_cpu_ms.unwrap() >= 900);
+    }
 
-        assert!(result.is_ok());
-        let cmd_result = result.unwrap();
-        assert!(cmd_result.success);
-        assert!(cmd_result.stdout.contains("Hello"));
+    #[cfg(windows)]
+    #[tokio::test]
+    async fn test_sandboxed_command_runs_in_job_object() {
+        let mut whitelist = CommandWhitelist::new();
+        whitelist.add_command(
+            "whoami",
+            WhitelistEntry {
+                command: "whoami".to_string(),
+                description: None,
+                max_args: Some(0),
+                allowed_arg_patterns: None,
+                requires_sudo: false,
+                seccomp_profile: SeccompProfile::Unrestricted,
+                ..Default::default()
+            },
+        );
+
+        let executor = CommandExecutor::new(ExecutorConfig::default(), whitelist);
+        let result = executor.execute("whoami", &[]).await.unwrap();
+
+        assert!(result.success);
+        assert!(!result.stdout.trim().is_empty());
     }
 }
@@ -0,0 +1,218 @@
+//! Shell-free glob expansion
+//!
+//! `*`, `?` and `[` are refused as shell metacharacters, so `ls *.txt` cannot
+//! reach a shell. For whitelist entries with `expand_globs` set, the executor
+//! expands such arguments itself before validation: matches are sorted, hidden
+//! files need an explicit leading `.`, every match must resolve inside the
+//! allowed roots, and the total number of paths is capped. The expanded paths
+//! are then validated like any other argument.
+
+use crate::path_scope::{expand_home, PathScope};
+use glob::{MatchOptions, Pattern};
+use std::path::{Component, Path, PathBuf};
+use thiserror::Error;
+
+/// Glob expansion errors
+#[derive(Error, Debug)]
+pub enum GlobError {
+    #[error("Invalid glob pattern {pattern}: {message}")]
+    InvalidPattern { pattern: String, message: String },
+
+    #[error("No files match {0}")]
+    NoMatches(String),
+
+    #[error("{pattern} matches {} outside allowed roots", path.display())]
+    OutsideRoots { pattern: String, path: PathBuf },
+
+    #[error("Glob patterns expand to more than {0} paths")]
+    TooManyPaths(usize),
+}
+
+/// Like a shell: `*` does not cross `/` or match a leading `.`
+const OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: true,
+};
+
+/// Expands glob arguments inside a path scope
+pub struct GlobExpander {
+    scope: PathScope,
+    base_dir: PathBuf,
+    max_paths: usize,
+}
+
+impl GlobExpander {
+    /// Expand relative patterns against `base_dir`, keeping matches inside
+    /// `scope` and at most `max_paths` of them in total
+    pub fn new(scope: PathScope, base_dir: impl Into<PathBuf>, max_paths: usize) -> Self {
+        Self {
+            scope,
+            base_dir: base_dir.into(),
+            max_paths,
+        }
+    }
+
+    /// Replace each pattern argument with the paths it matches
+    ///
+    /// Relative patterns yield relative paths starting `./` and absolute or
+    /// `~` patterns absolute ones. Other arguments are kept as they are.
+    pub fn expand(&self, args: &[String]) -> Result<Vec<String>, GlobError> {
+        let mut expanded = Vec::with_capacity(args.len());
+        let mut matched = 0;
+
+        for arg in args {
+            if !is_pattern(arg) {
+                expanded.push(arg.clone());
+                continue;
+            }
+
+            let paths = self.expand_one(arg, self.max_paths - matched)?;
+            matched += paths.len();
+            expanded.extend(paths);
+        }
+
+        Ok(expanded)
+    }
+
+    fn expand_one(&self, pattern: &str, limit: usize) -> Result<Vec<String>, GlobError> {
+        let path = expand_home(pattern);
+        let relative = path.is_relative();
+        let full = if relative {
+            // The base directory is literal even if its name contains `[` or `*`
+            format!("{}/{}", escape(&self.base_dir), pattern)
+        } else if pattern.starts_with('~') {
+            let home = expand_home("~");
+            format!("{}{}", escape(&home), pattern.trim_start_matches('~'))
+        } else {
+            pattern.to_string()
+        };
+
+        let entries = glob::glob_with(&full, OPTIONS).map_err(|e| GlobError::InvalidPattern {
+            pattern: pattern.to_string(),
+            message: e.msg.to_string(),
+        })?;
+
+        let mut paths = Vec::new();
+        // Unreadable directories are skipped, as a shell would
+        for path in entries.flatten() {
+            let resolved = self.scope.resolve(&path.to_string_lossy());
+            if !self.scope.contains(&resolved) {
+                return Err(GlobError::OutsideRoots {
+                    pattern: pattern.to_string(),
+                    path: resolved,
+                });
+            }
+            if paths.len() == limit {
+                return Err(GlobError::TooManyPaths(self.max_paths));
+            }
+
+            let path = if relative {
+                dot_slash(path.strip_prefix(&self.base_dir).unwrap_or(&path))
+            } else {
+                path
+            };
+            paths.push(path.to_string_lossy().to_string());
+        }
+
+        if paths.is_empty() {
+            return Err(GlobError::NoMatches(pattern.to_string()));
+        }
+
+        Ok(paths)
+    }
+}
+
+/// Whether an argument is a glob pattern (flags never are)
+pub fn is_pattern(arg: &str) -> bool {
+    !arg.starts_with('-') && arg.contains(['*', '?', '['])
+}
+
+/// `./` before a bare relative match, so a file named like `-rf` is not
+/// taken for a flag
+fn dot_slash(path: &Path) -> PathBuf {
+    match path.components().next() {
+        Some(Component::Normal(_)) => Path::new(".").join(path),
+        _ => path.to_path_buf(),
+    }
+}
+
+fn escape(path: &Path) -> String {
+    Pattern::escape(&path.to_string_lossy())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn expander(root: &Path, max_paths: usize) -> GlobExpander {
+        let scope = PathScope::new(&[root.to_string_lossy().to_string()], root);
+        GlobExpander::new(scope, root, max_paths)
+    }
+
+    #[test]
+    fn test_expand() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        for name in ["b.txt", "a.txt", "c.md", ".hidden.txt", "sub/d.txt"] {
+            let path = root.join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "x").unwrap();
+        }
+        let expander = expander(&root, 3);
+
+        assert_eq!(
+            expander.expand(&args(&["-l", "*.txt", "c.md"])).unwrap(),
+            args(&["-l", "./a.txt", "./b.txt", "c.md"])
+        );
+        assert_eq!(
+            expander.expand(&args(&["*/?.txt"])).unwrap(),
+            args(&["./sub/d.txt"])
+        );
+
+        let absolute = format!("{}/[ab].txt", root.display());
+        assert_eq!(
+            expander.expand(&[absolute]).unwrap(),
+            vec![
+                root.join("a.txt").to_string_lossy().to_string(),
+                root.join("b.txt").to_string_lossy().to_string(),
+            ]
+        );
+
+        assert!(matches!(
+            expander.expand(&args(&["*.txt", "*.md", "*"])),
+            Err(GlobError::TooManyPaths(3))
+        ));
+        assert!(matches!(
+            expander.expand(&args(&["*.rs"])),
+            Err(GlobError::NoMatches(_))
+        ));
+        // Matches that look like flags stay paths
+        fs::write(root.join("-rf"), "x").unwrap();
+        assert_eq!(expander.expand(&args(&["-*"])).unwrap(), args(&["-*"]));
+        assert_eq!(expander.expand(&args(&["?rf"])).unwrap(), args(&["./-rf"]));
+
+        assert!(matches!(
+            expander.expand(&args(&["[.txt"])),
+            Err(GlobError::InvalidPattern { .. })
+        ));
+    }
+
+    #[test]
+    fn test_matches_stay_inside_roots() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap().join("root");
+        fs::create_dir(&root).unwrap();
+        fs::write(dir.path().join("secret.txt"), "s").unwrap();
+
+        let err = expander(&root, 10)
+            .expand(&args(&["../*.txt"]))
+            .unwrap_err();
+        assert!(matches!(err, GlobError::OutsideRoots { .. }));
+    }
+}
@@ -0,0 +1,89 @@
+//! Per-session working directory jails
+//!
+//! With `ExecutorConfig::jail_root` set, each executor gets its own fresh
+//! directory below that root. Commands run in it, relative path arguments
+//! must stay inside it, and it is removed with everything in it when the
+//! executor is dropped, so experiments leave nothing behind.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{debug, warn};
+
+static NEXT_JAIL: AtomicU64 = AtomicU64::new(0);
+
+/// A session directory, deleted on drop
+#[derive(Debug)]
+pub struct Jail {
+    dir: PathBuf,
+}
+
+impl Jail {
+    /// Create a new, empty session directory below `root` (created if missing)
+    pub fn create(root: impl AsRef<Path>) -> io::Result<Self> {
+        let root = root.as_ref();
+        fs::create_dir_all(root)?;
+
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or_default();
+        let name = format!(
+            "session-{}-{}-{}",
+            std::process::id(),
+            NEXT_JAIL.fetch_add(1, Ordering::Relaxed),
+            nanos
+        );
+
+        // Fails rather than reuse a directory someone else prepared
+        let dir = root.join(name);
+        fs::create_dir(&dir)?;
+        let dir = dir.canonicalize()?;
+
+        debug!("Created jail {}", dir.display());
+        Ok(Self { dir })
+    }
+
+    /// Hand the directory to the user commands run as after privileges drop
+    #[cfg(unix)]
+    pub(crate) fn chown(&self, uid: libc::uid_t, gid: libc::gid_t) -> io::Result<()> {
+        std::os::unix::fs::chown(&self.dir, Some(uid), Some(gid))
+    }
+
+    /// The session directory
+    pub fn path(&self) -> &Path {
+        &self.dir
+    }
+}
+
+impl Drop for Jail {
+    fn drop(&mut self) {
+        match fs::remove_dir_all(&self.dir) {
+            Ok(()) => debug!("Removed jail {}", self.dir.display()),
+            Err(e) => warn!("Failed to remove jail {}: {}", self.dir.display(), e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sessions_are_separate_and_cleaned_up() {
+        let root = tempfile::tempdir().unwrap();
+        let first = Jail::create(root.path().join("jails")).unwrap();
+        let second = Jail::create(root.path().join("jails")).unwrap();
+        assert_ne!(first.path(), second.path());
+
+        fs::create_dir(first.path().join("scratch")).unwrap();
+        fs::write(first.path().join("scratch/notes.txt"), "x").unwrap();
+        let dir = first.path().to_path_buf();
+        drop(first);
+
+        assert!(!dir.exists());
+        assert!(second.path().is_dir());
+    }
+}
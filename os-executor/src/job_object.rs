@@ -0,0 +1,237 @@
+//! Windows Job Object sandbox backend
+//!
+//! Child processes are assigned to a job right after spawn. The job enforces a
+//! per-process memory limit, an active process cap and an optional hard CPU
+//! rate cap, and kills every process in the job when the handle is closed.
+
+use crate::sandbox::{ProcessUsage, SandboxConfig, SandboxError};
+use std::io;
+use std::mem;
+use std::ptr;
+use tracing::debug;
+use winapi::shared::minwindef::{DWORD, LPVOID};
+use winapi::um::handleapi::CloseHandle;
+use winapi::um::jobapi2::{
+    AssignProcessToJobObject, CreateJobObjectW, QueryInformationJobObject,
+    SetInformationJobObject, TerminateJobObject,
+};
+use winapi::um::winnt::{
+    JobObjectBasicAccountingInformation, JobObjectCpuRateControlInformation,
+    JobObjectExtendedLimitInformation, HANDLE, JOBOBJECT_BASIC_ACCOUNTING_INFORMATION,
+    JOBOBJECT_CPU_RATE_CONTROL_INFORMATION, JOBOBJECT_EXTENDED_LIMIT_INFORMATION,
+    JOB_OBJECT_CPU_RATE_CONTROL_ENABLE, JOB_OBJECT_CPU_RATE_CONTROL_HARD_CAP,
+    JOB_OBJECT_LIMIT_ACTIVE_PROCESS, JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE,
+    JOB_OBJECT_LIMIT_PROCESS_MEMORY,
+};
+
+/// Owned Job Object handle
+pub struct JobObject {
+    handle: HANDLE,
+}
+
+// The handle is only used through thread-safe kernel calls
+unsafe impl Send for JobObject {}
+unsafe impl Sync for JobObject {}
+
+impl JobObject {
+    /// Create an anonymous job with limits taken from the sandbox config
+    pub fn from_config(config: &SandboxConfig) -> Result<Self, SandboxError> {
+        let handle = unsafe { CreateJobObjectW(ptr::null_mut(), ptr::null()) };
+        if handle.is_null() {
+            return Err(SandboxError::ResourceLimitFailed(format!(
+                "CreateJobObjectW failed: {}",
+                io::Error::last_os_error()
+            )));
+        }
+
+        let job = Self { handle };
+        job.set_limits(config.max_memory_mb, config.max_processes)?;
+
+        if let Some(percent) = config.cpu_rate_percent {
+            job.set_cpu_rate(percent)?;
+        }
+
+        Ok(job)
+    }
+
+    /// Assign a running process to this job
+    pub fn assign(&self, process: HANDLE) -> Result<(), SandboxError> {
+        if unsafe { AssignProcessToJobObject(self.handle, process) } == 0 {
+            return Err(SandboxError::ResourceLimitFailed(format!(
+                "AssignProcessToJobObject failed: {}",
+                io::Error::last_os_error()
+            )));
+        }
+
+        debug!("Assigned process to job object");
+        Ok(())
+    }
+
+    /// Terminate every process in the job
+    pub fn terminate(&self, exit_code: u32) -> io::Result<()> {
+        if unsafe { TerminateJobObject(self.handle, exit_code) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        debug!("Terminated job object");
+        Ok(())
+    }
+
+    /// CPU time and peak process memory of everything that ran in the job
+    pub fn usage(&self) -> Option<ProcessUsage> {
+        let mut accounting: JOBOBJECT_BASIC_ACCOUNTING_INFORMATION = unsafe { mem::zeroed() };
+        let mut limits: JOBOBJECT_EXTENDED_LIMIT_INFORMATION = unsafe { mem::zeroed() };
+
+        if let Err(e) = self.query_information(
+            JobObjectBasicAccountingInformation,
+            &mut accounting as *mut _ as LPVOID,
+            mem::size_of_val(&accounting),
+        ) {
+            debug!("Job accounting unavailable: {}", e);
+            return None;
+        }
+
+        let max_rss_kb = self
+            .query_information(
+                JobObjectExtendedLimitInformation,
+                &mut limits as *mut _ as LPVOID,
+                mem::size_of_val(&limits),
+            )
+            .ok()
+            .map(|_| limits.PeakProcessMemoryUsed as u64 / 1024);
+
+        // Times are in 100ns units
+        let to_ms = |time: &winapi::shared::ntdef::LARGE_INTEGER| {
+            (unsafe { *time.QuadPart() } as u64) / 10_000
+        };
+
+        Some(ProcessUsage {
+            max_rss_kb,
+            user_cpu_ms: Some(to_ms(&accounting.TotalUserTime)),
+            sys_cpu_ms: Some(to_ms(&accounting.TotalKernelTime)),
+        })
+    }
+
+    fn set_limits(
+        &self,
+        max_memory_mb: Option<u64>,
+        max_processes: Option<u32>,
+    ) -> Result<(), SandboxError> {
+        let mut info: JOBOBJECT_EXTENDED_LIMIT_INFORMATION = unsafe { mem::zeroed() };
+        info.BasicLimitInformation.LimitFlags = JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE;
+
+        if let Some(mem_mb) = max_memory_mb {
+            info.BasicLimitInformation.LimitFlags |= JOB_OBJECT_LIMIT_PROCESS_MEMORY;
+            info.ProcessMemoryLimit = (mem_mb * 1024 * 1024) as usize;
+        }
+
+        if let Some(count) = max_processes {
+            info.BasicLimitInformation.LimitFlags |= JOB_OBJECT_LIMIT_ACTIVE_PROCESS;
+            info.BasicLimitInformation.ActiveProcessLimit = count;
+        }
+
+        self.set_information(
+            JobObjectExtendedLimitInformation,
+            &mut info as *mut _ as LPVOID,
+            mem::size_of::<JOBOBJECT_EXTENDED_LIMIT_INFORMATION>(),
+        )
+    }
+
+    fn set_cpu_rate(&self, percent: u32) -> Result<(), SandboxError> {
+        if percent == 0 || percent > 100 {
+            return Err(SandboxError::ConfigError(format!(
+                "CPU rate must be between 1 and 100 percent, got {}",
+                percent
+            )));
+        }
+
+        let mut info: JOBOBJECT_CPU_RATE_CONTROL_INFORMATION = unsafe { mem::zeroed() };
+        info.ControlFlags =
+            JOB_OBJECT_CPU_RATE_CONTROL_ENABLE | JOB_OBJECT_CPU_RATE_CONTROL_HARD_CAP;
+        // CpuRate is expressed in 1/100ths of a percent
+        unsafe {
+            *info.u.CpuRate_mut() = percent * 100;
+        }
+
+        self.set_information(
+            JobObjectCpuRateControlInformation,
+            &mut info as *mut _ as LPVOID,
+            mem::size_of::<JOBOBJECT_CPU_RATE_CONTROL_INFORMATION>(),
+        )
+    }
+
+    fn set_information(
+        &self,
+        class: winapi::um::winnt::JOBOBJECTINFOCLASS,
+        info: LPVOID,
+        len: usize,
+    ) -> Result<(), SandboxError> {
+        if unsafe { SetInformationJobObject(self.handle, class, info, len as DWORD) } == 0 {
+            return Err(SandboxError::ResourceLimitFailed(format!(
+                "SetInformationJobObject failed: {}",
+                io::Error::last_os_error()
+            )));
+        }
+
+        Ok(())
+    }
+
+    fn query_information(
+        &self,
+        class: winapi::um::winnt::JOBOBJECTINFOCLASS,
+        info: LPVOID,
+        len: usize,
+    ) -> io::Result<()> {
+        let ok = unsafe {
+            QueryInformationJobObject(self.handle, class, info, len as DWORD, ptr::null_mut())
+        };
+        if ok == 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for JobObject {
+    fn drop(&mut self) {
+        // KILL_ON_JOB_CLOSE terminates anything still running in the job
+        unsafe {
+            CloseHandle(self.handle);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_create_job_with_default_limits() {
+        let job = JobObject::from_config(&SandboxConfig::default());
+        assert!(job.is_ok());
+    }
+
+    #[test]
+    fn test_create_job_with_cpu_rate() {
+        let config = SandboxConfig {
+            cpu_rate_percent: Some(50),
+            ..Default::default()
+        };
+
+        assert!(JobObject::from_config(&config).is_ok());
+    }
+
+    #[test]
+    fn test_invalid_cpu_rate_rejected() {
+        let config = SandboxConfig {
+            cpu_rate_percent: Some(150),
+            ..Default::default()
+        };
+
+        assert!(matches!(
+            JobObject::from_config(&config),
+            Err(SandboxError::ConfigError(_))
+        ));
+    }
+}
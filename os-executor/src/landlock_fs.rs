@@ -0,0 +1,122 @@
+//! Landlock filesystem confinement (Linux)
+//!
+//! Restricts a command to the paths declared in its whitelist entry, plus the
+//! system paths every dynamically linked binary needs to start. The ruleset is
+//! built in the parent (opening the path descriptors) and enforced in the child
+//! with `restrict_self` right before exec. Kernels without Landlock (before
+//! 5.13, or with the LSM disabled) run the command unconfined with a warning.
+
+use crate::sandbox::SandboxError;
+use landlock::{
+    path_beneath_rules, Access, AccessFs, Ruleset, RulesetAttr, RulesetCreated, RulesetCreatedAttr,
+    ABI,
+};
+use std::path::PathBuf;
+use tracing::{debug, warn};
+
+/// Highest Landlock ABI we request; older kernels degrade to what they support
+const TARGET_ABI: ABI = ABI::V5;
+
+/// Read/execute access needed to load and run system binaries
+pub const SYSTEM_READ_PATHS: &[&str] = &[
+    "/usr",
+    "/bin",
+    "/sbin",
+    "/lib",
+    "/lib32",
+    "/lib64",
+    "/etc/ld.so.cache",
+    "/etc/localtime",
+    "/etc/nsswitch.conf",
+    "/etc/passwd",
+    "/etc/group",
+];
+
+/// Files commands may always read and write
+pub const SYSTEM_WRITE_PATHS: &[&str] = &["/dev/null"];
+
+/// Landlock ABI version supported by the running kernel, if any
+pub fn kernel_abi() -> Option<i32> {
+    // LANDLOCK_CREATE_RULESET_VERSION
+    const VERSION_FLAG: libc::c_uint = 1;
+
+    let version = unsafe {
+        libc::syscall(
+            libc::SYS_landlock_create_ruleset,
+            std::ptr::null::<libc::c_void>(),
+            0usize,
+            VERSION_FLAG,
+        )
+    };
+
+    (version > 0).then_some(version as i32)
+}
+
+/// Expand a leading `~` to the executor's home directory
+pub fn expand_path(path: &str) -> PathBuf {
+    if path == "~" || path.starts_with("~/") {
+        if let Some(home) = std::env::var_os("HOME") {
+            return PathBuf::from(home).join(path.trim_start_matches('~').trim_start_matches('/'));
+        }
+    }
+
+    PathBuf::from(path)
+}
+
+/// Build a ruleset granting read access to `read_paths` and full access to
+/// `write_paths`
+///
+/// Paths that do not exist are skipped. Returns `None` when the kernel has no
+/// Landlock support.
+pub fn build_ruleset(
+    read_paths: &[String],
+    write_paths: &[String],
+) -> Result<Option<RulesetCreated>, SandboxError> {
+    let Some(abi) = kernel_abi() else {
+        warn!("Landlock not available on this kernel; filesystem confinement disabled");
+        return Ok(None);
+    };
+
+    debug!("Building Landlock ruleset (kernel ABI v{})", abi);
+
+    let read: Vec<PathBuf> = SYSTEM_READ_PATHS
+        .iter()
+        .map(PathBuf::from)
+        .chain(read_paths.iter().map(|p| expand_path(p)))
+        .collect();
+
+    let write: Vec<PathBuf> = SYSTEM_WRITE_PATHS
+        .iter()
+        .map(PathBuf::from)
+        .chain(write_paths.iter().map(|p| expand_path(p)))
+        .collect();
+
+    let ruleset = Ruleset::default()
+        .handle_access(AccessFs::from_all(TARGET_ABI))
+        .and_then(|r| r.create())
+        .and_then(|r| r.add_rules(path_beneath_rules(&read, AccessFs::from_read(TARGET_ABI))))
+        .and_then(|r| r.add_rules(path_beneath_rules(&write, AccessFs::from_all(TARGET_ABI))))
+        .map_err(|e| SandboxError::ConfigError(format!("Invalid Landlock ruleset: {}", e)))?;
+
+    Ok(Some(ruleset))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_home() {
+        let home = std::env::var("HOME").unwrap();
+        assert_eq!(expand_path("~"), PathBuf::from(&home));
+        assert_eq!(expand_path("~/docs"), PathBuf::from(&home).join("docs"));
+        assert_eq!(expand_path("/tmp"), PathBuf::from("/tmp"));
+        assert_eq!(expand_path("~other"), PathBuf::from("~other"));
+    }
+
+    #[test]
+    fn test_build_ruleset_degrades_gracefully() {
+        let ruleset = build_ruleset(&["~".to_string()], &[]).unwrap();
+        assert_eq!(ruleset.is_some(), kernel_abi().is_some());
+    }
+}
@@ -1,21 +1,102 @@
 //! OS Executor - Secure command execution with sandboxing
 //!
 //! This module provides safe OS command execution with:
-//! - Command whitelisting
+//! - Command whitelisting, reloaded when the whitelist file changes
 //! - Privilege dropping
 //! - Resource limits (timeout, memory)
+//! - Result cache for repeated read-only queries
+//! - Per-command resource usage and kill reason reporting
+//! - Prioritized execution queue with concurrency limits
+//! - Cron-like and one-shot scheduled commands, persisted across restarts
 //! - Platform abstractions (Windows/macOS/Linux)
+//! - Constrained PowerShell cmdlets
+//! - Registry and service queries through the Windows API, scoped to allowed keys
+//! - Built-in file commands that need no external binary
+//! - Interactive sessions for REPL-like tools, with idle and lifetime limits
 //! - Shell injection protection
+//! - Shell-free glob expansion for commands that opt in
+//! - Path arguments confined to allowed roots
+//! - Per-session jail directories, removed when the executor is dropped
+//! - Journaled file mutations with undo
+//! - seccomp-bpf syscall filtering (Linux)
+//! - Landlock filesystem confinement (Linux)
+//! - cgroup v2 resource control (Linux)
+//! - Tamper-evident audit log
+//! - `doctor` self-check of sandbox, limits and whitelist, with a JSON report
+//! - User confirmation for risky commands
+//! - Low/medium/high risk scoring of requests, usable in policy rules
+//! - Signed capability tokens scoping what callers may run
+//! - Declarative allow/deny/confirm policies
+//! - Secret redaction in captured output
+//! - Output summaries (truncation, line counts, tables) for voice responses
 
+pub mod approval;
+pub mod audit;
+pub mod builtin;
+pub mod cache;
+pub mod capability;
+#[cfg(target_os = "linux")]
+pub mod cgroup;
+pub mod doctor;
 pub mod executor;
+pub mod globbing;
+pub mod jail;
+#[cfg(windows)]
+pub mod job_object;
+#[cfg(target_os = "linux")]
+pub mod landlock_fs;
+pub mod mutations;
+pub mod path_scope;
 pub mod platform;
+pub mod policy;
+pub mod postprocess;
+pub mod powershell;
+pub mod queue;
+pub mod redact;
+pub mod registry;
+pub mod risk;
 pub mod sandbox;
+pub mod scheduler;
+pub mod seccomp;
+#[cfg(windows)]
+pub mod services;
+pub mod session;
 pub mod whitelist;
 
-pub use executor::{CommandExecutor, CommandResult, ExecutorConfig, ExecutorError};
+pub use approval::{ApprovalDecision, ApprovalProvider, ApprovalRequest, StaticApproval};
+pub use audit::{AuditConfig, AuditEntry, AuditError, AuditLog, AuditQuery, AuditRecord};
+pub use builtin::{Builtin, BuiltinContext, BuiltinError};
+pub use cache::{CacheConfig, CacheStats, ResultCache};
+pub use capability::{Capability, CapabilityAuthority, CapabilityError, CapabilityToken};
+pub use doctor::{Check, CheckStatus, DoctorReport};
+pub use executor::{
+    AuthorizedExecutor, Canceller, CommandExecutor, CommandResult, ExecutionHandle, ExecutionPlan, ExecutorConfig,
+    ExecutorError, KillReason, OutputLine, Pipeline, PipelineStage, ResourceLimits,
+};
+pub use globbing::{GlobError, GlobExpander};
+pub use jail::Jail;
+pub use mutations::{
+    JournalRecord, Mutation, MutationConfig, MutationError, Mutator, Transaction,
+};
+pub use path_scope::PathScope;
 pub use platform::{Platform, PlatformInfo};
-pub use sandbox::{Sandbox, SandboxConfig, SandboxError};
-pub use whitelist::{CommandWhitelist, WhitelistEntry, WhitelistError};
+pub use policy::{Policy, PolicyAction, PolicyDecision, PolicyError, PolicyFile, PolicyRule};
+pub use postprocess::{OutputPostProcessor, PostProcess};
+pub use powershell::{PowerShellCmdlet, PowerShellError};
+pub use queue::{ExecutionQueue, Priority, QueueConfig, QueueMetrics};
+pub use redact::{RedactionConfig, Redactor};
+pub use registry::{Hive, RegistryKey};
+pub use risk::{RiskAssessment, RiskLevel};
+pub use sandbox::{
+    ProcessUsage, Sandbox, SandboxBackend, SandboxConfig, SandboxError, SandboxGuard,
+};
+pub use scheduler::{CronExpr, JobRun, Schedule, ScheduledJob, Scheduler, SchedulerError};
+pub use seccomp::SeccompProfile;
+pub use session::{Session, SessionConfig, SessionEnd, SessionError, SessionExecutor};
+pub use whitelist::{
+    CommandWhitelist, PlatformExecutable, SharedWhitelist, WhitelistChanged, WhitelistEntry,
+    WhitelistError, WhitelistWatcher,
+};
 
 /// Current version
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -1,29 +1,91 @@
 //! OS Executor CLI
 
-use os_executor::{CommandExecutor, CommandWhitelist, ExecutorConfig, PlatformInfo};
+use aether_config::{ConfigArgs, ConfigCommand};
+use aether_telemetry::{remote_span, TraceParent};
+use os_executor::{
+    doctor, risk, CheckStatus, CommandExecutor, CommandWhitelist, ExecutorConfig, Platform,
+    PlatformInfo, Policy, PolicyAction, SandboxBackend,
+};
+use serde::Serialize;
 use std::env;
-use tracing_subscriber;
+use tracing::{warn, Instrument, Span};
+
+/// How results are printed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OutputFormat {
+    Text,
+    Json,
+}
+
+impl std::str::FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            _ => Err(format!("Unknown output format: {} (expected text or json)", s)),
+        }
+    }
+}
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // Initialize logging
-    tracing_subscriber::fmt()
-        .with_env_filter("info")
-        .init();
+    // Initialize logging; stderr keeps stdout clean for JSON output. Spans
+    // are exported when OTEL_EXPORTER_OTLP_ENDPOINT is set
+    aether_telemetry::init("os-executor", "info")?;
+
+    let mut args: Vec<String> = env::args().collect();
+
+    // Global options come before the subcommand
+    let mut output = OutputFormat::Text;
+    let mut config_args = ConfigArgs::default();
+    while args.len() > 1 {
+        if config_args.take(&mut args, 1)? {
+            continue;
+        }
+        if !args[1].starts_with("--output") {
+            break;
+        }
 
-    let args: Vec<String> = env::args().collect();
+        let value = match args[1].strip_prefix("--output=") {
+            Some(value) => value.to_string(),
+            None if args[1] == "--output" && args.len() > 2 => args.remove(2),
+            None => {
+                eprintln!("Usage: os-executor --output <text|json> <command> ...");
+                std::process::exit(1);
+            }
+        };
+        args.remove(1);
+
+        output = match value.parse() {
+            Ok(format) => format,
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        };
+    }
 
     if args.len() < 2 {
         print_usage();
         return Ok(());
     }
 
+    if let Some(command) = ConfigCommand::parse(&args[1]) {
+        if let Err(e) = command.run(&config_args) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
     match args[1].as_str() {
         "info" => {
-            show_platform_info();
+            show_platform_info(output)?;
         }
         "list" => {
-            list_commands();
+            list_commands(output)?;
         }
         "exec" => {
             if args.len() < 3 {
@@ -34,10 +96,46 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             let command = &args[2];
             let cmd_args: Vec<String> = args[3..].to_vec();
 
-            execute_command(command, &cmd_args).await?;
+            // Part of the caller's trace when it passes one
+            let span = trace_parent().as_ref().map_or_else(Span::none, remote_span);
+            let result = execute_command(&config_args, command, &cmd_args, output)
+                .instrument(span)
+                .await;
+            aether_telemetry::shutdown();
+            result?;
+        }
+        "plan" => {
+            if args.len() < 3 {
+                eprintln!("Usage: os-executor plan <command> [args...]");
+                std::process::exit(1);
+            }
+
+            plan_command(&config_args, &args[2], &args[3..])?;
         }
-        "test" => {
-            run_tests().await?;
+        "policy" => {
+            if args.len() < 5 || args[2] != "test" {
+                eprintln!("Usage: os-executor policy test <policy-file> <command> [args...]");
+                std::process::exit(1);
+            }
+
+            test_policy(&args[3], &args[4], &args[5..])?;
+        }
+        "doctor" => {
+            let mut json = output == OutputFormat::Json;
+            let mut whitelist = None;
+            let mut rest = args[2..].iter();
+            while let Some(flag) = rest.next() {
+                match flag.as_str() {
+                    "--json" => json = true,
+                    "--whitelist" if rest.len() > 0 => whitelist = rest.next().map(String::as_str),
+                    _ => {
+                        eprintln!("Usage: os-executor doctor [--json] [--whitelist <file>]");
+                        std::process::exit(1);
+                    }
+                }
+            }
+
+            run_doctor(&config_args, json, whitelist).await?;
         }
         _ => {
             eprintln!("Unknown command: {}", args[1]);
@@ -56,17 +154,63 @@ fn print_usage() {
     println!("  os-executor info              Show platform information");
     println!("  os-executor list              List whitelisted commands");
     println!("  os-executor exec <cmd> [args] Execute a whitelisted command");
-    println!("  os-executor test              Run self-tests");
+    println!("  os-executor plan <cmd> [args] Show how a command would run (dry run)");
+    println!("  os-executor policy test <file> <cmd> [args]");
+    println!("                                Check a command against a policy file");
+    println!("  os-executor doctor [--json] [--whitelist <file>]");
+    println!("                                Check sandbox, limits and whitelist");
+    println!("  os-executor validate          Check the configuration");
+    println!("  os-executor print-effective-config");
+    println!("                                Print the configuration after all layers");
+    println!();
+    println!("Options:");
+    println!("  --output <text|json>          Print info, list and exec results as JSON");
+    println!("  --config <file>               Config file, TOML or YAML (default: AETHER_CONFIG)");
+    println!("  --set <key>=<value>           Override a config value, e.g. os_executor.allow_shell=true");
+    println!();
+    println!("Environment:");
+    println!("  TRACEPARENT                   W3C trace context exec continues");
+    println!("  OTEL_EXPORTER_OTLP_ENDPOINT   Export spans (built with the otel feature)");
     println!();
     println!("Examples:");
     println!("  os-executor exec ls -la");
     println!("  os-executor exec cat /etc/hosts");
     println!("  os-executor exec echo Hello World");
+    println!("  os-executor --output json exec ls -la");
+}
+
+/// The caller's trace from `TRACEPARENT`, for `exec` to continue
+fn trace_parent() -> Option<TraceParent> {
+    let header = env::var("TRACEPARENT").ok()?;
+    match header.parse() {
+        Ok(parent) => Some(parent),
+        Err(e) => {
+            warn!("Ignoring TRACEPARENT: {}", e);
+            None
+        }
+    }
 }
 
-fn show_platform_info() {
+/// `info` output: platform details plus the jail backend in use
+#[derive(Serialize)]
+struct InfoOutput {
+    #[serde(flatten)]
+    info: PlatformInfo,
+    sandbox_backend: &'static str,
+}
+
+fn show_platform_info(output: OutputFormat) -> Result<(), Box<dyn std::error::Error>> {
     let info = PlatformInfo::detect();
 
+    if output == OutputFormat::Json {
+        let info = InfoOutput {
+            info,
+            sandbox_backend: SandboxBackend::detect().name(),
+        };
+        println!("{}", serde_json::to_string_pretty(&info)?);
+        return Ok(());
+    }
+
     println!("Platform Information:");
     println!("  OS: {} ({})", info.os_name, info.platform.name());
     println!("  Version: {}", info.os_version);
@@ -77,12 +221,22 @@ fn show_platform_info() {
     println!("  User: {}", info.username);
     println!("  Home: {}", info.home_dir.as_deref().unwrap_or("unknown"));
     println!("  Sandbox Support: {}", info.has_sandbox_support);
+    println!("  Sandbox Backend: {}", SandboxBackend::detect().name());
+
+    Ok(())
 }
 
-fn list_commands() {
+fn list_commands(output: OutputFormat) -> Result<(), Box<dyn std::error::Error>> {
     let whitelist = CommandWhitelist::default();
     let commands = whitelist.commands();
 
+    if output == OutputFormat::Json {
+        let mut entries: Vec<_> = commands.iter().filter_map(|cmd| whitelist.get(cmd)).collect();
+        entries.sort_by(|a, b| a.command.cmp(&b.command));
+        println!("{}", serde_json::to_string_pretty(&entries)?);
+        return Ok(());
+    }
+
     println!("Whitelisted Commands ({}):", commands.len());
     println!();
 
@@ -102,17 +256,91 @@ fn list_commands() {
             }
         }
     }
+
+    Ok(())
+}
+
+/// Executor configuration from the `[os_executor]` section
+fn executor_config(config_args: &ConfigArgs) -> Result<ExecutorConfig, Box<dyn std::error::Error>> {
+    let config = config_args.load()?;
+    Ok(ExecutorConfig::from(&config.os_executor))
 }
 
-async fn execute_command(command: &str, args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
-    let config = ExecutorConfig {
-        enable_sandbox: false, // Disable sandbox for CLI usage
-        ..Default::default()
+fn plan_command(
+    config_args: &ConfigArgs,
+    command: &str,
+    args: &[String],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let executor = CommandExecutor::new(executor_config(config_args)?, CommandWhitelist::default());
+    let plan = executor.plan(command, args)?;
+
+    println!("{}", plan.summary());
+    println!();
+    println!("{}", serde_json::to_string_pretty(&plan)?);
+
+    Ok(())
+}
+
+fn test_policy(
+    policy_file: &str,
+    command: &str,
+    args: &[String],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let policy = Policy::load(policy_file)?;
+    let whitelist = CommandWhitelist::default();
+    let decision = match whitelist.get(command) {
+        Some(entry) => {
+            let risk = risk::assess_resolved(command, args, entry, Platform::current()).level;
+            policy.evaluate_with_risk(command, args, risk, None)
+        }
+        None => policy.evaluate(command, args),
     };
 
+    println!("Policy: {} ({} rules)", policy_file, policy.len());
+    println!("Request: {} {}", command, args.join(" "));
+    println!("Decision: {}", decision.explanation());
+
+    if !whitelist.is_whitelisted(command) {
+        println!("Note: {} is not in the default whitelist", command);
+    }
+
+    if decision.action == PolicyAction::Deny {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+async fn execute_command(
+    config_args: &ConfigArgs,
+    command: &str,
+    args: &[String],
+    output: OutputFormat,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // Sandboxed as configured; `--set os_executor.enable_sandbox=false` runs
+    // without it
+    let config = executor_config(config_args)?;
+
     let whitelist = CommandWhitelist::default();
     let executor = CommandExecutor::new(config, whitelist);
 
+    if output == OutputFormat::Json {
+        return match executor.execute(command, args).await {
+            Ok(result) => {
+                println!("{}", serde_json::to_string_pretty(&result)?);
+                if !result.success {
+                    std::process::exit(result.exit_code);
+                }
+                Ok(())
+            }
+            Err(e) => {
+                let error = serde_json::json!({ "error": e.to_string() });
+                println!("{}", serde_json::to_string_pretty(&error)?);
+                std::process::exit(1);
+            }
+        };
+    }
+
     println!("Executing: {} {}", command, args.join(" "));
     println!();
 
@@ -139,46 +367,46 @@ async fn execute_command(command: &str, args: &[String]) -> Result<(), Box<dyn s
     Ok(())
 }
 
-async fn run_tests() -> Result<(), Box<dyn std::error::Error>> {
-    println!("Running OS Executor Tests...");
-    println!();
-
-    let config = ExecutorConfig {
-        enable_sandbox: false,
-        ..Default::default()
+/// Check the installation against a whitelist: the file given, or the
+/// built-in one
+async fn run_doctor(
+    config_args: &ConfigArgs,
+    json: bool,
+    whitelist_file: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let whitelist = match whitelist_file {
+        Some(path) => CommandWhitelist::load(path)?,
+        None => CommandWhitelist::default(),
     };
+    let report = doctor::run(&executor_config(config_args)?, &whitelist).await;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        println!("OS Executor v{} on {}", report.version, report.platform);
+        println!();
+
+        for check in &report.checks {
+            let mark = match check.status {
+                CheckStatus::Pass => "✓",
+                CheckStatus::Warn => "!",
+                CheckStatus::Fail => "✗",
+                CheckStatus::Skip => "-",
+            };
+            println!("  {} {}: {}", mark, check.name, check.detail);
+        }
 
-    let whitelist = CommandWhitelist::default();
-    let executor = CommandExecutor::new(config, whitelist);
-
-    // Test 1: Echo command
-    println!("Test 1: Echo command");
-    let result = executor.execute("echo", &["Test".to_string()]).await?;
-    assert!(result.success);
-    assert!(result.stdout.contains("Test"));
-    println!("  ✓ Passed");
-
-    // Test 2: Date command
-    println!("Test 2: Date command");
-    let result = executor.execute("date", &[]).await?;
-    assert!(result.success);
-    println!("  ✓ Passed");
-
-    // Test 3: Non-whitelisted command (should fail)
-    println!("Test 3: Non-whitelisted command");
-    let result = executor.execute("rm", &["-rf".to_string(), "/".to_string()]).await;
-    assert!(result.is_err());
-    println!("  ✓ Passed (correctly blocked)");
-
-    // Test 4: Shell injection attempt
-    println!("Test 4: Shell injection protection");
-    let result = executor.execute("echo", &["test; rm -rf /".to_string()]).await;
-    // Should fail - shell metacharacters are blocked for security
-    assert!(result.is_err());
-    println!("  ✓ Passed (shell metacharacters blocked)");
+        println!();
+        if report.passed() {
+            println!("No problems found ✓");
+        } else {
+            println!("Some checks failed");
+        }
+    }
 
-    println!();
-    println!("All tests passed! ✓");
+    if !report.passed() {
+        std::process::exit(1);
+    }
 
     Ok(())
 }
@@ -0,0 +1,839 @@
+//! Journaled file mutations with undo
+//!
+//! Whitelisted commands are read-mostly; when the agent does need to change
+//! files ("save this note", "move that to Documents", "delete it"), it goes
+//! through a [`Mutator`]. Each request is a transaction of [`Mutation`]s that
+//! is checked against the path scope and policy, confirmed, applied all or
+//! nothing, and journaled so it can be reverted with [`Mutator::undo`].
+//! Deletion moves files to a trash directory rather than removing them.
+
+use crate::approval::{ApprovalDecision, ApprovalProvider, ApprovalRequest};
+use crate::path_scope::PathScope;
+use crate::policy::{Policy, PolicyAction, PolicyDecision};
+use crate::risk;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use thiserror::Error;
+use tracing::{info, warn};
+
+/// Mutation errors
+#[derive(Error, Debug)]
+pub enum MutationError {
+    #[error("Mutation I/O error on {}: {source}", .path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("Invalid journal: {0}")]
+    Journal(#[from] serde_json::Error),
+
+    #[error("Path outside allowed roots: {}", .0.display())]
+    OutsideRoots(PathBuf),
+
+    #[error("Path already exists: {}", .0.display())]
+    AlreadyExists(PathBuf),
+
+    #[error("Path not found: {}", .0.display())]
+    NotFound(PathBuf),
+
+    #[error("Contents exceed {0} bytes")]
+    TooLarge(usize),
+
+    #[error("Denied by policy: {0}")]
+    PolicyDenied(String),
+
+    #[error("Confirmation denied: {0}")]
+    ConfirmationDenied(String),
+
+    #[error("Unknown transaction: {0}")]
+    UnknownTransaction(String),
+
+    #[error("Transaction {0} was already undone")]
+    AlreadyUndone(String),
+
+    #[error("Cannot undo: {0}")]
+    Conflict(String),
+}
+
+/// A requested change
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Mutation {
+    /// Create a new file; fails if the path exists
+    CreateFile { path: String, contents: String },
+
+    /// Append to a file, creating it if missing
+    AppendFile { path: String, contents: String },
+
+    /// Move a file or directory; fails if the destination exists
+    Move { from: String, to: String },
+
+    /// Move a file or directory to the trash
+    Trash { path: String },
+}
+
+impl Mutation {
+    /// Name used for policy rules and approval prompts
+    pub fn name(&self) -> &'static str {
+        match self {
+            Mutation::CreateFile { .. } => "create_file",
+            Mutation::AppendFile { .. } => "append_file",
+            Mutation::Move { .. } => "move",
+            Mutation::Trash { .. } => "trash",
+        }
+    }
+
+    /// Paths the mutation touches, as given
+    pub fn paths(&self) -> Vec<String> {
+        match self {
+            Mutation::CreateFile { path, .. }
+            | Mutation::AppendFile { path, .. }
+            | Mutation::Trash { path } => vec![path.clone()],
+            Mutation::Move { from, to } => vec![from.clone(), to.clone()],
+        }
+    }
+}
+
+/// A completed change and what is needed to revert it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum JournalRecord {
+    Created {
+        path: PathBuf,
+        len: u64,
+        /// SHA-256 of the contents written (hex)
+        #[serde(default)]
+        sha256: Option<String>,
+    },
+    Appended {
+        path: PathBuf,
+        /// Length before the append (`None` if the file was created)
+        original_len: Option<u64>,
+        /// SHA-256 of the contents before the append
+        #[serde(default)]
+        original_sha256: Option<String>,
+        len: u64,
+        /// SHA-256 of the whole file after the append
+        #[serde(default)]
+        sha256: Option<String>,
+    },
+    Moved {
+        from: PathBuf,
+        to: PathBuf,
+    },
+    Trashed {
+        path: PathBuf,
+        trash_path: PathBuf,
+    },
+}
+
+/// Journaled transaction
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Transaction {
+    /// Transaction ID, passed to [`Mutator::undo`]
+    pub id: String,
+
+    /// When the transaction was applied
+    pub created_at: DateTime<Utc>,
+
+    /// Completed changes, in order
+    pub records: Vec<JournalRecord>,
+
+    /// Whether the transaction has been undone
+    #[serde(default)]
+    pub undone: bool,
+}
+
+/// Mutator settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MutationConfig {
+    /// Directory holding the journal (`journal/`) and trash (`trash/`)
+    pub state_dir: PathBuf,
+
+    /// Paths must resolve inside these roots (`~` expands to `$HOME`)
+    pub allowed_roots: Vec<String>,
+
+    /// Directory relative paths are resolved against (defaults to the current
+    /// directory)
+    #[serde(default)]
+    pub working_dir: Option<PathBuf>,
+
+    /// Ask the approval provider before every mutation, not only those the
+    /// policy marks `confirm`
+    #[serde(default = "default_requires_confirmation")]
+    pub requires_confirmation: bool,
+
+    /// Largest contents accepted for a single write
+    #[serde(default = "default_max_write_bytes")]
+    pub max_write_bytes: usize,
+}
+
+fn default_requires_confirmation() -> bool {
+    true
+}
+
+fn default_max_write_bytes() -> usize {
+    1024 * 1024 // 1MB
+}
+
+impl MutationConfig {
+    /// Settings for `state_dir` with mutations confined to `allowed_roots`
+    pub fn new(state_dir: impl Into<PathBuf>, allowed_roots: &[&str]) -> Self {
+        Self {
+            state_dir: state_dir.into(),
+            allowed_roots: allowed_roots.iter().map(|r| r.to_string()).collect(),
+            working_dir: None,
+            requires_confirmation: default_requires_confirmation(),
+            max_write_bytes: default_max_write_bytes(),
+        }
+    }
+}
+
+/// Applies and reverts journaled file mutations
+pub struct Mutator {
+    config: MutationConfig,
+    scope: PathScope,
+    policy: Option<Policy>,
+    approval: Option<Arc<dyn ApprovalProvider>>,
+    counter: AtomicU64,
+}
+
+impl Mutator {
+    /// Create a mutator, creating its journal and trash directories
+    pub fn new(config: MutationConfig) -> Result<Self, MutationError> {
+        for dir in [journal_dir(&config), trash_dir(&config)] {
+            fs::create_dir_all(&dir).map_err(io_error(&dir))?;
+        }
+
+        let base_dir = match config.working_dir {
+            Some(ref dir) => dir.clone(),
+            None => std::env::current_dir().map_err(io_error(Path::new(".")))?,
+        };
+        let scope = PathScope::new(&config.allowed_roots, base_dir);
+
+        Ok(Self {
+            config,
+            scope,
+            policy: None,
+            approval: None,
+            counter: AtomicU64::new(0),
+        })
+    }
+
+    /// Evaluate `policy` for every mutation, using its [`name`](Mutation::name)
+    /// as the command and its paths as the arguments
+    pub fn with_policy(mut self, policy: Policy) -> Self {
+        self.policy = Some(policy);
+        self
+    }
+
+    /// Ask `provider` before mutations that require confirmation
+    pub fn with_approval_provider(mut self, provider: Arc<dyn ApprovalProvider>) -> Self {
+        self.approval = Some(provider);
+        self
+    }
+
+    /// Check, confirm and apply `mutations` as one transaction
+    ///
+    /// Nothing is changed unless every mutation is in scope, allowed and
+    /// confirmed. If a mutation fails, those already applied are reverted.
+    pub async fn apply(&self, mutations: &[Mutation]) -> Result<Transaction, MutationError> {
+        for mutation in mutations {
+            self.check(mutation)?;
+        }
+        for mutation in mutations {
+            self.confirm(mutation).await?;
+        }
+
+        let mut transaction = Transaction {
+            id: self.next_id(),
+            created_at: Utc::now(),
+            records: Vec::new(),
+            undone: false,
+        };
+
+        for mutation in mutations {
+            match self.perform(&transaction.id, transaction.records.len(), mutation) {
+                Ok(record) => {
+                    transaction.records.push(record);
+                    self.save(&transaction)?;
+                }
+                Err(e) => {
+                    warn!("Mutation {} failed, rolling back: {}", mutation.name(), e);
+                    if !transaction.records.is_empty() {
+                        if let Err(rollback) = self.revert(&mut transaction) {
+                            warn!("Rollback of {} failed: {}", transaction.id, rollback);
+                        }
+                    }
+                    return Err(e);
+                }
+            }
+        }
+
+        info!(
+            "Applied transaction {} ({} changes)",
+            transaction.id,
+            transaction.records.len()
+        );
+        Ok(transaction)
+    }
+
+    /// Revert a transaction
+    ///
+    /// Fails with [`MutationError::Conflict`] without changing anything if a
+    /// file has been modified, or a path reused, since the transaction.
+    pub fn undo(&self, id: &str) -> Result<Transaction, MutationError> {
+        let mut transaction = self.load(id)?;
+        if transaction.undone {
+            return Err(MutationError::AlreadyUndone(id.to_string()));
+        }
+
+        check_revertible(&transaction.records)?;
+
+        self.revert(&mut transaction)?;
+        info!("Undid transaction {}", id);
+        Ok(transaction)
+    }
+
+    /// Journaled transactions, oldest first
+    pub fn transactions(&self) -> Result<Vec<Transaction>, MutationError> {
+        let dir = journal_dir(&self.config);
+        let mut transactions = Vec::new();
+
+        for entry in fs::read_dir(&dir).map_err(io_error(&dir))? {
+            let path = entry.map_err(io_error(&dir))?.path();
+            if path.extension().is_some_and(|ext| ext == "json") {
+                let json = fs::read(&path).map_err(io_error(&path))?;
+                transactions.push(serde_json::from_slice::<Transaction>(&json)?);
+            }
+        }
+
+        transactions.sort_by(|a, b| a.created_at.cmp(&b.created_at).then(a.id.cmp(&b.id)));
+        Ok(transactions)
+    }
+
+    /// Check scope, size and policy without touching the filesystem
+    fn check(&self, mutation: &Mutation) -> Result<(), MutationError> {
+        for path in mutation.paths() {
+            self.resolve(&path)?;
+        }
+
+        if let Mutation::CreateFile { contents, .. } | Mutation::AppendFile { contents, .. } =
+            mutation
+        {
+            if contents.len() > self.config.max_write_bytes {
+                return Err(MutationError::TooLarge(self.config.max_write_bytes));
+            }
+        }
+
+        if let Some(decision) = self.decide(mutation) {
+            if decision.action == PolicyAction::Deny {
+                return Err(MutationError::PolicyDenied(decision.explanation()));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The policy's decision on `mutation`, scored for risk
+    fn decide(&self, mutation: &Mutation) -> Option<PolicyDecision> {
+        let risk = risk::assess_mutation(mutation).level;
+        self.policy.as_ref().map(|policy| {
+            policy.evaluate_with_risk(
+                mutation.name(),
+                &mutation.paths(),
+                risk,
+                Some(self.scope.base_dir()),
+            )
+        })
+    }
+
+    /// Ask the approval provider if the config or policy requires it
+    ///
+    /// Fails closed: without a provider, such mutations are refused.
+    async fn confirm(&self, mutation: &Mutation) -> Result<(), MutationError> {
+        let policy_confirm = self
+            .decide(mutation)
+            .is_some_and(|decision| decision.action == PolicyAction::Confirm);
+        if !self.config.requires_confirmation && !policy_confirm {
+            return Ok(());
+        }
+
+        let provider = self.approval.as_ref().ok_or_else(|| {
+            MutationError::ConfirmationDenied(format!(
+                "{} requires confirmation but no approval provider is configured",
+                mutation.name()
+            ))
+        })?;
+
+        let request = ApprovalRequest {
+            command: mutation.name().to_string(),
+            args: mutation.paths(),
+            description: None,
+        };
+
+        match provider.request_approval(&request).await {
+            ApprovalDecision::Approved => Ok(()),
+            ApprovalDecision::Denied => Err(MutationError::ConfirmationDenied(request.prompt())),
+        }
+    }
+
+    fn resolve(&self, path: &str) -> Result<PathBuf, MutationError> {
+        let resolved = self.scope.resolve(path);
+        if self.scope.contains(&resolved) {
+            Ok(resolved)
+        } else {
+            Err(MutationError::OutsideRoots(resolved))
+        }
+    }
+
+    fn perform(
+        &self,
+        id: &str,
+        index: usize,
+        mutation: &Mutation,
+    ) -> Result<JournalRecord, MutationError> {
+        match mutation {
+            Mutation::CreateFile { path, contents } => {
+                let path = self.resolve(path)?;
+                let mut file = OpenOptions::new()
+                    .write(true)
+                    .create_new(true)
+                    .open(&path)
+                    .map_err(|e| match e.kind() {
+                        io::ErrorKind::AlreadyExists => MutationError::AlreadyExists(path.clone()),
+                        _ => io_error(&path)(e),
+                    })?;
+                file.write_all(contents.as_bytes())
+                    .map_err(io_error(&path))?;
+
+                Ok(JournalRecord::Created {
+                    len: contents.len() as u64,
+                    sha256: Some(hex::encode(Sha256::digest(contents))),
+                    path,
+                })
+            }
+            Mutation::AppendFile { path, contents } => {
+                let path = self.resolve(path)?;
+                let original_len = fs::metadata(&path).ok().map(|m| m.len());
+                let original_sha256 = match original_len {
+                    Some(_) => Some(sha256_file(&path).map_err(io_error(&path))?),
+                    None => None,
+                };
+                let mut file = OpenOptions::new()
+                    .append(true)
+                    .create(true)
+                    .open(&path)
+                    .map_err(io_error(&path))?;
+                file.write_all(contents.as_bytes())
+                    .map_err(io_error(&path))?;
+
+                Ok(JournalRecord::Appended {
+                    len: original_len.unwrap_or(0) + contents.len() as u64,
+                    sha256: Some(sha256_file(&path).map_err(io_error(&path))?),
+                    original_len,
+                    original_sha256,
+                    path,
+                })
+            }
+            Mutation::Move { from, to } => {
+                let from = self.resolve(from)?;
+                let to = self.resolve(to)?;
+                move_path(&from, &to)?;
+                Ok(JournalRecord::Moved { from, to })
+            }
+            Mutation::Trash { path } => {
+                let path = self.resolve(path)?;
+                let name = path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                let dir = trash_dir(&self.config).join(id);
+                fs::create_dir_all(&dir).map_err(io_error(&dir))?;
+
+                let trash_path = dir.join(format!("{}-{}", index, name));
+                move_path(&path, &trash_path)?;
+                Ok(JournalRecord::Trashed { path, trash_path })
+            }
+        }
+    }
+
+    /// Revert every record, newest first, and mark the transaction undone
+    fn revert(&self, transaction: &mut Transaction) -> Result<(), MutationError> {
+        for record in transaction.records.iter().rev() {
+            match record {
+                JournalRecord::Created { path, .. } => {
+                    fs::remove_file(path).map_err(io_error(path))?;
+                }
+                JournalRecord::Appended {
+                    path,
+                    original_len: Some(len),
+                    ..
+                } => {
+                    let file = OpenOptions::new()
+                        .write(true)
+                        .open(path)
+                        .map_err(io_error(path))?;
+                    file.set_len(*len).map_err(io_error(path))?;
+                }
+                JournalRecord::Appended {
+                    path,
+                    original_len: None,
+                    ..
+                } => {
+                    fs::remove_file(path).map_err(io_error(path))?;
+                }
+                JournalRecord::Moved { from, to } => move_path(to, from)?,
+                JournalRecord::Trashed { path, trash_path } => move_path(trash_path, path)?,
+            }
+        }
+
+        transaction.undone = true;
+        self.save(transaction)
+    }
+
+    fn next_id(&self) -> String {
+        format!(
+            "{}-{}",
+            Utc::now().format("%Y%m%dT%H%M%S%6f"),
+            self.counter.fetch_add(1, Ordering::Relaxed)
+        )
+    }
+
+    fn journal_path(&self, id: &str) -> Result<PathBuf, MutationError> {
+        let valid = !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+        if !valid {
+            return Err(MutationError::UnknownTransaction(id.to_string()));
+        }
+
+        Ok(journal_dir(&self.config).join(format!("{}.json", id)))
+    }
+
+    /// Write the journal entry atomically
+    fn save(&self, transaction: &Transaction) -> Result<(), MutationError> {
+        let path = self.journal_path(&transaction.id)?;
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_vec_pretty(transaction)?).map_err(io_error(&tmp))?;
+        fs::rename(&tmp, &path).map_err(io_error(&path))
+    }
+
+    fn load(&self, id: &str) -> Result<Transaction, MutationError> {
+        let path = self.journal_path(id)?;
+        let json = fs::read(&path).map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => MutationError::UnknownTransaction(id.to_string()),
+            _ => io_error(&path)(e),
+        })?;
+        Ok(serde_json::from_slice(&json)?)
+    }
+}
+
+fn journal_dir(config: &MutationConfig) -> PathBuf {
+    config.state_dir.join("journal")
+}
+
+fn trash_dir(config: &MutationConfig) -> PathBuf {
+    config.state_dir.join("trash")
+}
+
+fn io_error(path: &Path) -> impl FnOnce(io::Error) -> MutationError + '_ {
+    move |source| MutationError::Io {
+        path: path.to_path_buf(),
+        source,
+    }
+}
+
+/// SHA-256 of a file's contents (hex)
+fn sha256_file(path: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut fs::File::open(path)?, &mut hasher)?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// What a path holds once the newer records of a transaction are reverted
+#[derive(Debug, Clone)]
+enum Content {
+    /// As a record left it
+    Recorded { len: u64, sha256: Option<String> },
+    /// Whatever is on disk at this path now (it is moved back from there)
+    OnDisk(PathBuf),
+}
+
+impl Content {
+    /// Whether this is what a record wrote; journals without hashes only
+    /// compare lengths
+    fn is(&self, len: u64, sha256: Option<&String>) -> bool {
+        match self {
+            Content::Recorded {
+                len: recorded_len,
+                sha256: recorded,
+            } => {
+                *recorded_len == len
+                    && (sha256.is_none() || recorded.is_none() || recorded.as_ref() == sha256)
+            }
+            Content::OnDisk(path) => {
+                let same_len =
+                    fs::symlink_metadata(path).is_ok_and(|m| m.is_file() && m.len() == len);
+                same_len
+                    && match sha256 {
+                        Some(hash) => sha256_file(path).is_ok_and(|current| current == *hash),
+                        None => true,
+                    }
+            }
+        }
+    }
+}
+
+/// Check that reverting `records` would not clobber later changes
+///
+/// Records are replayed newest first over the current filesystem state, so a
+/// file created and then moved in the same transaction checks out. Files are
+/// compared by length and SHA-256, so an edit that keeps the length is caught.
+fn check_revertible(records: &[JournalRecord]) -> Result<(), MutationError> {
+    // Path -> what it will hold (None = absent) once the newer records are reverted
+    let mut overlay: HashMap<PathBuf, Option<Content>> = HashMap::new();
+    let state = |overlay: &HashMap<PathBuf, Option<Content>>, path: &Path| match overlay.get(path) {
+        Some(state) => state.clone(),
+        None => fs::symlink_metadata(path)
+            .ok()
+            .map(|_| Content::OnDisk(path.to_path_buf())),
+    };
+    let conflict = |what: String| Err(MutationError::Conflict(what));
+
+    for record in records.iter().rev() {
+        match record {
+            JournalRecord::Created {
+                path, len, sha256, ..
+            }
+            | JournalRecord::Appended {
+                path, len, sha256, ..
+            } => {
+                match state(&overlay, path) {
+                    Some(current) if current.is(*len, sha256.as_ref()) => {}
+                    Some(_) => return conflict(format!("{} was modified", path.display())),
+                    None => return conflict(format!("{} no longer exists", path.display())),
+                }
+
+                let reverted = match record {
+                    JournalRecord::Appended {
+                        original_len,
+                        original_sha256,
+                        ..
+                    } => original_len.map(|len| Content::Recorded {
+                        len,
+                        sha256: original_sha256.clone(),
+                    }),
+                    _ => None,
+                };
+                overlay.insert(path.clone(), reverted);
+            }
+            JournalRecord::Moved {
+                from: back,
+                to: current,
+            }
+            | JournalRecord::Trashed {
+                path: back,
+                trash_path: current,
+            } => {
+                let Some(moved) = state(&overlay, current) else {
+                    return conflict(format!("{} no longer exists", current.display()));
+                };
+                if state(&overlay, back).is_some() {
+                    return conflict(format!("{} exists again", back.display()));
+                }
+
+                overlay.insert(current.clone(), None);
+                overlay.insert(back.clone(), Some(moved));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Rename without replacing `to`, copying files across filesystems
+fn move_path(from: &Path, to: &Path) -> Result<(), MutationError> {
+    let metadata = fs::symlink_metadata(from).map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => MutationError::NotFound(from.to_path_buf()),
+        _ => io_error(from)(e),
+    })?;
+    if fs::symlink_metadata(to).is_ok() {
+        return Err(MutationError::AlreadyExists(to.to_path_buf()));
+    }
+
+    match fs::rename(from, to) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices && metadata.is_file() => {
+            fs::copy(from, to).map_err(io_error(to))?;
+            fs::remove_file(from).map_err(io_error(from))
+        }
+        Err(e) => Err(io_error(from)(e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::approval::StaticApproval;
+
+    fn test_mutator(dir: &Path) -> Mutator {
+        let root = dir.join("root");
+        fs::create_dir_all(&root).unwrap();
+
+        let config = MutationConfig {
+            working_dir: Some(root.clone()),
+            ..MutationConfig::new(dir.join("state"), &[root.to_str().unwrap()])
+        };
+
+        Mutator::new(config)
+            .unwrap()
+            .with_approval_provider(Arc::new(StaticApproval(ApprovalDecision::Approved)))
+    }
+
+    fn create(path: &str, contents: &str) -> Mutation {
+        Mutation::CreateFile {
+            path: path.to_string(),
+            contents: contents.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_apply_and_undo() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("root");
+        let mutator = test_mutator(dir.path());
+        fs::write(root.join("log.txt"), "one\n").unwrap();
+        fs::write(root.join("old.txt"), "old").unwrap();
+
+        let transaction = mutator
+            .apply(&[
+                create("note.txt", "hello"),
+                Mutation::AppendFile {
+                    path: "log.txt".to_string(),
+                    contents: "two\n".to_string(),
+                },
+                Mutation::Move {
+                    from: "note.txt".to_string(),
+                    to: "moved.txt".to_string(),
+                },
+                Mutation::Trash {
+                    path: "old.txt".to_string(),
+                },
+            ])
+            .await
+            .unwrap();
+
+        assert_eq!(fs::read_to_string(root.join("moved.txt")).unwrap(), "hello");
+        assert_eq!(
+            fs::read_to_string(root.join("log.txt")).unwrap(),
+            "one\ntwo\n"
+        );
+        assert!(!root.join("old.txt").exists());
+        assert_eq!(mutator.transactions().unwrap(), vec![transaction.clone()]);
+
+        mutator.undo(&transaction.id).unwrap();
+
+        assert!(!root.join("moved.txt").exists());
+        assert!(!root.join("note.txt").exists());
+        assert_eq!(fs::read_to_string(root.join("log.txt")).unwrap(), "one\n");
+        assert_eq!(fs::read_to_string(root.join("old.txt")).unwrap(), "old");
+        assert!(matches!(
+            mutator.undo(&transaction.id),
+            Err(MutationError::AlreadyUndone(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_failed_transaction_rolls_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("root");
+        let mutator = test_mutator(dir.path());
+        fs::write(root.join("existing.txt"), "keep").unwrap();
+
+        let result = mutator
+            .apply(&[create("a.txt", "a"), create("existing.txt", "clobber")])
+            .await;
+
+        assert!(matches!(result, Err(MutationError::AlreadyExists(_))));
+        assert!(!root.join("a.txt").exists());
+        assert_eq!(
+            fs::read_to_string(root.join("existing.txt")).unwrap(),
+            "keep"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_scope_policy_and_confirmation() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("root");
+        let policy = Policy::from_yaml(
+            r#"
+rules:
+  - name: no-trash
+    command: trash
+    action: deny
+"#,
+        )
+        .unwrap();
+        let mutator = test_mutator(dir.path()).with_policy(policy);
+
+        assert!(matches!(
+            mutator.apply(&[create("../escape.txt", "x")]).await,
+            Err(MutationError::OutsideRoots(_))
+        ));
+        assert!(matches!(
+            mutator
+                .apply(&[Mutation::Trash {
+                    path: "x".to_string()
+                }])
+                .await,
+            Err(MutationError::PolicyDenied(_))
+        ));
+
+        let denied =
+            mutator.with_approval_provider(Arc::new(StaticApproval(ApprovalDecision::Denied)));
+        assert!(matches!(
+            denied.apply(&[create("note.txt", "x")]).await,
+            Err(MutationError::ConfirmationDenied(_))
+        ));
+        assert!(!root.join("note.txt").exists());
+
+        fs::write(root.join("edited.txt"), "").unwrap();
+        let approved = test_mutator(dir.path());
+        let transaction = approved
+            .apply(&[Mutation::AppendFile {
+                path: "edited.txt".to_string(),
+                contents: "a".to_string(),
+            }])
+            .await
+            .unwrap();
+        fs::write(root.join("edited.txt"), "changed later").unwrap();
+        assert!(matches!(
+            approved.undo(&transaction.id),
+            Err(MutationError::Conflict(_))
+        ));
+
+        // Same length, different contents
+        let transaction = approved.apply(&[create("same.txt", "abc")]).await.unwrap();
+        fs::write(root.join("same.txt"), "xyz").unwrap();
+        assert!(matches!(
+            approved.undo(&transaction.id),
+            Err(MutationError::Conflict(_))
+        ));
+        fs::write(root.join("same.txt"), "abc").unwrap();
+        approved.undo(&transaction.id).unwrap();
+        assert!(!root.join("same.txt").exists());
+        assert!(matches!(
+            approved.undo("../../etc/passwd"),
+            Err(MutationError::UnknownTransaction(_))
+        ));
+    }
+}
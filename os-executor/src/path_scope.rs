@@ -0,0 +1,220 @@
+//! Path-aware argument validation
+//!
+//! Regex argument patterns cannot tell `notes.txt` from `../../etc/shadow`.
+//! [`PathScope`] finds the arguments that name files, resolves them the way
+//! the kernel would (following symlinks) and checks that they stay inside the
+//! configured roots.
+
+use std::path::{Component, Path, PathBuf};
+
+/// Allowed filesystem roots for path arguments
+#[derive(Debug, Clone)]
+pub struct PathScope {
+    roots: Vec<PathBuf>,
+    base_dir: PathBuf,
+}
+
+impl PathScope {
+    /// Create a scope from root directories (`~` expands to `$HOME`)
+    ///
+    /// Relative arguments are resolved against `base_dir`, the directory the
+    /// command runs in.
+    pub fn new(roots: &[String], base_dir: impl Into<PathBuf>) -> Self {
+        let base_dir = base_dir.into();
+        let roots = roots
+            .iter()
+            .map(|root| resolve(&base_dir, &expand_home(root)))
+            .collect();
+
+        Self { roots, base_dir }
+    }
+
+    /// Resolved roots
+    pub fn roots(&self) -> &[PathBuf] {
+        &self.roots
+    }
+
+    /// Directory relative arguments are resolved against
+    pub fn base_dir(&self) -> &Path {
+        &self.base_dir
+    }
+
+    /// Resolve a path against the base directory, following symlinks as far
+    /// as it exists (`~` expands to `$HOME`)
+    pub fn resolve(&self, path: &str) -> PathBuf {
+        resolve(&self.base_dir, &expand_home(path))
+    }
+
+    /// Path an argument refers to, resolved through symlinks
+    ///
+    /// Arguments are treated as paths when they contain `/`, start with `~` or
+    /// `.`, or name an existing entry in the base directory. For
+    /// `--flag=value` the value is checked, and for `-xVALUE` a value that
+    /// looks like a path (`-o/etc/passwd`). Other arguments return `None`.
+    pub fn resolve_arg(&self, arg: &str) -> Option<PathBuf> {
+        let value = arg_value(arg)?;
+
+        let path = expand_home(value);
+        if is_path_like(value) || self.base_dir.join(&path).symlink_metadata().is_ok() {
+            Some(resolve(&self.base_dir, &path))
+        } else {
+            None
+        }
+    }
+
+    /// Check that every path argument resolves inside one of the roots
+    ///
+    /// Returns the first offending argument's index and resolved path.
+    pub fn check(&self, args: &[String]) -> Result<(), (usize, PathBuf)> {
+        for (i, arg) in args.iter().enumerate() {
+            if let Some(path) = self.resolve_arg(arg) {
+                if !self.contains(&path) {
+                    return Err((i, path));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Like [`check`](Self::check), but only for relative path arguments;
+    /// absolute and `~` paths are not checked
+    pub fn check_relative(&self, args: &[String]) -> Result<(), (usize, PathBuf)> {
+        for (i, arg) in args.iter().enumerate() {
+            if arg_value(arg).is_some_and(|value| value.starts_with('/') || value.starts_with('~'))
+            {
+                continue;
+            }
+            if let Some(path) = self.resolve_arg(arg) {
+                if !self.contains(&path) {
+                    return Err((i, path));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Whether a resolved path lies inside one of the roots
+    pub fn contains(&self, path: &Path) -> bool {
+        self.roots.iter().any(|root| path.starts_with(root))
+    }
+}
+
+/// The part of an argument that may be a path: the value of `--flag=value`
+/// or `-xVALUE`, or the whole argument unless it is a flag
+fn arg_value(arg: &str) -> Option<&str> {
+    let value = match arg.split_once('=') {
+        Some((flag, value)) if flag.starts_with('-') => value,
+        _ if arg.starts_with("--") => return None,
+        // Only a path-like value, so `-la` is not taken for a file named `a`
+        _ if arg.starts_with('-') => arg.get(2..).filter(|value| is_path_like(value))?,
+        _ => arg,
+    };
+
+    (!value.is_empty()).then_some(value)
+}
+
+fn is_path_like(value: &str) -> bool {
+    value.contains('/') || value.starts_with('~') || value.starts_with('.')
+}
+
+/// Expand a leading `~` to `$HOME`
+pub(crate) fn expand_home(path: &str) -> PathBuf {
+    if path == "~" || path.starts_with("~/") {
+        if let Some(home) = std::env::var_os("HOME") {
+            return PathBuf::from(home).join(path.trim_start_matches('~').trim_start_matches('/'));
+        }
+    }
+
+    PathBuf::from(path)
+}
+
+/// Resolve `path` against `base_dir`, following symlinks as far as the path
+/// exists and normalizing the remainder lexically
+fn resolve(base_dir: &Path, path: &Path) -> PathBuf {
+    let absolute = base_dir.join(path);
+
+    for existing in absolute.ancestors() {
+        if let Ok(canonical) = existing.canonicalize() {
+            let rest = absolute.strip_prefix(existing).unwrap_or(Path::new(""));
+            return normalize(&canonical.join(rest));
+        }
+    }
+
+    normalize(&absolute)
+}
+
+/// Resolve `.` and `..` components without touching the filesystem
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            Component::CurDir => {}
+            other => normalized.push(other),
+        }
+    }
+
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_relative_escape_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("root");
+        std::fs::create_dir(&root).unwrap();
+        std::fs::write(root.join("notes.txt"), "hi").unwrap();
+
+        let scope = PathScope::new(&[root.to_string_lossy().to_string()], &root);
+
+        assert!(scope
+            .check(&args(&["-n", "notes.txt", "./new/file"]))
+            .is_ok());
+        assert!(scope.check(&args(&["hello"])).is_ok());
+
+        let (index, path) = scope.check(&args(&["-n", "../../etc/shadow"])).unwrap_err();
+        assert_eq!(index, 1);
+        assert!(!path.starts_with(&root));
+
+        assert!(scope.check(&args(&["--output=/etc/passwd"])).is_err());
+        assert!(scope.check(&args(&["-o/etc/passwd"])).is_err());
+        assert!(scope.check(&args(&["-I../include"])).is_err());
+        assert!(scope.check(&args(&["-la", "-n5", "-o./out"])).is_ok());
+        assert!(scope.check(&args(&["missing/../../x"])).is_err());
+
+        assert!(scope.check_relative(&args(&["/etc/passwd", "~/x"])).is_ok());
+        assert!(scope.check_relative(&args(&["--output=../x"])).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_escape_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("root");
+        let outside = dir.path().join("outside");
+        std::fs::create_dir(&root).unwrap();
+        std::fs::create_dir(&outside).unwrap();
+        std::fs::write(outside.join("secret"), "s").unwrap();
+        std::os::unix::fs::symlink(&outside, root.join("link")).unwrap();
+        std::os::unix::fs::symlink(outside.join("secret"), root.join("secret")).unwrap();
+
+        let scope = PathScope::new(&[root.to_string_lossy().to_string()], &root);
+
+        assert!(scope.check(&args(&["link/secret"])).is_err());
+        // Bare names are checked too when they exist
+        assert!(scope.check(&args(&["secret"])).is_err());
+        // `..` applies after following the link, as in the kernel
+        assert!(scope.check(&args(&["link/../root/notes"])).is_ok());
+    }
+}
@@ -4,7 +4,7 @@ use serde::{Deserialize, Serialize};
 use std::env;
 
 /// Platform identifier
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Platform {
     Linux,
     MacOS,
@@ -205,7 +205,7 @@ impl PlatformInfo {
         match platform {
             Platform::Linux => true,  // Has nsjail, seccomp, etc.
             Platform::MacOS => true,  // Has sandbox-exec
-            Platform::Windows => false, // Limited sandbox support
+            Platform::Windows => true, // Job Objects
             Platform::Unknown => false,
         }
     }
@@ -0,0 +1,625 @@
+//! Declarative execution policy
+//!
+//! A policy is an ordered list of rules loaded from YAML or TOML. Each rule
+//! matches on command, arguments, path scopes and time of day, and decides
+//! whether a request is allowed, denied or needs the user's confirmation. The
+//! first matching rule wins; requests no rule matches get the policy default.
+//!
+//! Policies are evaluated on top of the whitelist: a command must be
+//! whitelisted *and* allowed by the policy to run.
+//!
+//! ```yaml
+//! default: allow
+//! rules:
+//!   - name: no-recursive-delete
+//!     command: rm
+//!     args_contain: ["^-[a-zA-Z]*r"]
+//!     action: deny
+//!     reason: Recursive deletes are not allowed
+//!   - name: quiet-hours
+//!     command: "*"
+//!     hours: "22:00-07:00"
+//!     action: confirm
+//!   - name: confirm-high-risk
+//!     command: "*"
+//!     risk: high
+//!     action: confirm
+//! ```
+
+use crate::path_scope::PathScope;
+use crate::risk::RiskLevel;
+use chrono::{Local, NaiveTime};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::path::{Component, Path, PathBuf};
+use thiserror::Error;
+
+/// Policy errors
+#[derive(Error, Debug)]
+pub enum PolicyError {
+    #[error("Failed to read policy: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Invalid policy format: {0}")]
+    Parse(String),
+
+    #[error("Invalid rule {rule}: {reason}")]
+    InvalidRule { rule: String, reason: String },
+}
+
+/// What a policy decides for a request
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PolicyAction {
+    /// Run without asking
+    #[default]
+    Allow,
+    /// Refuse to run
+    Deny,
+    /// Run only after the approval provider approves
+    Confirm,
+}
+
+/// A single policy rule as written in the policy file
+///
+/// All conditions that are set must hold for the rule to match.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PolicyRule {
+    /// Rule name, reported in decisions
+    pub name: String,
+
+    /// Command name, or `*` for any command
+    pub command: String,
+
+    /// Matches if any argument matches any of these regexes
+    #[serde(default)]
+    pub args_contain: Option<Vec<String>>,
+
+    /// Matches if every argument matches one of these regexes
+    #[serde(default)]
+    pub args_only: Option<Vec<String>>,
+
+    /// Matches if there are at most this many arguments
+    #[serde(default)]
+    pub max_args: Option<usize>,
+
+    /// Matches if every path argument lies under one of these directories
+    ///
+    /// Path arguments are found as [`PathScope::resolve_arg`] does, resolved
+    /// against the directory the command runs in and through symlinks, so
+    /// neither `../..` nor a link can climb out of a scope. `~` expands to
+    /// `$HOME`.
+    #[serde(default)]
+    pub paths: Option<Vec<String>>,
+
+    /// Matches within a local time window `HH:MM-HH:MM` (may wrap midnight)
+    #[serde(default)]
+    pub hours: Option<String>,
+
+    /// Matches if the request's [risk](crate::risk) is at least this level
+    ///
+    /// Never matches when evaluated without a risk assessment.
+    #[serde(default)]
+    pub risk: Option<RiskLevel>,
+
+    /// Decision when the rule matches
+    pub action: PolicyAction,
+
+    /// Explanation reported when the rule matches
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+/// Policy file contents
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PolicyFile {
+    /// Decision when no rule matches
+    #[serde(default)]
+    pub default: PolicyAction,
+
+    /// Rules, evaluated in order
+    #[serde(default)]
+    pub rules: Vec<PolicyRule>,
+}
+
+/// Outcome of evaluating a request against a policy
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PolicyDecision {
+    /// Decision
+    pub action: PolicyAction,
+
+    /// Name of the matching rule (`None` when the default applied)
+    pub rule: Option<String>,
+
+    /// Reason given by the matching rule
+    pub reason: Option<String>,
+}
+
+impl PolicyDecision {
+    /// Human-readable explanation of the decision
+    pub fn explanation(&self) -> String {
+        let action = match self.action {
+            PolicyAction::Allow => "allowed",
+            PolicyAction::Deny => "denied",
+            PolicyAction::Confirm => "needs confirmation",
+        };
+
+        match (&self.rule, &self.reason) {
+            (Some(rule), Some(reason)) => format!("{} by rule {}: {}", action, rule, reason),
+            (Some(rule), None) => format!("{} by rule {}", action, rule),
+            (None, _) => format!("{} by default", action),
+        }
+    }
+}
+
+/// Local time window, possibly wrapping midnight
+#[derive(Debug, Clone, Copy)]
+struct TimeWindow {
+    start: NaiveTime,
+    end: NaiveTime,
+}
+
+impl TimeWindow {
+    fn parse(window: &str) -> Option<Self> {
+        let (start, end) = window.split_once('-')?;
+        Some(Self {
+            start: NaiveTime::parse_from_str(start.trim(), "%H:%M").ok()?,
+            end: NaiveTime::parse_from_str(end.trim(), "%H:%M").ok()?,
+        })
+    }
+
+    fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+/// Rule with its regexes, scopes and time window compiled
+#[derive(Debug, Clone)]
+struct CompiledRule {
+    rule: PolicyRule,
+    args_contain: Option<Vec<Regex>>,
+    args_only: Option<Vec<Regex>>,
+    paths: Option<Vec<PathBuf>>,
+    hours: Option<TimeWindow>,
+}
+
+impl CompiledRule {
+    fn compile(rule: PolicyRule) -> Result<Self, PolicyError> {
+        let invalid = |reason: String| PolicyError::InvalidRule {
+            rule: rule.name.clone(),
+            reason,
+        };
+
+        let compile_patterns = |patterns: &Option<Vec<String>>| {
+            patterns
+                .as_ref()
+                .map(|patterns| {
+                    patterns
+                        .iter()
+                        .map(|p| Regex::new(p).map_err(|e| invalid(e.to_string())))
+                        .collect::<Result<Vec<_>, _>>()
+                })
+                .transpose()
+        };
+
+        let args_contain = compile_patterns(&rule.args_contain)?;
+        let args_only = compile_patterns(&rule.args_only)?;
+
+        let paths = rule
+            .paths
+            .as_ref()
+            .map(|scopes| PathScope::new(scopes, "/").roots().to_vec());
+
+        let hours = rule
+            .hours
+            .as_deref()
+            .map(|window| {
+                TimeWindow::parse(window).ok_or_else(|| {
+                    invalid(format!("invalid hours {:?}, expected HH:MM-HH:MM", window))
+                })
+            })
+            .transpose()?;
+
+        Ok(Self {
+            args_contain,
+            args_only,
+            paths,
+            hours,
+            rule,
+        })
+    }
+
+    fn matches(
+        &self,
+        command: &str,
+        args: &[String],
+        risk: Option<RiskLevel>,
+        time: NaiveTime,
+        working_dir: &PathScope,
+    ) -> bool {
+        if self.rule.command != "*" && self.rule.command != command {
+            return false;
+        }
+
+        if let Some(min) = self.rule.risk {
+            if risk.is_none_or(|risk| risk < min) {
+                return false;
+            }
+        }
+
+        if self.rule.max_args.is_some_and(|max| args.len() > max) {
+            return false;
+        }
+
+        if let Some(patterns) = &self.args_contain {
+            if !args
+                .iter()
+                .any(|arg| patterns.iter().any(|p| p.is_match(arg)))
+            {
+                return false;
+            }
+        }
+
+        if let Some(patterns) = &self.args_only {
+            if !args
+                .iter()
+                .all(|arg| patterns.iter().any(|p| p.is_match(arg)))
+            {
+                return false;
+            }
+        }
+
+        if let Some(scopes) = &self.paths {
+            let in_scope = args
+                .iter()
+                .filter_map(|arg| working_dir.resolve_arg(arg))
+                .all(|path| scopes.iter().any(|scope| path.starts_with(scope)));
+            if !in_scope {
+                return false;
+            }
+        }
+
+        if let Some(window) = &self.hours {
+            if !window.contains(time) {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+/// Compiled execution policy
+#[derive(Debug, Clone, Default)]
+pub struct Policy {
+    default: PolicyAction,
+    rules: Vec<CompiledRule>,
+}
+
+impl Policy {
+    /// Compile a policy, validating its regexes and time windows
+    pub fn new(file: PolicyFile) -> Result<Self, PolicyError> {
+        let rules = file
+            .rules
+            .into_iter()
+            .map(CompiledRule::compile)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            default: file.default,
+            rules,
+        })
+    }
+
+    /// Load policy from YAML
+    pub fn from_yaml(yaml: &str) -> Result<Self, PolicyError> {
+        let file = serde_yaml::from_str(yaml).map_err(|e| PolicyError::Parse(e.to_string()))?;
+        Self::new(file)
+    }
+
+    /// Load policy from TOML
+    ///
+    /// Rules are written as `[[rules]]` tables.
+    pub fn from_toml(toml: &str) -> Result<Self, PolicyError> {
+        let file = toml::from_str(toml).map_err(|e| PolicyError::Parse(e.to_string()))?;
+        Self::new(file)
+    }
+
+    /// Load policy from a file, as TOML for `.toml` files and YAML otherwise
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, PolicyError> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)?;
+
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => Self::from_toml(&contents),
+            _ => Self::from_yaml(&contents),
+        }
+    }
+
+    /// Evaluate a request at the current local time
+    ///
+    /// Relative path arguments are resolved against the current directory.
+    pub fn evaluate(&self, command: &str, args: &[String]) -> PolicyDecision {
+        self.evaluate_at(command, args, Local::now().time())
+    }
+
+    /// Evaluate a request at the given local time
+    pub fn evaluate_at(&self, command: &str, args: &[String], time: NaiveTime) -> PolicyDecision {
+        self.decide(command, args, None, time, None)
+    }
+
+    /// Evaluate a scored request at the current local time, so rules with a
+    /// `risk` condition can match
+    ///
+    /// Relative path arguments are resolved against `working_dir`, the
+    /// directory the command runs in (`None` = the current directory).
+    pub fn evaluate_with_risk(
+        &self,
+        command: &str,
+        args: &[String],
+        risk: RiskLevel,
+        working_dir: Option<&Path>,
+    ) -> PolicyDecision {
+        self.decide(command, args, Some(risk), Local::now().time(), working_dir)
+    }
+
+    fn decide(
+        &self,
+        command: &str,
+        args: &[String],
+        risk: Option<RiskLevel>,
+        time: NaiveTime,
+        working_dir: Option<&Path>,
+    ) -> PolicyDecision {
+        let working_dir = match working_dir {
+            Some(dir) => dir.to_path_buf(),
+            None => std::env::current_dir().unwrap_or_else(|_| PathBuf::from("/")),
+        };
+        let working_dir = PathScope::new(&[], working_dir);
+
+        match self
+            .rules
+            .iter()
+            .find(|r| r.matches(command, args, risk, time, &working_dir))
+        {
+            Some(compiled) => PolicyDecision {
+                action: compiled.rule.action,
+                rule: Some(compiled.rule.name.clone()),
+                reason: compiled.rule.reason.clone(),
+            },
+            None => PolicyDecision {
+                action: self.default,
+                rule: None,
+                reason: None,
+            },
+        }
+    }
+
+    /// Number of rules
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+
+    /// Check if the policy has no rules
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+}
+
+/// Absolute or `~` path named by an argument, normalized lexically
+pub(crate) fn path_argument(arg: &str) -> Option<PathBuf> {
+    let value = match arg.split_once('=') {
+        Some((flag, value)) if flag.starts_with('-') => value,
+        _ => arg,
+    };
+
+    if value.starts_with('/') || value.starts_with('~') {
+        Some(normalize_path(value))
+    } else {
+        None
+    }
+}
+
+/// Expand `~` and resolve `.` and `..` without touching the filesystem
+fn normalize_path(path: &str) -> PathBuf {
+    let expanded = match path.strip_prefix('~') {
+        Some(rest) => {
+            let home = std::env::var("HOME").unwrap_or_default();
+            format!("{}{}", home, rest)
+        }
+        None => path.to_string(),
+    };
+
+    let mut normalized = PathBuf::from("/");
+    for component in Path::new(&expanded).components() {
+        match component {
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            Component::Normal(part) => normalized.push(part),
+            _ => {}
+        }
+    }
+
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POLICY: &str = r#"
+default: allow
+rules:
+  - name: no-recursive-delete
+    command: rm
+    args_contain: ["^-[a-zA-Z]*r"]
+    action: deny
+    reason: Recursive deletes are not allowed
+  - name: tmp-only
+    command: rm
+    paths: ["/tmp"]
+    action: confirm
+  - name: rm-elsewhere
+    command: rm
+    action: deny
+  - name: quiet-hours
+    command: "*"
+    hours: "22:00-07:00"
+    action: confirm
+"#;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn noon() -> NaiveTime {
+        NaiveTime::from_hms_opt(12, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn test_first_matching_rule_wins() {
+        let policy = Policy::from_yaml(POLICY).unwrap();
+        assert_eq!(policy.len(), 4);
+
+        let decision = policy.evaluate_at("rm", &args(&["-rf", "/tmp/x"]), noon());
+        assert_eq!(decision.action, PolicyAction::Deny);
+        assert_eq!(decision.rule.as_deref(), Some("no-recursive-delete"));
+        assert_eq!(
+            decision.explanation(),
+            "denied by rule no-recursive-delete: Recursive deletes are not allowed"
+        );
+
+        let decision = policy.evaluate_at("rm", &args(&["/tmp/a", "/tmp/b"]), noon());
+        assert_eq!(decision.action, PolicyAction::Confirm);
+
+        let decision = policy.evaluate_at("ls", &args(&["/etc"]), noon());
+        assert_eq!(decision.action, PolicyAction::Allow);
+        assert_eq!(decision.explanation(), "allowed by default");
+    }
+
+    #[test]
+    fn test_path_scopes_are_normalized() {
+        let policy = Policy::from_yaml(POLICY).unwrap();
+
+        let decision = policy.evaluate_at("rm", &args(&["/tmp/../etc/passwd"]), noon());
+        assert_eq!(decision.rule.as_deref(), Some("rm-elsewhere"));
+
+        let decision = policy.evaluate_at("rm", &args(&["/tmp/a", "/etc/b"]), noon());
+        assert_eq!(decision.action, PolicyAction::Deny);
+
+        assert_eq!(
+            path_argument("--output=/tmp/./x/../y"),
+            Some(PathBuf::from("/tmp/y"))
+        );
+        assert_eq!(path_argument("file.txt"), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_path_scopes_resolve_relative_paths_and_symlinks() {
+        let dir = tempfile::tempdir().unwrap();
+        let work = dir.path().join("work");
+        std::fs::create_dir(&work).unwrap();
+        std::os::unix::fs::symlink("/etc", work.join("etc")).unwrap();
+
+        let policy = Policy::from_yaml(&format!(
+            "default: deny\nrules:\n  - {{name: work, command: rm, paths: [{:?}], action: allow}}",
+            work
+        ))
+        .unwrap();
+        let rm = |args: &[&str]| {
+            policy
+                .evaluate_with_risk("rm", &self::args(args), RiskLevel::Low, Some(&work))
+                .rule
+        };
+
+        assert_eq!(rm(&["notes.txt", "./a/b"]).as_deref(), Some("work"));
+        assert_eq!(rm(&["../../etc/shadow"]), None);
+        assert_eq!(rm(&["etc/shadow"]), None);
+        assert_eq!(rm(&[&format!("{}/../x", work.display())]), None);
+    }
+
+    #[test]
+    fn test_hours_wrap_midnight() {
+        let policy = Policy::from_yaml(POLICY).unwrap();
+        let late = NaiveTime::from_hms_opt(23, 30, 0).unwrap();
+        let early = NaiveTime::from_hms_opt(6, 59, 0).unwrap();
+        let morning = NaiveTime::from_hms_opt(7, 0, 0).unwrap();
+
+        assert_eq!(
+            policy.evaluate_at("ls", &[], late).action,
+            PolicyAction::Confirm
+        );
+        assert_eq!(
+            policy.evaluate_at("ls", &[], early).action,
+            PolicyAction::Confirm
+        );
+        assert_eq!(
+            policy.evaluate_at("ls", &[], morning).action,
+            PolicyAction::Allow
+        );
+    }
+
+    #[test]
+    fn test_toml_policy() {
+        let policy = Policy::from_toml(
+            r#"
+default = "deny"
+
+[[rules]]
+name = "list-home"
+command = "ls"
+args_only = ["^-[la]+$", "^/home/"]
+max_args = 2
+action = "allow"
+"#,
+        )
+        .unwrap();
+
+        let allowed = policy.evaluate_at("ls", &args(&["-la", "/home/user"]), noon());
+        assert_eq!(allowed.action, PolicyAction::Allow);
+
+        let other_dir = policy.evaluate_at("ls", &args(&["/root"]), noon());
+        assert_eq!(other_dir.action, PolicyAction::Deny);
+        assert_eq!(other_dir.rule, None);
+    }
+
+    #[test]
+    fn test_risk_rules() {
+        let policy = Policy::from_yaml(
+            "rules:\n  - {name: risky, command: '*', risk: medium, action: confirm}",
+        )
+        .unwrap();
+
+        let confirm = |risk| policy.evaluate_with_risk("ls", &[], risk, None).action;
+        assert_eq!(confirm(RiskLevel::Low), PolicyAction::Allow);
+        assert_eq!(confirm(RiskLevel::Medium), PolicyAction::Confirm);
+        assert_eq!(confirm(RiskLevel::High), PolicyAction::Confirm);
+
+        // Unscored requests never match a risk condition
+        assert_eq!(
+            policy.evaluate_at("ls", &[], noon()).action,
+            PolicyAction::Allow
+        );
+    }
+
+    #[test]
+    fn test_invalid_rules_rejected() {
+        let bad_regex = "rules:\n  - {name: bad, command: ls, args_contain: ['('], action: deny}";
+        assert!(matches!(
+            Policy::from_yaml(bad_regex),
+            Err(PolicyError::InvalidRule { .. })
+        ));
+
+        let bad_hours = "rules:\n  - {name: bad, command: ls, hours: '9-5', action: deny}";
+        assert!(matches!(
+            Policy::from_yaml(bad_hours),
+            Err(PolicyError::InvalidRule { .. })
+        ));
+    }
+}
@@ -0,0 +1,234 @@
+//! Output post-processing
+//!
+//! A `find /` can print thousands of lines, which is no use read aloud.
+//! Post-processors condense a successful result into
+//! [`CommandResult::summary`], leaving stdout intact. Whitelist entries pick a
+//! built-in [`PostProcess`]; callers can register their own
+//! [`OutputPostProcessor`] for a command with
+//! [`CommandExecutor::with_post_processor`](crate::CommandExecutor::with_post_processor).
+
+use crate::executor::CommandResult;
+use serde::{Deserialize, Serialize};
+
+/// Condenses command output, e.g. for a voice response
+pub trait OutputPostProcessor: Send + Sync {
+    /// Summary of a successful `result`, or `None` to leave it without one
+    fn summarize(&self, result: &CommandResult) -> Option<String>;
+}
+
+/// Built-in post-processors, selected per whitelist entry
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PostProcess {
+    /// First `head` and last `tail` lines, with a count of the lines left out
+    Truncate {
+        #[serde(default)]
+        head: usize,
+        #[serde(default)]
+        tail: usize,
+    },
+
+    /// Number of output lines
+    CountLines,
+
+    /// Whitespace-aligned table with a header row (`ps`, `df`), given as
+    /// `Header: value` pairs per row
+    Table {
+        /// Columns to keep, by header name (all if empty)
+        #[serde(default)]
+        columns: Vec<String>,
+
+        /// Rows to include before counting the rest
+        #[serde(default = "default_max_rows")]
+        max_rows: usize,
+    },
+}
+
+fn default_max_rows() -> usize {
+    5
+}
+
+impl OutputPostProcessor for PostProcess {
+    fn summarize(&self, result: &CommandResult) -> Option<String> {
+        let lines: Vec<&str> = result.stdout.lines().collect();
+
+        match self {
+            PostProcess::Truncate { head, tail } => Some(truncate(&lines, *head, *tail)),
+            PostProcess::CountLines => Some(match lines.len() {
+                1 => "1 line".to_string(),
+                n => format!("{} lines", n),
+            }),
+            PostProcess::Table { columns, max_rows } => table(&lines, columns, *max_rows),
+        }
+    }
+}
+
+fn truncate(lines: &[&str], head: usize, tail: usize) -> String {
+    if lines.len() <= head + tail {
+        return lines.join("\n");
+    }
+
+    let omitted = lines.len() - head - tail;
+    let mut kept: Vec<String> = lines[..head].iter().map(|l| l.to_string()).collect();
+    kept.push(format!("... {} more {}", omitted, plural(omitted, "line")));
+    kept.extend(lines[lines.len() - tail..].iter().map(|l| l.to_string()));
+    kept.join("\n")
+}
+
+fn table(lines: &[&str], columns: &[String], max_rows: usize) -> Option<String> {
+    let mut lines = lines.iter().filter(|line| !line.trim().is_empty());
+    let header: Vec<&str> = lines.next()?.split_whitespace().collect();
+    let rows: Vec<Vec<&str>> = lines
+        .map(|line| split_columns(line, header.len()))
+        .collect();
+
+    let mut selected: Vec<usize> = columns
+        .iter()
+        .filter_map(|name| header.iter().position(|h| h.eq_ignore_ascii_case(name)))
+        .collect();
+    if selected.is_empty() {
+        selected = (0..header.len()).collect();
+    }
+
+    if rows.is_empty() {
+        return Some("No rows".to_string());
+    }
+
+    let mut summary: Vec<String> = rows
+        .iter()
+        .take(max_rows)
+        .map(|row| {
+            selected
+                .iter()
+                .filter_map(|&i| Some(format!("{}: {}", header[i], row.get(i)?)))
+                .collect::<Vec<_>>()
+                .join(", ")
+        })
+        .collect();
+
+    if rows.len() > max_rows {
+        let omitted = rows.len() - max_rows;
+        summary.push(format!("... {} more {}", omitted, plural(omitted, "row")));
+    }
+
+    Some(summary.join("\n"))
+}
+
+/// Split on whitespace into at most `n` fields; the last keeps the rest of the
+/// line, so a trailing column like a command line stays whole
+fn split_columns(line: &str, n: usize) -> Vec<&str> {
+    let mut fields = Vec::with_capacity(n);
+    let mut rest = line.trim();
+
+    while fields.len() + 1 < n {
+        match rest.split_once(char::is_whitespace) {
+            Some((field, tail)) => {
+                fields.push(field);
+                rest = tail.trim_start();
+            }
+            None => break,
+        }
+    }
+
+    if !rest.is_empty() {
+        fields.push(rest);
+    }
+    fields
+}
+
+fn plural(n: usize, word: &str) -> String {
+    if n == 1 {
+        word.to_string()
+    } else {
+        format!("{}s", word)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(stdout: &str) -> CommandResult {
+        CommandResult {
+            command: "test".to_string(),
+            args: Vec::new(),
+            stdout: stdout.to_string(),
+            stderr: String::new(),
+            exit_code: 0,
+            duration_ms: 0,
+            success: true,
+            peak_memory_bytes: None,
+            cpu_usage_usec: None,
+            max_rss_kb: None,
+            user_cpu_ms: None,
+            sys_cpu_ms: None,
+            was_killed_by: None,
+            summary: None,
+            cached: false,
+            risk: None,
+        }
+    }
+
+    #[test]
+    fn test_truncate_and_count() {
+        let output: String = (1..=100).map(|i| format!("{}\n", i)).collect();
+        let output = result(&output);
+
+        assert_eq!(
+            PostProcess::Truncate { head: 2, tail: 1 }.summarize(&output),
+            Some("1\n2\n... 97 more lines\n100".to_string())
+        );
+        assert_eq!(
+            PostProcess::Truncate { head: 0, tail: 99 }
+                .summarize(&output)
+                .unwrap()
+                .lines()
+                .next(),
+            Some("... 1 more line")
+        );
+        assert_eq!(
+            PostProcess::Truncate { head: 5, tail: 0 }.summarize(&result("a\nb\n")),
+            Some("a\nb".to_string())
+        );
+        assert_eq!(
+            PostProcess::CountLines.summarize(&output),
+            Some("100 lines".to_string())
+        );
+    }
+
+    #[test]
+    fn test_table() {
+        let ps = result(
+            "  PID TTY          TIME CMD\n\
+             \x20 101 pts/0    00:00:01 bash\n\
+             \x20 202 pts/0    00:00:00 python3 -m http.server\n\
+             \x20 303 pts/0    00:00:00 ps\n",
+        );
+
+        let table = PostProcess::Table {
+            columns: vec!["pid".to_string(), "CMD".to_string()],
+            max_rows: 2,
+        };
+        assert_eq!(
+            table.summarize(&ps),
+            Some(
+                "PID: 101, CMD: bash\nPID: 202, CMD: python3 -m http.server\n... 1 more row"
+                    .to_string()
+            )
+        );
+
+        let all = PostProcess::Table {
+            columns: Vec::new(),
+            max_rows: 1,
+        };
+        assert_eq!(
+            all.summarize(&ps).unwrap().lines().next(),
+            Some("PID: 101, TTY: pts/0, TIME: 00:00:01, CMD: bash")
+        );
+        assert_eq!(
+            all.summarize(&result("PID CMD\n")),
+            Some("No rows".to_string())
+        );
+        assert_eq!(all.summarize(&result("")), None);
+    }
+}
@@ -0,0 +1,197 @@
+//! Constrained PowerShell execution
+//!
+//! Whitelist entries with a [`PowerShellCmdlet`] run a single cmdlet through
+//! PowerShell instead of an executable. The session is switched to Constrained
+//! Language Mode before the cmdlet runs, profiles and prompts are disabled, and
+//! every argument value is passed as a single-quoted literal so nothing is
+//! expanded or evaluated.
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// PowerShell argument errors
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum PowerShellError {
+    #[error("Invalid cmdlet name: {0}")]
+    InvalidCmdlet(String),
+
+    #[error("Parameter not allowed: {0}")]
+    ParameterNotAllowed(String),
+
+    #[error("Missing value for parameter {0}")]
+    MissingValue(String),
+
+    #[error("Argument {0} contains characters not allowed in PowerShell literals")]
+    UnsafeValue(usize),
+}
+
+/// Statement run before the cmdlet; cannot be undone within the session
+const CONSTRAINED_LANGUAGE: &str =
+    "$ExecutionContext.SessionState.LanguageMode = 'ConstrainedLanguage'";
+
+/// Characters PowerShell treats as single quotes, plus the double quote that
+/// Windows command-line parsing would mangle
+const QUOTES: [char; 6] = ['\'', '"', '\u{2018}', '\u{2019}', '\u{201A}', '\u{201B}'];
+
+/// A whitelisted cmdlet and the parameters callers may pass
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PowerShellCmdlet {
+    /// Cmdlet name, e.g. `Get-ChildItem`
+    pub cmdlet: String,
+
+    /// Parameters that take a value, without the leading `-` (e.g. `Path`)
+    #[serde(default)]
+    pub parameters: Vec<String>,
+
+    /// Switch parameters, without the leading `-` (e.g. `Recurse`)
+    #[serde(default)]
+    pub switches: Vec<String>,
+}
+
+impl PowerShellCmdlet {
+    /// Create a cmdlet entry
+    pub fn new(cmdlet: &str, parameters: &[&str], switches: &[&str]) -> Self {
+        Self {
+            cmdlet: cmdlet.to_string(),
+            parameters: parameters.iter().map(|p| p.to_string()).collect(),
+            switches: switches.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    /// Check the arguments and build the script that runs the cmdlet
+    ///
+    /// Arguments starting with `-` must name an allowed parameter or switch
+    /// (case-insensitive); a parameter consumes the next argument as its
+    /// value. Other arguments are positional values.
+    pub fn script(&self, args: &[String]) -> Result<String, PowerShellError> {
+        let valid_name = !self.cmdlet.is_empty()
+            && self
+                .cmdlet
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-');
+        if !valid_name {
+            return Err(PowerShellError::InvalidCmdlet(self.cmdlet.clone()));
+        }
+
+        let mut script = format!("{}; {}", CONSTRAINED_LANGUAGE, self.cmdlet);
+        let mut args = args.iter().enumerate();
+
+        while let Some((i, arg)) = args.next() {
+            match arg.strip_prefix('-') {
+                Some(name) if contains_ignore_case(&self.switches, name) => {
+                    script.push_str(&format!(" -{}", name));
+                }
+                Some(name) if contains_ignore_case(&self.parameters, name) => {
+                    let (i, value) = args
+                        .next()
+                        .ok_or_else(|| PowerShellError::MissingValue(arg.clone()))?;
+                    script.push_str(&format!(" -{} {}", name, literal(i, value)?));
+                }
+                Some(_) => return Err(PowerShellError::ParameterNotAllowed(arg.clone())),
+                None => {
+                    script.push(' ');
+                    script.push_str(&literal(i, arg)?);
+                }
+            }
+        }
+
+        Ok(script)
+    }
+
+    /// Arguments for the PowerShell executable
+    pub fn argv(&self, args: &[String]) -> Result<Vec<String>, PowerShellError> {
+        Ok(vec![
+            "-NoProfile".to_string(),
+            "-NonInteractive".to_string(),
+            "-NoLogo".to_string(),
+            "-ExecutionPolicy".to_string(),
+            "Restricted".to_string(),
+            "-Command".to_string(),
+            self.script(args)?,
+        ])
+    }
+}
+
+/// PowerShell executable: Windows PowerShell on Windows, PowerShell 7
+/// elsewhere
+pub fn program() -> &'static str {
+    if cfg!(windows) {
+        "powershell"
+    } else {
+        "pwsh"
+    }
+}
+
+fn contains_ignore_case(names: &[String], name: &str) -> bool {
+    names.iter().any(|n| n.eq_ignore_ascii_case(name))
+}
+
+/// Quote a value as a single-quoted literal, which PowerShell never expands
+fn literal(index: usize, value: &str) -> Result<String, PowerShellError> {
+    if value.contains(QUOTES) || value.chars().any(char::is_control) {
+        return Err(PowerShellError::UnsafeValue(index));
+    }
+
+    Ok(format!("'{}'", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_script_quotes_values() {
+        let cmdlet = PowerShellCmdlet::new("Get-ChildItem", &["Path", "Filter"], &["Recurse"]);
+
+        let script = cmdlet
+            .script(&args(&[
+                "-path",
+                r"C:\Users\me\$HOME;rm",
+                "-Recurse",
+                "*.txt",
+            ]))
+            .unwrap();
+
+        assert_eq!(
+            script,
+            "$ExecutionContext.SessionState.LanguageMode = 'ConstrainedLanguage'; \
+             Get-ChildItem -path 'C:\\Users\\me\\$HOME;rm' -Recurse '*.txt'"
+        );
+        assert_eq!(
+            cmdlet.argv(&[]).unwrap()[..2],
+            ["-NoProfile", "-NonInteractive"]
+        );
+    }
+
+    #[test]
+    fn test_script_rejects_unsafe_arguments() {
+        let cmdlet = PowerShellCmdlet::new("Get-Content", &["Path"], &[]);
+
+        assert_eq!(
+            cmdlet.script(&args(&["-Path", "a'; Remove-Item x; '"])),
+            Err(PowerShellError::UnsafeValue(1))
+        );
+        assert_eq!(
+            cmdlet.script(&args(&["a\u{2019}b"])),
+            Err(PowerShellError::UnsafeValue(0))
+        );
+        assert_eq!(
+            cmdlet.script(&args(&["-Encoding", "utf8"])),
+            Err(PowerShellError::ParameterNotAllowed(
+                "-Encoding".to_string()
+            ))
+        );
+        assert_eq!(
+            cmdlet.script(&args(&["-Path"])),
+            Err(PowerShellError::MissingValue("-Path".to_string()))
+        );
+        assert!(matches!(
+            PowerShellCmdlet::new("Get-Item; iex", &[], &[]).script(&[]),
+            Err(PowerShellError::InvalidCmdlet(_))
+        ));
+    }
+}
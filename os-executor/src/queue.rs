@@ -0,0 +1,450 @@
+//! Concurrent execution queue
+//!
+//! The voice agent can fire several commands in quick succession.
+//! [`ExecutionQueue`] runs them through a shared [`CommandExecutor`] with a cap
+//! on total concurrency, optional per-command caps (e.g. one `find` at a time)
+//! and priorities, so an urgent request is not stuck behind a backlog.
+
+use crate::executor::{CommandExecutor, CommandResult, ExecutorError};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use tokio::sync::oneshot;
+use tracing::debug;
+
+/// Queue settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueueConfig {
+    /// Commands running at once, across all command names
+    pub max_concurrency: usize,
+
+    /// Commands running at once per command name (unlisted names are only
+    /// bounded by `max_concurrency`)
+    #[serde(default)]
+    pub per_command: HashMap<String, usize>,
+
+    /// Requests waiting at once; further submissions are rejected
+    #[serde(default)]
+    pub max_queue_depth: Option<usize>,
+}
+
+impl QueueConfig {
+    /// Check that every cap lets at least one command run
+    pub fn validate(&self) -> Result<(), ExecutorError> {
+        if self.max_concurrency == 0 {
+            return Err(ExecutorError::InvalidArguments(
+                "max_concurrency must be at least 1".to_string(),
+            ));
+        }
+
+        if let Some((command, _)) = self.per_command.iter().find(|(_, limit)| **limit == 0) {
+            return Err(ExecutorError::InvalidArguments(format!(
+                "per_command limit for {} must be at least 1",
+                command
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for QueueConfig {
+    fn default() -> Self {
+        Self {
+            max_concurrency: 4,
+            per_command: HashMap::new(),
+            max_queue_depth: Some(64),
+        }
+    }
+}
+
+/// Request priority; higher priorities are started first
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Priority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+/// Snapshot of queue activity
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QueueMetrics {
+    /// Requests waiting to start
+    pub queued: usize,
+
+    /// Highest `queued` seen
+    pub peak_queued: usize,
+
+    /// Commands currently running
+    pub running: usize,
+
+    /// Commands currently running, by name
+    pub running_by_command: HashMap<String, usize>,
+
+    /// Requests that ran to completion (successfully or not)
+    pub completed: u64,
+
+    /// Requests rejected because the queue was full
+    pub rejected: u64,
+}
+
+/// Request waiting for a slot
+struct Waiter {
+    priority: Priority,
+    seq: u64,
+    command: String,
+    start: oneshot::Sender<Permit>,
+}
+
+#[derive(Default)]
+struct QueueState {
+    waiting: Vec<Waiter>,
+    next_seq: u64,
+    metrics: QueueMetrics,
+}
+
+/// A running slot; releasing it (on drop) lets the next request start
+struct Permit {
+    queue: Option<Arc<Inner>>,
+    command: String,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        if let Some(queue) = self.queue.take() {
+            let mut state = queue.lock();
+            state.release(&self.command);
+            queue.dispatch(&mut state);
+        }
+    }
+}
+
+impl QueueState {
+    fn release(&mut self, command: &str) {
+        self.metrics.running -= 1;
+        if let Some(count) = self.metrics.running_by_command.get_mut(command) {
+            *count -= 1;
+            if *count == 0 {
+                self.metrics.running_by_command.remove(command);
+            }
+        }
+    }
+}
+
+struct Inner {
+    config: QueueConfig,
+    state: Mutex<QueueState>,
+}
+
+impl Inner {
+    fn lock(&self) -> MutexGuard<'_, QueueState> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Start waiting requests, highest priority and oldest first, while slots
+    /// are free
+    ///
+    /// A request blocked by its per-command cap does not hold up others.
+    fn dispatch(self: &Arc<Self>, state: &mut QueueState) {
+        // Submitters that gave up while waiting
+        state.waiting.retain(|waiter| !waiter.start.is_closed());
+        state.metrics.queued = state.waiting.len();
+
+        state
+            .waiting
+            .sort_by(|a, b| b.priority.cmp(&a.priority).then(a.seq.cmp(&b.seq)));
+
+        let mut i = 0;
+        while i < state.waiting.len() && state.metrics.running < self.config.max_concurrency {
+            let command = &state.waiting[i].command;
+            let running = state
+                .metrics
+                .running_by_command
+                .get(command)
+                .copied()
+                .unwrap_or(0);
+            let limit = self.config.per_command.get(command).copied();
+
+            if limit.is_some_and(|limit| running >= limit) {
+                i += 1;
+                continue;
+            }
+
+            let waiter = state.waiting.remove(i);
+            state.metrics.queued -= 1;
+            state.metrics.running += 1;
+            *state
+                .metrics
+                .running_by_command
+                .entry(waiter.command.clone())
+                .or_insert(0) += 1;
+
+            let permit = Permit {
+                queue: Some(self.clone()),
+                command: waiter.command,
+            };
+
+            // A dropped submitter hands the permit back; release it without
+            // re-entering the lock we hold
+            if let Err(mut permit) = waiter.start.send(permit) {
+                debug!("Queued request for {} was abandoned", permit.command);
+                permit.queue = None;
+                state.release(&permit.command);
+            }
+        }
+    }
+
+    /// Queue a request for `command`; the receiver gets its permit once it
+    /// may start
+    fn enqueue(
+        self: &Arc<Self>,
+        command: &str,
+        priority: Priority,
+    ) -> Result<oneshot::Receiver<Permit>, ExecutorError> {
+        let (start_tx, start_rx) = oneshot::channel();
+        let mut state = self.lock();
+
+        if self
+            .config
+            .max_queue_depth
+            .is_some_and(|max| state.metrics.queued >= max)
+        {
+            state.metrics.rejected += 1;
+            return Err(ExecutorError::ResourceLimitExceeded(
+                "Execution queue is full".to_string(),
+            ));
+        }
+
+        let seq = state.next_seq;
+        state.next_seq += 1;
+        state.waiting.push(Waiter {
+            priority,
+            seq,
+            command: command.to_string(),
+            start: start_tx,
+        });
+        state.metrics.queued += 1;
+        state.metrics.peak_queued = state.metrics.peak_queued.max(state.metrics.queued);
+
+        self.dispatch(&mut state);
+        Ok(start_rx)
+    }
+}
+
+/// Runs commands through an executor with bounded concurrency
+#[derive(Clone)]
+pub struct ExecutionQueue {
+    executor: Arc<CommandExecutor>,
+    inner: Arc<Inner>,
+}
+
+impl ExecutionQueue {
+    /// Create a queue in front of `executor`
+    ///
+    /// Fails with [`ExecutorError::InvalidArguments`] if a cap is 0, as no
+    /// command could ever start.
+    pub fn new(executor: Arc<CommandExecutor>, config: QueueConfig) -> Result<Self, ExecutorError> {
+        config.validate()?;
+
+        Ok(Self {
+            executor,
+            inner: Arc::new(Inner {
+                config,
+                state: Mutex::new(QueueState::default()),
+            }),
+        })
+    }
+
+    /// Wait for a slot, then execute the command
+    ///
+    /// Fails with [`ExecutorError::ResourceLimitExceeded`] if the queue is
+    /// full. Dropping the future gives up the request's place in the queue.
+    pub async fn submit(
+        &self,
+        command: &str,
+        args: &[String],
+        priority: Priority,
+    ) -> Result<CommandResult, ExecutorError> {
+        let start_rx = self.inner.enqueue(command, priority)?;
+
+        let permit = start_rx
+            .await
+            .map_err(|_| ExecutorError::ExecutionFailed("Execution queue closed".to_string()))?;
+
+        let result = self.executor.execute(command, args).await;
+
+        self.inner.lock().metrics.completed += 1;
+        drop(permit);
+
+        result
+    }
+
+    /// Current queue depth and activity
+    pub fn metrics(&self) -> QueueMetrics {
+        self.inner.lock().metrics.clone()
+    }
+
+    /// Executor commands are run on
+    pub fn executor(&self) -> &Arc<CommandExecutor> {
+        &self.executor
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::ExecutorConfig;
+    use crate::whitelist::{CommandWhitelist, WhitelistEntry};
+    use tokio::sync::oneshot::error::TryRecvError;
+
+    fn queue(config: QueueConfig) -> ExecutionQueue {
+        let mut whitelist = CommandWhitelist::new();
+        whitelist.add_command(
+            "echo",
+            WhitelistEntry {
+                command: "echo".to_string(),
+                ..Default::default()
+            },
+        );
+
+        let config_executor = ExecutorConfig {
+            enable_sandbox: false,
+            ..Default::default()
+        };
+
+        ExecutionQueue::new(
+            Arc::new(CommandExecutor::new(config_executor, whitelist)),
+            config,
+        )
+        .unwrap()
+    }
+
+    /// Whether the request has been started, holding on to its permit
+    fn started(request: &mut oneshot::Receiver<Permit>) -> Option<Permit> {
+        match request.try_recv() {
+            Ok(permit) => Some(permit),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Closed) => panic!("request dropped by the queue"),
+        }
+    }
+
+    #[test]
+    fn test_zero_caps_rejected() {
+        let zero = QueueConfig {
+            max_concurrency: 0,
+            ..Default::default()
+        };
+        assert!(matches!(
+            zero.validate(),
+            Err(ExecutorError::InvalidArguments(_))
+        ));
+
+        let zero_find = QueueConfig {
+            per_command: HashMap::from([("find".to_string(), 0)]),
+            ..Default::default()
+        };
+        assert!(zero_find.validate().is_err());
+        assert!(QueueConfig::default().validate().is_ok());
+    }
+
+    #[test]
+    fn test_high_priority_starts_first() {
+        let queue = queue(QueueConfig {
+            max_concurrency: 1,
+            ..Default::default()
+        });
+        let inner = &queue.inner;
+
+        let running = started(&mut inner.enqueue("sleep", Priority::Normal).unwrap()).unwrap();
+        let mut low = inner.enqueue("date", Priority::Low).unwrap();
+        let mut high = inner.enqueue("date", Priority::High).unwrap();
+        assert!(started(&mut low).is_none());
+        assert!(started(&mut high).is_none());
+        assert_eq!(queue.metrics().peak_queued, 2);
+
+        drop(running);
+        let high = started(&mut high).unwrap();
+        assert!(started(&mut low).is_none());
+
+        drop(high);
+        let low = started(&mut low).unwrap();
+        assert_eq!((queue.metrics().queued, queue.metrics().running), (0, 1));
+
+        drop(low);
+        assert_eq!(queue.metrics().running, 0);
+    }
+
+    #[test]
+    fn test_per_command_limit_does_not_block_others() {
+        let queue = queue(QueueConfig {
+            max_concurrency: 4,
+            per_command: HashMap::from([("sleep".to_string(), 1)]),
+            ..Default::default()
+        });
+        let inner = &queue.inner;
+
+        let first = started(&mut inner.enqueue("sleep", Priority::Normal).unwrap()).unwrap();
+        let mut second = inner.enqueue("sleep", Priority::Normal).unwrap();
+        assert!(started(&mut second).is_none());
+
+        // Queued after the second sleep and at a lower priority, but free to run
+        let echo = started(&mut inner.enqueue("echo", Priority::Low).unwrap()).unwrap();
+        let metrics = queue.metrics();
+        assert_eq!(metrics.running_by_command.get("sleep"), Some(&1));
+        assert_eq!(metrics.running_by_command.get("echo"), Some(&1));
+        assert_eq!(metrics.queued, 1);
+
+        drop(first);
+        assert!(started(&mut second).is_some());
+        drop(echo);
+    }
+
+    #[test]
+    fn test_full_queue_rejects() {
+        let queue = queue(QueueConfig {
+            max_concurrency: 1,
+            max_queue_depth: Some(1),
+            ..Default::default()
+        });
+        let inner = &queue.inner;
+
+        let running = started(&mut inner.enqueue("sleep", Priority::Normal).unwrap()).unwrap();
+        let waiting = inner.enqueue("echo", Priority::Normal).unwrap();
+
+        assert!(matches!(
+            inner.enqueue("echo", Priority::High),
+            Err(ExecutorError::ResourceLimitExceeded(_))
+        ));
+        assert_eq!(queue.metrics().rejected, 1);
+
+        // An abandoned request gives its slot straight back
+        drop(waiting);
+        drop(running);
+        let metrics = queue.metrics();
+        assert_eq!((metrics.queued, metrics.running), (0, 0));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_submit_runs_command() {
+        let queue = queue(QueueConfig::default());
+
+        let result = queue
+            .submit("echo", &["hi".to_string()], Priority::Normal)
+            .await
+            .unwrap();
+        assert_eq!(result.stdout.trim(), "hi");
+
+        let metrics = queue.metrics();
+        assert_eq!(
+            (metrics.queued, metrics.running, metrics.completed),
+            (0, 0, 1)
+        );
+    }
+}
@@ -0,0 +1,360 @@
+//! Scoped Windows registry queries
+//!
+//! The `reg_query` builtin reads keys through the registry API instead of
+//! shelling out to `reg.exe`. Only keys under the executor's
+//! `registry_allowlist` can be read; keys are compared case-insensitively,
+//! component by component, so `HKLM\SOFTWARE\Foo` does not admit
+//! `HKLM\SOFTWARE\FooBar`. Parsing and scoping work on every platform;
+//! reading is Windows-only.
+
+use std::fmt;
+use std::str::FromStr;
+
+/// Registry root key
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Hive {
+    ClassesRoot,
+    CurrentUser,
+    LocalMachine,
+    Users,
+    CurrentConfig,
+}
+
+impl Hive {
+    fn parse(name: &str) -> Option<Self> {
+        let name = name.strip_suffix(':').unwrap_or(name).to_ascii_uppercase();
+        Some(match name.as_str() {
+            "HKCR" | "HKEY_CLASSES_ROOT" => Hive::ClassesRoot,
+            "HKCU" | "HKEY_CURRENT_USER" => Hive::CurrentUser,
+            "HKLM" | "HKEY_LOCAL_MACHINE" => Hive::LocalMachine,
+            "HKU" | "HKEY_USERS" => Hive::Users,
+            "HKCC" | "HKEY_CURRENT_CONFIG" => Hive::CurrentConfig,
+            _ => return None,
+        })
+    }
+
+    /// Abbreviation as used by `reg.exe`
+    pub fn short_name(&self) -> &'static str {
+        match self {
+            Hive::ClassesRoot => "HKCR",
+            Hive::CurrentUser => "HKCU",
+            Hive::LocalMachine => "HKLM",
+            Hive::Users => "HKU",
+            Hive::CurrentConfig => "HKCC",
+        }
+    }
+}
+
+/// Registry key path such as `HKLM\SOFTWARE\Microsoft`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegistryKey {
+    pub hive: Hive,
+    /// Subkey names below the hive
+    pub path: Vec<String>,
+}
+
+impl RegistryKey {
+    /// Whether this key is `scope` or lies below it
+    pub fn is_within(&self, scope: &RegistryKey) -> bool {
+        self.hive == scope.hive
+            && self.path.len() >= scope.path.len()
+            && self
+                .path
+                .iter()
+                .zip(&scope.path)
+                .all(|(a, b)| a.eq_ignore_ascii_case(b))
+    }
+
+    /// Whether this key lies under any of the `allowlist` keys
+    ///
+    /// Allowlist entries that fail to parse are ignored.
+    pub fn is_allowed(&self, allowlist: &[String]) -> bool {
+        allowlist
+            .iter()
+            .filter_map(|scope| scope.parse::<RegistryKey>().ok())
+            .any(|scope| self.is_within(&scope))
+    }
+}
+
+impl FromStr for RegistryKey {
+    type Err = String;
+
+    /// Parse `HIVE\sub\key`; the hive may be abbreviated (`HKLM`) or spelled
+    /// out (`HKEY_LOCAL_MACHINE`), and a trailing `\` is ignored
+    fn from_str(key: &str) -> Result<Self, Self::Err> {
+        let key = key.strip_suffix('\\').unwrap_or(key);
+        let mut parts = key.split('\\');
+        let hive = parts
+            .next()
+            .and_then(Hive::parse)
+            .ok_or_else(|| format!("unknown registry hive in {:?}", key))?;
+
+        let path: Vec<String> = parts.map(str::to_string).collect();
+        if path.iter().any(|part| part.is_empty()) {
+            return Err(format!("empty key name in {:?}", key));
+        }
+
+        Ok(Self { hive, path })
+    }
+}
+
+impl fmt::Display for RegistryKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.hive.short_name())?;
+        for part in &self.path {
+            write!(f, "\\{}", part)?;
+        }
+        Ok(())
+    }
+}
+
+/// Keys readable by default: OS version and install details, service
+/// configuration, and regional settings
+pub fn default_allowlist() -> Vec<String> {
+    [
+        r"HKLM\SOFTWARE\Microsoft\Windows NT\CurrentVersion",
+        r"HKLM\SYSTEM\CurrentControlSet\Services",
+        r"HKCU\Control Panel\International",
+    ]
+    .iter()
+    .map(|key| key.to_string())
+    .collect()
+}
+
+#[cfg(windows)]
+pub use windows::query;
+
+#[cfg(windows)]
+mod windows {
+    use super::{Hive, RegistryKey};
+    use std::ffi::OsStr;
+    use std::io;
+    use std::os::windows::ffi::OsStrExt;
+    use std::ptr;
+    use winapi::shared::minwindef::{DWORD, HKEY};
+    use winapi::shared::winerror::{ERROR_MORE_DATA, ERROR_NO_MORE_ITEMS, ERROR_SUCCESS};
+    use winapi::um::winnt::{
+        KEY_READ, REG_BINARY, REG_DWORD, REG_EXPAND_SZ, REG_MULTI_SZ, REG_QWORD, REG_SZ,
+    };
+    use winapi::um::winreg::{
+        RegCloseKey, RegEnumKeyExW, RegEnumValueW, RegOpenKeyExW, HKEY_CLASSES_ROOT,
+        HKEY_CURRENT_CONFIG, HKEY_CURRENT_USER, HKEY_LOCAL_MACHINE, HKEY_USERS,
+    };
+
+    /// Longest key name the registry allows, plus the terminator
+    const MAX_KEY_NAME: usize = 256;
+
+    /// Longest value name the registry allows, plus the terminator
+    const MAX_VALUE_NAME: usize = 16384;
+
+    /// Open key handle, closed on drop
+    struct Key(HKEY);
+
+    impl Drop for Key {
+        fn drop(&mut self) {
+            unsafe { RegCloseKey(self.0) };
+        }
+    }
+
+    fn check(status: i32) -> io::Result<()> {
+        if status as DWORD == ERROR_SUCCESS {
+            Ok(())
+        } else {
+            Err(io::Error::from_raw_os_error(status))
+        }
+    }
+
+    fn wide(s: &str) -> Vec<u16> {
+        OsStr::new(s).encode_wide().chain(Some(0)).collect()
+    }
+
+    fn open(key: &RegistryKey) -> io::Result<Key> {
+        let root = match key.hive {
+            Hive::ClassesRoot => HKEY_CLASSES_ROOT,
+            Hive::CurrentUser => HKEY_CURRENT_USER,
+            Hive::LocalMachine => HKEY_LOCAL_MACHINE,
+            Hive::Users => HKEY_USERS,
+            Hive::CurrentConfig => HKEY_CURRENT_CONFIG,
+        };
+        let subkey = wide(&key.path.join("\\"));
+        let mut handle: HKEY = ptr::null_mut();
+
+        check(unsafe { RegOpenKeyExW(root, subkey.as_ptr(), 0, KEY_READ, &mut handle) })?;
+        Ok(Key(handle))
+    }
+
+    /// Subkeys (with a trailing `\`) and `name<TAB>type<TAB>data` value lines
+    /// of `key`, or just the data of one value
+    pub fn query(key: &RegistryKey, value: Option<&str>) -> io::Result<Vec<String>> {
+        let handle = open(key)?;
+        let values = values(&handle)?;
+
+        if let Some(name) = value {
+            return values
+                .into_iter()
+                .find(|(n, _, _)| n.eq_ignore_ascii_case(name))
+                .map(|(_, _, data)| vec![data])
+                .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "value not found"));
+        }
+
+        let mut lines: Vec<String> = subkeys(&handle)?
+            .into_iter()
+            .map(|name| format!("{}\\", name))
+            .collect();
+        lines.extend(values.into_iter().map(|(name, kind, data)| {
+            let name = if name.is_empty() { "(Default)" } else { name.as_str() };
+            format!("{}\t{}\t{}", name, kind, data)
+        }));
+        Ok(lines)
+    }
+
+    fn subkeys(key: &Key) -> io::Result<Vec<String>> {
+        let mut names = Vec::new();
+        let mut buf = [0u16; MAX_KEY_NAME];
+
+        for index in 0.. {
+            let mut len = buf.len() as DWORD;
+            let status = unsafe {
+                RegEnumKeyExW(
+                    key.0,
+                    index,
+                    buf.as_mut_ptr(),
+                    &mut len,
+                    ptr::null_mut(),
+                    ptr::null_mut(),
+                    ptr::null_mut(),
+                    ptr::null_mut(),
+                )
+            };
+            if status as DWORD == ERROR_NO_MORE_ITEMS {
+                break;
+            }
+            check(status)?;
+            names.push(String::from_utf16_lossy(&buf[..len as usize]));
+        }
+
+        names.sort_by_key(|name| name.to_lowercase());
+        Ok(names)
+    }
+
+    /// `(name, type, data)` of every value
+    fn values(key: &Key) -> io::Result<Vec<(String, &'static str, String)>> {
+        let mut values = Vec::new();
+        let mut name = vec![0u16; MAX_VALUE_NAME];
+        let mut data = vec![0u8; 1024];
+
+        let mut index = 0;
+        loop {
+            let mut name_len = name.len() as DWORD;
+            let mut data_len = data.len() as DWORD;
+            let mut kind: DWORD = 0;
+            let status = unsafe {
+                RegEnumValueW(
+                    key.0,
+                    index,
+                    name.as_mut_ptr(),
+                    &mut name_len,
+                    ptr::null_mut(),
+                    &mut kind,
+                    data.as_mut_ptr(),
+                    &mut data_len,
+                )
+            };
+            match status as DWORD {
+                ERROR_NO_MORE_ITEMS => break,
+                ERROR_MORE_DATA => {
+                    // Retry the same index with room for the data
+                    data.resize(data_len as usize, 0);
+                    continue;
+                }
+                _ => check(status)?,
+            }
+
+            values.push((
+                String::from_utf16_lossy(&name[..name_len as usize]),
+                type_name(kind),
+                format_data(kind, &data[..data_len as usize]),
+            ));
+            index += 1;
+        }
+
+        Ok(values)
+    }
+
+    fn type_name(kind: DWORD) -> &'static str {
+        match kind {
+            REG_SZ => "REG_SZ",
+            REG_EXPAND_SZ => "REG_EXPAND_SZ",
+            REG_MULTI_SZ => "REG_MULTI_SZ",
+            REG_DWORD => "REG_DWORD",
+            REG_QWORD => "REG_QWORD",
+            REG_BINARY => "REG_BINARY",
+            _ => "REG_UNKNOWN",
+        }
+    }
+
+    fn format_data(kind: DWORD, data: &[u8]) -> String {
+        let utf16 = || -> Vec<u16> {
+            data.chunks_exact(2)
+                .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+                .collect()
+        };
+
+        match kind {
+            REG_SZ | REG_EXPAND_SZ => {
+                let text = utf16();
+                let end = text.iter().position(|&c| c == 0).unwrap_or(text.len());
+                String::from_utf16_lossy(&text[..end])
+            }
+            REG_MULTI_SZ => utf16()
+                .split(|&c| c == 0)
+                .filter(|part| !part.is_empty())
+                .map(String::from_utf16_lossy)
+                .collect::<Vec<_>>()
+                .join("; "),
+            REG_DWORD if data.len() >= 4 => {
+                u32::from_le_bytes([data[0], data[1], data[2], data[3]]).to_string()
+            }
+            REG_QWORD if data.len() >= 8 => {
+                let mut bytes = [0u8; 8];
+                bytes.copy_from_slice(&data[..8]);
+                u64::from_le_bytes(bytes).to_string()
+            }
+            _ => data.iter().map(|b| format!("{:02x}", b)).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(key: &str) -> RegistryKey {
+        key.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse_and_display() {
+        let parsed = key(r"HKEY_LOCAL_MACHINE\SOFTWARE\Microsoft\");
+        assert_eq!(parsed.hive, Hive::LocalMachine);
+        assert_eq!(parsed.to_string(), r"HKLM\SOFTWARE\Microsoft");
+        assert_eq!(key("hkcu").path, Vec::<String>::new());
+        assert_eq!(key(r"HKLM:\SOFTWARE").hive, Hive::LocalMachine);
+
+        assert!(r"HKXX\SOFTWARE".parse::<RegistryKey>().is_err());
+        assert!(r"HKLM\\SOFTWARE".parse::<RegistryKey>().is_err());
+    }
+
+    #[test]
+    fn test_allowlist_scoping() {
+        let allowlist = default_allowlist();
+
+        assert!(key(r"HKLM\SOFTWARE\Microsoft\Windows NT\CurrentVersion").is_allowed(&allowlist));
+        assert!(
+            key(r"hklm\system\currentcontrolset\services\OpenVPNService").is_allowed(&allowlist)
+        );
+        assert!(!key(r"HKLM\SOFTWARE\Microsoft").is_allowed(&allowlist));
+        assert!(!key(r"HKLM\SYSTEM\CurrentControlSet\ServicesExtra").is_allowed(&allowlist));
+        assert!(!key(r"HKU\SYSTEM\CurrentControlSet\Services").is_allowed(&allowlist));
+    }
+}
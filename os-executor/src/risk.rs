@@ -0,0 +1,287 @@
+//! Risk scoring of command requests
+//!
+//! Every request gets a [`RiskAssessment`] from what the command is, which
+//! paths its arguments name, what its whitelist entry lets it change, and
+//! whether it needs elevated privileges. The level is reported on the
+//! [`ExecutionPlan`](crate::ExecutionPlan) and [`CommandResult`](crate::CommandResult),
+//! and policy rules can match on it, e.g. to confirm every high-risk request:
+//!
+//! ```yaml
+//! rules:
+//!   - name: confirm-high-risk
+//!     command: "*"
+//!     risk: high
+//!     action: confirm
+//! ```
+
+use crate::mutations::Mutation;
+use crate::platform::Platform;
+use crate::policy::path_argument;
+use crate::seccomp::SeccompProfile;
+use crate::whitelist::WhitelistEntry;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+
+/// Commands that delete, overwrite or change the state of the system
+const DESTRUCTIVE_COMMANDS: &[&str] = &[
+    "rm",
+    "rmdir",
+    "shred",
+    "dd",
+    "mkfs",
+    "truncate",
+    "kill",
+    "killall",
+    "pkill",
+    "shutdown",
+    "reboot",
+    "poweroff",
+    "halt",
+    "systemctl",
+    "chmod",
+    "chown",
+    "chgrp",
+    "mount",
+    "umount",
+];
+
+/// Commands that create, move or overwrite files
+const MODIFYING_COMMANDS: &[&str] = &["mv", "cp", "ln", "mkdir", "touch", "tee", "install"];
+
+/// Directories the operating system depends on
+const SYSTEM_PATHS: &[&str] = &[
+    "/boot", "/bin", "/dev", "/etc", "/lib", "/lib64", "/proc", "/sbin", "/sys", "/usr", "/var",
+];
+
+/// Coarse risk of a request
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum RiskLevel {
+    /// Read-only, confined to user data
+    #[default]
+    Low,
+    /// Can change files or reach the network
+    Medium,
+    /// Destructive, privileged or aimed at system paths
+    High,
+}
+
+impl RiskLevel {
+    fn from_score(score: u32) -> Self {
+        match score {
+            0..=1 => RiskLevel::Low,
+            2..=3 => RiskLevel::Medium,
+            _ => RiskLevel::High,
+        }
+    }
+}
+
+impl fmt::Display for RiskLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            RiskLevel::Low => "low",
+            RiskLevel::Medium => "medium",
+            RiskLevel::High => "high",
+        })
+    }
+}
+
+/// Score of a request and what contributed to it
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RiskAssessment {
+    /// Level the score falls into
+    pub level: RiskLevel,
+
+    /// Sum of the factors below (0-1 low, 2-3 medium, 4+ high)
+    pub score: u32,
+
+    /// One entry per factor that raised the score
+    pub reasons: Vec<String>,
+}
+
+impl RiskAssessment {
+    fn add(&mut self, points: u32, reason: String) {
+        self.score += points;
+        self.reasons.push(reason);
+    }
+}
+
+/// Score a request against its whitelist entry
+pub fn assess(command: &str, args: &[String], entry: &WhitelistEntry) -> RiskAssessment {
+    let mut assessment = RiskAssessment::default();
+
+    if entry.requires_sudo {
+        assessment.add(4, "runs with elevated privileges".to_string());
+    }
+
+    if DESTRUCTIVE_COMMANDS.contains(&command) {
+        assessment.add(
+            3,
+            format!("{} can delete data or change system state", command),
+        );
+        if args.iter().any(|arg| is_force_or_recursive(arg)) {
+            assessment.add(1, "recursive or forced".to_string());
+        }
+    } else if MODIFYING_COMMANDS.contains(&command) {
+        assessment.add(2, format!("{} can create or overwrite files", command));
+    }
+
+    if entry.seccomp_profile != SeccompProfile::ReadOnly {
+        if let Some(paths) = entry.writable_paths.as_ref().filter(|p| !p.is_empty()) {
+            assessment.add(1, format!("may write to {}", paths.join(", ")));
+        }
+    }
+
+    if entry.network_allowed() {
+        assessment.add(1, "has network access".to_string());
+    }
+
+    add_system_paths(&mut assessment, args);
+
+    assessment.level = RiskLevel::from_score(assessment.score);
+    assessment
+}
+
+/// Score what actually runs on `platform`: the entry's platform executable
+/// and its arguments, not the name it is whitelisted under
+pub fn assess_resolved(
+    command: &str,
+    args: &[String],
+    entry: &WhitelistEntry,
+    platform: Platform,
+) -> RiskAssessment {
+    let executable = entry
+        .executable_for(platform)
+        .filter(|_| entry.builtin.is_none() && entry.powershell.is_none());
+
+    match executable {
+        Some(executable) => {
+            let program = Path::new(&executable.program)
+                .file_stem()
+                .map_or(executable.program.clone(), |stem| {
+                    stem.to_string_lossy().to_string()
+                });
+            let args: Vec<String> = executable.args.iter().chain(args).cloned().collect();
+            assess(&program, &args, entry)
+        }
+        None => assess(command, args, entry),
+    }
+}
+
+/// Score a file mutation: it changes files, and system paths add to that
+pub fn assess_mutation(mutation: &Mutation) -> RiskAssessment {
+    let mut assessment = RiskAssessment::default();
+    assessment.add(2, format!("{} changes files", mutation.name()));
+    add_system_paths(&mut assessment, &mutation.paths());
+
+    assessment.level = RiskLevel::from_score(assessment.score);
+    assessment
+}
+
+fn add_system_paths(assessment: &mut RiskAssessment, args: &[String]) {
+    for path in args.iter().filter_map(|arg| path_argument(arg)) {
+        if is_system_path(&path) {
+            assessment.add(2, format!("targets system path {}", path.display()));
+        }
+    }
+}
+
+/// `-r`, `-f`, `-rf`, `--recursive`, `--force` and the like
+fn is_force_or_recursive(arg: &str) -> bool {
+    match arg.strip_prefix("--") {
+        Some(long) => matches!(long, "recursive" | "force"),
+        None => arg
+            .strip_prefix('-')
+            .is_some_and(|short| short.chars().any(|c| matches!(c, 'r' | 'R' | 'f'))),
+    }
+}
+
+fn is_system_path(path: &Path) -> bool {
+    path == Path::new("/") || SYSTEM_PATHS.iter().any(|system| path.starts_with(system))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::whitelist::CommandWhitelist;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn test_levels() {
+        let whitelist = CommandWhitelist::default();
+        let ls = whitelist.get("ls").unwrap();
+
+        let home = assess("ls", &args(&["-la", "/home/user"]), ls);
+        assert_eq!(home.level, RiskLevel::Low);
+        assert!(home.reasons.is_empty());
+
+        let etc = assess("ls", &args(&["/etc/ssh"]), ls);
+        assert_eq!(etc.level, RiskLevel::Medium);
+        assert_eq!(etc.reasons, vec!["targets system path /etc/ssh"]);
+
+        let rm = WhitelistEntry {
+            command: "rm".to_string(),
+            ..ls.clone()
+        };
+        assert_eq!(
+            assess("rm", &args(&["/tmp/x"]), &rm).level,
+            RiskLevel::Medium
+        );
+        let forced = assess("rm", &args(&["-rf", "/tmp/x"]), &rm);
+        assert_eq!((forced.level, forced.score), (RiskLevel::High, 4));
+
+        let sudo = WhitelistEntry {
+            requires_sudo: true,
+            ..ls.clone()
+        };
+        assert_eq!(assess("ls", &[], &sudo).level, RiskLevel::High);
+    }
+
+    #[test]
+    fn test_resolved_command_is_scored() {
+        use crate::whitelist::PlatformExecutable;
+        use std::collections::HashMap;
+
+        let tidy = WhitelistEntry {
+            command: "tidy".to_string(),
+            platforms: HashMap::from([(
+                Platform::current(),
+                PlatformExecutable {
+                    program: "/bin/rm".to_string(),
+                    args: vec!["-rf".to_string()],
+                },
+            )]),
+            ..Default::default()
+        };
+
+        let risk = assess_resolved("tidy", &args(&["/tmp/x"]), &tidy, Platform::current());
+        assert_eq!(risk.level, RiskLevel::High);
+        assert_eq!(risk.reasons[0], "rm can delete data or change system state");
+
+        let trash = Mutation::Trash {
+            path: "/etc/hosts".to_string(),
+        };
+        assert_eq!(assess_mutation(&trash).level, RiskLevel::High);
+        let create = Mutation::CreateFile {
+            path: "notes.txt".to_string(),
+            contents: String::new(),
+        };
+        assert_eq!(assess_mutation(&create).level, RiskLevel::Medium);
+    }
+
+    #[test]
+    fn test_force_or_recursive() {
+        assert!(is_force_or_recursive("-rf"));
+        assert!(is_force_or_recursive("-R"));
+        assert!(is_force_or_recursive("--force"));
+        assert!(!is_force_or_recursive("-la"));
+        assert!(!is_force_or_recursive("--format"));
+        assert!(!is_force_or_recursive("readme"));
+    }
+}
@@ -1,9 +1,11 @@
 //! Sandbox for secure command execution with privilege dropping
 
+use crate::whitelist::WhitelistEntry;
+use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
 use std::process::Command;
 use thiserror::Error;
-use tokio::process::Command as TokioCommand;
+use tokio::process::{Child as TokioChild, Command as TokioCommand};
 use tracing::debug;
 
 /// Sandbox errors
@@ -20,15 +22,19 @@ pub enum SandboxError {
 
     #[error("Configuration error: {0}")]
     ConfigError(String),
+
+    #[error("{0} sandbox is not available")]
+    BackendUnavailable(String),
 }
 
 /// Sandbox configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SandboxConfig {
-    /// Drop to this user (Unix only)
+    /// Drop to this user when running as root (Unix only)
     pub drop_to_user: Option<String>,
 
-    /// Drop to this group (Unix only)
+    /// Drop to this group when running as root (Unix only; defaults to the
+    /// user's primary group)
     pub drop_to_group: Option<String>,
 
     /// Maximum memory (MB)
@@ -40,19 +46,290 @@ pub struct SandboxConfig {
     /// Chroot directory (Unix only)
     pub chroot_dir: Option<String>,
 
-    /// Use nsjail if available (Linux only)
+    /// Use nsjail if available (Linux only). Equivalent to `backend: Some(Nsjail)`.
     pub use_nsjail: bool,
+
+    /// Process jail to wrap commands in. `None` auto-detects an installed one.
+    #[serde(default)]
+    pub backend: Option<SandboxBackend>,
+
+    /// Maximum number of active processes (Windows Job Objects, cgroup `pids.max`)
+    #[serde(default)]
+    pub max_processes: Option<u32>,
+
+    /// Hard CPU rate cap in percent of total CPU (Windows Job Objects, cgroup `cpu.max`)
+    #[serde(default)]
+    pub cpu_rate_percent: Option<u32>,
+
+    /// Delegated cgroup v2 directory to create per-command cgroups under (Linux only)
+    #[serde(default)]
+    pub cgroup_root: Option<String>,
 }
 
 impl Default for SandboxConfig {
     fn default() -> Self {
         Self {
             drop_to_user: Some("nobody".to_string()),
-            drop_to_group: Some("nobody".to_string()),
+            drop_to_group: None,
             max_memory_mb: Some(512),
             max_cpu_time_secs: Some(5),
             chroot_dir: None,
             use_nsjail: false, // Disabled by default
+            backend: None,      // Auto-detect
+            max_processes: Some(16),
+            cpu_rate_percent: None,
+            cgroup_root: None,
+        }
+    }
+}
+
+/// User and group a command runs as after privileges are dropped
+#[cfg(unix)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Credentials {
+    pub(crate) uid: libc::uid_t,
+    pub(crate) gid: libc::gid_t,
+}
+
+#[cfg(unix)]
+impl Credentials {
+    /// Look up `drop_to_user` / `drop_to_group`
+    ///
+    /// The group defaults to the user's primary group; with only a group set,
+    /// the current user is kept. Unknown names are an error.
+    pub(crate) fn resolve(config: &SandboxConfig) -> Result<Option<Self>, SandboxError> {
+        use nix::unistd::{getegid, geteuid, Group, User};
+
+        let lookup_failed = |e: nix::Error| SandboxError::PrivilegeDropFailed(e.to_string());
+
+        let user = match config.drop_to_user {
+            Some(ref name) => Some(
+                User::from_name(name)
+                    .map_err(lookup_failed)?
+                    .ok_or_else(|| {
+                        SandboxError::PrivilegeDropFailed(format!("User {} does not exist", name))
+                    })?,
+            ),
+            None => None,
+        };
+
+        let group = match config.drop_to_group {
+            Some(ref name) => Some(
+                Group::from_name(name)
+                    .map_err(lookup_failed)?
+                    .ok_or_else(|| {
+                        SandboxError::PrivilegeDropFailed(format!("Group {} does not exist", name))
+                    })?
+                    .gid,
+            ),
+            None => user.as_ref().map(|user| user.gid),
+        };
+
+        if user.is_none() && group.is_none() {
+            return Ok(None);
+        }
+
+        Ok(Some(Self {
+            uid: user.map_or(geteuid(), |user| user.uid).as_raw(),
+            gid: group.unwrap_or(getegid()).as_raw(),
+        }))
+    }
+
+    /// Clear supplementary groups and switch group, then user
+    ///
+    /// Runs in the forked child, so only async-signal-safe calls are used.
+    pub(crate) fn apply(self) -> std::io::Result<()> {
+        unsafe {
+            if libc::setgroups(0, std::ptr::null()) != 0
+                || libc::setgid(self.gid) != 0
+                || libc::setuid(self.uid) != 0
+            {
+                return Err(std::io::Error::last_os_error());
+            }
+
+            // setuid from root is permanent; make sure of it
+            if self.uid != 0 && libc::setuid(0) == 0 {
+                return Err(std::io::Error::from_raw_os_error(libc::EPERM));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// External process jail used to wrap commands (Linux only)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SandboxBackend {
+    Nsjail,
+    Bubblewrap,
+    Firejail,
+    /// No external jail; only in-process limits (rlimit, cgroup, Landlock, seccomp)
+    None,
+}
+
+/// Auto-detected backend, probed once per process
+static DETECTED_BACKEND: Lazy<SandboxBackend> = Lazy::new(SandboxBackend::detect);
+
+impl SandboxBackend {
+    /// First working jail in order of preference: nsjail, bubblewrap, firejail
+    pub fn detect() -> Self {
+        [Self::Nsjail, Self::Bubblewrap, Self::Firejail]
+            .into_iter()
+            .find(|backend| backend.is_available())
+            .unwrap_or(Self::None)
+    }
+
+    /// Check whether this backend can run on the current system
+    pub fn is_available(&self) -> bool {
+        if !cfg!(target_os = "linux") {
+            return matches!(self, Self::None);
+        }
+
+        match self {
+            Self::Nsjail => Sandbox::is_nsjail_available(),
+            // bwrap is often installed but unusable without user namespaces
+            Self::Bubblewrap => probe("bwrap", &["--ro-bind", "/", "/", "--", "true"]),
+            Self::Firejail => probe("firejail", &["--version"]),
+            Self::None => true,
+        }
+    }
+
+    /// Backend name as used in configuration
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Nsjail => "nsjail",
+            Self::Bubblewrap => "bubblewrap",
+            Self::Firejail => "firejail",
+            Self::None => "none",
+        }
+    }
+}
+
+/// `filter` in a memory file, as bwrap's `--seccomp` reads it
+///
+/// The file is close-on-exec; the child that needs it clears the flag.
+#[cfg(target_os = "linux")]
+fn seccomp_file(filter: &seccompiler::BpfProgram) -> Result<std::fs::File, SandboxError> {
+    use std::io::{Seek, Write};
+    use std::os::unix::io::FromRawFd;
+
+    let failed = |e: std::io::Error| SandboxError::ConfigError(format!("seccomp filter: {}", e));
+
+    let fd = unsafe { libc::memfd_create(c"seccomp".as_ptr(), libc::MFD_CLOEXEC) };
+    if fd < 0 {
+        return Err(failed(std::io::Error::last_os_error()));
+    }
+    let mut file = unsafe { std::fs::File::from_raw_fd(fd) };
+
+    // struct sock_filter, in native byte order
+    let program: Vec<u8> = filter
+        .iter()
+        .flat_map(|insn| {
+            let mut bytes = insn.code.to_ne_bytes().to_vec();
+            bytes.extend([insn.jt, insn.jf]);
+            bytes.extend(insn.k.to_ne_bytes());
+            bytes
+        })
+        .collect();
+    file.write_all(&program)
+        .and_then(|()| file.rewind())
+        .map_err(failed)?;
+
+    Ok(file)
+}
+
+fn probe(program: &str, args: &[&str]) -> bool {
+    Command::new(program)
+        .args(args)
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false)
+}
+
+/// Resource usage read from a command's cgroup after it exits
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CgroupUsage {
+    /// Peak memory usage (`memory.peak`, kernel 5.19+)
+    pub peak_memory_bytes: Option<u64>,
+
+    /// Total CPU time (`cpu.stat` usage_usec)
+    pub cpu_usage_usec: Option<u64>,
+
+    /// Processes killed by the OOM killer (`memory.events` oom_kill)
+    pub oom_kills: Option<u64>,
+}
+
+/// CPU time and peak memory of a finished process
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ProcessUsage {
+    /// Peak resident set size (KiB)
+    pub max_rss_kb: Option<u64>,
+
+    /// Time spent in user mode (milliseconds)
+    pub user_cpu_ms: Option<u64>,
+
+    /// Time spent in the kernel (milliseconds)
+    pub sys_cpu_ms: Option<u64>,
+}
+
+/// Sandbox state tied to a child process
+///
+/// Owns the per-command cgroup on Linux and the Job Object on Windows. Dropping
+/// the guard kills anything still running in them and releases them, so keep it
+/// alive until the child exits.
+#[derive(Default)]
+pub struct SandboxGuard {
+    #[cfg(target_os = "linux")]
+    cgroup: Option<crate::cgroup::Cgroup>,
+
+    #[cfg(windows)]
+    job: Option<crate::job_object::JobObject>,
+
+    /// The seccomp filter handed to bwrap
+    #[cfg(target_os = "linux")]
+    seccomp: Option<std::fs::File>,
+}
+
+impl SandboxGuard {
+    /// Resource usage accounted by the command's cgroup, if one was used
+    pub fn cgroup_usage(&self) -> Option<CgroupUsage> {
+        #[cfg(target_os = "linux")]
+        {
+            self.cgroup.as_ref().map(|cgroup| cgroup.usage())
+        }
+
+        #[cfg(not(target_os = "linux"))]
+        {
+            None
+        }
+    }
+
+    /// Accounting of every process that ran in the command's Job Object
+    pub fn job_usage(&self) -> Option<ProcessUsage> {
+        #[cfg(windows)]
+        {
+            self.job.as_ref().and_then(|job| job.usage())
+        }
+
+        #[cfg(not(windows))]
+        {
+            None
+        }
+    }
+
+    /// Kill every process confined by this guard
+    ///
+    /// Only Job Objects are handled here; on Unix the executor kills the
+    /// command's process group instead.
+    pub fn terminate(&self) {
+        #[cfg(windows)]
+        if let Some(job) = &self.job {
+            if let Err(e) = job.terminate(1) {
+                tracing::warn!("Failed to terminate job object: {}", e);
+            }
         }
     }
 }
@@ -68,21 +345,95 @@ impl Sandbox {
         Self { config }
     }
 
+    /// Backend commands will be wrapped with
+    ///
+    /// An explicit `backend` wins, then `use_nsjail`, then auto-detection.
+    pub fn backend(&self) -> SandboxBackend {
+        match self.config.backend {
+            Some(backend) => backend,
+            None if self.config.use_nsjail => SandboxBackend::Nsjail,
+            None => *DETECTED_BACKEND,
+        }
+    }
+
+    /// The backend, or an error if it was configured but is not installed
+    fn usable_backend(&self) -> Result<SandboxBackend, SandboxError> {
+        let backend = self.backend();
+        let detected = self.config.backend.is_none() && !self.config.use_nsjail;
+
+        if detected || backend.is_available() {
+            Ok(backend)
+        } else {
+            Err(SandboxError::BackendUnavailable(backend.name().to_string()))
+        }
+    }
+
     /// Wrap command with sandbox
+    ///
+    /// Per-command restrictions (the seccomp profile and declared paths) come
+    /// from the command's whitelist entry and apply with every backend. A
+    /// configured jail that is not installed is an error rather than running
+    /// the command with less confinement. The returned guard must outlive the
+    /// child.
     pub fn wrap_command(
         &self,
         command: &str,
         args: &[String],
-    ) -> Result<TokioCommand, SandboxError> {
-        #[cfg(target_os = "linux")]
-        {
-            if self.config.use_nsjail && Self::is_nsjail_available() {
-                return self.wrap_with_nsjail(command, args);
+        entry: &WhitelistEntry,
+    ) -> Result<(TokioCommand, SandboxGuard), SandboxError> {
+        match self.usable_backend()? {
+            #[cfg(target_os = "linux")]
+            SandboxBackend::Nsjail => {
+                Ok((self.wrap_with_nsjail(command, args, entry)?, SandboxGuard::default()))
+            }
+            #[cfg(target_os = "linux")]
+            SandboxBackend::Bubblewrap => self.wrap_with_bwrap(command, args, entry),
+            #[cfg(target_os = "linux")]
+            SandboxBackend::Firejail => {
+                self.wrap_with_jail("firejail", self.firejail_args(command, args, entry), entry)
             }
+            // Fallback to basic sandboxing
+            _ => self.wrap_basic(command, args, entry),
         }
+    }
 
-        // Fallback to basic sandboxing
-        self.wrap_basic(command, args)
+    /// Exact argv `wrap_command` would run, without spawning or creating a cgroup
+    ///
+    /// bwrap reads its seccomp filter from a descriptor created at spawn,
+    /// shown here as `<fd>`.
+    pub fn argv(&self, command: &str, args: &[String], entry: &WhitelistEntry) -> Vec<String> {
+        let (jail, jail_args) = match self.backend() {
+            #[cfg(target_os = "linux")]
+            SandboxBackend::Nsjail => ("nsjail", self.nsjail_args(command, args, entry)),
+            #[cfg(target_os = "linux")]
+            SandboxBackend::Bubblewrap => {
+                let seccomp = Self::filters_syscalls(entry).then(|| "<fd>".to_string());
+                ("bwrap", self.bwrap_args(command, args, entry, seccomp))
+            }
+            #[cfg(target_os = "linux")]
+            SandboxBackend::Firejail => ("firejail", self.firejail_args(command, args, entry)),
+            _ => {
+                let _ = entry;
+                return std::iter::once(command.to_string())
+                    .chain(args.iter().cloned())
+                    .collect();
+            }
+        };
+
+        std::iter::once(jail.to_string()).chain(jail_args).collect()
+    }
+
+    /// Sandbox configuration
+    pub fn config(&self) -> &SandboxConfig {
+        &self.config
+    }
+
+    /// Sandbox configuration with the entry's overrides applied
+    pub fn config_for(&self, entry: &WhitelistEntry) -> SandboxConfig {
+        SandboxConfig {
+            max_memory_mb: entry.max_memory_mb.or(self.config.max_memory_mb),
+            ..self.config.clone()
+        }
     }
 
     /// Basic sandboxing (all platforms)
@@ -90,52 +441,324 @@ impl Sandbox {
         &self,
         command: &str,
         args: &[String],
-    ) -> Result<TokioCommand, SandboxError> {
+        entry: &WhitelistEntry,
+    ) -> Result<(TokioCommand, SandboxGuard), SandboxError> {
         let mut cmd = TokioCommand::new(command);
         cmd.args(args);
 
+        #[allow(unused_mut)]
+        let mut guard = SandboxGuard::default();
+
         #[cfg(unix)]
         {
             // Drop privileges on Unix systems
-            self.apply_unix_sandbox(&mut cmd)?;
+            self.apply_unix_sandbox(&mut cmd, entry, &mut guard, true)?;
         }
 
-        #[cfg(windows)]
-        {
-            // Windows-specific sandboxing
-            self.apply_windows_sandbox(&mut cmd)?;
+        #[cfg(not(unix))]
+        let _ = entry;
+
+        Ok((cmd, guard))
+    }
+
+    /// Run the command inside bubblewrap
+    ///
+    /// The entry's seccomp filter is compiled here and passed to bwrap as a
+    /// memory file (`--seccomp`), which installs it right before the exec.
+    #[cfg(target_os = "linux")]
+    fn wrap_with_bwrap(
+        &self,
+        command: &str,
+        args: &[String],
+        entry: &WhitelistEntry,
+    ) -> Result<(TokioCommand, SandboxGuard), SandboxError> {
+        use std::os::unix::io::AsRawFd;
+
+        let filter =
+            crate::seccomp::build_filter(entry.seccomp_profile, entry.network_allowed(), None)?;
+        let seccomp = filter.as_ref().map(seccomp_file).transpose()?;
+        let fd = seccomp.as_ref().map(|file| file.as_raw_fd());
+
+        let bwrap_args = self.bwrap_args(command, args, entry, fd.map(|fd| fd.to_string()));
+        let (mut cmd, mut guard) = self.wrap_with_jail("bwrap", bwrap_args, entry)?;
+
+        // Only this child inherits the filter
+        if let Some(fd) = fd {
+            unsafe {
+                cmd.pre_exec(move || {
+                    if libc::fcntl(fd, libc::F_SETFD, 0) != 0 {
+                        return Err(std::io::Error::last_os_error());
+                    }
+                    Ok(())
+                });
+            }
         }
 
-        Ok(cmd)
+        guard.seccomp = seccomp;
+        Ok((cmd, guard))
+    }
+
+    /// Whether `entry` gets a seccomp filter (see [`crate::seccomp::build_filter`])
+    #[cfg(target_os = "linux")]
+    fn filters_syscalls(entry: &WhitelistEntry) -> bool {
+        entry.seccomp_profile != crate::seccomp::SeccompProfile::Unrestricted
+            || !entry.network_allowed()
+    }
+
+    /// Run the command inside an external jail (bwrap or firejail)
+    ///
+    /// The jail provides namespaces, mounts and the seccomp filter. rlimits and
+    /// the cgroup are still applied to the jail process and inherited by the
+    /// command; Landlock and seccomp are not installed in-process, since they
+    /// would also restrict the jail's setup.
+    #[cfg(target_os = "linux")]
+    fn wrap_with_jail(
+        &self,
+        jail: &str,
+        jail_args: Vec<String>,
+        entry: &WhitelistEntry,
+    ) -> Result<(TokioCommand, SandboxGuard), SandboxError> {
+        let mut cmd = TokioCommand::new(jail);
+        cmd.args(&jail_args);
+
+        let mut guard = SandboxGuard::default();
+        self.apply_unix_sandbox(&mut cmd, entry, &mut guard, false)?;
+
+        Ok((cmd, guard))
+    }
+
+    /// Arguments for bubblewrap
+    ///
+    /// Mirrors the in-process semantics: when the entry declares paths, only the
+    /// system paths and those paths are mounted (read-only or read-write);
+    /// otherwise the whole filesystem is mounted read-only. Nothing else is
+    /// writable. Network access is removed unless the entry allows it, and the
+    /// seccomp filter is read from the descriptor `seccomp`.
+    #[cfg(target_os = "linux")]
+    fn bwrap_args(
+        &self,
+        command: &str,
+        args: &[String],
+        entry: &WhitelistEntry,
+        seccomp: Option<String>,
+    ) -> Vec<String> {
+        use crate::landlock_fs::{expand_path, SYSTEM_READ_PATHS, SYSTEM_WRITE_PATHS};
+
+        let mut bwrap: Vec<String> = vec![
+            "--die-with-parent".to_string(),
+            "--new-session".to_string(),
+            "--unshare-all".to_string(),
+            "--hostname".to_string(),
+            "sandbox".to_string(),
+        ];
+
+        if entry.network_allowed() {
+            bwrap.push("--share-net".to_string());
+        }
+
+        let mut bind = |flag: &str, path: String| {
+            bwrap.extend([flag.to_string(), path.clone(), path]);
+        };
+
+        if entry.allowed_paths.is_some() || entry.writable_paths.is_some() {
+            for path in SYSTEM_READ_PATHS {
+                bind("--ro-bind-try", path.to_string());
+            }
+            for path in entry.allowed_paths.iter().flatten() {
+                bind("--ro-bind-try", expand_path(path).to_string_lossy().to_string());
+            }
+            for path in entry.writable_paths.iter().flatten() {
+                bind("--bind-try", expand_path(path).to_string_lossy().to_string());
+            }
+        } else {
+            bind("--ro-bind", "/".to_string());
+        }
+
+        bwrap.extend(["--proc".to_string(), "/proc".to_string()]);
+        for path in SYSTEM_WRITE_PATHS {
+            bwrap.extend(["--dev-bind".to_string(), path.to_string(), path.to_string()]);
+        }
+
+        if let Some(fd) = seccomp {
+            bwrap.extend(["--seccomp".to_string(), fd]);
+        }
+
+        bwrap.push("--".to_string());
+        bwrap.push(command.to_string());
+        bwrap.extend_from_slice(args);
+
+        bwrap
+    }
+
+    /// Arguments for firejail, with the same path and network semantics as bwrap
+    ///
+    /// firejail's filters cannot check syscall arguments; the filesystem is
+    /// read-only anyway, so the `read_only` profile only adds a dropped
+    /// `execveat`.
+    #[cfg(target_os = "linux")]
+    fn firejail_args(&self, command: &str, args: &[String], entry: &WhitelistEntry) -> Vec<String> {
+        use crate::landlock_fs::expand_path;
+        use crate::seccomp::SeccompProfile;
+
+        let mut firejail: Vec<String> = vec![
+            "--quiet".to_string(),
+            "--noprofile".to_string(),
+            "--nonewprivs".to_string(),
+            "--private-dev".to_string(),
+            "--hostname=sandbox".to_string(),
+            "--read-only=/".to_string(),
+        ];
+
+        if !entry.network_allowed() {
+            firejail.push("--net=none".to_string());
+            firejail.push("--protocol=unix".to_string());
+        }
+
+        if entry.seccomp_profile == SeccompProfile::ReadOnly {
+            firejail.push("--seccomp.drop=execveat".to_string());
+        }
+
+        for path in entry.allowed_paths.iter().flatten() {
+            let path = expand_path(path).to_string_lossy().to_string();
+            firejail.push(format!("--whitelist={}", path));
+            firejail.push(format!("--read-only={}", path));
+        }
+        for path in entry.writable_paths.iter().flatten() {
+            let path = expand_path(path).to_string_lossy().to_string();
+            firejail.push(format!("--whitelist={}", path));
+            firejail.push(format!("--read-write={}", path));
+        }
+
+        if let Some(count) = self.config.max_processes {
+            firejail.push(format!("--rlimit-nproc={}", count));
+        }
+
+        firejail.push("--".to_string());
+        firejail.push(command.to_string());
+        firejail.extend_from_slice(args);
+
+        firejail
     }
 
     /// Apply Unix-specific sandbox settings
     #[cfg(unix)]
-    fn apply_unix_sandbox(&self, cmd: &mut TokioCommand) -> Result<(), SandboxError> {
-        // Drop privileges if requested
-        if let Some(ref username) = self.config.drop_to_user {
-            // Note: Actual privilege dropping requires running as root
-            // This is a placeholder for the concept
-            debug!("Would drop privileges to user: {}", username);
+    fn apply_unix_sandbox(
+        &self,
+        cmd: &mut TokioCommand,
+        entry: &WhitelistEntry,
+        guard: &mut SandboxGuard,
+        confine: bool,
+    ) -> Result<(), SandboxError> {
+        // Only root can switch user, so only root looks the target up (before fork)
+        let credentials = if nix::unistd::geteuid().is_root() {
+            Credentials::resolve(&self.config)?
+        } else {
+            if self.config.drop_to_user.is_some() || self.config.drop_to_group.is_some() {
+                debug!("Not running as root; commands keep the current user");
+            }
+            None
+        };
 
-            // In production, you would use:
-            // - nix::unistd::setuid()
-            // - nix::unistd::setgid()
-            // But this requires root privileges
+        // Give network-denied commands their own network namespace (only a
+        // loopback interface, down). Creating one needs root; otherwise seccomp
+        // still refuses network sockets where the profile allows.
+        #[cfg(target_os = "linux")]
+        let isolate_network = confine && Self::isolates_network(entry);
+        #[cfg(target_os = "linux")]
+        if confine && !entry.network_allowed() && !isolate_network {
+            debug!("Not running as root; no network namespace for {}", entry.command);
         }
 
         // Clone values to move into closure (avoid lifetime issues)
-        let max_cpu_time = self.config.max_cpu_time_secs;
-        let max_memory = self.config.max_memory_mb;
+        let config = self.config_for(entry);
+        let max_cpu_time = config.max_cpu_time_secs;
+        let max_memory = config.max_memory_mb;
+        let chroot_dir = config
+            .chroot_dir
+            .as_ref()
+            .map(|dir| {
+                std::ffi::CString::new(dir.as_str())
+                    .map_err(|e| SandboxError::ConfigError(format!("chroot_dir: {}", e)))
+            })
+            .transpose()?;
+
+        // Create the command's cgroup up front; the child joins it from pre_exec
+        #[cfg(target_os = "linux")]
+        let cgroup_procs = match config.cgroup_root {
+            Some(ref root) => {
+                let cgroup = crate::cgroup::Cgroup::create(std::path::Path::new(root), &config)?;
+                let procs = cgroup.procs_file()?;
+                guard.cgroup = Some(cgroup);
+                Some(procs)
+            }
+            None => None,
+        };
+
+        #[cfg(not(target_os = "linux"))]
+        let _ = guard;
+
+        // Compile the seccomp filter before fork; only installing it happens in the child
+        #[cfg(target_os = "linux")]
+        let seccomp_filter = if confine {
+            crate::seccomp::build_filter(
+                entry.seccomp_profile,
+                entry.network_allowed(),
+                Self::exec_path_ptr(cmd),
+            )?
+        } else {
+            None
+        };
+
+        // Open the Landlock path descriptors before fork as well
+        #[cfg(target_os = "linux")]
+        let mut landlock_ruleset = if confine
+            && (entry.allowed_paths.is_some() || entry.writable_paths.is_some())
+        {
+            crate::landlock_fs::build_ruleset(
+                entry.allowed_paths.as_deref().unwrap_or(&[]),
+                entry.writable_paths.as_deref().unwrap_or(&[]),
+            )?
+        } else {
+            None
+        };
+
+        #[cfg(not(target_os = "linux"))]
+        {
+            let _ = confine;
+
+            if entry.seccomp_profile != crate::seccomp::SeccompProfile::Unrestricted {
+                tracing::warn!(
+                    "seccomp profile {:?} ignored: not supported on this platform",
+                    entry.seccomp_profile
+                );
+            }
+
+            if entry.allowed_paths.is_some() || entry.writable_paths.is_some() {
+                tracing::warn!("Filesystem confinement ignored: Landlock requires Linux");
+            }
+        }
 
         // Set resource limits using libc
         unsafe {
             cmd.pre_exec(move || {
+                // Join the cgroup first so every limit below is accounted there
+                #[cfg(target_os = "linux")]
+                if let Some(ref procs) = cgroup_procs {
+                    use std::os::unix::io::AsRawFd;
+
+                    let written = libc::write(procs.as_raw_fd(), b"0".as_ptr().cast(), 1);
+                    if written != 1 {
+                        return Err(std::io::Error::last_os_error());
+                    }
+                }
+
                 // Set CPU time limit
                 if let Some(cpu_secs) = max_cpu_time {
+                    // SIGXCPU at the soft limit tells a CPU timeout apart
+                    // from other kills; SIGKILL follows a second later
                     let rlimit = libc::rlimit {
                         rlim_cur: cpu_secs,
-                        rlim_max: cpu_secs,
+                        rlim_max: cpu_secs + 1,
                     };
 
                     if libc::setrlimit(libc::RLIMIT_CPU, &rlimit) != 0 {
@@ -156,6 +779,38 @@ impl Sandbox {
                     }
                 }
 
+                #[cfg(target_os = "linux")]
+                if isolate_network && libc::unshare(libc::CLONE_NEWNET) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+
+                // chroot needs root, so it comes before the privilege drop
+                if let Some(ref dir) = chroot_dir {
+                    if libc::chroot(dir.as_ptr()) != 0 || libc::chdir(c"/".as_ptr()) != 0 {
+                        return Err(std::io::Error::last_os_error());
+                    }
+                }
+
+                // Drop privileges once the root-only setup above is done
+                if let Some(credentials) = credentials {
+                    credentials.apply()?;
+                }
+
+                #[cfg(target_os = "linux")]
+                if let Some(ruleset) = landlock_ruleset.take() {
+                    if ruleset.restrict_self().is_err() {
+                        return Err(std::io::Error::last_os_error());
+                    }
+                }
+
+                // Install the syscall filter last so the setup above is unaffected
+                #[cfg(target_os = "linux")]
+                if let Some(ref filter) = seccomp_filter {
+                    if seccompiler::apply_filter(filter).is_err() {
+                        return Err(std::io::Error::last_os_error());
+                    }
+                }
+
                 Ok(())
             });
         }
@@ -163,16 +818,59 @@ impl Sandbox {
         Ok(())
     }
 
-    /// Apply Windows-specific sandbox settings
-    #[cfg(windows)]
-    fn apply_windows_sandbox(&self, _cmd: &mut TokioCommand) -> Result<(), SandboxError> {
-        // Windows sandboxing would use:
-        // - Job Objects for resource limits
-        // - Restricted tokens for privilege reduction
-        // This is a placeholder
+    /// Whether the in-process backend runs `entry` in a new network namespace
+    #[cfg(target_os = "linux")]
+    pub fn isolates_network(entry: &WhitelistEntry) -> bool {
+        !entry.network_allowed() && nix::unistd::geteuid().is_root()
+    }
 
-        warn!("Windows sandboxing not fully implemented yet");
-        Ok(())
+    /// Address of the program path the child's initial `execve` will receive
+    ///
+    /// The command keeps the path as a C string and exposes the same buffer
+    /// through `get_program`. With a `/` in the path, `execvp` passes that
+    /// pointer straight to `execve`; without one it searches `PATH` and builds
+    /// a new string, so the address is unknown.
+    #[cfg(target_os = "linux")]
+    fn exec_path_ptr(cmd: &TokioCommand) -> Option<u64> {
+        use std::os::unix::ffi::OsStrExt;
+
+        let program = cmd.as_std().get_program().as_bytes();
+        if program.contains(&b'/') {
+            Some(program.as_ptr() as u64)
+        } else {
+            None
+        }
+    }
+
+    /// Apply sandbox limits that can only be set once the process exists
+    ///
+    /// On Windows the child is assigned to a Job Object enforcing memory, CPU
+    /// rate and process count limits, held by `guard`. Elsewhere limits are
+    /// applied before exec and this does nothing.
+    pub fn attach(
+        &self,
+        child: &TokioChild,
+        entry: &WhitelistEntry,
+        guard: &mut SandboxGuard,
+    ) -> Result<(), SandboxError> {
+        #[cfg(windows)]
+        {
+            let handle = child.raw_handle().ok_or_else(|| {
+                SandboxError::ResourceLimitFailed("Child process has already exited".to_string())
+            })?;
+
+            let job = crate::job_object::JobObject::from_config(&self.config_for(entry))?;
+            job.assign(handle as winapi::um::winnt::HANDLE)?;
+
+            guard.job = Some(job);
+            Ok(())
+        }
+
+        #[cfg(not(windows))]
+        {
+            let _ = (child, entry, guard);
+            Ok(())
+        }
     }
 
     /// Wrap command with nsjail (Linux only)
@@ -181,7 +879,23 @@ impl Sandbox {
         &self,
         command: &str,
         args: &[String],
+        entry: &WhitelistEntry,
     ) -> Result<TokioCommand, SandboxError> {
+        let mut cmd = TokioCommand::new("nsjail");
+        cmd.args(self.nsjail_args(command, args, entry));
+
+        Ok(cmd)
+    }
+
+    /// Arguments for nsjail
+    ///
+    /// Paths are mounted as with bwrap, and the seccomp profile is passed as
+    /// the equivalent Kafel policy.
+    #[cfg(target_os = "linux")]
+    fn nsjail_args(&self, command: &str, args: &[String], entry: &WhitelistEntry) -> Vec<String> {
+        use crate::landlock_fs::{expand_path, SYSTEM_READ_PATHS, SYSTEM_WRITE_PATHS};
+
+        let config = self.config_for(entry);
         let mut nsjail_args = vec![
             "--mode".to_string(),
             "o".to_string(), // Once mode
@@ -190,22 +904,69 @@ impl Sandbox {
             "--max_cpus".to_string(),
             "1".to_string(),
             "--time_limit".to_string(),
-            self.config
+            config
                 .max_cpu_time_secs
                 .unwrap_or(5)
                 .to_string(),
             "--rlimit_as".to_string(),
-            format!("{}", self.config.max_memory_mb.unwrap_or(512)),
-            "--".to_string(),
-            command.to_string(),
+            format!("{}", config.max_memory_mb.unwrap_or(512)),
         ];
 
-        nsjail_args.extend_from_slice(args);
+        if entry.network_allowed() {
+            nsjail_args.push("--disable_clone_newnet".to_string());
+        }
 
-        let mut cmd = TokioCommand::new("nsjail");
-        cmd.args(&nsjail_args);
+        let mut mount = |flag: &str, path: String| {
+            nsjail_args.extend([flag.to_string(), path]);
+        };
 
-        Ok(cmd)
+        if entry.allowed_paths.is_some() || entry.writable_paths.is_some() {
+            // nsjail fails on a missing mount source, unlike bwrap's --ro-bind-try
+            for path in SYSTEM_READ_PATHS {
+                if std::path::Path::new(path).exists() {
+                    mount("--bindmount_ro", path.to_string());
+                }
+            }
+            for path in entry.allowed_paths.iter().flatten() {
+                mount(
+                    "--bindmount_ro",
+                    expand_path(path).to_string_lossy().to_string(),
+                );
+            }
+            for path in entry.writable_paths.iter().flatten() {
+                mount(
+                    "--bindmount",
+                    expand_path(path).to_string_lossy().to_string(),
+                );
+            }
+            for path in SYSTEM_WRITE_PATHS {
+                mount("--bindmount", path.to_string());
+            }
+        } else {
+            // Mounted read-only without --rw
+            mount("--chroot", "/".to_string());
+        }
+
+        if let Some(policy) =
+            crate::seccomp::kafel_policy(entry.seccomp_profile, entry.network_allowed())
+        {
+            nsjail_args.extend(["--seccomp_string".to_string(), policy]);
+        }
+
+        // nsjail switches user itself, inside its namespaces
+        if let Some(ref user) = self.config.drop_to_user {
+            nsjail_args.extend(["--user".to_string(), user.clone()]);
+        }
+        if let Some(ref group) = self.config.drop_to_group {
+            nsjail_args.extend(["--group".to_string(), group.clone()]);
+        }
+
+        nsjail_args.push("--".to_string());
+        nsjail_args.push(command.to_string());
+
+        nsjail_args.extend_from_slice(args);
+
+        nsjail_args
     }
 
     /// Check if nsjail is available
@@ -266,12 +1027,202 @@ mod tests {
         assert_eq!(config.max_cpu_time_secs, Some(5));
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn test_credentials_resolve() {
+        let config = |user: Option<&str>, group: Option<&str>| SandboxConfig {
+            drop_to_user: user.map(str::to_string),
+            drop_to_group: group.map(str::to_string),
+            ..SandboxConfig::default()
+        };
+
+        assert_eq!(
+            Credentials::resolve(&config(Some("root"), None)).unwrap(),
+            Some(Credentials { uid: 0, gid: 0 })
+        );
+        assert_eq!(Credentials::resolve(&config(None, None)).unwrap(), None);
+        assert!(matches!(
+            Credentials::resolve(&config(Some("no-such-user-aetheros"), None)),
+            Err(SandboxError::PrivilegeDropFailed(_))
+        ));
+        assert!(matches!(
+            Credentials::resolve(&config(Some("root"), Some("no-such-group-aetheros"))),
+            Err(SandboxError::PrivilegeDropFailed(_))
+        ));
+    }
+
     #[test]
     fn test_sandbox_creation() {
         let config = SandboxConfig::default();
         let _sandbox = Sandbox::new(config);
     }
 
+    #[test]
+    fn test_sandbox_config_deserialize_without_job_limits() {
+        let json = r#"{
+            "drop_to_user": null,
+            "drop_to_group": null,
+            "max_memory_mb": 256,
+            "max_cpu_time_secs": 2,
+            "chroot_dir": null,
+            "use_nsjail": false
+        }"#;
+
+        let config: SandboxConfig = serde_json::from_str(json).unwrap();
+        assert_eq!(config.max_processes, None);
+        assert_eq!(config.cpu_rate_percent, None);
+    }
+
+    #[test]
+    fn test_backend_serde_names() {
+        let backend: SandboxBackend = serde_json::from_str("\"bubblewrap\"").unwrap();
+        assert_eq!(backend, SandboxBackend::Bubblewrap);
+        assert_eq!(SandboxBackend::None.name(), "none");
+        assert!(SandboxBackend::None.is_available());
+    }
+
+    #[test]
+    fn test_unavailable_backend_fails() {
+        let config = SandboxConfig {
+            backend: Some(SandboxBackend::Firejail),
+            ..Default::default()
+        };
+        let sandbox = Sandbox::new(config);
+        assert_eq!(sandbox.backend(), SandboxBackend::Firejail);
+
+        let result = sandbox.wrap_command("true", &[], &WhitelistEntry::default());
+        if !SandboxBackend::Firejail.is_available() {
+            assert!(matches!(result, Err(SandboxError::BackendUnavailable(_))));
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    fn entry(paths: Option<Vec<String>>, profile: crate::seccomp::SeccompProfile) -> WhitelistEntry {
+        WhitelistEntry {
+            command: "cat".to_string(),
+            allowed_paths: paths,
+            seccomp_profile: profile,
+            ..Default::default()
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_bwrap_args_mount_whole_fs_read_only_by_default() {
+        use crate::seccomp::SeccompProfile;
+
+        let sandbox = Sandbox::new(SandboxConfig::default());
+        let args = sandbox.bwrap_args(
+            "/bin/cat",
+            &["file.txt".to_string()],
+            &entry(None, SeccompProfile::Unrestricted),
+            None,
+        );
+
+        let joined = args.join(" ");
+        assert!(joined.contains("--ro-bind / /"));
+        assert!(joined.contains("--share-net"));
+        assert!(!joined.contains("--tmpfs"));
+        assert!(!joined.contains("--seccomp"));
+        assert!(joined.ends_with("-- /bin/cat file.txt"));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_bwrap_args_bind_declared_paths() {
+        use crate::seccomp::SeccompProfile;
+
+        let sandbox = Sandbox::new(SandboxConfig::default());
+        let entry = entry(
+            Some(vec!["/srv/data".to_string()]),
+            SeccompProfile::ReadOnly,
+        );
+        let args = sandbox.bwrap_args("/bin/cat", &[], &entry, Some("7".to_string()));
+
+        let joined = args.join(" ");
+        assert!(!joined.contains("--ro-bind / /"));
+        assert!(joined.contains("--ro-bind-try /srv/data /srv/data"));
+        assert!(joined.contains("--ro-bind-try /usr /usr"));
+        assert!(!joined.contains("--share-net"));
+        assert!(joined.contains("--seccomp 7 --"));
+
+        let argv = sandbox.argv("/bin/cat", &[], &entry).join(" ");
+        if sandbox.backend() == SandboxBackend::Bubblewrap {
+            assert!(argv.contains("--seccomp <fd>"));
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_firejail_args() {
+        use crate::seccomp::SeccompProfile;
+
+        let sandbox = Sandbox::new(SandboxConfig::default());
+        let args = sandbox.firejail_args(
+            "/bin/cat",
+            &[],
+            &entry(Some(vec!["/srv/data".to_string()]), SeccompProfile::NoNetwork),
+        );
+
+        assert!(args.contains(&"--net=none".to_string()));
+        assert!(args.contains(&"--protocol=unix".to_string()));
+        assert!(args.contains(&"--read-only=/".to_string()));
+        assert!(args.contains(&"--read-only=/srv/data".to_string()));
+        assert!(!args.contains(&"--private-tmp".to_string()));
+        assert!(args.contains(&"--rlimit-nproc=16".to_string()));
+        assert_eq!(args.last().unwrap(), "/bin/cat");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_entry_overrides() {
+        use crate::seccomp::SeccompProfile;
+
+        let sandbox = Sandbox::new(SandboxConfig::default());
+        let mut entry = entry(None, SeccompProfile::ReadOnly);
+        entry.allow_network = Some(true);
+        entry.max_memory_mb = Some(64);
+
+        let bwrap = sandbox.bwrap_args("/bin/cat", &[], &entry, None);
+        let firejail = sandbox.firejail_args("/bin/cat", &[], &entry);
+        let nsjail = sandbox.nsjail_args("/bin/cat", &[], &entry).join(" ");
+
+        assert!(bwrap.contains(&"--share-net".to_string()));
+        assert!(!firejail.contains(&"--net=none".to_string()));
+        assert!(nsjail.contains("--rlimit_as 64 --disable_clone_newnet"));
+        assert_eq!(sandbox.config_for(&entry).max_memory_mb, Some(64));
+        assert_eq!(sandbox.config().max_memory_mb, Some(512));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_nsjail_args_map_paths_and_profile() {
+        use crate::seccomp::SeccompProfile;
+
+        let sandbox = Sandbox::new(SandboxConfig::default());
+        let args = sandbox
+            .nsjail_args(
+                "/bin/cat",
+                &[],
+                &entry(
+                    Some(vec!["/srv/data".to_string()]),
+                    SeccompProfile::ReadOnly,
+                ),
+            )
+            .join(" ");
+        assert!(args.contains("--bindmount_ro /srv/data"));
+        assert!(args.contains("--bindmount_ro /usr"));
+        assert!(args.contains("--bindmount /dev/null"));
+        assert!(!args.contains("--chroot"));
+        assert!(args.contains("--seccomp_string ERRNO(1) {"));
+
+        let args = sandbox
+            .nsjail_args("/bin/cat", &[], &entry(None, SeccompProfile::Unrestricted))
+            .join(" ");
+        assert!(args.contains("--chroot / "));
+        assert!(!args.contains("--seccomp_string"));
+    }
+
     #[test]
     fn test_nsjail_detection() {
         // This will fail on systems without nsjail, which is expected
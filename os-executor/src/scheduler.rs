@@ -0,0 +1,717 @@
+//! Scheduled and deferred execution
+//!
+//! Lets the agent act on requests like "run a disk usage report every night".
+//! Jobs run on a cron schedule or once at a given time, are persisted to a
+//! JSON file so they survive restarts, and go through the executor like any
+//! other request, so the whitelist, policy, approval and sandbox all apply
+//! when they fire.
+
+use crate::executor::{CommandExecutor, CommandResult, ExecutorError};
+use chrono::{
+    DateTime, Datelike, Duration, Local, NaiveDate, NaiveDateTime, TimeZone, Timelike, Utc,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use thiserror::Error;
+use tokio::sync::{broadcast, Notify};
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+/// Longest the scheduler sleeps before re-reading the clock, so wall clock
+/// jumps (suspend, NTP) delay jobs by at most this much
+const MAX_SLEEP: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// Buffered run notifications per subscriber
+const RUN_CHANNEL_CAPACITY: usize = 32;
+
+/// Scheduler errors
+#[derive(Error, Debug)]
+pub enum SchedulerError {
+    #[error("Invalid schedule {expr:?}: {reason}")]
+    InvalidSchedule { expr: String, reason: String },
+
+    #[error("Schedule never fires: {0}")]
+    NeverFires(String),
+
+    #[error("Command rejected: {0}")]
+    Rejected(#[from] ExecutorError),
+
+    #[error("Unknown job: {0}")]
+    UnknownJob(String),
+
+    #[error("Schedule store I/O error on {}: {source}", .path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("Invalid schedule store: {0}")]
+    Store(#[from] serde_json::Error),
+}
+
+/// When a job runs
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Schedule {
+    /// Repeatedly, on a cron expression evaluated in local time
+    Cron(String),
+
+    /// Once, at the given time
+    At(DateTime<Utc>),
+}
+
+impl Schedule {
+    /// Repeating schedule; the expression is checked here
+    pub fn cron(expr: &str) -> Result<Self, SchedulerError> {
+        expr.parse::<CronExpr>()?;
+        Ok(Schedule::Cron(expr.trim().to_string()))
+    }
+
+    /// Run once, `delay` from now
+    pub fn after(delay: std::time::Duration) -> Self {
+        let at = Duration::from_std(delay)
+            .ok()
+            .and_then(|delay| Utc::now().checked_add_signed(delay));
+        Schedule::At(at.unwrap_or(DateTime::<Utc>::MAX_UTC))
+    }
+
+    /// First run strictly after `after`, or `None` if there is none
+    pub fn next_after(
+        &self,
+        after: DateTime<Utc>,
+    ) -> Result<Option<DateTime<Utc>>, SchedulerError> {
+        match self {
+            Schedule::Cron(expr) => {
+                let cron: CronExpr = expr.parse()?;
+                Ok(cron
+                    .next_after(&after.with_timezone(&Local))
+                    .map(|next| next.with_timezone(&Utc)))
+            }
+            Schedule::At(at) => Ok((*at > after).then_some(*at)),
+        }
+    }
+}
+
+/// Parsed five-field cron expression (`minute hour day month weekday`)
+///
+/// Fields accept `*`, numbers, ranges (`1-5`), lists (`1,15`) and steps
+/// (`*/15`, `0-30/10`); weekdays are 0-7 with 0 and 7 both Sunday. When both
+/// day and weekday are restricted, either may match, as in cron. The
+/// shorthands `@hourly`, `@daily`, `@weekly`, `@monthly` and `@yearly` are
+/// also accepted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CronExpr {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl FromStr for CronExpr {
+    type Err = SchedulerError;
+
+    fn from_str(expr: &str) -> Result<Self, Self::Err> {
+        let invalid = |reason: String| SchedulerError::InvalidSchedule {
+            expr: expr.to_string(),
+            reason,
+        };
+
+        let expanded = match expr.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            other => other,
+        };
+
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(invalid(format!("expected 5 fields, got {}", fields.len())));
+        };
+
+        let mut weekdays = parse_field(weekday, 0, 7).map_err(invalid)?;
+        // 7 is Sunday too
+        if weekdays & (1 << 7) != 0 {
+            weekdays = (weekdays & !(1 << 7)) | 1;
+        }
+
+        Ok(Self {
+            minutes: parse_field(minute, 0, 59).map_err(invalid)?,
+            hours: parse_field(hour, 0, 23).map_err(invalid)?,
+            days: parse_field(day, 1, 31).map_err(invalid)?,
+            months: parse_field(month, 1, 12).map_err(invalid)?,
+            weekdays,
+            any_day: day == "*",
+            any_weekday: weekday == "*",
+        })
+    }
+}
+
+impl CronExpr {
+    /// First matching minute strictly after `after`
+    ///
+    /// Local times skipped by a DST change never match. Gives up after five
+    /// years, so impossible dates like `0 0 31 2 *` return `None`.
+    pub fn next_after<Tz: TimeZone>(&self, after: &DateTime<Tz>) -> Option<DateTime<Tz>> {
+        let tz = after.timezone();
+        let start = after.naive_local().with_second(0)?.with_nanosecond(0)?;
+        let mut t = start + Duration::minutes(1);
+        let limit = start + Duration::days(5 * 366);
+
+        while t < limit {
+            if !has(self.months, t.month()) {
+                t = start_of_next_month(t)?;
+            } else if !self.day_matches(t.date()) {
+                t = t.date().succ_opt()?.and_hms_opt(0, 0, 0)?;
+            } else if !has(self.hours, t.hour()) {
+                t = t.date().and_hms_opt(t.hour(), 0, 0)? + Duration::hours(1);
+            } else if !has(self.minutes, t.minute()) {
+                t += Duration::minutes(1);
+            } else {
+                match tz.from_local_datetime(&t).earliest() {
+                    Some(next) if next > *after => return Some(next),
+                    _ => t += Duration::minutes(1),
+                }
+            }
+        }
+
+        None
+    }
+
+    fn day_matches(&self, date: NaiveDate) -> bool {
+        let day = has(self.days, date.day());
+        let weekday = has(self.weekdays, date.weekday().num_days_from_sunday());
+
+        match (self.any_day, self.any_weekday) {
+            (true, true) => true,
+            (true, false) => weekday,
+            (false, true) => day,
+            (false, false) => day || weekday,
+        }
+    }
+}
+
+fn has(mask: u64, value: u32) -> bool {
+    mask & (1 << value) != 0
+}
+
+fn start_of_next_month(t: NaiveDateTime) -> Option<NaiveDateTime> {
+    let (year, month) = match t.month() {
+        12 => (t.year() + 1, 1),
+        month => (t.year(), month + 1),
+    };
+    NaiveDate::from_ymd_opt(year, month, 1)?.and_hms_opt(0, 0, 0)
+}
+
+/// Bitmask of the values a cron field matches
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64, String> {
+    let number = |s: &str| {
+        s.parse::<u32>()
+            .map_err(|_| format!("{:?} is not a number", s))
+    };
+
+    let mut mask = 0;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, Some(number(step)?)),
+            None => (part, None),
+        };
+
+        let (lo, hi) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((lo, hi)) => (number(lo)?, number(hi)?),
+            // `5/10` means from 5 to the end in steps of 10
+            None if step.is_some() => (number(range)?, max),
+            None => (number(range)?, number(range)?),
+        };
+
+        if lo < min || hi > max || lo > hi {
+            return Err(format!("{:?} is outside {}-{}", part, min, max));
+        }
+
+        let step = step.unwrap_or(1);
+        if step == 0 {
+            return Err(format!("{:?} has a zero step", part));
+        }
+
+        for value in (lo..=hi).step_by(step as usize) {
+            mask |= 1 << value;
+        }
+    }
+
+    Ok(mask)
+}
+
+/// A scheduled command
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScheduledJob {
+    /// Job ID, passed to [`Scheduler::cancel`]
+    pub id: String,
+
+    /// Whitelisted command to run
+    pub command: String,
+
+    /// Command arguments
+    pub args: Vec<String>,
+
+    /// When the job runs
+    pub schedule: Schedule,
+
+    /// When the job was scheduled
+    pub created_at: DateTime<Utc>,
+
+    /// When the job runs next (`None` while a one-shot job is running)
+    pub next_run: Option<DateTime<Utc>>,
+
+    /// When the job last started
+    #[serde(default)]
+    pub last_run: Option<DateTime<Utc>>,
+
+    /// Exit code of the last run
+    #[serde(default)]
+    pub last_exit_code: Option<i32>,
+
+    /// Error of the last run, if it could not be executed
+    #[serde(default)]
+    pub last_error: Option<String>,
+}
+
+/// Outcome of one scheduled run, sent to [`Scheduler::subscribe`]rs
+#[derive(Debug, Clone)]
+pub struct JobRun {
+    /// Job that ran
+    pub job_id: String,
+
+    /// When the run started
+    pub started_at: DateTime<Utc>,
+
+    /// Command result, or why it could not be executed
+    pub result: Result<CommandResult, String>,
+}
+
+#[derive(Default)]
+struct SchedulerState {
+    jobs: Vec<ScheduledJob>,
+    running: HashSet<String>,
+}
+
+/// Runs commands on a schedule through an executor
+pub struct Scheduler {
+    executor: Arc<CommandExecutor>,
+    path: PathBuf,
+    state: Mutex<SchedulerState>,
+    changed: Notify,
+    runs: broadcast::Sender<JobRun>,
+    counter: AtomicU64,
+}
+
+impl Scheduler {
+    /// Load jobs persisted at `path` (created on first save)
+    ///
+    /// Runs missed while the process was down are not caught up: repeating
+    /// jobs resume at their next slot, and overdue one-shot jobs run as soon
+    /// as the scheduler starts.
+    pub fn open(
+        executor: Arc<CommandExecutor>,
+        path: impl Into<PathBuf>,
+    ) -> Result<Self, SchedulerError> {
+        let path = path.into();
+        let mut jobs: Vec<ScheduledJob> = match fs::read(&path) {
+            Ok(json) => serde_json::from_slice(&json)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(io_error(&path)(e)),
+        };
+
+        let now = Utc::now();
+        jobs.retain_mut(|job| match job.schedule {
+            Schedule::Cron(_) => {
+                job.next_run = job.schedule.next_after(now).ok().flatten();
+                job.next_run.is_some()
+            }
+            // A one-shot job without a next run was interrupted mid-run
+            Schedule::At(_) => job.next_run.is_some(),
+        });
+
+        info!(
+            "Loaded {} scheduled jobs from {}",
+            jobs.len(),
+            path.display()
+        );
+
+        let (runs, _) = broadcast::channel(RUN_CHANNEL_CAPACITY);
+        Ok(Self {
+            executor,
+            path,
+            state: Mutex::new(SchedulerState {
+                jobs,
+                running: HashSet::new(),
+            }),
+            changed: Notify::new(),
+            runs,
+            counter: AtomicU64::new(0),
+        })
+    }
+
+    /// Add a job
+    ///
+    /// The command is checked against the whitelist now; policy and approval
+    /// are checked each time it runs.
+    pub fn schedule(
+        &self,
+        command: &str,
+        args: &[String],
+        schedule: Schedule,
+    ) -> Result<ScheduledJob, SchedulerError> {
+        self.executor.plan(command, args)?;
+
+        let now = Utc::now();
+        let next_run = match schedule {
+            // An overdue one-shot job runs right away
+            Schedule::At(at) => Some(at),
+            Schedule::Cron(ref expr) => Some(
+                schedule
+                    .next_after(now)?
+                    .ok_or_else(|| SchedulerError::NeverFires(expr.clone()))?,
+            ),
+        };
+
+        let job = ScheduledJob {
+            id: self.next_id(now),
+            command: command.to_string(),
+            args: args.to_vec(),
+            schedule,
+            created_at: now,
+            next_run,
+            last_run: None,
+            last_exit_code: None,
+            last_error: None,
+        };
+
+        let mut state = self.lock();
+        state.jobs.push(job.clone());
+        self.save(&state.jobs)?;
+        drop(state);
+
+        info!("Scheduled {} as job {}", command, job.id);
+        self.changed.notify_one();
+        Ok(job)
+    }
+
+    /// Remove a job; a run already in progress is not stopped
+    pub fn cancel(&self, id: &str) -> Result<ScheduledJob, SchedulerError> {
+        let mut state = self.lock();
+        let index = state
+            .jobs
+            .iter()
+            .position(|job| job.id == id)
+            .ok_or_else(|| SchedulerError::UnknownJob(id.to_string()))?;
+
+        let job = state.jobs.remove(index);
+        self.save(&state.jobs)?;
+        drop(state);
+
+        info!("Cancelled job {}", id);
+        self.changed.notify_one();
+        Ok(job)
+    }
+
+    /// Scheduled jobs, soonest first
+    pub fn list(&self) -> Vec<ScheduledJob> {
+        let mut jobs = self.lock().jobs.clone();
+        jobs.sort_by_key(|job| (job.next_run.is_none(), job.next_run));
+        jobs
+    }
+
+    /// Receive the outcome of every run from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<JobRun> {
+        self.runs.subscribe()
+    }
+
+    /// Run due jobs in the background until the returned task is aborted
+    pub fn start(self: &Arc<Self>) -> JoinHandle<()> {
+        let scheduler = self.clone();
+        tokio::spawn(async move {
+            loop {
+                let now = Utc::now();
+                scheduler.run_due(now);
+
+                let sleep = scheduler
+                    .next_wakeup()
+                    .map(|next| (next - now).to_std().unwrap_or_default())
+                    .unwrap_or(MAX_SLEEP)
+                    .min(MAX_SLEEP);
+
+                tokio::select! {
+                    _ = tokio::time::sleep(sleep) => {}
+                    _ = scheduler.changed.notified() => {}
+                }
+            }
+        })
+    }
+
+    fn next_wakeup(&self) -> Option<DateTime<Utc>> {
+        self.lock().jobs.iter().filter_map(|job| job.next_run).min()
+    }
+
+    /// Start every job due at `now`
+    ///
+    /// A repeating job still running from its previous slot skips this one.
+    fn run_due(self: &Arc<Self>, now: DateTime<Utc>) -> Vec<JoinHandle<()>> {
+        let mut state = self.lock();
+        let mut due = Vec::new();
+
+        let SchedulerState { jobs, running } = &mut *state;
+        for job in jobs.iter_mut() {
+            if job.next_run.is_none_or(|next| next > now) {
+                continue;
+            }
+
+            // One-shot jobs have no next run
+            job.next_run = job.schedule.next_after(now).ok().flatten();
+
+            if !running.insert(job.id.clone()) {
+                debug!("Job {} is still running; skipping this run", job.id);
+                continue;
+            }
+
+            job.last_run = Some(now);
+            due.push(job.clone());
+        }
+
+        // Repeating jobs whose expression stopped matching
+        jobs.retain(|job| job.next_run.is_some() || running.contains(&job.id));
+
+        if due.is_empty() {
+            return Vec::new();
+        }
+
+        if let Err(e) = self.save(&state.jobs) {
+            warn!("Failed to save schedule: {}", e);
+        }
+        drop(state);
+
+        due.into_iter()
+            .map(|job| {
+                let scheduler = self.clone();
+                tokio::spawn(async move { scheduler.run_job(job, now).await })
+            })
+            .collect()
+    }
+
+    async fn run_job(&self, job: ScheduledJob, started_at: DateTime<Utc>) {
+        info!("Running scheduled job {}: {}", job.id, job.command);
+
+        let result = self
+            .executor
+            .execute(&job.command, &job.args)
+            .await
+            .map_err(|e| e.to_string());
+
+        {
+            let mut state = self.lock();
+            state.running.remove(&job.id);
+
+            if let Some(index) = state.jobs.iter().position(|j| j.id == job.id) {
+                let entry = &mut state.jobs[index];
+                entry.last_exit_code = result.as_ref().ok().map(|r| r.exit_code);
+                entry.last_error = result.as_ref().err().cloned();
+
+                if entry.next_run.is_none() {
+                    state.jobs.remove(index);
+                }
+            }
+
+            if let Err(e) = self.save(&state.jobs) {
+                warn!("Failed to save schedule: {}", e);
+            }
+        }
+
+        // No subscribers is fine
+        let _ = self.runs.send(JobRun {
+            job_id: job.id,
+            started_at,
+            result,
+        });
+    }
+
+    fn lock(&self) -> MutexGuard<'_, SchedulerState> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn next_id(&self, now: DateTime<Utc>) -> String {
+        format!(
+            "{}-{}",
+            now.format("%Y%m%dT%H%M%S"),
+            self.counter.fetch_add(1, Ordering::Relaxed)
+        )
+    }
+
+    /// Write the job list atomically
+    fn save(&self, jobs: &[ScheduledJob]) -> Result<(), SchedulerError> {
+        if let Some(parent) = self.path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent).map_err(io_error(parent))?;
+        }
+
+        let tmp = self.path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_vec_pretty(jobs)?).map_err(io_error(&tmp))?;
+        fs::rename(&tmp, &self.path).map_err(io_error(&self.path))
+    }
+}
+
+fn io_error(path: &Path) -> impl FnOnce(io::Error) -> SchedulerError + '_ {
+    move |source| SchedulerError::Io {
+        path: path.to_path_buf(),
+        source,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::ExecutorConfig;
+    use crate::whitelist::{CommandWhitelist, WhitelistEntry};
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_cron_next_after() {
+        let next = |expr: &str, after: &str| {
+            expr.parse::<CronExpr>()
+                .unwrap()
+                .next_after(&utc(after))
+                .map(|t| t.to_rfc3339())
+        };
+
+        assert_eq!(
+            next("0 2 * * *", "2026-03-10T02:00:00Z").as_deref(),
+            Some("2026-03-11T02:00:00+00:00")
+        );
+        assert_eq!(
+            next("*/15 * * * *", "2026-03-10T10:07:30Z").as_deref(),
+            Some("2026-03-10T10:15:00+00:00")
+        );
+        // 2026-03-14 is a Saturday; weekdays only
+        assert_eq!(
+            next("30 9 * * 1-5", "2026-03-14T00:00:00Z").as_deref(),
+            Some("2026-03-16T09:30:00+00:00")
+        );
+        assert_eq!(
+            next("@monthly", "2026-12-15T00:00:00Z").as_deref(),
+            Some("2027-01-01T00:00:00+00:00")
+        );
+        // Day or weekday: the 13th, or a Sunday (7)
+        assert_eq!(
+            next("0 0 13 * 7", "2026-03-10T00:00:00Z").as_deref(),
+            Some("2026-03-13T00:00:00+00:00")
+        );
+        assert_eq!(next("0 0 31 2 *", "2026-01-01T00:00:00Z"), None);
+
+        for invalid in [
+            "* * * *",
+            "60 * * * *",
+            "*/0 * * * *",
+            "a * * * *",
+            "5-1 * * * *",
+        ] {
+            assert!(matches!(
+                invalid.parse::<CronExpr>(),
+                Err(SchedulerError::InvalidSchedule { .. })
+            ));
+        }
+    }
+
+    #[cfg(unix)]
+    fn scheduler(path: &Path) -> Arc<Scheduler> {
+        let mut whitelist = CommandWhitelist::new();
+        whitelist.add_command(
+            "echo",
+            WhitelistEntry {
+                command: "echo".to_string(),
+                ..Default::default()
+            },
+        );
+        let config = ExecutorConfig {
+            enable_sandbox: false,
+            ..Default::default()
+        };
+
+        let executor = Arc::new(CommandExecutor::new(config, whitelist));
+        Arc::new(Scheduler::open(executor, path).unwrap())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_jobs_run_persist_and_cancel() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("schedule.json");
+        let scheduler = scheduler(&path);
+        let args = vec!["report".to_string()];
+
+        assert!(matches!(
+            scheduler.schedule("rm", &[], Schedule::after(std::time::Duration::ZERO)),
+            Err(SchedulerError::Rejected(_))
+        ));
+
+        let nightly = scheduler
+            .schedule("echo", &args, Schedule::cron("0 2 * * *").unwrap())
+            .unwrap();
+        let once = scheduler
+            .schedule(
+                "echo",
+                &args,
+                Schedule::At(Utc::now() - Duration::seconds(1)),
+            )
+            .unwrap();
+        assert_eq!(scheduler.list()[0].id, once.id);
+
+        // Jobs survive a restart
+        let scheduler = self::scheduler(&path);
+        assert_eq!(scheduler.list().len(), 2);
+
+        let mut runs = scheduler.subscribe();
+        for handle in scheduler.run_due(Utc::now()) {
+            handle.await.unwrap();
+        }
+
+        let run = runs.recv().await.unwrap();
+        assert_eq!(run.job_id, once.id);
+        assert_eq!(run.result.unwrap().stdout, "report\n");
+
+        // The one-shot job is gone; the nightly one is untouched
+        let jobs = scheduler.list();
+        assert_eq!(jobs.len(), 1);
+        assert_eq!(jobs[0].id, nightly.id);
+        assert_eq!(jobs[0].last_run, None);
+
+        // Run the nightly job as if its slot had come
+        let slot = jobs[0].next_run.unwrap();
+        for handle in scheduler.run_due(slot) {
+            handle.await.unwrap();
+        }
+        let job = &scheduler.list()[0];
+        assert_eq!((job.last_run, job.last_exit_code), (Some(slot), Some(0)));
+        assert!(job.next_run.unwrap() > slot);
+
+        scheduler.cancel(&nightly.id).unwrap();
+        assert!(matches!(
+            scheduler.cancel(&nightly.id),
+            Err(SchedulerError::UnknownJob(_))
+        ));
+        assert!(self::scheduler(&path).list().is_empty());
+    }
+}
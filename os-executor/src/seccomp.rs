@@ -0,0 +1,352 @@
+//! seccomp-bpf syscall filtering for sandboxed commands
+//!
+//! Each whitelist entry selects a [`SeccompProfile`]. The matching BPF program
+//! is compiled before spawn and installed in `pre_exec`, so it applies to the
+//! command from its first instruction. Filters are deny-lists: listed syscalls
+//! fail with `EPERM` and everything else is allowed.
+
+use serde::{Deserialize, Serialize};
+
+/// Syscall profile applied to a command (Linux only)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SeccompProfile {
+    /// No syscall filtering
+    #[default]
+    Unrestricted,
+
+    /// No network access: only Unix-domain sockets, no connect/bind/listen/accept
+    NoNetwork,
+
+    /// `NoNetwork`, plus no exec beyond the initial one and no filesystem writes
+    ReadOnly,
+}
+
+impl SeccompProfile {
+    /// Whether the profile permits network access on its own
+    ///
+    /// A whitelist entry's `allow_network` overrides this.
+    pub fn allows_network(self) -> bool {
+        self == SeccompProfile::Unrestricted
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub use linux::build_filter;
+
+/// The syscalls [`build_filter`] denies, as a Kafel policy for nsjail's
+/// `--seccomp_string`
+///
+/// nsjail installs the policy right before its own exec, so `execve` stays
+/// allowed and only `execveat` is denied, as when `build_filter` is not
+/// given the exec path. `None` when nothing is denied.
+#[cfg(target_os = "linux")]
+pub fn kafel_policy(profile: SeccompProfile, allow_network: bool) -> Option<String> {
+    let mut denied: Vec<String> = Vec::new();
+
+    if !allow_network {
+        denied.push(format!(
+            "socket(domain, type, protocol) {{ domain != {} }}",
+            libc::AF_UNIX
+        ));
+        denied.extend(["connect", "bind", "listen", "accept", "accept4"].map(str::to_string));
+    }
+
+    if profile == SeccompProfile::ReadOnly {
+        let write = libc::O_WRONLY | libc::O_RDWR | libc::O_CREAT | libc::O_TRUNC;
+        denied.push("execveat".to_string());
+        denied.push(format!(
+            "openat(dirfd, path, flags, mode) {{ (flags & {:#x}) != 0 }}",
+            write
+        ));
+        denied.extend(
+            [
+                "openat2",
+                "unlinkat",
+                "renameat",
+                "renameat2",
+                "mkdirat",
+                "mknodat",
+                "linkat",
+                "symlinkat",
+                "fchmod",
+                "fchmodat",
+                "fchown",
+                "fchownat",
+                "truncate",
+                "ftruncate",
+                "utimensat",
+            ]
+            .map(str::to_string),
+        );
+
+        // Legacy path-based syscalls that newer architectures no longer have
+        if cfg!(target_arch = "x86_64") {
+            denied.push(format!(
+                "open(path, flags, mode) {{ (flags & {:#x}) != 0 }}",
+                write
+            ));
+            denied.extend(
+                [
+                    "creat", "unlink", "rename", "mkdir", "rmdir", "mknod", "link", "symlink",
+                    "chmod", "chown", "lchown", "utime", "utimes",
+                ]
+                .map(str::to_string),
+            );
+        }
+    }
+
+    if denied.is_empty() {
+        return None;
+    }
+
+    Some(format!(
+        "ERRNO({}) {{ {} }} DEFAULT ALLOW",
+        libc::EPERM,
+        denied.join(", ")
+    ))
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use super::SeccompProfile;
+    use crate::sandbox::SandboxError;
+    use seccompiler::{
+        BpfProgram, SeccompAction, SeccompCmpArgLen, SeccompCmpOp, SeccompCondition, SeccompFilter,
+        SeccompRule,
+    };
+    use std::collections::BTreeMap;
+
+    type Rules = BTreeMap<i64, Vec<SeccompRule>>;
+
+    /// Compile the BPF program for a profile
+    ///
+    /// Network syscalls are denied unless `allow_network` is set, independently
+    /// of the profile. `exec_path` is the address of the program path that the initial `execve`
+    /// will receive. Under `ReadOnly`, `execve` is only allowed with exactly that
+    /// pointer, which blocks any exec the command attempts itself. When the
+    /// address is unknown the initial exec cannot be told apart, so `execve`
+    /// stays allowed and only `execveat` is blocked.
+    ///
+    /// Returns `None` when nothing is denied ([`SeccompProfile::Unrestricted`]
+    /// with network allowed).
+    pub fn build_filter(
+        profile: SeccompProfile,
+        allow_network: bool,
+        exec_path: Option<u64>,
+    ) -> Result<Option<BpfProgram>, SandboxError> {
+        let mut rules = Rules::new();
+
+        if !allow_network {
+            deny_network(&mut rules)?;
+        }
+
+        if profile == SeccompProfile::ReadOnly {
+            deny_exec(&mut rules, exec_path)?;
+            deny_writes(&mut rules)?;
+        }
+
+        if rules.is_empty() {
+            return Ok(None);
+        }
+
+        let filter = SeccompFilter::new(
+            rules,
+            SeccompAction::Allow,
+            SeccompAction::Errno(libc::EPERM as u32),
+            std::env::consts::ARCH.try_into().map_err(filter_error)?,
+        )
+        .map_err(filter_error)?;
+
+        let program: BpfProgram = filter.try_into().map_err(filter_error)?;
+        Ok(Some(program))
+    }
+
+    fn deny_network(rules: &mut Rules) -> Result<(), SandboxError> {
+        // Unix-domain sockets stay available (e.g. for nscd lookups)
+        rules.insert(
+            libc::SYS_socket,
+            vec![rule(vec![condition(
+                0,
+                SeccompCmpArgLen::Dword,
+                SeccompCmpOp::Ne,
+                libc::AF_UNIX as u64,
+            )?])?],
+        );
+
+        for syscall in [
+            libc::SYS_connect,
+            libc::SYS_bind,
+            libc::SYS_listen,
+            libc::SYS_accept,
+            libc::SYS_accept4,
+        ] {
+            rules.insert(syscall, vec![]);
+        }
+
+        Ok(())
+    }
+
+    fn deny_exec(rules: &mut Rules, exec_path: Option<u64>) -> Result<(), SandboxError> {
+        rules.insert(libc::SYS_execveat, vec![]);
+
+        if let Some(ptr) = exec_path {
+            rules.insert(
+                libc::SYS_execve,
+                vec![rule(vec![condition(
+                    0,
+                    SeccompCmpArgLen::Qword,
+                    SeccompCmpOp::Ne,
+                    ptr,
+                )?])?],
+            );
+        }
+
+        Ok(())
+    }
+
+    fn deny_writes(rules: &mut Rules) -> Result<(), SandboxError> {
+        rules.insert(libc::SYS_openat, open_for_write_rules(2)?);
+        rules.insert(libc::SYS_openat2, vec![]);
+
+        for syscall in [
+            libc::SYS_unlinkat,
+            libc::SYS_renameat,
+            libc::SYS_renameat2,
+            libc::SYS_mkdirat,
+            libc::SYS_mknodat,
+            libc::SYS_linkat,
+            libc::SYS_symlinkat,
+            libc::SYS_fchmod,
+            libc::SYS_fchmodat,
+            libc::SYS_fchown,
+            libc::SYS_fchownat,
+            libc::SYS_truncate,
+            libc::SYS_ftruncate,
+            libc::SYS_utimensat,
+        ] {
+            rules.insert(syscall, vec![]);
+        }
+
+        // Legacy path-based syscalls that newer architectures no longer have
+        #[cfg(target_arch = "x86_64")]
+        {
+            rules.insert(libc::SYS_open, open_for_write_rules(1)?);
+
+            for syscall in [
+                libc::SYS_creat,
+                libc::SYS_unlink,
+                libc::SYS_rename,
+                libc::SYS_mkdir,
+                libc::SYS_rmdir,
+                libc::SYS_mknod,
+                libc::SYS_link,
+                libc::SYS_symlink,
+                libc::SYS_chmod,
+                libc::SYS_chown,
+                libc::SYS_lchown,
+                libc::SYS_utime,
+                libc::SYS_utimes,
+            ] {
+                rules.insert(syscall, vec![]);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// One rule per write-implying open flag, so any of them matches
+    fn open_for_write_rules(flags_arg: u8) -> Result<Vec<SeccompRule>, SandboxError> {
+        [libc::O_WRONLY, libc::O_RDWR, libc::O_CREAT, libc::O_TRUNC]
+            .into_iter()
+            .map(|flag| {
+                rule(vec![condition(
+                    flags_arg,
+                    SeccompCmpArgLen::Dword,
+                    SeccompCmpOp::MaskedEq(flag as u64),
+                    flag as u64,
+                )?])
+            })
+            .collect()
+    }
+
+    fn condition(
+        arg: u8,
+        len: SeccompCmpArgLen,
+        op: SeccompCmpOp,
+        value: u64,
+    ) -> Result<SeccompCondition, SandboxError> {
+        SeccompCondition::new(arg, len, op, value).map_err(filter_error)
+    }
+
+    fn rule(conditions: Vec<SeccompCondition>) -> Result<SeccompRule, SandboxError> {
+        SeccompRule::new(conditions).map_err(filter_error)
+    }
+
+    fn filter_error(e: seccompiler::BackendError) -> SandboxError {
+        SandboxError::ConfigError(format!("Invalid seccomp filter: {}", e))
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unrestricted_has_no_filter() {
+        assert!(build_filter(SeccompProfile::Unrestricted, true, None)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_profiles_compile() {
+        let no_network = build_filter(SeccompProfile::NoNetwork, false, None)
+            .unwrap()
+            .unwrap();
+        let read_only = build_filter(SeccompProfile::ReadOnly, false, Some(0x1000))
+            .unwrap()
+            .unwrap();
+
+        assert!(!no_network.is_empty());
+        assert!(read_only.len() > no_network.len());
+    }
+
+    #[test]
+    fn test_network_override() {
+        // Unrestricted profile with network denied still gets a filter
+        let no_network = build_filter(SeccompProfile::Unrestricted, false, None)
+            .unwrap()
+            .unwrap();
+        let read_only_online = build_filter(SeccompProfile::ReadOnly, true, Some(0x1000))
+            .unwrap()
+            .unwrap();
+        let read_only = build_filter(SeccompProfile::ReadOnly, false, Some(0x1000))
+            .unwrap()
+            .unwrap();
+
+        assert!(!no_network.is_empty());
+        assert!(read_only_online.len() < read_only.len());
+    }
+
+    #[test]
+    fn test_kafel_policy() {
+        assert_eq!(kafel_policy(SeccompProfile::Unrestricted, true), None);
+
+        let no_network = kafel_policy(SeccompProfile::NoNetwork, false).unwrap();
+        assert!(no_network.starts_with("ERRNO(1) { socket(domain, type, protocol) { domain != 1 }"));
+        assert!(no_network.ends_with("accept4 } DEFAULT ALLOW"));
+
+        let read_only = kafel_policy(SeccompProfile::ReadOnly, true).unwrap();
+        assert!(!read_only.contains("socket"));
+        assert!(read_only
+            .contains("execveat, openat(dirfd, path, flags, mode) { (flags & 0x243) != 0 }"));
+    }
+
+    #[test]
+    fn test_profile_serde_names() {
+        let profile: SeccompProfile = serde_json::from_str("\"read_only\"").unwrap();
+        assert_eq!(profile, SeccompProfile::ReadOnly);
+        assert_eq!(SeccompProfile::default(), SeccompProfile::Unrestricted);
+    }
+}
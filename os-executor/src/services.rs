@@ -0,0 +1,127 @@
+//! Windows service status queries
+//!
+//! Backs the `list_services` builtin: enumerates Win32 services through the
+//! Service Control Manager with enumerate-only access, so nothing can be
+//! started, stopped or reconfigured.
+
+use std::io;
+use std::ptr;
+use winapi::shared::minwindef::DWORD;
+use winapi::shared::winerror::ERROR_MORE_DATA;
+use winapi::um::winnt::SERVICE_WIN32;
+use winapi::um::winsvc::{
+    CloseServiceHandle, EnumServicesStatusExW, OpenSCManagerW, ENUM_SERVICE_STATUS_PROCESSW,
+    SC_ENUM_PROCESS_INFO, SC_HANDLE, SC_MANAGER_ENUMERATE_SERVICE, SERVICE_CONTINUE_PENDING,
+    SERVICE_PAUSED, SERVICE_PAUSE_PENDING, SERVICE_RUNNING, SERVICE_START_PENDING,
+    SERVICE_STATE_ALL, SERVICE_STOPPED, SERVICE_STOP_PENDING,
+};
+
+/// A service and its current state
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServiceInfo {
+    /// Service key name, e.g. `OpenVPNService`
+    pub name: String,
+
+    /// Name shown in the Services console
+    pub display_name: String,
+
+    /// `running`, `stopped`, `paused` or a pending state
+    pub state: &'static str,
+}
+
+/// Service Control Manager handle, closed on drop
+struct Manager(SC_HANDLE);
+
+impl Drop for Manager {
+    fn drop(&mut self) {
+        unsafe { CloseServiceHandle(self.0) };
+    }
+}
+
+/// Every Win32 service, sorted by name
+pub fn list() -> io::Result<Vec<ServiceInfo>> {
+    let handle = unsafe { OpenSCManagerW(ptr::null(), ptr::null(), SC_MANAGER_ENUMERATE_SERVICE) };
+    if handle.is_null() {
+        return Err(io::Error::last_os_error());
+    }
+    let manager = Manager(handle);
+
+    let mut services = Vec::new();
+    let mut resume: DWORD = 0;
+    // u64 words keep the entries aligned
+    let mut buf: Vec<u64> = vec![0; 8 * 1024];
+
+    loop {
+        let mut needed: DWORD = 0;
+        let mut returned: DWORD = 0;
+        let ok = unsafe {
+            EnumServicesStatusExW(
+                manager.0,
+                SC_ENUM_PROCESS_INFO,
+                SERVICE_WIN32,
+                SERVICE_STATE_ALL,
+                buf.as_mut_ptr() as *mut u8,
+                (buf.len() * 8) as DWORD,
+                &mut needed,
+                &mut returned,
+                &mut resume,
+                ptr::null(),
+            )
+        };
+        let more = ok == 0;
+        if more {
+            let e = io::Error::last_os_error();
+            if e.raw_os_error() != Some(ERROR_MORE_DATA as i32) {
+                return Err(e);
+            }
+        }
+
+        let entries = unsafe {
+            std::slice::from_raw_parts(
+                buf.as_ptr() as *const ENUM_SERVICE_STATUS_PROCESSW,
+                returned as usize,
+            )
+        };
+        for entry in entries {
+            services.push(ServiceInfo {
+                name: unsafe { from_wide(entry.lpServiceName) },
+                display_name: unsafe { from_wide(entry.lpDisplayName) },
+                state: state_name(entry.ServiceStatusProcess.dwCurrentState),
+            });
+        }
+
+        if !more {
+            break;
+        }
+        // More services remain; `resume` continues where this call stopped
+        let needed = (needed as usize).div_ceil(8);
+        if needed > buf.len() {
+            buf.resize(needed, 0);
+        }
+    }
+
+    services.sort_by_key(|service| service.name.to_lowercase());
+    Ok(services)
+}
+
+fn state_name(state: DWORD) -> &'static str {
+    match state {
+        SERVICE_RUNNING => "running",
+        SERVICE_STOPPED => "stopped",
+        SERVICE_PAUSED => "paused",
+        SERVICE_START_PENDING => "starting",
+        SERVICE_STOP_PENDING => "stopping",
+        SERVICE_PAUSE_PENDING => "pausing",
+        SERVICE_CONTINUE_PENDING => "resuming",
+        _ => "unknown",
+    }
+}
+
+/// Read a NUL-terminated UTF-16 string
+unsafe fn from_wide(s: *const u16) -> String {
+    if s.is_null() {
+        return String::new();
+    }
+    let len = (0..).take_while(|&i| *s.add(i) != 0).count();
+    String::from_utf16_lossy(std::slice::from_raw_parts(s, len))
+}
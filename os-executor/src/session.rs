@@ -0,0 +1,631 @@
+//! Interactive sessions
+//!
+//! Some tools are only useful as a long-lived process fed input a line at a
+//! time, like `python3 -i`. [`SessionExecutor`] spawns whitelisted entries
+//! marked `interactive` in the same sandbox as one-shot commands and hands
+//! back a [`Session`] to write lines to and read output from. A supervisor
+//! task kills the process when it has been idle or alive too long, or when
+//! the session is closed or dropped.
+
+use crate::audit::OutputHasher;
+use crate::capability::CapabilityToken;
+use crate::executor::{kill_process_tree, CommandExecutor, ExecutorError};
+use crate::redact::Redactor;
+use crate::sandbox::SandboxGuard;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::process::{Child, ChildStdin};
+use tokio::sync::{mpsc, oneshot, watch};
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+use tracing::{debug, info};
+
+/// Output chunks buffered between the reader task and the session
+const OUTPUT_CHANNEL_CAPACITY: usize = 64;
+
+/// Session errors
+#[derive(Error, Debug)]
+pub enum SessionError {
+    #[error(transparent)]
+    Executor(#[from] ExecutorError),
+
+    #[error("No matching output within {0:?}")]
+    Timeout(Duration),
+
+    #[error("Unread session output exceeds {0} bytes")]
+    OutputLimit(usize),
+
+    #[error("Session input line exceeds {0} bytes")]
+    InputLimit(usize),
+
+    #[error("Session ended: {end}")]
+    Ended {
+        end: SessionEnd,
+        /// Output received after the last successful read
+        output: String,
+    },
+
+    #[error("Failed to write to session: {0}")]
+    Write(#[source] io::Error),
+}
+
+/// Why a session's process is no longer running
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionEnd {
+    /// The process exited on its own with this code
+    Exited(i32),
+
+    /// Killed after `idle_timeout_secs` without input or output
+    IdleTimeout,
+
+    /// Killed after `max_lifetime_secs`
+    LifetimeExceeded,
+
+    /// Killed when its unread output went over the output limit
+    OutputLimitExceeded,
+
+    /// Closed or dropped by the caller
+    Closed,
+}
+
+impl fmt::Display for SessionEnd {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SessionEnd::Exited(code) => write!(f, "exited with code {}", code),
+            SessionEnd::IdleTimeout => write!(f, "idle timeout"),
+            SessionEnd::LifetimeExceeded => write!(f, "maximum lifetime exceeded"),
+            SessionEnd::OutputLimitExceeded => write!(f, "output limit exceeded"),
+            SessionEnd::Closed => write!(f, "closed"),
+        }
+    }
+}
+
+/// Session lifetimes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionConfig {
+    /// Kill the process after this long without input or output
+    pub idle_timeout_secs: u64,
+
+    /// Kill the process this long after it started, however busy
+    pub max_lifetime_secs: u64,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {
+            idle_timeout_secs: 300,
+            max_lifetime_secs: 3600,
+        }
+    }
+}
+
+/// Starts interactive sessions through an executor
+#[derive(Clone)]
+pub struct SessionExecutor {
+    executor: Arc<CommandExecutor>,
+    config: SessionConfig,
+}
+
+impl SessionExecutor {
+    /// Create a session executor in front of `executor`
+    pub fn new(executor: Arc<CommandExecutor>, config: SessionConfig) -> Self {
+        Self { executor, config }
+    }
+
+    /// Spawn an interactive command
+    ///
+    /// The whitelist entry must set `interactive`. Validation, policy,
+    /// confirmation and the sandbox apply as for
+    /// [`execute`](CommandExecutor::execute); the entry's output limit caps
+    /// output that has been received but not yet read, and each line sent.
+    pub async fn open(&self, command: &str, args: &[String]) -> Result<Session, SessionError> {
+        self.open_as(command, args, None).await
+    }
+
+    /// Spawn an interactive command on behalf of the holder of `token`, as for
+    /// [`execute_with_capability`](CommandExecutor::execute_with_capability)
+    pub async fn open_with_capability(
+        &self,
+        command: &str,
+        args: &[String],
+        token: &CapabilityToken,
+    ) -> Result<Session, SessionError> {
+        self.open_as(command, args, Some(token)).await
+    }
+
+    async fn open_as(
+        &self,
+        command: &str,
+        args: &[String],
+        token: Option<&CapabilityToken>,
+    ) -> Result<Session, SessionError> {
+        let (mut child, guard, entry) = self.executor.spawn_session(command, args, token).await?;
+
+        let (stdin, stdout, stderr) =
+            match (child.stdin.take(), child.stdout.take(), child.stderr.take()) {
+                (Some(stdin), Some(stdout), Some(stderr)) => (stdin, stdout, stderr),
+                _ => {
+                    kill_process_tree(&mut child, guard.as_ref());
+                    return Err(ExecutorError::ExecutionFailed(
+                        "Failed to capture session pipes".to_string(),
+                    )
+                    .into());
+                }
+            };
+
+        info!("Opened session: {} with {} args", command, args.len());
+
+        let (output_tx, output_rx) = mpsc::channel(OUTPUT_CHANNEL_CAPACITY);
+        let reader = tokio::spawn(read_output(stdout, stderr, output_tx));
+
+        let (activity_tx, activity_rx) = watch::channel(Instant::now());
+        let (kill_tx, kill_rx) = oneshot::channel();
+        let (ended_tx, ended_rx) = watch::channel(None);
+
+        let supervisor = tokio::spawn(supervise(Supervised {
+            executor: self.executor.clone(),
+            command: command.to_string(),
+            args: args.to_vec(),
+            config: self.config.clone(),
+            child,
+            guard,
+            reader,
+            activity: activity_rx,
+            kill: kill_rx,
+            ended: ended_tx,
+        }));
+
+        Ok(Session {
+            stdin: Some(stdin),
+            output: output_rx,
+            pending: Vec::new(),
+            max_pending: self.executor.output_limit_for(&entry),
+            activity: activity_tx,
+            ended: ended_rx,
+            kill: Some(kill_tx),
+            supervisor: Some(supervisor),
+            redactor: self.executor.redactor().clone(),
+        })
+    }
+}
+
+/// A running interactive process
+///
+/// stdout and stderr are read as one stream, so prompts written to either
+/// are seen. Dropping the session kills the process.
+pub struct Session {
+    stdin: Option<ChildStdin>,
+    output: mpsc::Receiver<Vec<u8>>,
+    pending: Vec<u8>,
+    max_pending: usize,
+    activity: watch::Sender<Instant>,
+    ended: watch::Receiver<Option<SessionEnd>>,
+    /// Tells the supervisor why to kill the process; taken once it is told
+    kill: Option<oneshot::Sender<SessionEnd>>,
+    supervisor: Option<JoinHandle<()>>,
+    redactor: Redactor,
+}
+
+impl Session {
+    /// Write `line` and a newline to the process
+    ///
+    /// Fails with [`SessionError::InputLimit`] if the line is over the
+    /// output limit, or with [`SessionError::Ended`] if the process is gone,
+    /// including when it is killed while the write waits on a full pipe.
+    pub async fn send_line(&mut self, line: &str) -> Result<(), SessionError> {
+        let ended = *self.ended.borrow();
+        if let Some(end) = ended {
+            return Err(self.ended_error(end));
+        }
+        if self.kill.is_none() {
+            let end = self.wait_ended().await;
+            return Err(self.ended_error(end));
+        }
+
+        if line.len() >= self.max_pending {
+            return Err(SessionError::InputLimit(self.max_pending));
+        }
+
+        let stdin = self
+            .stdin
+            .as_mut()
+            .ok_or_else(|| SessionError::Write(io::ErrorKind::BrokenPipe.into()))?;
+
+        let mut input = Vec::with_capacity(line.len() + 1);
+        input.extend_from_slice(line.as_bytes());
+        input.push(b'\n');
+
+        let write = async {
+            stdin.write_all(&input).await?;
+            stdin.flush().await
+        };
+        let mut ended = self.ended.clone();
+        let killed = async move {
+            let end = ended.wait_for(|end| end.is_some()).await;
+            end.ok().and_then(|end| *end).unwrap_or(SessionEnd::Closed)
+        };
+
+        tokio::select! {
+            written = write => written.map_err(SessionError::Write)?,
+            end = killed => return Err(self.ended_error(end)),
+        }
+
+        self.touch();
+        Ok(())
+    }
+
+    /// Read output up to and including the first occurrence of `pattern`
+    ///
+    /// Output after the match stays buffered for the next read. Fails with
+    /// [`SessionError::Timeout`] if `pattern` has not appeared within
+    /// `timeout`, leaving what was read buffered, or with
+    /// [`SessionError::Ended`] if the process is gone.
+    ///
+    /// Fails with [`SessionError::OutputLimit`] if the unread output goes
+    /// over the limit first; the output is discarded and the process killed,
+    /// so later calls fail with [`SessionEnd::OutputLimitExceeded`].
+    pub async fn read_until(
+        &mut self,
+        pattern: &str,
+        timeout: Duration,
+    ) -> Result<String, SessionError> {
+        if self.kill.is_none() {
+            let end = self.wait_ended().await;
+            return Err(self.ended_error(end));
+        }
+
+        let deadline = tokio::time::sleep(timeout);
+        tokio::pin!(deadline);
+
+        loop {
+            if let Some(end) = find(&self.pending, pattern.as_bytes()) {
+                let rest = self.pending.split_off(end);
+                let matched = std::mem::replace(&mut self.pending, rest);
+                return Ok(self.redact(&matched));
+            }
+
+            if self.pending.len() > self.max_pending {
+                self.pending = Vec::new();
+                self.kill(SessionEnd::OutputLimitExceeded);
+                return Err(SessionError::OutputLimit(self.max_pending));
+            }
+
+            tokio::select! {
+                chunk = self.output.recv() => match chunk {
+                    Some(chunk) => {
+                        self.pending.extend_from_slice(&chunk);
+                        self.touch();
+                    }
+                    None => {
+                        let end = self.wait_ended().await;
+                        return Err(self.ended_error(end));
+                    }
+                },
+                _ = &mut deadline => return Err(SessionError::Timeout(timeout)),
+            }
+        }
+    }
+
+    /// How the process ended, if it has
+    pub fn ended(&self) -> Option<SessionEnd> {
+        *self.ended.borrow()
+    }
+
+    /// Close stdin, kill the process and wait for it to be cleaned up
+    ///
+    /// Returns how the session ended; [`SessionEnd::Closed`] unless it had
+    /// already ended for another reason.
+    pub async fn close(mut self) -> SessionEnd {
+        self.kill(SessionEnd::Closed);
+        if let Some(supervisor) = self.supervisor.take() {
+            let _ = supervisor.await;
+        }
+
+        self.ended().unwrap_or(SessionEnd::Closed)
+    }
+
+    /// Close stdin and have the supervisor kill the process for `reason`,
+    /// unless it has been told to already
+    fn kill(&mut self, reason: SessionEnd) {
+        self.stdin = None;
+        if let Some(kill) = self.kill.take() {
+            let _ = kill.send(reason);
+        }
+    }
+
+    fn touch(&self) {
+        self.activity.send_replace(Instant::now());
+    }
+
+    async fn wait_ended(&mut self) -> SessionEnd {
+        match self.ended.wait_for(|end| end.is_some()).await {
+            Ok(end) => end.unwrap_or(SessionEnd::Closed),
+            Err(_) => SessionEnd::Closed,
+        }
+    }
+
+    fn ended_error(&mut self, end: SessionEnd) -> SessionError {
+        let pending = std::mem::take(&mut self.pending);
+        SessionError::Ended {
+            end,
+            output: self.redact(&pending),
+        }
+    }
+
+    fn redact(&self, output: &[u8]) -> String {
+        let text = String::from_utf8_lossy(output);
+        self.redactor.redact(&text).into_owned()
+    }
+}
+
+/// End offset of the first occurrence of `pattern` in `haystack`
+fn find(haystack: &[u8], pattern: &[u8]) -> Option<usize> {
+    if pattern.is_empty() {
+        return Some(0);
+    }
+
+    haystack
+        .windows(pattern.len())
+        .position(|window| window == pattern)
+        .map(|start| start + pattern.len())
+}
+
+/// Forward stdout and stderr chunks until both close, hashing them for the
+/// audit log
+///
+/// Both streams are hashed as one, in arrival order, so nothing is buffered
+/// for the life of the session.
+async fn read_output<O, E>(mut stdout: O, mut stderr: E, tx: mpsc::Sender<Vec<u8>>) -> String
+where
+    O: AsyncRead + Unpin,
+    E: AsyncRead + Unpin,
+{
+    let mut hasher = OutputHasher::default();
+    let mut stdout_buf = [0u8; 4096];
+    let mut stderr_buf = [0u8; 4096];
+    let mut stdout_open = true;
+    let mut stderr_open = true;
+
+    while stdout_open || stderr_open {
+        let chunk = tokio::select! {
+            read = stdout.read(&mut stdout_buf), if stdout_open => match read {
+                Ok(0) | Err(_) => {
+                    stdout_open = false;
+                    continue;
+                }
+                Ok(n) => {
+                    hasher.stdout(&String::from_utf8_lossy(&stdout_buf[..n]));
+                    stdout_buf[..n].to_vec()
+                }
+            },
+            read = stderr.read(&mut stderr_buf), if stderr_open => match read {
+                Ok(0) | Err(_) => {
+                    stderr_open = false;
+                    continue;
+                }
+                Ok(n) => {
+                    hasher.stdout(&String::from_utf8_lossy(&stderr_buf[..n]));
+                    stderr_buf[..n].to_vec()
+                }
+            },
+        };
+
+        if tx.send(chunk).await.is_err() {
+            debug!("Session dropped; stopped reading output");
+            break;
+        }
+    }
+
+    hasher.finish()
+}
+
+struct Supervised {
+    executor: Arc<CommandExecutor>,
+    command: String,
+    args: Vec<String>,
+    config: SessionConfig,
+    child: Child,
+    guard: Option<SandboxGuard>,
+    reader: JoinHandle<String>,
+    activity: watch::Receiver<Instant>,
+    kill: oneshot::Receiver<SessionEnd>,
+    ended: watch::Sender<Option<SessionEnd>>,
+}
+
+/// Wait for the process to exit or for a reason to kill it, then clean up
+async fn supervise(mut s: Supervised) {
+    let started = Instant::now();
+    let idle_timeout = Duration::from_secs(s.config.idle_timeout_secs);
+    let lifetime = tokio::time::sleep(Duration::from_secs(s.config.max_lifetime_secs));
+    tokio::pin!(lifetime);
+
+    let end = loop {
+        let idle = tokio::time::sleep_until(*s.activity.borrow() + idle_timeout);
+
+        tokio::select! {
+            status = s.child.wait() => {
+                break SessionEnd::Exited(status.ok().and_then(|s| s.code()).unwrap_or(-1));
+            }
+            _ = idle => break SessionEnd::IdleTimeout,
+            _ = &mut lifetime => break SessionEnd::LifetimeExceeded,
+            // Closed, over its output limit, or the session was dropped
+            reason = &mut s.kill => break reason.unwrap_or(SessionEnd::Closed),
+            changed = s.activity.changed() => {
+                if changed.is_err() {
+                    break SessionEnd::Closed;
+                }
+            }
+        }
+    };
+
+    // No-op if the process already exited
+    kill_process_tree(&mut s.child, s.guard.as_ref());
+    let _ = s.child.wait().await;
+    let output_hash = s.reader.await.unwrap_or_default();
+    drop(s.guard);
+
+    info!("Session {} ended: {}", s.command, end);
+
+    let duration_ms = started.elapsed().as_millis() as u64;
+    let error;
+    let outcome = match end {
+        SessionEnd::Exited(code) => Ok((code, output_hash)),
+        SessionEnd::IdleTimeout => {
+            error = ExecutorError::TimeoutExceeded(s.config.idle_timeout_secs);
+            Err(&error)
+        }
+        SessionEnd::LifetimeExceeded => {
+            error = ExecutorError::TimeoutExceeded(s.config.max_lifetime_secs);
+            Err(&error)
+        }
+        SessionEnd::OutputLimitExceeded => {
+            error = ExecutorError::ResourceLimitExceeded("Session output limit".to_string());
+            Err(&error)
+        }
+        SessionEnd::Closed => {
+            error = ExecutorError::Cancelled;
+            Err(&error)
+        }
+    };
+    s.executor
+        .audit_session(&s.command, &s.args, outcome, duration_ms);
+
+    s.ended.send_replace(Some(end));
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::executor::ExecutorConfig;
+    use crate::whitelist::{CommandWhitelist, WhitelistEntry};
+
+    fn sessions(config: SessionConfig) -> SessionExecutor {
+        let mut whitelist = CommandWhitelist::new();
+        for (name, interactive) in [("cat", true), ("echo", true), ("ls", false)] {
+            whitelist.add_command(
+                name,
+                WhitelistEntry {
+                    command: name.to_string(),
+                    interactive,
+                    ..Default::default()
+                },
+            );
+        }
+
+        let executor = ExecutorConfig {
+            enable_sandbox: false,
+            ..Default::default()
+        };
+        SessionExecutor::new(Arc::new(CommandExecutor::new(executor, whitelist)), config)
+    }
+
+    #[tokio::test]
+    async fn test_send_and_read() {
+        let sessions = sessions(SessionConfig::default());
+        let wait = Duration::from_secs(5);
+
+        assert!(matches!(
+            sessions.open("ls", &[]).await,
+            Err(SessionError::Executor(ExecutorError::PermissionDenied(_)))
+        ));
+
+        let mut session = sessions.open("cat", &[]).await.unwrap();
+        session.send_line("first").await.unwrap();
+        session.send_line("second").await.unwrap();
+        assert_eq!(session.read_until("\n", wait).await.unwrap(), "first\n");
+        assert_eq!(session.read_until("ond\n", wait).await.unwrap(), "second\n");
+        assert!(matches!(
+            session.read_until("never", Duration::from_millis(50)).await,
+            Err(SessionError::Timeout(_))
+        ));
+        assert_eq!(session.close().await, SessionEnd::Closed);
+
+        let mut session = sessions.open("echo", &["bye".to_string()]).await.unwrap();
+        match session.read_until("never", wait).await {
+            Err(SessionError::Ended { end, output }) => {
+                assert_eq!(end, SessionEnd::Exited(0));
+                assert_eq!(output, "bye\n");
+            }
+            other => panic!("unexpected: {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_output_limit_closes_session() {
+        let mut whitelist = CommandWhitelist::new();
+        whitelist.add_command(
+            "cat",
+            WhitelistEntry {
+                command: "cat".to_string(),
+                interactive: true,
+                max_output_bytes: Some(8),
+                ..Default::default()
+            },
+        );
+        let executor = ExecutorConfig {
+            enable_sandbox: false,
+            ..Default::default()
+        };
+        let sessions = SessionExecutor::new(
+            Arc::new(CommandExecutor::new(executor, whitelist)),
+            SessionConfig::default(),
+        );
+        let wait = Duration::from_secs(5);
+
+        let mut session = sessions.open("cat", &[]).await.unwrap();
+        assert!(matches!(
+            session.send_line("far too long").await,
+            Err(SessionError::InputLimit(8))
+        ));
+
+        // 10 bytes echoed back without a match
+        session.send_line("1234").await.unwrap();
+        session.send_line("5678").await.unwrap();
+        assert!(matches!(
+            session.read_until("never", wait).await,
+            Err(SessionError::OutputLimit(8))
+        ));
+
+        // Killed, so every later call says why
+        assert!(matches!(
+            session.send_line("more").await,
+            Err(SessionError::Ended {
+                end: SessionEnd::OutputLimitExceeded,
+                ..
+            })
+        ));
+        assert!(matches!(
+            session.read_until("\n", wait).await,
+            Err(SessionError::Ended {
+                end: SessionEnd::OutputLimitExceeded,
+                ..
+            })
+        ));
+        assert_eq!(session.close().await, SessionEnd::OutputLimitExceeded);
+    }
+
+    #[tokio::test]
+    async fn test_idle_session_is_killed() {
+        let sessions = sessions(SessionConfig {
+            idle_timeout_secs: 1,
+            ..Default::default()
+        });
+
+        let mut session = sessions.open("cat", &[]).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+        assert_eq!(session.ended(), Some(SessionEnd::IdleTimeout));
+        assert!(matches!(
+            session.send_line("late").await,
+            Err(SessionError::Ended {
+                end: SessionEnd::IdleTimeout,
+                ..
+            })
+        ));
+    }
+}
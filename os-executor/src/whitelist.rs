@@ -1,8 +1,18 @@
 //! Command whitelist for allowed OS commands
 
+use crate::builtin::Builtin;
+use crate::platform::Platform;
+use crate::postprocess::PostProcess;
+use crate::powershell::PowerShellCmdlet;
+use crate::seccomp::SeccompProfile;
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
 use thiserror::Error;
+use tokio::sync::broadcast;
+use tracing::{info, warn};
 
 /// Whitelist errors
 #[derive(Error, Debug)]
@@ -15,10 +25,16 @@ pub enum WhitelistError {
 
     #[error("Invalid whitelist format: {0}")]
     InvalidFormat(String),
+
+    #[error("Invalid pattern for {command}: {reason}")]
+    InvalidPattern { command: String, reason: String },
+
+    #[error("Failed to watch whitelist: {0}")]
+    WatchFailed(String),
 }
 
 /// Whitelist entry for a command
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct WhitelistEntry {
     /// Command name
     pub command: String,
@@ -34,6 +50,131 @@ pub struct WhitelistEntry {
 
     /// Requires sudo/admin
     pub requires_sudo: bool,
+
+    /// Syscall filter applied when sandboxed (Linux only)
+    #[serde(default)]
+    pub seccomp_profile: SeccompProfile,
+
+    /// Paths the command may read when sandboxed (Landlock, Linux only).
+    /// `~` expands to the home directory. `None` leaves the filesystem unconfined.
+    #[serde(default)]
+    pub allowed_paths: Option<Vec<String>>,
+
+    /// Paths the command may read and write when sandboxed (Landlock, Linux only)
+    #[serde(default)]
+    pub writable_paths: Option<Vec<String>>,
+
+    /// Whether input may be piped to the command's stdin
+    #[serde(default)]
+    pub allow_stdin: bool,
+
+    /// Whether the command may run as a long-lived interactive session
+    /// (see [`SessionExecutor`](crate::SessionExecutor))
+    #[serde(default)]
+    pub interactive: bool,
+
+    /// Ask the executor's approval provider before each run
+    #[serde(default)]
+    pub requires_confirmation: bool,
+
+    /// Timeout override (seconds; defaults to `ExecutorConfig::max_timeout_secs`)
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+
+    /// Memory limit override (MB; defaults to `SandboxConfig::max_memory_mb`)
+    #[serde(default)]
+    pub max_memory_mb: Option<u64>,
+
+    /// Output limit override (bytes; defaults to `ExecutorConfig::max_output_bytes`)
+    #[serde(default)]
+    pub max_output_bytes: Option<usize>,
+
+    /// Network access override when sandboxed (defaults to what the seccomp
+    /// profile permits: only `unrestricted` allows network). Denied commands
+    /// run in their own network namespace on Linux.
+    #[serde(default)]
+    pub allow_network: Option<bool>,
+
+    /// Executable to run instead of `command` on specific platforms
+    #[serde(default)]
+    pub platforms: HashMap<Platform, PlatformExecutable>,
+
+    /// Run this cmdlet through constrained PowerShell instead of an executable
+    #[serde(default)]
+    pub powershell: Option<PowerShellCmdlet>,
+
+    /// Run this built-in command in-process instead of an executable
+    #[serde(default)]
+    pub builtin: Option<Builtin>,
+
+    /// Condense successful output into `CommandResult::summary`
+    #[serde(default)]
+    pub post_process: Option<PostProcess>,
+
+    /// Serve repeated identical runs from the executor's result cache
+    #[serde(default)]
+    pub cacheable: bool,
+
+    /// Cache TTL override (seconds; defaults to `CacheConfig::default_ttl_secs`)
+    #[serde(default)]
+    pub cache_ttl_secs: Option<u64>,
+    /// Expand `*`, `?` and `[...]` in arguments to matching paths inside the
+    /// allowed roots before validation (see [`globbing`](crate::globbing))
+    #[serde(default)]
+    pub expand_globs: bool,
+}
+
+/// Platform-specific replacement for a whitelisted command
+///
+/// The user's arguments are validated as usual and appended after `args`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PlatformExecutable {
+    /// Program to run (resolved through `PATH`)
+    pub program: String,
+
+    /// Arguments placed before the user's arguments
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+impl PlatformExecutable {
+    /// Whether the program is `cmd.exe`, which re-parses its command line
+    pub fn is_cmd(&self) -> bool {
+        std::path::Path::new(&self.program)
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .is_some_and(|stem| stem.eq_ignore_ascii_case("cmd"))
+    }
+}
+
+/// Run a `cmd.exe` builtin on Windows (AutoRun and delayed expansion disabled)
+fn windows_builtin(builtin: &[&str]) -> HashMap<Platform, PlatformExecutable> {
+    let args = ["/d", "/v:off", "/c"]
+        .iter()
+        .chain(builtin)
+        .map(|arg| arg.to_string())
+        .collect();
+
+    HashMap::from([(
+        Platform::Windows,
+        PlatformExecutable {
+            program: "cmd".to_string(),
+            args,
+        },
+    )])
+}
+
+impl WhitelistEntry {
+    /// Replacement executable for `platform`, if any
+    pub fn executable_for(&self, platform: Platform) -> Option<&PlatformExecutable> {
+        self.platforms.get(&platform)
+    }
+
+    /// Whether the sandboxed command may use the network
+    pub fn network_allowed(&self) -> bool {
+        self.allow_network
+            .unwrap_or_else(|| self.seccomp_profile.allows_network())
+    }
 }
 
 /// Command whitelist
@@ -72,6 +213,10 @@ impl CommandWhitelist {
                     r"^[a-zA-Z0-9\./_-]+$".to_string(), // Paths
                 ]),
                 requires_sudo: false,
+                seccomp_profile: SeccompProfile::ReadOnly,
+                platforms: windows_builtin(&["dir"]),
+                expand_globs: true,
+                ..Default::default()
             },
         );
 
@@ -84,6 +229,11 @@ impl CommandWhitelist {
                 max_args: Some(10),
                 allowed_arg_patterns: Some(vec![r"^[a-zA-Z0-9\./_-]+$".to_string()]),
                 requires_sudo: false,
+                seccomp_profile: SeccompProfile::ReadOnly,
+                allowed_paths: Some(vec!["~".to_string()]),
+                platforms: windows_builtin(&["type"]),
+                expand_globs: true,
+                ..Default::default()
             },
         );
 
@@ -99,6 +249,10 @@ impl CommandWhitelist {
                     r"^[a-zA-Z0-9\./_\-\s]+$".to_string(), // Patterns and paths
                 ]),
                 requires_sudo: false,
+                seccomp_profile: SeccompProfile::ReadOnly,
+                allowed_paths: Some(vec!["~".to_string()]),
+                allow_stdin: true,
+                ..Default::default()
             },
         );
 
@@ -114,6 +268,9 @@ impl CommandWhitelist {
                     r"^[a-zA-Z0-9\./_-]+$".to_string(),
                 ]),
                 requires_sudo: false,
+                seccomp_profile: SeccompProfile::ReadOnly,
+                expand_globs: true,
+                ..Default::default()
             },
         );
 
@@ -126,6 +283,9 @@ impl CommandWhitelist {
                 max_args: Some(0),
                 allowed_arg_patterns: None,
                 requires_sudo: false,
+                seccomp_profile: SeccompProfile::ReadOnly,
+                platforms: windows_builtin(&["cd"]),
+                ..Default::default()
             },
         );
 
@@ -141,6 +301,9 @@ impl CommandWhitelist {
                     r"^[a-zA-Z0-9\./_\-\*\?]+$".to_string(),
                 ]),
                 requires_sudo: false,
+                seccomp_profile: SeccompProfile::ReadOnly,
+                post_process: Some(PostProcess::Truncate { head: 10, tail: 0 }),
+                ..Default::default()
             },
         );
 
@@ -156,6 +319,11 @@ impl CommandWhitelist {
                     r"^[a-zA-Z0-9\./_-]+$".to_string(),
                 ]),
                 requires_sudo: false,
+                seccomp_profile: SeccompProfile::ReadOnly,
+                allowed_paths: Some(vec!["~".to_string()]),
+                allow_stdin: true,
+                expand_globs: true,
+                ..Default::default()
             },
         );
 
@@ -170,6 +338,11 @@ impl CommandWhitelist {
                     r"^[a-zA-Z0-9\./_-]+$".to_string(),
                 ]),
                 requires_sudo: false,
+                seccomp_profile: SeccompProfile::ReadOnly,
+                allowed_paths: Some(vec!["~".to_string()]),
+                allow_stdin: true,
+                expand_globs: true,
+                ..Default::default()
             },
         );
 
@@ -185,6 +358,11 @@ impl CommandWhitelist {
                     r"^[a-zA-Z0-9\./_-]+$".to_string(),
                 ]),
                 requires_sudo: false,
+                seccomp_profile: SeccompProfile::ReadOnly,
+                allowed_paths: Some(vec!["~".to_string()]),
+                allow_stdin: true,
+                expand_globs: true,
+                ..Default::default()
             },
         );
 
@@ -200,6 +378,10 @@ impl CommandWhitelist {
                     r"^[a-zA-Z0-9\./_-]+$".to_string(),
                 ]),
                 requires_sudo: false,
+                seccomp_profile: SeccompProfile::ReadOnly,
+                cacheable: true,
+                expand_globs: true,
+                ..Default::default()
             },
         );
 
@@ -212,6 +394,9 @@ impl CommandWhitelist {
                 max_args: Some(50),
                 allowed_arg_patterns: None, // Allow any args for echo
                 requires_sudo: false,
+                seccomp_profile: SeccompProfile::ReadOnly,
+                platforms: windows_builtin(&["echo"]),
+                ..Default::default()
             },
         );
 
@@ -224,12 +409,103 @@ impl CommandWhitelist {
                 max_args: Some(5),
                 allowed_arg_patterns: Some(vec![r"^[\+%a-zA-Z0-9\-:/ ]+$".to_string()]),
                 requires_sudo: false,
+                seccomp_profile: SeccompProfile::ReadOnly,
+                platforms: windows_builtin(&["date", "/t"]),
+                ..Default::default()
             },
         );
 
+        whitelist.add_builtin_commands();
+
+        if Platform::current().is_windows() {
+            whitelist.add_powershell_commands();
+            whitelist.add_windows_builtin_commands();
+        }
+
         whitelist
     }
 
+    /// Add the built-in file commands under their own names
+    ///
+    /// Part of the default whitelist on every platform.
+    pub fn add_builtin_commands(&mut self) {
+        for (builtin, description) in [
+            (Builtin::ListDir, "List directory contents"),
+            (Builtin::ReadFile, "Read file contents"),
+            (Builtin::FindFiles, "Find files by name"),
+            (Builtin::DiskUsage, "Show disk usage"),
+        ] {
+            self.add_command(
+                builtin.name(),
+                WhitelistEntry {
+                    command: builtin.name().to_string(),
+                    description: Some(description.to_string()),
+                    max_args: Some(10),
+                    seccomp_profile: SeccompProfile::ReadOnly,
+                    builtin: Some(builtin),
+                    post_process: (builtin == Builtin::FindFiles)
+                        .then_some(PostProcess::Truncate { head: 10, tail: 0 }),
+                    cacheable: builtin == Builtin::DiskUsage,
+                    ..Default::default()
+                },
+            );
+        }
+    }
+
+    /// Add the registry and service query builtins
+    ///
+    /// Part of the default whitelist on Windows.
+    pub fn add_windows_builtin_commands(&mut self) {
+        for (builtin, description) in [
+            (Builtin::RegQuery, "Read registry keys"),
+            (Builtin::ListServices, "List services and their state"),
+        ] {
+            self.add_command(
+                builtin.name(),
+                WhitelistEntry {
+                    command: builtin.name().to_string(),
+                    description: Some(description.to_string()),
+                    max_args: Some(2),
+                    seccomp_profile: SeccompProfile::ReadOnly,
+                    builtin: Some(builtin),
+                    post_process: (builtin == Builtin::ListServices)
+                        .then_some(PostProcess::Truncate { head: 10, tail: 0 }),
+                    ..Default::default()
+                },
+            );
+        }
+    }
+
+    /// Add read-only PowerShell cmdlets for basic file queries
+    ///
+    /// Part of the default whitelist on Windows.
+    pub fn add_powershell_commands(&mut self) {
+        // (cmdlet, description, parameters, switches)
+        let cmdlets: [(&str, &str, &[&str], &[&str]); 7] = [
+            ("Get-ChildItem", "List directory contents", &["Path", "Filter"], &["Recurse"]),
+            ("Get-Content", "Read file contents", &["Path", "TotalCount", "Tail"], &[]),
+            ("Get-Item", "File information", &["Path"], &[]),
+            ("Get-Location", "Print working directory", &[], &[]),
+            ("Get-Date", "Show date/time", &["Format"], &[]),
+            ("Select-String", "Search text", &["Path", "Pattern"], &["SimpleMatch"]),
+            ("Test-Path", "Check whether a path exists", &["Path"], &[]),
+        ];
+
+        for (cmdlet, description, parameters, switches) in cmdlets {
+            self.add_command(
+                cmdlet,
+                WhitelistEntry {
+                    command: cmdlet.to_string(),
+                    description: Some(description.to_string()),
+                    max_args: Some(10),
+                    seccomp_profile: SeccompProfile::ReadOnly,
+                    powershell: Some(PowerShellCmdlet::new(cmdlet, parameters, switches)),
+                    ..Default::default()
+                },
+            );
+        }
+    }
+
     /// Add command to whitelist
     pub fn add_command(&mut self, name: &str, entry: WhitelistEntry) {
         self.entries.insert(name.to_string(), entry);
@@ -283,6 +559,47 @@ impl CommandWhitelist {
             .map_err(|e| WhitelistError::InvalidFormat(e.to_string()))
     }
 
+    /// Load from a file, as JSON for `.json` files and YAML otherwise
+    ///
+    /// Argument patterns are compiled so a bad regex fails the load instead of
+    /// silently never matching.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, WhitelistError> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| WhitelistError::LoadFailed(format!("{}: {}", path.display(), e)))?;
+
+        let whitelist = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => Self::from_json(&contents)?,
+            _ => Self::from_yaml(&contents)?,
+        };
+        whitelist.validate()?;
+
+        Ok(whitelist)
+    }
+
+    /// Check that every argument pattern is a valid regex
+    pub fn validate(&self) -> Result<(), WhitelistError> {
+        for (name, entry) in &self.entries {
+            for pattern in entry.allowed_arg_patterns.iter().flatten() {
+                regex::Regex::new(pattern).map_err(|e| WhitelistError::InvalidPattern {
+                    command: name.clone(),
+                    reason: e.to_string(),
+                })?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Load the whitelist at `path` and reload it whenever the file changes
+    ///
+    /// The parent directory is watched so editors that replace the file are
+    /// picked up. A reload that fails to parse or validate is logged and the
+    /// previous whitelist stays in effect.
+    pub fn watch(path: impl AsRef<Path>) -> Result<WhitelistWatcher, WhitelistError> {
+        WhitelistWatcher::start(path.as_ref())
+    }
+
     /// Get number of whitelisted commands
     pub fn len(&self) -> usize {
         self.entries.len()
@@ -294,6 +611,198 @@ impl CommandWhitelist {
     }
 }
 
+/// Whitelist handle shared between executors and a [`WhitelistWatcher`]
+///
+/// Readers take a snapshot per request; reloads swap in a new whitelist
+/// without affecting requests already validated against the old one.
+#[derive(Debug, Clone)]
+pub struct SharedWhitelist {
+    current: Arc<RwLock<Arc<CommandWhitelist>>>,
+}
+
+impl SharedWhitelist {
+    /// Share a whitelist
+    pub fn new(whitelist: CommandWhitelist) -> Self {
+        Self {
+            current: Arc::new(RwLock::new(Arc::new(whitelist))),
+        }
+    }
+
+    /// Current whitelist
+    pub fn snapshot(&self) -> Arc<CommandWhitelist> {
+        self.current
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+
+    /// Replace the whitelist, returning the previous one
+    pub fn replace(&self, whitelist: CommandWhitelist) -> Arc<CommandWhitelist> {
+        let mut current = self
+            .current
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        std::mem::replace(&mut *current, Arc::new(whitelist))
+    }
+}
+
+impl From<CommandWhitelist> for SharedWhitelist {
+    fn from(whitelist: CommandWhitelist) -> Self {
+        Self::new(whitelist)
+    }
+}
+
+/// Commands that changed in a whitelist reload
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WhitelistChanged {
+    /// Newly whitelisted commands
+    pub added: Vec<String>,
+
+    /// Commands no longer whitelisted
+    pub removed: Vec<String>,
+
+    /// Commands whose entries changed
+    pub modified: Vec<String>,
+}
+
+impl WhitelistChanged {
+    fn between(old: &CommandWhitelist, new: &CommandWhitelist) -> Self {
+        let mut changed = Self::default();
+
+        for (name, entry) in &new.entries {
+            match old.entries.get(name) {
+                None => changed.added.push(name.clone()),
+                Some(previous) if previous != entry => changed.modified.push(name.clone()),
+                Some(_) => {}
+            }
+        }
+
+        changed.removed = old
+            .entries
+            .keys()
+            .filter(|name| !new.entries.contains_key(*name))
+            .cloned()
+            .collect();
+
+        changed.added.sort();
+        changed.removed.sort();
+        changed.modified.sort();
+        changed
+    }
+
+    /// Whether nothing changed
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty()
+    }
+}
+
+/// Keeps a [`SharedWhitelist`] in sync with a file
+///
+/// Dropping the watcher stops reloading; the shared whitelist keeps its last
+/// contents.
+pub struct WhitelistWatcher {
+    path: PathBuf,
+    whitelist: SharedWhitelist,
+    events: broadcast::Sender<WhitelistChanged>,
+    _watcher: RecommendedWatcher,
+}
+
+/// Reload notifications buffered per subscriber
+const WHITELIST_EVENT_CAPACITY: usize = 16;
+
+impl WhitelistWatcher {
+    fn start(path: &Path) -> Result<Self, WhitelistError> {
+        let whitelist = SharedWhitelist::new(CommandWhitelist::load(path)?);
+        let (events, _) = broadcast::channel(WHITELIST_EVENT_CAPACITY);
+
+        let path = path.to_path_buf();
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+
+        let handler = {
+            let path = path.clone();
+            let whitelist = whitelist.clone();
+            let events = events.clone();
+
+            move |event: notify::Result<notify::Event>| match event {
+                Ok(event) if event.paths.iter().any(|p| p.file_name() == path.file_name()) => {
+                    if event.kind.is_modify() || event.kind.is_create() {
+                        Self::reload_into(&path, &whitelist, &events);
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => warn!("Whitelist watch error: {}", e),
+            }
+        };
+
+        let mut watcher = notify::recommended_watcher(handler)
+            .map_err(|e| WhitelistError::WatchFailed(e.to_string()))?;
+        watcher
+            .watch(&dir, RecursiveMode::NonRecursive)
+            .map_err(|e| WhitelistError::WatchFailed(e.to_string()))?;
+
+        info!("Watching whitelist {}", path.display());
+
+        Ok(Self {
+            path,
+            whitelist,
+            events,
+            _watcher: watcher,
+        })
+    }
+
+    /// Shared whitelist kept up to date by this watcher
+    pub fn whitelist(&self) -> SharedWhitelist {
+        self.whitelist.clone()
+    }
+
+    /// Receive a [`WhitelistChanged`] event after each reload that changed
+    /// something
+    pub fn subscribe(&self) -> broadcast::Receiver<WhitelistChanged> {
+        self.events.subscribe()
+    }
+
+    /// Reload the file now
+    pub fn reload(&self) -> Result<WhitelistChanged, WhitelistError> {
+        Self::swap(&self.path, &self.whitelist, &self.events)
+    }
+
+    fn reload_into(
+        path: &Path,
+        whitelist: &SharedWhitelist,
+        events: &broadcast::Sender<WhitelistChanged>,
+    ) {
+        if let Err(e) = Self::swap(path, whitelist, events) {
+            warn!("Keeping previous whitelist: {}", e);
+        }
+    }
+
+    fn swap(
+        path: &Path,
+        whitelist: &SharedWhitelist,
+        events: &broadcast::Sender<WhitelistChanged>,
+    ) -> Result<WhitelistChanged, WhitelistError> {
+        let loaded = CommandWhitelist::load(path)?;
+        let changed = WhitelistChanged::between(&whitelist.snapshot(), &loaded);
+
+        if !changed.is_empty() {
+            whitelist.replace(loaded);
+            info!(
+                "Reloaded whitelist: {} added, {} removed, {} modified",
+                changed.added.len(),
+                changed.removed.len(),
+                changed.modified.len()
+            );
+            // No subscribers is fine
+            let _ = events.send(changed.clone());
+        }
+
+        Ok(changed)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -321,6 +830,8 @@ mod tests {
                 max_args: None,
                 allowed_arg_patterns: None,
                 requires_sudo: false,
+                seccomp_profile: SeccompProfile::Unrestricted,
+                ..Default::default()
             },
         );
 
@@ -352,4 +863,95 @@ mod tests {
         assert!(commands.contains(&"echo".to_string()));
         assert!(commands.len() > 5);
     }
+
+    #[test]
+    fn test_platform_executables() {
+        let whitelist = CommandWhitelist::default();
+        let cat = whitelist.get("cat").unwrap();
+
+        let windows = cat.executable_for(Platform::Windows).unwrap();
+        assert!(windows.is_cmd());
+        assert_eq!(windows.args, ["/d", "/v:off", "/c", "type"]);
+        assert!(cat.executable_for(Platform::Linux).is_none());
+
+        let yaml = whitelist.to_yaml().unwrap();
+        let restored = CommandWhitelist::from_yaml(&yaml).unwrap();
+        assert_eq!(restored.get("cat"), Some(cat));
+    }
+
+    #[test]
+    fn test_load_rejects_invalid_patterns() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("whitelist.yaml");
+        std::fs::write(
+            &path,
+            "ls:\n  command: ls\n  allowed_arg_patterns: ['(']\n  requires_sudo: false\n",
+        )
+        .unwrap();
+
+        assert!(matches!(
+            CommandWhitelist::load(&path),
+            Err(WhitelistError::InvalidPattern { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_watch_reloads_on_change() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("whitelist.json");
+
+        let mut whitelist = CommandWhitelist::new();
+        for name in ["ls", "cat"] {
+            whitelist.add_command(
+                name,
+                WhitelistEntry {
+                    command: name.to_string(),
+                    ..Default::default()
+                },
+            );
+        }
+        std::fs::write(&path, whitelist.to_json().unwrap()).unwrap();
+
+        let watcher = CommandWhitelist::watch(&path).unwrap();
+        let shared = watcher.whitelist();
+        let mut events = watcher.subscribe();
+        assert!(shared.snapshot().is_whitelisted("cat"));
+
+        whitelist.remove_command("cat");
+        whitelist.add_command(
+            "echo",
+            WhitelistEntry {
+                command: "echo".to_string(),
+                ..Default::default()
+            },
+        );
+        whitelist.add_command(
+            "ls",
+            WhitelistEntry {
+                command: "ls".to_string(),
+                max_args: Some(2),
+                ..Default::default()
+            },
+        );
+        std::fs::write(&path, whitelist.to_json().unwrap()).unwrap();
+
+        let changed = tokio::time::timeout(std::time::Duration::from_secs(5), events.recv())
+            .await
+            .expect("no reload event")
+            .unwrap();
+        assert_eq!(
+            changed,
+            WhitelistChanged {
+                added: vec!["echo".to_string()],
+                removed: vec!["cat".to_string()],
+                modified: vec!["ls".to_string()],
+            }
+        );
+        assert!(!shared.snapshot().is_whitelisted("cat"));
+
+        // A broken file keeps the previous whitelist
+        std::fs::write(&path, "{ not json").unwrap();
+        assert!(watcher.reload().is_err());
+        assert!(shared.snapshot().is_whitelisted("echo"));
+    }
 }
@@ -0,0 +1,108 @@
+//! The home the skill controls
+//!
+//! A simulation: lights per room and one thermostat, kept in memory. A real
+//! skill would send the same changes to its hub.
+
+use crate::HomeError;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Lowest temperature the thermostat can be set to (°C)
+pub const MIN_CELSIUS: f32 = 10.0;
+
+/// Highest temperature the thermostat can be set to (°C)
+pub const MAX_CELSIUS: f32 = 30.0;
+
+/// Lights and a thermostat
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Home {
+    /// Whether each room's lights are on
+    lights: BTreeMap<String, bool>,
+
+    /// Thermostat setting (°C)
+    celsius: f32,
+}
+
+impl Default for Home {
+    /// A kitchen, living room and bedroom, lights off, at 20°C
+    fn default() -> Self {
+        Self::new(&["kitchen", "living room", "bedroom"])
+    }
+}
+
+impl Home {
+    /// A home with lights in `rooms`, all off, at 20°C
+    pub fn new(rooms: &[&str]) -> Self {
+        Self {
+            lights: rooms
+                .iter()
+                .map(|room| (room.to_lowercase(), false))
+                .collect(),
+            celsius: 20.0,
+        }
+    }
+
+    /// Whether the lights in `room` are on; `None` if it has none
+    pub fn light(&self, room: &str) -> Option<bool> {
+        self.lights.get(room).copied()
+    }
+
+    /// Turn the lights in `room` on or off, or in every room without one
+    pub fn set_lights(&mut self, room: Option<&str>, on: bool) -> Result<(), HomeError> {
+        match room {
+            Some(room) => {
+                let light = self
+                    .lights
+                    .get_mut(room)
+                    .ok_or_else(|| HomeError::UnknownRoom(room.to_string()))?;
+                *light = on;
+            }
+            None => self.lights.values_mut().for_each(|light| *light = on),
+        }
+        Ok(())
+    }
+
+    pub fn temperature(&self) -> f32 {
+        self.celsius
+    }
+
+    pub fn set_temperature(&mut self, celsius: f32) -> Result<(), HomeError> {
+        if !(MIN_CELSIUS..=MAX_CELSIUS).contains(&celsius) {
+            return Err(HomeError::OutOfRange(celsius));
+        }
+        self.celsius = celsius;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lights() {
+        let mut home = Home::default();
+        assert_eq!(home.light("kitchen"), Some(false));
+        assert_eq!(home.light("garage"), None);
+
+        home.set_lights(Some("kitchen"), true).unwrap();
+        assert_eq!(home.light("kitchen"), Some(true));
+        assert_eq!(home.light("bedroom"), Some(false));
+        assert_eq!(
+            home.set_lights(Some("garage"), true),
+            Err(HomeError::UnknownRoom("garage".to_string()))
+        );
+
+        home.set_lights(None, true).unwrap();
+        assert_eq!(home.light("bedroom"), Some(true));
+    }
+
+    #[test]
+    fn test_thermostat() {
+        let mut home = Home::default();
+        home.set_temperature(21.5).unwrap();
+        assert_eq!(home.temperature(), 21.5);
+        assert_eq!(home.set_temperature(45.0), Err(HomeError::OutOfRange(45.0)));
+        assert_eq!(home.temperature(), 21.5);
+    }
+}
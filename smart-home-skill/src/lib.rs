@@ -0,0 +1,37 @@
+//! Smart Home Skill - An example agent-core skill
+//!
+//! Adds lights and a thermostat to the voice agent without changing it:
+//! - "turn on the kitchen lights", "switch the lights off in the bedroom",
+//!   "turn off all the lights"
+//! - "set the thermostat to 21 degrees", "what's the temperature"
+//! - A simulated [`Home`] standing in for a hub such as Home Assistant
+//!
+//! Registered with the agent's skills at startup:
+//!
+//! ```ignore
+//! executors.skills.register(Arc::new(SmartHomeSkill::default()))?;
+//! ```
+
+pub mod home;
+pub mod skill;
+
+pub use home::{Home, MAX_CELSIUS, MIN_CELSIUS};
+pub use skill::{HomeRequest, SmartHomeSkill, SKILL_NAME};
+
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum HomeError {
+    #[error("There are no lights in the {0}")]
+    UnknownRoom(String),
+
+    #[error(
+        "The thermostat only goes from {} to {} degrees",
+        MIN_CELSIUS,
+        MAX_CELSIUS
+    )]
+    OutOfRange(f32),
+}
+
+/// Library version
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -0,0 +1,228 @@
+//! The skill
+//!
+//! [`HomeRequest::parse`] understands what is said about the lights and the
+//! thermostat; [`SmartHomeSkill`] applies it to its [`Home`] and says what
+//! it did.
+
+use crate::home::Home;
+use aether_skills::{Skill, SkillDescription, SkillError, SkillOutput};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use tracing::info;
+
+/// The skill's name in the registry and on its commands
+pub const SKILL_NAME: &str = "smart_home";
+
+/// What was asked of the home
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum HomeRequest {
+    /// Turn the lights in `room` on or off, in every room without one
+    Lights {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        room: Option<String>,
+        on: bool,
+    },
+
+    /// Set the thermostat (°C)
+    SetTemperature { celsius: f32 },
+
+    /// Say what the thermostat is set to
+    Temperature,
+}
+
+impl HomeRequest {
+    /// The request in `transcript` (lowercase, without the wake phrase)
+    pub fn parse(transcript: &str) -> Option<Self> {
+        let words: Vec<&str> = transcript
+            .split_whitespace()
+            .filter(|word| *word != "the")
+            .collect();
+
+        match words.as_slice() {
+            ["what's" | "whats", "temperature"] | ["what", "is", "temperature"] => {
+                Some(Self::Temperature)
+            }
+            ["set", "thermostat" | "temperature" | "heating", "to", celsius, unit @ ..]
+                if matches!(unit, [] | ["degrees"] | ["degrees", "celsius"]) =>
+            {
+                let celsius = celsius.trim_end_matches('°').parse().ok()?;
+                Some(Self::SetTemperature { celsius })
+            }
+            ["turn" | "switch", rest @ ..] => Self::lights(rest),
+            _ => None,
+        }
+    }
+
+    /// "on the kitchen lights", "lights off in bedroom", "off all lights"
+    fn lights(words: &[&str]) -> Option<Self> {
+        let on = match (words.contains(&"on"), words.contains(&"off")) {
+            (true, false) => true,
+            (false, true) => false,
+            _ => return None,
+        };
+        let words: Vec<&str> = words
+            .iter()
+            .copied()
+            .filter(|word| !matches!(*word, "on" | "off" | "all" | "in"))
+            .collect();
+        let at = words
+            .iter()
+            .position(|word| matches!(*word, "light" | "lights"))?;
+        let room = [&words[..at], &words[at + 1..]].concat().join(" ");
+
+        Some(Self::Lights {
+            room: (!room.is_empty()).then_some(room),
+            on,
+        })
+    }
+}
+
+/// Lights and the thermostat, by voice
+#[derive(Clone, Default)]
+pub struct SmartHomeSkill {
+    home: Arc<Mutex<Home>>,
+}
+
+impl SmartHomeSkill {
+    pub fn new(home: Home) -> Self {
+        Self {
+            home: Arc::new(Mutex::new(home)),
+        }
+    }
+
+    /// The home as it is now
+    pub fn home(&self) -> Home {
+        self.home.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl Skill for SmartHomeSkill {
+    fn describe(&self) -> SkillDescription {
+        SkillDescription {
+            name: SKILL_NAME.to_string(),
+            summary: "Turns lights on and off and sets the thermostat".to_string(),
+            examples: vec![
+                "turn on the kitchen lights".to_string(),
+                "turn off all the lights".to_string(),
+                "set the thermostat to 21 degrees".to_string(),
+                "what's the temperature".to_string(),
+            ],
+        }
+    }
+
+    fn match_intent(&self, transcript: &str) -> Option<serde_json::Value> {
+        serde_json::to_value(HomeRequest::parse(transcript)?).ok()
+    }
+
+    async fn execute(&self, request: serde_json::Value) -> Result<SkillOutput, SkillError> {
+        let request: HomeRequest =
+            serde_json::from_value(request).map_err(|e| SkillError::InvalidRequest {
+                skill: SKILL_NAME.to_string(),
+                reason: e.to_string(),
+            })?;
+        info!("Home request: {:?}", request);
+
+        let mut home = self.home.lock().unwrap();
+        let done = match &request {
+            HomeRequest::Lights { room, on } => {
+                let state = if *on { "on" } else { "off" };
+                home.set_lights(room.as_deref(), *on).map(|()| match room {
+                    Some(room) => format!("The {} lights are {}.", room, state),
+                    None => format!("All the lights are {}.", state),
+                })
+            }
+            HomeRequest::SetTemperature { celsius } => home
+                .set_temperature(*celsius)
+                .map(|()| format!("The thermostat is set to {} degrees.", celsius)),
+            HomeRequest::Temperature => Ok(format!(
+                "The thermostat is set to {} degrees.",
+                home.temperature()
+            )),
+        };
+
+        Ok(match done {
+            Ok(summary) => SkillOutput {
+                data: serde_json::to_value(&*home).unwrap_or_default(),
+                ..SkillOutput::done(summary)
+            },
+            Err(e) => SkillOutput::failed(format!("{}.", e)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn lights(room: Option<&str>, on: bool) -> Option<HomeRequest> {
+        Some(HomeRequest::Lights {
+            room: room.map(str::to_string),
+            on,
+        })
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            HomeRequest::parse("turn on the kitchen lights"),
+            lights(Some("kitchen"), true)
+        );
+        assert_eq!(
+            HomeRequest::parse("switch the lights off in the living room"),
+            lights(Some("living room"), false)
+        );
+        assert_eq!(
+            HomeRequest::parse("turn off all the lights"),
+            lights(None, false)
+        );
+        assert_eq!(
+            HomeRequest::parse("set the thermostat to 21.5 degrees"),
+            Some(HomeRequest::SetTemperature { celsius: 21.5 })
+        );
+        assert_eq!(
+            HomeRequest::parse("what's the temperature"),
+            Some(HomeRequest::Temperature)
+        );
+
+        assert_eq!(HomeRequest::parse("turn on the tv"), None);
+        assert_eq!(HomeRequest::parse("turn the lights on and off"), None);
+        assert_eq!(HomeRequest::parse("set the thermostat to warm"), None);
+        assert_eq!(HomeRequest::parse("open github.com"), None);
+    }
+
+    #[tokio::test]
+    async fn test_execute() {
+        let skill = SmartHomeSkill::default();
+        let request = skill.match_intent("turn on the kitchen lights").unwrap();
+        assert_eq!(
+            request,
+            json!({ "action": "lights", "room": "kitchen", "on": true })
+        );
+
+        let output = skill.execute(request).await.unwrap();
+        assert!(output.success);
+        assert_eq!(
+            output.summary.as_deref(),
+            Some("The kitchen lights are on.")
+        );
+        assert_eq!(skill.home().light("kitchen"), Some(true));
+
+        // Said to the user, not an error
+        let request = skill.match_intent("turn on the garage lights").unwrap();
+        let output = skill.execute(request).await.unwrap();
+        assert!(!output.success);
+        assert_eq!(
+            output.summary.as_deref(),
+            Some("There are no lights in the garage.")
+        );
+
+        assert!(matches!(
+            skill.execute(json!({ "action": "dim" })).await,
+            Err(SkillError::InvalidRequest { .. })
+        ));
+    }
+}
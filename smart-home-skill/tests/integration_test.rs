@@ -0,0 +1,30 @@
+//! Integration tests for the smart home skill
+
+use agent_core::{Command, Executors, Outcome};
+use smart_home_skill::{Home, SmartHomeSkill, SKILL_NAME};
+use std::sync::Arc;
+
+#[tokio::test]
+async fn test_registered_skill() {
+    let skill = SmartHomeSkill::new(Home::new(&["office"]));
+    let mut executors = Executors::default();
+    executors.skills.register(Arc::new(skill.clone())).unwrap();
+
+    // Offered the transcript as the agent does, wake phrase and all
+    let command = executors
+        .skills
+        .route("Hey Aether, turn on the office lights.")
+        .unwrap();
+    assert!(matches!(&command, Command::Skill { skill, .. } if skill == SKILL_NAME));
+    assert!(executors.skills.route("run ls -la").is_none());
+
+    match executors.dispatch(&command).await.unwrap() {
+        Outcome::Skill { skill, output } => {
+            assert_eq!(skill, SKILL_NAME);
+            assert_eq!(output.summary.as_deref(), Some("The office lights are on."));
+            assert_eq!(output.data["lights"]["office"], true);
+        }
+        other => panic!("unexpected outcome: {:?}", other),
+    }
+    assert_eq!(skill.home().light("office"), Some(true));
+}
@@ -0,0 +1,415 @@
+//! Session archive module
+//!
+//! Writes each transcribed utterance to disk as a 16kHz mono FLAC file with a
+//! JSON transcript sidecar, organized as `<root>/<YYYY-MM-DD>/<session_id>/`.
+//!
+//! The streaming windows of an utterance overlap, so they are collected with
+//! [`SessionArchive::record`] and written once, when speech ends, by
+//! [`FinishedUtterance::write`].
+
+use crate::audio_preprocessor::{AudioPreprocessor, AudioSample, WHISPER_SAMPLE_RATE};
+use crate::whisper_wrapper::{TranscriptionResult, TranscriptionSegment};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+use tracing::{debug, info, warn};
+
+#[derive(Error, Debug)]
+pub enum ArchiveError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("FLAC encoding failed: {0}")]
+    Flac(String),
+
+    #[error("Transcript serialization failed: {0}")]
+    Serialization(#[from] serde_json::Error),
+}
+
+/// Archive configuration
+#[derive(Debug, Clone)]
+pub struct ArchiveConfig {
+    /// Root directory for archived sessions
+    pub root: PathBuf,
+
+    /// Delete date directories older than this many days (None = keep forever)
+    pub retention_days: Option<u64>,
+}
+
+impl ArchiveConfig {
+    /// Create a config that archives under `root` with no retention limit
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            retention_days: None,
+        }
+    }
+}
+
+/// Transcript sidecar written next to each utterance's audio
+#[derive(Debug, Serialize)]
+struct UtteranceRecord<'a> {
+    session_id: &'a str,
+    index: usize,
+    audio_file: &'a str,
+    start_ms: u64,
+    end_ms: u64,
+    text: &'a str,
+    confidence: f32,
+    language: &'a str,
+    segments: Vec<SegmentRecord<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+struct SegmentRecord<'a> {
+    start_ms: i64,
+    end_ms: i64,
+    text: &'a str,
+    confidence: f32,
+}
+
+/// Archive for a single streaming session
+pub struct SessionArchive {
+    session_id: String,
+    session_dir: PathBuf,
+    utterances: usize,
+    current: Option<Utterance>,
+}
+
+/// The windows of an utterance still being spoken
+struct Utterance {
+    start_ms: u64,
+    /// Session sample index just past `audio`
+    end_sample: usize,
+    audio: Vec<AudioSample>,
+    /// Segments on the session timeline
+    segments: Vec<TranscriptionSegment>,
+    confidence_sum: f32,
+    windows: usize,
+    language: String,
+}
+
+/// An utterance that has ended, ready to be written
+pub struct FinishedUtterance {
+    session_id: String,
+    session_dir: PathBuf,
+    index: usize,
+    utterance: Utterance,
+}
+
+impl SessionArchive {
+    /// Create a new session directory under today's date
+    pub fn create(config: &ArchiveConfig) -> Result<Self, ArchiveError> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+
+        let session_id = format!("session-{}", now.as_millis());
+        let session_dir = config.root.join(date_string(now)).join(&session_id);
+        fs::create_dir_all(&session_dir)?;
+
+        info!("Archiving session to {:?}", session_dir);
+
+        Ok(Self {
+            session_id,
+            session_dir,
+            utterances: 0,
+            current: None,
+        })
+    }
+
+    /// Session identifier
+    pub fn session_id(&self) -> &str {
+        &self.session_id
+    }
+
+    /// Directory holding this session's files
+    pub fn session_dir(&self) -> &Path {
+        &self.session_dir
+    }
+
+    /// Number of utterances archived so far
+    pub fn utterance_count(&self) -> usize {
+        self.utterances
+    }
+
+    /// Add a transcribed window to the utterance being spoken
+    ///
+    /// `start_sample` and `start_ms` place the window on the session timeline.
+    /// Audio already recorded from an overlapping window is skipped, as are
+    /// segments that end before the utterance so far.
+    pub fn record(
+        &mut self,
+        start_sample: usize,
+        audio: &[AudioSample],
+        transcript: &TranscriptionResult,
+        start_ms: u64,
+    ) {
+        let utterance = self.current.get_or_insert_with(|| Utterance {
+            start_ms,
+            end_sample: start_sample,
+            audio: Vec::new(),
+            segments: Vec::new(),
+            confidence_sum: 0.0,
+            windows: 0,
+            language: transcript.language.clone(),
+        });
+
+        let overlap = utterance
+            .end_sample
+            .saturating_sub(start_sample)
+            .min(audio.len());
+        utterance.audio.extend_from_slice(&audio[overlap..]);
+        utterance.end_sample = utterance.end_sample.max(start_sample + audio.len());
+
+        let covered_ms = utterance.segments.last().map_or(i64::MIN, |seg| seg.end_ms);
+        utterance.segments.extend(
+            transcript
+                .segments
+                .iter()
+                .map(|seg| TranscriptionSegment {
+                    start_ms: seg.start_ms + start_ms as i64,
+                    end_ms: seg.end_ms + start_ms as i64,
+                    text: seg.text.trim().to_string(),
+                    confidence: seg.confidence,
+                })
+                .filter(|seg| seg.end_ms > covered_ms),
+        );
+        utterance.confidence_sum += transcript.confidence;
+        utterance.windows += 1;
+    }
+
+    /// End the utterance being spoken, if any, and number it
+    ///
+    /// Cheap: the returned utterance is encoded and written by
+    /// [`FinishedUtterance::write`], off the caller's lock.
+    pub fn finish_utterance(&mut self) -> Option<FinishedUtterance> {
+        let utterance = self.current.take()?;
+        self.utterances += 1;
+
+        Some(FinishedUtterance {
+            session_id: self.session_id.clone(),
+            session_dir: self.session_dir.clone(),
+            index: self.utterances,
+            utterance,
+        })
+    }
+}
+
+impl FinishedUtterance {
+    /// Encode the utterance as FLAC and write it with its aligned transcript
+    ///
+    /// Blocking; returns the FLAC path.
+    pub fn write(self) -> Result<PathBuf, ArchiveError> {
+        let utterance = &self.utterance;
+        let stem = format!("utterance-{:05}", self.index);
+        let flac_name = format!("{}.flac", stem);
+        let flac_path = self.session_dir.join(&flac_name);
+
+        fs::write(&flac_path, encode_flac(&utterance.audio)?)?;
+
+        let duration_ms = (utterance.audio.len() as u64 * 1000) / WHISPER_SAMPLE_RATE as u64;
+        let text = utterance
+            .segments
+            .iter()
+            .map(|seg| seg.text.as_str())
+            .filter(|text| !text.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ");
+        let record = UtteranceRecord {
+            session_id: &self.session_id,
+            index: self.index,
+            audio_file: &flac_name,
+            start_ms: utterance.start_ms,
+            end_ms: utterance.start_ms + duration_ms,
+            text: &text,
+            confidence: utterance.confidence_sum / utterance.windows.max(1) as f32,
+            language: &utterance.language,
+            segments: utterance
+                .segments
+                .iter()
+                .map(|seg| SegmentRecord {
+                    start_ms: seg.start_ms,
+                    end_ms: seg.end_ms,
+                    text: &seg.text,
+                    confidence: seg.confidence,
+                })
+                .collect(),
+        };
+
+        let json_path = self.session_dir.join(format!("{}.json", stem));
+        fs::write(&json_path, serde_json::to_vec_pretty(&record)?)?;
+
+        debug!("Archived utterance {} to {:?}", self.index, flac_path);
+
+        Ok(flac_path)
+    }
+}
+
+/// Encode 16kHz mono audio as 16-bit FLAC
+fn encode_flac(audio: &[AudioSample]) -> Result<Vec<u8>, ArchiveError> {
+    use flacenc::component::BitRepr;
+    use flacenc::error::Verify;
+
+    let samples: Vec<i32> = AudioPreprocessor::f32_to_i16(audio)
+        .into_iter()
+        .map(i32::from)
+        .collect();
+
+    let config = flacenc::config::Encoder::default()
+        .into_verified()
+        .map_err(|(_, e)| ArchiveError::Flac(e.to_string()))?;
+    let source =
+        flacenc::source::MemSource::from_samples(&samples, 1, 16, WHISPER_SAMPLE_RATE as usize);
+    let stream = flacenc::encode_with_fixed_block_size(&config, source, config.block_size)
+        .map_err(|e| ArchiveError::Flac(e.to_string()))?;
+
+    let mut sink = flacenc::bitsink::ByteSink::new();
+    stream
+        .write(&mut sink)
+        .map_err(|e| ArchiveError::Flac(e.to_string()))?;
+    Ok(sink.as_slice().to_vec())
+}
+
+/// Delete date directories older than the configured retention period
+///
+/// Returns the number of directories removed.
+pub fn apply_retention(config: &ArchiveConfig) -> Result<usize, ArchiveError> {
+    let Some(days) = config.retention_days else {
+        return Ok(0);
+    };
+
+    if !config.root.exists() {
+        return Ok(0);
+    }
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let cutoff = date_string(now.saturating_sub(Duration::from_secs(days * 86_400)));
+
+    let mut removed = 0;
+    for entry in fs::read_dir(&config.root)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+
+        // Date directories are YYYY-MM-DD, so string order is chronological
+        if entry.file_type()?.is_dir() && is_date_dir(&name) && name < cutoff {
+            match fs::remove_dir_all(entry.path()) {
+                Ok(()) => removed += 1,
+                Err(e) => warn!("Failed to remove expired archive {:?}: {}", entry.path(), e),
+            }
+        }
+    }
+
+    if removed > 0 {
+        info!("Removed {} expired archive directories", removed);
+    }
+
+    Ok(removed)
+}
+
+fn is_date_dir(name: &str) -> bool {
+    name.len() == 10
+        && name
+            .chars()
+            .enumerate()
+            .all(|(i, c)| if i == 4 || i == 7 { c == '-' } else { c.is_ascii_digit() })
+}
+
+/// Format a duration since the Unix epoch as a UTC `YYYY-MM-DD` date
+fn date_string(since_epoch: Duration) -> String {
+    // Civil-from-days conversion (Howard Hinnant's algorithm)
+    let days = (since_epoch.as_secs() / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let doe = days.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::whisper_wrapper::TranscriptionSegment;
+
+    fn sample_transcript() -> TranscriptionResult {
+        TranscriptionResult {
+            text: "open the browser".to_string(),
+            confidence: 0.9,
+            processing_time_ms: 10,
+            language: "en".to_string(),
+            segments: vec![TranscriptionSegment {
+                start_ms: 0,
+                end_ms: 500,
+                text: "open the browser".to_string(),
+                confidence: 0.9,
+            }],
+        }
+    }
+
+    #[test]
+    fn test_date_string() {
+        assert_eq!(date_string(Duration::from_secs(0)), "1970-01-01");
+        assert_eq!(date_string(Duration::from_secs(951_782_400)), "2000-02-29");
+        assert_eq!(date_string(Duration::from_secs(1_704_067_199)), "2023-12-31");
+    }
+
+    #[test]
+    fn test_overlapping_windows_write_one_flac() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = ArchiveConfig::new(dir.path());
+        let mut archive = SessionArchive::create(&config).unwrap();
+        assert!(archive.finish_utterance().is_none());
+
+        // Two 500ms windows overlapping by 50ms, from 1s into the session
+        let audio = vec![0.25; 8000];
+        archive.record(16_000, &audio, &sample_transcript(), 1000);
+        archive.record(23_200, &audio, &sample_transcript(), 1450);
+        let flac_path = archive.finish_utterance().unwrap().write().unwrap();
+        assert_eq!(flac_path.file_name().unwrap(), "utterance-00001.flac");
+        assert_eq!(archive.utterance_count(), 1);
+
+        let reader = claxon::FlacReader::open(&flac_path).unwrap();
+        assert_eq!(reader.streaminfo().sample_rate, WHISPER_SAMPLE_RATE);
+        assert_eq!(reader.streaminfo().samples, Some(15_200));
+
+        let json = fs::read_to_string(flac_path.with_extension("json")).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["start_ms"], 1000);
+        assert_eq!(value["end_ms"], 1950);
+        assert_eq!(value["segments"][0]["start_ms"], 1000);
+        assert_eq!(value["segments"][1]["start_ms"], 1450);
+        assert_eq!(value["text"], "open the browser open the browser");
+
+        // The next utterance starts afresh
+        archive.record(48_000, &audio, &sample_transcript(), 3000);
+        let next = archive.finish_utterance().unwrap().write().unwrap();
+        assert_eq!(next.file_name().unwrap(), "utterance-00002.flac");
+    }
+
+    #[test]
+    fn test_retention_removes_old_dates() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("2000-01-01/session-1")).unwrap();
+        fs::create_dir_all(dir.path().join("not-a-date")).unwrap();
+
+        let config = ArchiveConfig {
+            root: dir.path().to_path_buf(),
+            retention_days: Some(30),
+        };
+        let archive = SessionArchive::create(&config).unwrap();
+
+        assert_eq!(apply_retention(&config).unwrap(), 1);
+        assert!(!dir.path().join("2000-01-01").exists());
+        assert!(dir.path().join("not-a-date").exists());
+        assert!(archive.session_dir().exists());
+    }
+}
@@ -3,6 +3,7 @@
 /// Handles resampling, normalization, and format conversion for STT.
 /// Ensures audio is in the correct format for Whisper (16kHz, mono, f32).
 
+use std::path::Path;
 use thiserror::Error;
 use tracing::{debug, trace, warn};
 
@@ -28,6 +29,9 @@ pub enum PreprocessorError {
 
     #[error("Audio format conversion failed: {0}")]
     FormatConversionError(String),
+
+    #[error("Failed to read WAV file: {0}")]
+    WavReadError(String),
 }
 
 /// Audio format specification
@@ -226,6 +230,50 @@ impl AudioPreprocessor {
             .collect()
     }
 
+    /// Root-mean-square energy of the samples (0.0 for an empty buffer)
+    pub fn rms(samples: &[AudioSample]) -> f32 {
+        if samples.is_empty() {
+            return 0.0;
+        }
+
+        let sum_squares: f32 = samples.iter().map(|&s| s * s).sum();
+        (sum_squares / samples.len() as f32).sqrt()
+    }
+
+    /// Read a WAV file into interleaved f32 samples and its format
+    pub fn read_wav(path: impl AsRef<Path>) -> Result<(Vec<AudioSample>, AudioFormat), PreprocessorError> {
+        let reader = hound::WavReader::open(path.as_ref())
+            .map_err(|e| PreprocessorError::WavReadError(e.to_string()))?;
+        let spec = reader.spec();
+
+        let samples = match spec.sample_format {
+            hound::SampleFormat::Float => reader
+                .into_samples::<f32>()
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| PreprocessorError::WavReadError(e.to_string()))?,
+            hound::SampleFormat::Int if spec.bits_per_sample == 16 => {
+                let pcm = reader
+                    .into_samples::<i16>()
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|e| PreprocessorError::WavReadError(e.to_string()))?;
+                Self::i16_to_f32(&pcm)
+            }
+            hound::SampleFormat::Int => {
+                let scale = (1i64 << (spec.bits_per_sample - 1)) as f32;
+                reader
+                    .into_samples::<i32>()
+                    .map(|s| s.map(|v| v as f32 / scale))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|e| PreprocessorError::WavReadError(e.to_string()))?
+            }
+        };
+
+        let format = AudioFormat::new(spec.sample_rate, spec.channels, spec.bits_per_sample);
+        debug!("Read {} samples from WAV ({:?})", samples.len(), format);
+
+        Ok((samples, format))
+    }
+
     /// Get input format
     pub fn input_format(&self) -> AudioFormat {
         self.input_format
@@ -338,6 +386,42 @@ mod tests {
         assert_eq!(i16_samples[1], -i16::MAX); // Clamped to -1.0, then scaled
     }
 
+    #[test]
+    fn test_rms() {
+        assert_eq!(AudioPreprocessor::rms(&[]), 0.0);
+        assert_relative_eq!(AudioPreprocessor::rms(&[0.5, -0.5, 0.5, -0.5]), 0.5, epsilon = 0.001);
+    }
+
+    #[test]
+    fn test_read_wav_i16() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.wav");
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: 44100,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        for _ in 0..100 {
+            writer.write_sample(i16::MAX).unwrap();
+            writer.write_sample(0i16).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let (samples, format) = AudioPreprocessor::read_wav(&path).unwrap();
+
+        assert_eq!(format, AudioFormat::new(44100, 2, 16));
+        assert_eq!(samples.len(), 200);
+        assert_relative_eq!(samples[0], 1.0, epsilon = 0.001);
+    }
+
+    #[test]
+    fn test_read_wav_missing_file() {
+        let result = AudioPreprocessor::read_wav("does-not-exist.wav");
+        assert!(matches!(result, Err(PreprocessorError::WavReadError(_))));
+    }
+
     #[test]
     fn test_process_empty_buffer() {
         let format = AudioFormat::new(16000, 1, 16);
@@ -0,0 +1,259 @@
+//! Command grammar module
+//!
+//! Biases transcription toward the agent's command phrases. The phrase list is
+//! passed to Whisper as an initial prompt, and transcripts are post-corrected by
+//! mapping known mis-recognitions back to their canonical phrase where a
+//! command is said: near the start of the transcript, once.
+
+use serde::Deserialize;
+use std::path::Path;
+use thiserror::Error;
+use tracing::{debug, info};
+
+#[derive(Error, Debug)]
+pub enum GrammarError {
+    #[error("Failed to read grammar file: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Invalid grammar file: {0}")]
+    Parse(#[from] serde_json::Error),
+}
+
+/// Phrases belonging to one intent
+#[derive(Debug, Clone, Deserialize)]
+pub struct IntentPhrases {
+    /// Intent name (e.g. "open_app")
+    pub intent: String,
+
+    /// Canonical command phrase (e.g. "open")
+    pub phrase: String,
+
+    /// Mis-recognitions or synonyms that are rewritten to `phrase`
+    #[serde(default)]
+    pub variants: Vec<String>,
+}
+
+/// Command grammar loaded from a JSON file
+///
+/// ```json
+/// { "intents": [ { "intent": "screenshot", "phrase": "screenshot", "variants": ["screen shot"] } ] }
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct CommandGrammar {
+    pub intents: Vec<IntentPhrases>,
+}
+
+/// Words shorter than this are never fuzzy-matched (too many false positives)
+const MIN_FUZZY_WORD_LEN: usize = 4;
+
+/// A variant must start within this many words of the start to be corrected
+/// ("take a screen shot"); later words are the command's arguments
+const COMMAND_WORDS: usize = 3;
+
+impl CommandGrammar {
+    /// Load a grammar from a JSON file
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, GrammarError> {
+        let content = std::fs::read_to_string(path.as_ref())?;
+        let grammar = Self::from_json(&content)?;
+
+        info!(
+            "Loaded command grammar with {} intents from {:?}",
+            grammar.intents.len(),
+            path.as_ref()
+        );
+
+        Ok(grammar)
+    }
+
+    /// Parse a grammar from a JSON string
+    pub fn from_json(json: &str) -> Result<Self, GrammarError> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// Prompt text that primes Whisper's decoder with the command vocabulary
+    pub fn initial_prompt(&self) -> String {
+        let phrases: Vec<&str> = self.intents.iter().map(|i| i.phrase.as_str()).collect();
+        phrases.join(", ")
+    }
+
+    /// Rewrite a known variant to its canonical phrase
+    ///
+    /// Only the command is corrected: the first variant starting within
+    /// [`COMMAND_WORDS`] words of the start, so an ordinary word later on
+    /// ("search for often used apps") is left as said. Variants are matched
+    /// case-insensitively on word boundaries, ignoring punctuation. Without
+    /// one, the first word is fuzzy-matched (edit distance 1) against
+    /// single-word phrases, since that is where command verbs appear.
+    pub fn correct(&self, text: &str) -> String {
+        let words: Vec<&str> = text.split_whitespace().collect();
+        let normalized: Vec<String> = words.iter().map(|w| normalize(w)).collect();
+
+        let mut output: Vec<String> = Vec::with_capacity(words.len());
+        let mut corrected = false;
+        let mut i = 0;
+
+        'outer: while i < words.len() {
+            let intents = match corrected || i >= COMMAND_WORDS {
+                true => &[][..],
+                false => &self.intents[..],
+            };
+            for intent in intents {
+                for variant in &intent.variants {
+                    let variant_words: Vec<String> =
+                        variant.split_whitespace().map(normalize).collect();
+
+                    if !variant_words.is_empty()
+                        && normalized[i..].starts_with(&variant_words)
+                    {
+                        output.push(intent.phrase.clone());
+                        corrected = true;
+                        i += variant_words.len();
+                        continue 'outer;
+                    }
+                }
+            }
+
+            if i == 0 {
+                if let Some(phrase) = self.fuzzy_command(&normalized[0]) {
+                    output.push(phrase.to_string());
+                    corrected = true;
+                    i += 1;
+                    continue;
+                }
+            }
+
+            output.push(words[i].to_string());
+            i += 1;
+        }
+
+        let output = output.join(" ");
+        if output != text {
+            debug!("Grammar correction: {:?} -> {:?}", text, output);
+        }
+
+        output
+    }
+
+    /// Intent whose phrase appears in the (corrected) text
+    pub fn match_intent(&self, text: &str) -> Option<&str> {
+        let normalized: Vec<String> = text.split_whitespace().map(normalize).collect();
+
+        self.intents
+            .iter()
+            .find(|intent| {
+                let phrase: Vec<String> = intent.phrase.split_whitespace().map(normalize).collect();
+                !phrase.is_empty() && normalized.windows(phrase.len()).any(|w| w == phrase.as_slice())
+            })
+            .map(|intent| intent.intent.as_str())
+    }
+
+    fn fuzzy_command(&self, word: &str) -> Option<&str> {
+        if word.len() < MIN_FUZZY_WORD_LEN {
+            return None;
+        }
+
+        self.intents
+            .iter()
+            .map(|intent| intent.phrase.as_str())
+            .filter(|phrase| !phrase.contains(' ') && phrase.len() >= MIN_FUZZY_WORD_LEN)
+            .find(|phrase| *phrase != word && edit_distance(phrase, word) <= 1)
+    }
+}
+
+/// Lowercase a word and strip surrounding punctuation
+fn normalize(word: &str) -> String {
+    word.trim_matches(|c: char| !c.is_alphanumeric()).to_lowercase()
+}
+
+/// Levenshtein distance between two short strings
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.iter().enumerate() {
+        let mut curr = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == cb { 0 } else { 1 };
+            curr[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(curr[j] + 1);
+        }
+        prev = curr;
+    }
+
+    prev[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grammar() -> CommandGrammar {
+        CommandGrammar::from_json(
+            r#"{
+                "intents": [
+                    { "intent": "open_app", "phrase": "open", "variants": ["often", "opened up"] },
+                    { "intent": "web_search", "phrase": "search", "variants": ["surge"] },
+                    { "intent": "screenshot", "phrase": "screenshot", "variants": ["screen shot", "green shot"] }
+                ]
+            }"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_initial_prompt() {
+        assert_eq!(grammar().initial_prompt(), "open, search, screenshot");
+    }
+
+    #[test]
+    fn test_variant_correction() {
+        let g = grammar();
+        assert_eq!(g.correct("Take a screen shot."), "Take a screenshot");
+        assert_eq!(g.correct("often the browser"), "open the browser");
+        assert_eq!(g.correct("Surge for rust tutorials"), "search for rust tutorials");
+    }
+
+    #[test]
+    fn test_only_the_command_is_corrected() {
+        let g = grammar();
+        // Arguments keep the words that happen to be variants
+        assert_eq!(
+            g.correct("surge for apps I often use"),
+            "search for apps I often use"
+        );
+        assert_eq!(g.correct("often often"), "open often");
+        assert_eq!(
+            g.correct("what is the weather like often"),
+            "what is the weather like often"
+        );
+    }
+
+    #[test]
+    fn test_fuzzy_first_word() {
+        let g = grammar();
+        assert_eq!(g.correct("serch for cats"), "search for cats");
+        // Only the first word is fuzzy-matched
+        assert_eq!(g.correct("please serch"), "please serch");
+    }
+
+    #[test]
+    fn test_unrelated_text_unchanged() {
+        let g = grammar();
+        assert_eq!(g.correct("what time is it"), "what time is it");
+    }
+
+    #[test]
+    fn test_match_intent() {
+        let g = grammar();
+        assert_eq!(g.match_intent("take a screenshot"), Some("screenshot"));
+        assert_eq!(g.match_intent("Open the browser"), Some("open_app"));
+        assert_eq!(g.match_intent("hello there"), None);
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("search", "serch"), 1);
+        assert_eq!(edit_distance("open", "open"), 0);
+        assert_eq!(edit_distance("open", "close"), 4);
+    }
+}
@@ -0,0 +1,137 @@
+//! gRPC service
+//!
+//! `SpeechToText.Transcribe` takes a stream of 16-bit 16kHz mono PCM and
+//! returns the [`StreamingEvent`]s of transcribing it, as their JSON (see
+//! `proto/stt.proto`). Each call is a session of its own, registered in a
+//! [`SessionRegistry`] while its stream lasts.
+
+use crate::audio_preprocessor::AudioPreprocessor;
+use crate::metrics::SessionRegistry;
+use crate::streaming::{StreamingEvent, StreamingSTT};
+use futures::Stream;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tonic::{Request, Response, Status, Streaming};
+use tracing::{debug, warn};
+
+pub mod proto {
+    tonic::include_proto!("aether.stt.v1");
+}
+
+use proto::speech_to_text_server::{SpeechToText, SpeechToTextServer};
+use proto::{AudioChunk, TranscriptEvent};
+
+/// Chunks queued for a session before the client's stream waits
+const AUDIO_QUEUE: usize = 32;
+
+/// Serves transcription to other processes
+#[derive(Clone)]
+pub struct GrpcServer {
+    stt: Arc<StreamingSTT>,
+    sessions: SessionRegistry,
+}
+
+impl GrpcServer {
+    /// Serve sessions like `stt`: its models, grammar and config
+    pub fn new(stt: Arc<StreamingSTT>) -> Self {
+        Self {
+            stt,
+            sessions: SessionRegistry::new(),
+        }
+    }
+
+    /// Register live sessions in `sessions`, e.g. the one `/metrics` reads
+    pub fn with_sessions(mut self, sessions: SessionRegistry) -> Self {
+        self.sessions = sessions;
+        self
+    }
+
+    /// The tonic service, to add to a server of one's own
+    pub fn into_service(self) -> SpeechToTextServer<Self> {
+        SpeechToTextServer::new(self)
+    }
+
+    /// Serve on `addr` until `shutdown` completes or the server fails
+    pub async fn serve(
+        self,
+        addr: SocketAddr,
+        shutdown: impl Future<Output = ()>,
+    ) -> Result<(), tonic::transport::Error> {
+        tonic::transport::Server::builder()
+            .add_service(self.into_service())
+            .serve_with_shutdown(addr, shutdown)
+            .await
+    }
+}
+
+#[tonic::async_trait]
+impl SpeechToText for GrpcServer {
+    type TranscribeStream = Pin<Box<dyn Stream<Item = Result<TranscriptEvent, Status>> + Send>>;
+
+    async fn transcribe(
+        &self,
+        request: Request<Streaming<AudioChunk>>,
+    ) -> Result<Response<Self::TranscribeStream>, Status> {
+        let session = Arc::new(
+            self.stt
+                .new_session()
+                .map_err(|e| Status::internal(e.to_string()))?,
+        );
+        session
+            .start()
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        let mut audio = request.into_inner();
+        let (audio_tx, audio_rx) = mpsc::channel(AUDIO_QUEUE);
+        let events = session.process_stream(audio_rx).await;
+        // Dropped with the response stream, which unregisters the session
+        let registered = self.sessions.register(session);
+
+        tokio::spawn(async move {
+            loop {
+                match audio.message().await {
+                    Ok(Some(chunk)) => {
+                        let pcm: Vec<i16> = chunk
+                            .pcm
+                            .chunks_exact(2)
+                            .map(|b| i16::from_le_bytes([b[0], b[1]]))
+                            .collect();
+                        if audio_tx
+                            .send(AudioPreprocessor::i16_to_f32(&pcm))
+                            .await
+                            .is_err()
+                        {
+                            break;
+                        }
+                    }
+                    Ok(None) => break,
+                    Err(status) => {
+                        warn!("Audio stream ended: {}", status);
+                        break;
+                    }
+                }
+            }
+            // Dropping the sender flushes the session and ends its events
+            debug!("gRPC session audio ended");
+        });
+
+        let stream = futures::stream::unfold(
+            (events, registered),
+            |(mut events, registered)| async move {
+                let event = events.recv().await?;
+                Some((encode(&event), (events, registered)))
+            },
+        );
+        Ok(Response::new(Box::pin(stream)))
+    }
+}
+
+fn encode(event: &StreamingEvent) -> Result<TranscriptEvent, Status> {
+    serde_json::to_vec(event)
+        .map(|event| TranscriptEvent { event })
+        .map_err(|e| Status::internal(e.to_string()))
+}
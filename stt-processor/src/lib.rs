@@ -2,13 +2,23 @@
 ///
 /// Provides speech-to-text functionality using Whisper with streaming support.
 
+pub mod archive;
 pub mod audio_preprocessor;
+pub mod grammar;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod metrics;
 pub mod streaming;
 pub mod whisper_wrapper;
 
 // Re-export main types
+pub use archive::{ArchiveConfig, ArchiveError, FinishedUtterance, SessionArchive};
 pub use audio_preprocessor::{AudioFormat, AudioPreprocessor, AudioSample, PreprocessorError, WHISPER_SAMPLE_RATE};
-pub use streaming::{StreamingConfig, StreamingEvent, StreamingSTT, StreamingStats, StreamingError};
+pub use grammar::{CommandGrammar, GrammarError, IntentPhrases};
+#[cfg(feature = "grpc")]
+pub use grpc::GrpcServer;
+pub use metrics::{render_prometheus, RegisteredSession, SessionRegistry, PROMETHEUS_CONTENT_TYPE};
+pub use streaming::{OverflowPolicy, StreamingConfig, StreamingEvent, StreamingSTT, StreamingStats, StreamingError};
 pub use whisper_wrapper::{
     TranscriptionResult, TranscriptionSegment, WhisperConfig, WhisperError, WhisperProcessor,
 };
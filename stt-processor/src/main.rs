@@ -1,22 +1,122 @@
-/// STT Service binary
-///
-/// Standalone speech-to-text service with gRPC interface.
+//! STT Service binary
+//!
+//! Speech-to-text command line tool and HTTP service.
 
+use aether_config::{ConfigArgs, ConfigCommand, SttSection};
+use aether_health::{Health, Report};
+use aether_telemetry::{remote_span, TraceParent};
+use clap::{Parser, Subcommand};
 use stt_processor::{
-    AudioFormat, StreamingConfig, StreamingSTT, WhisperConfig, WhisperProcessor,
+    render_prometheus, ArchiveConfig, AudioFormat, AudioPreprocessor, CommandGrammar,
+    SessionRegistry, StreamingConfig, StreamingEvent, StreamingSTT, WhisperConfig,
+    WhisperProcessor, PROMETHEUS_CONTENT_TYPE, WHISPER_SAMPLE_RATE,
 };
+use std::io::Write;
+use std::path::PathBuf;
 use std::sync::Arc;
-use tracing::{error, info};
-use tracing_subscriber;
+use tokio::io::AsyncReadExt;
+use tokio::sync::mpsc;
+use tracing::{debug, error, info, Instrument, Span};
 use axum::{
+    extract::State,
+    http::{header, StatusCode},
     routing::get,
     Router,
     Json,
     response::IntoResponse,
-    http::StatusCode,
 };
 use serde::{Serialize, Deserialize};
 
+#[derive(Parser)]
+#[command(name = "stt-service")]
+#[command(about = "AetherOS speech-to-text processor", long_about = None)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Commands>,
+
+    /// Whisper model file (overrides stt.model_path)
+    #[arg(long, global = true)]
+    model: Option<PathBuf>,
+
+    /// Config file, TOML or YAML (default: AETHER_CONFIG)
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+
+    /// Override a config value, e.g. `--set stt.language=de`
+    #[arg(long = "set", global = true, value_name = "KEY=VALUE")]
+    overrides: Vec<String>,
+
+    /// W3C traceparent of the caller's span; `listen` continues its trace
+    #[arg(long, global = true, env = "TRACEPARENT")]
+    traceparent: Option<TraceParent>,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Transcribe a WAV file and print the text
+    Transcribe {
+        /// WAV file to transcribe
+        file: PathBuf,
+
+        /// Print segment timestamps
+        #[arg(long)]
+        segments: bool,
+    },
+
+    /// Live captions from the default microphone, until Ctrl-C
+    Listen {
+        /// Read raw 16-bit PCM from stdin instead (e.g. `arecord -f S16_LE -r 16000 -c 1`)
+        #[arg(long)]
+        stdin: bool,
+
+        /// Sample rate of the PCM on stdin
+        #[arg(long, default_value_t = WHISPER_SAMPLE_RATE, requires = "stdin")]
+        sample_rate: u32,
+
+        /// Channel count of the PCM on stdin
+        #[arg(long, default_value_t = 1, requires = "stdin")]
+        channels: u16,
+    },
+
+    /// Run the HTTP service, and the gRPC one if asked for (default)
+    Serve {
+        /// HTTP listen address (overrides stt.http_addr)
+        #[arg(long)]
+        http: Option<String>,
+
+        /// gRPC listen address (overrides stt.grpc_addr; needs the `grpc` feature)
+        #[arg(long)]
+        grpc: Option<String>,
+    },
+
+    /// Measure transcription speed on a WAV file
+    Bench {
+        /// WAV file to transcribe
+        #[arg(long)]
+        file: PathBuf,
+
+        /// Number of timed runs
+        #[arg(long, default_value_t = 3)]
+        iterations: usize,
+    },
+
+    /// Check the configuration
+    Validate,
+
+    /// Print the configuration after all layers, as TOML
+    PrintEffectiveConfig,
+}
+
+impl Commands {
+    fn config_command(&self) -> Option<ConfigCommand> {
+        match self {
+            Commands::Validate => Some(ConfigCommand::Validate),
+            Commands::PrintEffectiveConfig => Some(ConfigCommand::PrintEffectiveConfig),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 struct HealthResponse {
     status: String,
@@ -24,6 +124,13 @@ struct HealthResponse {
     version: String,
 }
 
+/// Shared state for HTTP handlers
+#[derive(Clone)]
+struct AppState {
+    sessions: SessionRegistry,
+    health: Health,
+}
+
 async fn health_check() -> impl IntoResponse {
     Json(HealthResponse {
         status: "healthy".to_string(),
@@ -32,96 +139,483 @@ async fn health_check() -> impl IntoResponse {
     })
 }
 
+async fn liveness(State(state): State<AppState>) -> impl IntoResponse {
+    health_response(state.health.liveness())
+}
+
+async fn readiness(State(state): State<AppState>) -> impl IntoResponse {
+    health_response(state.health.readiness().await)
+}
+
+fn health_response(report: Report) -> impl IntoResponse {
+    let status =
+        StatusCode::from_u16(report.status_code()).unwrap_or(StatusCode::SERVICE_UNAVAILABLE);
+    (status, Json(report))
+}
+
+async fn metrics(State(state): State<AppState>) -> impl IntoResponse {
+    let stats = state.sessions.stats().await;
+    let model_loaded = state
+        .health
+        .readiness()
+        .await
+        .checks
+        .get("model")
+        .is_some_and(|check| check.ok);
+
+    (
+        [(header::CONTENT_TYPE, PROMETHEUS_CONTENT_TYPE)],
+        render_prometheus(&stats, model_loaded),
+    )
+}
+
 #[tokio::main]
 async fn main() {
-    // Initialize tracing
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            tracing_subscriber::EnvFilter::from_default_env()
-                .add_directive("stt_processor=info".parse().unwrap()),
-        )
-        .init();
+    // Initialize tracing, exporting spans when OTEL_EXPORTER_OTLP_ENDPOINT is set
+    if let Err(e) = aether_telemetry::init("stt-service", "stt_processor=info") {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
 
-    info!("Starting AetherOS STT Service");
+    let cli = Cli::parse();
+
+    let mut config_args = ConfigArgs {
+        file: cli.config,
+        overrides: cli.overrides,
+    };
+    if let Some(model) = cli.model {
+        config_args.set("stt.model_path", model.display().to_string());
+    }
+
+    if let Some(command) = cli.command.as_ref().and_then(Commands::config_command) {
+        if let Err(e) = command.run(&config_args) {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
 
     // Load configuration
-    let whisper_config = match load_whisper_config() {
-        Ok(cfg) => cfg,
+    let config = match config_args.load() {
+        Ok(config) => config.stt,
         Err(e) => {
-            error!("Failed to load Whisper configuration: {}", e);
+            error!("Failed to load configuration: {}", e);
             std::process::exit(1);
         }
     };
+    let mut whisper_config = WhisperConfig::from(&config);
 
-    // Create Whisper processor
-    let whisper = match WhisperProcessor::new(whisper_config.clone()) {
-        Ok(w) => Arc::new(w),
+    let grammar = match load_grammar(&config) {
+        Ok(grammar) => grammar,
         Err(e) => {
-            error!("Failed to create Whisper processor: {}", e);
+            error!("Failed to load command grammar: {}", e);
             std::process::exit(1);
         }
     };
 
+    if let Some(grammar) = &grammar {
+        whisper_config.initial_prompt = Some(grammar.initial_prompt());
+    }
+
+    let result = match cli.command {
+        Some(Commands::Transcribe { file, segments }) => {
+            transcribe(whisper_config, grammar, file, segments)
+        }
+        Some(Commands::Listen {
+            stdin,
+            sample_rate,
+            channels,
+        }) => {
+            let span = cli.traceparent.as_ref().map_or_else(Span::none, remote_span);
+            let stdin = stdin.then(|| AudioFormat::new(sample_rate, channels, 16));
+            listen(whisper_config, grammar, stdin)
+                .instrument(span)
+                .await
+        }
+        Some(Commands::Bench { file, iterations }) => bench(whisper_config, file, iterations),
+        Some(Commands::Serve { http, grpc }) => {
+            let http = http.unwrap_or_else(|| config.http_addr.clone());
+            let grpc = grpc.or_else(|| config.grpc_addr.clone());
+            serve(whisper_config, grammar, &config, &http, grpc.as_deref()).await
+        }
+        None => {
+            let grpc = config.grpc_addr.as_deref();
+            serve(whisper_config, grammar, &config, &config.http_addr, grpc).await
+        }
+        // Handled above
+        Some(Commands::Validate | Commands::PrintEffectiveConfig) => Ok(()),
+    };
+    aether_telemetry::shutdown();
+
+    if let Err(e) = result {
+        error!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+/// Transcribe a WAV file in one pass
+fn transcribe(
+    whisper_config: WhisperConfig,
+    grammar: Option<CommandGrammar>,
+    file: PathBuf,
+    segments: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let whisper = WhisperProcessor::new(whisper_config)?;
+    let audio = load_audio(&file)?;
+
+    let mut result = whisper.transcribe(&audio)?;
+    if let Some(grammar) = &grammar {
+        result.text = grammar.correct(&result.text);
+        for segment in &mut result.segments {
+            segment.text = grammar.correct(&segment.text);
+        }
+    }
+
+    if segments {
+        for segment in &result.segments {
+            println!(
+                "[{:>7.2}s -> {:>7.2}s] {}",
+                segment.start_ms as f32 / 1000.0,
+                segment.end_ms as f32 / 1000.0,
+                segment.text.trim()
+            );
+        }
+    } else {
+        println!("{}", result.text);
+    }
+
+    Ok(())
+}
+
+/// Stream the microphone, or PCM in `stdin` format, through StreamingSTT
+/// and print live captions
+async fn listen(
+    whisper_config: WhisperConfig,
+    grammar: Option<CommandGrammar>,
+    stdin: Option<AudioFormat>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let whisper = Arc::new(WhisperProcessor::new(whisper_config)?);
+
+    let (audio_tx, audio_rx) = mpsc::channel(32);
+    let input_format = match stdin {
+        Some(format) => {
+            read_stdin(audio_tx, format);
+            format
+        }
+        None => {
+            let (microphone, format) = Microphone::open(audio_tx)?;
+            info!(
+                "Listening on the microphone ({} Hz, {} channels), Ctrl-C to stop",
+                format.sample_rate, format.channels
+            );
+
+            // Closing the microphone ends the audio, which flushes the last words
+            tokio::spawn(async move {
+                aether_health::shutdown_signal().await;
+                drop(microphone);
+            });
+            format
+        }
+    };
+
+    let mut streaming_stt = StreamingSTT::new(whisper, input_format, StreamingConfig::default())?;
+    if let Some(grammar) = grammar {
+        streaming_stt = streaming_stt.with_grammar(grammar);
+    }
+    streaming_stt.start().await?;
+    let mut events = streaming_stt.process_stream(audio_rx).await;
+
+    let mut stdout = std::io::stdout();
+    while let Some(event) = events.recv().await {
+        match event {
+            StreamingEvent::Partial { text, .. } => {
+                print!("\r\x1b[K{}", text);
+                stdout.flush()?;
+            }
+            StreamingEvent::Final { text, .. } | StreamingEvent::Corrected { text, .. } => {
+                println!("\r\x1b[K{}", text);
+            }
+            StreamingEvent::Warning { message, .. } => eprintln!("\nwarning: {}", message),
+            StreamingEvent::Error { message } => eprintln!("\nerror: {}", message),
+            StreamingEvent::EndOfSpeech => break,
+        }
+    }
+
+    println!();
+    Ok(())
+}
+
+/// Read raw 16-bit PCM in `format` from stdin into `audio_tx`, until EOF
+fn read_stdin(audio_tx: mpsc::Sender<Vec<f32>>, format: AudioFormat) {
+    // 100ms of 16-bit PCM per read
+    let bytes_per_read = (format.sample_rate as usize / 10) * format.channels as usize * 2;
+
+    tokio::spawn(async move {
+        let mut stdin = tokio::io::stdin();
+        let mut buf = vec![0u8; bytes_per_read];
+
+        loop {
+            match stdin.read(&mut buf).await {
+                Ok(0) => break,
+                Ok(n) => {
+                    let pcm: Vec<i16> = buf[..n - n % 2]
+                        .chunks_exact(2)
+                        .map(|b| i16::from_le_bytes([b[0], b[1]]))
+                        .collect();
+
+                    if pcm.is_empty() {
+                        continue;
+                    }
+
+                    if audio_tx.send(AudioPreprocessor::i16_to_f32(&pcm)).await.is_err() {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    error!("Failed to read stdin: {}", e);
+                    break;
+                }
+            }
+        }
+    });
+}
+
+/// The default input device, capturing until dropped
+///
+/// A cpal stream must stay on the thread that built it, so it lives on a
+/// thread of its own.
+struct Microphone {
+    _stop: std::sync::mpsc::Sender<()>,
+}
+
+impl Microphone {
+    /// Start capturing into `audio_tx`; returns the device's format
+    fn open(audio_tx: mpsc::Sender<Vec<f32>>) -> Result<(Self, AudioFormat), String> {
+        let (stop_tx, stop_rx) = std::sync::mpsc::channel::<()>();
+        let (ready_tx, ready_rx) = std::sync::mpsc::channel();
+
+        std::thread::spawn(move || match input_stream(audio_tx) {
+            Ok((stream, format)) => {
+                let _ = ready_tx.send(Ok(format));
+                // Returns once the microphone is dropped
+                let _ = stop_rx.recv();
+                drop(stream);
+            }
+            Err(e) => {
+                let _ = ready_tx.send(Err(e));
+            }
+        });
+
+        let format = ready_rx
+            .recv()
+            .map_err(|_| "microphone thread exited".to_string())??;
+        Ok((Self { _stop: stop_tx }, format))
+    }
+}
+
+/// Build and start a stream from the default input device
+fn input_stream(audio_tx: mpsc::Sender<Vec<f32>>) -> Result<(cpal::Stream, AudioFormat), String> {
+    use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+
+    let device = cpal::default_host()
+        .default_input_device()
+        .ok_or("no audio input device")?;
+    let supported = device.default_input_config().map_err(|e| e.to_string())?;
+    let format = AudioFormat::new(supported.sample_rate().0, supported.channels(), 32);
+    let config = supported.config();
+
+    let stream = match supported.sample_format() {
+        cpal::SampleFormat::F32 => device.build_input_stream(
+            &config,
+            move |data: &[f32], _: &cpal::InputCallbackInfo| deliver(&audio_tx, data.to_vec()),
+            microphone_error,
+            None,
+        ),
+        cpal::SampleFormat::I16 => device.build_input_stream(
+            &config,
+            move |data: &[i16], _: &cpal::InputCallbackInfo| {
+                deliver(&audio_tx, AudioPreprocessor::i16_to_f32(data))
+            },
+            microphone_error,
+            None,
+        ),
+        other => return Err(format!("unsupported microphone sample format {:?}", other)),
+    }
+    .map_err(|e| e.to_string())?;
+
+    stream.play().map_err(|e| e.to_string())?;
+    Ok((stream, format))
+}
+
+/// Queue captured audio without blocking the audio thread
+fn deliver(audio_tx: &mpsc::Sender<Vec<f32>>, audio: Vec<f32>) {
+    if audio_tx.try_send(audio).is_err() {
+        debug!("Transcription is behind, dropping microphone audio");
+    }
+}
+
+fn microphone_error(e: cpal::StreamError) {
+    error!("Microphone error: {}", e);
+}
+
+/// Report processing time and real-time factor for a WAV file
+fn bench(
+    whisper_config: WhisperConfig,
+    file: PathBuf,
+    iterations: usize,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let model_path = whisper_config.model_path.clone();
+    let whisper = WhisperProcessor::new(whisper_config)?;
+    let audio = load_audio(&file)?;
+    let audio_secs = audio.len() as f64 / WHISPER_SAMPLE_RATE as f64;
+
+    // Warm-up run (not timed)
+    whisper.transcribe(&audio)?;
+
+    let iterations = iterations.max(1);
+    let mut total = std::time::Duration::ZERO;
+    for _ in 0..iterations {
+        let start = std::time::Instant::now();
+        whisper.transcribe(&audio)?;
+        total += start.elapsed();
+    }
+
+    let avg_secs = total.as_secs_f64() / iterations as f64;
+
+    println!("Model:            {}", model_path.display());
+    println!("File:             {}", file.display());
+    println!("Audio duration:   {:.2}s", audio_secs);
+    println!("Iterations:       {}", iterations);
+    println!("Avg processing:   {:.3}s", avg_secs);
+    println!("Real-time factor: {:.3}", avg_secs / audio_secs.max(f64::EPSILON));
+
+    Ok(())
+}
+
+/// Load a WAV file and convert it to 16kHz mono
+fn load_audio(file: &std::path::Path) -> Result<Vec<f32>, Box<dyn std::error::Error>> {
+    let (samples, format) = AudioPreprocessor::read_wav(file)?;
+    let preprocessor = AudioPreprocessor::new(format)?;
+
+    Ok(preprocessor.process(&samples)?)
+}
+
+/// Run the HTTP service
+async fn serve(
+    whisper_config: WhisperConfig,
+    grammar: Option<CommandGrammar>,
+    config: &SttSection,
+    http_addr: &str,
+    grpc_addr: Option<&str>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    info!("Starting AetherOS STT Service");
+
+    #[cfg(not(feature = "grpc"))]
+    if grpc_addr.is_some() {
+        return Err("gRPC needs stt-service built with the grpc feature".into());
+    }
+
+    let health = Health::new("stt-service", env!("CARGO_PKG_VERSION"));
+    let model = health.check("model");
+
+    // Create Whisper processor
+    let whisper = Arc::new(WhisperProcessor::new(whisper_config.clone())?);
+    model.ready();
+
     info!("Whisper model loaded: {:?}", whisper_config.model_path);
     info!("Language: {}, Threads: {}", whisper_config.language, whisper_config.num_threads);
 
     // Create streaming STT
     let input_format = AudioFormat::whisper_format();
-    let streaming_config = StreamingConfig::default();
+    let streaming_config = StreamingConfig {
+        archive: load_archive_config(config),
+        ..Default::default()
+    };
 
-    let streaming_stt = match StreamingSTT::new(whisper, input_format, streaming_config) {
-        Ok(stt) => stt,
-        Err(e) => {
-            error!("Failed to create streaming STT: {}", e);
-            std::process::exit(1);
+    let mut streaming_stt = StreamingSTT::new(whisper, input_format, streaming_config)?;
+    if let Some(grammar) = grammar {
+        streaming_stt = streaming_stt.with_grammar(grammar);
+    }
+
+    // Optional larger model for re-transcribing low-confidence finals
+    if let Some(refiner_path) = &config.refiner_model_path {
+        let refiner_config = WhisperConfig {
+            model_path: refiner_path.clone(),
+            ..whisper_config.clone()
+        };
+
+        match WhisperProcessor::new(refiner_config) {
+            Ok(refiner) => streaming_stt = streaming_stt.with_refiner(Arc::new(refiner)),
+            Err(e) => error!("Failed to load refiner model, corrections disabled: {}", e),
         }
-    };
+    }
+
+    // Only gRPC streams audio in this mode
+    #[cfg_attr(not(feature = "grpc"), allow(unused_variables))]
+    let streaming_stt = Arc::new(streaming_stt);
+    let sessions = SessionRegistry::new();
 
     info!("STT service initialized successfully");
     info!("Ready to process audio");
 
-    // Start HTTP server for health checks
+    let state = AppState {
+        sessions: sessions.clone(),
+        health,
+    };
+
+    // Start HTTP server for health checks and metrics
     let app = Router::new()
-        .route("/health", get(health_check));
+        .route("/health", get(health_check))
+        .route(aether_health::LIVENESS_PATH, get(liveness))
+        .route(aether_health::READINESS_PATH, get(readiness))
+        .route("/metrics", get(metrics))
+        .with_state(state);
 
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:8002")
-        .await
-        .expect("Failed to bind to port 8002");
+    let listener = tokio::net::TcpListener::bind(http_addr).await?;
+
+    info!("HTTP server listening on http://{}", http_addr);
+
+    let http = axum::serve(listener, app).with_graceful_shutdown(aether_health::shutdown_signal());
 
-    info!("HTTP server listening on http://0.0.0.0:8002");
+    #[cfg(feature = "grpc")]
+    if let Some(grpc_addr) = grpc_addr {
+        let grpc = stt_processor::GrpcServer::new(streaming_stt)
+            .with_sessions(sessions)
+            .serve(grpc_addr.parse()?, aether_health::shutdown_signal());
+        info!("gRPC server listening on {}", grpc_addr);
+
+        let (http, grpc) = tokio::join!(http, grpc);
+        http?;
+        grpc?;
+
+        info!("Shutting down STT service");
+        return Ok(());
+    }
 
     // Start server
-    axum::serve(listener, app)
-        .await
-        .expect("Failed to start HTTP server");
+    http.await?;
 
     info!("Shutting down STT service");
+    Ok(())
 }
 
-/// Load Whisper configuration from environment
-fn load_whisper_config() -> Result<WhisperConfig, Box<dyn std::error::Error>> {
-    let model_path = std::env::var("WHISPER_MODEL_PATH")
-        .unwrap_or_else(|_| "models/ggml-base.en.bin".to_string());
-
-    let language = std::env::var("WHISPER_LANGUAGE").unwrap_or_else(|_| "en".to_string());
+/// Session archive configuration, if `stt.archive_dir` is set
+fn load_archive_config(config: &SttSection) -> Option<ArchiveConfig> {
+    let root = config.archive_dir.clone()?;
+    let retention_days = config.archive_retention_days;
 
-    let num_threads = std::env::var("WHISPER_THREADS")
-        .ok()
-        .and_then(|s| s.parse().ok())
-        .unwrap_or_else(|| std::thread::available_parallelism().map(|n| n.get()).unwrap_or(4));
+    info!("Session archival enabled: {} (retention: {:?} days)", root.display(), retention_days);
 
-    let use_gpu = std::env::var("WHISPER_USE_GPU")
-        .ok()
-        .and_then(|s| s.parse().ok())
-        .unwrap_or(true);
-
-    Ok(WhisperConfig {
-        model_path: model_path.into(),
-        language,
-        num_threads,
-        use_gpu,
-        translate: false,
-        print_progress: false,
-        max_segment_length: 1000,
+    Some(ArchiveConfig {
+        root,
+        retention_days,
     })
 }
+
+/// Load the optional command grammar named by `stt.grammar_path`
+fn load_grammar(config: &SttSection) -> Result<Option<CommandGrammar>, Box<dyn std::error::Error>> {
+    match &config.grammar_path {
+        Some(path) => Ok(Some(CommandGrammar::load(path)?)),
+        None => Ok(None),
+    }
+}
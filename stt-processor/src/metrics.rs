@@ -0,0 +1,250 @@
+//! Metrics export module
+//!
+//! Renders the statistics of live streaming sessions in the Prometheus
+//! text exposition format for the service's `/metrics` endpoint.
+
+use crate::streaming::{StreamingSTT, StreamingStats};
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Content type for the Prometheus text exposition format
+pub const PROMETHEUS_CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+/// Sessions currently streaming, shared between the servers that run them
+/// and `/metrics`
+#[derive(Clone, Default)]
+pub struct SessionRegistry {
+    sessions: Arc<Mutex<HashMap<u64, Arc<StreamingSTT>>>>,
+    next_id: Arc<AtomicU64>,
+}
+
+impl SessionRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Track `session` until the returned guard is dropped
+    pub fn register(&self, session: Arc<StreamingSTT>) -> RegisteredSession {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.sessions.lock().insert(id, session);
+
+        RegisteredSession {
+            sessions: self.sessions.clone(),
+            id,
+        }
+    }
+
+    /// Number of registered sessions
+    pub fn len(&self) -> usize {
+        self.sessions.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sessions.lock().is_empty()
+    }
+
+    /// Statistics of every registered session
+    pub async fn stats(&self) -> Vec<StreamingStats> {
+        let sessions: Vec<_> = self.sessions.lock().values().cloned().collect();
+
+        let mut stats = Vec::with_capacity(sessions.len());
+        for session in sessions {
+            stats.push(session.stats().await);
+        }
+        stats
+    }
+}
+
+/// Keeps a session in its [`SessionRegistry`]; dropping it removes the session
+pub struct RegisteredSession {
+    sessions: Arc<Mutex<HashMap<u64, Arc<StreamingSTT>>>>,
+    id: u64,
+}
+
+impl Drop for RegisteredSession {
+    fn drop(&mut self) {
+        self.sessions.lock().remove(&self.id);
+    }
+}
+
+/// Render the statistics of live sessions as Prometheus metrics
+///
+/// Counts and queue sizes are summed across sessions, the real-time factor
+/// is the worst (highest) one and confidence is averaged over the chunks
+/// each session transcribed.
+pub fn render_prometheus(sessions: &[StreamingStats], model_loaded: bool) -> String {
+    let sum = |field: fn(&StreamingStats) -> usize| -> f64 {
+        sessions.iter().map(|s| field(s) as f64).sum()
+    };
+    let active = sessions.iter().filter(|s| s.is_active).count();
+    let real_time_factor = sessions
+        .iter()
+        .map(|s| s.real_time_factor as f64)
+        .fold(0.0, f64::max);
+    let chunks = sum(|s| s.chunks_processed);
+    let average_confidence = if chunks > 0.0 {
+        sessions
+            .iter()
+            .map(|s| s.average_confidence as f64 * s.chunks_processed as f64)
+            .sum::<f64>()
+            / chunks
+    } else {
+        0.0
+    };
+
+    let mut out = String::new();
+
+    write_metric(
+        &mut out,
+        "stt_model_loaded",
+        "gauge",
+        "Whether the Whisper model is loaded (1) or not (0)",
+        if model_loaded { 1.0 } else { 0.0 },
+    );
+    write_metric(
+        &mut out,
+        "stt_sessions_active",
+        "gauge",
+        "Streaming sessions currently active",
+        active as f64,
+    );
+    write_metric(
+        &mut out,
+        "stt_real_time_factor",
+        "gauge",
+        "Highest processing time divided by audio duration among live sessions",
+        real_time_factor,
+    );
+    write_metric(
+        &mut out,
+        "stt_chunk_queue_depth",
+        "gauge",
+        "Full audio chunks waiting to be transcribed",
+        sum(|s| s.queue_depth),
+    );
+    write_metric(
+        &mut out,
+        "stt_buffer_samples",
+        "gauge",
+        "Samples currently held in audio buffers",
+        sum(|s| s.buffer_size),
+    );
+    write_metric(
+        &mut out,
+        "stt_average_confidence",
+        "gauge",
+        "Mean transcription confidence across live sessions",
+        average_confidence,
+    );
+    write_metric(
+        &mut out,
+        "stt_samples_processed_total",
+        "counter",
+        "Samples processed by live sessions",
+        sum(|s| s.total_samples_processed),
+    );
+    write_metric(
+        &mut out,
+        "stt_chunks_processed_total",
+        "counter",
+        "Chunks transcribed by live sessions",
+        chunks,
+    );
+    write_metric(
+        &mut out,
+        "stt_silent_chunks_skipped_total",
+        "counter",
+        "Chunks skipped by the silence gate in live sessions",
+        sum(|s| s.skipped_silent_chunks),
+    );
+    write_metric(
+        &mut out,
+        "stt_dropped_samples_total",
+        "counter",
+        "Samples dropped due to buffer overflow in live sessions",
+        sum(|s| s.dropped_samples),
+    );
+
+    out
+}
+
+fn write_metric(out: &mut String, name: &str, kind: &str, help: &str, value: f64) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+    let _ = writeln!(out, "{} {}", name, value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_stats() -> StreamingStats {
+        StreamingStats {
+            total_samples_processed: 16000,
+            chunks_processed: 2,
+            buffer_size: 800,
+            is_active: true,
+            last_transcription_length: 12,
+            queue_depth: 0,
+            dropped_samples: 42,
+            skipped_silent_chunks: 3,
+            silence_ms: 0,
+            real_time_factor: 0.25,
+            average_confidence: 0.85,
+        }
+    }
+
+    #[test]
+    fn test_render_includes_all_metrics() {
+        let output = render_prometheus(&[sample_stats()], true);
+
+        assert!(output.contains("stt_model_loaded 1"));
+        assert!(output.contains("stt_sessions_active 1"));
+        assert!(output.contains("stt_real_time_factor 0.25"));
+        assert!(output.contains("stt_chunk_queue_depth 0"));
+        assert!(output.contains("stt_dropped_samples_total 42"));
+        assert!(output.contains("stt_silent_chunks_skipped_total 3"));
+        assert!(output.contains("# TYPE stt_chunks_processed_total counter"));
+    }
+
+    #[test]
+    fn test_render_model_not_loaded() {
+        let output = render_prometheus(&[sample_stats()], false);
+        assert!(output.contains("stt_model_loaded 0"));
+    }
+
+    #[test]
+    fn test_render_aggregates_sessions() {
+        let busy = StreamingStats {
+            average_confidence: 0.75,
+            ..sample_stats()
+        };
+        let idle = StreamingStats {
+            chunks_processed: 6,
+            is_active: false,
+            dropped_samples: 8,
+            real_time_factor: 0.5,
+            average_confidence: 0.25,
+            ..sample_stats()
+        };
+        let output = render_prometheus(&[busy, idle], true);
+
+        assert!(output.contains("stt_sessions_active 1"));
+        assert!(output.contains("stt_real_time_factor 0.5"));
+        assert!(output.contains("stt_chunks_processed_total 8"));
+        assert!(output.contains("stt_dropped_samples_total 50"));
+        assert!(output.contains("stt_average_confidence 0.375"));
+    }
+
+    #[test]
+    fn test_render_no_sessions() {
+        let output = render_prometheus(&[], true);
+
+        assert!(output.contains("stt_sessions_active 0"));
+        assert!(output.contains("stt_real_time_factor 0"));
+        assert!(output.contains("stt_average_confidence 0"));
+    }
+}
@@ -1,15 +1,22 @@
-/// Streaming STT processor module
-///
-/// Handles real-time speech-to-text with chunked processing and context accumulation.
+//! Streaming STT processor module
+//!
+//! Handles real-time speech-to-text with chunked processing and context accumulation.
 
+use crate::archive::{self, ArchiveConfig, SessionArchive};
 use crate::audio_preprocessor::{AudioFormat, AudioPreprocessor, AudioSample, PreprocessorError};
+use crate::grammar::CommandGrammar;
 use crate::whisper_wrapper::{TranscriptionResult, WhisperError, WhisperProcessor};
+use aether_events::{ErrorEvent, Event, TranscriptFinal, TranscriptPartial};
+use parking_lot::Mutex;
+use serde::Serialize;
 use std::collections::VecDeque;
 use std::sync::Arc;
-use std::time::{Duration, Instant};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 use thiserror::Error;
-use tokio::sync::{mpsc, RwLock};
-use tracing::{debug, info, trace, warn};
+use tokio::sync::{broadcast, mpsc, RwLock};
+use tokio::task::JoinHandle;
+use tracing::field::Empty;
+use tracing::{debug, info, info_span, trace, warn, Span};
 
 /// Chunk size in milliseconds (500ms windows)
 pub const CHUNK_DURATION_MS: u64 = 500;
@@ -20,6 +27,18 @@ pub const CHUNK_OVERLAP_MS: u64 = 50;
 /// Maximum context buffer size in seconds
 pub const MAX_CONTEXT_DURATION_SECS: u64 = 30;
 
+/// Final results below this confidence are re-transcribed by the refiner model
+pub const DEFAULT_CORRECTION_THRESHOLD: f32 = 0.6;
+
+/// Chunks with RMS energy below this level are treated as silence
+pub const DEFAULT_SILENCE_RMS_THRESHOLD: f32 = 0.01;
+
+/// Capacity of the corrections broadcast channel
+const CORRECTIONS_CHANNEL_CAPACITY: usize = 32;
+
+/// Re-transcriptions in flight at once; the oldest is dropped for a new one
+const MAX_PENDING_CORRECTIONS: usize = 4;
+
 #[derive(Error, Debug)]
 pub enum StreamingError {
     #[error("Preprocessing error: {0}")]
@@ -42,13 +61,20 @@ pub enum StreamingError {
 }
 
 /// Streaming transcription event
-#[derive(Debug, Clone)]
+///
+/// All `*_ms` offsets are measured on the session clock started by `start()`.
+/// `captured_at_ms` is the Unix time (ms) the audio was captured, when the caller
+/// supplied it through `process_captured_chunk`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
 pub enum StreamingEvent {
     /// Partial transcription result (may change as more context arrives)
     Partial {
         text: String,
         confidence: f32,
+        /// Session offset of the end of the transcribed audio
         timestamp_ms: u64,
+        captured_at_ms: Option<u64>,
     },
 
     /// Final transcription result (stable, won't change)
@@ -57,17 +83,101 @@ pub enum StreamingEvent {
         confidence: f32,
         start_ms: u64,
         end_ms: u64,
+        /// English translation, when `translate_finals` is enabled
+        translation: Option<String>,
+        captured_at_ms: Option<u64>,
+    },
+
+    /// Improved transcript of an earlier low-confidence `Final` result,
+    /// produced by the larger refiner model
+    Corrected {
+        original: String,
+        text: String,
+        confidence: f32,
+        start_ms: u64,
+        end_ms: u64,
+        captured_at_ms: Option<u64>,
     },
 
     /// End of speech detected
     EndOfSpeech,
 
+    /// Non-fatal problem, e.g. audio lost to a buffer overflow
+    Warning {
+        message: String,
+        dropped_samples: usize,
+    },
+
     /// Error occurred
     Error {
         message: String,
     },
 }
 
+impl StreamingEvent {
+    /// The shared event to publish for this one: transcripts and errors;
+    /// `None` for the rest
+    pub fn to_event(&self) -> Option<Event> {
+        match self {
+            StreamingEvent::Partial {
+                text,
+                confidence,
+                timestamp_ms,
+                ..
+            } => Some(
+                TranscriptPartial {
+                    text: text.clone(),
+                    confidence: *confidence,
+                    timestamp_ms: *timestamp_ms,
+                }
+                .into(),
+            ),
+            StreamingEvent::Final {
+                text,
+                confidence,
+                start_ms,
+                end_ms,
+                ..
+            } => Some(
+                TranscriptFinal {
+                    text: text.clone(),
+                    confidence: *confidence,
+                    start_ms: *start_ms,
+                    end_ms: *end_ms,
+                }
+                .into(),
+            ),
+            StreamingEvent::Error { message } => Some(
+                ErrorEvent {
+                    message: message.clone(),
+                    code: None,
+                }
+                .into(),
+            ),
+            StreamingEvent::Corrected { .. }
+            | StreamingEvent::EndOfSpeech
+            | StreamingEvent::Warning { .. } => None,
+        }
+    }
+}
+
+/// What to do when incoming audio would exceed `max_buffer_duration_secs`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverflowPolicy {
+    /// Discard the oldest buffered audio
+    #[default]
+    DropOldest,
+
+    /// Discard the part of the incoming audio that does not fit
+    DropNewest,
+
+    /// Transcribe buffered chunks until the incoming audio fits
+    Block,
+
+    /// Reject the incoming audio with `StreamingError::BufferOverflow`
+    Error,
+}
+
 /// Streaming configuration
 #[derive(Debug, Clone)]
 pub struct StreamingConfig {
@@ -88,6 +198,21 @@ pub struct StreamingConfig {
 
     /// Maximum queue size before backpressure
     pub max_queue_size: usize,
+
+    /// Behavior when the audio buffer is full
+    pub overflow_policy: OverflowPolicy,
+
+    /// Run an extra translate-to-English pass on final results
+    pub translate_finals: bool,
+
+    /// Final results below this confidence are re-run through the refiner model
+    pub correction_threshold: f32,
+
+    /// Skip Whisper for chunks whose RMS energy is below this level (0.0 disables gating)
+    pub silence_rms_threshold: f32,
+
+    /// Write each transcribed utterance to disk (None = archival disabled)
+    pub archive: Option<ArchiveConfig>,
 }
 
 impl Default for StreamingConfig {
@@ -99,27 +224,86 @@ impl Default for StreamingConfig {
             min_partial_confidence: 0.5,
             enable_partial_results: true,
             max_queue_size: 100,
+            overflow_policy: OverflowPolicy::default(),
+            translate_finals: false,
+            correction_threshold: DEFAULT_CORRECTION_THRESHOLD,
+            silence_rms_threshold: DEFAULT_SILENCE_RMS_THRESHOLD,
+            archive: None,
         }
     }
 }
 
+/// Session-clock placement of a transcribed window
+#[derive(Debug, Clone, Copy)]
+struct EventTiming {
+    start_ms: u64,
+    end_ms: u64,
+    captured_at_ms: Option<u64>,
+}
+
 /// Streaming STT processor state
 struct StreamingState {
     audio_buffer: VecDeque<AudioSample>,
+    /// Session sample index of `audio_buffer[0]`
+    buffer_offset_samples: usize,
     last_transcription: String,
     total_samples_processed: usize,
     chunks_processed: usize,
+    skipped_silent_chunks: usize,
+    /// Consecutive silent audio since the last non-silent chunk
+    silence_ms: u64,
     is_active: bool,
+    /// Monotonic clock started by `start()`
+    session_start: Instant,
+    /// Session offset of sample 0, fixed when the first audio arrives
+    audio_origin_ms: Option<u64>,
+    /// Caller-supplied capture times as (session sample index, Unix ms)
+    capture_marks: VecDeque<(usize, u64)>,
+    dropped_samples: usize,
+    transcribed_audio_ms: u64,
+    processing_time_ms: u64,
+    confidence_sum: f32,
 }
 
 impl StreamingState {
+    /// Place the window `[start_sample, start_sample + len)` on the session clock
+    fn timing(&mut self, start_sample: usize, len: usize) -> EventTiming {
+        // Marks before the buffer start are no longer needed, except the last one
+        while self.capture_marks.len() > 1 && self.capture_marks[1].0 <= start_sample {
+            self.capture_marks.pop_front();
+        }
+
+        let origin = self.audio_origin_ms.unwrap_or(0);
+        let captured_at_ms = self
+            .capture_marks
+            .front()
+            .filter(|(sample, _)| *sample <= start_sample)
+            .map(|(sample, unix_ms)| unix_ms + samples_to_ms(start_sample - sample));
+
+        EventTiming {
+            start_ms: origin + samples_to_ms(start_sample),
+            end_ms: origin + samples_to_ms(start_sample + len),
+            captured_at_ms,
+        }
+    }
+
     fn new() -> Self {
         Self {
             audio_buffer: VecDeque::new(),
+            buffer_offset_samples: 0,
             last_transcription: String::new(),
             total_samples_processed: 0,
             chunks_processed: 0,
+            skipped_silent_chunks: 0,
+            silence_ms: 0,
             is_active: false,
+            session_start: Instant::now(),
+            audio_origin_ms: None,
+            capture_marks: VecDeque::new(),
+            dropped_samples: 0,
+            transcribed_audio_ms: 0,
+            processing_time_ms: 0,
+            confidence_sum: 0.0,
         }
     }
 }
@@ -127,9 +311,15 @@ impl StreamingState {
 /// Streaming STT processor
 pub struct StreamingSTT {
     whisper: Arc<WhisperProcessor>,
+    refiner: Option<Arc<WhisperProcessor>>,
+    grammar: Option<Arc<CommandGrammar>>,
     preprocessor: AudioPreprocessor,
     config: StreamingConfig,
     state: Arc<RwLock<StreamingState>>,
+    corrections: broadcast::Sender<StreamingEvent>,
+    pending_corrections: Arc<Mutex<Vec<JoinHandle<()>>>>,
+    archive: Arc<Mutex<Option<SessionArchive>>>,
+    archive_writes: Arc<Mutex<Vec<JoinHandle<()>>>>,
 }
 
 impl StreamingSTT {
@@ -144,115 +334,536 @@ impl StreamingSTT {
         info!("Initializing streaming STT");
         info!("Chunk duration: {}ms, overlap: {}ms", config.chunk_duration_ms, config.overlap_ms);
 
+        let (corrections, _) = broadcast::channel(CORRECTIONS_CHANNEL_CAPACITY);
+
         Ok(Self {
             whisper,
+            refiner: None,
+            grammar: None,
             preprocessor,
             config,
             state: Arc::new(RwLock::new(StreamingState::new())),
+            corrections,
+            pending_corrections: Arc::new(Mutex::new(Vec::new())),
+            archive: Arc::new(Mutex::new(None)),
+            archive_writes: Arc::new(Mutex::new(Vec::new())),
         })
     }
 
+    /// Enable two-tier transcription with a larger refiner model
+    ///
+    /// The primary model (typically `tiny.en`) keeps partials fast; final results
+    /// below `correction_threshold` are re-transcribed by the refiner in the
+    /// background and reported as `StreamingEvent::Corrected`.
+    pub fn with_refiner(mut self, refiner: Arc<WhisperProcessor>) -> Self {
+        info!("Refiner model enabled: {:?}", refiner.config().model_path);
+        self.refiner = Some(refiner);
+        self
+    }
+
+    /// Post-correct transcripts toward the given command phrases
+    ///
+    /// For decode-time biasing, also set `WhisperConfig::initial_prompt` to
+    /// `grammar.initial_prompt()` when creating the Whisper processor.
+    pub fn with_grammar(mut self, grammar: CommandGrammar) -> Self {
+        info!("Command grammar enabled: {} intents", grammar.intents.len());
+        self.grammar = Some(Arc::new(grammar));
+        self
+    }
+
+    /// A new session with this one's models, grammar and config, e.g. one
+    /// per client of a service
+    pub fn new_session(&self) -> Result<Self, StreamingError> {
+        let mut session = Self::new(
+            self.whisper.clone(),
+            self.preprocessor.input_format(),
+            self.config.clone(),
+        )?;
+        session.refiner = self.refiner.clone();
+        session.grammar = self.grammar.clone();
+        Ok(session)
+    }
+
+    /// Subscribe to `Corrected` events produced by the refiner model
+    pub fn subscribe_corrections(&self) -> broadcast::Receiver<StreamingEvent> {
+        self.corrections.subscribe()
+    }
+
+    /// Wait until all in-flight re-transcriptions have completed
+    pub async fn wait_for_corrections(&self) {
+        let handles: Vec<JoinHandle<()>> = self.pending_corrections.lock().drain(..).collect();
+
+        for handle in handles {
+            if let Err(e) = handle.await {
+                warn!("Correction task failed: {}", e);
+            }
+        }
+    }
+
     /// Start streaming transcription
     pub async fn start(&self) -> Result<(), StreamingError> {
         let mut state = self.state.write().await;
-        state.is_active = true;
-        state.audio_buffer.clear();
-        state.last_transcription.clear();
-        state.total_samples_processed = 0;
-        state.chunks_processed = 0;
+        *state = StreamingState {
+            is_active: true,
+            ..StreamingState::new()
+        };
+
+        if let Some(config) = &self.config.archive {
+            if let Err(e) = archive::apply_retention(config) {
+                warn!("Archive retention cleanup failed: {}", e);
+            }
+
+            match SessionArchive::create(config) {
+                Ok(session) => *self.archive.lock() = Some(session),
+                Err(e) => warn!("Failed to create session archive, archival disabled: {}", e),
+            }
+        }
 
         info!("Streaming STT started");
         Ok(())
     }
 
     /// Stop streaming transcription
-    pub async fn stop(&self) -> Result<(), StreamingError> {
+    ///
+    /// Flushes any buffered audio first and returns the resulting `Final` event.
+    /// The session archive's last utterance is on disk once this returns.
+    pub async fn stop(&self) -> Result<Option<StreamingEvent>, StreamingError> {
+        let event = self.flush().await?;
+        self.wait_for_archive().await;
+
         let mut state = self.state.write().await;
         state.is_active = false;
 
         info!("Streaming STT stopped");
-        Ok(())
+        Ok(event)
     }
 
-    /// Process audio chunk
-    pub async fn process_chunk(&self, audio: &[AudioSample]) -> Result<Option<StreamingEvent>, StreamingError> {
+    /// Process audio captured at a known time
+    ///
+    /// `captured_at` is the wall-clock time of the first sample; it is carried
+    /// through to `captured_at_ms` on the resulting events.
+    pub async fn process_captured_chunk(
+        &self,
+        audio: &[AudioSample],
+        captured_at: SystemTime,
+    ) -> Result<Vec<StreamingEvent>, StreamingError> {
+        let unix_ms = captured_at
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+
+        self.ingest(audio, Some(unix_ms)).await
+    }
+
+    /// Flush buffered audio shorter than a full chunk
+    ///
+    /// Transcribes the remaining buffer, emits a `Final` event and resets the
+    /// buffer. Returns `None` if there is no unprocessed audio left.
+    pub async fn flush(&self) -> Result<Option<StreamingEvent>, StreamingError> {
         let mut state = self.state.write().await;
 
-        if !state.is_active {
+        // After a chunk, the buffer still holds the overlap that was already transcribed
+        let overlap_samples = (self.config.overlap_ms * 16) as usize;
+        let chunks_seen = state.chunks_processed + state.skipped_silent_chunks;
+        let already_seen = if chunks_seen > 0 { overlap_samples } else { 0 };
+
+        if state.audio_buffer.len() <= already_seen {
+            state.audio_buffer.clear();
+            drop(state);
+            self.finish_utterance();
             return Ok(None);
         }
 
-        if audio.is_empty() {
+        let start_sample = state.buffer_offset_samples;
+        let remaining: Vec<AudioSample> = state.audio_buffer.drain(..).collect();
+        state.buffer_offset_samples += remaining.len();
+        let timing = state.timing(start_sample, remaining.len());
+
+        if self.is_silent(&remaining) {
+            state.skipped_silent_chunks += 1;
+            state.silence_ms += (remaining.len() * 1000 / 16000) as u64;
+            trace!("Skipping silent remainder of {} samples", remaining.len());
+            drop(state);
+            self.finish_utterance();
+            return Ok(None);
+        }
+
+        state.chunks_processed += 1;
+        state.silence_ms = 0;
+
+        // Release lock before transcription (can take time)
+        drop(state);
+
+        let result = self.transcribe(&remaining)?;
+        self.archive_window(start_sample, &remaining, &result, timing.start_ms);
+        self.finish_utterance();
+        let translation = self.translate_final(&remaining)?;
+        let duration_ms = samples_to_ms(remaining.len());
+        self.schedule_correction(&remaining, &result.text, result.confidence, timing);
+
+        let mut state = self.state.write().await;
+        state.transcribed_audio_ms += duration_ms;
+        state.processing_time_ms += result.processing_time_ms;
+        state.confidence_sum += result.confidence;
+        state.last_transcription.clear();
+
+        debug!(
+            "Flushed {} buffered samples: {} chars, confidence: {:.2}",
+            remaining.len(),
+            result.text.len(),
+            result.confidence
+        );
+
+        Ok(Some(StreamingEvent::Final {
+            text: result.text,
+            confidence: result.confidence,
+            start_ms: timing.start_ms,
+            end_ms: timing.end_ms,
+            translation,
+            captured_at_ms: timing.captured_at_ms,
+        }))
+    }
+
+    /// Re-transcribe a low-confidence final result with the refiner model
+    fn schedule_correction(
+        &self,
+        audio: &[AudioSample],
+        original: &str,
+        confidence: f32,
+        timing: EventTiming,
+    ) {
+        let Some(refiner) = self.refiner.clone() else {
+            return;
+        };
+
+        if confidence >= self.config.correction_threshold {
+            return;
+        }
+
+        debug!(
+            "Confidence {:.2} below {:.2}, scheduling re-transcription",
+            confidence, self.config.correction_threshold
+        );
+
+        // Part of the voice command's trace, though it finishes after it
+        let span = info_span!(
+            "stt_refine",
+            audio_ms = samples_to_ms(audio.len()),
+            confidence = Empty,
+            chars = Empty,
+        );
+        let audio = audio.to_vec();
+        let original = original.to_string();
+        let corrections = self.corrections.clone();
+        let grammar = self.grammar.clone();
+
+        let handle = tokio::spawn(async move {
+            let refine_span = span.clone();
+            let result = tokio::task::spawn_blocking(move || {
+                refine_span.in_scope(|| refiner.transcribe(&audio))
+            })
+            .await;
+
+            match result {
+                Ok(Ok(result)) => {
+                    record_transcription(&span, &result);
+                    let text = match &grammar {
+                        Some(grammar) => grammar.correct(&result.text),
+                        None => result.text,
+                    };
+
+                    // No subscribers is fine; the correction is simply dropped
+                    let _ = corrections.send(StreamingEvent::Corrected {
+                        original,
+                        text,
+                        confidence: result.confidence,
+                        start_ms: timing.start_ms,
+                        end_ms: timing.end_ms,
+                        captured_at_ms: timing.captured_at_ms,
+                    });
+                }
+                Ok(Err(e)) => warn!("Re-transcription failed: {}", e),
+                Err(e) => warn!("Re-transcription task panicked: {}", e),
+            }
+        });
+
+        let mut pending = self.pending_corrections.lock();
+        pending.retain(|correction| !correction.is_finished());
+        if pending.len() >= MAX_PENDING_CORRECTIONS {
+            warn!(
+                "{} corrections in flight, dropping the oldest",
+                MAX_PENDING_CORRECTIONS
+            );
+            pending.remove(0).abort();
+        }
+        pending.push(handle);
+    }
+
+    /// Transcribe audio with the primary model and apply grammar correction
+    fn transcribe(&self, audio: &[AudioSample]) -> Result<TranscriptionResult, StreamingError> {
+        let span = info_span!(
+            "stt_transcribe",
+            audio_ms = samples_to_ms(audio.len()),
+            confidence = Empty,
+            chars = Empty,
+        );
+        let _entered = span.enter();
+        let mut result = self.whisper.transcribe(audio)?;
+
+        if let Some(grammar) = &self.grammar {
+            result.text = grammar.correct(&result.text);
+        }
+
+        record_transcription(&span, &result);
+        Ok(result)
+    }
+
+    /// Add a transcribed window to the session archive's utterance, if enabled
+    fn archive_window(
+        &self,
+        start_sample: usize,
+        audio: &[AudioSample],
+        result: &TranscriptionResult,
+        start_ms: u64,
+    ) {
+        if let Some(session) = self.archive.lock().as_mut() {
+            session.record(start_sample, audio, result, start_ms);
+        }
+    }
+
+    /// End the archived utterance, if any, and write it on a blocking thread
+    fn finish_utterance(&self) {
+        let finished = self
+            .archive
+            .lock()
+            .as_mut()
+            .and_then(|s| s.finish_utterance());
+        let Some(utterance) = finished else {
+            return;
+        };
+
+        let handle = tokio::task::spawn_blocking(move || {
+            if let Err(e) = utterance.write() {
+                warn!("Failed to archive utterance: {}", e);
+            }
+        });
+
+        let mut writes = self.archive_writes.lock();
+        writes.retain(|write| !write.is_finished());
+        writes.push(handle);
+    }
+
+    /// Wait until the archived utterances have been written
+    async fn wait_for_archive(&self) {
+        let handles: Vec<JoinHandle<()>> = self.archive_writes.lock().drain(..).collect();
+
+        for handle in handles {
+            if let Err(e) = handle.await {
+                warn!("Archive write task failed: {}", e);
+            }
+        }
+    }
+
+    /// Translate a finalized utterance when dual output is enabled
+    ///
+    /// Partials are never translated to keep the extra pass off the hot path.
+    fn translate_final(&self, audio: &[AudioSample]) -> Result<Option<String>, StreamingError> {
+        if !self.config.translate_finals {
             return Ok(None);
         }
 
+        let result = self.whisper.translate(audio)?;
+        Ok(Some(result.text))
+    }
+
+    /// Process audio chunk
+    ///
+    /// Returns the transcription event, if a chunk was completed. Overflow
+    /// warnings are only reported through `process_chunk_events` and `process_stream`.
+    pub async fn process_chunk(&self, audio: &[AudioSample]) -> Result<Option<StreamingEvent>, StreamingError> {
+        let events = self.process_chunk_events(audio).await?;
+
+        Ok(events
+            .into_iter()
+            .rfind(|event| !matches!(event, StreamingEvent::Warning { .. })))
+    }
+
+    /// Process audio chunk, returning every event it produced
+    ///
+    /// Besides the transcription result this includes `Warning` events when the
+    /// buffer overflowed, and any extra chunks transcribed under `OverflowPolicy::Block`.
+    pub async fn process_chunk_events(&self, audio: &[AudioSample]) -> Result<Vec<StreamingEvent>, StreamingError> {
+        self.ingest(audio, None).await
+    }
+
+    /// Buffer incoming audio and transcribe a chunk if one is complete
+    async fn ingest(
+        &self,
+        audio: &[AudioSample],
+        captured_at_ms: Option<u64>,
+    ) -> Result<Vec<StreamingEvent>, StreamingError> {
+        let mut events = Vec::new();
+
+        if !self.state.read().await.is_active || audio.is_empty() {
+            return Ok(events);
+        }
+
         trace!("Processing chunk: {} samples", audio.len());
 
         // Preprocess audio
         let processed = self.preprocessor.process(audio)?;
+        let max_samples = (self.config.max_buffer_duration_secs * 16000) as usize;
+
+        // Block: catch up on buffered chunks until the new audio fits
+        if self.config.overflow_policy == OverflowPolicy::Block {
+            loop {
+                let buffered = self.state.read().await.audio_buffer.len();
+                if buffered + processed.len() <= max_samples || buffered < self.chunk_samples() {
+                    break;
+                }
+
+                if let Some(event) = self.transcribe_next_chunk().await? {
+                    events.push(event);
+                }
+            }
+        }
+
+        let mut state = self.state.write().await;
+
+        // Anchor sample 0 on the session clock when the first audio arrives
+        if state.audio_origin_ms.is_none() {
+            let elapsed_ms = state.session_start.elapsed().as_millis() as u64;
+            state.audio_origin_ms = Some(elapsed_ms.saturating_sub(samples_to_ms(processed.len())));
+        }
+
+        let first_sample = state.buffer_offset_samples + state.audio_buffer.len();
 
         // Add to buffer
-        state.audio_buffer.extend(processed.iter());
         state.total_samples_processed += processed.len();
+        let overflow = (state.audio_buffer.len() + processed.len()).saturating_sub(max_samples);
 
-        // Check buffer size limit
-        let max_samples = (self.config.max_buffer_duration_secs * 16000) as usize;
-        if state.audio_buffer.len() > max_samples {
-            warn!("Buffer overflow, dropping oldest samples");
-            let to_drop = state.audio_buffer.len() - max_samples;
-            state.audio_buffer.drain(0..to_drop);
+        if overflow == 0 {
+            state.audio_buffer.extend(processed.iter());
+        } else {
+            match self.config.overflow_policy {
+                OverflowPolicy::Error => {
+                    state.dropped_samples += processed.len();
+                    warn!("Buffer overflow, rejecting {} samples", processed.len());
+                    return Err(StreamingError::BufferOverflow);
+                }
+                OverflowPolicy::DropNewest => {
+                    let keep = processed.len() - overflow;
+                    state.audio_buffer.extend(processed[..keep].iter());
+                    state.dropped_samples += overflow;
+                    warn!("Buffer overflow, dropped {} newest samples", overflow);
+                }
+                // Block falls back to dropping when the new audio alone exceeds the buffer
+                OverflowPolicy::DropOldest | OverflowPolicy::Block => {
+                    state.audio_buffer.extend(processed.iter());
+                    let to_drop = state.audio_buffer.len() - max_samples;
+                    state.audio_buffer.drain(0..to_drop);
+                    state.buffer_offset_samples += to_drop;
+                    state.dropped_samples += to_drop;
+                    warn!("Buffer overflow, dropped {} oldest samples", to_drop);
+                }
+            }
+
+            events.push(StreamingEvent::Warning {
+                message: format!("Audio buffer overflow ({:?})", self.config.overflow_policy),
+                dropped_samples: overflow,
+            });
         }
 
-        // Check if we have enough for a chunk
-        let chunk_samples = (self.config.chunk_duration_ms * 16) as usize; // 16kHz * ms / 1000
+        if let Some(unix_ms) = captured_at_ms {
+            state.capture_marks.push_back((first_sample, unix_ms));
+        }
+
+        drop(state);
 
-        if state.audio_buffer.len() >= chunk_samples {
-            let chunk: Vec<AudioSample> = state.audio_buffer.iter().take(chunk_samples).copied().collect();
+        if let Some(event) = self.transcribe_next_chunk().await? {
+            events.push(event);
+        }
 
-            // Remove processed samples (minus overlap)
-            let overlap_samples = (self.config.overlap_ms * 16) as usize;
-            let to_remove = chunk_samples.saturating_sub(overlap_samples);
-            state.audio_buffer.drain(0..to_remove);
+        Ok(events)
+    }
 
-            state.chunks_processed += 1;
+    /// Transcribe one chunk from the front of the buffer, if enough audio is queued
+    async fn transcribe_next_chunk(&self) -> Result<Option<StreamingEvent>, StreamingError> {
+        let mut state = self.state.write().await;
 
-            // Release lock before transcription (can take time)
-            drop(state);
+        // Check if we have enough for a chunk
+        let chunk_samples = self.chunk_samples();
 
-            // Transcribe chunk
-            let result = self.whisper.transcribe(&chunk)?;
+        if state.audio_buffer.len() < chunk_samples {
+            return Ok(None);
+        }
 
-            // Determine event type
-            let event = if self.config.enable_partial_results {
-                StreamingEvent::Partial {
-                    text: result.text.clone(),
-                    confidence: result.confidence,
-                    timestamp_ms: (chunk_samples * 1000 / 16000) as u64,
-                }
-            } else {
-                StreamingEvent::Final {
-                    text: result.text.clone(),
-                    confidence: result.confidence,
-                    start_ms: 0,
-                    end_ms: (chunk_samples * 1000 / 16000) as u64,
-                }
-            };
+        let chunk: Vec<AudioSample> = state.audio_buffer.iter().take(chunk_samples).copied().collect();
+
+        // Remove processed samples (minus overlap)
+        let overlap_samples = (self.config.overlap_ms * 16) as usize;
+        let to_remove = chunk_samples.saturating_sub(overlap_samples);
+        let start_sample = state.buffer_offset_samples;
+        state.audio_buffer.drain(0..to_remove);
+        state.buffer_offset_samples += to_remove;
+        let timing = state.timing(start_sample, chunk_samples);
+
+        // Silent chunks only extend the silence counter
+        if self.is_silent(&chunk) {
+            state.skipped_silent_chunks += 1;
+            state.silence_ms += (to_remove * 1000 / 16000) as u64;
+            trace!("Skipping silent chunk ({}ms of silence)", state.silence_ms);
+            drop(state);
+            self.finish_utterance();
+            return Ok(None);
+        }
 
-            // Update state
-            let mut state = self.state.write().await;
-            state.last_transcription = result.text;
+        state.chunks_processed += 1;
+        state.silence_ms = 0;
 
-            debug!(
-                "Chunk {} transcribed: {} chars, confidence: {:.2}",
-                state.chunks_processed,
-                state.last_transcription.len(),
-                result.confidence
-            );
+        // Release lock before transcription (can take time)
+        drop(state);
 
-            Ok(Some(event))
+        // Transcribe chunk
+        let result = self.transcribe(&chunk)?;
+        self.archive_window(start_sample, &chunk, &result, timing.start_ms);
+
+        // Determine event type
+        let event = if self.config.enable_partial_results {
+            StreamingEvent::Partial {
+                text: result.text.clone(),
+                confidence: result.confidence,
+                timestamp_ms: timing.end_ms,
+                captured_at_ms: timing.captured_at_ms,
+            }
         } else {
-            Ok(None)
-        }
+            self.schedule_correction(&chunk, &result.text, result.confidence, timing);
+
+            StreamingEvent::Final {
+                text: result.text.clone(),
+                confidence: result.confidence,
+                start_ms: timing.start_ms,
+                end_ms: timing.end_ms,
+                translation: self.translate_final(&chunk)?,
+                captured_at_ms: timing.captured_at_ms,
+            }
+        };
+
+        // Update state
+        let mut state = self.state.write().await;
+        state.last_transcription = result.text;
+        state.transcribed_audio_ms += (chunk_samples * 1000 / 16000) as u64;
+        state.processing_time_ms += result.processing_time_ms;
+        state.confidence_sum += result.confidence;
+
+        debug!(
+            "Chunk {} transcribed: {} chars, confidence: {:.2}",
+            state.chunks_processed,
+            state.last_transcription.len(),
+            result.confidence
+        );
+
+        Ok(Some(event))
     }
 
     /// Process audio stream (async iterator)
@@ -264,33 +875,78 @@ impl StreamingSTT {
 
         let self_clone = Self {
             whisper: self.whisper.clone(),
+            refiner: self.refiner.clone(),
+            grammar: self.grammar.clone(),
             preprocessor: AudioPreprocessor::new(self.preprocessor.input_format()).unwrap(),
             config: self.config.clone(),
             state: self.state.clone(),
+            corrections: self.corrections.clone(),
+            pending_corrections: self.pending_corrections.clone(),
+            archive: self.archive.clone(),
+            archive_writes: self.archive_writes.clone(),
         };
 
+        let mut corrections_rx = self.subscribe_corrections();
+
         tokio::spawn(async move {
-            while let Some(audio) = audio_rx.recv().await {
-                match self_clone.process_chunk(&audio).await {
-                    Ok(Some(event)) => {
-                        if tx.send(event).await.is_err() {
-                            warn!("Event receiver dropped");
+            loop {
+                tokio::select! {
+                    audio = audio_rx.recv() => {
+                        let Some(audio) = audio else {
                             break;
+                        };
+
+                        match self_clone.process_chunk_events(&audio).await {
+                            Ok(events) => {
+                                let mut receiver_dropped = false;
+                                for event in events {
+                                    if tx.send(event).await.is_err() {
+                                        receiver_dropped = true;
+                                        break;
+                                    }
+                                }
+
+                                if receiver_dropped {
+                                    warn!("Event receiver dropped");
+                                    break;
+                                }
+                            }
+                            Err(e) => {
+                                let _ = tx.send(StreamingEvent::Error {
+                                    message: e.to_string(),
+                                }).await;
+                                break;
+                            }
                         }
                     }
-                    Ok(None) => {
-                        // Not enough audio yet
-                    }
-                    Err(e) => {
-                        let _ = tx.send(StreamingEvent::Error {
-                            message: e.to_string(),
-                        }).await;
-                        break;
+                    Ok(correction) = corrections_rx.recv() => {
+                        if tx.send(correction).await.is_err() {
+                            warn!("Event receiver dropped");
+                            break;
+                        }
                     }
                 }
             }
 
-            // Stream ended
+            // Stream ended: transcribe whatever is left in the buffer
+            match self_clone.flush().await {
+                Ok(Some(event)) => {
+                    let _ = tx.send(event).await;
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    let _ = tx.send(StreamingEvent::Error {
+                        message: e.to_string(),
+                    }).await;
+                }
+            }
+
+            // Deliver corrections that were still in flight
+            self_clone.wait_for_corrections().await;
+            while let Ok(correction) = corrections_rx.try_recv() {
+                let _ = tx.send(correction).await;
+            }
+
             let _ = tx.send(StreamingEvent::EndOfSpeech).await;
         });
 
@@ -301,15 +957,44 @@ impl StreamingSTT {
     pub async fn stats(&self) -> StreamingStats {
         let state = self.state.read().await;
 
+        let real_time_factor = if state.transcribed_audio_ms > 0 {
+            state.processing_time_ms as f32 / state.transcribed_audio_ms as f32
+        } else {
+            0.0
+        };
+
+        let average_confidence = if state.chunks_processed > 0 {
+            state.confidence_sum / state.chunks_processed as f32
+        } else {
+            0.0
+        };
+
         StreamingStats {
             total_samples_processed: state.total_samples_processed,
             chunks_processed: state.chunks_processed,
             buffer_size: state.audio_buffer.len(),
             is_active: state.is_active,
             last_transcription_length: state.last_transcription.len(),
+            queue_depth: state.audio_buffer.len() / self.chunk_samples().max(1),
+            dropped_samples: state.dropped_samples,
+            skipped_silent_chunks: state.skipped_silent_chunks,
+            silence_ms: state.silence_ms,
+            real_time_factor,
+            average_confidence,
         }
     }
 
+    /// Whether audio falls below the silence gate
+    fn is_silent(&self, audio: &[AudioSample]) -> bool {
+        self.config.silence_rms_threshold > 0.0
+            && AudioPreprocessor::rms(audio) < self.config.silence_rms_threshold
+    }
+
+    /// Number of 16kHz samples in one chunk
+    fn chunk_samples(&self) -> usize {
+        (self.config.chunk_duration_ms * 16) as usize // 16kHz * ms / 1000
+    }
+
     /// Get last transcription
     pub async fn last_transcription(&self) -> String {
         let state = self.state.read().await;
@@ -324,7 +1009,20 @@ impl StreamingSTT {
     }
 }
 
+/// Duration of a number of 16kHz samples in milliseconds
+fn samples_to_ms(samples: usize) -> u64 {
+    (samples * 1000 / 16000) as u64
+}
+
+/// Record a transcription pass's result on its span
+fn record_transcription(span: &Span, result: &TranscriptionResult) {
+    span.record("confidence", result.confidence as f64);
+    span.record("chars", result.text.chars().count() as u64);
+}
+
 /// Streaming statistics
+///
+/// Counters are per session and reset on `start()`.
 #[derive(Debug, Clone)]
 pub struct StreamingStats {
     pub total_samples_processed: usize,
@@ -332,12 +1030,68 @@ pub struct StreamingStats {
     pub buffer_size: usize,
     pub is_active: bool,
     pub last_transcription_length: usize,
+
+    /// Full chunks waiting in the buffer (grows when falling behind)
+    pub queue_depth: usize,
+
+    /// Samples discarded because the buffer exceeded its limit
+    pub dropped_samples: usize,
+
+    /// Chunks skipped by the silence gate instead of being transcribed
+    pub skipped_silent_chunks: usize,
+
+    /// Length of the current run of silence
+    pub silence_ms: u64,
+
+    /// Processing time divided by audio duration (> 1.0 is slower than real time)
+    pub real_time_factor: f32,
+
+    /// Mean confidence across transcribed chunks
+    pub average_confidence: f32,
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_to_event() {
+        let partial = StreamingEvent::Partial {
+            text: "open git".to_string(),
+            confidence: 0.7,
+            timestamp_ms: 900,
+            captured_at_ms: None,
+        };
+        assert_eq!(
+            partial.to_event(),
+            Some(Event::TranscriptPartial(TranscriptPartial {
+                text: "open git".to_string(),
+                confidence: 0.7,
+                timestamp_ms: 900,
+            }))
+        );
+
+        let fin = StreamingEvent::Final {
+            text: "open github.com".to_string(),
+            confidence: 0.85,
+            start_ms: 0,
+            end_ms: 1500,
+            translation: None,
+            captured_at_ms: Some(1_767_225_600_000),
+        };
+        assert_eq!(fin.to_event().unwrap().name(), "transcript_final");
+        assert!(StreamingEvent::EndOfSpeech.to_event().is_none());
+
+        // As sent by the gRPC service
+        let json = serde_json::to_value(&fin).unwrap();
+        assert_eq!(json["type"], "final");
+        assert_eq!(json["end_ms"], 1500);
+        assert_eq!(
+            serde_json::to_value(&StreamingEvent::EndOfSpeech).unwrap(),
+            serde_json::json!({ "type": "end_of_speech" })
+        );
+    }
+
     #[test]
     fn test_streaming_config_default() {
         let config = StreamingConfig::default();
@@ -371,5 +1125,378 @@ mod tests {
         assert_eq!(state.chunks_processed, 0);
         assert_eq!(state.total_samples_processed, 0);
         assert!(state.audio_buffer.is_empty());
+        assert_eq!(state.dropped_samples, 0);
+    }
+
+    #[tokio::test]
+    async fn test_stats_track_real_time_factor() {
+        let whisper = Arc::new(WhisperProcessor::new(Default::default()).unwrap());
+        let stt = StreamingSTT::new(whisper, AudioFormat::whisper_format(), StreamingConfig::default()).unwrap();
+        stt.start().await.unwrap();
+
+        let audio = vec![0.1; 8000];
+        let event = stt.process_chunk(&audio).await.unwrap();
+        assert!(event.is_some());
+
+        let stats = stt.stats().await;
+        assert_eq!(stats.chunks_processed, 1);
+        assert!(stats.real_time_factor > 0.0);
+        assert!(stats.average_confidence > 0.0);
+        assert_eq!(stats.dropped_samples, 0);
+    }
+
+    #[tokio::test]
+    async fn test_flush_transcribes_partial_chunk() {
+        let whisper = Arc::new(WhisperProcessor::new(Default::default()).unwrap());
+        let stt = StreamingSTT::new(whisper, AudioFormat::whisper_format(), StreamingConfig::default()).unwrap();
+        stt.start().await.unwrap();
+
+        // 200ms is shorter than a chunk, so nothing is emitted yet
+        let audio = vec![0.1; 3200];
+        assert!(stt.process_chunk(&audio).await.unwrap().is_none());
+
+        match stt.flush().await.unwrap() {
+            Some(StreamingEvent::Final { end_ms, .. }) => assert_eq!(end_ms, 200),
+            other => panic!("Expected Final event, got {:?}", other),
+        }
+
+        assert_eq!(stt.stats().await.buffer_size, 0);
+        assert!(stt.flush().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_stop_flushes_buffer() {
+        let whisper = Arc::new(WhisperProcessor::new(Default::default()).unwrap());
+        let stt = StreamingSTT::new(whisper, AudioFormat::whisper_format(), StreamingConfig::default()).unwrap();
+        stt.start().await.unwrap();
+
+        stt.process_chunk(&vec![0.1; 1600]).await.unwrap();
+
+        let event = stt.stop().await.unwrap();
+        assert!(matches!(event, Some(StreamingEvent::Final { .. })));
+        assert!(!stt.stats().await.is_active);
+    }
+
+    #[tokio::test]
+    async fn test_translate_finals_dual_output() {
+        let whisper = Arc::new(WhisperProcessor::new(Default::default()).unwrap());
+        let config = StreamingConfig {
+            enable_partial_results: false,
+            translate_finals: true,
+            ..Default::default()
+        };
+        let stt = StreamingSTT::new(whisper, AudioFormat::whisper_format(), config).unwrap();
+        stt.start().await.unwrap();
+
+        match stt.process_chunk(&vec![0.1; 8000]).await.unwrap() {
+            Some(StreamingEvent::Final { text, translation, .. }) => {
+                assert!(!text.is_empty());
+                assert!(translation.unwrap().contains("translation"));
+            }
+            other => panic!("Expected Final event, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_low_confidence_final_is_corrected() {
+        let whisper = Arc::new(WhisperProcessor::new(Default::default()).unwrap());
+        let refiner = Arc::new(WhisperProcessor::new(Default::default()).unwrap());
+        let config = StreamingConfig {
+            enable_partial_results: false,
+            // Mock confidence is 0.85, so everything falls below this threshold
+            correction_threshold: 0.9,
+            ..Default::default()
+        };
+        let stt = StreamingSTT::new(whisper, AudioFormat::whisper_format(), config)
+            .unwrap()
+            .with_refiner(refiner);
+        let mut corrections = stt.subscribe_corrections();
+        stt.start().await.unwrap();
+
+        let event = stt.process_chunk(&vec![0.1; 8000]).await.unwrap();
+        assert!(matches!(event, Some(StreamingEvent::Final { .. })));
+
+        stt.wait_for_corrections().await;
+        match corrections.try_recv().unwrap() {
+            StreamingEvent::Corrected { original, end_ms, .. } => {
+                assert!(!original.is_empty());
+                assert_eq!(end_ms, 500);
+            }
+            other => panic!("Expected Corrected event, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pending_corrections_drop_oldest() {
+        let whisper = Arc::new(WhisperProcessor::new(Default::default()).unwrap());
+        let refiner = Arc::new(WhisperProcessor::new(Default::default()).unwrap());
+        let config = StreamingConfig {
+            correction_threshold: 0.9,
+            ..Default::default()
+        };
+        let stt = StreamingSTT::new(whisper, AudioFormat::whisper_format(), config)
+            .unwrap()
+            .with_refiner(refiner);
+
+        // Corrections that never finish, each closing its channel when dropped
+        let mut dropped = Vec::new();
+        for _ in 0..MAX_PENDING_CORRECTIONS {
+            let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+            let stuck = tokio::spawn(async move {
+                let _tx = tx;
+                std::future::pending::<()>().await
+            });
+            stt.pending_corrections.lock().push(stuck);
+            dropped.push(rx);
+        }
+
+        let timing = EventTiming {
+            start_ms: 0,
+            end_ms: 500,
+            captured_at_ms: None,
+        };
+        stt.schedule_correction(&[0.1; 8000], "open the browser", 0.5, timing);
+        assert_eq!(
+            stt.pending_corrections.lock().len(),
+            MAX_PENDING_CORRECTIONS
+        );
+
+        // Only the oldest was aborted
+        assert!(dropped.remove(0).await.is_err());
+        for mut rx in dropped {
+            assert!(matches!(
+                rx.try_recv(),
+                Err(tokio::sync::oneshot::error::TryRecvError::Empty)
+            ));
+        }
+
+        for correction in stt.pending_corrections.lock().drain(..) {
+            correction.abort();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_confident_final_not_corrected() {
+        let whisper = Arc::new(WhisperProcessor::new(Default::default()).unwrap());
+        let refiner = Arc::new(WhisperProcessor::new(Default::default()).unwrap());
+        let config = StreamingConfig {
+            enable_partial_results: false,
+            ..Default::default()
+        };
+        let stt = StreamingSTT::new(whisper, AudioFormat::whisper_format(), config)
+            .unwrap()
+            .with_refiner(refiner);
+        let mut corrections = stt.subscribe_corrections();
+        stt.start().await.unwrap();
+
+        stt.process_chunk(&vec![0.1; 8000]).await.unwrap();
+
+        stt.wait_for_corrections().await;
+        assert!(corrections.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_archive_writes_each_utterance() {
+        let dir = tempfile::tempdir().unwrap();
+        let whisper = Arc::new(WhisperProcessor::new(Default::default()).unwrap());
+        let config = StreamingConfig {
+            archive: Some(ArchiveConfig::new(dir.path())),
+            ..Default::default()
+        };
+        let stt = StreamingSTT::new(whisper, AudioFormat::whisper_format(), config).unwrap();
+        stt.start().await.unwrap();
+
+        stt.process_chunk(&vec![0.1; 8000]).await.unwrap();
+        stt.process_chunk(&vec![0.1; 1600]).await.unwrap();
+        stt.stop().await.unwrap();
+
+        // Both windows are one utterance, written once without their overlap
+        let session_dir = stt.archive.lock().as_ref().unwrap().session_dir().to_path_buf();
+        assert!(session_dir.join("utterance-00001.flac").exists());
+        assert!(!session_dir.join("utterance-00002.flac").exists());
+
+        let json = std::fs::read_to_string(session_dir.join("utterance-00001.json")).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["start_ms"], 0);
+        assert_eq!(value["end_ms"], 600);
+    }
+
+    fn overflow_stt(policy: OverflowPolicy) -> StreamingSTT {
+        let whisper = Arc::new(WhisperProcessor::new(Default::default()).unwrap());
+        let config = StreamingConfig {
+            max_buffer_duration_secs: 1,
+            overflow_policy: policy,
+            ..Default::default()
+        };
+        StreamingSTT::new(whisper, AudioFormat::whisper_format(), config).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_overflow_drop_oldest_emits_warning() {
+        let stt = overflow_stt(OverflowPolicy::DropOldest);
+        stt.start().await.unwrap();
+
+        let events = stt.process_chunk_events(&vec![0.1; 20000]).await.unwrap();
+
+        assert!(matches!(
+            events[0],
+            StreamingEvent::Warning { dropped_samples: 4000, .. }
+        ));
+        assert!(matches!(events[1], StreamingEvent::Partial { .. }));
+        assert_eq!(stt.stats().await.dropped_samples, 4000);
+    }
+
+    #[tokio::test]
+    async fn test_overflow_drop_newest_keeps_buffer() {
+        let stt = overflow_stt(OverflowPolicy::DropNewest);
+        stt.start().await.unwrap();
+
+        let events = stt.process_chunk_events(&vec![0.1; 17000]).await.unwrap();
+
+        assert!(matches!(
+            events[0],
+            StreamingEvent::Warning { dropped_samples: 1000, .. }
+        ));
+        let stats = stt.stats().await;
+        assert_eq!(stats.dropped_samples, 1000);
+        assert_eq!(stats.buffer_size, 16000 - 7200);
+    }
+
+    #[tokio::test]
+    async fn test_overflow_error_rejects_audio() {
+        let stt = overflow_stt(OverflowPolicy::Error);
+        stt.start().await.unwrap();
+
+        let result = stt.process_chunk_events(&vec![0.1; 17000]).await;
+
+        assert!(matches!(result, Err(StreamingError::BufferOverflow)));
+        assert_eq!(stt.stats().await.buffer_size, 0);
+    }
+
+    #[tokio::test]
+    async fn test_overflow_block_catches_up_without_loss() {
+        let stt = overflow_stt(OverflowPolicy::Block);
+        stt.start().await.unwrap();
+
+        // Pre-fill the buffer as if transcription had fallen behind
+        stt.state.write().await.audio_buffer.extend(vec![0.1; 12000]);
+        let events = stt.process_chunk_events(&vec![0.1; 8000]).await.unwrap();
+
+        assert!(events.iter().all(|e| !matches!(e, StreamingEvent::Warning { .. })));
+        assert!(events.len() >= 2);
+        assert_eq!(stt.stats().await.dropped_samples, 0);
+    }
+
+    #[tokio::test]
+    async fn test_grammar_corrects_transcripts() {
+        let whisper = Arc::new(WhisperProcessor::new(Default::default()).unwrap());
+        let grammar = CommandGrammar::from_json(
+            r#"{ "intents": [ { "intent": "open_app", "phrase": "open", "variants": ["mock segment"] } ] }"#,
+        )
+        .unwrap();
+        let stt = StreamingSTT::new(whisper, AudioFormat::whisper_format(), StreamingConfig::default())
+            .unwrap()
+            .with_grammar(grammar);
+        stt.start().await.unwrap();
+
+        match stt.process_chunk(&vec![0.1; 8000]).await.unwrap() {
+            Some(StreamingEvent::Partial { text, .. }) => assert!(text.starts_with("open 1")),
+            other => panic!("Expected Partial event, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_silent_chunks_skip_whisper() {
+        let whisper = Arc::new(WhisperProcessor::new(Default::default()).unwrap());
+        let stt = StreamingSTT::new(whisper, AudioFormat::whisper_format(), StreamingConfig::default()).unwrap();
+        stt.start().await.unwrap();
+
+        assert!(stt.process_chunk(&vec![0.0; 8000]).await.unwrap().is_none());
+        assert!(stt.process_chunk(&vec![0.001; 8000]).await.unwrap().is_none());
+
+        let stats = stt.stats().await;
+        assert_eq!(stats.skipped_silent_chunks, 2);
+        assert_eq!(stats.chunks_processed, 0);
+        assert_eq!(stats.silence_ms, 900);
+
+        // Speech resets the silence counter
+        assert!(stt.process_chunk(&vec![0.1; 8000]).await.unwrap().is_some());
+        assert_eq!(stt.stats().await.silence_ms, 0);
+    }
+
+    #[tokio::test]
+    async fn test_silence_gate_disabled() {
+        let whisper = Arc::new(WhisperProcessor::new(Default::default()).unwrap());
+        let config = StreamingConfig {
+            silence_rms_threshold: 0.0,
+            ..Default::default()
+        };
+        let stt = StreamingSTT::new(whisper, AudioFormat::whisper_format(), config).unwrap();
+        stt.start().await.unwrap();
+
+        assert!(stt.process_chunk(&vec![0.0; 8000]).await.unwrap().is_some());
+        assert_eq!(stt.stats().await.skipped_silent_chunks, 0);
+    }
+
+    #[tokio::test]
+    async fn test_timestamps_follow_session_clock() {
+        let whisper = Arc::new(WhisperProcessor::new(Default::default()).unwrap());
+        let stt = StreamingSTT::new(whisper, AudioFormat::whisper_format(), StreamingConfig::default()).unwrap();
+        stt.start().await.unwrap();
+
+        let mut timestamps = Vec::new();
+        for _ in 0..3 {
+            if let Some(StreamingEvent::Partial { timestamp_ms, .. }) =
+                stt.process_chunk(&vec![0.1; 8000]).await.unwrap()
+            {
+                timestamps.push(timestamp_ms);
+            }
+        }
+
+        // Each chunk advances by chunk duration minus overlap
+        assert_eq!(timestamps, vec![500, 950, 1400]);
+    }
+
+    #[tokio::test]
+    async fn test_capture_timestamp_propagates() {
+        let whisper = Arc::new(WhisperProcessor::new(Default::default()).unwrap());
+        let stt = StreamingSTT::new(whisper, AudioFormat::whisper_format(), StreamingConfig::default()).unwrap();
+        stt.start().await.unwrap();
+
+        let captured_at = UNIX_EPOCH + std::time::Duration::from_millis(1_000_000);
+        let events = stt.process_captured_chunk(&vec![0.1; 8000], captured_at).await.unwrap();
+        match &events[0] {
+            StreamingEvent::Partial { captured_at_ms, .. } => assert_eq!(*captured_at_ms, Some(1_000_000)),
+            other => panic!("Expected Partial event, got {:?}", other),
+        }
+
+        // The next window starts 450ms into the first captured chunk
+        let next = captured_at + std::time::Duration::from_millis(500);
+        let events = stt.process_captured_chunk(&vec![0.1; 8000], next).await.unwrap();
+        match &events[0] {
+            StreamingEvent::Partial { captured_at_ms, .. } => assert_eq!(*captured_at_ms, Some(1_000_450)),
+            other => panic!("Expected Partial event, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_partials_not_translated() {
+        let whisper = Arc::new(WhisperProcessor::new(Default::default()).unwrap());
+        let config = StreamingConfig {
+            translate_finals: true,
+            ..Default::default()
+        };
+        let stt = StreamingSTT::new(whisper, AudioFormat::whisper_format(), config).unwrap();
+        stt.start().await.unwrap();
+
+        let event = stt.process_chunk(&vec![0.1; 8000]).await.unwrap();
+        assert!(matches!(event, Some(StreamingEvent::Partial { .. })));
+
+        // Trailing audio is finalized (and translated) on stop
+        stt.process_chunk(&vec![0.1; 1600]).await.unwrap();
+        match stt.stop().await.unwrap() {
+            Some(StreamingEvent::Final { translation, .. }) => assert!(translation.is_some()),
+            other => panic!("Expected Final event, got {:?}", other),
+        }
     }
 }
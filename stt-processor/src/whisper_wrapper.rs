@@ -89,6 +89,9 @@ pub struct WhisperConfig {
     
     /// Maximum segment length in characters
     pub max_segment_length: usize,
+    
+    /// Text that primes the decoder with expected vocabulary (e.g. command phrases)
+    pub initial_prompt: Option<String>,
 }
 
 impl Default for WhisperConfig {
@@ -101,6 +104,20 @@ impl Default for WhisperConfig {
             translate: false,
             print_progress: false,
             max_segment_length: 1000,
+            initial_prompt: None,
+        }
+    }
+}
+
+impl From<&aether_config::SttSection> for WhisperConfig {
+    fn from(section: &aether_config::SttSection) -> Self {
+        let defaults = Self::default();
+        Self {
+            model_path: section.model_path.clone(),
+            language: section.language.clone(),
+            num_threads: section.threads.unwrap_or(defaults.num_threads),
+            use_gpu: section.use_gpu,
+            ..defaults
         }
     }
 }
@@ -165,13 +182,23 @@ mod real_impl {
         
         /// Transcribe audio samples
         pub fn transcribe(&self, audio: &[AudioSample]) -> Result<TranscriptionResult, WhisperError> {
+            self.run(audio, self.config.translate)
+        }
+        
+        /// Transcribe audio samples and translate the result to English
+        pub fn translate(&self, audio: &[AudioSample]) -> Result<TranscriptionResult, WhisperError> {
+            self.run(audio, true)
+        }
+        
+        /// Run a full Whisper pass over the audio
+        fn run(&self, audio: &[AudioSample], translate: bool) -> Result<TranscriptionResult, WhisperError> {
             if audio.is_empty() {
                 return Err(WhisperError::InvalidAudioFormat(
                     "Empty audio buffer".to_string()
                 ));
             }
             
-            debug!("Transcribing {} samples", audio.len());
+            debug!("Transcribing {} samples (translate={})", audio.len(), translate);
             let start_time = std::time::Instant::now();
             
             // Create transcription parameters
@@ -179,10 +206,13 @@ mod real_impl {
             
             // Configure parameters
             params.set_language(Some(&self.config.language));
-            params.set_translate(self.config.translate);
+            params.set_translate(translate);
             params.set_print_progress(self.config.print_progress);
             params.set_print_special(false);
             params.set_print_realtime(false);
+            if let Some(prompt) = &self.config.initial_prompt {
+                params.set_initial_prompt(prompt);
+            }
             params.set_n_threads(self.config.num_threads as i32);
             
             // Lock context and transcribe
@@ -246,7 +276,7 @@ mod real_impl {
                 text: full_text.trim().to_string(),
                 confidence: avg_confidence,
                 processing_time_ms: elapsed,
-                language: self.config.language.clone(),
+                language: if translate { "en".to_string() } else { self.config.language.clone() },
                 segments,
             })
         }
@@ -302,10 +332,14 @@ mod real_impl {
 #[cfg(not(feature = "whisper"))]
 mod mock_impl {
     use super::*;
+    use parking_lot::Mutex;
+    use std::collections::VecDeque;
     
     /// Mock Whisper STT processor
     pub struct WhisperProcessor {
         config: WhisperConfig,
+        /// Transcripts to return in turn instead of placeholders
+        script: Option<Mutex<VecDeque<String>>>,
     }
 
     impl WhisperProcessor {
@@ -317,18 +351,47 @@ mod mock_impl {
             info!("Mock model path: {:?}", config.model_path);
             info!("Using {} threads (mock)", config.num_threads);
             
-            Ok(Self { config })
+            Ok(Self {
+                config,
+                script: None,
+            })
+        }
+        
+        /// Create a mock that hears `transcripts`, one per transcription in
+        /// turn, and nothing after them
+        ///
+        /// For testing the pipeline with recorded audio: what is said is
+        /// known, so the mock says it instead of a placeholder.
+        pub fn scripted<I, S>(config: WhisperConfig, transcripts: I) -> Result<Self, WhisperError>
+        where
+            I: IntoIterator<Item = S>,
+            S: Into<String>,
+        {
+            let mut processor = Self::new(config)?;
+            processor.script = Some(Mutex::new(
+                transcripts.into_iter().map(Into::into).collect(),
+            ));
+            Ok(processor)
         }
         
         /// Mock transcribe audio samples
         pub fn transcribe(&self, audio: &[AudioSample]) -> Result<TranscriptionResult, WhisperError> {
+            self.run(audio, self.config.translate)
+        }
+        
+        /// Mock transcribe and translate audio samples to English
+        pub fn translate(&self, audio: &[AudioSample]) -> Result<TranscriptionResult, WhisperError> {
+            self.run(audio, true)
+        }
+        
+        fn run(&self, audio: &[AudioSample], translate: bool) -> Result<TranscriptionResult, WhisperError> {
             if audio.is_empty() {
                 return Err(WhisperError::InvalidAudioFormat(
                     "Empty audio buffer".to_string()
                 ));
             }
             
-            debug!("MOCK transcribing {} samples", audio.len());
+            debug!("MOCK transcribing {} samples (translate={})", audio.len(), translate);
             
             // Simulate processing time
             let processing_time = (audio.len() as f32 / WHISPER_SAMPLE_RATE as f32 * 100.0) as u64;
@@ -336,6 +399,30 @@ mod mock_impl {
             
             // Generate mock transcription
             let duration_secs = audio.len() as f32 / WHISPER_SAMPLE_RATE as f32;
+            
+            if let Some(script) = &self.script {
+                let text = script.lock().pop_front().unwrap_or_default();
+                let segments = if text.is_empty() {
+                    Vec::new()
+                } else {
+                    vec![TranscriptionSegment {
+                        start_ms: 0,
+                        end_ms: (duration_secs * 1000.0) as i64,
+                        text: text.clone(),
+                        confidence: 0.95,
+                    }]
+                };
+                debug!("MOCK scripted transcription: {:?}", text);
+                
+                return Ok(TranscriptionResult {
+                    text,
+                    confidence: 0.95,
+                    processing_time_ms: processing_time,
+                    language: self.config.language.clone(),
+                    segments,
+                });
+            }
+            
             let num_segments = (duration_secs / 2.0).ceil() as usize; // ~2s per segment
             
             let mut segments = Vec::new();
@@ -345,7 +432,8 @@ mod mock_impl {
                 let start_ms = (i as f32 * 2000.0) as i64;
                 let end_ms = ((i + 1) as f32 * 2000.0).min(duration_secs * 1000.0) as i64;
                 
-                let segment_text = format!(" Mock segment {} at {:.1}s", i + 1, start_ms as f32 / 1000.0);
+                let label = if translate { "translation" } else { "segment" };
+                let segment_text = format!(" Mock {} {} at {:.1}s", label, i + 1, start_ms as f32 / 1000.0);
                 
                 segments.push(TranscriptionSegment {
                     start_ms,
@@ -363,7 +451,7 @@ mod mock_impl {
                 text: full_text.trim().to_string(),
                 confidence: 0.85,
                 processing_time_ms: processing_time,
-                language: self.config.language.clone(),
+                language: if translate { "en".to_string() } else { self.config.language.clone() },
                 segments,
             })
         }
@@ -421,6 +509,37 @@ mod tests {
         assert!(!result.segments.is_empty());
     }
 
+    #[test]
+    #[cfg(not(feature = "whisper"))]
+    fn test_scripted_mock() {
+        let processor =
+            WhisperProcessor::scripted(WhisperConfig::default(), ["hey aether", "what's the date"])
+                .unwrap();
+        let audio: Vec<f32> = vec![0.1; 8000];
+
+        assert_eq!(processor.transcribe(&audio).unwrap().text, "hey aether");
+        let result = processor.transcribe(&audio).unwrap();
+        assert_eq!(result.text, "what's the date");
+        assert_eq!(result.segments.len(), 1);
+        assert_eq!(result.segments[0].end_ms, 500);
+
+        // Then nothing more is heard
+        let result = processor.transcribe(&audio).unwrap();
+        assert!(result.text.is_empty());
+        assert!(result.segments.is_empty());
+    }
+
+    #[test]
+    fn test_mock_translate() {
+        let processor = WhisperProcessor::new(WhisperConfig::default()).unwrap();
+        let audio: Vec<f32> = vec![0.1; 16000];
+        
+        let result = processor.translate(&audio).unwrap();
+        
+        assert!(result.text.contains("translation"));
+        assert!(!processor.config().translate);
+    }
+
     #[test]
     fn test_empty_audio() {
         let config = WhisperConfig::default();
@@ -3,8 +3,9 @@
 /// Tests end-to-end transcription with real and synthetic audio.
 
 use stt_processor::{
-    AudioFormat, AudioPreprocessor, AudioSample, StreamingConfig, StreamingEvent,
-    StreamingSTT, WhisperConfig, WhisperProcessor, WHISPER_SAMPLE_RATE,
+    render_prometheus, AudioFormat, AudioPreprocessor, AudioSample, SessionRegistry,
+    StreamingConfig, StreamingEvent, StreamingSTT, WhisperConfig, WhisperProcessor,
+    WHISPER_SAMPLE_RATE,
 };
 use std::sync::Arc;
 use test_case::test_case;
@@ -196,6 +197,54 @@ fn test_chunk_size_calculations() {
     assert_eq!(overlap_samples, 800);
 }
 
+#[tokio::test]
+async fn test_streaming_metrics_export() {
+    let whisper = Arc::new(WhisperProcessor::new(WhisperConfig::default()).unwrap());
+    let streaming_stt =
+        StreamingSTT::new(whisper, AudioFormat::whisper_format(), StreamingConfig::default())
+            .unwrap();
+
+    streaming_stt.start().await.unwrap();
+    let chunk = generate_test_audio(0.5, 440.0);
+    streaming_stt.process_chunk(&chunk).await.unwrap();
+
+    let stats = streaming_stt.stats().await;
+    assert_eq!(stats.chunks_processed, 1);
+    assert!(stats.real_time_factor > 0.0);
+
+    let output = render_prometheus(&[stats], true);
+    assert!(output.contains("stt_chunks_processed_total 1"));
+    assert!(output.contains("stt_model_loaded 1"));
+}
+
+#[tokio::test]
+async fn test_session_registry_tracks_live_sessions() {
+    let whisper = Arc::new(WhisperProcessor::new(WhisperConfig::default()).unwrap());
+    let streaming_stt = StreamingSTT::new(
+        whisper,
+        AudioFormat::whisper_format(),
+        StreamingConfig::default(),
+    )
+    .unwrap();
+    let session = Arc::new(streaming_stt.new_session().unwrap());
+    session.start().await.unwrap();
+
+    let sessions = SessionRegistry::new();
+    let registered = sessions.register(session.clone());
+    session
+        .process_chunk(&generate_test_audio(0.5, 440.0))
+        .await
+        .unwrap();
+
+    let stats = sessions.stats().await;
+    assert_eq!(stats.len(), 1);
+    assert_eq!(stats[0].chunks_processed, 1);
+    assert!(render_prometheus(&stats, true).contains("stt_sessions_active 1"));
+
+    drop(registered);
+    assert!(sessions.is_empty());
+}
+
 // Note: Tests requiring Whisper model are commented out
 // Uncomment when model file is available
 
@@ -0,0 +1,87 @@
+"""Generate the WAV fixtures for the end-to-end tests.
+
+Standard library only, so it runs anywhere Python does:
+
+    python tests/fixtures/generate_fixtures.py
+
+The recordings are synthetic, shaped for the mock engines rather than for a
+real model: the wake word is a loud, bright burst (the mock wake-word engine
+fires on frames above 0.4 RMS that pass the VAD), and the command is voiced
+tones at speaking level. What is "said" comes from the scripted mock
+transcriber. For a real model, record the phrase instead (16 kHz mono,
+16-bit) and point AETHER_E2E_WAV at it.
+"""
+
+import math
+import struct
+import wave
+from pathlib import Path
+
+SAMPLE_RATE = 16000
+FIXTURES = Path(__file__).parent
+
+
+def silence(duration: float) -> list[float]:
+    """Digital silence."""
+    return [0.0] * int(duration * SAMPLE_RATE)
+
+
+def burst(frequency: float, duration: float, amplitude: float) -> list[float]:
+    """A steady tone with 5 ms fades, standing in for "Hey Aether"."""
+    count = int(duration * SAMPLE_RATE)
+    fade = int(0.005 * SAMPLE_RATE)
+    return [
+        amplitude
+        * min(1.0, i / fade, (count - i) / fade)
+        * math.sin(2 * math.pi * frequency * i / SAMPLE_RATE)
+        for i in range(count)
+    ]
+
+
+def syllables(pitches: list[float], duration: float, amplitude: float) -> list[float]:
+    """Voiced syllables: a fundamental and two formants under a sine
+    envelope each, standing in for "what's the date"."""
+    per_syllable = int(duration * SAMPLE_RATE / len(pitches))
+    samples = []
+    for pitch in pitches:
+        for i in range(per_syllable):
+            t = i / SAMPLE_RATE
+            envelope = math.sin(math.pi * i / per_syllable)
+            voice = (
+                0.6 * math.sin(2 * math.pi * pitch * t)
+                + 0.3 * math.sin(2 * math.pi * 700 * t)
+                + 0.1 * math.sin(2 * math.pi * 1220 * t)
+            )
+            samples.append(amplitude * envelope * voice)
+    return samples
+
+
+def write_wav(path: Path, samples: list[float]) -> None:
+    """Write 16 kHz mono 16-bit PCM."""
+    pcm = b"".join(
+        struct.pack("<h", int(max(-1.0, min(1.0, s)) * 32767)) for s in samples
+    )
+    with wave.open(str(path), "wb") as wav_file:
+        wav_file.setnchannels(1)
+        wav_file.setsampwidth(2)
+        wav_file.setframerate(SAMPLE_RATE)
+        wav_file.writeframes(pcm)
+
+
+def main() -> None:
+    # "Hey Aether, what's the date": speech from 0.3 s to 2.1 s, then the
+    # silence that ends the command
+    audio = (
+        silence(0.3)
+        + burst(3000, 0.4, 0.8)
+        + silence(0.2)
+        + syllables([180, 200, 170, 150], 1.2, 0.4)
+        + silence(2.0)
+    )
+    path = FIXTURES / "hey_aether_whats_the_date.wav"
+    write_wav(path, audio)
+    print(f"Wrote {path} ({len(audio) / SAMPLE_RATE:.1f}s)")
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,96 @@
+//! Latency budgets
+//!
+//! How long each stage of a voice command took, measured from the speech
+//! in the recording, and how long it may take. The defaults allow for the
+//! agent waiting out `AgentConfig::end_of_speech` (800 ms of silence, seen
+//! in 450 ms steps of the STT), so most of the response budget is waiting
+//! for the user to have finished.
+
+use std::time::Duration;
+
+/// How long each stage took; `None` if it never happened
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Latencies {
+    /// Start of speech to the wake word being detected
+    pub wake_word: Option<Duration>,
+
+    /// End of speech to the final transcript
+    pub transcript: Option<Duration>,
+
+    /// Final transcript to the command's result
+    pub command: Option<Duration>,
+
+    /// End of speech to the command's result: what the user waits
+    pub response: Option<Duration>,
+}
+
+/// How long each stage may take
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LatencyBudget {
+    pub wake_word: Duration,
+    pub transcript: Duration,
+    pub command: Duration,
+    pub response: Duration,
+}
+
+impl Default for LatencyBudget {
+    fn default() -> Self {
+        Self {
+            wake_word: Duration::from_millis(500),
+            transcript: Duration::from_millis(2000),
+            command: Duration::from_millis(500),
+            response: Duration::from_millis(2500),
+        }
+    }
+}
+
+impl LatencyBudget {
+    /// Each stage that took longer than its budget, or never happened
+    pub fn overruns(&self, latencies: &Latencies) -> Vec<String> {
+        [
+            ("wake word", latencies.wake_word, self.wake_word),
+            ("transcript", latencies.transcript, self.transcript),
+            ("command", latencies.command, self.command),
+            ("response", latencies.response, self.response),
+        ]
+        .into_iter()
+        .filter_map(|(stage, took, budget)| match took {
+            None => Some(format!("{}: never happened", stage)),
+            Some(took) if took > budget => Some(format!(
+                "{}: took {}ms, budget {}ms",
+                stage,
+                took.as_millis(),
+                budget.as_millis()
+            )),
+            Some(_) => None,
+        })
+        .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overruns() {
+        let budget = LatencyBudget::default();
+        let mut latencies = Latencies {
+            wake_word: Some(Duration::from_millis(120)),
+            transcript: Some(Duration::from_millis(1400)),
+            command: Some(Duration::from_millis(20)),
+            response: Some(Duration::from_millis(1420)),
+        };
+        assert!(budget.overruns(&latencies).is_empty());
+
+        latencies.wake_word = Some(Duration::from_millis(900));
+        latencies.command = None;
+        assert_eq!(
+            budget.overruns(&latencies),
+            [
+                "wake word: took 900ms, budget 500ms",
+                "command: never happened"
+            ]
+        );
+    }
+}
@@ -0,0 +1,50 @@
+//! AetherOS End-to-End Tests - Recorded voice commands through the pipeline
+//!
+//! Plays a recording into the agent the way a microphone would, in real
+//! time, and records what came of it:
+//! - Wake-word detection (`wakeword-detector`, mock engine)
+//! - Speech-to-text (`stt-processor`, scripted mock or a Whisper model)
+//! - Routing and execution (`agent-core`, `os-executor`)
+//! - Latency budgets, from the speech in the recording to each event
+//!
+//! ```ignore
+//! let recording = Recording::open("fixtures/hey_aether_whats_the_date.wav")?;
+//! let agent = voice_agent(scripted(&["Hey Aether, what's the date?"])?)?;
+//! let timeline = play(&agent, &recording).await?;
+//! assert!(LatencyBudget::default().overruns(&timeline.latencies()).is_empty());
+//! ```
+
+pub mod budget;
+pub mod pipeline;
+pub mod recording;
+
+pub use budget::{Latencies, LatencyBudget};
+#[cfg(feature = "whisper")]
+pub use pipeline::model;
+#[cfg(not(feature = "whisper"))]
+pub use pipeline::scripted;
+pub use pipeline::{play, voice_agent, Timeline, CHUNK_SAMPLES};
+pub use recording::{Recording, FRAME_SAMPLES, SPEECH_RMS};
+
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum HarnessError {
+    #[error("Recording error: {0}")]
+    Recording(#[from] stt_processor::PreprocessorError),
+
+    #[error("Wake-word detector error: {0}")]
+    Detector(#[from] wakeword_detector::DetectorError),
+
+    #[error("Whisper error: {0}")]
+    Whisper(#[from] stt_processor::WhisperError),
+
+    #[error("Speech-to-text error: {0}")]
+    Streaming(#[from] stt_processor::StreamingError),
+
+    #[error("Agent error: {0}")]
+    Agent(#[from] agent_core::AgentError),
+}
+
+/// Library version
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -0,0 +1,260 @@
+//! The pipeline
+//!
+//! [`voice_agent`] puts the agent together as `agent-core` does for a
+//! microphone: the mock wake-word engine, streaming speech-to-text and the
+//! OS executor. [`play`] feeds it a [`Recording`] in real time and returns
+//! what it published, timed from the start of playback.
+
+use crate::budget::Latencies;
+use crate::recording::{samples_to_duration, Recording};
+use crate::HarnessError;
+use aether_events::{CommandExecuted, CommandPlanned, Event, TranscriptFinal};
+use agent_core::{AgentConfig, AgentCore, Executors, KeywordRouter};
+use os_executor::{
+    Capability, CapabilityAuthority, CommandExecutor, CommandWhitelist, ExecutorConfig,
+};
+use std::ops::Range;
+use std::sync::Arc;
+use std::time::Duration;
+use stt_processor::{AudioFormat, StreamingConfig, StreamingSTT, WhisperConfig, WhisperProcessor};
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+use wakeword_detector::{DetectorConfig, WakeWordDetector};
+
+/// Samples per chunk played into the agent: 32 ms, as a capture callback
+/// delivers them
+pub const CHUNK_SAMPLES: usize = 512;
+
+/// Chunks queued for the agent before playback waits
+const AUDIO_QUEUE: usize = 64;
+
+/// The scripted mock transcriber, hearing `heard` in turn
+#[cfg(not(feature = "whisper"))]
+pub fn scripted(heard: &[&str]) -> Result<WhisperProcessor, HarnessError> {
+    Ok(WhisperProcessor::scripted(
+        WhisperConfig::default(),
+        heard.iter().copied(),
+    )?)
+}
+
+/// The Whisper model at `path`
+#[cfg(feature = "whisper")]
+pub fn model(path: impl Into<std::path::PathBuf>) -> Result<WhisperProcessor, HarnessError> {
+    let config = WhisperConfig {
+        model_path: path.into(),
+        ..Default::default()
+    };
+    Ok(WhisperProcessor::new(config)?)
+}
+
+/// The agent with the mock wake-word engine, `whisper` for speech-to-text
+/// and the default OS command whitelist, unsandboxed; the agent holds a
+/// capability token for the executor, as with `AGENT_CAPABILITY_KEY`
+pub fn voice_agent(whisper: WhisperProcessor) -> Result<AgentCore, HarnessError> {
+    let detector = WakeWordDetector::new(DetectorConfig {
+        access_key: "e2e".to_string(),
+        ..Default::default()
+    })?;
+    let stt = StreamingSTT::new(
+        Arc::new(whisper),
+        AudioFormat::whisper_format(),
+        StreamingConfig::default(),
+    )?;
+    let config = ExecutorConfig {
+        enable_sandbox: false,
+        ..Default::default()
+    };
+    let authority = CapabilityAuthority::new("e2e");
+    let executors = Executors {
+        os: Some(Arc::new(
+            CommandExecutor::new(config, CommandWhitelist::default())
+                .with_capability_authority(authority.clone()),
+        )),
+        os_capability: Some(authority.issue(&Capability::new("e2e", &["*"]))),
+        ..Default::default()
+    };
+
+    Ok(AgentCore::new(
+        detector,
+        stt,
+        KeywordRouter::default(),
+        executors,
+        AgentConfig::default(),
+    ))
+}
+
+/// Play `recording` into `agent` in real time, until the agent has handled
+/// what it heard and the recording has ended
+pub async fn play(agent: &AgentCore, recording: &Recording) -> Result<Timeline, HarnessError> {
+    let mut subscription = agent.subscribe().await?;
+    let (audio_tx, audio_rx) = mpsc::channel(AUDIO_QUEUE);
+    let started = Instant::now();
+
+    let playback = async move {
+        for (index, chunk) in recording.samples().chunks(CHUNK_SAMPLES).enumerate() {
+            // A chunk arrives once the last of it has been captured
+            let captured = samples_to_duration(index * CHUNK_SAMPLES + chunk.len());
+            tokio::time::sleep_until(started + captured).await;
+            if audio_tx.send(chunk.to_vec()).await.is_err() {
+                break;
+            }
+        }
+        // Dropping the sender ends the agent's audio
+    };
+
+    let listening = async {
+        let run = agent.run(audio_rx);
+        tokio::pin!(run);
+
+        let mut events = Vec::new();
+        let mut subscribed = true;
+        let result = loop {
+            tokio::select! {
+                result = &mut run => break result,
+                envelope = subscription.recv(), if subscribed => match envelope {
+                    Some(envelope) => events.push((started.elapsed(), envelope.event)),
+                    None => subscribed = false,
+                },
+            }
+        };
+
+        // Published as the agent stopped
+        while let Some(envelope) = subscription.try_recv() {
+            events.push((started.elapsed(), envelope.event));
+        }
+        result.map(|()| events)
+    };
+
+    let ((), events) = tokio::join!(playback, listening);
+    Ok(Timeline {
+        speech: recording.speech(),
+        events: events?,
+    })
+}
+
+/// What the agent published while a recording played
+#[derive(Debug, Clone)]
+pub struct Timeline {
+    /// Where the recording's speech was, from the start of playback
+    pub speech: Option<Range<Duration>>,
+
+    /// Each event, with when it was received from the start of playback
+    pub events: Vec<(Duration, Event)>,
+}
+
+impl Timeline {
+    /// When the wake word was detected
+    pub fn wake_word(&self) -> Option<Duration> {
+        self.find(|event| matches!(event, Event::WakeWordDetected(_)).then_some(()))
+            .map(|(at, ())| at)
+    }
+
+    /// The first final transcript, and when it was received
+    pub fn transcript(&self) -> Option<(Duration, &TranscriptFinal)> {
+        self.find(|event| match event {
+            Event::TranscriptFinal(transcript) => Some(transcript),
+            _ => None,
+        })
+    }
+
+    /// The first command planned, and when it was received
+    pub fn planned(&self) -> Option<(Duration, &CommandPlanned)> {
+        self.find(|event| match event {
+            Event::CommandPlanned(planned) => Some(planned),
+            _ => None,
+        })
+    }
+
+    /// The first command's result, and when it was received
+    pub fn executed(&self) -> Option<(Duration, &CommandExecuted)> {
+        self.find(|event| match event {
+            Event::CommandExecuted(executed) => Some(executed),
+            _ => None,
+        })
+    }
+
+    /// How long each stage took from the speech; none without speech
+    pub fn latencies(&self) -> Latencies {
+        let Some(speech) = &self.speech else {
+            return Latencies::default();
+        };
+        let transcript = self.transcript().map(|(at, _)| at);
+        let executed = self.executed().map(|(at, _)| at);
+
+        Latencies {
+            wake_word: self.wake_word().map(|at| at.saturating_sub(speech.start)),
+            transcript: transcript.map(|at| at.saturating_sub(speech.end)),
+            command: transcript
+                .zip(executed)
+                .map(|(heard, done)| done.saturating_sub(heard)),
+            response: executed.map(|at| at.saturating_sub(speech.end)),
+        }
+    }
+
+    fn find<'a, T>(&'a self, pick: impl Fn(&'a Event) -> Option<T>) -> Option<(Duration, T)> {
+        self.events
+            .iter()
+            .find_map(|(at, event)| Some((*at, pick(event)?)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aether_events::{Executor, WakeWordDetected};
+
+    fn at(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn test_latencies() {
+        let mut timeline = Timeline {
+            speech: Some(at(300)..at(2100)),
+            events: vec![
+                (
+                    at(420),
+                    Event::WakeWordDetected(WakeWordDetected {
+                        confidence: 0.85,
+                        keyword_index: 0,
+                        timestamp_us: 0,
+                    }),
+                ),
+                (
+                    at(3400),
+                    Event::TranscriptFinal(TranscriptFinal {
+                        text: "what's the date".to_string(),
+                        confidence: 0.95,
+                        start_ms: 0,
+                        end_ms: 2000,
+                    }),
+                ),
+                (
+                    at(3420),
+                    Event::CommandExecuted(CommandExecuted {
+                        executor: Executor::Os,
+                        success: true,
+                        duration_ms: 5,
+                        summary: None,
+                        output: serde_json::Value::Null,
+                    }),
+                ),
+            ],
+        };
+
+        assert_eq!(timeline.transcript().unwrap().1.text, "what's the date");
+        assert!(timeline.planned().is_none());
+        assert_eq!(
+            timeline.latencies(),
+            Latencies {
+                wake_word: Some(at(120)),
+                transcript: Some(at(1300)),
+                command: Some(at(20)),
+                response: Some(at(1320)),
+            }
+        );
+
+        timeline.speech = None;
+        assert_eq!(timeline.latencies(), Latencies::default());
+    }
+}
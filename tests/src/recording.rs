@@ -0,0 +1,94 @@
+//! Recordings
+//!
+//! A [`Recording`] is audio as the agent takes it, 16 kHz mono PCM, read
+//! from a WAV file of any rate and channel count. Where its speech starts
+//! and ends is what the latency budgets are measured from.
+
+use crate::HarnessError;
+use std::ops::Range;
+use std::path::Path;
+use std::time::Duration;
+use stt_processor::AudioPreprocessor;
+use wakeword_detector::SAMPLE_RATE;
+
+/// Samples per frame when looking for speech: 30 ms, as the wake-word VAD
+pub const FRAME_SAMPLES: usize = 480;
+
+/// Frames at or above this RMS level are speech, as for the STT's silence
+/// gate
+pub const SPEECH_RMS: f32 = 0.01;
+
+/// Audio to play into the agent
+#[derive(Debug, Clone)]
+pub struct Recording {
+    samples: Vec<i16>,
+}
+
+impl Recording {
+    /// Read a WAV file, converted to 16 kHz mono
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, HarnessError> {
+        let (samples, format) = AudioPreprocessor::read_wav(path)?;
+        let samples = AudioPreprocessor::new(format)?.process(&samples)?;
+        Ok(Self::from_samples(AudioPreprocessor::f32_to_i16(&samples)))
+    }
+
+    /// A recording of 16 kHz mono PCM
+    pub fn from_samples(samples: Vec<i16>) -> Self {
+        Self { samples }
+    }
+
+    pub fn samples(&self) -> &[i16] {
+        &self.samples
+    }
+
+    pub fn duration(&self) -> Duration {
+        samples_to_duration(self.samples.len())
+    }
+
+    /// From the start of the first frame of speech to the end of the last,
+    /// `None` if there is none
+    pub fn speech(&self) -> Option<Range<Duration>> {
+        let speech: Vec<usize> = self
+            .samples
+            .chunks(FRAME_SAMPLES)
+            .enumerate()
+            .filter(|(_, frame)| rms(frame) >= SPEECH_RMS)
+            .map(|(index, _)| index * FRAME_SAMPLES)
+            .collect();
+
+        let start = *speech.first()?;
+        let end = (speech.last()? + FRAME_SAMPLES).min(self.samples.len());
+        Some(samples_to_duration(start)..samples_to_duration(end))
+    }
+}
+
+fn rms(frame: &[i16]) -> f32 {
+    AudioPreprocessor::rms(&AudioPreprocessor::i16_to_f32(frame))
+}
+
+pub(crate) fn samples_to_duration(samples: usize) -> Duration {
+    Duration::from_micros(samples as u64 * 1_000_000 / SAMPLE_RATE as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_speech() {
+        // 0.3 s of silence, 0.6 s of a tone, 0.3 s of silence
+        let mut samples = vec![0i16; 4800];
+        samples.extend((0..9600).map(|i| if i % 8 < 4 { 8000 } else { -8000 }));
+        samples.extend(vec![0i16; 4800]);
+
+        let recording = Recording::from_samples(samples);
+        assert_eq!(recording.duration(), Duration::from_millis(1200));
+        assert_eq!(
+            recording.speech(),
+            Some(Duration::from_millis(300)..Duration::from_millis(900))
+        );
+
+        let silent = Recording::from_samples(vec![0; 16000]);
+        assert_eq!(silent.speech(), None);
+    }
+}
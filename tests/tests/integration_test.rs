@@ -0,0 +1,117 @@
+//! End-to-end tests: recorded voice commands through the whole pipeline
+//!
+//! Played in real time, so each takes as long as its recording.
+
+use aether_e2e::{play, voice_agent, LatencyBudget, Recording, Timeline};
+use aether_events::Executor;
+
+/// "Hey Aether, what's the date", synthetic (see `fixtures/generate_fixtures.py`)
+const WHATS_THE_DATE: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/fixtures/hey_aether_whats_the_date.wav"
+);
+
+/// The fixture up to the end of its wake word and the pause after it (0.9 s)
+#[cfg(feature = "whisper")]
+const WAKE_WORD_SAMPLES: usize = 14_400;
+
+/// The agent heard the date asked for and ran `date`; with the
+/// `latency-budgets` feature, in budget too
+fn assert_ran_date(timeline: &Timeline) {
+    assert!(
+        timeline.wake_word().is_some(),
+        "no wake word in {:#?}",
+        timeline
+    );
+
+    let (_, heard) = timeline.transcript().expect("no transcript");
+    assert!(
+        heard.text.to_lowercase().contains("date"),
+        "heard {:?}",
+        heard.text
+    );
+
+    let (_, planned) = timeline.planned().expect("no command planned");
+    assert_eq!(planned.executor, Executor::Os);
+    assert_eq!(
+        planned.command,
+        serde_json::json!({ "executor": "os", "command": "date", "args": [] })
+    );
+
+    let (_, executed) = timeline.executed().expect("no command executed");
+    assert_eq!(executed.executor, Executor::Os);
+    assert!(executed.success, "date failed: {:?}", executed.output);
+    let stdout = executed.output["result"]["stdout"]
+        .as_str()
+        .unwrap_or_default();
+    assert!(!stdout.trim().is_empty(), "date printed nothing");
+
+    let latencies = timeline.latencies();
+    if cfg!(feature = "latency-budgets") {
+        let overruns = LatencyBudget::default().overruns(&latencies);
+        assert!(
+            overruns.is_empty(),
+            "over budget: {:?} ({:?})",
+            overruns,
+            latencies
+        );
+    } else {
+        eprintln!("latencies: {:?}", latencies);
+    }
+}
+
+#[cfg(not(feature = "whisper"))]
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_whats_the_date() {
+    let recording = Recording::open(WHATS_THE_DATE).unwrap();
+    let speech = recording.speech().unwrap();
+    assert!(speech.start < speech.end && speech.end < recording.duration());
+
+    let whisper = aether_e2e::scripted(&["Hey Aether, what's the date?"]).unwrap();
+    let agent = voice_agent(whisper).unwrap();
+    let timeline = play(&agent, &recording).await.unwrap();
+
+    assert_ran_date(&timeline);
+    assert_eq!(
+        timeline.transcript().unwrap().1.text,
+        "Hey Aether, what's the date?"
+    );
+}
+
+#[cfg(not(feature = "whisper"))]
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_silence() {
+    // Heard nothing, so nothing is transcribed or run
+    let recording = Recording::from_samples(vec![0; 16000]);
+    let whisper = aether_e2e::scripted(&["Hey Aether, what's the date?"]).unwrap();
+    let agent = voice_agent(whisper).unwrap();
+
+    let timeline = play(&agent, &recording).await.unwrap();
+    assert!(timeline.speech.is_none());
+    assert!(timeline.events.is_empty(), "{:#?}", timeline.events);
+}
+
+/// A real recording through a real model: `AETHER_E2E_WAV` (someone saying
+/// "Hey Aether, what's the date") and `AETHER_E2E_WHISPER_MODEL` (e.g.
+/// `models/ggml-tiny.en.bin`); run with `--ignored`
+#[cfg(feature = "whisper")]
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+#[ignore = "needs AETHER_E2E_WAV and AETHER_E2E_WHISPER_MODEL"]
+async fn test_recorded_whats_the_date() {
+    let wav = std::env::var("AETHER_E2E_WAV").expect("AETHER_E2E_WAV is not set");
+    let model =
+        std::env::var("AETHER_E2E_WHISPER_MODEL").expect("AETHER_E2E_WHISPER_MODEL is not set");
+
+    // The mock wake-word engine only fires on a loud, bright burst, not on
+    // speech, so the fixture's wake word comes first
+    let wake_word = Recording::open(WHATS_THE_DATE).unwrap();
+    let said = Recording::open(wav).unwrap();
+    let recording = Recording::from_samples(
+        [&wake_word.samples()[..WAKE_WORD_SAMPLES], said.samples()].concat(),
+    );
+
+    let agent = voice_agent(aether_e2e::model(model).unwrap()).unwrap();
+    let timeline = play(&agent, &recording).await.unwrap();
+
+    assert_ran_date(&timeline);
+}
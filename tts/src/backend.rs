@@ -0,0 +1,150 @@
+//! Synthesis backends
+//!
+//! A [`TtsBackend`] speaks one piece of text with a voice and rate, writing
+//! the audio to a sink as it is produced. [`crate::PiperBackend`] runs Piper
+//! voices offline; [`MockBackend`] makes tones, for tests and machines
+//! without voices.
+
+use crate::sink::AudioSink;
+use crate::TtsError;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+/// How to speak
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpeechOptions {
+    /// Voice name; `None` for the backend's default
+    pub voice: Option<String>,
+
+    /// Speaking rate; 1.0 is the voice's own, 2.0 twice as fast
+    pub rate: f32,
+}
+
+impl Default for SpeechOptions {
+    fn default() -> Self {
+        Self {
+            voice: None,
+            rate: 1.0,
+        }
+    }
+}
+
+/// Whether `name` can name a voice: not empty, and no path separators or
+/// `..` that would reach outside a voices directory
+pub(crate) fn is_voice_name(name: &str) -> bool {
+    !name.is_empty() && !name.contains(['/', '\\']) && !name.contains("..")
+}
+
+/// A voice a backend can speak with
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VoiceInfo {
+    pub name: String,
+
+    /// e.g. `en_US`
+    pub language: Option<String>,
+
+    pub sample_rate: u32,
+}
+
+/// A speech synthesizer
+#[async_trait]
+pub trait TtsBackend: Send + Sync {
+    /// The voices available
+    fn voices(&self) -> Result<Vec<VoiceInfo>, TtsError>;
+
+    /// Sample rate of the audio for `options`' voice
+    fn sample_rate(&self, options: &SpeechOptions) -> Result<u32, TtsError>;
+
+    /// Speak `text` into `sink`, writing audio as it is produced; the sink
+    /// is not finished
+    async fn synthesize(
+        &self,
+        text: &str,
+        options: &SpeechOptions,
+        sink: &mut dyn AudioSink,
+    ) -> Result<(), TtsError>;
+}
+
+/// A tone per word instead of speech
+#[derive(Debug, Clone)]
+pub struct MockBackend {
+    pub sample_rate: u32,
+
+    /// Length of each word at rate 1.0
+    pub word_ms: u32,
+}
+
+impl Default for MockBackend {
+    fn default() -> Self {
+        Self {
+            sample_rate: 22050,
+            word_ms: 250,
+        }
+    }
+}
+
+#[async_trait]
+impl TtsBackend for MockBackend {
+    fn voices(&self) -> Result<Vec<VoiceInfo>, TtsError> {
+        Ok(vec![VoiceInfo {
+            name: "mock".to_string(),
+            language: None,
+            sample_rate: self.sample_rate,
+        }])
+    }
+
+    fn sample_rate(&self, _options: &SpeechOptions) -> Result<u32, TtsError> {
+        Ok(self.sample_rate)
+    }
+
+    async fn synthesize(
+        &self,
+        text: &str,
+        options: &SpeechOptions,
+        sink: &mut dyn AudioSink,
+    ) -> Result<(), TtsError> {
+        let word_samples =
+            (self.sample_rate as f32 * self.word_ms as f32 / 1000.0 / options.rate) as usize;
+        let tone: Vec<i16> = (0..word_samples)
+            .map(|i| {
+                let t = i as f32 / self.sample_rate as f32;
+                ((t * 440.0 * std::f32::consts::TAU).sin() * 4000.0) as i16
+            })
+            .collect();
+
+        for _ in text.split_whitespace() {
+            sink.write(&tone, self.sample_rate).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sink::BufferSink;
+
+    #[tokio::test]
+    async fn test_mock_rate() {
+        let backend = MockBackend::default();
+        let mut normal = BufferSink::default();
+        let mut fast = BufferSink::default();
+
+        backend
+            .synthesize("two words", &SpeechOptions::default(), &mut normal)
+            .await
+            .unwrap();
+        let options = SpeechOptions {
+            rate: 2.0,
+            ..Default::default()
+        };
+        backend
+            .synthesize("two words", &options, &mut fast)
+            .await
+            .unwrap();
+
+        assert_eq!(normal.samples.len(), 2 * 5512);
+        assert_eq!(fast.samples.len(), normal.samples.len() / 2);
+        assert_eq!(normal.sample_rate, Some(22050));
+    }
+}
@@ -0,0 +1,194 @@
+//! Speaking responses
+//!
+//! [`TtsEngine`] parses the text (plain or markup), and has its backend
+//! synthesize it a sentence at a time, so the sink can start playing the
+//! first sentence while the rest is synthesized.
+
+use crate::backend::{MockBackend, SpeechOptions, TtsBackend};
+use crate::markup::{parse_markup, plain_text, Segment};
+use crate::piper::{PiperBackend, PiperConfig};
+use crate::sink::{AudioSink, BufferSink};
+use crate::TtsError;
+use std::sync::Arc;
+use tracing::field::Empty;
+use tracing::{info_span, Instrument};
+
+/// Speaks text with a backend
+#[derive(Clone)]
+pub struct TtsEngine {
+    backend: Arc<dyn TtsBackend>,
+    defaults: SpeechOptions,
+}
+
+impl TtsEngine {
+    pub fn new(backend: impl TtsBackend + 'static) -> Self {
+        Self {
+            backend: Arc::new(backend),
+            defaults: SpeechOptions::default(),
+        }
+    }
+
+    /// The engine `[tts]` configures; `None` for backend `none`
+    pub fn from_config(config: &aether_config::TtsSection) -> Option<Self> {
+        let engine = match config.backend.as_str() {
+            "piper" => Self::new(PiperBackend::new(PiperConfig::from(config))),
+            "mock" => Self::new(MockBackend::default()),
+            _ => return None,
+        };
+        Some(engine.with_rate(config.rate))
+    }
+
+    /// Speak with `voice` unless the markup says otherwise
+    pub fn with_voice(mut self, voice: impl Into<String>) -> Self {
+        self.defaults.voice = Some(voice.into());
+        self
+    }
+
+    /// Speak at `rate`; markup rates are relative to it
+    pub fn with_rate(mut self, rate: f32) -> Self {
+        self.defaults.rate = rate;
+        self
+    }
+
+    pub fn backend(&self) -> &dyn TtsBackend {
+        self.backend.as_ref()
+    }
+
+    /// Speak `text` into `sink`, then finish the sink
+    ///
+    /// Runs in a `tts_speak` span, so the reply is part of the voice
+    /// command's trace.
+    pub async fn speak(&self, text: &str, sink: &mut dyn AudioSink) -> Result<(), TtsError> {
+        self.speak_as(text, true, sink).await
+    }
+
+    /// Speak `text` as it is into `sink`, never as markup, then finish the
+    /// sink
+    ///
+    /// For text the engine's caller did not write, such as a command's
+    /// output summarized as a reply.
+    pub async fn speak_plain(&self, text: &str, sink: &mut dyn AudioSink) -> Result<(), TtsError> {
+        self.speak_as(text, false, sink).await
+    }
+
+    async fn speak_as(
+        &self,
+        text: &str,
+        markup: bool,
+        sink: &mut dyn AudioSink,
+    ) -> Result<(), TtsError> {
+        let span = info_span!(
+            "tts_speak",
+            chars = text.chars().count() as u64,
+            otel.status_code = Empty,
+        );
+        let result = async {
+            let segments = if markup {
+                parse_markup(text, &self.defaults)
+            } else {
+                Ok(plain_text(text, &self.defaults))
+            };
+            let spoken = match segments {
+                Ok(segments) => self.speak_segments(segments, sink).await,
+                Err(e) => Err(e),
+            };
+            let finished = sink.finish().await;
+            spoken.and(finished)
+        }
+        .instrument(span.clone())
+        .await;
+
+        if result.is_err() {
+            span.record("otel.status_code", "ERROR");
+        }
+        result
+    }
+
+    /// Speak `text` into memory
+    pub async fn synthesize(&self, text: &str) -> Result<BufferSink, TtsError> {
+        let mut sink = BufferSink::default();
+        self.speak(text, &mut sink).await?;
+        Ok(sink)
+    }
+
+    async fn speak_segments(
+        &self,
+        segments: Vec<Segment>,
+        sink: &mut dyn AudioSink,
+    ) -> Result<(), TtsError> {
+        // Pauses are silence in the voice spoken last
+        let mut current = self.defaults.clone();
+        for segment in segments {
+            match segment {
+                Segment::Speech { text, options } => {
+                    for sentence in sentences(&text) {
+                        self.backend.synthesize(sentence, &options, sink).await?;
+                    }
+                    current = options;
+                }
+                Segment::Pause(pause) => {
+                    let sample_rate = self.backend.sample_rate(&current)?;
+                    let silence = vec![0; (pause.as_secs_f64() * sample_rate as f64) as usize];
+                    if !silence.is_empty() {
+                        sink.write(&silence, sample_rate).await?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `text` split after each `.`, `!` or `?` that ends a word
+fn sentences(text: &str) -> Vec<&str> {
+    let mut sentences = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let ends_word = matches!(chars.peek(), Some((_, next)) if next.is_whitespace());
+        if matches!(c, '.' | '!' | '?') && ends_word {
+            sentences.push(text[start..=i].trim());
+            start = i + 1;
+        }
+    }
+    sentences.push(text[start..].trim());
+    sentences.retain(|sentence| !sentence.is_empty());
+    sentences
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sentences() {
+        assert_eq!(
+            sentences("Done! It took 2.5 seconds. Anything else?"),
+            vec!["Done!", "It took 2.5 seconds.", "Anything else?"]
+        );
+        assert_eq!(sentences("Opening github.com"), vec!["Opening github.com"]);
+        assert!(sentences(" ").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_speak() {
+        let engine = TtsEngine::new(MockBackend::default()).with_rate(2.0);
+        let word = 2756;
+
+        let audio = engine.synthesize("Timer done. Pasta ready.").await.unwrap();
+        assert_eq!(audio.samples.len(), 4 * word);
+
+        let markup = r#"<speak>Done <break time="1s"/> <prosody rate="50%">now</prosody></speak>"#;
+        let audio = engine.synthesize(markup).await.unwrap();
+        assert_eq!(audio.samples.len(), word + 22050 + 2 * word);
+        assert!(audio.samples[word..word + 22050].iter().all(|s| *s == 0));
+
+        // Markup read out as it is: three "words", no pause
+        let mut sink = BufferSink::default();
+        engine
+            .speak_plain("<speak>Done <break/> now</speak>", &mut sink)
+            .await
+            .unwrap();
+        assert_eq!(sink.samples.len(), 3 * word);
+    }
+}
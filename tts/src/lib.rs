@@ -0,0 +1,47 @@
+//! TTS - Text-to-speech for AetherOS
+//!
+//! Speaks responses back to the user:
+//! - Pluggable backends through the `TtsBackend` trait
+//! - Offline synthesis with Piper ONNX voices
+//! - Streaming, a sentence at a time, to audio sinks: a player, a WAV file,
+//!   a channel or a buffer
+//! - SSML-style markup for rate, voice and pauses
+
+pub mod backend;
+pub mod engine;
+pub mod markup;
+pub mod piper;
+pub mod sink;
+
+pub use backend::{MockBackend, SpeechOptions, TtsBackend, VoiceInfo};
+pub use engine::TtsEngine;
+pub use markup::{parse_markup, plain_text, Segment};
+pub use piper::{PiperBackend, PiperConfig};
+pub use sink::{AudioChunk, AudioSink, BufferSink, ChannelSink, PlayerSink, WavSink};
+
+use std::path::PathBuf;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum TtsError {
+    #[error("Invalid markup: {0}")]
+    Markup(String),
+
+    #[error("Voice not found: {0}")]
+    VoiceNotFound(String),
+
+    #[error("Invalid voice config {path}: {reason}")]
+    VoiceConfig { path: PathBuf, reason: String },
+
+    #[error("Synthesis failed: {0}")]
+    Synthesis(String),
+
+    #[error("Audio sink error: {0}")]
+    Sink(String),
+
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Library version
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -0,0 +1,136 @@
+//! TTS CLI
+//!
+//! Speaks text, plain or `<speak>` markup, with the voice configured in
+//! `[tts]`, through the player or into a WAV file.
+
+use aether_config::{ConfigArgs, ConfigCommand};
+use aether_telemetry::{remote_span, TraceParent};
+use clap::{Parser, Subcommand};
+use std::io::Read;
+use std::path::PathBuf;
+use tracing::{Instrument, Span};
+use tts::{AudioSink, PlayerSink, TtsEngine, WavSink};
+
+#[derive(Parser)]
+#[command(name = "tts")]
+#[command(about = "Offline text-to-speech for AetherOS", long_about = None)]
+struct Cli {
+    #[command(subcommand)]
+    command: Commands,
+
+    /// Voice (overrides tts.voice)
+    #[arg(long, global = true)]
+    voice: Option<String>,
+
+    /// Speaking rate, 1.0 normal (overrides tts.rate)
+    #[arg(long, global = true)]
+    rate: Option<f64>,
+
+    /// Config file, TOML or YAML (default: AETHER_CONFIG)
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+
+    /// Override a config value, e.g. `--set tts.backend=mock`
+    #[arg(long = "set", global = true, value_name = "KEY=VALUE")]
+    overrides: Vec<String>,
+
+    /// W3C traceparent of the caller's span; `say` continues its trace
+    #[arg(long, global = true, env = "TRACEPARENT")]
+    traceparent: Option<TraceParent>,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Speak text
+    Say {
+        /// Text or markup to speak; `-` reads it from stdin
+        text: String,
+
+        /// Write a WAV file instead of playing
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// List the voices available
+    Voices,
+
+    /// Check the configuration
+    Validate,
+
+    /// Print the configuration after all layers, as TOML
+    PrintEffectiveConfig,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    // Spans are exported when OTEL_EXPORTER_OTLP_ENDPOINT is set
+    aether_telemetry::init("tts", "tts=info")?;
+
+    let cli = Cli::parse();
+    let mut config_args = ConfigArgs {
+        file: cli.config,
+        overrides: cli.overrides,
+    };
+    if let Some(voice) = cli.voice {
+        config_args.set("tts.voice", voice);
+    }
+    if let Some(rate) = cli.rate {
+        config_args.set("tts.rate", rate);
+    }
+
+    let config_command = match cli.command {
+        Commands::Validate => Some(ConfigCommand::Validate),
+        Commands::PrintEffectiveConfig => Some(ConfigCommand::PrintEffectiveConfig),
+        _ => None,
+    };
+    if let Some(command) = config_command {
+        command.run(&config_args)?;
+        return Ok(());
+    }
+
+    let config = config_args.load()?.tts;
+    let engine = TtsEngine::from_config(&config)
+        .ok_or_else(|| anyhow::anyhow!("Text-to-speech is off (tts.backend = \"none\")"))?;
+
+    match cli.command {
+        Commands::Say { text, output } => {
+            let text = if text == "-" {
+                let mut text = String::new();
+                std::io::stdin().read_to_string(&mut text)?;
+                text
+            } else {
+                text
+            };
+
+            let mut sink: Box<dyn AudioSink> = match &output {
+                Some(path) => Box::new(WavSink::new(path)),
+                None => Box::new(PlayerSink::new(config.player.clone())),
+            };
+            let span = cli
+                .traceparent
+                .as_ref()
+                .map_or_else(Span::none, remote_span);
+            let result = engine.speak(&text, sink.as_mut()).instrument(span).await;
+            aether_telemetry::shutdown();
+            result?;
+            if let Some(path) = output {
+                println!("✓ Wrote {}", path.display());
+            }
+        }
+
+        Commands::Voices => {
+            for voice in engine.backend().voices()? {
+                println!(
+                    "{}\t{}\t{} Hz",
+                    voice.name,
+                    voice.language.as_deref().unwrap_or("-"),
+                    voice.sample_rate
+                );
+            }
+        }
+
+        Commands::Validate | Commands::PrintEffectiveConfig => unreachable!(),
+    }
+
+    Ok(())
+}
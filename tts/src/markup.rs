@@ -0,0 +1,315 @@
+//! SSML-style markup
+//!
+//! Text starting with `<speak>` is read as a small subset of SSML; anything
+//! else is spoken as it is:
+//!
+//! ```text
+//! <speak>
+//!   Your timer is done. <break time="300ms"/>
+//!   <prosody rate="slow">The pasta is ready.</prosody>
+//!   <voice name="en_GB-alan-medium">Enjoy!</voice>
+//! </speak>
+//! ```
+//!
+//! `rate` is `x-slow`, `slow`, `medium`, `fast`, `x-fast`, a factor (`1.2`)
+//! or a percentage (`80%`); nested rates multiply. Breaks are cut to 10 s.
+//! `<s>` and `<p>` are accepted and change nothing; other prosody attributes
+//! are ignored.
+
+use crate::backend::{is_voice_name, SpeechOptions};
+use crate::TtsError;
+use std::time::Duration;
+
+/// Pause of a `<break/>` without a `time`
+const DEFAULT_BREAK: Duration = Duration::from_millis(500);
+
+/// The longest `<break/>`
+const MAX_BREAK: Duration = Duration::from_secs(10);
+
+/// A piece of what to say
+#[derive(Debug, Clone, PartialEq)]
+pub enum Segment {
+    /// Text to speak, with its rate and voice
+    Speech {
+        text: String,
+        options: SpeechOptions,
+    },
+
+    /// Silence
+    Pause(Duration),
+}
+
+/// `text` spoken as it is with `defaults`, even if it looks like markup
+pub fn plain_text(text: &str, defaults: &SpeechOptions) -> Vec<Segment> {
+    let mut segments = Vec::new();
+    push_text(&mut segments, text, defaults);
+    segments
+}
+
+/// The segments of `text`, plain or `<speak>` markup, starting from
+/// `defaults`
+pub fn parse_markup(text: &str, defaults: &SpeechOptions) -> Result<Vec<Segment>, TtsError> {
+    let mut segments = Vec::new();
+    let text = text.trim();
+    if !text.starts_with("<speak") {
+        push_text(&mut segments, text, defaults);
+        return Ok(segments);
+    }
+
+    // Open tags, with the options from before each
+    let mut open: Vec<(String, SpeechOptions)> = Vec::new();
+    let mut options = defaults.clone();
+    let mut rest = text;
+    while let Some(start) = rest.find('<') {
+        push_text(&mut segments, &unescape(&rest[..start]), &options);
+        let end = rest[start..]
+            .find('>')
+            .map(|end| start + end)
+            .ok_or_else(|| TtsError::Markup("unterminated tag".to_string()))?;
+        let tag = Tag::parse(&rest[start + 1..end])?;
+        rest = &rest[end + 1..];
+
+        if tag.closing {
+            match open.pop() {
+                Some((name, before)) if name == tag.name => options = before,
+                Some((name, _)) => {
+                    return Err(TtsError::Markup(format!(
+                        "</{}> does not close <{}>",
+                        tag.name, name
+                    )))
+                }
+                None => return Err(TtsError::Markup(format!("</{}> is not open", tag.name))),
+            }
+            continue;
+        }
+
+        let before = options.clone();
+        match tag.name.as_str() {
+            "speak" | "s" | "p" => {}
+            "prosody" => {
+                if let Some(rate) = tag.attr("rate") {
+                    options.rate *= parse_rate(rate)?;
+                }
+            }
+            "voice" => {
+                let name = tag
+                    .attr("name")
+                    .ok_or_else(|| TtsError::Markup("<voice> needs a name".to_string()))?;
+                if !is_voice_name(name) {
+                    return Err(TtsError::Markup(format!("invalid voice name {:?}", name)));
+                }
+                options.voice = Some(name.to_string());
+            }
+            "break" => {
+                let pause = match tag.attr("time") {
+                    Some(time) => parse_time(time)?,
+                    None => DEFAULT_BREAK,
+                };
+                segments.push(Segment::Pause(pause));
+            }
+            other => return Err(TtsError::Markup(format!("unsupported tag <{}>", other))),
+        }
+        if !tag.self_closing {
+            open.push((tag.name, before));
+        }
+    }
+    push_text(&mut segments, &unescape(rest), &options);
+
+    match open.pop() {
+        Some((name, _)) => Err(TtsError::Markup(format!("<{}> is not closed", name))),
+        None => Ok(segments),
+    }
+}
+
+/// Add `text` to the segments, joining it to the last one if it is spoken
+/// the same way
+fn push_text(segments: &mut Vec<Segment>, text: &str, options: &SpeechOptions) {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.is_empty() {
+        return;
+    }
+    if let Some(Segment::Speech {
+        text: last,
+        options: last_options,
+    }) = segments.last_mut()
+    {
+        if last_options == options {
+            last.push(' ');
+            last.push_str(&text);
+            return;
+        }
+    }
+    segments.push(Segment::Speech {
+        text,
+        options: options.clone(),
+    });
+}
+
+/// A tag, between `<` and `>`
+#[derive(Debug)]
+struct Tag {
+    name: String,
+    attrs: Vec<(String, String)>,
+    closing: bool,
+    self_closing: bool,
+}
+
+impl Tag {
+    fn parse(inner: &str) -> Result<Self, TtsError> {
+        let invalid = || TtsError::Markup(format!("invalid tag <{}>", inner));
+        let closing = inner.starts_with('/');
+        let self_closing = inner.ends_with('/');
+        let body = inner.trim_start_matches('/').trim_end_matches('/').trim();
+
+        let (name, mut rest) = body.split_once(char::is_whitespace).unwrap_or((body, ""));
+        if name.is_empty() {
+            return Err(invalid());
+        }
+
+        let mut attrs = Vec::new();
+        loop {
+            rest = rest.trim_start();
+            if rest.is_empty() {
+                break;
+            }
+            let (key, value) = rest.split_once('=').ok_or_else(invalid)?;
+            let value = value.trim_start();
+            let quote = value
+                .chars()
+                .next()
+                .filter(|c| *c == '"' || *c == '\'')
+                .ok_or_else(invalid)?;
+            let (value, after) = value[1..].split_once(quote).ok_or_else(invalid)?;
+            attrs.push((key.trim().to_string(), unescape(value)));
+            rest = after;
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            attrs,
+            closing,
+            self_closing,
+        })
+    }
+
+    fn attr(&self, name: &str) -> Option<&str> {
+        self.attrs
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+fn parse_rate(rate: &str) -> Result<f32, TtsError> {
+    let factor = match rate {
+        "x-slow" => Some(0.5),
+        "slow" => Some(0.75),
+        "medium" | "default" => Some(1.0),
+        "fast" => Some(1.25),
+        "x-fast" => Some(1.5),
+        _ => match rate.strip_suffix('%') {
+            Some(percent) => percent.parse::<f32>().ok().map(|p| p / 100.0),
+            None => rate.parse::<f32>().ok(),
+        },
+    };
+    factor
+        .filter(|factor| factor.is_finite() && *factor > 0.0)
+        .ok_or_else(|| TtsError::Markup(format!("invalid rate {:?}", rate)))
+}
+
+fn parse_time(time: &str) -> Result<Duration, TtsError> {
+    let seconds = match time.strip_suffix("ms") {
+        Some(ms) => ms.parse::<f64>().ok().map(|ms| ms / 1000.0),
+        None => time.strip_suffix('s').and_then(|s| s.parse::<f64>().ok()),
+    };
+    seconds
+        .filter(|seconds| seconds.is_finite() && *seconds >= 0.0)
+        .map(|seconds| Duration::from_secs_f64(seconds.min(MAX_BREAK.as_secs_f64())))
+        .ok_or_else(|| TtsError::Markup(format!("invalid time {:?}", time)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn speech(text: &str, rate: f32, voice: Option<&str>) -> Segment {
+        Segment::Speech {
+            text: text.to_string(),
+            options: SpeechOptions {
+                voice: voice.map(str::to_string),
+                rate,
+            },
+        }
+    }
+
+    #[test]
+    fn test_plain_text() {
+        let segments = parse_markup("  Is 3 < 4?\n Yes. ", &SpeechOptions::default()).unwrap();
+        assert_eq!(segments, vec![speech("Is 3 < 4? Yes.", 1.0, None)]);
+        assert!(parse_markup("", &SpeechOptions::default())
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_markup() {
+        let markup = r#"<speak>
+            Timer done. <break time="300ms"/>
+            <prosody rate="slow">Pasta <prosody rate="200%">is</prosody> ready.</prosody>
+            <voice name='en_GB-alan-medium'>Fish &amp; chips.</voice>
+            <s>Bye.</s>
+        </speak>"#;
+        let segments = parse_markup(markup, &SpeechOptions::default()).unwrap();
+        assert_eq!(
+            segments,
+            vec![
+                speech("Timer done.", 1.0, None),
+                Segment::Pause(Duration::from_millis(300)),
+                speech("Pasta", 0.75, None),
+                speech("is", 1.5, None),
+                speech("ready.", 0.75, None),
+                speech("Fish & chips.", 1.0, Some("en_GB-alan-medium")),
+                speech("Bye.", 1.0, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_break_capped() {
+        let markup = r#"<speak><break time="1e300s"/><break time="9999999999999ms"/></speak>"#;
+        let segments = parse_markup(markup, &SpeechOptions::default()).unwrap();
+        assert_eq!(segments, vec![Segment::Pause(MAX_BREAK); 2]);
+
+        let segments = plain_text("<speak>Hi</speak>", &SpeechOptions::default());
+        assert_eq!(segments, vec![speech("<speak>Hi</speak>", 1.0, None)]);
+    }
+
+    #[test]
+    fn test_invalid_markup() {
+        let defaults = SpeechOptions::default();
+        for markup in [
+            "<speak>Hello",
+            "<speak>Hello</voice></speak>",
+            "<speak><emphasis>Hi</emphasis></speak>",
+            "<speak><prosody rate=\"warp\">Hi</prosody></speak>",
+            "<speak><break time=\"soon\"/></speak>",
+            "<speak><voice>Hi</voice></speak>",
+            "<speak><voice name=\"../../x\">Hi</voice></speak>",
+            "<speak>Hi <break",
+        ] {
+            assert!(
+                matches!(parse_markup(markup, &defaults), Err(TtsError::Markup(_))),
+                "{}",
+                markup
+            );
+        }
+    }
+}
@@ -0,0 +1,347 @@
+//! Piper voices
+//!
+//! Runs the `piper` executable offline with an ONNX voice model: the text
+//! goes to its stdin, and the raw 16-bit PCM it writes to stdout is passed to
+//! the sink as it arrives. A voice named `en_US-lessac-medium` is
+//! `<voices_dir>/en_US-lessac-medium.onnx`, with its config (sample rate,
+//! language, speed) in `en_US-lessac-medium.onnx.json` next to it.
+
+use crate::backend::{is_voice_name, SpeechOptions, TtsBackend, VoiceInfo};
+use crate::sink::AudioSink;
+use crate::TtsError;
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::path::PathBuf;
+use std::process::Stdio;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::process::Command;
+use tracing::debug;
+
+/// Bytes read from piper at a time
+const READ_CHUNK: usize = 8192;
+
+/// Piper backend configuration
+#[derive(Debug, Clone, PartialEq)]
+pub struct PiperConfig {
+    /// The `piper` executable
+    pub binary: PathBuf,
+
+    /// Directory of `<voice>.onnx` models and their `.onnx.json` configs
+    pub voices_dir: PathBuf,
+
+    /// Voice used when the options name none
+    pub default_voice: String,
+
+    /// Speaker of a multi-speaker voice
+    pub speaker: Option<u32>,
+}
+
+impl Default for PiperConfig {
+    fn default() -> Self {
+        Self {
+            binary: PathBuf::from("piper"),
+            voices_dir: PathBuf::from("models/piper"),
+            default_voice: "en_US-lessac-medium".to_string(),
+            speaker: None,
+        }
+    }
+}
+
+impl From<&aether_config::TtsSection> for PiperConfig {
+    fn from(section: &aether_config::TtsSection) -> Self {
+        Self {
+            binary: section.piper_binary.clone(),
+            voices_dir: section.voices_dir.clone(),
+            default_voice: section.voice.clone(),
+            speaker: section.speaker,
+        }
+    }
+}
+
+/// The parts of a voice's `.onnx.json` used here
+#[derive(Debug, Deserialize)]
+struct VoiceFile {
+    audio: AudioSettings,
+
+    #[serde(default)]
+    language: Option<Language>,
+
+    #[serde(default)]
+    inference: Inference,
+}
+
+#[derive(Debug, Deserialize)]
+struct AudioSettings {
+    sample_rate: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct Language {
+    code: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+struct Inference {
+    length_scale: f32,
+}
+
+impl Default for Inference {
+    fn default() -> Self {
+        Self { length_scale: 1.0 }
+    }
+}
+
+/// Speaks with Piper voices
+#[derive(Debug, Clone)]
+pub struct PiperBackend {
+    config: PiperConfig,
+}
+
+impl PiperBackend {
+    pub fn new(config: PiperConfig) -> Self {
+        Self { config }
+    }
+
+    pub fn config(&self) -> &PiperConfig {
+        &self.config
+    }
+
+    /// The voice `options` name, or the default
+    fn voice_name<'a>(&'a self, options: &'a SpeechOptions) -> &'a str {
+        options
+            .voice
+            .as_deref()
+            .unwrap_or(&self.config.default_voice)
+    }
+
+    fn model_path(&self, voice: &str) -> PathBuf {
+        self.config.voices_dir.join(format!("{}.onnx", voice))
+    }
+
+    fn voice_file(&self, voice: &str) -> Result<VoiceFile, TtsError> {
+        // Names come from markup too, and must stay inside the voices dir
+        if !is_voice_name(voice) {
+            return Err(TtsError::VoiceNotFound(voice.to_string()));
+        }
+        let path = self.config.voices_dir.join(format!("{}.onnx.json", voice));
+        let contents = match std::fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Err(TtsError::VoiceNotFound(voice.to_string()))
+            }
+            Err(e) => return Err(e.into()),
+        };
+        serde_json::from_str(&contents).map_err(|e| TtsError::VoiceConfig {
+            path,
+            reason: e.to_string(),
+        })
+    }
+}
+
+#[async_trait]
+impl TtsBackend for PiperBackend {
+    fn voices(&self) -> Result<Vec<VoiceInfo>, TtsError> {
+        let entries = match std::fs::read_dir(&self.config.voices_dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut voices = Vec::new();
+        for entry in entries {
+            let file_name = entry?.file_name();
+            let Some(name) = file_name.to_str().and_then(|f| f.strip_suffix(".onnx")) else {
+                continue;
+            };
+            match self.voice_file(name) {
+                Ok(voice) => voices.push(VoiceInfo {
+                    name: name.to_string(),
+                    language: voice.language.map(|language| language.code),
+                    sample_rate: voice.audio.sample_rate,
+                }),
+                // A model without its config can't be used
+                Err(TtsError::VoiceNotFound(_)) => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        voices.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(voices)
+    }
+
+    fn sample_rate(&self, options: &SpeechOptions) -> Result<u32, TtsError> {
+        Ok(self.voice_file(self.voice_name(options))?.audio.sample_rate)
+    }
+
+    async fn synthesize(
+        &self,
+        text: &str,
+        options: &SpeechOptions,
+        sink: &mut dyn AudioSink,
+    ) -> Result<(), TtsError> {
+        let voice = self.voice_name(options);
+        let voice_file = self.voice_file(voice)?;
+        let sample_rate = voice_file.audio.sample_rate;
+        let length_scale = voice_file.inference.length_scale / options.rate;
+
+        let mut command = Command::new(&self.config.binary);
+        command
+            .arg("--model")
+            .arg(self.model_path(voice))
+            .arg("--output-raw")
+            .arg("--length_scale")
+            .arg(length_scale.to_string());
+        if let Some(speaker) = self.config.speaker {
+            command.arg("--speaker").arg(speaker.to_string());
+        }
+        debug!("Speaking {:?} with {} at {}x", text, voice, options.rate);
+
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| {
+                TtsError::Synthesis(format!(
+                    "failed to start {}: {}",
+                    self.config.binary.display(),
+                    e
+                ))
+            })?;
+
+        // Piper speaks a line at a time
+        let mut stdin = child.stdin.take().expect("piped stdin");
+        let line = format!("{}\n", text.replace(['\r', '\n'], " "));
+        stdin.write_all(line.as_bytes()).await?;
+        drop(stdin);
+
+        let mut stdout = child.stdout.take().expect("piped stdout");
+        let mut buffer = vec![0u8; READ_CHUNK];
+        let mut odd_byte = None;
+        loop {
+            let read = stdout.read(&mut buffer).await?;
+            if read == 0 {
+                break;
+            }
+
+            let mut bytes: Vec<u8> = odd_byte.take().into_iter().collect();
+            bytes.extend_from_slice(&buffer[..read]);
+            if bytes.len() % 2 == 1 {
+                odd_byte = bytes.pop();
+            }
+            let samples: Vec<i16> = bytes
+                .chunks_exact(2)
+                .map(|pair| i16::from_le_bytes([pair[0], pair[1]]))
+                .collect();
+            if !samples.is_empty() {
+                sink.write(&samples, sample_rate).await?;
+            }
+        }
+
+        let output = child.wait_with_output().await?;
+        if !output.status.success() {
+            return Err(TtsError::Synthesis(format!(
+                "piper exited with {}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sink::BufferSink;
+    use std::os::unix::fs::PermissionsExt;
+    use tempfile::TempDir;
+
+    const VOICE_JSON: &str =
+        r#"{"audio": {"sample_rate": 16000}, "language": {"code": "en_GB"}, "num_speakers": 4}"#;
+
+    /// A voices directory with one voice, and a fake piper recording its
+    /// arguments
+    fn setup() -> (TempDir, PiperBackend) {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join("en_GB-test-low.onnx"), b"").unwrap();
+        std::fs::write(dir.path().join("en_GB-test-low.onnx.json"), VOICE_JSON).unwrap();
+        // Without a config, so not a usable voice
+        std::fs::write(dir.path().join("broken.onnx"), b"").unwrap();
+
+        let piper = dir.path().join("piper");
+        std::fs::write(
+            &piper,
+            "#!/bin/sh\ncat > /dev/null\necho \"$@\" > \"$(dirname \"$0\")/args\"\nprintf '\\001\\000\\002\\000\\003'\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&piper, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let backend = PiperBackend::new(PiperConfig {
+            binary: piper,
+            voices_dir: dir.path().to_path_buf(),
+            default_voice: "en_GB-test-low".to_string(),
+            speaker: Some(3),
+        });
+        (dir, backend)
+    }
+
+    #[test]
+    fn test_voices() {
+        let (_dir, backend) = setup();
+        assert_eq!(
+            backend.voices().unwrap(),
+            vec![VoiceInfo {
+                name: "en_GB-test-low".to_string(),
+                language: Some("en_GB".to_string()),
+                sample_rate: 16000,
+            }]
+        );
+
+        let missing = PiperBackend::new(PiperConfig {
+            voices_dir: PathBuf::from("/nonexistent/voices"),
+            ..Default::default()
+        });
+        assert!(missing.voices().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_synthesize() {
+        let (dir, backend) = setup();
+        let mut sink = BufferSink::default();
+        let options = SpeechOptions {
+            rate: 2.0,
+            ..Default::default()
+        };
+
+        backend
+            .synthesize("Hello", &options, &mut sink)
+            .await
+            .unwrap();
+        assert_eq!(sink.samples, vec![1, 2]);
+        assert_eq!(sink.sample_rate, Some(16000));
+
+        let args = std::fs::read_to_string(dir.path().join("args")).unwrap();
+        assert!(args.contains("en_GB-test-low.onnx --output-raw --length_scale 0.5"));
+        assert!(args.contains("--speaker 3"));
+
+        let options = SpeechOptions {
+            voice: Some("de_DE-missing".to_string()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            backend.synthesize("Hallo", &options, &mut sink).await,
+            Err(TtsError::VoiceNotFound(_))
+        ));
+
+        let options = SpeechOptions {
+            voice: Some("../en_GB-test-low".to_string()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            backend.synthesize("Hello", &options, &mut sink).await,
+            Err(TtsError::VoiceNotFound(_))
+        ));
+    }
+}
@@ -0,0 +1,259 @@
+//! Audio sinks
+//!
+//! Synthesized audio is 16-bit mono PCM, written to an [`AudioSink`] a
+//! chunk at a time as it is produced, so playback starts before the whole
+//! response is synthesized.
+
+use crate::TtsError;
+use async_trait::async_trait;
+use hound::{SampleFormat, WavSpec, WavWriter};
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::PathBuf;
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+use tokio::process::{Child, ChildStdin, Command};
+use tokio::sync::mpsc;
+use tracing::debug;
+
+/// Where synthesized audio goes
+#[async_trait]
+pub trait AudioSink: Send {
+    /// Play or keep `samples`, 16-bit mono at `sample_rate`
+    async fn write(&mut self, samples: &[i16], sample_rate: u32) -> Result<(), TtsError>;
+
+    /// Called after the last samples of a response
+    async fn finish(&mut self) -> Result<(), TtsError> {
+        Ok(())
+    }
+}
+
+/// Keeps the audio in memory
+#[derive(Debug, Clone, Default)]
+pub struct BufferSink {
+    pub samples: Vec<i16>,
+    pub sample_rate: Option<u32>,
+}
+
+#[async_trait]
+impl AudioSink for BufferSink {
+    async fn write(&mut self, samples: &[i16], sample_rate: u32) -> Result<(), TtsError> {
+        check_rate(&mut self.sample_rate, sample_rate)?;
+        self.samples.extend_from_slice(samples);
+        Ok(())
+    }
+}
+
+/// A chunk of audio sent by a [`ChannelSink`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioChunk {
+    pub samples: Vec<i16>,
+    pub sample_rate: u32,
+}
+
+/// Sends the audio to a channel, a chunk at a time
+#[derive(Debug, Clone)]
+pub struct ChannelSink {
+    sender: mpsc::Sender<AudioChunk>,
+}
+
+impl ChannelSink {
+    pub fn new(sender: mpsc::Sender<AudioChunk>) -> Self {
+        Self { sender }
+    }
+}
+
+#[async_trait]
+impl AudioSink for ChannelSink {
+    async fn write(&mut self, samples: &[i16], sample_rate: u32) -> Result<(), TtsError> {
+        let chunk = AudioChunk {
+            samples: samples.to_vec(),
+            sample_rate,
+        };
+        self.sender
+            .send(chunk)
+            .await
+            .map_err(|_| TtsError::Sink("audio channel closed".to_string()))
+    }
+}
+
+/// Writes the audio to a WAV file, finalized by [`AudioSink::finish`]
+pub struct WavSink {
+    path: PathBuf,
+    writer: Option<WavWriter<BufWriter<File>>>,
+    sample_rate: Option<u32>,
+}
+
+impl WavSink {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            writer: None,
+            sample_rate: None,
+        }
+    }
+}
+
+#[async_trait]
+impl AudioSink for WavSink {
+    async fn write(&mut self, samples: &[i16], sample_rate: u32) -> Result<(), TtsError> {
+        check_rate(&mut self.sample_rate, sample_rate)?;
+        if self.writer.is_none() {
+            let spec = WavSpec {
+                channels: 1,
+                sample_rate,
+                bits_per_sample: 16,
+                sample_format: SampleFormat::Int,
+            };
+            self.writer = Some(WavWriter::create(&self.path, spec).map_err(wav_error)?);
+        }
+
+        let writer = self.writer.as_mut().expect("writer created");
+        for sample in samples {
+            writer.write_sample(*sample).map_err(wav_error)?;
+        }
+        Ok(())
+    }
+
+    async fn finish(&mut self) -> Result<(), TtsError> {
+        if let Some(writer) = self.writer.take() {
+            writer.finalize().map_err(wav_error)?;
+        }
+        Ok(())
+    }
+}
+
+/// Pipes the audio, raw little-endian PCM, to a player command such as
+/// `aplay` or `paplay`; `{sample_rate}` in its arguments is replaced
+pub struct PlayerSink {
+    command: Vec<String>,
+    player: Option<(Child, ChildStdin, u32)>,
+}
+
+impl PlayerSink {
+    pub fn new(command: Vec<String>) -> Self {
+        Self {
+            command,
+            player: None,
+        }
+    }
+
+    fn spawn(&self, sample_rate: u32) -> Result<(Child, ChildStdin), TtsError> {
+        let (program, args) = self
+            .command
+            .split_first()
+            .ok_or_else(|| TtsError::Sink("no player command".to_string()))?;
+        let args = args
+            .iter()
+            .map(|arg| arg.replace("{sample_rate}", &sample_rate.to_string()));
+        debug!("Starting player {} at {} Hz", program, sample_rate);
+
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| TtsError::Sink(format!("failed to start {}: {}", program, e)))?;
+        let stdin = child.stdin.take().expect("piped stdin");
+        Ok((child, stdin))
+    }
+}
+
+#[async_trait]
+impl AudioSink for PlayerSink {
+    async fn write(&mut self, samples: &[i16], sample_rate: u32) -> Result<(), TtsError> {
+        // A player plays one sample rate; a new voice may need another
+        if matches!(&self.player, Some((_, _, rate)) if *rate != sample_rate) {
+            self.finish().await?;
+        }
+        if self.player.is_none() {
+            let (child, stdin) = self.spawn(sample_rate)?;
+            self.player = Some((child, stdin, sample_rate));
+        }
+
+        let (_, stdin, _) = self.player.as_mut().expect("player started");
+        let bytes: Vec<u8> = samples.iter().flat_map(|s| s.to_le_bytes()).collect();
+        stdin
+            .write_all(&bytes)
+            .await
+            .map_err(|e| TtsError::Sink(format!("player stopped: {}", e)))
+    }
+
+    /// Wait for the player to play everything
+    async fn finish(&mut self) -> Result<(), TtsError> {
+        let Some((mut child, stdin, _)) = self.player.take() else {
+            return Ok(());
+        };
+        drop(stdin);
+        let status = child.wait().await?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(TtsError::Sink(format!(
+                "{} exited with {}",
+                self.command[0], status
+            )))
+        }
+    }
+}
+
+fn check_rate(current: &mut Option<u32>, sample_rate: u32) -> Result<(), TtsError> {
+    match *current.get_or_insert(sample_rate) {
+        rate if rate == sample_rate => Ok(()),
+        rate => Err(TtsError::Sink(format!(
+            "sample rate changed from {} to {}",
+            rate, sample_rate
+        ))),
+    }
+}
+
+fn wav_error(e: hound::Error) -> TtsError {
+    TtsError::Sink(e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_buffer_sample_rate() {
+        let mut sink = BufferSink::default();
+        sink.write(&[1, 2], 16000).await.unwrap();
+        sink.write(&[3], 16000).await.unwrap();
+        assert_eq!(sink.samples, vec![1, 2, 3]);
+        assert!(sink.write(&[4], 22050).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_wav_sink() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("speech.wav");
+        let mut sink = WavSink::new(&path);
+        sink.write(&[100, -100, 0], 22050).await.unwrap();
+        sink.finish().await.unwrap();
+
+        let mut reader = hound::WavReader::open(&path).unwrap();
+        assert_eq!(reader.spec().sample_rate, 22050);
+        let samples: Vec<i16> = reader.samples().map(Result::unwrap).collect();
+        assert_eq!(samples, vec![100, -100, 0]);
+    }
+
+    #[tokio::test]
+    async fn test_player_sink() {
+        let dir = TempDir::new().unwrap();
+        let out = dir.path().join("out-{sample_rate}.raw");
+        let command = ["sh", "-c", "cat > \"$0\"", out.to_str().unwrap()];
+        let mut sink = PlayerSink::new(command.map(String::from).to_vec());
+
+        sink.write(&[1, 2, 3], 16000).await.unwrap();
+        sink.write(&[4], 22050).await.unwrap();
+        sink.finish().await.unwrap();
+
+        let first = std::fs::read(dir.path().join("out-16000.raw")).unwrap();
+        assert_eq!(first, vec![1, 0, 2, 0, 3, 0]);
+        let second = std::fs::read(dir.path().join("out-22050.raw")).unwrap();
+        assert_eq!(second, vec![4, 0]);
+    }
+}
@@ -0,0 +1,65 @@
+//! Integration tests for tts
+
+use aether_config::TtsSection;
+use tempfile::TempDir;
+use tokio::sync::mpsc;
+use tts::{ChannelSink, MockBackend, TtsEngine, TtsError, WavSink};
+
+#[tokio::test]
+async fn test_streams_to_channel() {
+    let engine = TtsEngine::new(MockBackend::default());
+    let (tx, mut rx) = mpsc::channel(16);
+    let mut sink = ChannelSink::new(tx);
+
+    engine
+        .speak(
+            "<speak>Timer done. <voice name=\"mock\">Enjoy!</voice></speak>",
+            &mut sink,
+        )
+        .await
+        .unwrap();
+    drop(sink);
+
+    // One chunk per word, as it is synthesized
+    let mut chunks = Vec::new();
+    while let Some(chunk) = rx.recv().await {
+        chunks.push(chunk);
+    }
+    assert_eq!(chunks.len(), 3);
+    assert!(chunks.iter().all(|chunk| chunk.sample_rate == 22050));
+}
+
+#[tokio::test]
+async fn test_speak_to_wav() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("reply.wav");
+    let engine = TtsEngine::new(MockBackend::default());
+
+    engine
+        .speak("Opening github.com", &mut WavSink::new(&path))
+        .await
+        .unwrap();
+
+    let reader = hound::WavReader::open(&path).unwrap();
+    assert_eq!(reader.spec().channels, 1);
+    assert_eq!(reader.len(), 2 * 5512);
+}
+
+#[tokio::test]
+async fn test_engine_from_config() {
+    let mut config = TtsSection {
+        backend: "mock".to_string(),
+        ..Default::default()
+    };
+    let engine = TtsEngine::from_config(&config).unwrap();
+    assert_eq!(engine.backend().voices().unwrap()[0].name, "mock");
+    assert!(matches!(
+        engine
+            .speak("<speak>Hi", &mut tts::BufferSink::default())
+            .await,
+        Err(TtsError::Markup(_))
+    ));
+
+    config.backend = "none".to_string();
+    assert!(TtsEngine::from_config(&config).is_none());
+}
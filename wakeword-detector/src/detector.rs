@@ -5,11 +5,14 @@
 
 use crate::audio_buffer::{AudioBuffer, AudioSample, SAMPLE_RATE};
 use crate::vad::{VadConfig, VoiceActivityDetector};
+use aether_events::WakeWordDetected;
+use aether_telemetry::TraceParent;
 use std::path::Path;
 use std::sync::Arc;
 use thiserror::Error;
 use tokio::sync::{mpsc, RwLock};
-use tracing::{debug, error, info, warn};
+use tracing::field::Empty;
+use tracing::{debug, error, info, info_span, warn};
 
 #[derive(Error, Debug)]
 pub enum DetectorError {
@@ -43,6 +46,20 @@ pub struct WakeWordEvent {
 
     /// Index of the detected keyword (if multiple keywords supported)
     pub keyword_index: i32,
+
+    /// The trace of the voice command this wake word starts; transcription
+    /// and the command's execution continue it
+    pub trace_parent: TraceParent,
+}
+
+impl From<&WakeWordEvent> for WakeWordDetected {
+    fn from(event: &WakeWordEvent) -> Self {
+        WakeWordDetected {
+            confidence: event.confidence,
+            keyword_index: event.keyword_index,
+            timestamp_us: event.timestamp,
+        }
+    }
 }
 
 /// Configuration for wake-word detector
@@ -80,6 +97,18 @@ impl Default for DetectorConfig {
     }
 }
 
+impl From<&aether_config::WakewordSection> for DetectorConfig {
+    fn from(section: &aether_config::WakewordSection) -> Self {
+        Self {
+            access_key: section.access_key.clone(),
+            model_path: section.model_path.clone(),
+            sensitivity: section.sensitivity,
+            enable_vad_prefilter: section.enable_vad_prefilter,
+            ..Default::default()
+        }
+    }
+}
+
 impl DetectorConfig {
     /// Validate configuration
     pub fn validate(&self) -> Result<(), DetectorError> {
@@ -231,7 +260,7 @@ impl WakeWordDetector {
 
             if should_process {
                 // Run wake-word detection
-                if let Err(e) = self.detect_wake_word(&frame).await {
+                if let Err(e) = self.detect_wake_word(&mut state, &frame) {
                     error!("Wake-word detection error: {}", e);
                 }
             }
@@ -256,18 +285,24 @@ impl WakeWordDetector {
     /// NOTE: This is a placeholder. In production, this would call
     /// the actual Porcupine SDK. For testing, we simulate detection
     /// based on audio energy patterns.
-    async fn detect_wake_word(&self, frame: &[AudioSample]) -> Result<(), DetectorError> {
+    fn detect_wake_word(
+        &self,
+        state: &mut DetectorState,
+        frame: &[AudioSample],
+    ) -> Result<(), DetectorError> {
         // Mock detection logic for testing
         // In production: use pv_porcupine::Porcupine::process()
 
-        let detection_result = self.mock_porcupine_process(frame);
+        let detection_result = self.mock_porcupine_process(frame, state.frames_processed);
 
         if let Some(keyword_index) = detection_result {
-            info!("Wake-word detected! (keyword_index: {})", keyword_index);
-
-            let state = self.state.read().await;
+            // The root of the voice command's trace
+            let span = info_span!(parent: None, "wake_word", keyword_index, trace_id = Empty);
+            let trace_parent = TraceParent::from_span(&span).unwrap_or_else(TraceParent::generate);
+            span.record("trace_id", trace_parent.trace_id.as_str());
+            span.in_scope(|| info!("Wake-word detected! (keyword_index: {})", keyword_index));
 
-            // Capture audio context (last 3 seconds)
+            // Capture audio context (the frame and what follows it)
             let audio_context = state.audio_buffer.peek(state.audio_buffer.len());
 
             let event = WakeWordEvent {
@@ -275,6 +310,7 @@ impl WakeWordDetector {
                 confidence: 0.85, // Mock confidence
                 audio_context,
                 keyword_index,
+                trace_parent,
             };
 
             // Send event
@@ -283,8 +319,6 @@ impl WakeWordDetector {
             }
 
             // Update stats
-            drop(state);
-            let mut state = self.state.write().await;
             state.wake_words_detected += 1;
         }
 
@@ -294,7 +328,7 @@ impl WakeWordDetector {
     /// Mock Porcupine processing (for testing without actual SDK)
     ///
     /// Returns Some(keyword_index) if wake-word detected, None otherwise.
-    fn mock_porcupine_process(&self, frame: &[AudioSample]) -> Option<i32> {
+    fn mock_porcupine_process(&self, frame: &[AudioSample], frames_processed: u64) -> Option<i32> {
         // Simple energy-based mock detection
         // In real implementation: return porcupine.process(frame)
 
@@ -311,8 +345,8 @@ impl WakeWordDetector {
         // Simulate detection on high-energy frames (simplified)
         // Real Porcupine would use trained neural network
         if rms > 0.4 {
-            // Randomly detect to simulate occasional triggers
-            if self.state.try_read().map(|s| s.frames_processed % 100 == 0).unwrap_or(false) {
+            // Detect every 100th frame to simulate occasional triggers
+            if frames_processed % 100 == 0 {
                 return Some(0); // Keyword index 0
             }
         }
@@ -460,6 +494,21 @@ mod tests {
         assert!(config.validate().is_err());
     }
 
+    #[test]
+    fn test_config_from_section() {
+        let section = aether_config::WakewordSection {
+            access_key: "test_key".to_string(),
+            sensitivity: 0.7,
+            ..Default::default()
+        };
+
+        let config = DetectorConfig::from(&section);
+        assert_eq!(config.sensitivity, 0.7);
+        assert_eq!(config.model_path, "models/aether.ppn");
+        assert_eq!(config.sample_rate, SAMPLE_RATE);
+        assert!(config.validate().is_ok());
+    }
+
     #[tokio::test]
     async fn test_event_reception() {
         let config = test_config();
@@ -467,15 +516,26 @@ mod tests {
 
         detector.start().await.unwrap();
 
-        // Generate high-energy audio to trigger mock detection
-        let samples: Vec<i16> = vec![i16::MAX / 2; 5000];
+        // High-energy audio triggers the mock on the first frame
+        let samples: Vec<i16> = (0..5000)
+            .map(|i| {
+                if i % 2 == 0 {
+                    i16::MAX / 2
+                } else {
+                    -i16::MAX / 2
+                }
+            })
+            .collect();
 
         detector.process_audio(&samples).await.unwrap();
 
-        // Try to receive event (may or may not trigger in mock)
-        if let Some(event) = detector.try_recv_event().await {
-            assert!(event.confidence > 0.0);
-            assert_eq!(event.keyword_index, 0);
-        }
+        let event = detector.try_recv_event().await.expect("no wake-word event");
+        assert!(event.confidence > 0.0);
+        assert_eq!(event.keyword_index, 0);
+        // Each detection starts a trace of its own
+        assert_eq!(event.trace_parent.trace_id.len(), 32);
+        // The detecting frame is part of the context
+        assert!(event.audio_context.len() >= 480);
+        assert_eq!(detector.stats().await.wake_words_detected, 1);
     }
 }
@@ -1,34 +1,76 @@
 /// Wake-word detection service binary
 ///
-/// Standalone service that listens for the "Hey Aether" wake-word.
+/// Standalone service that listens for the "Hey Aether" wake-word, with
+/// `/healthz` and `/readyz` on `wakeword.health_addr`.
+///
+/// Usage: wakeword-detector [--config <file>] [--set <key>=<value>]...
+///        [validate | print-effective-config]
 
-use tracing::{error, info};
-use tracing_subscriber;
+use aether_config::{ConfigArgs, ConfigCommand, WakewordSection};
+use aether_health::Health;
+use tracing::{error, info, warn};
 use wakeword_detector::{DetectorConfig, WakeWordDetector};
 
 #[tokio::main]
 async fn main() {
-    // Initialize tracing
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            tracing_subscriber::EnvFilter::from_default_env()
-                .add_directive("wakeword_detector=debug".parse().unwrap())
-        )
-        .init();
+    // Initialize tracing, exporting spans when OTEL_EXPORTER_OTLP_ENDPOINT is set
+    if let Err(e) = aether_telemetry::init("wakeword-service", "wakeword_detector=debug") {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+
+    let mut args: Vec<String> = std::env::args().collect();
+    let mut config_args = ConfigArgs::default();
+    loop {
+        match config_args.take(&mut args, 1) {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Some(name) = args.get(1) {
+        let Some(command) = ConfigCommand::parse(name) else {
+            eprintln!("Unknown command: {}", name);
+            eprintln!("Usage: wakeword-detector [--config <file>] [--set <key>=<value>]... [validate|print-effective-config]");
+            std::process::exit(1);
+        };
+        if let Err(e) = command.run(&config_args) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
 
     info!("Starting AetherOS Wake-word Detection Service");
 
     // Load configuration
-    let config = match load_config() {
-        Ok(cfg) => cfg,
+    let section = match config_args.load() {
+        Ok(cfg) => cfg.wakeword,
         Err(e) => {
             error!("Failed to load configuration: {}", e);
             std::process::exit(1);
         }
     };
 
+    // Health endpoints, answering while the detector starts
+    let health = Health::new("wakeword-service", env!("CARGO_PKG_VERSION"));
+    let detector_ready = health.check("detector");
+    health.probe("audio_device", || async {
+        tokio::task::spawn_blocking(audio_device)
+            .await
+            .unwrap_or_else(|_| Err("audio device check failed".to_string()))
+    });
+    if let Err(e) = aether_health::serve(health, &section.health_addr).await {
+        error!("{}", e);
+        std::process::exit(1);
+    }
+
     // Create detector
-    let detector = match WakeWordDetector::new(config) {
+    let detector = match WakeWordDetector::new(detector_config(&section)) {
         Ok(det) => det,
         Err(e) => {
             error!("Failed to create detector: {}", e);
@@ -41,23 +83,32 @@ async fn main() {
         error!("Failed to start detector: {}", e);
         std::process::exit(1);
     }
+    detector_ready.ready();
 
     info!("Wake-word detector running. Listening for 'Hey Aether'...");
 
-    // Event loop
+    // Event loop, until the channel closes or we are told to stop
+    let shutdown = aether_health::shutdown_signal();
+    tokio::pin!(shutdown);
     loop {
-        match detector.recv_event().await {
-            Some(event) => {
-                info!(
-                    "Wake-word detected! confidence={:.2}, timestamp={}",
-                    event.confidence, event.timestamp
-                );
-
-                // In production: send event to Agent Core via gRPC
-                // For now: just log
-            }
-            None => {
-                info!("Event channel closed, shutting down");
+        tokio::select! {
+            event = detector.recv_event() => match event {
+                Some(event) => {
+                    info!(
+                        "Wake-word detected! confidence={:.2}, timestamp={}, trace_id={}",
+                        event.confidence, event.timestamp, event.trace_parent.trace_id
+                    );
+
+                    // Standalone, the event is only logged; agent-core runs the
+                    // detector in-process and transcribes the command that follows
+                }
+                None => {
+                    info!("Event channel closed, shutting down");
+                    break;
+                }
+            },
+            _ = &mut shutdown => {
+                info!("Shutdown requested");
                 break;
             }
         }
@@ -69,30 +120,29 @@ async fn main() {
     }
 
     info!("Wake-word detection service stopped");
+    aether_telemetry::shutdown();
+}
+
+/// Detector configuration from the `[wakeword]` section
+fn detector_config(section: &WakewordSection) -> DetectorConfig {
+    let mut config = DetectorConfig::from(section);
+    if config.access_key.is_empty() {
+        warn!("wakeword.access_key not set, using test key");
+        config.access_key = "test_key".to_string();
+    }
+    config
 }
 
-/// Load configuration from environment or config file
-fn load_config() -> Result<DetectorConfig, Box<dyn std::error::Error>> {
-    // In production: load from config file or environment
-    // For now: use defaults with placeholder access key
-
-    let access_key = std::env::var("PORCUPINE_ACCESS_KEY")
-        .unwrap_or_else(|_| {
-            eprintln!("Warning: PORCUPINE_ACCESS_KEY not set, using test key");
-            "test_key".to_string()
-        });
-
-    let model_path = std::env::var("WAKEWORD_MODEL_PATH")
-        .unwrap_or_else(|_| "models/aether.ppn".to_string());
-
-    let sensitivity = std::env::var("WAKEWORD_SENSITIVITY")
-        .unwrap_or_else(|_| "0.5".to_string())
-        .parse::<f32>()?;
-
-    Ok(DetectorConfig {
-        access_key,
-        model_path,
-        sensitivity,
-        ..Default::default()
+/// Whether the default input device can be opened for listening; the
+/// device's error is logged
+fn audio_device() -> Result<(), String> {
+    use cpal::traits::{DeviceTrait, HostTrait};
+
+    let device = cpal::default_host()
+        .default_input_device()
+        .ok_or("no audio input device")?;
+    device.default_input_config().map(|_| ()).map_err(|e| {
+        warn!("{}: {}", device.name().unwrap_or_default(), e);
+        "audio input device unavailable".to_string()
     })
 }
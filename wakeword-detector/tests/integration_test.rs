@@ -212,6 +212,12 @@ async fn test_multiple_wake_words() {
 
     // With mock implementation, may or may not detect multiple
     // In production, should detect close to 3
+
+    // One trace per voice command
+    let mut trace_ids = std::collections::HashSet::new();
+    while let Some(event) = detector.try_recv_event().await {
+        assert!(trace_ids.insert(event.trace_parent.trace_id));
+    }
 }
 
 #[tokio::test]